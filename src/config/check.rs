@@ -0,0 +1,358 @@
+//! Full check of a config without starting the service (`--check-config`): job IDs,
+//! schedules, state files, folders and their permissions, overlapping jobs and portability.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use super::format::ConfigFormat;
+use super::models::{BackupJob, JobKind, Schedule, ServiceConfig, MAX_POLL_INTERVAL_SECS};
+use super::overlap::check_overlaps;
+use super::portability::{check_portability, TargetOs};
+use crate::core::probe_target;
+use crate::state::StateManager;
+use crate::storage::{check_replicas, TargetUrl};
+
+/// Intervals shorter than this start a run before the last one could finish
+const SHORT_INTERVAL_SECS: u64 = 60;
+
+/// How long a target may take to accept the write test
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether an issue keeps the config from working
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Works, but likely not as intended
+    Warning,
+    /// The service rejects the config or the job fails on every run
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A problem found in a config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// Where the setting is, e.g. `jobs[docs].schedule`
+    pub location: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(location: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, location: location.into(), message: message.into() }
+    }
+
+    fn warning(location: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, location: location.into(), message: message.into() }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.location, self.message)
+    }
+}
+
+/// Issues of `config` when run on `target`, errors first. Folders are only looked at on
+/// the platform they are checked for, since a config for another machine names its disks.
+pub async fn check_config(config: &ServiceConfig, target: TargetOs) -> Vec<ConfigIssue> {
+    let mut issues = check_settings(config);
+    for issue in check_portability(config, target) {
+        issues.push(ConfigIssue::warning(issue.location, issue.message));
+    }
+
+    if target == TargetOs::current() {
+        for job in &config.jobs {
+            check_folders(&mut issues, job).await;
+        }
+        if let Some(parent) = config.state_path.parent().filter(|p| !p.as_os_str().is_empty())
+            && !parent.is_dir()
+        {
+            issues.push(ConfigIssue::error("state_path", format!("folder '{}' does not exist", parent.display())));
+        }
+    }
+
+    issues.sort_by_key(|issue| std::cmp::Reverse(issue.severity));
+    issues
+}
+
+/// Job IDs, schedules, state files and overlapping jobs
+fn check_settings(config: &ServiceConfig) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+
+    for (index, job) in config.jobs.iter().enumerate() {
+        if job.id.trim().is_empty() {
+            issues.push(ConfigIssue::error(format!("jobs[{}].id", index), "job has no ID"));
+        } else if let Some(first) = seen.insert(job.id.as_str(), index) {
+            issues.push(ConfigIssue::error(
+                format!("jobs[{}]", job.id),
+                format!("ID is used by the jobs at positions {} and {}; each job needs its own", first, index),
+            ));
+        }
+        check_schedule(&mut issues, job);
+    }
+    check_limits(&mut issues, config);
+
+    if let Err(e) = StateManager::job_files(config) {
+        issues.push(ConfigIssue::error("jobs[].state_path", format!("{:#}", e)));
+    }
+
+    for overlap in check_overlaps(&config.jobs) {
+        issues.push(ConfigIssue {
+            severity: if overlap.rejected { Severity::Error } else { Severity::Warning },
+            location: overlap.location,
+            message: overlap.message,
+        });
+    }
+
+    for issue in check_replicas(config) {
+        issues.push(ConfigIssue::error(issue.location, issue.message));
+    }
+
+    issues
+}
+
+fn check_schedule(issues: &mut Vec<ConfigIssue>, job: &BackupJob) {
+    let at = format!("jobs[{}].schedule", job.id);
+    let time = |hour: u32, minute: u32| {
+        (hour > 23 || minute > 59).then(|| format!("{:02}:{:02} is not a time of day (hour 0-23, minute 0-59)", hour, minute))
+    };
+
+    let problem = match &job.schedule {
+        Schedule::Interval { seconds: 0 } => Some("interval of 0 seconds".to_string()),
+        Schedule::Interval { seconds } if *seconds < SHORT_INTERVAL_SECS => {
+            issues.push(ConfigIssue::warning(&at, format!("runs every {} seconds; a run rarely finishes that fast", seconds)));
+            None
+        }
+        Schedule::Interval { .. } | Schedule::Cron { .. } => None,
+        Schedule::Daily { hour, minute } => time(*hour, *minute),
+        Schedule::Weekly { day, .. } if !(1..=7).contains(day) => {
+            Some(format!("day {} is not a weekday (1 = Monday to 7 = Sunday)", day))
+        }
+        Schedule::Weekly { hour, minute, .. } => time(*hour, *minute),
+    };
+
+    if let Some(problem) = problem {
+        issues.push(ConfigIssue::error(at, problem));
+    }
+}
+
+/// Settings whose value is taken to mean something else than it says
+fn check_limits(issues: &mut Vec<ConfigIssue>, config: &ServiceConfig) {
+    match config.poll_interval_seconds {
+        0 => issues.push(ConfigIssue::warning("poll_interval_seconds", "0 counts as 1 second")),
+        seconds if seconds > MAX_POLL_INTERVAL_SECS => issues.push(ConfigIssue::warning(
+            "poll_interval_seconds",
+            format!("{} counts as {} seconds, the longest interval", seconds, MAX_POLL_INTERVAL_SECS),
+        )),
+        _ => {}
+    }
+
+    const KEEPS_ONE: &str = "0 keeps 1 backup; the newest backup is never removed";
+    const NO_LIMIT: &str = "0 means no limit; leave the setting out instead";
+
+    if config.retention_count == 0 {
+        issues.push(ConfigIssue::warning("retention_count", KEEPS_ONE));
+    }
+    for (index, window) in config.throttle.iter().enumerate() {
+        if window.max_bytes_per_sec == Some(0) {
+            issues.push(ConfigIssue::warning(format!("throttle[{}].max_bytes_per_sec", index), NO_LIMIT));
+        }
+    }
+    for job in &config.jobs {
+        // Jobs with replicas are refused with 0 by `check_replicas`
+        if job.retention_count == Some(0) && job.replicas.is_empty() {
+            issues.push(ConfigIssue::warning(format!("jobs[{}].retention_count", job.id), KEEPS_ONE));
+        }
+        if job.max_bytes_per_sec == Some(0) {
+            issues.push(ConfigIssue::warning(format!("jobs[{}].max_bytes_per_sec", job.id), NO_LIMIT));
+        }
+    }
+}
+
+/// Settings in the config file at `path` that KeepHive does not know, such as misspelled
+/// names; they are ignored when the config is loaded. `config` is the config read from `text`.
+pub fn check_unknown_keys(path: &Path, text: &str, config: &ServiceConfig) -> Vec<ConfigIssue> {
+    let (Ok(raw), Ok(known)) = (ConfigFormat::from_path(path).parse::<serde_json::Value>(text), serde_json::to_value(config)) else {
+        return Vec::new();
+    };
+
+    let mut unknown = Vec::new();
+    unknown_keys(&raw, &known, "", &mut unknown);
+    unknown.into_iter()
+        .map(|location| ConfigIssue::warning(location, "unknown setting, ignored"))
+        .collect()
+}
+
+/// Keys of `raw` missing from `known`, the same document written back from the parsed
+/// config, which holds every setting KeepHive reads. Jobs are named by their ID.
+fn unknown_keys(raw: &serde_json::Value, known: &serde_json::Value, at: &str, unknown: &mut Vec<String>) {
+    use serde_json::Value;
+
+    match (raw, known) {
+        (Value::Object(raw), Value::Object(known)) => {
+            for (key, value) in raw {
+                let location = if at.is_empty() { key.clone() } else { format!("{}.{}", at, key) };
+                match known.get(key) {
+                    Some(known) => unknown_keys(value, known, &location, unknown),
+                    None => unknown.push(location),
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(known)) => {
+            for (index, (raw, known)) in raw.iter().zip(known).enumerate() {
+                let label = raw.get("id").and_then(Value::as_str).map_or_else(|| index.to_string(), str::to_string);
+                unknown_keys(raw, known, &format!("{}[{}]", at, label), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Source and target of a job on this machine
+async fn check_folders(issues: &mut Vec<ConfigIssue>, job: &BackupJob) {
+    let at = |field: &str| format!("jobs[{}].{}", job.id, field);
+
+    if job.kind == JobKind::Files {
+        if job.source.as_os_str().is_empty() {
+            issues.push(ConfigIssue::error(at("source"), "no source folder"));
+        } else if TargetUrl::from_path(&job.source).is_none()
+            && let Some(problem) = source_problem(&job.source).await
+        {
+            issues.push(ConfigIssue::error(at("source"), problem));
+        }
+    }
+
+    if let Some(url) = TargetUrl::from_path(&job.target) {
+        issues.push(ConfigIssue::error(
+            at("target"),
+            format!("cannot be a {}:// URL; add it to the job's replicas instead", url.scheme),
+        ));
+    } else if job.target.is_dir() {
+        if let Err(e) = probe_target(&job.target, PROBE_TIMEOUT).await {
+            issues.push(ConfigIssue::error(at("target"), format!("{:#}", e)));
+        }
+    } else if job.target.exists() {
+        issues.push(ConfigIssue::error(at("target"), format!("'{}' is not a folder", job.target.display())));
+    } else {
+        issues.push(ConfigIssue::warning(
+            at("target"),
+            format!("'{}' does not exist yet; the first run creates it", job.target.display()),
+        ));
+    }
+}
+
+/// Why a source folder cannot be backed up, if it cannot
+async fn source_problem(source: &Path) -> Option<String> {
+    if !source.exists() {
+        return Some(format!("'{}' does not exist", source.display()));
+    }
+    if !source.is_dir() {
+        return Some(format!("'{}' is not a folder", source.display()));
+    }
+    tokio::fs::read_dir(source).await.err()
+        .map(|e| format!("cannot read '{}': {}", source.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+
+    fn config(jobs: serde_json::Value) -> ServiceConfig {
+        serde_json::from_value(serde_json::json!({ "jobs": jobs })).unwrap()
+    }
+
+    fn issues(issues: &[ConfigIssue]) -> Vec<(Severity, &str)> {
+        issues.iter().map(|issue| (issue.severity, issue.location.as_str())).collect()
+    }
+
+    #[test]
+    fn test_duplicate_ids_and_bad_schedules_are_errors() {
+        let config = config(serde_json::json!([
+            { "id": "docs", "source": "/srv/docs", "target": "/mnt/a", "schedule": { "type": "daily", "hour": 24, "minute": 0 } },
+            { "id": "docs", "source": "/srv/mail", "target": "/mnt/b", "schedule": { "type": "weekly", "day": 0, "hour": 2, "minute": 0 } },
+            { "id": "fast", "source": "/srv/fast", "target": "/mnt/c", "schedule": { "type": "interval", "seconds": 10 } },
+            { "id": "never", "source": "/srv/never", "target": "/mnt/d", "schedule": { "type": "interval", "seconds": 0 } }
+        ]));
+
+        assert_eq!(issues(&check_settings(&config)), [
+            (Severity::Error, "jobs[docs].schedule"),
+            (Severity::Error, "jobs[docs]"),
+            (Severity::Error, "jobs[docs].schedule"),
+            (Severity::Warning, "jobs[fast].schedule"),
+            (Severity::Error, "jobs[never].schedule"),
+        ]);
+    }
+
+    #[test]
+    fn test_settings_that_mean_something_else_are_warned_about() {
+        let mut config = config(serde_json::json!([
+            { "id": "docs", "source": "/srv/docs", "target": "/mnt/a", "retention_count": 0, "max_bytes_per_sec": 0,
+              "schedule": { "type": "interval", "seconds": 3600 } }
+        ]));
+        config.poll_interval_seconds = 3600;
+
+        assert_eq!(issues(&check_settings(&config)), [
+            (Severity::Warning, "poll_interval_seconds"),
+            (Severity::Warning, "jobs[docs].retention_count"),
+            (Severity::Warning, "jobs[docs].max_bytes_per_sec"),
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_warnings() {
+        let text = r#"{
+            "retension_count": 3,
+            "storage": { "webdav": { "usrname": "alice" } },
+            "jobs": [
+                { "id": "docs", "source": "/srv/docs", "target": "/mnt/a", "exclud": ["*.tmp"],
+                  "schedule": { "type": "daily", "hour": 2, "minute": 0, "second": 0 } }
+            ]
+        }"#;
+        let config = parse_config(Path::new("c.json"), text).unwrap();
+
+        let found = check_unknown_keys(Path::new("c.json"), text, &config);
+        let mut locations: Vec<&str> = found.iter().map(|issue| issue.location.as_str()).collect();
+        locations.sort();
+        assert_eq!(locations, ["jobs[docs].exclud", "jobs[docs].schedule.second", "retension_count", "storage.webdav.usrname"]);
+        assert!(found.iter().all(|issue| issue.severity == Severity::Warning));
+    }
+
+    #[tokio::test]
+    async fn test_folders_are_checked_on_this_machine() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let target = dir.path().join("dst");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(dir.path().join("file"), b"x").unwrap();
+
+        let config = config(serde_json::json!([
+            { "id": "ok", "source": source, "target": dir.path().join("new"), "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "missing", "source": dir.path().join("missing"), "target": dir.path().join("file"), "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "inside", "source": source, "target": source.join("backups"), "schedule": { "type": "interval", "seconds": 3600 } }
+        ]));
+        std::fs::create_dir_all(&target).unwrap();
+
+        let found = check_config(&config, TargetOs::current()).await;
+        assert_eq!(issues(&found), [
+            (Severity::Error, "jobs[inside].target"),
+            (Severity::Error, "jobs[missing].source"),
+            (Severity::Error, "jobs[missing].target"),
+            (Severity::Warning, "jobs[ok].target"),
+            (Severity::Warning, "jobs[inside].target"),
+        ]);
+        assert!(!dir.path().join("new").exists(), "Checking creates no folders");
+    }
+}
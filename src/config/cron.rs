@@ -0,0 +1,267 @@
+//! Cron expressions for `Schedule::Cron`: five fields (minute, hour, day of month,
+//! month, day of week) with `*`, lists, ranges and steps, as in crontab. Expressions
+//! are parsed when the config is loaded, so a typo fails the load instead of a run.
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Days searched for the next match; 29 February can be eight years away
+const SEARCH_DAYS: i64 = 366 * 9;
+
+/// Parsed cron expression, stored in the config as its source text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronExpression {
+    source: String,
+    /// Bit per minute 0-59
+    minutes: u64,
+    /// Bit per hour 0-23
+    hours: u64,
+    /// Bit per day of month 1-31
+    days: u64,
+    /// Bit per month 1-12
+    months: u64,
+    /// Bit per weekday, 0 = Sunday
+    weekdays: u64,
+    /// Day of month field starts with `*`
+    any_day: bool,
+    /// Day of week field starts with `*`
+    any_weekday: bool,
+}
+
+impl CronExpression {
+    /// First matching minute strictly after `after`, as local wall-clock time
+    pub fn next_after(&self, after: NaiveDateTime) -> NaiveDateTime {
+        let today = after.date();
+
+        for offset in 0..SEARCH_DAYS {
+            let date = today + Duration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+
+            for hour in bits(self.hours, 0..24) {
+                for minute in bits(self.minutes, 0..60) {
+                    let candidate = date.and_hms_opt(hour, minute, 0).unwrap();
+                    if candidate > after {
+                        return candidate;
+                    }
+                }
+            }
+        }
+
+        // `parse` rejects expressions that never match
+        unreachable!("cron expression '{}' has no match", self.source)
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !has(self.months, date.month()) {
+            return false;
+        }
+
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+
+        // As in crontab: when both day fields are restricted, either one matching is enough
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    fn parse(source: &str) -> Result<Self> {
+        let expanded = match source.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("expected 5 fields (minute hour day-of-month month day-of-week), found {}", fields.len());
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAY_NAMES, 0).context("day of week")?;
+        // 7 is Sunday as well as 0
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        let expression = CronExpression {
+            source: source.trim().to_string(),
+            minutes: parse_field(minute, 0, 59, &[], 0).context("minute")?,
+            hours: parse_field(hour, 0, 23, &[], 0).context("hour")?,
+            days: parse_field(day, 1, 31, &[], 0).context("day of month")?,
+            months: parse_field(month, 1, 12, &MONTH_NAMES, 1).context("month")?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        };
+
+        if !expression.any_day && expression.any_weekday && !expression.has_possible_day() {
+            bail!("the day of month never occurs in the selected months");
+        }
+
+        Ok(expression)
+    }
+
+    /// Whether a selected day of month exists in a selected month (leap years included)
+    fn has_possible_day(&self) -> bool {
+        const MONTH_DAYS: [u32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+        bits(self.months, 1..13).any(|month| bits(self.days, 1..MONTH_DAYS[month as usize - 1] + 1).next().is_some())
+    }
+}
+
+/// Parse one field into a bit set; `names` map to values starting at `first_name`
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], first_name: u32) -> Result<u64> {
+    let value = |text: &str| -> Result<u32> {
+        let value = match names.iter().position(|name| name.eq_ignore_ascii_case(text)) {
+            Some(index) => index as u32 + first_name,
+            None => text.parse().with_context(|| format!("'{}' is not a number", text))?,
+        };
+        if value < min || value > max {
+            bail!("{} is outside {}-{}", value, min, max);
+        }
+        Ok(value)
+    };
+
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().with_context(|| format!("invalid step '{}'", step))?;
+                if step == 0 {
+                    bail!("step must be at least 1");
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/15` runs from 5 to the end of the range
+            None if step.is_some() => (value(range)?, max),
+            None => {
+                let single = value(range)?;
+                (single, single)
+            }
+        };
+        if start > end {
+            bail!("range {}-{} is backwards", start, end);
+        }
+
+        for v in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << v;
+        }
+    }
+
+    Ok(set)
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn bits(set: u64, range: std::ops::Range<u32>) -> impl Iterator<Item = u32> {
+    range.filter(move |&v| has(set, v))
+}
+
+impl FromStr for CronExpression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s).with_context(|| format!("Invalid cron expression '{}'", s))
+    }
+}
+
+impl TryFrom<String> for CronExpression {
+    type Error = String;
+
+    /// Keeps the whole error chain, since serde only shows the outermost message
+    fn try_from(value: String) -> std::result::Result<Self, String> {
+        value.parse().map_err(|e: anyhow::Error| format!("{:#}", e))
+    }
+}
+
+impl From<CronExpression> for String {
+    fn from(expression: CronExpression) -> Self {
+        expression.source
+    }
+}
+
+impl fmt::Display for CronExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap()
+    }
+
+    fn cron(expression: &str) -> CronExpression {
+        expression.parse().unwrap()
+    }
+
+    #[test]
+    fn test_weekdays_at_fixed_time() {
+        // 2025-01-10 is a Friday
+        let expression = cron("30 2 * * 1-5");
+
+        assert_eq!(expression.next_after(at(2025, 1, 9, 12, 0)), at(2025, 1, 10, 2, 30));
+        assert_eq!(expression.next_after(at(2025, 1, 10, 2, 30)), at(2025, 1, 13, 2, 30));
+        assert_eq!(cron("0 3 * * SUN").next_after(at(2025, 1, 10, 0, 0)), at(2025, 1, 12, 3, 0));
+        assert_eq!(cron("0 3 * * 7").weekdays, cron("0 3 * * 0").weekdays);
+    }
+
+    #[test]
+    fn test_month_days_lists_and_steps() {
+        assert_eq!(cron("0 1 1 * *").next_after(at(2025, 1, 31, 23, 0)), at(2025, 2, 1, 1, 0));
+        assert_eq!(cron("@monthly").next_after(at(2025, 12, 15, 0, 0)), at(2026, 1, 1, 0, 0));
+        assert_eq!(cron("*/15 9-17 * * *").next_after(at(2025, 1, 6, 17, 45)), at(2025, 1, 7, 9, 0));
+        assert_eq!(cron("5,50 * * * *").next_after(at(2025, 1, 6, 10, 5)), at(2025, 1, 6, 10, 50));
+        assert_eq!(cron("0 0 29 2 *").next_after(at(2025, 3, 1, 0, 0)), at(2028, 2, 29, 0, 0));
+    }
+
+    #[test]
+    fn test_restricted_day_fields_match_either() {
+        // 2025-01-06 is a Monday: the 15th or any Monday
+        let expression = cron("0 4 15 * mon");
+
+        assert_eq!(expression.next_after(at(2025, 1, 6, 5, 0)), at(2025, 1, 13, 4, 0));
+        assert_eq!(expression.next_after(at(2025, 1, 13, 5, 0)), at(2025, 1, 15, 4, 0));
+    }
+
+    #[test]
+    fn test_rejects_invalid_expressions() {
+        for invalid in ["", "* * * *", "60 * * * *", "* 24 * * *", "0 0 0 * *", "0 0 * 13 *", "*/0 * * * *", "5-1 * * * *", "0 0 31 2 *", "0 0 * * funday"] {
+            assert!(invalid.parse::<CronExpression>().is_err(), "accepted '{}'", invalid);
+        }
+    }
+
+    #[test]
+    fn test_serializes_as_source_text() {
+        let expression = cron("30 2 * * 1-5");
+
+        assert_eq!(serde_json::to_string(&expression).unwrap(), "\"30 2 * * 1-5\"");
+        assert_eq!(serde_json::from_str::<CronExpression>("\"30 2 * * 1-5\"").unwrap(), expression);
+        assert!(serde_json::from_str::<CronExpression>("\"30 2 * *\"").is_err());
+    }
+}
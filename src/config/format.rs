@@ -0,0 +1,149 @@
+//! Config file formats. JSON is the default; files ending in `.toml`, `.yaml` or `.yml`
+//! are read as TOML or YAML, which need no escaped backslashes in Windows paths and are
+//! what configuration management tools usually generate.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+use super::models::ServiceConfig;
+use super::recipes::expand_recipes;
+
+/// Format of a config file, chosen by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Format of the file at `path`; anything but `.toml`, `.yaml` and `.yml` is JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).as_deref() {
+            Some("toml") => Self::Toml,
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+
+    /// Parse a document of this format
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T> {
+        match self {
+            Self::Json => serde_json::from_str(text).map_err(anyhow::Error::from),
+            Self::Toml => toml::from_str(text).map_err(anyhow::Error::from),
+            Self::Yaml => serde_norway::from_str(text).map_err(anyhow::Error::from),
+        }
+        .with_context(|| format!("Invalid {}", self))
+    }
+
+    /// Write `value` as a document of this format
+    pub fn to_string<T: Serialize>(self, value: &T) -> Result<String> {
+        match self {
+            Self::Json => serde_json::to_string_pretty(value).map_err(anyhow::Error::from),
+            Self::Toml => toml::to_string_pretty(value).map_err(anyhow::Error::from),
+            Self::Yaml => serde_norway::to_string(value).map_err(anyhow::Error::from),
+        }
+        .with_context(|| format!("Failed to write {}", self))
+    }
+}
+
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "JSON",
+            Self::Toml => "TOML",
+            Self::Yaml => "YAML",
+        })
+    }
+}
+
+/// Parse the config file at `path` from its text, in the format its extension names,
+/// and expand application recipes
+pub fn parse_config(path: &Path, text: &str) -> Result<ServiceConfig> {
+    let mut config: ServiceConfig = ConfigFormat::from_path(path).parse(text)
+        .context("Failed to parse config file")?;
+    expand_recipes(&mut config)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Schedule;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_format_follows_extension() {
+        assert_eq!(ConfigFormat::from_path(Path::new("keephive.TOML")), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path(Path::new("keephive.yml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("keephive.yaml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("keephive_config.json")), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path(Path::new("keephive")), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn test_toml_and_yaml_configs_match_json() {
+        let json = parse_config(Path::new("c.json"), r#"{
+            "retention_count": 5,
+            "jobs": [{
+                "id": "docs",
+                "source": "C:\\Users\\Me\\Documents",
+                "target": "D:\\Backups",
+                "exclude": ["*.tmp"],
+                "schedule": { "type": "daily", "hour": 2, "minute": 30 }
+            }]
+        }"#).unwrap();
+
+        let toml = parse_config(Path::new("c.toml"), r#"
+            retention_count = 5
+
+            [[jobs]]
+            id = "docs"
+            source = 'C:\Users\Me\Documents'
+            target = 'D:\Backups'
+            exclude = ["*.tmp"]
+            schedule = { type = "daily", hour = 2, minute = 30 }
+        "#).unwrap();
+
+        let yaml = parse_config(Path::new("c.yaml"), r#"
+retention_count: 5
+jobs:
+  - id: docs
+    source: C:\Users\Me\Documents
+    target: D:\Backups
+    exclude: ["*.tmp"]
+    schedule:
+      type: daily
+      hour: 2
+      minute: 30
+"#).unwrap();
+
+        assert_eq!(json.jobs[0].source, PathBuf::from("C:\\Users\\Me\\Documents"));
+        assert_eq!(json.jobs[0].schedule, Schedule::Daily { hour: 2, minute: 30 });
+        assert_eq!(toml.jobs, json.jobs);
+        assert_eq!(yaml.jobs, json.jobs);
+        assert_eq!((toml.retention_count, yaml.retention_count), (5, 5));
+    }
+
+    #[test]
+    fn test_config_round_trips_through_each_format() {
+        let config = parse_config(Path::new("c.json"), r#"{
+            "jobs": [{ "id": "docs", "source": "/srv/docs", "target": "/mnt/backups", "schedule": { "type": "interval", "seconds": 3600 } }]
+        }"#).unwrap();
+
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let text = format.to_string(&config).unwrap();
+            let parsed: ServiceConfig = format.parse(&text).unwrap();
+            assert_eq!(parsed.jobs, config.jobs, "{} round trip", format);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_names_the_format() {
+        let error = parse_config(Path::new("c.yaml"), "jobs: [").unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid YAML"), "{:#}", error);
+    }
+}
@@ -1,3 +1,17 @@
+pub mod check;
+pub mod cron;
+pub mod format;
 pub mod models;
+pub mod overlap;
+pub mod policy;
+pub mod portability;
+pub mod recipes;
+pub mod wizard;
 
-pub use models::{BackupConfig, BackupJob, LogRotation, Schedule, ServiceConfig, DEFAULT_RETENTION_COUNT};
+pub use models::{AccessTier, AppRecipe, ArchiveConfig, ArchiveFormat, AzureConfig, BackupConfig, BackupJob, BackupMode, ConfirmationTimeout, DiskFullConfig, DockerVolumeConfig, DumpConfig, Durability, ExcludeProfile, FirstRunConfig, GoogleDriveConfig, HttpApiConfig, JobHooks, JobKind, JobRetryConfig, LargeRunConfig, LogRotation, NameConflicts, NameNormalization, NetworkTargetConfig, NotificationConfig, NotifyTriggers, PathRedaction, PullConfig, ReplicaServerConfig, RegistryHive, ReplicationConfig, RsyncConfig, Schedule, ServiceConfig, ShareCredentials, SmtpSecurity, StateSaveMode, StorageConfig, SystemStateConfig, TargetProbeConfig, ThrottleWindow, VerifyConfig, WebDavConfig, WhenBusy, WslConfig, DEFAULT_RETENTION_COUNT, MAX_POLL_INTERVAL_SECS};
+pub use check::{check_config, check_unknown_keys, ConfigIssue, Severity};
+pub use cron::CronExpression;
+pub use format::{parse_config, ConfigFormat};
+pub use overlap::{check_overlaps, validate_job_overlaps, JobOverlap};
+pub use portability::{check_portability, PortabilityIssue, TargetOs};
+pub use recipes::expand_recipes;
@@ -1,12 +1,18 @@
 use chrono::Duration;
-use chrono::{Datelike, Local, Timelike};
+use chrono::{DateTime, Datelike, Local, LocalResult, NaiveDateTime, Timelike, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::CronExpression;
 
 /// Default number of backups to retain per job
 pub const DEFAULT_RETENTION_COUNT: usize = 5;
+/// Longest wait between checks for due jobs, whatever `poll_interval_seconds` says
+pub const MAX_POLL_INTERVAL_SECS: u64 = 300;
 const DEFAULT_LOG_LEVEL: &str = "info";
 const DEFAULT_STATE_FILE: &str = ".keephive_state.json";
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
 
 #[inline]
 fn default_retention_count() -> usize {
@@ -23,6 +29,16 @@ fn default_state_path() -> PathBuf {
     PathBuf::from(DEFAULT_STATE_FILE)
 }
 
+#[inline]
+fn default_poll_interval() -> u64 {
+    DEFAULT_POLL_INTERVAL_SECS
+}
+
+#[inline]
+fn default_manifest_checksums() -> bool {
+    true
+}
+
 /// Main service configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceConfig {
@@ -48,6 +64,107 @@ pub struct ServiceConfig {
     /// Log file rotation strategy
     #[serde(default)]
     pub log_rotation: LogRotation,
+
+    /// Hide the folders of paths in log output, for logs collected centrally
+    #[serde(default)]
+    pub log_path_redaction: PathRedaction,
+
+    /// Durability level for backup and state writes
+    #[serde(default)]
+    pub durability: Durability,
+
+    /// State file write strategy
+    #[serde(default)]
+    pub state_save: StateSaveMode,
+
+    /// Keep each job's state in a file of its own, in a folder next to `state_path`
+    #[serde(default)]
+    pub per_job_state: bool,
+
+    /// Seconds between checks for due jobs (1 to 300); a finished job wakes the service right
+    /// away. Batched state updates are flushed at these checks, so they may be written that much later.
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_seconds: u64,
+
+    /// Maximum number of jobs running at once (None = unlimited); ready jobs beyond the limit are queued
+    #[serde(default)]
+    pub max_concurrent_jobs: Option<usize>,
+
+    /// Run jobs whose source or target share a physical disk one after another
+    #[serde(default)]
+    pub disk_aware_scheduling: bool,
+
+    /// Resident memory budget in MiB; while the daemon is above it, no further jobs start
+    #[serde(default)]
+    pub memory_limit_mb: Option<u64>,
+
+    /// Find the fastest copy buffer size and parallel file count per source and target
+    #[serde(default)]
+    pub autotune: bool,
+
+    /// Control channel endpoint (named pipe on Windows, Unix socket elsewhere)
+    #[serde(default)]
+    pub control_endpoint: Option<String>,
+
+    /// Where one-off folder backups requested over the control channel are stored
+    #[serde(default)]
+    pub adhoc_target: Option<PathBuf>,
+
+    /// Retry policy for copying completed backups to replica targets
+    #[serde(default)]
+    pub replication: ReplicationConfig,
+
+    /// Time windows with their own bandwidth and concurrency limits (first match wins)
+    #[serde(default)]
+    pub throttle: Vec<ThrottleWindow>,
+
+    /// Warn about, and optionally hold, runs expected to copy more than a threshold
+    #[serde(default)]
+    pub large_run: Option<LargeRunConfig>,
+
+    /// Make the first run of a new job an estimate that is reviewed before anything is copied
+    #[serde(default)]
+    pub first_run: Option<FirstRunConfig>,
+
+    /// Emergency cleanup when a target fills up during a backup
+    #[serde(default)]
+    pub on_disk_full: DiskFullConfig,
+
+    /// Check between runs that the targets can still be reached
+    #[serde(default)]
+    pub target_probe: Option<TargetProbeConfig>,
+
+    /// Re-read copied files and compare them with the source
+    #[serde(default)]
+    pub verify: VerifyConfig,
+
+    /// Record a CRC-32 of every copied file in the manifest, checked on restore
+    #[serde(default = "default_manifest_checksums")]
+    pub manifest_checksums: bool,
+
+    /// Settings for the built-in remote storage backends used by `scheme://` replicas
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Let other KeepHive instances pull completed backups from this one
+    #[serde(default)]
+    pub replica_server: Option<ReplicaServerConfig>,
+
+    /// Credentials for jobs whose source is a `keephive://` replica server
+    #[serde(default)]
+    pub pull: PullConfig,
+
+    /// Waiting for and reconnecting `\\server\share` targets that are offline when a run starts
+    #[serde(default)]
+    pub network_targets: NetworkTargetConfig,
+
+    /// HTTP API for dashboards and remote monitoring (disabled when unset)
+    #[serde(default)]
+    pub http_api: Option<HttpApiConfig>,
+
+    /// Email sent when runs finish (disabled when unset)
+    #[serde(default)]
+    pub notifications: Option<NotificationConfig>,
 }
 
 
@@ -69,13 +186,643 @@ impl Default for LogRotation {
     }
 }
 
+/// How paths are written to the logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathRedaction {
+    /// Full paths
+    #[default]
+    Off,
+    /// Each folder replaced by a short hash, so the same folder can still be recognized
+    Hash,
+    /// Folders left out, keeping the root and the file name
+    Truncate,
+}
+
+/// Durability level for on-disk writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Durability {
+    /// Files are synced, directory entries are left to the OS
+    #[default]
+    Normal,
+    /// Parent directories are also synced after backup completion and state renames,
+    /// so created/renamed entries survive sudden power loss
+    Strict,
+}
+
+/// State file write strategy
+///
+/// In batched mode, status transitions (Idle/Running/Failed) are still written
+/// immediately; only intermediate updates such as next_run recalculations are
+/// deferred and may be lost on a crash (they are recomputed on startup).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StateSaveMode {
+    /// Write and fsync the state file on every update
+    #[default]
+    Immediate,
+    /// Defer minor updates and flush them periodically
+    Batched {
+        /// Maximum time a deferred update stays in memory
+        flush_interval_seconds: u64,
+    },
+}
+
+/// Retries for replica copies; each replica target retries independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    /// Attempts per replica before giving up until the next backup
+    #[serde(default = "default_replication_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled after each failure (capped at one hour)
+    #[serde(default = "default_replication_retry_delay")]
+    pub retry_delay_seconds: u64,
+
+    /// Files of each remote upload downloaded again and compared with the original,
+    /// for backends that do not checksum uploads themselves (0 = off)
+    #[serde(default = "default_replication_verify_sample")]
+    pub verify_sample: usize,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_replication_attempts(),
+            retry_delay_seconds: default_replication_retry_delay(),
+            verify_sample: default_replication_verify_sample(),
+        }
+    }
+}
+
+impl ReplicationConfig {
+    /// Delay before retry number `attempt` (1-based)
+    pub fn retry_delay(&self, attempt: u32) -> std::time::Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        let seconds = self.retry_delay_seconds.saturating_mul(factor).min(3600);
+        std::time::Duration::from_secs(seconds)
+    }
+}
+
+fn default_replication_attempts() -> u32 {
+    5
+}
+
+fn default_replication_retry_delay() -> u64 {
+    60
+}
+
+fn default_replication_verify_sample() -> usize {
+    3
+}
+
+/// Replica server that other instances pull completed backups from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicaServerConfig {
+    /// Address to listen on, e.g. `127.0.0.1:7443`
+    pub listen: String,
+
+    /// Listen on an address other machines can reach. Transfers are plain TCP, so this
+    /// is only for a trusted LAN or a VPN; without it only loopback addresses are accepted.
+    #[serde(default)]
+    pub allow_lan: bool,
+
+    /// Environment variable holding the token shared with pulling instances
+    #[serde(default = "default_replica_token_env")]
+    pub token_env: String,
+
+    /// Jobs whose backups may be pulled (empty = all jobs)
+    #[serde(default)]
+    pub jobs: Vec<String>,
+}
+
+/// How this instance authenticates to replica servers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PullConfig {
+    /// Environment variable holding the token shared with the replica server
+    #[serde(default = "default_replica_token_env")]
+    pub token_env: String,
+}
+
+impl Default for PullConfig {
+    fn default() -> Self {
+        Self {
+            token_env: default_replica_token_env(),
+        }
+    }
+}
+
+fn default_replica_token_env() -> String {
+    "KEEPHIVE_REPLICA_TOKEN".to_string()
+}
+
+/// How runs treat a network share target (`\\server\share\...`) that cannot be reached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkTargetConfig {
+    /// How long to keep retrying an unreachable share before the run fails (0 = fail at once)
+    #[serde(default = "default_network_wait")]
+    pub wait_seconds: u64,
+
+    /// Pause between attempts to reach the share
+    #[serde(default = "default_network_retry_interval")]
+    pub retry_interval_seconds: u64,
+
+    /// Accounts used to reconnect shares; shares without one reconnect as the service account
+    #[serde(default)]
+    pub credentials: Vec<ShareCredentials>,
+}
+
+impl Default for NetworkTargetConfig {
+    fn default() -> Self {
+        Self {
+            wait_seconds: default_network_wait(),
+            retry_interval_seconds: default_network_retry_interval(),
+            credentials: Vec::new(),
+        }
+    }
+}
+
+impl NetworkTargetConfig {
+    /// Credentials configured for `share` (`\\server\share`), compared case-insensitively
+    pub fn credentials_for(&self, share: &Path) -> Option<&ShareCredentials> {
+        let share = share.to_string_lossy();
+        self.credentials.iter().find(|c| c.share.trim_end_matches('\\').eq_ignore_ascii_case(share.trim_end_matches('\\')))
+    }
+}
+
+fn default_network_wait() -> u64 {
+    120
+}
+
+fn default_network_retry_interval() -> u64 {
+    15
+}
+
+/// Account for one network share
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareCredentials {
+    /// Share the account is for, e.g. `\\nas\backups`
+    pub share: String,
+
+    /// User name, e.g. `NAS\backup` or `backup@example.com`
+    pub username: String,
+
+    /// Environment variable holding the password
+    #[serde(default)]
+    pub password_env: Option<String>,
+}
+
+/// Embedded HTTP server exposing job status and control
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpApiConfig {
+    /// Address to listen on, e.g. `127.0.0.1:7480`
+    pub bind: String,
+
+    /// Environment variable holding the bearer token clients must send; required
+    /// unless `bind` is a loopback address
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+/// Email reports of finished runs, sent through an SMTP server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// SMTP server host name
+    pub smtp_server: String,
+
+    /// SMTP port (587 for STARTTLS, 465 for TLS, 25 without encryption)
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+
+    /// How the connection to the server is encrypted
+    #[serde(default)]
+    pub security: SmtpSecurity,
+
+    /// Account to log in with (no login if unset)
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Environment variable holding the account's password
+    #[serde(default)]
+    pub password_env: Option<String>,
+
+    /// Sender address
+    pub from: String,
+
+    /// Recipient addresses
+    pub to: Vec<String>,
+
+    /// Runs that send an email, unless a job sets its own `notify`
+    #[serde(flatten)]
+    pub triggers: NotifyTriggers,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// Encryption of the SMTP connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade the connection with STARTTLS, failing if the server does not offer it
+    #[default]
+    Starttls,
+    /// TLS from the start (SMTPS)
+    Tls,
+    /// Plain text, for relays on the local network
+    None,
+}
+
+/// Which finished runs send an email
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifyTriggers {
+    /// Runs that failed
+    #[serde(default = "default_true")]
+    pub on_failure: bool,
+
+    /// Runs that completed with every file copied
+    #[serde(default)]
+    pub on_success: bool,
+
+    /// Runs that completed but skipped files they could not copy
+    #[serde(default = "default_true")]
+    pub on_partial: bool,
+}
+
+impl Default for NotifyTriggers {
+    fn default() -> Self {
+        Self {
+            on_failure: true,
+            on_success: false,
+            on_partial: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Settings for the built-in remote storage backends
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// WebDAV servers (`webdav://` and `webdav+http://` replicas), e.g. Nextcloud or ownCloud
+    #[serde(default)]
+    pub webdav: WebDavConfig,
+
+    /// Azure Blob Storage (`azblob://` replicas)
+    #[serde(default)]
+    pub azure: AzureConfig,
+
+    /// Google Drive (`gdrive://` replicas)
+    #[serde(default)]
+    pub gdrive: GoogleDriveConfig,
+
+    /// rsync over SSH (`ssh://` replicas) or to an rsync daemon (`rsync://` replicas)
+    #[serde(default)]
+    pub rsync: RsyncConfig,
+}
+
+impl StorageConfig {
+    /// Monthly transfer cap in bytes for the backend recording usage as `backend`,
+    /// with all backend names counted against the same cap
+    pub fn monthly_transfer_cap(&self, backend: &str) -> Option<(u64, &'static [&'static str])> {
+        let (cap, backends): (_, &'static [&'static str]) = match backend {
+            "webdav" => (self.webdav.max_monthly_transfer_gb, &["webdav"]),
+            "azblob" => (self.azure.max_monthly_transfer_gb, &["azblob"]),
+            "gdrive" => (self.gdrive.max_monthly_transfer_gb, &["gdrive"]),
+            "ssh" | "rsync" => (self.rsync.max_monthly_transfer_gb, &["ssh", "rsync"]),
+            _ => return None,
+        };
+
+        cap.map(|gb| (gb.saturating_mul(1024 * 1024 * 1024), backends))
+    }
+}
+
+/// WebDAV account used for `webdav://` replicas
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    /// User name; a user given in the replica URL (`webdav://user@host/...`) takes precedence
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Environment variable holding the password or app token
+    #[serde(default)]
+    pub password_env: Option<String>,
+
+    /// Files larger than this are uploaded in chunks on Nextcloud (0 = never chunk)
+    #[serde(default = "default_webdav_chunk_size_mb")]
+    pub chunk_size_mb: u64,
+
+    /// Pause replication through this backend once this much was sent and received in a calendar month
+    #[serde(default)]
+    pub max_monthly_transfer_gb: Option<u64>,
+
+    /// Send the user name and password to `webdav+http://` replicas, where anyone on the
+    /// network can read them (refused otherwise)
+    #[serde(default)]
+    pub allow_plain_http_auth: bool,
+}
+
+impl Default for WebDavConfig {
+    fn default() -> Self {
+        Self {
+            username: None,
+            password_env: None,
+            chunk_size_mb: default_webdav_chunk_size_mb(),
+            max_monthly_transfer_gb: None,
+            allow_plain_http_auth: false,
+        }
+    }
+}
+
+fn default_webdav_chunk_size_mb() -> u64 {
+    10
+}
+
+/// Azure Blob Storage access used for `azblob://account/container/prefix` replicas
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AzureConfig {
+    /// Environment variable holding a SAS token; without one, the managed identity is used
+    #[serde(default)]
+    pub sas_token_env: Option<String>,
+
+    /// Client ID of a user-assigned managed identity (system-assigned if unset)
+    #[serde(default)]
+    pub managed_identity_client_id: Option<String>,
+
+    /// Access tier of uploaded blobs, unless the replica URL sets `?tier=` (account default if unset)
+    #[serde(default)]
+    pub tier: Option<AccessTier>,
+
+    /// Leave removal of old backups to a lifecycle management policy instead of `retention_count`
+    #[serde(default)]
+    pub lifecycle_retention: bool,
+
+    /// Pause replication through this backend once this much was sent and received in a calendar month
+    #[serde(default)]
+    pub max_monthly_transfer_gb: Option<u64>,
+}
+
+/// Google Drive account used for `gdrive://Folder/Path` replicas, authorized once with `--gdrive-login`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GoogleDriveConfig {
+    /// OAuth client ID of type "TVs and Limited Input devices"
+    #[serde(default)]
+    pub client_id: Option<String>,
+
+    /// Secret of that client (not confidential for this client type)
+    #[serde(default)]
+    pub client_secret: Option<String>,
+
+    /// Pause replication through this backend once this much was sent and received in a calendar month
+    #[serde(default)]
+    pub max_monthly_transfer_gb: Option<u64>,
+}
+
+/// How `ssh://` and `rsync://` replicas are reached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RsyncConfig {
+    /// rsync executable (e.g. cwRsync's `rsync.exe`)
+    #[serde(default = "default_rsync_command")]
+    pub rsync_command: String,
+
+    /// ssh executable used for `ssh://` replicas
+    #[serde(default = "default_ssh_command")]
+    pub ssh_command: String,
+
+    /// Private key for `ssh://` replicas (the ssh default keys otherwise)
+    #[serde(default)]
+    pub identity_file: Option<PathBuf>,
+
+    /// Environment variable holding the rsync daemon password for `rsync://` replicas
+    #[serde(default)]
+    pub password_env: Option<String>,
+
+    /// Pause replication through this backend once this much was sent and received in a calendar month
+    #[serde(default)]
+    pub max_monthly_transfer_gb: Option<u64>,
+}
+
+impl Default for RsyncConfig {
+    fn default() -> Self {
+        Self {
+            rsync_command: default_rsync_command(),
+            ssh_command: default_ssh_command(),
+            identity_file: None,
+            password_env: None,
+            max_monthly_transfer_gb: None,
+        }
+    }
+}
+
+fn default_rsync_command() -> String {
+    "rsync".to_string()
+}
+
+fn default_ssh_command() -> String {
+    "ssh".to_string()
+}
+
+/// Azure Blob access tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessTier {
+    Hot,
+    Cool,
+    Cold,
+    /// Offline; blobs must be rehydrated (hours) before they can be restored
+    Archive,
+}
+
+impl AccessTier {
+    /// Value of the `x-ms-access-tier` header
+    pub fn header_value(self) -> &'static str {
+        match self {
+            AccessTier::Hot => "Hot",
+            AccessTier::Cool => "Cool",
+            AccessTier::Cold => "Cold",
+            AccessTier::Archive => "Archive",
+        }
+    }
+}
+
+impl std::str::FromStr for AccessTier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hot" => Ok(AccessTier::Hot),
+            "cool" => Ok(AccessTier::Cool),
+            "cold" => Ok(AccessTier::Cold),
+            "archive" => Ok(AccessTier::Archive),
+            _ => Err(format!("Unknown access tier '{}' (expected hot, cool, cold or archive)", s)),
+        }
+    }
+}
+
+/// Post-copy verification of backup contents against the source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum VerifyConfig {
+    /// No verification
+    #[default]
+    Off,
+    /// Compare a random sample of copied files; any mismatch escalates to full verification
+    Sample {
+        /// Share of files checked per run (1-100)
+        #[serde(default = "default_verify_percent")]
+        percent: u8,
+    },
+    /// Compare every copied file (reads source and backup again)
+    Full,
+}
+
+fn default_verify_percent() -> u8 {
+    5
+}
+
+/// Periodic check of the targets that already hold backups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetProbeConfig {
+    /// Time between two checks of every target
+    #[serde(default = "default_probe_interval")]
+    pub interval_seconds: u64,
+
+    /// How long a target may be unreachable before it is reported
+    #[serde(default = "default_probe_alert")]
+    pub alert_after_seconds: u64,
+}
+
+fn default_probe_interval() -> u64 {
+    900
+}
+
+fn default_probe_alert() -> u64 {
+    24 * 3600
+}
+
+/// What to do when a target runs out of space mid-backup
+///
+/// The incomplete backup is always deleted to give the space back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskFullConfig {
+    /// Prune the target down to this many complete backups (at least one), remove
+    /// partial ones, and retry the run once if anything was freed (None = just fail)
+    #[serde(default)]
+    pub emergency_retention: Option<usize>,
+}
+
+/// Check performed before runs whose source is larger than a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeRunConfig {
+    /// Estimated run size above which the check applies
+    pub threshold_bytes: u64,
+
+    /// Wait for confirmation instead of only logging a warning
+    #[serde(default)]
+    pub confirm: bool,
+
+    /// How long to wait for confirmation
+    #[serde(default = "default_confirmation_timeout")]
+    pub timeout_seconds: u64,
+
+    /// What to do when nobody answers in time
+    #[serde(default)]
+    pub on_timeout: ConfirmationTimeout,
+}
+
+/// Outcome of an unanswered confirmation request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmationTimeout {
+    /// Skip this run; the job runs again at its next scheduled time
+    #[default]
+    Skip,
+    /// Run anyway
+    Proceed,
+}
+
+fn default_confirmation_timeout() -> u64 {
+    600
+}
+
+/// Estimate-only first run of jobs that have no backup yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstRunConfig {
+    /// How long the estimate waits for `--confirm` before the job copies anyway
+    /// (0 = wait until confirmed)
+    #[serde(default = "default_first_run_grace")]
+    pub grace_seconds: u64,
+}
+
+fn default_first_run_grace() -> u64 {
+    24 * 3600
+}
+
+/// Time of day window with its own limits, e.g. 10 MB/s on weekdays 08:00-18:00
+///
+/// A window whose end is not after its start runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThrottleWindow {
+    /// Days the window starts on (1=Monday, 7=Sunday); empty means every day
+    #[serde(default)]
+    pub days: Vec<u32>,
+
+    /// Start hour (0-23)
+    pub start_hour: u32,
+    /// Start minute (0-59)
+    #[serde(default)]
+    pub start_minute: u32,
+
+    /// End hour (0-24), exclusive
+    pub end_hour: u32,
+    /// End minute (0-59)
+    #[serde(default)]
+    pub end_minute: u32,
+
+    /// Copy rate shared by all running jobs (None = unlimited)
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+
+    /// Replaces the global max_concurrent_jobs while the window is active
+    #[serde(default)]
+    pub max_concurrent_jobs: Option<usize>,
+}
+
+impl ThrottleWindow {
+    /// Whether the window covers the given local wall-clock time
+    pub fn is_active_at(&self, now: NaiveDateTime) -> bool {
+        let start = self.start_hour * 60 + self.start_minute;
+        let end = self.end_hour * 60 + self.end_minute;
+        let minute_of_day = now.hour() * 60 + now.minute();
+        let today = now.weekday().number_from_monday();
+
+        if start < end {
+            return self.starts_on(today) && (start..end).contains(&minute_of_day);
+        }
+
+        // Overnight window: the evening part of its own day or the morning after
+        let yesterday = now.weekday().pred().number_from_monday();
+        (self.starts_on(today) && minute_of_day >= start)
+            || (self.starts_on(yesterday) && minute_of_day < end)
+    }
+
+    fn starts_on(&self, day: u32) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+}
+
 /// Individual backup job configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BackupJob {
     /// Unique job identifier
     pub id: String,
 
-    /// Source directory to backup
+    /// Source directory to backup (only used by `files` jobs)
+    #[serde(default)]
     pub source: PathBuf,
 
     /// Target directory for backups
@@ -87,6 +834,427 @@ pub struct BackupJob {
     /// Optional description
     #[serde(default)]
     pub description: String,
+
+    /// Backups to keep for this job (overrides the global retention_count)
+    #[serde(default)]
+    pub retention_count: Option<usize>,
+
+    /// Largest total size of this job's backups in GB; the oldest are removed until the rest fit
+    #[serde(default)]
+    pub retention_max_total_gb: Option<u64>,
+
+    /// Days a backup is kept; older backups are removed whatever `retention_count` allows
+    #[serde(default)]
+    pub retention_max_age_days: Option<u32>,
+
+    /// File this job's state is kept in instead of the shared state file
+    #[serde(default)]
+    pub state_path: Option<PathBuf>,
+
+    /// Secondary targets each completed backup is copied to
+    #[serde(default)]
+    pub replicas: Vec<PathBuf>,
+
+    /// Built-in sets of files and folders left out of this job's backups
+    #[serde(default)]
+    pub exclude_profiles: Vec<ExcludeProfile>,
+
+    /// Further files and folders left out, matched like the exclusion profiles (`*` wildcards)
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Renaming of names the target cannot hold (case conflicts, reserved or invalid names)
+    #[serde(default)]
+    pub name_conflicts: NameConflicts,
+
+    /// Unicode normalization form names are stored in (unchanged if not set)
+    #[serde(default)]
+    pub normalize_names: Option<NameNormalization>,
+
+    /// Longest path the target accepts (260 for network shares, the platform limit otherwise)
+    #[serde(default)]
+    pub max_path_length: Option<usize>,
+
+    /// What the job backs up: its source folder (default) or the Windows system state
+    #[serde(default)]
+    pub kind: JobKind,
+
+    /// Registry hives and system files exported by `system-state` jobs
+    #[serde(default)]
+    pub system_state: SystemStateConfig,
+
+    /// Dump command of `database-dump` jobs
+    #[serde(default)]
+    pub dump: Option<DumpConfig>,
+
+    /// Distribution exported by `wsl-distro` jobs
+    #[serde(default)]
+    pub wsl: Option<WslConfig>,
+
+    /// Named volume exported by `docker-volume` jobs
+    #[serde(default)]
+    pub docker: Option<DockerVolumeConfig>,
+
+    /// Application recipe filling in source, exclusions, lock files and hooks at config load
+    #[serde(default)]
+    pub app: Option<AppRecipe>,
+
+    /// Files below the source an application holds while it runs (`*` matches one folder);
+    /// the run is skipped while any of them is in use
+    #[serde(default)]
+    pub lock_files: Vec<String>,
+
+    /// Commands run before and after each backup of this job
+    #[serde(default)]
+    pub hooks: JobHooks,
+
+    /// Runs of this job that send an email, instead of the `notifications` defaults
+    #[serde(default)]
+    pub notify: Option<NotifyTriggers>,
+
+    /// Processes whose open data files would be inconsistent in a backup (`outlook.exe`)
+    #[serde(default)]
+    pub busy_processes: Vec<String>,
+
+    /// What a run does while one of `busy_processes` is running
+    #[serde(default)]
+    pub when_busy: WhenBusy,
+
+    /// Run a failed backup again after a delay instead of waiting for the next schedule
+    #[serde(default)]
+    pub retry: Option<JobRetryConfig>,
+
+    /// Copy every file each run, or only files changed since the last backup
+    #[serde(default)]
+    pub mode: BackupMode,
+
+    /// Write each backup as one compressed archive file instead of a folder copy
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+
+    /// Copy rate of this job alone (None = unlimited); throttle windows still apply on top
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+
+    /// Copy from a shadow copy of the source volume, so open and locked files are
+    /// backed up (Windows only)
+    #[serde(default)]
+    pub shadow_copy: bool,
+
+    /// Read files that cannot be opened otherwise through `BackupRead` with the backup
+    /// privilege, which bypasses their permissions (Windows only, never for ad-hoc jobs)
+    #[serde(default)]
+    pub backup_privilege: bool,
+
+    /// Only report what each run would copy, exclude and remove, without writing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Archive a job's backups are written to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    pub format: ArchiveFormat,
+
+    /// Compression level: 1-9 for zip (default 6), 1-19 for tar.zst (default 3)
+    #[serde(default)]
+    pub level: Option<u32>,
+
+    /// zstd executable compressing `tar.zst` archives
+    #[serde(default = "default_zstd_command")]
+    pub zstd_command: String,
+}
+
+/// File format of archive backups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    /// Deflate-compressed zip, readable without extra tools on Windows
+    #[serde(rename = "zip")]
+    Zip,
+
+    /// tar stream compressed by the zstd command
+    #[serde(rename = "tar.zst")]
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// File name extension, without the leading dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarZst => "tar.zst",
+        }
+    }
+}
+
+fn default_zstd_command() -> String {
+    "zstd".to_string()
+}
+
+/// How much of the source each backup copies
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Copy every file
+    #[default]
+    Full,
+
+    /// Copy files whose size or modification time changed since the latest backup;
+    /// unchanged files are hard-linked from it
+    Incremental,
+}
+
+/// Reaction of a job to one of its `busy_processes` running
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WhenBusy {
+    /// Back up anyway and log a warning
+    #[default]
+    Warn,
+
+    /// Try again every 15 minutes; after 12 hours back up anyway with a warning
+    Defer,
+}
+
+/// Retries of a failed job run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobRetryConfig {
+    /// Runs in total, the first one included, before the job stays failed
+    #[serde(default = "default_job_retry_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled after each failure (capped at six hours)
+    #[serde(default = "default_job_retry_backoff")]
+    pub backoff_seconds: u64,
+}
+
+impl JobRetryConfig {
+    /// Delay before retry number `attempt` (1-based)
+    pub fn retry_delay(&self, attempt: u32) -> std::time::Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        let seconds = self.backoff_seconds.saturating_mul(factor).min(6 * 3600);
+        std::time::Duration::from_secs(seconds)
+    }
+}
+
+fn default_job_retry_attempts() -> u32 {
+    3
+}
+
+fn default_job_retry_backoff() -> u64 {
+    300
+}
+
+/// Application whose data a job backs up, see `config::recipes`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AppRecipe {
+    /// KeePass / KeePassXC database folder
+    Keepass,
+
+    /// Thunderbird profiles, skipped while Thunderbird is open
+    Thunderbird,
+
+    /// Minecraft Java server folder, with saving paused over RCON during the copy
+    MinecraftServer,
+}
+
+/// Shell commands run around a backup (`cmd /C` on Windows, `sh -c` elsewhere)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobHooks {
+    /// Run before copying; the backup fails if it fails
+    #[serde(default)]
+    pub pre: Option<String>,
+
+    /// Run after the backup whenever `pre` succeeded, even if the backup failed
+    #[serde(default)]
+    pub post: Option<String>,
+
+    /// Run after a successful backup, given the run's result as JSON on stdin and in
+    /// the file named by `KEEPHIVE_RESULT_FILE`
+    #[serde(default)]
+    pub on_success_command: Option<String>,
+
+    /// Run after a failed backup, given the run's result like `on_success_command`
+    #[serde(default)]
+    pub on_failure_command: Option<String>,
+}
+
+/// Kind of data a job backs up
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobKind {
+    /// Files and folders below `source`
+    #[default]
+    Files,
+
+    /// Registry hives and key system files, exported from a shadow copy (Windows only)
+    SystemState,
+
+    /// Output of a dump command such as `pg_dump`, `mysqldump` or `sqlcmd`
+    DatabaseDump,
+
+    /// A WSL2 distribution's filesystem, exported with `wsl --export`
+    WslDistro,
+
+    /// A Docker named volume, streamed as a tar file from a temporary container
+    DockerVolume,
+}
+
+/// WSL distribution of a `wsl-distro` job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WslConfig {
+    /// Name as listed by `wsl --list`
+    pub distro: String,
+
+    /// Shut the distribution down before exporting, so no process writes during the export
+    #[serde(default)]
+    pub terminate: bool,
+
+    /// Folder the export is written to before it is backed up (default: the temp folder)
+    #[serde(default)]
+    pub staging: Option<PathBuf>,
+}
+
+/// Docker volume of a `docker-volume` job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DockerVolumeConfig {
+    /// Name as listed by `docker volume ls`
+    pub volume: String,
+
+    /// Image of the temporary container running `tar`
+    #[serde(default = "default_tar_image")]
+    pub image: String,
+
+    /// Leave containers using the volume running instead of pausing them during the export
+    #[serde(default)]
+    pub keep_running: bool,
+
+    /// Folder the export is written to before it is backed up (default: the temp folder)
+    #[serde(default)]
+    pub staging: Option<PathBuf>,
+}
+
+fn default_tar_image() -> String {
+    "busybox".to_string()
+}
+
+/// How a `database-dump` job produces its dump
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DumpConfig {
+    /// Shell command run in an empty dump folder, also given as `KEEPHIVE_DUMP_DIR`
+    pub command: String,
+
+    /// File in the dump folder the command's standard output is written to;
+    /// None if the command writes its files itself
+    #[serde(default)]
+    pub output: Option<String>,
+
+    /// Folder dumps are written to before they are backed up (default: the temp folder)
+    #[serde(default)]
+    pub staging: Option<PathBuf>,
+
+    /// Longest the command may run before it is stopped
+    #[serde(default = "default_dump_timeout")]
+    pub timeout_seconds: u64,
+}
+
+fn default_dump_timeout() -> u64 {
+    4 * 3600
+}
+
+/// What a `system-state` job exports
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SystemStateConfig {
+    /// Hives under HKEY_LOCAL_MACHINE saved with `RegSaveKeyEx`
+    #[serde(default = "default_registry_hives")]
+    pub hives: Vec<RegistryHive>,
+
+    /// Files and folders copied from a shadow copy of their volume
+    #[serde(default = "default_system_files")]
+    pub files: Vec<PathBuf>,
+}
+
+impl Default for SystemStateConfig {
+    fn default() -> Self {
+        Self {
+            hives: default_registry_hives(),
+            files: default_system_files(),
+        }
+    }
+}
+
+fn default_registry_hives() -> Vec<RegistryHive> {
+    vec![RegistryHive::System, RegistryHive::Software, RegistryHive::Sam, RegistryHive::Security]
+}
+
+fn default_system_files() -> Vec<PathBuf> {
+    vec![
+        PathBuf::from(r"C:\Windows\System32\drivers\etc"),
+        PathBuf::from(r"C:\Windows\System32\Tasks"),
+    ]
+}
+
+/// Registry hive under HKEY_LOCAL_MACHINE
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistryHive {
+    System,
+    Software,
+    /// Local accounts and their password hashes
+    Sam,
+    /// LSA secrets and cached domain logons
+    Security,
+}
+
+impl RegistryHive {
+    /// Key name under HKEY_LOCAL_MACHINE, also used as the file name of the export
+    pub fn key_name(self) -> &'static str {
+        match self {
+            RegistryHive::System => "SYSTEM",
+            RegistryHive::Software => "SOFTWARE",
+            RegistryHive::Sam => "SAM",
+            RegistryHive::Security => "SECURITY",
+        }
+    }
+}
+
+/// Unicode normalization form for file names
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NameNormalization {
+    /// Composed, as Windows and most Linux tools write names
+    Nfc,
+
+    /// Decomposed, as macOS writes names
+    Nfd,
+}
+
+/// How names a case-insensitive or Windows-compatible target (SMB, exFAT) cannot hold are stored
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NameConflicts {
+    /// Copy names unchanged
+    #[default]
+    Off,
+
+    /// Replace invalid characters and number names that differ only in case: `Readme (2).md`
+    Suffix,
+
+    /// Like `suffix`, but conflicting names get a hash of the source name: `Readme~1a2b3c4d.md`
+    Hash,
+}
+
+/// Built-in exclusion profile, selected per job in `exclude_profiles`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExcludeProfile {
+    /// Thumbnail caches, page and hibernation files, recycle bins, Office lock files,
+    /// browser caches and OneDrive files that are only in the cloud
+    WindowsNoise,
+
+    /// Dependency folders and build caches of common development tools
+    DevCaches,
 }
 
 /// Backup schedule configuration
@@ -116,36 +1284,52 @@ pub enum Schedule {
         /// Minute (0-59)
         minute: u32,
     },
+
+    /// Whenever a cron expression matches, such as "30 2 * * 1-5"
+    Cron {
+        /// Minute, hour, day of month, month and day of week, in local time
+        expression: CronExpression,
+    },
 }
 
 impl Schedule {
     /// Get duration until next run from now
-    pub fn next_run_duration(&self, last_run: Option<chrono::DateTime<chrono::Utc>>) -> Duration {
+    pub fn next_run_duration(&self, last_run: Option<DateTime<Utc>>) -> Duration {
+        let now = Local::now();
+        let next = self.next_run_after(now, last_run);
+
+        if next <= now {
+            Duration::zero()
+        } else {
+            next.signed_duration_since(now)
+        }
+    }
+
+    /// Get the next run time as seen from `now`
+    pub fn next_run_after(&self, now: DateTime<Local>, last_run: Option<DateTime<Utc>>) -> DateTime<Local> {
         match self {
             Schedule::Interval { seconds } => {
-                if let Some(last) = last_run {
-                    let elapsed = Local::now().signed_duration_since(last);
-                    let interval = Duration::seconds(*seconds as i64);
-                    if elapsed >= interval {
-                        Duration::zero()
-                    } else {
-                        interval - elapsed
+                match last_run {
+                    Some(last) => {
+                        let next = last.with_timezone(&Local) + Duration::seconds(*seconds as i64);
+                        next.max(now)
                     }
-                } else {
-                    Duration::zero()
+                    None => now,
                 }
             }
             Schedule::Daily { hour, minute } => {
-                Self::calculate_next_daily(*hour, *minute, last_run)
+                Self::calculate_next_daily(now, *hour, *minute)
             }
             Schedule::Weekly { day, hour, minute } => {
-                Self::calculate_next_weekly(*day, *hour, *minute, last_run)
+                Self::calculate_next_weekly(now, *day, *hour, *minute)
+            }
+            Schedule::Cron { expression } => {
+                Self::resolve_local(expression.next_after(now.naive_local()))
             }
         }
     }
 
-    fn calculate_next_daily(hour: u32, minute: u32, _last_run: Option<chrono::DateTime<chrono::Utc>>) -> Duration {
-        let now = Local::now();
+    fn calculate_next_daily(now: DateTime<Local>, hour: u32, minute: u32) -> DateTime<Local> {
         let today_scheduled = now
             .date_naive()
             .and_hms_opt(hour, minute, 0)
@@ -159,12 +1343,10 @@ impl Schedule {
                 .unwrap()
         };
 
-        let next_datetime = next.and_local_timezone(now.timezone()).unwrap();
-        next_datetime.signed_duration_since(now)
+        Self::resolve_local(next)
     }
 
-    fn calculate_next_weekly(day: u32, hour: u32, minute: u32, _last_run: Option<chrono::DateTime<chrono::Utc>>) -> Duration {
-        let now = Local::now();
+    fn calculate_next_weekly(now: DateTime<Local>, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
         let current_weekday = now.weekday().num_days_from_monday() + 1; // 1=Monday, 7=Sunday
 
         // Calculate days until target weekday
@@ -194,8 +1376,41 @@ impl Schedule {
             .and_hms_opt(hour, minute, 0)
             .expect("Invalid hour/minute for weekly schedule");
 
-        let next = next_datetime.and_local_timezone(now.timezone()).unwrap();
-        next.signed_duration_since(now)
+        Self::resolve_local(next_datetime)
+    }
+
+    /// Map a wall-clock time to local time, handling DST gaps and overlaps
+    fn resolve_local(naive: NaiveDateTime) -> DateTime<Local> {
+        match naive.and_local_timezone(Local) {
+            LocalResult::Single(dt) => dt,
+            // Clocks go back: use the first occurrence
+            LocalResult::Ambiguous(earliest, _) => earliest,
+            // Clocks go forward: the time does not exist, run right after the gap
+            LocalResult::None => Self::resolve_local(naive + Duration::hours(1)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Interval { seconds } => write!(f, "every {}s", seconds),
+            Schedule::Daily { hour, minute } => write!(f, "daily at {:02}:{:02}", hour, minute),
+            Schedule::Weekly { day, hour, minute } => {
+                let day_name = match day {
+                    1 => "Monday",
+                    2 => "Tuesday",
+                    3 => "Wednesday",
+                    4 => "Thursday",
+                    5 => "Friday",
+                    6 => "Saturday",
+                    7 => "Sunday",
+                    _ => "invalid day",
+                };
+                write!(f, "weekly on {} at {:02}:{:02}", day_name, hour, minute)
+            }
+            Schedule::Cron { expression } => write!(f, "cron \"{}\"", expression),
+        }
     }
 }
 
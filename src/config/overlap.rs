@@ -0,0 +1,206 @@
+//! Checks across jobs at config load. Jobs whose folders overlap copy the same files
+//! twice, or back up each other's backups so that every run is larger than the last.
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+use super::models::{BackupJob, JobKind};
+use crate::core::Exclusions;
+use crate::storage::TargetUrl;
+
+/// Two jobs whose folders overlap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobOverlap {
+    /// Setting that overlaps, e.g. `jobs[docs].target`
+    pub location: String,
+    pub message: String,
+    /// Whether the overlap breaks backups, instead of only wasting space
+    pub rejected: bool,
+}
+
+impl fmt::Display for JobOverlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Overlapping folders of `jobs`
+pub fn check_overlaps(jobs: &[BackupJob]) -> Vec<JobOverlap> {
+    let mut overlaps = Vec::new();
+
+    for (i, job) in jobs.iter().enumerate() {
+        let Some(source) = source_folder(job) else {
+            continue;
+        };
+
+        for (j, other) in jobs.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+
+            // Backups written into a source are copied by its next run, which is larger each time
+            for (field, folder) in written_folders(other) {
+                if folder.starts_with(&source) && !excluded(job, &source, &folder) {
+                    overlaps.push(JobOverlap {
+                        location: format!("jobs[{}].{}", other.id, field),
+                        message: format!(
+                            "'{}' is inside the source of job '{}', which would back up these backups again on every run",
+                            folder.display(), job.id
+                        ),
+                        rejected: true,
+                    });
+                }
+            }
+
+            let Some(other_source) = source_folder(other) else {
+                continue;
+            };
+
+            if j > i && other_source == source && normalize(&other.target) == normalize(&job.target) {
+                overlaps.push(JobOverlap {
+                    location: format!("jobs[{}]", other.id),
+                    message: format!(
+                        "backs up the same source to the same target as job '{}'; their backups and retention would collide",
+                        job.id
+                    ),
+                    rejected: true,
+                });
+            } else if other_source != source && other_source.starts_with(&source) && !excluded(job, &source, &other_source) {
+                overlaps.push(JobOverlap {
+                    location: format!("jobs[{}].source", other.id),
+                    message: format!(
+                        "'{}' is inside the source of job '{}'; its files are copied by both jobs",
+                        other_source.display(), job.id
+                    ),
+                    rejected: false,
+                });
+            }
+        }
+    }
+
+    overlaps
+}
+
+/// Log overlaps that only cost space and time, and fail on those that break backups
+pub fn validate_job_overlaps(jobs: &[BackupJob]) -> Result<()> {
+    let overlaps = check_overlaps(jobs);
+
+    for overlap in overlaps.iter().filter(|overlap| !overlap.rejected) {
+        warn!("Overlapping jobs: {}", overlap);
+    }
+
+    let rejected: Vec<String> = overlaps.iter()
+        .filter(|overlap| overlap.rejected)
+        .map(|overlap| format!("  - {}", overlap))
+        .collect();
+    if !rejected.is_empty() {
+        bail!("Overlapping jobs detected in configuration:\n{}\n\nMove the targets out of the other jobs' sources, or exclude them there.", rejected.join("\n"));
+    }
+
+    Ok(())
+}
+
+/// Source folder a job reads, for jobs that copy files
+fn source_folder(job: &BackupJob) -> Option<PathBuf> {
+    if job.kind != JobKind::Files || job.source.as_os_str().is_empty() || TargetUrl::from_path(&job.source).is_some() {
+        return None;
+    }
+    Some(normalize(&job.source))
+}
+
+/// Local folders a job writes backups to: its target and its replicas on disk
+fn written_folders(job: &BackupJob) -> Vec<(String, PathBuf)> {
+    let mut folders = vec![("target".to_string(), normalize(&job.target))];
+    for (i, replica) in job.replicas.iter().enumerate() {
+        if TargetUrl::from_path(replica).is_none() {
+            folders.push((format!("replicas[{}]", i), normalize(replica)));
+        }
+    }
+    folders
+}
+
+/// Whether `job` leaves out `folder` below its `source`, or a folder containing it
+fn excluded(job: &BackupJob, source: &Path, folder: &Path) -> bool {
+    let exclusions = Exclusions::from_profiles(&job.exclude_profiles).with_patterns(&job.exclude);
+    let Ok(relative) = folder.strip_prefix(source) else {
+        return false;
+    };
+
+    let mut prefix = PathBuf::new();
+    relative.components().any(|component| {
+        prefix.push(component);
+        exclusions.excludes_path(&prefix)
+    })
+}
+
+/// The path as written, without `.` parts and trailing separators, and case-insensitive
+/// on Windows; folders that do not exist yet are compared the same way
+fn normalize(path: &Path) -> PathBuf {
+    let normalized: PathBuf = path.components().filter(|c| *c != Component::CurDir).collect();
+
+    #[cfg(windows)]
+    let normalized = PathBuf::from(normalized.to_string_lossy().to_lowercase());
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jobs(json: &str) -> Vec<BackupJob> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn locations(overlaps: &[JobOverlap]) -> Vec<(&str, bool)> {
+        overlaps.iter().map(|overlap| (overlap.location.as_str(), overlap.rejected)).collect()
+    }
+
+    #[test]
+    fn test_target_inside_another_source_is_rejected() {
+        let jobs = jobs(r#"[
+            { "id": "home", "source": "/home/me", "target": "/mnt/backups/home", "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "docs", "source": "/srv/docs", "target": "/home/me/./backups/docs/", "schedule": { "type": "interval", "seconds": 3600 },
+              "replicas": ["/home/me/copy", "webdav://cloud.example.com/home/me"] }
+        ]"#);
+
+        assert_eq!(locations(&check_overlaps(&jobs)), [("jobs[docs].target", true), ("jobs[docs].replicas[0]", true)]);
+        assert!(validate_job_overlaps(&jobs).is_err());
+    }
+
+    #[test]
+    fn test_excluded_target_is_allowed() {
+        let jobs = jobs(r#"[
+            { "id": "home", "source": "/home/me", "target": "/mnt/backups/home", "exclude": ["Backups"], "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "docs", "source": "/srv/docs", "target": "/home/me/backups/docs", "schedule": { "type": "interval", "seconds": 3600 } }
+        ]"#);
+
+        assert!(check_overlaps(&jobs).is_empty());
+    }
+
+    #[test]
+    fn test_same_source_and_target_is_rejected() {
+        let jobs = jobs(r#"[
+            { "id": "a", "source": "/srv/docs", "target": "/mnt/backups", "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "b", "source": "/srv/docs/", "target": "/mnt/backups", "schedule": { "type": "daily", "hour": 2, "minute": 0 } },
+            { "id": "c", "source": "/srv/docs", "target": "/mnt/offsite", "schedule": { "type": "interval", "seconds": 3600 } }
+        ]"#);
+
+        assert_eq!(locations(&check_overlaps(&jobs)), [("jobs[b]", true)]);
+    }
+
+    #[test]
+    fn test_nested_source_is_a_warning() {
+        let jobs = jobs(r#"[
+            { "id": "home", "source": "/home/me", "target": "/mnt/backups/home", "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "photos", "source": "/home/me/Pictures", "target": "/mnt/backups/photos", "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "dump", "kind": "database-dump", "source": "", "target": "/mnt/backups/db", "schedule": { "type": "interval", "seconds": 3600 },
+              "dump": { "command": "pg_dump app" } }
+        ]"#);
+
+        assert_eq!(locations(&check_overlaps(&jobs)), [("jobs[photos].source", false)]);
+        assert!(validate_job_overlaps(&jobs).is_ok());
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use tracing::{error, info};
+
+use super::models::ServiceConfig;
+use crate::core::{to_hex, Sha256};
+
+/// Settings enforced by the machine's administrators, e.g. through the Group Policy
+/// template in `policy/`, taking precedence over the config file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    /// Backups kept per job; replaces the global and every per-job retention count
+    pub retention_count: Option<usize>,
+
+    /// Job targets and replicas must lie below one of these (empty = anywhere)
+    pub allowed_target_roots: Vec<PathBuf>,
+
+    /// Replica targets added to every job (`{job}` is replaced by the job ID)
+    pub replicas: Vec<PathBuf>,
+
+    pub manifest_checksums: Option<bool>,
+
+    /// Reject config reloads and service starts that change retention, targets, replicas
+    /// or storage backends
+    pub lock_settings: bool,
+}
+
+/// The settings `lock_settings` protects, recorded in the state file so edits made while
+/// the service was stopped are noticed at the next start
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedSettings {
+    pub retention_count: usize,
+
+    #[serde(default)]
+    pub adhoc_target: Option<PathBuf>,
+
+    /// SHA-256 of the storage backend settings, which may hold credentials
+    pub storage: String,
+
+    #[serde(default)]
+    pub jobs: BTreeMap<String, LockedJob>,
+}
+
+/// Protected settings of one job
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedJob {
+    pub target: PathBuf,
+
+    #[serde(default)]
+    pub replicas: Vec<PathBuf>,
+
+    #[serde(default)]
+    pub retention_count: Option<usize>,
+
+    #[serde(default)]
+    pub retention_max_age_days: Option<u32>,
+
+    #[serde(default)]
+    pub retention_max_total_gb: Option<u64>,
+}
+
+impl LockedSettings {
+    /// Protected settings of a config the policy was applied to
+    pub fn of(config: &ServiceConfig) -> Self {
+        let mut storage = Sha256::new();
+        storage.update(&serde_json::to_vec(&config.storage).unwrap_or_default());
+
+        Self {
+            retention_count: config.retention_count,
+            adhoc_target: config.adhoc_target.clone(),
+            storage: to_hex(&storage.finish()),
+            jobs: config.jobs.iter()
+                .map(|job| (job.id.clone(), LockedJob {
+                    target: job.target.clone(),
+                    replicas: job.replicas.clone(),
+                    retention_count: job.retention_count,
+                    retention_max_age_days: job.retention_max_age_days,
+                    retention_max_total_gb: job.retention_max_total_gb,
+                }))
+                .collect(),
+        }
+    }
+
+    /// Every protected setting `new` changes, one entry per setting
+    pub fn changes(&self, new: &LockedSettings) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        if self.retention_count != new.retention_count {
+            changes.push(format!("retention_count changed from {} to {}",
+                self.retention_count, new.retention_count));
+        }
+
+        if self.adhoc_target != new.adhoc_target {
+            changes.push("adhoc_target changed".to_string());
+        }
+
+        if self.storage != new.storage {
+            changes.push("storage backend settings changed".to_string());
+        }
+
+        for (id, job) in &new.jobs {
+            let Some(current) = self.jobs.get(id) else {
+                changes.push(format!("job {} added", id));
+                continue;
+            };
+
+            if current.target != job.target {
+                changes.push(format!("target of job {} changed", id));
+            }
+            if current.replicas != job.replicas {
+                changes.push(format!("replicas of job {} changed", id));
+            }
+            if current.retention_count != job.retention_count {
+                changes.push(format!("retention_count of job {} changed", id));
+            }
+            if current.retention_max_age_days != job.retention_max_age_days {
+                changes.push(format!("retention_max_age_days of job {} changed", id));
+            }
+            if current.retention_max_total_gb != job.retention_max_total_gb {
+                changes.push(format!("retention_max_total_gb of job {} changed", id));
+            }
+        }
+
+        for id in self.jobs.keys().filter(|id| !new.jobs.contains_key(*id)) {
+            changes.push(format!("job {} removed", id));
+        }
+
+        changes
+    }
+}
+
+impl Policy {
+    /// Whether any setting is enforced
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge the policy into a config loaded from file.
+    ///
+    /// Jobs and replicas targeting a location the policy does not allow are dropped
+    /// and logged as errors, so the remaining jobs keep running.
+    pub fn apply(&self, config: &mut ServiceConfig) {
+        if let Some(retention_count) = self.retention_count {
+            config.retention_count = retention_count;
+            for job in &mut config.jobs {
+                job.retention_count = None;
+            }
+        }
+
+        if let Some(manifest_checksums) = self.manifest_checksums {
+            config.manifest_checksums = manifest_checksums;
+        }
+
+        for job in &mut config.jobs {
+            for replica in &self.replicas {
+                if !job.replicas.contains(replica) {
+                    job.replicas.push(replica.clone());
+                }
+            }
+        }
+
+        if self.allowed_target_roots.is_empty() {
+            return;
+        }
+
+        if let Some(adhoc_target) = config.adhoc_target.take_if(|target| !self.allows(target)) {
+            error!("Policy violation: adhoc_target {} is not below an allowed target root; ad-hoc backups disabled",
+                adhoc_target.display());
+        }
+
+        config.jobs.retain_mut(|job| {
+            if !self.allows(&job.target) {
+                error!("Policy violation: job {} targets {}, which is not below an allowed target root; job disabled",
+                    job.id, job.target.display());
+                return false;
+            }
+
+            job.replicas.retain(|replica| {
+                let allowed = self.allows(replica);
+                if !allowed {
+                    error!("Policy violation: replica {} of job {} is not below an allowed target root; replica disabled",
+                        replica.display(), job.id);
+                }
+                allowed
+            });
+            true
+        });
+    }
+
+    /// Protected settings `new` changes compared to the running config, if settings are locked.
+    ///
+    /// Both configs are compared after the policy was applied, so values the policy
+    /// enforces anyway never count as a change.
+    pub fn reload_violations(&self, current: &ServiceConfig, new: &ServiceConfig) -> Vec<String> {
+        self.startup_violations(Some(&LockedSettings::of(current)), new)
+    }
+
+    /// Protected settings `config` changes compared to the ones recorded when the service
+    /// last ran, if settings are locked. Nothing recorded yet means nothing to compare.
+    pub fn startup_violations(&self, recorded: Option<&LockedSettings>, config: &ServiceConfig) -> Vec<String> {
+        match recorded {
+            Some(recorded) if self.lock_settings => recorded.changes(&LockedSettings::of(config)),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether `target` lies below an allowed root, or no roots are set. Both are resolved
+    /// first, so `..` parts and links cannot lead out of a root, and compared
+    /// case-insensitively, as on Windows.
+    pub fn allows(&self, target: &Path) -> bool {
+        if self.allowed_target_roots.is_empty() {
+            return true;
+        }
+        let target = resolve(target);
+
+        self.allowed_target_roots.iter()
+            .any(|root| target.starts_with(resolve(root)))
+    }
+}
+
+/// `path` without `.` and `..` parts, with the part that exists resolved through links and
+/// junctions, in lower case
+fn resolve(path: &Path) -> PathBuf {
+    let mut lexical = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            other => lexical.push(other),
+        }
+    }
+
+    let mut resolved = lexical.clone();
+    if lexical.is_absolute() {
+        let existing = lexical.ancestors().find(|ancestor| ancestor.exists());
+        if let Some(existing) = existing
+            && let Ok(canonical) = dunce::canonicalize(existing)
+        {
+            resolved = canonical.join(lexical.strip_prefix(existing).unwrap_or(Path::new("")));
+        }
+    }
+
+    PathBuf::from(resolved.to_string_lossy().to_lowercase())
+}
+
+/// Policy set for this machine, if any
+pub fn machine_policy() -> Result<Policy> {
+    #[cfg(windows)]
+    {
+        crate::platform::windows::registry::read_policy()
+    }
+
+    #[cfg(not(windows))]
+    {
+        Ok(Policy::default())
+    }
+}
+
+/// Apply the machine's policy to a config just loaded from file
+pub fn apply_machine_policy(config: &mut ServiceConfig) -> Result<()> {
+    let policy = machine_policy()?;
+
+    if !policy.is_empty() {
+        info!("Applying machine policy: {:?}", policy);
+        policy.apply(config);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ServiceConfig {
+        serde_json::from_str(r#"{
+            "jobs": [
+                { "id": "docs", "source": "/home/me/docs", "target": "/mnt/Backup/docs", "retention_count": 30,
+                  "replicas": ["/mnt/usb/docs"], "schedule": { "type": "daily", "hour": 2, "minute": 0 } },
+                { "id": "music", "source": "/home/me/music", "target": "/tmp/music",
+                  "schedule": { "type": "daily", "hour": 3, "minute": 0 } }
+            ],
+            "retention_count": 2
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_policy_overrides_file_settings() {
+        let mut config = config();
+        let policy = Policy {
+            retention_count: Some(10),
+            replicas: vec![PathBuf::from("/mnt/nas/{job}")],
+            manifest_checksums: Some(false),
+            ..Policy::default()
+        };
+
+        policy.apply(&mut config);
+
+        assert_eq!(config.retention_count, 10);
+        assert_eq!(config.jobs[0].retention_count, None);
+        assert!(!config.manifest_checksums);
+        assert_eq!(config.jobs[1].replicas, vec![PathBuf::from("/mnt/nas/{job}")]);
+        assert_eq!(config.jobs[0].replicas.len(), 2);
+    }
+
+    #[test]
+    fn test_targets_outside_allowed_roots_are_dropped() {
+        let mut config = config();
+        let policy = Policy {
+            allowed_target_roots: vec![PathBuf::from("/MNT/backup")],
+            ..Policy::default()
+        };
+
+        policy.apply(&mut config);
+
+        assert_eq!(config.jobs.len(), 1, "Job targeting /tmp is dropped");
+        assert_eq!(config.jobs[0].id, "docs");
+        assert!(config.jobs[0].replicas.is_empty(), "Replica on /mnt/usb is dropped");
+    }
+
+    #[test]
+    fn test_parent_parts_and_links_cannot_leave_a_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("Allowed");
+        std::fs::create_dir_all(&allowed).unwrap();
+        let policy = Policy { allowed_target_roots: vec![allowed.clone()], ..Policy::default() };
+
+        assert!(policy.allows(&allowed.join("docs")));
+        assert!(policy.allows(&allowed.join("new/../docs")));
+        assert!(!policy.allows(&allowed.join("../Anywhere")));
+        assert!(!policy.allows(&allowed.join("../../..")));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), allowed.join("out")).unwrap();
+            assert!(!policy.allows(&allowed.join("out/elsewhere")), "A link out of the root is followed");
+        }
+
+        let mut config = config();
+        config.adhoc_target = Some(dir.path().join("adhoc"));
+        policy.apply(&mut config);
+        assert_eq!(config.adhoc_target, None);
+    }
+
+    #[test]
+    fn test_locked_settings_reject_protected_changes() {
+        let current = config();
+        let policy = Policy { lock_settings: true, ..Policy::default() };
+
+        let mut edited = config();
+        edited.jobs[0].schedule = crate::config::Schedule::Interval { seconds: 600 };
+        edited.log_level = "debug".to_string();
+        assert!(policy.reload_violations(&current, &edited).is_empty(), "Unprotected settings may change");
+
+        edited.retention_count = 1;
+        edited.jobs[0].target = PathBuf::from("/tmp/docs");
+        edited.jobs.remove(1);
+        assert_eq!(policy.reload_violations(&current, &edited), vec![
+            "retention_count changed from 2 to 1".to_string(),
+            "target of job docs changed".to_string(),
+            "job music removed".to_string(),
+        ]);
+
+        assert!(Policy::default().reload_violations(&current, &edited).is_empty(), "Unlocked settings may change");
+    }
+
+    #[test]
+    fn test_every_locked_setting_is_compared() {
+        let recorded = LockedSettings::of(&config());
+        let policy = Policy { lock_settings: true, ..Policy::default() };
+        let violations = |edit: fn(&mut ServiceConfig)| {
+            let mut edited = config();
+            edit(&mut edited);
+            policy.startup_violations(Some(&recorded), &edited)
+        };
+
+        assert!(violations(|_| {}).is_empty());
+        assert_eq!(violations(|c| c.retention_count = 5), vec!["retention_count changed from 2 to 5"]);
+        assert_eq!(violations(|c| c.adhoc_target = Some(PathBuf::from("/tmp/adhoc"))), vec!["adhoc_target changed"]);
+        assert_eq!(violations(|c| c.storage.webdav.username = Some("other".to_string())),
+            vec!["storage backend settings changed"]);
+        assert_eq!(violations(|c| c.jobs[1].id = "films".to_string()), vec!["job films added", "job music removed"]);
+        assert_eq!(violations(|c| c.jobs[0].target = PathBuf::from("/tmp/docs")), vec!["target of job docs changed"]);
+        assert_eq!(violations(|c| c.jobs[0].replicas.clear()), vec!["replicas of job docs changed"]);
+        assert_eq!(violations(|c| c.jobs[0].retention_count = None), vec!["retention_count of job docs changed"]);
+        assert_eq!(violations(|c| c.jobs[0].retention_max_age_days = Some(7)),
+            vec!["retention_max_age_days of job docs changed"]);
+        assert_eq!(violations(|c| c.jobs[0].retention_max_total_gb = Some(50)),
+            vec!["retention_max_total_gb of job docs changed"]);
+
+        assert!(policy.startup_violations(None, &config()).is_empty(), "Nothing recorded yet");
+        let mut edited = config();
+        edited.retention_count = 5;
+        assert!(Policy::default().startup_violations(Some(&recorded), &edited).is_empty(), "Settings not locked");
+    }
+}
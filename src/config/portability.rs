@@ -0,0 +1,350 @@
+//! Checks for a config that is moved to another platform, e.g. from a Windows PC to a
+//! Linux NAS. Paths are inspected as text, since a `C:\` path is only a drive path when
+//! read on Windows.
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::models::{BackupJob, JobKind, Schedule, ServiceConfig};
+use crate::storage::TargetUrl;
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul",
+    "com0", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt0", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Characters Windows rejects in file names
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Extensions of programs and scripts that only run on Windows
+const WINDOWS_PROGRAMS: &[&str] = &[".exe", ".bat", ".cmd", ".ps1"];
+
+/// Platform a config is checked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetOs {
+    Windows,
+    Linux,
+    FreeBsd,
+    MacOs,
+}
+
+impl TargetOs {
+    /// Platform this binary was built for
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else if cfg!(target_os = "freebsd") {
+            Self::FreeBsd
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+
+    fn is_windows(self) -> bool {
+        self == Self::Windows
+    }
+}
+
+impl FromStr for TargetOs {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "windows" => Ok(Self::Windows),
+            "linux" => Ok(Self::Linux),
+            "freebsd" => Ok(Self::FreeBsd),
+            "macos" => Ok(Self::MacOs),
+            other => bail!("Unknown target OS '{}' (expected windows, linux, freebsd or macos)", other),
+        }
+    }
+}
+
+impl fmt::Display for TargetOs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Windows => "Windows",
+            Self::Linux => "Linux",
+            Self::FreeBsd => "FreeBSD",
+            Self::MacOs => "macOS",
+        })
+    }
+}
+
+/// A setting that will not work, or not work the same, on the target platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortabilityIssue {
+    /// Where the setting is, e.g. `jobs[docs].target`
+    pub location: String,
+    pub message: String,
+}
+
+impl PortabilityIssue {
+    fn new(location: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { location: location.into(), message: message.into() }
+    }
+}
+
+impl fmt::Display for PortabilityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Settings of `config` that would break or behave differently on `target`
+pub fn check_portability(config: &ServiceConfig, target: TargetOs) -> Vec<PortabilityIssue> {
+    let mut issues = Vec::new();
+
+    check_path(&mut issues, "state_path", &config.state_path, target);
+    if let Some(log_directory) = &config.log_directory {
+        check_path(&mut issues, "log_directory", log_directory, target);
+    }
+    if let Some(adhoc_target) = &config.adhoc_target {
+        check_path(&mut issues, "adhoc_target", adhoc_target, target);
+    }
+    if let Some(endpoint) = &config.control_endpoint {
+        check_control_endpoint(&mut issues, endpoint, target);
+    }
+
+    for job in &config.jobs {
+        check_job(&mut issues, job, target);
+    }
+
+    // Only the time zone of the machine decides when windows open
+    if !target.is_windows() && !config.throttle.is_empty() {
+        issues.push(PortabilityIssue::new(
+            "throttle",
+            format!("windows use the local time of the {} machine, which is often UTC on servers and containers", target),
+        ));
+    }
+
+    issues
+}
+
+fn check_job(issues: &mut Vec<PortabilityIssue>, job: &BackupJob, target: TargetOs) {
+    let at = |field: &str| format!("jobs[{}].{}", job.id, field);
+
+    if !target.is_windows() {
+        match job.kind {
+            JobKind::SystemState => issues.push(PortabilityIssue::new(at("kind"), "system-state jobs back up the Windows registry and only run on Windows")),
+            JobKind::WslDistro => issues.push(PortabilityIssue::new(at("kind"), "wsl-distro jobs need WSL and only run on Windows")),
+            _ => {}
+        }
+        if job.shadow_copy {
+            issues.push(PortabilityIssue::new(at("shadow_copy"), "shadow copies are a Windows feature; locked files are skipped instead"));
+        }
+    }
+
+    // Sources of exports are not file paths
+    if job.kind == JobKind::Files {
+        check_path(issues, &at("source"), &job.source, target);
+    }
+    check_path(issues, &at("target"), &job.target, target);
+    for (i, replica) in job.replicas.iter().enumerate() {
+        if TargetUrl::from_path(replica).is_none() {
+            check_path(issues, &at(&format!("replicas[{}]", i)), replica, target);
+        }
+    }
+    if let Some(staging) = job.dump.as_ref().and_then(|dump| dump.staging.as_ref()) {
+        check_path(issues, &at("dump.staging"), staging, target);
+    }
+
+    if !target.is_windows() {
+        let commands = [
+            ("dump.command", job.dump.as_ref().map(|dump| dump.command.as_str())),
+            ("hooks.pre", job.hooks.pre.as_deref()),
+            ("hooks.post", job.hooks.post.as_deref()),
+            ("hooks.on_success_command", job.hooks.on_success_command.as_deref()),
+            ("hooks.on_failure_command", job.hooks.on_failure_command.as_deref()),
+        ];
+        for (field, command) in commands {
+            if let Some(command) = command
+                && runs_windows_program(command)
+            {
+                issues.push(PortabilityIssue::new(at(field), format!("'{}' runs a Windows program", command)));
+            }
+        }
+
+        for process in job.busy_processes.iter().filter(|p| p.to_ascii_lowercase().ends_with(".exe")) {
+            issues.push(PortabilityIssue::new(
+                at("busy_processes"),
+                format!("process names have no .exe extension on {} ('{}')", target, process),
+            ));
+        }
+
+        if let Some(time) = wall_clock_time(&job.schedule) {
+            issues.push(PortabilityIssue::new(
+                at("schedule"),
+                format!(
+                    "{} is local time of the {} machine, which is often UTC on servers and containers (check TZ or timedatectl)",
+                    time, target
+                ),
+            ));
+        }
+    }
+}
+
+fn check_path(issues: &mut Vec<PortabilityIssue>, location: &str, path: &Path, target: TargetOs) {
+    let text = path.to_string_lossy();
+    if text.is_empty() {
+        return;
+    }
+
+    if target.is_windows() {
+        if text.starts_with('/') {
+            issues.push(PortabilityIssue::new(location, format!("'{}' has no drive letter, Windows resolves it on the current drive", text)));
+        }
+
+        // The drive letter's colon is the only one allowed
+        let names = text.get(drive_prefix_len(&text)..).unwrap_or_default();
+        for name in names.split(['/', '\\']).filter(|name| !name.is_empty()) {
+            if let Some(problem) = windows_name_problem(name) {
+                issues.push(PortabilityIssue::new(location, format!("'{}' {}", name, problem)));
+            }
+        }
+        return;
+    }
+
+    if text.starts_with(r"\\") {
+        issues.push(PortabilityIssue::new(
+            location,
+            format!("'{}' is a Windows network path; mount the share and use its mount point", text),
+        ));
+    } else if drive_prefix_len(&text) > 0 {
+        issues.push(PortabilityIssue::new(
+            location,
+            format!("'{}' uses a drive letter, which {} does not have; use a path such as /mnt/backups", text, target),
+        ));
+    } else if text.contains('\\') {
+        issues.push(PortabilityIssue::new(
+            location,
+            format!("'{}' uses backslashes, which are part of file names on {}", text, target),
+        ));
+    }
+}
+
+fn check_control_endpoint(issues: &mut Vec<PortabilityIssue>, endpoint: &str, target: TargetOs) {
+    let is_pipe = endpoint.to_ascii_lowercase().starts_with(r"\\.\pipe\");
+
+    if target.is_windows() && !is_pipe {
+        issues.push(PortabilityIssue::new("control_endpoint", format!("'{}' is not a named pipe (\\\\.\\pipe\\NAME)", endpoint)));
+    } else if !target.is_windows() && is_pipe {
+        issues.push(PortabilityIssue::new("control_endpoint", format!("'{}' is a named pipe; use a Unix socket path on {}", endpoint, target)));
+    }
+}
+
+/// Length of a leading `C:`, if any
+fn drive_prefix_len(path: &str) -> usize {
+    let bytes = path.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' { 2 } else { 0 }
+}
+
+/// Why Windows cannot hold a file or folder named `name`
+fn windows_name_problem(name: &str) -> Option<&'static str> {
+    if name.contains(INVALID_CHARS) || name.chars().any(char::is_control) {
+        return Some("contains characters Windows does not allow in names");
+    }
+    if name != "." && name != ".." && name.ends_with(['.', ' ']) {
+        return Some("ends with a dot or space, which Windows drops");
+    }
+
+    let base = name.split('.').next().unwrap_or(name).trim_end().to_ascii_lowercase();
+    RESERVED_NAMES.contains(&base.as_str()).then_some("is a device name Windows reserves")
+}
+
+fn runs_windows_program(command: &str) -> bool {
+    let program = command.split_whitespace().next().unwrap_or_default().trim_matches('"').to_ascii_lowercase();
+    let program = program.rsplit(['/', '\\']).next().unwrap_or_default();
+
+    WINDOWS_PROGRAMS.iter().any(|extension| program.ends_with(extension))
+        || matches!(program, "cmd" | "powershell" | "robocopy" | "sqlcmd")
+}
+
+/// Time of day a schedule fires at, for schedules tied to the wall clock
+fn wall_clock_time(schedule: &Schedule) -> Option<String> {
+    match schedule {
+        Schedule::Interval { .. } => None,
+        Schedule::Daily { hour, minute } | Schedule::Weekly { hour, minute, .. } => Some(format!("{:02}:{:02}", hour, minute)),
+        Schedule::Cron { expression } => Some(format!("cron \"{}\"", expression)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: &str) -> ServiceConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn locations(issues: &[PortabilityIssue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.location.as_str()).collect()
+    }
+
+    #[test]
+    fn test_windows_config_checked_for_linux() {
+        let config = config(r#"{
+            "jobs": [{
+                "id": "docs",
+                "source": "C:\\Users\\Me\\Documents",
+                "target": "\\\\nas\\backups",
+                "replicas": ["webdav://cloud.example.com/backups", "backups\\copy"],
+                "schedule": { "type": "daily", "hour": 2, "minute": 30 },
+                "hooks": { "pre": "C:\\Tools\\stop.bat --quiet" },
+                "busy_processes": ["OUTLOOK.EXE"]
+            }, {
+                "id": "registry",
+                "kind": "system-state",
+                "source": "C:\\",
+                "target": "/mnt/backups/registry",
+                "schedule": { "type": "interval", "seconds": 3600 }
+            }],
+            "control_endpoint": "\\\\.\\pipe\\keephive"
+        }"#);
+
+        let issues = check_portability(&config, TargetOs::Linux);
+        assert_eq!(locations(&issues), [
+            "control_endpoint",
+            "jobs[docs].source",
+            "jobs[docs].target",
+            "jobs[docs].replicas[1]",
+            "jobs[docs].hooks.pre",
+            "jobs[docs].busy_processes",
+            "jobs[docs].schedule",
+            "jobs[registry].kind",
+        ]);
+        assert!(issues[6].message.contains("02:30"));
+        assert!(check_portability(&config, TargetOs::Windows).iter().all(|issue| issue.location != "jobs[docs].source"));
+    }
+
+    #[test]
+    fn test_linux_config_checked_for_windows() {
+        let config = config(r#"{
+            "jobs": [{
+                "id": "home",
+                "source": "/home/me/notes: draft",
+                "target": "D:\\Backups\\aux.d\\home",
+                "schedule": { "type": "daily", "hour": 2, "minute": 0 }
+            }]
+        }"#);
+
+        let issues = check_portability(&config, TargetOs::Windows);
+        assert_eq!(locations(&issues), ["jobs[home].source", "jobs[home].source", "jobs[home].target"]);
+        assert!(issues[0].message.contains("no drive letter"));
+        assert!(issues[1].message.contains("'notes: draft'"));
+        assert!(issues[2].message.contains("reserves"));
+        assert!(check_portability(&config, TargetOs::FreeBsd).iter().all(|issue| issue.location == "jobs[home].target" || issue.location == "jobs[home].schedule"));
+    }
+
+    #[test]
+    fn test_parses_target_os() {
+        assert_eq!("Linux".parse::<TargetOs>().unwrap(), TargetOs::Linux);
+        assert_eq!("freebsd".parse::<TargetOs>().unwrap(), TargetOs::FreeBsd);
+        assert!("beos".parse::<TargetOs>().is_err());
+    }
+}
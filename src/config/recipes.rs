@@ -0,0 +1,147 @@
+//! Application recipes: jobs with an `app` get the paths, exclusions, lock files
+//! and hooks that application needs, so they can be backed up safely while in use.
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+use super::models::{AppRecipe, BackupJob, ServiceConfig};
+
+/// What a recipe adds to a job
+struct Recipe {
+    name: &'static str,
+
+    /// Source used when the job sets none
+    default_source: fn() -> Option<PathBuf>,
+    exclude: &'static [&'static str],
+    lock_files: &'static [&'static str],
+    pre: Option<&'static str>,
+    post: Option<&'static str>,
+}
+
+/// KeePass saves through a temporary file, so the database can be copied while open
+const KEEPASS: Recipe = Recipe {
+    name: "keepass",
+    default_source: || None,
+    exclude: &["*.tmp", "*.lock", ".~lock.*"],
+    lock_files: &[],
+    pre: None,
+    post: None,
+};
+
+/// Thunderbird rewrites mail folders while running and holds a lock in each profile
+const THUNDERBIRD: Recipe = Recipe {
+    name: "thunderbird",
+    default_source: thunderbird_profiles,
+    exclude: &["cache2", "startupCache", "Crash Reports", "minidumps", "parent.lock", ".parentlock", "lock"],
+    lock_files: if cfg!(windows) { &["Profiles/*/parent.lock"] } else { &["*/lock"] },
+    pre: None,
+    post: None,
+};
+
+/// The server keeps writing chunks unless saving is paused; `mcrcon` reads the RCON
+/// password from `MCRCON_PASS` (and host and port from `MCRCON_HOST` / `MCRCON_PORT`)
+const MINECRAFT_SERVER: Recipe = Recipe {
+    name: "minecraft-server",
+    default_source: || None,
+    exclude: &["logs", "crash-reports", "session.lock"],
+    lock_files: &[],
+    pre: Some("mcrcon \"save-off\" \"save-all flush\""),
+    post: Some("mcrcon \"save-on\""),
+};
+
+fn recipe(app: AppRecipe) -> &'static Recipe {
+    match app {
+        AppRecipe::Keepass => &KEEPASS,
+        AppRecipe::Thunderbird => &THUNDERBIRD,
+        AppRecipe::MinecraftServer => &MINECRAFT_SERVER,
+    }
+}
+
+/// Expand the recipes of all jobs that name an `app`
+pub fn expand_recipes(config: &mut ServiceConfig) -> Result<()> {
+    for job in &mut config.jobs {
+        expand_recipe(job)?;
+    }
+    Ok(())
+}
+
+/// Fill in what the job's recipe provides. Settings in the job win: a source or hook
+/// given there is kept, exclusions and lock files are added to its own.
+pub fn expand_recipe(job: &mut BackupJob) -> Result<()> {
+    let Some(app) = job.app else {
+        return Ok(());
+    };
+    let recipe = recipe(app);
+
+    if job.source.as_os_str().is_empty() {
+        match (recipe.default_source)() {
+            Some(source) => job.source = source,
+            None => bail!("Job '{}' needs a source for its {} recipe", job.id, recipe.name),
+        }
+    }
+
+    for pattern in recipe.exclude {
+        if !job.exclude.iter().any(|p| p == pattern) {
+            job.exclude.push(pattern.to_string());
+        }
+    }
+
+    for pattern in recipe.lock_files {
+        if !job.lock_files.iter().any(|p| p == pattern) {
+            job.lock_files.push(pattern.to_string());
+        }
+    }
+
+    if job.hooks.pre.is_none() && job.hooks.post.is_none() {
+        job.hooks.pre = recipe.pre.map(str::to_string);
+        job.hooks.post = recipe.post.map(str::to_string);
+    }
+
+    Ok(())
+}
+
+/// `%APPDATA%\Thunderbird` on Windows, `~/.thunderbird` elsewhere
+fn thunderbird_profiles() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("Thunderbird"))
+    } else {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".thunderbird"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(json: &str) -> BackupJob {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_recipes_expand_into_normal_settings() {
+        let mut minecraft = job(r#"{
+            "id": "mc", "app": "minecraft-server", "source": "/srv/minecraft", "target": "/backups",
+            "exclude": ["logs", "dynmap"],
+            "schedule": { "type": "interval", "seconds": 3600 }
+        }"#);
+
+        expand_recipe(&mut minecraft).unwrap();
+        expand_recipe(&mut minecraft).unwrap();
+
+        assert_eq!(minecraft.source, PathBuf::from("/srv/minecraft"));
+        assert_eq!(minecraft.exclude, vec!["logs", "dynmap", "crash-reports", "session.lock"]);
+        assert_eq!(minecraft.hooks.pre.as_deref(), Some("mcrcon \"save-off\" \"save-all flush\""));
+        assert_eq!(minecraft.hooks.post.as_deref(), Some("mcrcon \"save-on\""));
+    }
+
+    #[test]
+    fn test_recipe_without_default_source_needs_one() {
+        let mut keepass = job(r#"{
+            "id": "vault", "app": "keepass", "target": "/backups",
+            "schedule": { "type": "interval", "seconds": 3600 }
+        }"#);
+
+        let error = expand_recipe(&mut keepass).unwrap_err();
+        assert!(error.to_string().contains("needs a source"));
+    }
+}
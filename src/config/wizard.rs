@@ -0,0 +1,159 @@
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use super::models::Schedule;
+
+/// User folders offered by `--wizard`, with the schedule proposed for each
+const USER_FOLDERS: &[(&str, Schedule)] = &[
+    ("Documents", Schedule::Daily { hour: 12, minute: 0 }),
+    ("Desktop", Schedule::Daily { hour: 12, minute: 30 }),
+    ("Pictures", Schedule::Weekly { day: 7, hour: 13, minute: 0 }),
+];
+
+/// Folder under the chosen drive that receives all backups
+pub const WIZARD_TARGET_FOLDER: &str = "KeepHive";
+
+/// A user folder found on this machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderSuggestion {
+    pub name: String,
+    pub path: PathBuf,
+    pub schedule: Schedule,
+}
+
+/// A drive backups could be written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriveSuggestion {
+    pub root: PathBuf,
+    pub label: String,
+    pub free_bytes: u64,
+    pub removable: bool,
+}
+
+/// Common folders of the current user that exist
+pub fn detect_user_folders() -> Vec<FolderSuggestion> {
+    // Explorer knows where each folder is, including folders OneDrive redirects
+    #[cfg(windows)]
+    {
+        use crate::platform::windows::known_folders::known_folder;
+
+        USER_FOLDERS.iter()
+            .filter_map(|(name, schedule)| {
+                let path = known_folder(name).filter(|p| p.is_dir())?;
+                Some(FolderSuggestion { name: name.to_string(), path, schedule: schedule.clone() })
+            })
+            .collect()
+    }
+
+    #[cfg(not(windows))]
+    {
+        let profile = std::env::var_os("HOME").map(PathBuf::from);
+
+        match profile {
+            Some(profile) => user_folders_in(&profile),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Known folders directly in a profile
+#[cfg(any(not(windows), test))]
+fn user_folders_in(profile: &Path) -> Vec<FolderSuggestion> {
+    USER_FOLDERS.iter()
+        .filter_map(|(name, schedule)| {
+            let path = profile.join(name);
+            path.is_dir().then(|| FolderSuggestion { name: name.to_string(), path, schedule: schedule.clone() })
+        })
+        .collect()
+}
+
+/// Local and removable drives with their free space, most free space first
+pub fn detect_target_drives() -> Vec<DriveSuggestion> {
+    #[cfg(windows)]
+    {
+        use crate::platform::windows::file_ops::get_disk_free_space;
+        use crate::platform::windows::volume::local_drives;
+
+        let mut drives: Vec<DriveSuggestion> = local_drives().into_iter()
+            .filter_map(|(root, label, removable)| {
+                let free_bytes = get_disk_free_space(&root).ok()?;
+                Some(DriveSuggestion { root, label, free_bytes, removable })
+            })
+            .collect();
+
+        drives.sort_by_key(|d| std::cmp::Reverse(d.free_bytes));
+        drives
+    }
+
+    // Elsewhere the wizard asks for a target path
+    #[cfg(not(windows))]
+    {
+        Vec::new()
+    }
+}
+
+/// Whether `drive` holds any of the folders, so a disk failure would take out both copies
+pub fn shares_disk(drive: &DriveSuggestion, folders: &[FolderSuggestion]) -> bool {
+    folders.iter().any(|f| f.path.starts_with(&drive.root))
+}
+
+/// A ready-to-run config backing up `folders` below `target_root`.
+///
+/// State and logs go to `data_dir`, usually the directory of the config file, so
+/// the service finds them regardless of its working directory.
+pub fn build_config(folders: &[FolderSuggestion], target_root: &Path, retention_count: usize, data_dir: &Path) -> Value {
+    let jobs: Vec<Value> = folders.iter()
+        .map(|folder| json!({
+            "id": folder.name.to_lowercase(),
+            "source": folder.path,
+            "target": target_root.join(&folder.name),
+            "schedule": folder.schedule,
+            "description": format!("{} backup created by the setup wizard", folder.name),
+        }))
+        .collect();
+
+    json!({
+        "jobs": jobs,
+        "retention_count": retention_count,
+        "log_level": "info",
+        "state_path": data_dir.join("keephive_state.json"),
+        "log_directory": data_dir.join("logs"),
+        "log_rotation": { "type": "daily" },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detects_existing_folders() {
+        let profile = tempdir().unwrap();
+        std::fs::create_dir_all(profile.path().join("Documents")).unwrap();
+        std::fs::create_dir_all(profile.path().join("Desktop")).unwrap();
+
+        let folders = user_folders_in(profile.path());
+        assert_eq!(folders.len(), 2, "Pictures does not exist");
+        assert_eq!(folders[0].path, profile.path().join("Documents"));
+        assert_eq!(folders[1].path, profile.path().join("Desktop"));
+    }
+
+    #[test]
+    fn test_built_config_loads() {
+        let folders = vec![FolderSuggestion {
+            name: "Documents".to_string(),
+            path: PathBuf::from("/home/me/Documents"),
+            schedule: Schedule::Daily { hour: 12, minute: 0 },
+        }];
+
+        let value = build_config(&folders, Path::new("/mnt/usb/KeepHive"), 7, Path::new("/etc/keephive"));
+        let config: ServiceConfig = serde_json::from_value(value).unwrap();
+
+        assert_eq!(config.jobs[0].id, "documents");
+        assert_eq!(config.jobs[0].target, PathBuf::from("/mnt/usb/KeepHive/Documents"));
+        assert_eq!(config.retention_count, 7);
+        assert_eq!(config.state_path, PathBuf::from("/etc/keephive/keephive_state.json"));
+    }
+}
@@ -0,0 +1,289 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+use crate::config::BackupJob;
+use crate::core::manifest::{BackupManifest, ManifestDiff, ManifestEntry};
+
+/// Size of a directory including everything below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectorySize {
+    pub path: String,
+    pub size: u64,
+    pub files: u64,
+}
+
+/// Size change of a single file between two backups (0 = absent)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeDelta {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+impl SizeDelta {
+    pub fn delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+}
+
+/// Largest files in a backup, biggest first
+pub fn largest_files(manifest: &BackupManifest, top: usize) -> Vec<ManifestEntry> {
+    let mut files = manifest.files.clone();
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(top);
+    files
+}
+
+/// Largest directories in a backup by cumulative size, biggest first
+pub fn largest_directories(manifest: &BackupManifest, top: usize) -> Vec<DirectorySize> {
+    let mut totals: HashMap<&str, (u64, u64)> = HashMap::new();
+
+    for file in &manifest.files {
+        // Credit the file to every ancestor directory
+        for (i, _) in file.path.match_indices('/') {
+            let total = totals.entry(&file.path[..i]).or_default();
+            total.0 += file.size;
+            total.1 += 1;
+        }
+    }
+
+    let mut dirs: Vec<DirectorySize> = totals.into_iter()
+        .map(|(path, (size, files))| DirectorySize { path: path.to_string(), size, files })
+        .collect();
+
+    dirs.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    dirs.truncate(top);
+    dirs
+}
+
+/// Files whose size changed the most between two backups, by absolute change
+pub fn largest_deltas(diff: &ManifestDiff, top: usize) -> Vec<SizeDelta> {
+    let added = diff.added.iter()
+        .map(|f| SizeDelta { path: f.path.clone(), old_size: 0, new_size: f.size });
+
+    let removed = diff.removed.iter()
+        .map(|f| SizeDelta { path: f.path.clone(), old_size: f.size, new_size: 0 });
+
+    let modified = diff.modified.iter()
+        .map(|(old, new)| SizeDelta { path: new.path.clone(), old_size: old.size, new_size: new.size });
+
+    let mut deltas: Vec<SizeDelta> = added.chain(removed).chain(modified)
+        .filter(|d| d.delta() != 0)
+        .collect();
+
+    deltas.sort_by(|a, b| {
+        b.delta().unsigned_abs().cmp(&a.delta().unsigned_abs())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    deltas.truncate(top);
+    deltas
+}
+
+/// A complete backup as retention sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetainedBackup {
+    pub name: String,
+    pub created: DateTime<Utc>,
+    /// Space its removal frees when the backups before it are already gone (files
+    /// hard-linked into a newer backup stay)
+    pub freed_bytes: u64,
+    /// Bytes no older backup holds, i.e. what this run added to the target
+    pub new_bytes: u64,
+}
+
+/// A job's retention settings; absent rules do not remove anything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionRules {
+    pub count: Option<usize>,
+    pub max_age: Option<Duration>,
+    pub max_total_bytes: Option<u64>,
+}
+
+impl RetentionRules {
+    /// Rules of `job`, keeping `default_count` backups when it sets no count of its own
+    pub fn for_job(job: &BackupJob, default_count: usize) -> Self {
+        Self {
+            count: Some(job.retention_count.unwrap_or(default_count)),
+            max_age: job.retention_max_age_days.map(|days| Duration::days(days.into())),
+            max_total_bytes: job.retention_max_total_gb.map(|gb| gb.saturating_mul(1024 * 1024 * 1024)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    Count,
+    Age,
+    Size,
+}
+
+/// A backup the projection expects retention to remove
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectedRemoval {
+    pub name: String,
+    /// Future run that removes it, 1 being the next
+    pub run: usize,
+    /// When that run is expected, if the interval between runs is known
+    pub at: Option<DateTime<Utc>>,
+    pub reason: RemovalReason,
+}
+
+/// How retention is expected to play out over the coming runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionProjection {
+    /// Average time between the existing backups
+    pub interval: Option<Duration>,
+    /// Average bytes a run adds, from the existing backups after the first
+    pub added_per_run: u64,
+    /// Space the backups take now, hard links counted once
+    pub total_bytes: u64,
+    /// Space they take after the last projected run
+    pub projected_bytes: u64,
+    pub removals: Vec<ProjectedRemoval>,
+}
+
+/// Project the next `runs` runs of a job: each adds a backup growing the target like the
+/// recent ones did, then count, age and size rules are applied in the order the executor
+/// uses, never removing the newest backup. `backups` are oldest first.
+pub fn project_retention(backups: &[RetainedBackup], rules: &RetentionRules, runs: usize, now: DateTime<Utc>) -> RetentionProjection {
+    let interval = match (backups.first(), backups.last()) {
+        (Some(first), Some(last)) if backups.len() > 1 => Some((last.created - first.created) / (backups.len() as i32 - 1)),
+        _ => None,
+    };
+    let added_per_run = match backups.len() {
+        0 => 0,
+        1 => backups[0].new_bytes,
+        n => backups[1..].iter().map(|b| b.new_bytes).sum::<u64>() / (n as u64 - 1),
+    };
+    let total_bytes = backups.iter().map(|b| b.freed_bytes).sum();
+
+    let mut kept: Vec<(String, DateTime<Utc>, u64)> = backups.iter()
+        .map(|b| (b.name.clone(), b.created, b.freed_bytes))
+        .collect();
+    let mut removals = Vec::new();
+
+    for run in 1..=runs {
+        // A run overdue since the last backup happens now
+        let at = interval.map(|interval| {
+            let first = backups.last().map_or(now, |b| b.created + interval).max(now);
+            first + interval * (run as i32 - 1)
+        });
+        kept.push((format!("(run {})", run), at.unwrap_or(now), added_per_run));
+
+        let mut remove = |kept: &mut Vec<(String, DateTime<Utc>, u64)>, reason| {
+            let (name, _, _) = kept.remove(0);
+            removals.push(ProjectedRemoval { name, run, at, reason });
+        };
+
+        if let Some(count) = rules.count {
+            while kept.len() > count.max(1) {
+                remove(&mut kept, RemovalReason::Count);
+            }
+        }
+        if let Some(max_age) = rules.max_age {
+            let cutoff = at.unwrap_or(now) - max_age;
+            while kept.len() > 1 && kept[0].1 < cutoff {
+                remove(&mut kept, RemovalReason::Age);
+            }
+        }
+        if let Some(max_bytes) = rules.max_total_bytes {
+            while kept.len() > 1 && kept.iter().map(|b| b.2).sum::<u64>() > max_bytes {
+                remove(&mut kept, RemovalReason::Size);
+            }
+        }
+    }
+
+    RetentionProjection {
+        interval,
+        added_per_run,
+        total_bytes,
+        projected_bytes: kept.iter().map(|b| b.2).sum(),
+        removals,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(files: &[(&str, u64)]) -> BackupManifest {
+        BackupManifest::new("backup".into(), files.iter()
+            .map(|(path, size)| ManifestEntry { path: path.to_string(), size: *size, modified: None, crc32: None, original_path: None })
+            .collect())
+    }
+
+    fn retained(name: &str, day: i64, freed_bytes: u64, new_bytes: u64) -> RetainedBackup {
+        let created = DateTime::from_timestamp(day * 86400, 0).unwrap();
+        RetainedBackup { name: name.into(), created, freed_bytes, new_bytes }
+    }
+
+    #[test]
+    fn test_project_retention_applies_rules_in_order() {
+        let backups = [retained("a", 1, 100, 100), retained("b", 2, 10, 10), retained("c", 3, 110, 30)];
+        let now = DateTime::from_timestamp(3 * 86400, 0).unwrap();
+
+        let rules = RetentionRules { count: Some(3), ..RetentionRules::default() };
+        let projection = project_retention(&backups, &rules, 2, now);
+        assert_eq!(projection.interval, Some(Duration::days(1)));
+        assert_eq!(projection.added_per_run, 20);
+        assert_eq!(projection.total_bytes, 220);
+        assert_eq!(projection.removals.iter().map(|r| (r.name.as_str(), r.run)).collect::<Vec<_>>(), vec![("a", 1), ("b", 2)]);
+        assert_eq!(projection.removals[0].at, DateTime::from_timestamp(4 * 86400, 0));
+
+        // 220 + 20 over a 200 byte budget: removing "a" is enough
+        let rules = RetentionRules { max_total_bytes: Some(200), max_age: Some(Duration::days(10)), ..RetentionRules::default() };
+        let projection = project_retention(&backups, &rules, 1, now);
+        assert_eq!(projection.removals, vec![ProjectedRemoval {
+            name: "a".into(), run: 1, at: DateTime::from_timestamp(4 * 86400, 0), reason: RemovalReason::Size,
+        }]);
+        assert_eq!(projection.projected_bytes, 140);
+
+        // Age never takes the newest backup
+        let rules = RetentionRules { max_age: Some(Duration::hours(1)), ..RetentionRules::default() };
+        let projection = project_retention(&backups[..1], &rules, 1, now);
+        assert_eq!(projection.removals.len(), 1, "Only the old backup, not the new run");
+        assert_eq!(projection.removals[0].reason, RemovalReason::Age);
+    }
+
+    #[test]
+    fn test_largest_files() {
+        let m = manifest(&[("a.txt", 10), ("b/big.iso", 500), ("c.txt", 50)]);
+
+        let top = largest_files(&m, 2);
+
+        assert_eq!(top.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec!["b/big.iso", "c.txt"]);
+    }
+
+    #[test]
+    fn test_largest_directories_are_cumulative() {
+        let m = manifest(&[
+            ("root.txt", 1000),
+            ("docs/a.txt", 10),
+            ("docs/old/b.txt", 30),
+            ("media/v.mp4", 20),
+        ]);
+
+        let dirs = largest_directories(&m, 10);
+
+        assert_eq!(dirs[0], DirectorySize { path: "docs".into(), size: 40, files: 2 });
+        assert_eq!(dirs[1], DirectorySize { path: "docs/old".into(), size: 30, files: 1 });
+        assert_eq!(dirs[2], DirectorySize { path: "media".into(), size: 20, files: 1 });
+        assert_eq!(dirs.len(), 3, "Files at the root are not attributed to a directory");
+    }
+
+    #[test]
+    fn test_largest_deltas_rank_growth_and_shrinkage() {
+        let old = manifest(&[("log.txt", 100), ("gone.bin", 300), ("same.txt", 5)]);
+        let new = manifest(&[("log.txt", 1100), ("new.db", 50), ("same.txt", 5)]);
+        let diff = ManifestDiff::between(&old, &new);
+
+        let deltas = largest_deltas(&diff, 10);
+
+        assert_eq!(deltas.len(), 3);
+        assert_eq!(deltas[0].path, "log.txt");
+        assert_eq!(deltas[0].delta(), 1000);
+        assert_eq!(deltas[1].path, "gone.bin");
+        assert_eq!(deltas[1].delta(), -300);
+        assert_eq!(deltas[2].path, "new.db");
+    }
+}
@@ -0,0 +1,454 @@
+//! Backups written as a single zip or tar.zst archive instead of a folder. The source tree
+//! is streamed through an encoder, so nothing is staged on the target; tar.zst output is
+//! piped through the `zstd` command, which compresses on all cores.
+
+pub(crate) mod deflate;
+mod tar;
+mod zip;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::Child;
+use tracing::{debug, warn};
+
+use crate::config::{ArchiveConfig, ArchiveFormat};
+use crate::core::copy_engine::{CopyOptions, CopyProgress};
+use crate::core::copy_error::{CopyError, CopyFailure};
+use crate::core::exclude::is_backup_area;
+use crate::core::manifest::{LinkEntry, ManifestEntry, SkippedEntry};
+use crate::core::throttle::BandwidthLimiter;
+use crate::core::Crc32;
+use crate::observability::{format_bytes, record_bytes_copied, redacted};
+
+use self::tar::TarEncoder;
+use self::zip::ZipEncoder;
+
+/// Buffered output is handed to the file or compressor in chunks of this size
+const FLUSH_SIZE: usize = 1024 * 1024;
+
+/// Read size for source files
+const READ_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Compression level when the config leaves it out
+const DEFAULT_ZIP_LEVEL: u32 = 6;
+const DEFAULT_ZSTD_LEVEL: u32 = 3;
+
+/// An entry as stored in an archive
+pub(crate) struct EntryInfo {
+    /// Path inside the archive, always `/`-separated
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Unix mode including the file type bits; `None` on Windows
+    pub mode: Option<u32>,
+}
+
+impl EntryInfo {
+    fn new(name: String, metadata: &std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::MetadataExt;
+            Some(metadata.mode())
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+
+        Self {
+            name,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            mode,
+        }
+    }
+}
+
+/// How a file's entry ended
+pub(crate) struct FinishedFile {
+    /// Zero bytes added after the data, up to the size announced by `start_file`
+    pub filled: u64,
+
+    /// False if the file did not match the size announced by `start_file`
+    pub complete: bool,
+}
+
+/// Archive format, turning entries into bytes appended to `out`
+pub(crate) trait ArchiveEncoder: Send {
+    fn add_directory(&mut self, info: &EntryInfo, out: &mut Vec<u8>);
+
+    fn start_file(&mut self, info: &EntryInfo, out: &mut Vec<u8>) -> Result<()>;
+
+    /// Append data to the open file, returning how much of it the entry took
+    fn write_data(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<usize>;
+
+    fn finish_file(&mut self, out: &mut Vec<u8>) -> Result<FinishedFile>;
+
+    fn finish(&mut self, out: &mut Vec<u8>);
+}
+
+/// Whether a target entry is an archive backup rather than a folder
+pub fn is_archive_backup(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    [ArchiveFormat::Zip, ArchiveFormat::TarZst].iter()
+        .any(|format| name.ends_with(&format!(".{}", format.extension())))
+}
+
+/// Archive being written to the target
+pub struct ArchiveWriter {
+    encoder: Box<dyn ArchiveEncoder>,
+    sink: Box<dyn AsyncWrite + Send + Unpin>,
+    /// `zstd` process reading the tar stream, for tar.zst archives
+    compressor: Option<Child>,
+    buffer: Vec<u8>,
+    path: PathBuf,
+}
+
+impl ArchiveWriter {
+    /// Create the archive file at `path`, replacing an existing one
+    pub async fn create(path: &Path, config: &ArchiveConfig) -> Result<Self> {
+        let (encoder, sink, compressor): (Box<dyn ArchiveEncoder>, Box<dyn AsyncWrite + Send + Unpin>, _) = match config.format {
+            ArchiveFormat::Zip => {
+                let file = tokio::fs::File::create(path).await
+                    .map_err(CopyError::target_side)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                let level = config.level.unwrap_or(DEFAULT_ZIP_LEVEL);
+                (Box::new(ZipEncoder::new(level)), Box::new(tokio::io::BufWriter::new(file)), None)
+            }
+            ArchiveFormat::TarZst => {
+                let level = config.level.unwrap_or(DEFAULT_ZSTD_LEVEL);
+                let mut child = tokio::process::Command::new(&config.zstd_command)
+                    .args(["-q", "-f", "-T0", &format!("-{}", level), "-o"])
+                    .arg(path)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| match e.kind() {
+                        std::io::ErrorKind::NotFound => anyhow::anyhow!(
+                            "{} was not found; tar.zst archives need zstd on the PATH or archive.zstd_command",
+                            config.zstd_command
+                        ),
+                        _ => anyhow::Error::new(e).context(format!("Failed to start {}", config.zstd_command)),
+                    })?;
+                let stdin = child.stdin.take().context("zstd stdin unavailable")?;
+                (Box::new(TarEncoder::new()), Box::new(stdin), Some(child))
+            }
+        };
+
+        Ok(Self {
+            encoder,
+            sink,
+            compressor,
+            buffer: Vec::with_capacity(FLUSH_SIZE * 2),
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Add the files of `source` below the archive root, honoring the job's exclusions.
+    /// Links are recorded instead of followed, unreadable files are skipped, as in a folder copy.
+    pub async fn add_tree<F>(
+        &mut self,
+        source: &Path,
+        options: &CopyOptions,
+        limiter: &BandwidthLimiter,
+        mut progress_callback: F,
+    ) -> Result<CopyProgress>
+    where
+        F: FnMut(&CopyProgress) + Send,
+    {
+        let mut progress = CopyProgress::default();
+        let mut pending = vec![source.to_path_buf()];
+
+        while let Some(directory) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&directory).await
+                .context("Failed to read source directory")?;
+
+            let mut listing = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                listing.push(entry);
+            }
+            listing.sort_by_key(|entry| entry.file_name());
+
+            let mut subdirectories = Vec::new();
+
+            for entry in listing {
+                let source_path = entry.path();
+                let relative_path = source_path.strip_prefix(source)
+                    .context("Failed to calculate relative path")?;
+                let name = crate::core::manifest::normalize_relative_path(relative_path);
+
+                let metadata = match entry.metadata().await {
+                    Ok(m) => m,
+                    Err(e) => {
+                        warn!("Cannot access file metadata {}: {}", redacted(&source_path), e);
+                        let failure = CopyError::source_side(e).failure;
+                        progress.files_skipped += 1;
+                        progress.failures.record(failure);
+                        progress.skipped.push(SkippedEntry::new(relative_path, relative_path, None, failure));
+                        continue;
+                    }
+                };
+
+                if options.exclusions.excludes(relative_path, &metadata) {
+                    debug!("Excluded by profile: {}", redacted(&source_path));
+                    continue;
+                }
+
+                if metadata.is_dir() && is_backup_area(&source_path).await {
+                    warn!("Not backing up {}: it holds KeepHive backups", redacted(&source_path));
+                    progress.files_skipped += 1;
+                    progress.failures.record(CopyFailure::BackupArea);
+                    progress.skipped.push(SkippedEntry::new(relative_path, relative_path, None, CopyFailure::BackupArea));
+                    continue;
+                }
+
+                if metadata.is_symlink() {
+                    let target = tokio::fs::read_link(&source_path).await.ok();
+                    let directory = tokio::fs::metadata(&source_path).await.is_ok_and(|m| m.is_dir());
+                    debug!("Not following link: {} -> {:?}", redacted(&source_path), target);
+                    progress.links.push(LinkEntry::new(relative_path, target.as_deref(), directory));
+                    continue;
+                }
+
+                if metadata.is_dir() {
+                    self.encoder.add_directory(&EntryInfo::new(name, &metadata), &mut self.buffer);
+                    subdirectories.push(source_path);
+                } else if metadata.is_file() {
+                    progress.current_file = Some(source_path.clone());
+
+                    match self.add_file(&source_path, EntryInfo::new(name, &metadata), limiter).await? {
+                        Ok((bytes, crc32)) => {
+                            progress.bytes_copied += bytes;
+                            progress.files_copied += 1;
+                            progress.files.push(
+                                ManifestEntry::new(relative_path, bytes, metadata.modified().ok().map(DateTime::<Utc>::from))
+                                    .with_crc32(options.hash.then_some(crc32))
+                            );
+                        }
+                        Err(e) => {
+                            warn!("Skipping {}: {}", redacted(&source_path), e);
+                            progress.files_skipped += 1;
+                            progress.failures.record(e.failure);
+                            progress.skipped.push(SkippedEntry::new(relative_path, relative_path, Some(&metadata), e.failure));
+                        }
+                    }
+                    progress_callback(&progress);
+                }
+
+                self.flush_buffer(false).await?;
+            }
+
+            // Popped in listing order
+            pending.extend(subdirectories.into_iter().rev());
+        }
+
+        progress.current_file = None;
+        Ok(progress)
+    }
+
+    /// Stream one file into the archive, returning the size and CRC32 of what the archive
+    /// holds for it. Source errors are returned inside, to skip the file; target errors fail
+    /// the archive.
+    async fn add_file(
+        &mut self,
+        source_path: &Path,
+        info: EntryInfo,
+        limiter: &BandwidthLimiter,
+    ) -> Result<std::result::Result<(u64, u32), CopyError>> {
+        // Opened before the entry starts, so an unreadable file leaves no trace in the archive
+        let mut file = match tokio::fs::File::open(source_path).await {
+            Ok(file) => file,
+            Err(e) => return Ok(Err(CopyError::source_side(e))),
+        };
+
+        self.encoder.start_file(&info, &mut self.buffer)?;
+
+        let mut chunk = vec![0u8; READ_BUFFER_SIZE];
+        let mut crc = Crc32::new();
+        let mut bytes = 0u64;
+
+        let mut read_error = None;
+
+        loop {
+            let read = match file.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) => {
+                    read_error = Some(e);
+                    break;
+                }
+            };
+
+            if limiter.is_enabled() {
+                limiter.consume(read as u64).await;
+            }
+
+            let taken = self.encoder.write_data(&chunk[..read], &mut self.buffer)?;
+            crc.update(&chunk[..taken]);
+            bytes += taken as u64;
+            record_bytes_copied(read as u64);
+            self.flush_buffer(false).await?;
+        }
+
+        let finished = self.encoder.finish_file(&mut self.buffer)?;
+
+        // The entry has started, so a read error can only end it early; the cut-off
+        // entry stays in the archive, but the file is reported as skipped
+        if let Some(e) = read_error {
+            debug!("Read failed, {} is incomplete in the archive", redacted(source_path));
+            return Ok(Err(CopyError::source_side(e)));
+        }
+
+        if !finished.complete {
+            warn!("{} changed size while it was archived ({} of {})",
+                redacted(source_path), format_bytes(bytes), format_bytes(info.size));
+        }
+
+        chunk.fill(0);
+        let mut filled = finished.filled;
+        while filled > 0 {
+            let zeros = filled.min(chunk.len() as u64) as usize;
+            crc.update(&chunk[..zeros]);
+            filled -= zeros as u64;
+        }
+
+        Ok(Ok((bytes + finished.filled, crc.finish())))
+    }
+
+    /// Add a file held in memory, such as the manifest
+    pub async fn add_bytes(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let info = EntryInfo {
+            name: name.to_string(),
+            size: data.len() as u64,
+            modified: Some(SystemTime::now()),
+            mode: cfg!(unix).then_some(0o100644),
+        };
+
+        self.encoder.start_file(&info, &mut self.buffer)?;
+        self.encoder.write_data(data, &mut self.buffer)?;
+        self.encoder.finish_file(&mut self.buffer)?;
+        self.flush_buffer(false).await
+    }
+
+    /// Write the archive's trailer and wait until it is on disk
+    pub async fn finish(mut self, sync: bool) -> Result<()> {
+        self.encoder.finish(&mut self.buffer);
+        self.flush_buffer(true).await?;
+
+        match self.compressor.take() {
+            None => {
+                self.sink.flush().await.map_err(CopyError::target_side)
+                    .context("Failed to write archive")?;
+            }
+            Some(child) => {
+                self.sink.shutdown().await.map_err(CopyError::target_side)
+                    .context("Failed to write archive")?;
+                // zstd sees the end of its input once stdin is closed
+                drop(self.sink);
+                Self::wait_for_compressor(child).await?;
+            }
+        }
+
+        if sync {
+            let file = tokio::fs::OpenOptions::new().write(true).open(&self.path).await?;
+            file.sync_all().await.map_err(CopyError::target_side)
+                .context("Failed to sync archive")?;
+        }
+
+        Ok(())
+    }
+
+    /// Hand buffered output to the file or compressor, once enough has collected or on `force`
+    async fn flush_buffer(&mut self, force: bool) -> Result<()> {
+        if self.buffer.is_empty() || (!force && self.buffer.len() < FLUSH_SIZE) {
+            return Ok(());
+        }
+
+        if let Err(e) = self.sink.write_all(&self.buffer).await {
+            // A broken pipe only says zstd stopped; its own error says why
+            if let Some(child) = self.compressor.take() {
+                Self::wait_for_compressor(child).await?;
+            }
+            return Err(CopyError::target_side(e)).context("Failed to write archive");
+        }
+
+        self.buffer.clear();
+        Ok(())
+    }
+
+    async fn wait_for_compressor(child: Child) -> Result<()> {
+        let output = child.wait_with_output().await
+            .context("Failed to wait for zstd")?;
+
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            if message.contains("No space left") {
+                return Err(CopyError::target_side(std::io::Error::new(std::io::ErrorKind::StorageFull, message)))
+                    .context("zstd failed");
+            }
+            bail!("zstd failed: {}", message);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::MANIFEST_FILE;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_zip_archive_holds_tree_and_manifest() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        std::fs::create_dir_all(source.path().join("docs/empty")).unwrap();
+        std::fs::write(source.path().join("docs/a.txt"), "hello hello hello").unwrap();
+        std::fs::write(source.path().join("b.txt"), vec![7u8; 100_000]).unwrap();
+
+        let config: ArchiveConfig = serde_json::from_str(r#"{ "format": "zip" }"#).unwrap();
+        let path = target.path().join("backup.zip");
+        let mut writer = ArchiveWriter::create(&path, &config).await.unwrap();
+
+        let mut calls = 0;
+        let progress = writer.add_tree(source.path(), &CopyOptions::default(), &BandwidthLimiter::default(), |_| calls += 1)
+            .await.unwrap();
+        writer.add_bytes(MANIFEST_FILE, b"{}").await.unwrap();
+        writer.finish(false).await.unwrap();
+
+        assert_eq!(progress.files_copied, 2);
+        assert_eq!(progress.bytes_copied, 100_017);
+        assert_eq!(calls, 2);
+        assert!(is_archive_backup(&path));
+
+        let archive = std::fs::read(&path).unwrap();
+        assert_eq!(&archive[..4], b"PK\x03\x04");
+        for name in ["b.txt", "docs/", "docs/a.txt", "docs/empty/", MANIFEST_FILE] {
+            assert!(archive.windows(name.len()).any(|w| w == name.as_bytes()), "{} missing", name);
+        }
+        // Highly repetitive content is compressed
+        assert!(archive.len() < 10_000);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_failing_mid_read_is_skipped() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        let config: ArchiveConfig = serde_json::from_str(r#"{ "format": "zip" }"#).unwrap();
+        let mut writer = ArchiveWriter::create(&target.path().join("backup.zip"), &config).await.unwrap();
+
+        // A folder opens like a file on Unix, but reading it fails
+        let metadata = std::fs::metadata(source.path()).unwrap();
+        let info = EntryInfo::new("folder".to_string(), &metadata);
+        let result = writer.add_file(source.path(), info, &BandwidthLimiter::default()).await.unwrap();
+
+        assert!(result.is_err(), "A read error skips the file");
+        writer.finish(false).await.unwrap();
+    }
+}
@@ -0,0 +1,398 @@
+//! DEFLATE compression (RFC 1951) with the fixed Huffman code, for zip entries and the
+//! [`Deflate`](crate::core::Deflate) copy transform.
+//!
+//! Matches are found through hash chains over the last 32 KiB. A block that would not
+//! get smaller is stored instead, so already compressed files grow by a few bytes only.
+
+/// Distance limit of DEFLATE back references
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// Input compressed as one block
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Largest stored block
+const MAX_STORED: usize = 65535;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+const HASH_BITS: u32 = 15;
+const HASH_SIZE: usize = 1 << HASH_BITS;
+const NO_POSITION: u32 = u32::MAX;
+
+/// Smallest length of each length code 257-285, and its extra bits
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+/// Smallest distance of each distance code 0-29, and its extra bits
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+const END_OF_BLOCK: u16 = 256;
+
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+/// Streaming compressor producing a raw DEFLATE stream
+pub struct Deflater {
+    /// Up to `WINDOW_SIZE` bytes already compressed, then input waiting for a full block
+    window: Vec<u8>,
+    history: usize,
+    max_chain: usize,
+    bits: BitWriter,
+}
+
+impl Deflater {
+    /// `level` 1-9 trades speed for size by how many earlier positions are tried per match
+    pub fn new(level: u32) -> Self {
+        Self {
+            window: Vec::with_capacity(WINDOW_SIZE + BLOCK_SIZE),
+            history: 0,
+            max_chain: 1 << level.clamp(1, 9),
+            bits: BitWriter::default(),
+        }
+    }
+
+    /// Compress `data`, appending finished output to `out`
+    pub fn write(&mut self, mut data: &[u8], out: &mut Vec<u8>) {
+        while !data.is_empty() {
+            let room = self.history + BLOCK_SIZE - self.window.len();
+            let take = room.min(data.len());
+            self.window.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.window.len() == self.history + BLOCK_SIZE {
+                self.compress_block(out);
+            }
+        }
+    }
+
+    /// Compress what is left and end the stream
+    pub fn finish(mut self, out: &mut Vec<u8>) {
+        if self.window.len() > self.history {
+            self.compress_block(out);
+        }
+
+        // An empty final block: BFINAL, fixed Huffman, end of block
+        self.bits.put(out, 1, 1);
+        self.bits.put(out, 1, 2);
+        self.bits.put_code(out, END_OF_BLOCK);
+        self.bits.flush(out);
+    }
+
+    fn compress_block(&mut self, out: &mut Vec<u8>) {
+        let tokens = self.find_matches();
+        let input = &self.window[self.history..];
+
+        let fixed_bits: usize = 3 + tokens.iter().map(token_bits).sum::<usize>() + 7;
+        let stored_bits = input.len().div_ceil(MAX_STORED) * 40 + 8 + input.len() * 8;
+
+        if fixed_bits < stored_bits {
+            self.bits.put(out, 0, 1);
+            self.bits.put(out, 1, 2);
+            for token in &tokens {
+                self.bits.put_token(out, *token);
+            }
+            self.bits.put_code(out, END_OF_BLOCK);
+        } else {
+            for chunk in input.chunks(MAX_STORED) {
+                self.bits.put(out, 0, 1);
+                self.bits.put(out, 0, 2);
+                self.bits.flush(out);
+
+                let length = chunk.len() as u16;
+                out.extend_from_slice(&length.to_le_bytes());
+                out.extend_from_slice(&(!length).to_le_bytes());
+                out.extend_from_slice(chunk);
+            }
+        }
+
+        // Keep the last 32 KiB for back references from the next block
+        let keep = self.window.len().min(WINDOW_SIZE);
+        self.window.drain(..self.window.len() - keep);
+        self.history = keep;
+    }
+
+    /// Greedy LZ77 over the pending input, referring back into the history too
+    fn find_matches(&self) -> Vec<Token> {
+        let data = &self.window;
+        let mut head = vec![NO_POSITION; HASH_SIZE];
+        let mut prev = vec![NO_POSITION; data.len()];
+        let mut tokens = Vec::with_capacity(data.len() - self.history);
+
+        let insert = |pos: usize, head: &mut [u32], prev: &mut [u32]| {
+            if pos + MIN_MATCH <= data.len() {
+                let h = hash(&data[pos..]);
+                prev[pos] = head[h];
+                head[h] = pos as u32;
+            }
+        };
+
+        for pos in 0..self.history {
+            insert(pos, &mut head, &mut prev);
+        }
+
+        let mut pos = self.history;
+        while pos < data.len() {
+            let (length, distance) = self.longest_match(&head, &prev, pos);
+
+            if length >= MIN_MATCH {
+                tokens.push(Token::Match { length: length as u16, distance: distance as u16 });
+                for p in pos..pos + length {
+                    insert(p, &mut head, &mut prev);
+                }
+                pos += length;
+            } else {
+                tokens.push(Token::Literal(data[pos]));
+                insert(pos, &mut head, &mut prev);
+                pos += 1;
+            }
+        }
+
+        tokens
+    }
+
+    fn longest_match(&self, head: &[u32], prev: &[u32], pos: usize) -> (usize, usize) {
+        let data = &self.window;
+        if pos + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+
+        let limit = (data.len() - pos).min(MAX_MATCH);
+        let mut best = (0, 0);
+        let mut candidate = head[hash(&data[pos..])];
+        let mut chain = self.max_chain;
+
+        while candidate != NO_POSITION && chain > 0 {
+            let start = candidate as usize;
+            let distance = pos - start;
+            if distance > WINDOW_SIZE {
+                break;
+            }
+
+            let length = data[start..].iter()
+                .zip(&data[pos..pos + limit])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, distance);
+                if length == limit {
+                    break;
+                }
+            }
+
+            candidate = prev[start];
+            chain -= 1;
+        }
+
+        best
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Code index of a length or distance in a base table
+fn code_index(bases: &[u16], value: u16) -> usize {
+    bases.iter().rposition(|&base| base <= value).unwrap()
+}
+
+/// Fixed Huffman code of a literal/length symbol and its bit length
+fn fixed_code(symbol: u16) -> (u16, u32) {
+    match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + symbol - 280, 8),
+    }
+}
+
+fn token_bits(token: &Token) -> usize {
+    match *token {
+        Token::Literal(byte) => fixed_code(byte as u16).1 as usize,
+        Token::Match { length, distance } => {
+            let length_code = code_index(&LENGTH_BASE, length);
+            let distance_code = code_index(&DISTANCE_BASE, distance);
+            fixed_code(257 + length_code as u16).1 as usize
+                + LENGTH_EXTRA[length_code] as usize
+                + 5
+                + DISTANCE_EXTRA[distance_code] as usize
+        }
+    }
+}
+
+/// Bits packed least significant first, as DEFLATE stores them
+#[derive(Default)]
+struct BitWriter {
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, out: &mut Vec<u8>, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored most significant bit first
+    fn put_huffman(&mut self, out: &mut Vec<u8>, code: u16, bits: u32) {
+        let reversed = (code as u32).reverse_bits() >> (32 - bits);
+        self.put(out, reversed, bits);
+    }
+
+    fn put_code(&mut self, out: &mut Vec<u8>, symbol: u16) {
+        let (code, bits) = fixed_code(symbol);
+        self.put_huffman(out, code, bits);
+    }
+
+    fn put_token(&mut self, out: &mut Vec<u8>, token: Token) {
+        match token {
+            Token::Literal(byte) => self.put_code(out, byte as u16),
+            Token::Match { length, distance } => {
+                let length_code = code_index(&LENGTH_BASE, length);
+                self.put_code(out, 257 + length_code as u16);
+                self.put(out, (length - LENGTH_BASE[length_code]) as u32, LENGTH_EXTRA[length_code] as u32);
+
+                let distance_code = code_index(&DISTANCE_BASE, distance);
+                self.put_huffman(out, distance_code as u16, 5);
+                self.put(out, (distance - DISTANCE_BASE[distance_code]) as u32, DISTANCE_EXTRA[distance_code] as u32);
+            }
+        }
+    }
+
+    /// Pad to a byte boundary
+    fn flush(&mut self, out: &mut Vec<u8>) {
+        if self.count > 0 {
+            out.push(self.buffer as u8);
+        }
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+                value |= (bit as u32) << i;
+                self.position += 1;
+            }
+            value
+        }
+
+        /// Next fixed Huffman literal/length symbol, read most significant bit first
+        fn symbol(&mut self) -> u32 {
+            let mut code = 0u32;
+            for length in 1..=9 {
+                code = (code << 1) | self.bits(1);
+                match (length, code) {
+                    (7, 0..=0x17) => return code + 256,
+                    (8, 0x30..=0xBF) => return code - 0x30,
+                    (8, 0xC0..=0xC7) => return code - 0xC0 + 280,
+                    (9, 0x190..=0x1FF) => return code - 0x190 + 144,
+                    _ => {}
+                }
+            }
+            panic!("invalid fixed Huffman code {:#x}", code)
+        }
+    }
+
+    /// Decoder for the stored and fixed Huffman blocks `Deflater` writes
+    pub(crate) fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader { data, position: 0 };
+        let mut out: Vec<u8> = Vec::new();
+
+        loop {
+            let last = reader.bits(1) == 1;
+            match reader.bits(2) {
+                0 => {
+                    let padding = (8 - reader.position % 8) % 8;
+                    reader.bits(padding as u32);
+                    let length = reader.bits(16) as usize;
+                    assert_eq!(reader.bits(16) as usize, !length & 0xFFFF);
+                    for _ in 0..length {
+                        out.push(reader.bits(8) as u8);
+                    }
+                }
+                1 => loop {
+                    match reader.symbol() {
+                        symbol @ 0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        symbol => {
+                            let index = symbol as usize - 257;
+                            let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32) as usize;
+                            let code = (reader.bits(5).reverse_bits() >> 27) as usize;
+                            let distance = DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code] as u32) as usize;
+                            for _ in 0..length {
+                                out.push(out[out.len() - distance]);
+                            }
+                        }
+                    }
+                },
+                kind => panic!("unexpected block type {}", kind),
+            }
+            if last {
+                return out;
+            }
+        }
+    }
+
+    fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+        let mut deflater = Deflater::new(level);
+        let mut out = Vec::new();
+        // Uneven pieces cross block boundaries in different places
+        for piece in data.chunks(7919) {
+            deflater.write(piece, &mut out);
+        }
+        deflater.finish(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_round_trips_repetitive_and_random_data() {
+        let text: Vec<u8> = (0..200_000).flat_map(|i| format!("line {} of the backup log\n", i % 977).into_bytes()).collect();
+        let compressed = deflate(&text, 6);
+        assert!(compressed.len() < text.len() / 5, "{} of {} bytes", compressed.len(), text.len());
+        assert_eq!(inflate(&compressed), text);
+
+        // Incompressible input is stored, barely growing
+        let mut state = 0x1234_5678u32;
+        let noise: Vec<u8> = (0..150_000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect();
+        let compressed = deflate(&noise, 1);
+        assert!(compressed.len() < noise.len() + 64);
+        assert_eq!(inflate(&compressed), noise);
+
+        assert_eq!(inflate(&deflate(b"", 6)), b"");
+        assert_eq!(inflate(&deflate(&[7; MAX_MATCH * 3 + 1], 9)), vec![7; MAX_MATCH * 3 + 1]);
+    }
+}
@@ -0,0 +1,194 @@
+//! tar (POSIX pax) stream writing. Names longer than the ustar fields and sizes above
+//! 8 GiB go into pax extended headers.
+
+use anyhow::Result;
+use std::time::SystemTime;
+
+use super::{ArchiveEncoder, EntryInfo, FinishedFile};
+
+const BLOCK: usize = 512;
+
+/// Largest size the 11 octal digits of a ustar header hold
+const MAX_USTAR_SIZE: u64 = 0o777_7777_7777;
+
+const TYPE_FILE: u8 = b'0';
+const TYPE_DIRECTORY: u8 = b'5';
+const TYPE_PAX: u8 = b'x';
+
+#[derive(Default)]
+pub struct TarEncoder {
+    /// Size announced in the open entry's header, and how much of it was written
+    declared: u64,
+    written: u64,
+    /// Data past the announced size was cut off
+    truncated: bool,
+}
+
+impl TarEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn write_entry_header(&mut self, out: &mut Vec<u8>, name: &str, size: u64, info: &EntryInfo, kind: u8) {
+        let mut records = String::new();
+        if name.len() > 100 {
+            records.push_str(&pax_record("path", name));
+        }
+        if size > MAX_USTAR_SIZE {
+            records.push_str(&pax_record("size", &size.to_string()));
+        }
+
+        if !records.is_empty() {
+            let pax = EntryInfo { name: "././@PaxHeader".to_string(), size: 0, modified: info.modified, mode: Some(0o644) };
+            out.extend_from_slice(&header(&pax.name, records.len() as u64, &pax, TYPE_PAX));
+            out.extend_from_slice(records.as_bytes());
+            pad(out, records.len() as u64);
+        }
+
+        out.extend_from_slice(&header(name, size.min(MAX_USTAR_SIZE), info, kind));
+    }
+}
+
+impl ArchiveEncoder for TarEncoder {
+    fn add_directory(&mut self, info: &EntryInfo, out: &mut Vec<u8>) {
+        self.write_entry_header(out, &format!("{}/", info.name), 0, info, TYPE_DIRECTORY);
+    }
+
+    fn start_file(&mut self, info: &EntryInfo, out: &mut Vec<u8>) -> Result<()> {
+        self.write_entry_header(out, &info.name, info.size, info, TYPE_FILE);
+        self.declared = info.size;
+        self.written = 0;
+        self.truncated = false;
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+        // A file that grew while it was read is cut at the announced size
+        let room = (self.declared - self.written).min(data.len() as u64) as usize;
+        out.extend_from_slice(&data[..room]);
+        self.truncated |= room < data.len();
+        self.written += room as u64;
+        Ok(room)
+    }
+
+    fn finish_file(&mut self, out: &mut Vec<u8>) -> Result<FinishedFile> {
+        // A file that shrank is filled up with zeros
+        let filled = self.declared - self.written;
+        out.resize(out.len() + filled as usize, 0);
+        pad(out, self.declared);
+        Ok(FinishedFile { filled, complete: filled == 0 && !self.truncated })
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        out.resize(out.len() + 2 * BLOCK, 0);
+    }
+}
+
+/// ustar header block; `name` is cut to the 100 byte field when a pax header carries it
+fn header(name: &str, size: u64, info: &EntryInfo, kind: u8) -> [u8; BLOCK] {
+    let mut block = [0u8; BLOCK];
+    let name = name.as_bytes();
+    let cut = name.len().min(100);
+    block[..cut].copy_from_slice(&name[..cut]);
+
+    let default_mode = if kind == TYPE_DIRECTORY { 0o755 } else { 0o644 };
+    let mtime = info.modified
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs());
+
+    octal(&mut block[100..108], info.mode.map_or(default_mode, |mode| mode & 0o7777) as u64);
+    octal(&mut block[108..116], 0);
+    octal(&mut block[116..124], 0);
+    octal(&mut block[124..136], size);
+    octal(&mut block[136..148], mtime.min(MAX_USTAR_SIZE));
+    block[156] = kind;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+
+    // The checksum is taken with its own field filled with spaces
+    block[148..156].fill(b' ');
+    let checksum: u32 = block.iter().map(|&b| b as u32).sum();
+    block[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+
+    block
+}
+
+/// Zero-padded octal number filling a field but its final NUL
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{:0width$o}", value, width = width);
+    field[..width].copy_from_slice(digits.as_bytes());
+    field[width] = 0;
+}
+
+/// `"<length> <key>=<value>\n"`, where the length counts itself
+fn pax_record(key: &str, value: &str) -> String {
+    let body = key.len() + value.len() + 3;
+    let mut length = body + 1;
+    while length != body + length.to_string().len() {
+        length = body + length.to_string().len();
+    }
+    format!("{} {}={}\n", length, key, value)
+}
+
+/// Fill the last block of `size` bytes of data
+fn pad(out: &mut Vec<u8>, size: u64) {
+    let remainder = (size % BLOCK as u64) as usize;
+    if remainder != 0 {
+        out.resize(out.len() + BLOCK - remainder, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(block: &[u8], range: std::ops::Range<usize>) -> String {
+        String::from_utf8_lossy(&block[range]).trim_end_matches('\0').to_string()
+    }
+
+    #[test]
+    fn test_entries_with_long_names_and_changed_sizes() {
+        let mut tar = TarEncoder::new();
+        let mut out = Vec::new();
+
+        let long_name = format!("{}/file.txt", "folder".repeat(20));
+        let info = EntryInfo { name: long_name.clone(), size: 5, modified: None, mode: Some(0o100640) };
+        tar.start_file(&info, &mut out).unwrap();
+        assert_eq!(tar.write_data(b"hello world", &mut out).unwrap(), 5);
+        assert!(!tar.finish_file(&mut out).unwrap().complete, "Grew while read");
+        tar.finish(&mut out);
+
+        // pax header with the full name, then the ustar header, data and the end blocks
+        assert_eq!(out.len(), BLOCK * 6);
+        assert_eq!(out[156], TYPE_PAX);
+        assert!(String::from_utf8_lossy(&out[BLOCK..2 * BLOCK]).contains(&format!("path={}\n", long_name)));
+
+        let header = &out[2 * BLOCK..3 * BLOCK];
+        assert_eq!(header[156], TYPE_FILE);
+        assert_eq!(field(header, 100..108), "0000640");
+        assert_eq!(field(header, 124..136), "00000000005");
+        let checksum: u32 = header.iter().enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+            .sum();
+        assert_eq!(u32::from_str_radix(field(header, 148..154).as_str(), 8).unwrap(), checksum);
+        assert_eq!(&out[3 * BLOCK..3 * BLOCK + 5], b"hello");
+        assert!(out[4 * BLOCK..].iter().all(|&b| b == 0));
+
+        let mut out = Vec::new();
+        let shrunk = EntryInfo { name: "shrunk.txt".to_string(), size: 8, modified: None, mode: None };
+        tar.start_file(&shrunk, &mut out).unwrap();
+        assert_eq!(tar.write_data(b"abc", &mut out).unwrap(), 3);
+        let finished = tar.finish_file(&mut out).unwrap();
+        assert!(!finished.complete, "Shrank while read");
+        assert_eq!(finished.filled, 5);
+        assert_eq!(&out[BLOCK..BLOCK + 8], b"abc\0\0\0\0\0");
+    }
+
+    #[test]
+    fn test_pax_record_length_counts_itself() {
+        assert_eq!(pax_record("path", "a"), "9 path=a\n");
+        let record = pax_record("path", &"x".repeat(95));
+        assert_eq!(record.len().to_string(), record.split(' ').next().unwrap());
+    }
+}
@@ -0,0 +1,376 @@
+//! Zip archive writing. Entries are streamed with data descriptors, since their size and
+//! CRC are only known once the source has been read, and use zip64 fields where sizes
+//! or offsets pass 4 GiB.
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::{ArchiveEncoder, EntryInfo, FinishedFile};
+use crate::core::{CopyTransform, Crc32, Deflate, FileTransform};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP64_END: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+/// Sizes in a data descriptor followed by the data
+const FLAG_DESCRIPTOR: u16 = 1 << 3;
+/// Names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+
+/// Files this large get zip64 sizes up front; deflate may grow them slightly
+const ZIP64_THRESHOLD: u64 = 0xF000_0000;
+
+const MAX_U16: u64 = 0xFFFF;
+const MAX_U32: u64 = 0xFFFF_FFFF;
+
+/// An entry as listed in the central directory
+struct CentralEntry {
+    name: String,
+    offset: u64,
+    crc32: u32,
+    compressed: u64,
+    size: u64,
+    method: u16,
+    time: u16,
+    date: u16,
+    external_attributes: u32,
+    zip64: bool,
+}
+
+struct OpenFile {
+    entry: CentralEntry,
+    compressor: Box<dyn FileTransform>,
+    crc: Crc32,
+    start: u64,
+}
+
+pub struct ZipEncoder {
+    compression: Deflate,
+    /// Bytes emitted so far, the offset of the next entry
+    offset: u64,
+    entries: Vec<CentralEntry>,
+    open: Option<OpenFile>,
+}
+
+impl ZipEncoder {
+    pub fn new(level: u32) -> Self {
+        Self {
+            compression: Deflate::new(level),
+            offset: 0,
+            entries: Vec::new(),
+            open: None,
+        }
+    }
+
+    fn emit(&mut self, out: &mut Vec<u8>, bytes: &[u8]) {
+        out.extend_from_slice(bytes);
+        self.offset += bytes.len() as u64;
+    }
+
+    fn write_local_header(&mut self, out: &mut Vec<u8>, entry: &CentralEntry, flags: u16) {
+        let mut header = Vec::with_capacity(30 + entry.name.len() + 20);
+        header.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        header.extend_from_slice(&version_needed(entry).to_le_bytes());
+        header.extend_from_slice(&flags.to_le_bytes());
+        header.extend_from_slice(&entry.method.to_le_bytes());
+        header.extend_from_slice(&entry.time.to_le_bytes());
+        header.extend_from_slice(&entry.date.to_le_bytes());
+        // CRC and sizes follow in the data descriptor
+        header.extend_from_slice(&0u32.to_le_bytes());
+        let size_field = if entry.zip64 { MAX_U32 as u32 } else { 0 };
+        header.extend_from_slice(&size_field.to_le_bytes());
+        header.extend_from_slice(&size_field.to_le_bytes());
+        header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+
+        let extra = if entry.zip64 { zip64_extra(&[0, 0]) } else { Vec::new() };
+        header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        header.extend_from_slice(entry.name.as_bytes());
+        header.extend_from_slice(&extra);
+
+        self.emit(out, &header);
+    }
+}
+
+impl ArchiveEncoder for ZipEncoder {
+    fn add_directory(&mut self, info: &EntryInfo, out: &mut Vec<u8>) {
+        let (time, date) = dos_time(info.modified);
+        let entry = CentralEntry {
+            name: format!("{}/", info.name),
+            offset: self.offset,
+            crc32: 0,
+            compressed: 0,
+            size: 0,
+            method: METHOD_STORED,
+            time,
+            date,
+            external_attributes: external_attributes(info.mode, true),
+            zip64: false,
+        };
+
+        self.write_local_header(out, &entry, FLAG_UTF8);
+        self.entries.push(entry);
+    }
+
+    fn start_file(&mut self, info: &EntryInfo, out: &mut Vec<u8>) -> Result<()> {
+        let (time, date) = dos_time(info.modified);
+        let entry = CentralEntry {
+            name: info.name.clone(),
+            offset: self.offset,
+            crc32: 0,
+            compressed: 0,
+            size: 0,
+            method: METHOD_DEFLATE,
+            time,
+            date,
+            external_attributes: external_attributes(info.mode, false),
+            zip64: info.size >= ZIP64_THRESHOLD,
+        };
+
+        self.write_local_header(out, &entry, FLAG_DESCRIPTOR | FLAG_UTF8);
+        self.open = Some(OpenFile {
+            entry,
+            compressor: self.compression.begin(Path::new(&info.name))?,
+            crc: Crc32::new(),
+            start: self.offset,
+        });
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+        let open = self.open.as_mut().expect("no open zip entry");
+        let before = out.len();
+
+        open.crc.update(data);
+        open.entry.size += data.len() as u64;
+        open.compressor.process(data, out)?;
+
+        self.offset += (out.len() - before) as u64;
+        Ok(data.len())
+    }
+
+    fn finish_file(&mut self, out: &mut Vec<u8>) -> Result<FinishedFile> {
+        let mut open = self.open.take().expect("no open zip entry");
+        let before = out.len();
+        open.compressor.finish(out)?;
+        self.offset += (out.len() - before) as u64;
+
+        let mut entry = open.entry;
+        entry.crc32 = open.crc.finish();
+        entry.compressed = self.offset - open.start;
+
+        // A file that grew past 4 GiB while it was read cannot be described any more
+        let fits = entry.zip64 || (entry.size <= MAX_U32 && entry.compressed <= MAX_U32);
+
+        let mut descriptor = Vec::with_capacity(24);
+        descriptor.extend_from_slice(&DATA_DESCRIPTOR.to_le_bytes());
+        descriptor.extend_from_slice(&entry.crc32.to_le_bytes());
+        if entry.zip64 {
+            descriptor.extend_from_slice(&entry.compressed.to_le_bytes());
+            descriptor.extend_from_slice(&entry.size.to_le_bytes());
+        } else {
+            descriptor.extend_from_slice(&(entry.compressed as u32).to_le_bytes());
+            descriptor.extend_from_slice(&(entry.size as u32).to_le_bytes());
+        }
+        self.emit(out, &descriptor);
+
+        self.entries.push(entry);
+        Ok(FinishedFile { filled: 0, complete: fits })
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        let directory_start = self.offset;
+
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            let needs_zip64 = [entry.size, entry.compressed, entry.offset].map(|value| value >= MAX_U32);
+            let mut zip64_values = Vec::new();
+            let mut field = |value: u64, large: bool| -> u32 {
+                if large {
+                    zip64_values.push(value);
+                    MAX_U32 as u32
+                } else {
+                    value as u32
+                }
+            };
+            let size = field(entry.size, needs_zip64[0]);
+            let compressed = field(entry.compressed, needs_zip64[1]);
+            let offset = field(entry.offset, needs_zip64[2]);
+            let extra = if zip64_values.is_empty() { Vec::new() } else { zip64_extra(&zip64_values) };
+
+            let mut header = Vec::with_capacity(46 + entry.name.len() + extra.len());
+            header.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            header.extend_from_slice(&version_made_by().to_le_bytes());
+            header.extend_from_slice(&version_needed(entry).max(if extra.is_empty() { 0 } else { VERSION_ZIP64 }).to_le_bytes());
+            let flags = if entry.method == METHOD_DEFLATE { FLAG_DESCRIPTOR | FLAG_UTF8 } else { FLAG_UTF8 };
+            header.extend_from_slice(&flags.to_le_bytes());
+            header.extend_from_slice(&entry.method.to_le_bytes());
+            header.extend_from_slice(&entry.time.to_le_bytes());
+            header.extend_from_slice(&entry.date.to_le_bytes());
+            header.extend_from_slice(&entry.crc32.to_le_bytes());
+            header.extend_from_slice(&compressed.to_le_bytes());
+            header.extend_from_slice(&size.to_le_bytes());
+            header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            header.extend_from_slice(&0u16.to_le_bytes()); // disk number
+            header.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            header.extend_from_slice(&entry.external_attributes.to_le_bytes());
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(entry.name.as_bytes());
+            header.extend_from_slice(&extra);
+
+            self.emit(out, &header);
+        }
+
+        let count = entries.len() as u64;
+        let directory_size = self.offset - directory_start;
+
+        if count >= MAX_U16 || directory_start >= MAX_U32 || directory_size >= MAX_U32 {
+            let zip64_end = self.offset;
+            let mut record = Vec::with_capacity(56 + 20);
+            record.extend_from_slice(&ZIP64_END.to_le_bytes());
+            record.extend_from_slice(&44u64.to_le_bytes());
+            record.extend_from_slice(&version_made_by().to_le_bytes());
+            record.extend_from_slice(&VERSION_ZIP64.to_le_bytes());
+            record.extend_from_slice(&0u32.to_le_bytes());
+            record.extend_from_slice(&0u32.to_le_bytes());
+            record.extend_from_slice(&count.to_le_bytes());
+            record.extend_from_slice(&count.to_le_bytes());
+            record.extend_from_slice(&directory_size.to_le_bytes());
+            record.extend_from_slice(&directory_start.to_le_bytes());
+
+            record.extend_from_slice(&ZIP64_LOCATOR.to_le_bytes());
+            record.extend_from_slice(&0u32.to_le_bytes());
+            record.extend_from_slice(&zip64_end.to_le_bytes());
+            record.extend_from_slice(&1u32.to_le_bytes());
+            self.emit(out, &record);
+        }
+
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&(count.min(MAX_U16) as u16).to_le_bytes());
+        end.extend_from_slice(&(count.min(MAX_U16) as u16).to_le_bytes());
+        end.extend_from_slice(&(directory_size.min(MAX_U32) as u32).to_le_bytes());
+        end.extend_from_slice(&(directory_start.min(MAX_U32) as u32).to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.emit(out, &end);
+    }
+}
+
+fn version_needed(entry: &CentralEntry) -> u16 {
+    if entry.zip64 { VERSION_ZIP64 } else { VERSION_DEFAULT }
+}
+
+/// Host system in the high byte (3 = Unix, 0 = MS-DOS/Windows) decides how attributes are read
+fn version_made_by() -> u16 {
+    let host = if cfg!(unix) { 3 } else { 0 };
+    (host << 8) | VERSION_ZIP64
+}
+
+/// Unix mode in the high half on Unix, the MS-DOS directory bit elsewhere
+fn external_attributes(mode: Option<u32>, directory: bool) -> u32 {
+    let dos = if directory { 0x10 } else { 0 };
+    match mode {
+        Some(mode) if cfg!(unix) => (mode << 16) | dos,
+        _ => dos,
+    }
+}
+
+/// Zip64 extended information field holding `values` in header order
+fn zip64_extra(values: &[u64]) -> Vec<u8> {
+    let mut extra = Vec::with_capacity(4 + values.len() * 8);
+    extra.extend_from_slice(&1u16.to_le_bytes());
+    extra.extend_from_slice(&((values.len() * 8) as u16).to_le_bytes());
+    for value in values {
+        extra.extend_from_slice(&value.to_le_bytes());
+    }
+    extra
+}
+
+/// MS-DOS time and date in local time; zip cannot hold times before 1980
+fn dos_time(modified: Option<SystemTime>) -> (u16, u16) {
+    let local: DateTime<Local> = modified.unwrap_or_else(SystemTime::now).into();
+    if local.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+
+    let time = (local.hour() << 11) | (local.minute() << 5) | (local.second() / 2);
+    let date = (((local.year() - 1980) as u32) << 9) | (local.month() << 5) | local.day();
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::archive::deflate::tests::inflate;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_entries_are_listed_and_readable() {
+        let mut zip = ZipEncoder::new(6);
+        let mut out = Vec::new();
+        let contents = b"KeepHive zip entry, KeepHive zip entry, KeepHive zip entry".repeat(100);
+
+        let folder = EntryInfo { name: "docs".to_string(), size: 0, modified: None, mode: Some(0o755) };
+        zip.add_directory(&folder, &mut out);
+
+        let file = EntryInfo { name: "docs/näme.txt".to_string(), size: contents.len() as u64, modified: None, mode: Some(0o644) };
+        zip.start_file(&file, &mut out).unwrap();
+        zip.write_data(&contents[..1000], &mut out).unwrap();
+        zip.write_data(&contents[1000..], &mut out).unwrap();
+        assert!(zip.finish_file(&mut out).unwrap().complete);
+        zip.finish(&mut out);
+
+        // End of central directory: two entries, directory right before it
+        let end = out.len() - 22;
+        assert_eq!(u32_at(&out, end), END_OF_CENTRAL_DIRECTORY);
+        assert_eq!(u16_at(&out, end + 10), 2);
+        let directory = u32_at(&out, end + 16) as usize;
+        assert_eq!(directory + u32_at(&out, end + 12) as usize, end);
+
+        // Second central header points at the file's local header
+        let first_name = u16_at(&out, directory + 28) as usize;
+        let second = directory + 46 + first_name;
+        assert_eq!(u32_at(&out, second), CENTRAL_HEADER);
+        assert_eq!(u32_at(&out, second + 16), {
+            let mut crc = Crc32::new();
+            crc.update(&contents);
+            crc.finish()
+        });
+        let compressed = u32_at(&out, second + 20) as usize;
+        assert_eq!(u32_at(&out, second + 24) as usize, contents.len());
+        assert_eq!(&out[second + 46..second + 46 + u16_at(&out, second + 28) as usize], "docs/näme.txt".as_bytes());
+
+        let local = u32_at(&out, second + 42) as usize;
+        assert_eq!(u32_at(&out, local), LOCAL_HEADER);
+        let data = local + 30 + u16_at(&out, local + 26) as usize + u16_at(&out, local + 28) as usize;
+        assert!(compressed < contents.len() / 4);
+        assert_eq!(inflate(&out[data..data + compressed]), contents);
+        assert_eq!(u32_at(&out, data + compressed), DATA_DESCRIPTOR);
+    }
+
+    #[test]
+    fn test_dos_time_clamps_to_1980() {
+        assert_eq!(dos_time(Some(SystemTime::UNIX_EPOCH)), (0, (1 << 5) | 1));
+    }
+}
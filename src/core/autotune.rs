@@ -0,0 +1,144 @@
+//! Copy tuning: the buffer size and number of files copied at once, either fixed or
+//! found by trying a few combinations at the start of a run.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How long each candidate is measured
+const TRIAL_DURATION: Duration = Duration::from_secs(3);
+
+/// Buffer size and parallel file copies used by the copy engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CopyTuning {
+    pub buffer_size: usize,
+    pub parallel_files: usize,
+}
+
+impl Default for CopyTuning {
+    fn default() -> Self {
+        Self {
+            buffer_size: 1024 * 1024,
+            parallel_files: 1,
+        }
+    }
+}
+
+/// Combinations tried in order; small files gain from parallel copies, large ones from big buffers
+const CANDIDATES: [CopyTuning; 4] = [
+    CopyTuning { buffer_size: 256 * 1024, parallel_files: 1 },
+    CopyTuning { buffer_size: 1024 * 1024, parallel_files: 1 },
+    CopyTuning { buffer_size: 4 * 1024 * 1024, parallel_files: 1 },
+    CopyTuning { buffer_size: 1024 * 1024, parallel_files: 4 },
+];
+
+/// Measures each candidate for `TRIAL_DURATION` of copying and then keeps the fastest
+#[derive(Debug, Clone)]
+pub struct Autotuner {
+    trial: usize,
+    trial_start: Option<(Instant, u64)>,
+    rates: Vec<(CopyTuning, f64)>,
+    locked: Option<CopyTuning>,
+}
+
+impl Default for Autotuner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Autotuner {
+    pub fn new() -> Self {
+        Self {
+            trial: 0,
+            trial_start: None,
+            rates: Vec::new(),
+            locked: None,
+        }
+    }
+
+    /// Tuning to copy with now: the candidate on trial, or the winner once all were measured
+    pub fn current(&self) -> CopyTuning {
+        self.locked.unwrap_or(CANDIDATES[self.trial])
+    }
+
+    /// Best combination, once every candidate was measured. None if the run ended first.
+    pub fn locked(&self) -> Option<CopyTuning> {
+        self.locked
+    }
+
+    /// Note that `bytes_copied` bytes were copied in total by `now`
+    pub fn record(&mut self, bytes_copied: u64, now: Instant) {
+        if self.locked.is_some() {
+            return;
+        }
+
+        let Some((started, start_bytes)) = self.trial_start else {
+            self.trial_start = Some((now, bytes_copied));
+            return;
+        };
+
+        let elapsed = now.duration_since(started);
+        if elapsed < TRIAL_DURATION {
+            return;
+        }
+
+        let rate = (bytes_copied - start_bytes) as f64 / elapsed.as_secs_f64();
+        self.rates.push((CANDIDATES[self.trial], rate));
+        self.trial_start = Some((now, bytes_copied));
+
+        if self.trial + 1 < CANDIDATES.len() {
+            self.trial += 1;
+            return;
+        }
+
+        let (best, rate) = self.rates.iter()
+            .copied()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("every candidate was measured");
+        info!(
+            "Autotune picked {} KiB buffers and {} parallel files ({:.1} MB/s)",
+            best.buffer_size / 1024,
+            best.parallel_files,
+            rate / 1_000_000.0
+        );
+        self.locked = Some(best);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fastest_candidate_is_locked_in() {
+        let mut tuner = Autotuner::new();
+        let start = Instant::now();
+        let speeds = [10u64, 40, 20, 30];
+
+        tuner.record(0, start);
+        let mut bytes = 0;
+        for (i, speed) in speeds.iter().enumerate() {
+            assert_eq!(tuner.current(), CANDIDATES[i]);
+            assert_eq!(tuner.locked(), None);
+
+            bytes += speed * TRIAL_DURATION.as_secs();
+            tuner.record(bytes, start + TRIAL_DURATION * (i as u32 + 1));
+        }
+
+        assert_eq!(tuner.locked(), Some(CANDIDATES[1]));
+        assert_eq!(tuner.current(), CANDIDATES[1]);
+    }
+
+    #[test]
+    fn test_short_runs_lock_nothing() {
+        let mut tuner = Autotuner::new();
+        let start = Instant::now();
+
+        tuner.record(0, start);
+        tuner.record(1000, start + Duration::from_secs(1));
+
+        assert_eq!(tuner.locked(), None);
+        assert_eq!(tuner.current(), CANDIDATES[0]);
+    }
+}
@@ -1,25 +1,93 @@
-use crate::core::{validate_backup_job, CopyEngine};
+use crate::config::{ArchiveConfig, BackupMode, Durability, NetworkTargetConfig, VerifyConfig};
+use crate::core::{
+    crc32_file, is_archive_backup, probe_share, unc_share, validate_backup_job, verify_backup, write_renames, Autotuner, BackupManifest, BandwidthLimiter, Baseline, CopyEngine, CopyError,
+    ArchiveWriter, CopyFailure, CopyOptions, CopyProgress, PathReport, RetainedBackup, SkippedEntry, TransformChain, MANIFEST_FILE, RENAMES_FILE,
+};
+use crate::observability::{format_bytes, format_duration, redacted};
+use crate::platform::{sync_directory, FaultInjector};
 use crate::state::BackupMetadata;
 use anyhow::{bail, Context, Result};
 use chrono::Utc;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 #[cfg(windows)]
 use crate::platform::windows::is_reserved_name;
 
+/// Marker file written into a backup directory once every file has been copied and synced.
+/// Directories without it are treated as partial, even if they were never renamed `_PARTIAL`.
+pub const COMPLETE_MARKER: &str = ".keephive_complete";
+
+/// Marker file written into every target, so that jobs never copy it should it turn up
+/// inside a source (a target mounted into a source folder, or a source moved above it)
+pub const TARGET_MARKER: &str = ".keephive_target";
+
+/// How long a network share target gets to answer one availability check
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct BackupOrchestrator {
     copy_engine: CopyEngine,
+    durability: Durability,
+    verify: VerifyConfig,
+    checksums: bool,
+    network_targets: NetworkTargetConfig,
 }
 
 impl BackupOrchestrator {
     pub fn new() -> Self {
+        Self::with_durability(Durability::default())
+    }
+
+    /// Create orchestrator with a specific durability level
+    pub fn with_durability(durability: Durability) -> Self {
+        Self::with_bandwidth_limiter(durability, Arc::new(BandwidthLimiter::default()))
+    }
+
+    /// Create orchestrator whose copies share a bandwidth limiter with other jobs
+    pub fn with_bandwidth_limiter(durability: Durability, limiter: Arc<BandwidthLimiter>) -> Self {
         Self {
-            copy_engine: CopyEngine::new(),
+            copy_engine: CopyEngine::with_bandwidth_limiter(limiter),
+            durability,
+            verify: VerifyConfig::default(),
+            checksums: false,
+            network_targets: NetworkTargetConfig::default(),
         }
     }
 
+    /// Verify copied files against the source before a backup is marked complete
+    pub fn with_verification(mut self, verify: VerifyConfig) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Record a checksum of every copied file in the manifest
+    pub fn with_checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Wait for and reconnect network share targets that are offline when a run starts
+    pub fn with_network_targets(mut self, network_targets: NetworkTargetConfig) -> Self {
+        self.network_targets = network_targets;
+        self
+    }
+
+    /// Run copied files through custom transforms
+    pub fn with_transforms(mut self, transforms: TransformChain) -> Self {
+        self.copy_engine = self.copy_engine.with_transforms(transforms);
+        self
+    }
+
+    /// Inject copy faults, for exercising failure handling
+    pub fn with_faults(mut self, faults: Option<Arc<FaultInjector>>) -> Self {
+        self.copy_engine = self.copy_engine.with_faults(faults);
+        self
+    }
+
     /// Execute backup with crash recovery support
     pub async fn execute_backup(
         &self,
@@ -28,7 +96,92 @@ impl BackupOrchestrator {
         target: &Path,
         cancellation: CancellationToken,
     ) -> Result<BackupMetadata> {
-        info!("Starting backup: {} ({} -> {})", job_id, source.display(), target.display());
+        self.execute_backup_with(job_id, source, target, &CopyOptions::default(), cancellation).await
+    }
+
+    /// Wait until a network share target answers, reconnecting it between attempts, for
+    /// up to `network_targets.wait_seconds`; local targets are not checked
+    async fn wait_for_target(&self, target: &Path, cancellation: &CancellationToken) -> Result<()> {
+        let Some(share) = unc_share(target) else {
+            return Ok(());
+        };
+
+        let config = &self.network_targets;
+        let interval = Duration::from_secs(config.retry_interval_seconds.max(1));
+        let deadline = Instant::now() + Duration::from_secs(config.wait_seconds);
+
+        loop {
+            let error = match probe_share(&share, PROBE_TIMEOUT).await {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+
+            if let Err(e) = self.reconnect_share(&share).await {
+                debug!("Reconnecting {} failed: {:#}", share.display(), e);
+            } else if probe_share(&share, PROBE_TIMEOUT).await.is_ok() {
+                info!("Reconnected network target {}", share.display());
+                return Ok(());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(error.context(format!(
+                    "Network target {} is unavailable (waited {})",
+                    share.display(),
+                    format_duration(Duration::from_secs(config.wait_seconds))
+                )));
+            }
+
+            let pause = interval.min(remaining);
+            warn!("Network target unavailable, retrying in {}: {}", format_duration(pause), error);
+            tokio::select! {
+                _ = tokio::time::sleep(pause) => {}
+                _ = cancellation.cancelled() => bail!("Backup cancelled while waiting for {}", share.display()),
+            }
+        }
+    }
+
+    /// Connect to `share` again, with the account configured for it if any
+    #[cfg(windows)]
+    async fn reconnect_share(&self, share: &Path) -> Result<()> {
+        let account = match self.network_targets.credentials_for(share) {
+            Some(credentials) => {
+                let password = match &credentials.password_env {
+                    Some(var) => std::env::var(var)
+                        .with_context(|| format!("Share password variable {} is not set", var))?,
+                    None => String::new(),
+                };
+                Some((credentials.username.clone(), password))
+            }
+            None => None,
+        };
+
+        let share = share.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let account = account.as_ref().map(|(user, password)| (user.as_str(), password.as_str()));
+            crate::platform::windows::network::connect_share(&share, account)
+        })
+        .await?
+    }
+
+    /// Shares are mounted by the system elsewhere; there is nothing to reconnect
+    #[cfg(not(windows))]
+    async fn reconnect_share(&self, share: &Path) -> Result<()> {
+        bail!("Reconnecting {} is only supported on Windows", share.display())
+    }
+
+    /// Execute backup with a job's exclusions and name handling
+    pub async fn execute_backup_with(
+        &self,
+        job_id: &str,
+        source: &Path,
+        target: &Path,
+        options: &CopyOptions,
+        cancellation: CancellationToken,
+    ) -> Result<BackupMetadata> {
+        info!("Starting backup: {} ({} -> {})", job_id, redacted(source), redacted(target));
+
+        self.wait_for_target(target, &cancellation).await?;
 
         // Prerequisites validation
         let validation = validate_backup_job(source, target).await?;
@@ -41,10 +194,25 @@ impl BackupOrchestrator {
             warn!("Validation warning: {}", warning);
         }
 
+        Self::mark_target(target).await;
+
+        if let Some(archive) = &options.archive {
+            return self.execute_archive_backup(job_id, source, target, options, archive, cancellation).await;
+        }
+
         // Create backup directory with timestamp
         let backup_name = Self::generate_backup_name(source);
         let backup_path = target.join(&backup_name);
 
+        // Paths the target cannot hold are reported together up front, then skipped
+        let report = Self::check_paths(source, target, options).await?;
+        if !report.is_clean() {
+            warn!("{}; skipping them", report.summary());
+            for line in report.lines() {
+                warn!("Path too long for target: {}", line);
+            }
+        }
+
         // Check for existing backup (crash recovery scenario)
         if backup_path.exists() {
             warn!("Backup directory already exists, removing: {}", backup_path.display());
@@ -56,9 +224,18 @@ impl BackupOrchestrator {
 
         let mut metadata = BackupMetadata::new(backup_name.clone(), backup_path.clone());
 
+        // Files the last backup could not copy are tried before anything else
+        let mut options = options.clone();
+        options.too_long = report.too_long.into_iter().map(|long| long.relative).collect();
+        options.retry_first = Self::previously_skipped(target, source).await;
+        options.hash = self.checksums || self.verify != VerifyConfig::Off;
+        if options.mode == BackupMode::Incremental {
+            options.baseline = self.incremental_baseline(target, source).await.map(Arc::new);
+        }
+
         // Execute copy with cancellation support
         let copy_result = tokio::select! {
-            result = self.copy_with_progress(source, &backup_path, &mut metadata) => result,
+            result = self.copy_and_verify(source, &backup_path, &options, &mut metadata) => result,
             _ = cancellation.cancelled() => {
                 warn!("Backup cancelled for job: {}", job_id);
                 self.mark_partial(&backup_path).await?;
@@ -67,14 +244,45 @@ impl BackupOrchestrator {
         };
 
         match copy_result {
-            Ok(_) => {
+            Ok(progress) => {
                 metadata.mark_complete();
-                info!("Backup completed: {} ({} files, {} bytes)",
-                    job_id, metadata.files_copied, metadata.bytes_copied);
+
+                let manifest = BackupManifest::new(backup_name, progress.files)
+                    .with_links(progress.links)
+                    .with_skipped(progress.skipped);
+                if let Err(e) = manifest.write(&backup_path).await {
+                    error!("Failed to write backup manifest: {}", e);
+                    self.abandon_backup(&backup_path, &e).await?;
+                    return Err(e);
+                }
+
+                if !progress.renames.is_empty()
+                    && let Err(e) = write_renames(&backup_path, &progress.renames).await
+                {
+                    error!("Failed to write rename mapping: {}", e);
+                    self.abandon_backup(&backup_path, &e).await?;
+                    return Err(e);
+                }
+
+                // The marker goes last: a backup is only complete once its manifest is on disk
+                if let Err(e) = Self::write_complete_marker(&backup_path, &metadata).await {
+                    error!("Failed to finalize backup: {}", e);
+                    self.abandon_backup(&backup_path, &e).await?;
+                    return Err(e);
+                }
+
+                if self.durability == Durability::Strict {
+                    // Persist the marker entry and the backup directory entry itself
+                    sync_directory(&backup_path).await?;
+                    sync_directory(target).await?;
+                }
+
+                info!("Backup completed: {} ({} files, {})",
+                    job_id, metadata.files_copied, format_bytes(metadata.bytes_copied));
             }
             Err(e) => {
                 error!("Backup failed: {}", e);
-                self.mark_partial(&backup_path).await?;
+                self.abandon_backup(&backup_path, &e).await?;
                 return Err(e);
             }
         }
@@ -82,31 +290,306 @@ impl BackupOrchestrator {
         Ok(metadata)
     }
 
-    /// Copy with progress tracking
+    /// Stream the source into a single archive file in `target`. The archive is written
+    /// under a `_PARTIAL` name and renamed once it is complete.
+    async fn execute_archive_backup(
+        &self,
+        job_id: &str,
+        source: &Path,
+        target: &Path,
+        options: &CopyOptions,
+        archive: &ArchiveConfig,
+        cancellation: CancellationToken,
+    ) -> Result<BackupMetadata> {
+        if options.mode == BackupMode::Incremental {
+            warn!("Incremental mode does not apply to archive backups, archiving every file");
+        }
+        if self.verify != VerifyConfig::Off {
+            warn!("Skipping verification: archive backups are not compared with the source");
+        }
+        if self.copy_engine.has_transforms() {
+            warn!("Copy transforms do not apply to archive backups");
+        }
+
+        let backup_name = format!("{}.{}", Self::generate_backup_name(source), archive.format.extension());
+        let backup_path = target.join(&backup_name);
+        let partial_path = target.join(format!("{}_PARTIAL", backup_name));
+
+        let mut metadata = BackupMetadata::new(backup_name.clone(), backup_path.clone());
+        let mut options = options.clone();
+        options.hash = self.checksums;
+
+        let write_result = tokio::select! {
+            result = self.write_archive(source, &partial_path, &options, archive, &mut metadata) => result,
+            _ = cancellation.cancelled() => {
+                warn!("Backup cancelled for job: {}", job_id);
+                warn!("Left partial archive: {}", partial_path.display());
+                bail!("Backup cancelled");
+            }
+        };
+
+        if let Err(e) = write_result {
+            error!("Backup failed: {}", e);
+            if CopyError::classify(&e) == CopyFailure::DiskFull {
+                match tokio::fs::remove_file(&partial_path).await {
+                    Ok(()) => warn!("Target full, removed incomplete archive: {}", partial_path.display()),
+                    Err(remove_error) => warn!("Failed to remove incomplete archive {}: {}", partial_path.display(), remove_error),
+                }
+            } else {
+                warn!("Left partial archive: {}", partial_path.display());
+            }
+            return Err(e);
+        }
+
+        tokio::fs::rename(&partial_path, &backup_path).await
+            .context("Failed to finalize archive")?;
+
+        if self.durability == Durability::Strict {
+            sync_directory(target).await?;
+        }
+
+        info!("Backup completed: {} ({} files, {}, archived to {})",
+            job_id, metadata.files_copied, format_bytes(metadata.bytes_copied), backup_path.display());
+
+        Ok(metadata)
+    }
+
+    /// Write the archive with the manifest and completion marker inside, so an extracted
+    /// archive is an ordinary backup folder
+    async fn write_archive(
+        &self,
+        source: &Path,
+        path: &Path,
+        options: &CopyOptions,
+        archive: &ArchiveConfig,
+        metadata: &mut BackupMetadata,
+    ) -> Result<CopyProgress> {
+        let mut writer = ArchiveWriter::create(path, archive).await?;
+
+        let mut progress = writer.add_tree(
+            source,
+            options,
+            &self.copy_engine.limiter_for(options),
+            |p| {
+                metadata.bytes_copied = p.bytes_copied;
+                metadata.files_copied = p.files_copied;
+                metadata.files_skipped = p.files_skipped;
+                metadata.skip_reasons = p.failures;
+            },
+        ).await?;
+
+        metadata.bytes_copied = progress.bytes_copied;
+        metadata.files_copied = progress.files_copied;
+        metadata.files_skipped = progress.files_skipped;
+        metadata.skip_reasons = progress.failures;
+        metadata.mark_complete();
+
+        if progress.files_skipped > 0 {
+            warn!("{} files skipped, listed in the archived manifest", progress.files_skipped);
+        }
+
+        let manifest = BackupManifest::new(metadata.backup_name.clone(), std::mem::take(&mut progress.files))
+            .with_links(std::mem::take(&mut progress.links))
+            .with_skipped(progress.skipped.clone());
+        writer.add_bytes(MANIFEST_FILE, &serde_json::to_vec(&manifest).context("Failed to serialize backup manifest")?).await?;
+        writer.add_bytes(COMPLETE_MARKER, &serde_json::to_vec_pretty(metadata).context("Failed to serialize completion marker")?).await?;
+
+        writer.finish(true).await?;
+        Ok(progress)
+    }
+
+    /// Paths of a backup of `source` into `target` that would exceed the target's limits
+    pub async fn check_paths(source: &Path, target: &Path, options: &CopyOptions) -> Result<PathReport> {
+        let backup_path = target.join(Self::generate_backup_name(source));
+        let limit = crate::core::path_limit(target, options.max_path_length).await;
+
+        crate::core::check_path_lengths(source, &backup_path, options, limit).await
+    }
+
+    /// Copy, then checksum and verify the copy if configured; mismatches fail the backup
+    async fn copy_and_verify(
+        &self,
+        source: &Path,
+        backup_path: &Path,
+        options: &CopyOptions,
+        metadata: &mut BackupMetadata,
+    ) -> Result<CopyProgress> {
+        let mut progress = self.copy_with_progress(source, backup_path, options, metadata).await?;
+
+        if self.verify != VerifyConfig::Off && self.copy_engine.has_transforms() {
+            warn!("Skipping verification: copy transforms change file contents");
+        } else if self.verify != VerifyConfig::Off {
+            let summary = verify_backup(source, backup_path, &progress.files, self.verify).await?;
+            let mismatched = summary.mismatched.clone();
+            metadata.verification = Some(summary);
+
+            if !mismatched.is_empty() {
+                bail!(
+                    "Verification failed: {} files differ from the source (first: {})",
+                    mismatched.len(),
+                    mismatched[0]
+                );
+            }
+        }
+
+        // Copies hashed while they were made already carry their checksum
+        if self.checksums {
+            for entry in progress.files.iter_mut().filter(|entry| entry.crc32.is_none()) {
+                entry.crc32 = Some(crc32_file(&backup_path.join(&entry.path)).await
+                    .context("Failed to checksum backup copy")?);
+            }
+        }
+
+        Ok(progress)
+    }
+
+    /// Copy with progress tracking, returning the copied files, links and renames for the manifest
     async fn copy_with_progress(
         &self,
         source: &Path,
         backup_path: &Path,
+        options: &CopyOptions,
         metadata: &mut BackupMetadata,
-    ) -> Result<()> {
-        let progress = self.copy_engine.copy_directory(
+    ) -> Result<CopyProgress> {
+        let progress = self.copy_engine.copy_directory_with(
             source,
             backup_path,
+            options,
             |p| {
                 metadata.bytes_copied = p.bytes_copied;
                 metadata.files_copied = p.files_copied;
                 metadata.files_skipped = p.files_skipped;
+                metadata.files_unchanged = p.files_unchanged;
+                metadata.skip_reasons = p.failures;
             },
         ).await?;
 
         metadata.bytes_copied = progress.bytes_copied;
+        metadata.tuning = progress.autotuner.as_ref().and_then(Autotuner::locked);
         metadata.files_copied = progress.files_copied;
         metadata.files_skipped = progress.files_skipped;
+        metadata.files_unchanged = progress.files_unchanged;
+        metadata.skip_reasons = progress.failures;
+
+        if progress.files_skipped > 0 {
+            let reasons = progress.failures;
+            warn!(
+                "{} files skipped: {} source unreadable, {} target write failed, {} path too long, {} folders holding backups",
+                progress.files_skipped,
+                reasons.source_unreadable,
+                reasons.target_write_failed,
+                reasons.path_too_long,
+                reasons.backup_area
+            );
+        }
+
+        if progress.files_unchanged > 0 {
+            info!("{} unchanged files linked from the previous backup", progress.files_unchanged);
+        }
+
+        if !progress.links.is_empty() {
+            info!("{} junctions and symbolic links not followed, recorded in the manifest", progress.links.len());
+        }
+
+        if !progress.renames.is_empty() {
+            warn!("{} names the target cannot hold were changed, see {}", progress.renames.len(), RENAMES_FILE);
+        }
+
+        Ok(progress)
+    }
+
+    /// Write the completion marker and sync it to disk
+    async fn write_complete_marker(backup_path: &Path, metadata: &BackupMetadata) -> Result<()> {
+        let marker_path = backup_path.join(COMPLETE_MARKER);
+
+        let json = serde_json::to_vec_pretty(metadata)
+            .context("Failed to serialize completion marker")?;
+
+        tokio::fs::write(&marker_path, &json).await
+            .context("Failed to write completion marker")?;
+
+        let marker_file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(&marker_path)
+            .await?;
+
+        marker_file.sync_all().await
+            .context("Failed to sync completion marker")?;
 
         Ok(())
     }
 
+    /// Check whether a backup directory carries the completion marker. Archives only
+    /// get their backup name once complete.
+    pub async fn is_complete_backup(backup_path: &Path) -> bool {
+        if is_archive_backup(backup_path) {
+            return tokio::fs::metadata(backup_path).await.is_ok_and(|m| m.is_file());
+        }
+
+        tokio::fs::try_exists(backup_path.join(COMPLETE_MARKER)).await
+            .unwrap_or(false)
+    }
+
+    /// Write the completion marker into the backups of `source` in `target` made before
+    /// markers existed, returning how many were marked. `unfinished` backups were being
+    /// written when the old version stopped and are left unmarked.
+    pub async fn mark_legacy_backups(target: &Path, source: &Path, unfinished: &[PathBuf]) -> Result<usize> {
+        let mut entries = tokio::fs::read_dir(target).await
+            .with_context(|| format!("Failed to read target directory {}", target.display()))?;
+        let mut marked = 0;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if !entry.file_type().await?.is_dir()
+                || !Self::is_backup_of(&path, source)
+                || unfinished.iter().any(|backup| backup.file_name() == path.file_name())
+                || Self::is_complete_backup(&path).await
+            {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let mut metadata = BackupMetadata::new(name, path.clone());
+            metadata.mark_complete();
+            Self::write_complete_marker(&path, &metadata).await?;
+            marked += 1;
+        }
+
+        Ok(marked)
+    }
+
+    /// Write `TARGET_MARKER` into a target that does not have it yet
+    async fn mark_target(target: &Path) {
+        let marker = target.join(TARGET_MARKER);
+        if tokio::fs::try_exists(&marker).await.unwrap_or(false) {
+            return;
+        }
+
+        let text = "KeepHive writes backups here. Jobs do not back up folders holding this file.\n";
+        if let Err(e) = tokio::fs::write(&marker, text).await {
+            warn!("Could not mark target {}: {}", target.display(), e);
+        }
+    }
+
     /// Mark backup as partial by renaming directory
+    /// Deal with a failed backup: on a full target delete it to give the space back, otherwise keep it as partial
+    async fn abandon_backup(&self, backup_path: &Path, error: &anyhow::Error) -> Result<()> {
+        if CopyError::classify(error) != CopyFailure::DiskFull {
+            return self.mark_partial(backup_path).await;
+        }
+
+        match tokio::fs::remove_dir_all(backup_path).await {
+            Ok(()) => {
+                warn!("Target full, removed incomplete backup: {}", backup_path.display());
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Failed to remove incomplete backup {}: {}", backup_path.display(), e);
+                self.mark_partial(backup_path).await
+            }
+        }
+    }
+
     async fn mark_partial(&self, backup_path: &Path) -> Result<()> {
         let partial_name = format!("{}_PARTIAL", backup_path.file_name()
             .and_then(|n| n.to_str())
@@ -117,6 +600,12 @@ impl BackupOrchestrator {
         tokio::fs::rename(backup_path, &partial_path).await
             .context("Failed to mark backup as partial")?;
 
+        if self.durability == Durability::Strict
+            && let Some(target) = partial_path.parent()
+        {
+            sync_directory(target).await?;
+        }
+
         warn!("Marked backup as PARTIAL: {}", partial_path.display());
         Ok(())
     }
@@ -189,6 +678,19 @@ impl BackupOrchestrator {
             if let Some(name) = entry.file_name().to_str() {
                 if name.ends_with("_PARTIAL") {
                     partial_backups.push(entry.path());
+                    continue;
+                }
+
+                if name.starts_with(".keephive") {
+                    continue;
+                }
+
+                // Backup directories that never got their completion marker
+                // (e.g. power loss before the _PARTIAL rename could happen)
+                let is_dir = entry.metadata().await.map(|m| m.is_dir()).unwrap_or(false);
+                if is_dir && !Self::is_complete_backup(&entry.path()).await {
+                    warn!("Backup is missing its completion marker: {}", entry.path().display());
+                    partial_backups.push(entry.path());
                 }
             }
         }
@@ -200,8 +702,8 @@ impl BackupOrchestrator {
         Ok(partial_backups)
     }
 
-    /// Clean old backups keeping only the specified retention count
-    pub async fn cleanup_old_backups(target: &Path, retention_count: usize) -> Result<()> {
+    /// List completed backups in a target directory, oldest first
+    pub async fn list_complete_backups(target: &Path) -> Result<Vec<PathBuf>> {
         let mut backups = Vec::new();
 
         let mut entries = tokio::fs::read_dir(target).await?;
@@ -214,29 +716,397 @@ impl BackupOrchestrator {
                 }
 
                 if let Ok(metadata) = entry.metadata().await {
-                    if metadata.is_dir() {
+                    // Incomplete backups are neither counted nor removed by retention
+                    let candidate = metadata.is_dir() || (metadata.is_file() && is_archive_backup(&entry.path()));
+                    if candidate && Self::is_complete_backup(&entry.path()).await {
                         backups.push((entry.path(), metadata.modified().ok()));
                     }
                 }
             }
         }
 
-        // Sort by modification time (newest first)
-        backups.sort_by(|a, b| b.1.cmp(&a.1));
+        // Sort by modification time (oldest first)
+        backups.sort_by(|a, b| a.1.cmp(&b.1));
+
+        Ok(backups.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Completed backups of the job backing up `source`, oldest first. Jobs sharing a
+    /// target name their backups after their own source folder, so others are left out.
+    pub async fn list_job_backups(target: &Path, source: &Path) -> Result<Vec<PathBuf>> {
+        let mut backups = Self::list_complete_backups(target).await?;
+        backups.retain(|backup| Self::is_backup_of(backup, source));
+        Ok(backups)
+    }
+
+    /// Whether `backup` is named like a backup of `source`: the source folder name,
+    /// then the timestamp, then an archive extension if any
+    pub fn is_backup_of(backup: &Path, source: &Path) -> bool {
+        let name = backup.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let source_name = source.file_name().and_then(|n| n.to_str()).unwrap_or("backup");
+        let prefix = format!("{}_", Self::sanitize_backup_name(source_name));
+
+        let Some(rest) = name.strip_prefix(&prefix) else {
+            return false;
+        };
+
+        // Digits where `generate_backup_name` writes them
+        const STAMP: &str = "0000-00-00_000000_000";
+        let stamp_matches = rest.len() >= STAMP.len()
+            && rest.bytes().zip(STAMP.bytes()).all(|(c, t)| if t == b'0' { c.is_ascii_digit() } else { c == t });
+
+        stamp_matches && (rest.len() == STAMP.len() || rest[STAMP.len()..].starts_with('.'))
+    }
+
+    /// Files the job's latest complete backup in `target` recorded as skipped; none if there
+    /// is no such backup or its manifest cannot be read
+    async fn previously_skipped(target: &Path, source: &Path) -> Vec<SkippedEntry> {
+        let Some(latest) = Self::latest_job_backup(target, source).await else {
+            return Vec::new();
+        };
+
+        match BackupManifest::load(&latest).await {
+            Ok(manifest) => manifest.skipped,
+            Err(e) => {
+                debug!("No skipped files from {}: {}", latest.display(), e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Latest complete backup directory of the job backing up `source`; archives are passed
+    /// over, as files cannot be linked from them
+    pub(crate) async fn latest_job_backup(target: &Path, source: &Path) -> Option<PathBuf> {
+        let backups = Self::list_job_backups(target, source).await.ok()?;
+        backups.into_iter().rev().find(|backup| !is_archive_backup(backup))
+    }
+
+    /// Latest complete backup of this job with a manifest, which an incremental backup links unchanged files from
+    async fn incremental_baseline(&self, target: &Path, source: &Path) -> Option<Baseline> {
+        // Transformed copies cannot be compared with the source by size
+        if self.copy_engine.has_transforms() {
+            info!("Copy transforms registered, copying every file");
+            return None;
+        }
+
+        let Some(latest) = Self::latest_job_backup(target, source).await else {
+            info!("No earlier backup in {}, copying every file", target.display());
+            return None;
+        };
+
+        match BackupManifest::load(&latest).await {
+            Ok(manifest) => {
+                info!("Incremental backup based on {}", latest.display());
+                Some(Baseline::new(latest, manifest))
+            }
+            Err(e) => {
+                warn!("Copying every file, no usable manifest in {}: {}", latest.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Resolve a backup reference: `latest` or `previous` backup of the job backing up `source`,
+    /// a directory name in `target`, or a path
+    pub async fn resolve_backup(target: &Path, source: &Path, reference: &str) -> Result<PathBuf> {
+        match reference {
+            "latest" | "previous" => {
+                let backups = Self::list_job_backups(target, source).await
+                    .with_context(|| format!("Failed to list backups in {}", target.display()))?;
+                let offset = if reference == "latest" { 1 } else { 2 };
+
+                let backup = backups.len().checked_sub(offset)
+                    .map(|i| backups[i].clone())
+                    .with_context(|| format!("No {} backup in {}", reference, target.display()))?;
+
+                if is_archive_backup(&backup) {
+                    bail!("The {} backup is an archive, extract it to use it: {}", reference, backup.display());
+                }
+                Ok(backup)
+            }
+            _ => {
+                let by_name = target.join(reference);
+                if by_name.is_dir() {
+                    return Ok(by_name);
+                }
+
+                let by_path = PathBuf::from(reference);
+                if by_path.is_dir() {
+                    return Ok(by_path);
+                }
+
+                if by_name.is_file() && is_archive_backup(&by_name) {
+                    bail!("Backup {} is an archive, extract it to use it", reference);
+                }
+
+                bail!("Backup not found: {} (looked in {})", reference, target.display())
+            }
+        }
+    }
+
+    /// Clean old backups of the job backing up `source`, keeping only the specified retention
+    /// count, and never fewer than one. Backups of other jobs sharing the target are left alone.
+    /// `protected`, the job's last successful backup, is kept even when it is among the oldest.
+    ///
+    /// Like every cleanup, this must run under the target's lock (`scheduler::TargetLocks`),
+    /// so no backup is being written to `target` meanwhile.
+    pub async fn cleanup_old_backups(target: &Path, source: &Path, retention_count: usize, protected: Option<&Path>) -> Result<usize> {
+        let backups = Self::list_job_backups(target, source).await?;
+        let retention_count = retention_count.max(1);
+        let mut removed = 0;
 
         // Remove old backups beyond retention count
         if backups.len() > retention_count {
-            for (path, _) in backups.iter().skip(retention_count) {
+            for path in backups.iter().take(backups.len() - retention_count) {
+                if is_protected(path, protected) {
+                    continue;
+                }
                 info!("Removing old backup: {}", path.display());
-                tokio::fs::remove_dir_all(path).await
-                    .context("Failed to remove old backup")?;
+                Self::remove_backup(path).await?;
+                removed += 1;
             }
         }
 
-        Ok(())
+        Ok(removed)
+    }
+
+    /// Remove backups of the job backing up `source` last modified longer than `max_age` ago,
+    /// returning how many were removed. The job's newest backup and `protected` are always
+    /// kept, however old they are.
+    pub async fn cleanup_older_than(target: &Path, source: &Path, max_age: Duration, protected: Option<&Path>) -> Result<usize> {
+        let backups = Self::list_job_backups(target, source).await?;
+        let now = std::time::SystemTime::now();
+        let mut removed = 0;
+
+        for path in backups.iter().take(backups.len().saturating_sub(1)) {
+            if is_protected(path, protected) {
+                continue;
+            }
+
+            let modified = tokio::fs::metadata(path).await
+                .and_then(|metadata| metadata.modified())
+                .with_context(|| format!("Failed to read the age of backup {}", path.display()))?;
+
+            // Oldest first, so the rest are younger
+            if now.duration_since(modified).unwrap_or_default() <= max_age {
+                break;
+            }
+
+            info!("Removing expired backup: {}", path.display());
+            Self::remove_backup(path).await?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Remove the oldest backups of the job backing up `source` until the rest take up at most
+    /// `max_bytes`, returning how many were removed. Backups of other jobs sharing the target are
+    /// neither measured nor removed. Files hard-linked between incremental backups are counted once,
+    /// and only free space once no remaining backup links them. The newest backup and `protected` are always kept.
+    pub async fn cleanup_to_size(target: &Path, source: &Path, max_bytes: u64, protected: Option<&Path>) -> Result<usize> {
+        let backups = Self::list_job_backups(target, source).await?;
+
+        let mut contents = Vec::with_capacity(backups.len());
+        for path in &backups {
+            contents.push(backup_files(path).await
+                .with_context(|| format!("Failed to measure backup {}", path.display()))?);
+        }
+
+        // Size of each distinct file and how many backups hold it
+        let mut files: HashMap<FileKey, (u64, usize)> = HashMap::new();
+        for backup in &contents {
+            for (key, size) in backup {
+                files.entry(*key).or_insert((*size, 0)).1 += 1;
+            }
+        }
+        let mut total: u64 = files.values().map(|(size, _)| size).sum();
+        let mut removed = 0;
+
+        for (path, backup) in backups.iter().zip(&contents).take(backups.len().saturating_sub(1)) {
+            if total <= max_bytes {
+                break;
+            }
+            if is_protected(path, protected) {
+                continue;
+            }
+
+            info!("Removing old backup: {} (backups take {}, budget {})", path.display(), format_bytes(total), format_bytes(max_bytes));
+            Self::remove_backup(path).await?;
+            removed += 1;
+
+            for (key, _) in backup {
+                if let Some((size, holders)) = files.get_mut(key) {
+                    *holders -= 1;
+                    if *holders == 0 {
+                        total -= *size;
+                    }
+                }
+            }
+        }
+
+        if total > max_bytes {
+            warn!("The latest backup in {} alone is larger than its size budget ({} of {})",
+                target.display(), format_bytes(total), format_bytes(max_bytes));
+        }
+
+        Ok(removed)
+    }
+
+    /// Complete backups of the job backing up `source` with the space each takes, oldest
+    /// first, for projecting retention. Hard-linked files are counted once.
+    pub async fn measure_backups(target: &Path, source: &Path) -> Result<Vec<RetainedBackup>> {
+        let backups = Self::list_job_backups(target, source).await?;
+
+        let mut contents = Vec::with_capacity(backups.len());
+        for path in &backups {
+            contents.push(backup_files(path).await
+                .with_context(|| format!("Failed to measure backup {}", path.display()))?);
+        }
+
+        // Oldest and newest backup holding each file
+        let mut holders: HashMap<FileKey, (usize, usize, u64)> = HashMap::new();
+        for (index, backup) in contents.iter().enumerate() {
+            for (key, size) in backup {
+                holders.entry(*key).or_insert((index, index, *size)).1 = index;
+            }
+        }
+
+        let mut measured = Vec::with_capacity(backups.len());
+        for (index, path) in backups.iter().enumerate() {
+            let modified = tokio::fs::metadata(path).await?.modified()?;
+            let sum = |held: fn(&(usize, usize, u64)) -> usize| holders.values()
+                .filter(|holder| held(holder) == index)
+                .map(|holder| holder.2)
+                .sum();
+
+            measured.push(RetainedBackup {
+                name: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                created: modified.into(),
+                freed_bytes: sum(|holder| holder.1),
+                new_bytes: sum(|holder| holder.0),
+            });
+        }
+
+        Ok(measured)
+    }
+
+    /// Delete a complete backup folder or archive
+    async fn remove_backup(path: &Path) -> Result<()> {
+        if path.is_file() {
+            tokio::fs::remove_file(path).await
+        } else {
+            tokio::fs::remove_dir_all(path).await
+        }
+        .context("Failed to remove old backup")
+    }
+
+    /// Remove `_PARTIAL` directories and archives left by failed runs, returning how many were removed
+    pub async fn remove_partial_backups(target: &Path) -> Result<usize> {
+        let mut entries = tokio::fs::read_dir(target).await
+            .context("Failed to read target directory")?;
+        let mut removed = 0;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let is_partial = entry.file_name().to_string_lossy().ends_with("_PARTIAL");
+
+            if !is_partial {
+                continue;
+            }
+
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                info!("Removing partial backup: {}", entry.path().display());
+                tokio::fs::remove_dir_all(entry.path()).await
+                    .context("Failed to remove partial backup")?;
+                removed += 1;
+            } else if file_type.is_file() {
+                info!("Removing partial archive: {}", entry.path().display());
+                tokio::fs::remove_file(entry.path()).await
+                    .context("Failed to remove partial archive")?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
     }
 }
 
+/// Whether `path` is the protected backup; names are compared, as the target may have
+/// been reached under another drive letter when the backup was recorded
+fn is_protected(path: &Path, protected: Option<&Path>) -> bool {
+    protected.is_some_and(|protected| protected.file_name() == path.file_name())
+}
+
+/// Identifies a file on disk, so hard links to it are counted once
+type FileKey = (u64, u64);
+
+/// Files of a backup directory, or the archive file itself, with their sizes
+async fn backup_files(path: &Path) -> Result<Vec<(FileKey, u64)>> {
+    let mut files = Vec::new();
+    let mut stack = vec![path.to_path_buf()];
+
+    while let Some(current) = stack.pop() {
+        let metadata = tokio::fs::symlink_metadata(&current).await?;
+        if metadata.is_dir() {
+            let mut entries = tokio::fs::read_dir(&current).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                stack.push(entry.path());
+            }
+        } else if metadata.is_file() {
+            files.push((file_key(&current, &metadata), metadata.len()));
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn file_key(_path: &Path, metadata: &std::fs::Metadata) -> FileKey {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+/// Volume serial number and file index; a file that cannot be opened counts as distinct
+#[cfg(windows)]
+fn file_key(path: &Path, _metadata: &std::fs::Metadata) -> FileKey {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::{BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle};
+
+    // Opening the file without access rights is enough to query it
+    let opened = std::fs::OpenOptions::new()
+        .access_mode(0)
+        .share_mode(0x1 | 0x2 | 0x4) // FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE
+        .open(path);
+    let Ok(file) = opened else {
+        return distinct_file_key();
+    };
+
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+    match unsafe { GetFileInformationByHandle(HANDLE(file.as_raw_handle()), &mut info) } {
+        Ok(()) => (
+            info.dwVolumeSerialNumber as u64,
+            (info.nFileIndexHigh as u64) << 32 | info.nFileIndexLow as u64,
+        ),
+        Err(_) => distinct_file_key(),
+    }
+}
+
+/// Without a file index, every file counts as distinct
+#[cfg(not(unix))]
+fn distinct_file_key() -> FileKey {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+    (u64::MAX, NEXT_KEY.fetch_add(1, Ordering::Relaxed))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_key(_path: &Path, _metadata: &std::fs::Metadata) -> FileKey {
+    distinct_file_key()
+}
+
 impl Default for BackupOrchestrator {
     fn default() -> Self {
         Self::new()
@@ -246,6 +1116,131 @@ impl Default for BackupOrchestrator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::NameConflicts;
+    use crate::core::{LinkEntry, Rename, RenameReason, RestoreOptions, RestoreOrchestrator};
+    use crate::platform::FaultPlan;
+    use tempfile::tempdir;
+
+    /// Name of a backup of `source` made on day `day` of January 2024
+    fn job_backup_name(source: &Path, day: u32) -> String {
+        let source_name = source.file_name().unwrap().to_string_lossy();
+        format!("{}_2024-01-{:02}_000000_000", BackupOrchestrator::sanitize_backup_name(&source_name), day)
+    }
+
+    async fn create_backup_dir(target: &Path, name: &str, complete: bool) -> PathBuf {
+        let path = target.join(name);
+        tokio::fs::create_dir_all(&path).await.unwrap();
+        if complete {
+            tokio::fs::write(path.join(COMPLETE_MARKER), b"{}").await.unwrap();
+        }
+        path
+    }
+
+    #[tokio::test]
+    async fn test_execute_backup_writes_complete_marker() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("file.txt"), b"data").await.unwrap();
+
+        let orchestrator = BackupOrchestrator::new();
+        let metadata = orchestrator.execute_backup(
+            "test_job",
+            source.path(),
+            target.path(),
+            CancellationToken::new(),
+        ).await.unwrap();
+
+        assert!(metadata.is_complete);
+        assert!(BackupOrchestrator::is_complete_backup(&metadata.backup_path).await);
+    }
+
+    #[tokio::test]
+    async fn test_execute_backup_with_strict_durability() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("file.txt"), b"data").await.unwrap();
+
+        let orchestrator = BackupOrchestrator::with_durability(Durability::Strict);
+        let metadata = orchestrator.execute_backup(
+            "test_job",
+            source.path(),
+            target.path(),
+            CancellationToken::new(),
+        ).await.unwrap();
+
+        assert!(BackupOrchestrator::is_complete_backup(&metadata.backup_path).await);
+    }
+
+    #[tokio::test]
+    async fn test_detect_partial_backups_without_marker() {
+        let target = tempdir().unwrap();
+
+        create_backup_dir(target.path(), "docs_2024-01-01_000000_000", true).await;
+        let unmarked = create_backup_dir(target.path(), "docs_2024-01-02_000000_000", false).await;
+        let renamed = create_backup_dir(target.path(), "docs_2024-01-03_000000_000_PARTIAL", false).await;
+
+        let mut partials = BackupOrchestrator::detect_partial_backups(target.path()).await.unwrap();
+        partials.sort();
+
+        assert_eq!(partials, vec![unmarked, renamed]);
+    }
+
+    #[tokio::test]
+    async fn test_backups_from_before_markers_are_marked_once() {
+        let target = tempdir().unwrap();
+        let source = Path::new("/home/me/docs");
+
+        let old = create_backup_dir(target.path(), "docs_2024-01-01_000000_000", false).await;
+        let unfinished = create_backup_dir(target.path(), "docs_2024-01-02_000000_000", false).await;
+        create_backup_dir(target.path(), "docs_2024-01-03_000000_000_PARTIAL", false).await;
+        create_backup_dir(target.path(), "photos_2024-01-01_000000_000", false).await;
+
+        let marked = BackupOrchestrator::mark_legacy_backups(target.path(), source, std::slice::from_ref(&unfinished)).await.unwrap();
+        assert_eq!(marked, 1);
+        assert_eq!(BackupOrchestrator::list_complete_backups(target.path()).await.unwrap(), vec![old]);
+        assert!(!BackupOrchestrator::is_complete_backup(&unfinished).await);
+
+        assert_eq!(BackupOrchestrator::mark_legacy_backups(target.path(), source, &[]).await.unwrap(), 1, "Only the unfinished one is left");
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_ignores_backups_without_marker() {
+        let target = tempdir().unwrap();
+
+        let unmarked = create_backup_dir(target.path(), "docs_2024-01-01_000000_000", false).await;
+        for day in 2..=4 {
+            create_backup_dir(target.path(), &format!("docs_2024-01-0{}_000000_000", day), true).await;
+        }
+
+        BackupOrchestrator::cleanup_old_backups(target.path(), Path::new("/home/me/docs"), 1, None).await.unwrap();
+
+        let mut remaining = Vec::new();
+        let mut entries = tokio::fs::read_dir(target.path()).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            remaining.push(entry.path());
+        }
+
+        assert_eq!(remaining.len(), 2, "One complete backup plus the unmarked one should remain");
+        assert!(unmarked.exists(), "Incomplete backup must not be removed by retention");
+    }
+
+    #[tokio::test]
+    async fn test_emergency_cleanup_counts_removed_backups() {
+        let target = tempdir().unwrap();
+
+        let partial = create_backup_dir(target.path(), "docs_2024-01-01_000000_000_PARTIAL", false).await;
+        let unmarked = create_backup_dir(target.path(), "docs_2024-01-02_000000_000", false).await;
+        for day in 3..=5 {
+            create_backup_dir(target.path(), &format!("docs_2024-01-0{}_000000_000", day), true).await;
+        }
+
+        assert_eq!(BackupOrchestrator::remove_partial_backups(target.path()).await.unwrap(), 1);
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), Path::new("/home/me/docs"), 1, None).await.unwrap(), 2);
+
+        assert!(!partial.exists());
+        assert!(unmarked.exists(), "Only directories renamed _PARTIAL are removed");
+        assert_eq!(BackupOrchestrator::list_complete_backups(target.path()).await.unwrap().len(), 1);
+    }
 
     #[test]
     fn test_sanitize_backup_name_prevents_path_traversal() {
@@ -300,6 +1295,17 @@ mod tests {
         assert_eq!(sanitized, "backup", "Should return 'backup' for only dots");
     }
 
+    #[test]
+    fn test_backups_are_told_apart_by_source_folder() {
+        let source = Path::new("/data/docs");
+
+        assert!(BackupOrchestrator::is_backup_of(Path::new("/t/docs_2025-01-02_030405_006"), source));
+        assert!(BackupOrchestrator::is_backup_of(Path::new("/t/docs_2025-01-02_030405_006.zip"), source));
+        assert!(!BackupOrchestrator::is_backup_of(Path::new("/t/photos_2025-01-02_030405_006"), source));
+        assert!(!BackupOrchestrator::is_backup_of(Path::new("/t/docs_old_2025-01-02_030405_006"), source));
+        assert!(!BackupOrchestrator::is_backup_of(Path::new("/t/docs_2025-01-02"), source));
+    }
+
     #[test]
     fn test_sanitize_backup_name_trims_dots() {
         // Leading dots
@@ -452,7 +1458,7 @@ mod tests {
 
         // Generate multiple backup names
         let name1 = BackupOrchestrator::generate_backup_name(source);
-        std::thread::sleep(std::time::Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(5));
         let name2 = BackupOrchestrator::generate_backup_name(source);
 
         // Should be different due to microsecond precision
@@ -506,4 +1512,482 @@ mod tests {
         assert_eq!(BackupOrchestrator::sanitize_backup_name("AuX"), "_AuX");
         assert_eq!(BackupOrchestrator::sanitize_backup_name("cOm1"), "_cOm1");
     }
+
+    #[tokio::test]
+    async fn test_execute_backup_writes_manifest() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::create_dir_all(source.path().join("docs")).await.unwrap();
+        tokio::fs::write(source.path().join("docs/a.txt"), b"abc").await.unwrap();
+
+        let orchestrator = BackupOrchestrator::new();
+        let metadata = orchestrator.execute_backup(
+            "job", source.path(), target.path(), CancellationToken::new(),
+        ).await.unwrap();
+
+        let manifest = BackupManifest::load(&metadata.backup_path).await.unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].path, "docs/a.txt");
+        assert_eq!(manifest.files[0].size, 3);
+    }
+
+    #[tokio::test]
+    async fn test_archive_backups_are_listed_and_cleaned_up() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::create_dir_all(source.path().join("docs")).await.unwrap();
+        tokio::fs::write(source.path().join("docs/a.txt"), b"abc").await.unwrap();
+
+        let options = CopyOptions {
+            archive: Some(serde_json::from_str(r#"{ "format": "zip" }"#).unwrap()),
+            ..CopyOptions::default()
+        };
+        let orchestrator = BackupOrchestrator::new();
+        let first = orchestrator.execute_backup_with("job", source.path(), target.path(), &options, CancellationToken::new())
+            .await.unwrap();
+        let second = orchestrator.execute_backup_with("job", source.path(), target.path(), &options, CancellationToken::new())
+            .await.unwrap();
+
+        assert!(first.backup_path.is_file());
+        assert!(first.backup_name.ends_with(".zip"));
+        assert_eq!(second.files_copied, 1);
+        assert_eq!(BackupOrchestrator::list_complete_backups(target.path()).await.unwrap(), vec![first.backup_path.clone(), second.backup_path.clone()]);
+        assert!(BackupOrchestrator::resolve_backup(target.path(), source.path(), "latest").await.is_err(), "Archives cannot be restored in place");
+
+        let partial = target.path().join("docs_2024-01-01_000000_000.zip_PARTIAL");
+        tokio::fs::write(&partial, b"PK").await.unwrap();
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), source.path(), 1, None).await.unwrap(), 1);
+        assert_eq!(BackupOrchestrator::remove_partial_backups(target.path()).await.unwrap(), 1);
+        assert!(!first.backup_path.exists() && !partial.exists());
+        assert!(second.backup_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_to_size_removes_oldest_until_backups_fit() {
+        let target = tempdir().unwrap();
+        let mut backups = Vec::new();
+        for day in 1..=3 {
+            let path = create_backup_dir(target.path(), &format!("docs_2024-01-0{}_000000_000", day), true).await;
+            tokio::fs::write(path.join("data.bin"), vec![day as u8; 1000]).await.unwrap();
+            let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(day * 86400);
+            std::fs::File::open(&path).unwrap().set_modified(modified).unwrap();
+            backups.push(path);
+        }
+
+        // Another job's backup on the same target counts against neither budget
+        let photos = create_backup_dir(target.path(), "photos_2024-01-01_000000_000", true).await;
+        tokio::fs::write(photos.join("data.bin"), vec![0u8; 5000]).await.unwrap();
+
+        let docs = Path::new("/home/me/docs");
+        assert_eq!(BackupOrchestrator::cleanup_to_size(target.path(), docs, 3100, None).await.unwrap(), 0);
+        assert_eq!(BackupOrchestrator::cleanup_to_size(target.path(), docs, 1500, None).await.unwrap(), 2);
+        assert!(!backups[0].exists() && !backups[1].exists());
+
+        // The newest backup stays even when it alone is over the budget
+        assert_eq!(BackupOrchestrator::cleanup_to_size(target.path(), docs, 10, None).await.unwrap(), 0);
+        assert!(backups[2].exists());
+        assert!(photos.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_older_than_keeps_recent_and_newest_backups() {
+        let target = tempdir().unwrap();
+        let docs = Path::new("/home/me/docs");
+        let day = Duration::from_secs(86400);
+        let mut backups = Vec::new();
+        for (i, age_days) in [40, 20, 2].into_iter().enumerate() {
+            let path = create_backup_dir(target.path(), &format!("docs_2024-01-0{}_000000_000", i + 1), true).await;
+            std::fs::File::open(&path).unwrap().set_modified(std::time::SystemTime::now() - day * age_days).unwrap();
+            backups.push(path);
+        }
+
+        // Another job's backup on the same target is neither removed nor the newest of this job
+        let photos = create_backup_dir(target.path(), "photos_2024-01-09_000000_000", true).await;
+        std::fs::File::open(&photos).unwrap().set_modified(std::time::SystemTime::now() - day * 50).unwrap();
+
+        assert_eq!(BackupOrchestrator::cleanup_older_than(target.path(), docs, day * 30, None).await.unwrap(), 1);
+        assert!(!backups[0].exists() && backups[1].exists());
+        assert!(photos.exists());
+
+        // Even when every backup has expired, the newest stays
+        assert_eq!(BackupOrchestrator::cleanup_older_than(target.path(), docs, day, None).await.unwrap(), 1);
+        assert!(!backups[1].exists() && backups[2].exists());
+
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), Path::new("/home/me/docs"), 0, None).await.unwrap(), 0);
+        assert!(backups[2].exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_keeps_last_backup() {
+        let target = tempdir().unwrap();
+        let mut backups = Vec::new();
+        for day in 1..=3u64 {
+            let path = create_backup_dir(target.path(), &format!("docs_2024-01-0{}_000000_000", day), true).await;
+            let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(day * 86400);
+            std::fs::File::open(&path).unwrap().set_modified(modified).unwrap();
+            backups.push(path);
+        }
+
+        // State still points at the oldest backup, e.g. after the newer ones were copied in
+        let last_backup = Path::new("/elsewhere/docs_2024-01-01_000000_000");
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), Path::new("/home/me/docs"), 1, Some(last_backup)).await.unwrap(), 1);
+        assert!(backups[0].exists() && !backups[1].exists() && backups[2].exists());
+
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), Path::new("/home/me/docs"), 1, None).await.unwrap(), 1);
+        assert!(!backups[0].exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_leaves_other_jobs_on_shared_target() {
+        let target = tempdir().unwrap();
+        let docs = Path::new("/home/me/docs");
+        let photos = Path::new("/home/me/photos");
+
+        let only_photos = create_backup_dir(target.path(), &job_backup_name(photos, 1), true).await;
+        let mut docs_backups = Vec::new();
+        for day in 2..=4u32 {
+            let path = create_backup_dir(target.path(), &job_backup_name(docs, day), true).await;
+            let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(u64::from(day) * 86400);
+            std::fs::File::open(&path).unwrap().set_modified(modified).unwrap();
+            docs_backups.push(path);
+        }
+
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), docs, 1, None).await.unwrap(), 2);
+        assert!(only_photos.exists(), "Another job's only backup must survive this job's retention");
+        assert!(!docs_backups[0].exists() && !docs_backups[1].exists() && docs_backups[2].exists());
+
+        assert_eq!(BackupOrchestrator::cleanup_old_backups(target.path(), photos, 1, None).await.unwrap(), 0);
+        assert!(only_photos.exists() && docs_backups[2].exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cleanup_to_size_counts_hard_links_once() {
+        let target = tempdir().unwrap();
+        let mut backups = Vec::<PathBuf>::new();
+        for day in 1..=3 {
+            let path = create_backup_dir(target.path(), &format!("docs_2024-01-0{}_000000_000", day), true).await;
+            if day == 3 {
+                // Incremental backup linking the unchanged file of the one before
+                std::fs::hard_link(backups[1].join("data.bin"), path.join("data.bin")).unwrap();
+            } else {
+                tokio::fs::write(path.join("data.bin"), vec![day as u8; 1000]).await.unwrap();
+            }
+            let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(day * 86400);
+            std::fs::File::open(&path).unwrap().set_modified(modified).unwrap();
+            backups.push(path);
+        }
+
+        // Two distinct files on disk, so removing the oldest is enough
+        assert_eq!(BackupOrchestrator::cleanup_to_size(target.path(), Path::new("/home/me/docs"), 1500, None).await.unwrap(), 1);
+        assert!(!backups[0].exists() && backups[1].exists());
+
+        // Backups of other jobs sharing the target are not measured
+        let photos = create_backup_dir(target.path(), "photos_2024-01-04_000000_000", true).await;
+        let measured = BackupOrchestrator::measure_backups(target.path(), Path::new("/home/me/docs")).await.unwrap();
+        tokio::fs::remove_dir_all(photos).await.unwrap();
+        assert_eq!(measured.iter().map(|b| (b.freed_bytes, b.new_bytes)).collect::<Vec<_>>(), vec![(2, 1002), (1002, 2)], "Completion markers are 2 bytes each");
+
+        // Removing the second frees nothing while the newest links its file
+        assert_eq!(BackupOrchestrator::cleanup_to_size(target.path(), Path::new("/home/me/docs"), 500, None).await.unwrap(), 1);
+        assert_eq!(std::fs::read(backups[2].join("data.bin")).unwrap(), vec![2u8; 1000]);
+    }
+
+    #[tokio::test]
+    async fn test_tuned_copy_copies_files_in_parallel() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::create_dir_all(source.path().join("docs/sub")).await.unwrap();
+        for i in 0..7 {
+            tokio::fs::write(source.path().join(format!("docs/{}.txt", i)), vec![b'x'; i * 1000]).await.unwrap();
+        }
+        tokio::fs::write(source.path().join("docs/sub/last.txt"), b"last").await.unwrap();
+
+        let orchestrator = BackupOrchestrator::new().with_verification(VerifyConfig::Full);
+        let options = CopyOptions {
+            tuning: Some(crate::core::CopyTuning { buffer_size: 4096, parallel_files: 3 }),
+            ..CopyOptions::default()
+        };
+        let metadata = orchestrator.execute_backup_with(
+            "job", source.path(), target.path(), &options, CancellationToken::new(),
+        ).await.unwrap();
+
+        assert_eq!(metadata.files_copied, 8);
+        assert_eq!(metadata.bytes_copied, 21_004);
+        assert_eq!(tokio::fs::read(metadata.backup_path.join("docs/6.txt")).await.unwrap(), vec![b'x'; 6000]);
+        assert_eq!(metadata.tuning, None);
+    }
+
+    #[tokio::test]
+    async fn test_paths_too_long_for_the_target_are_skipped() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        let long_name = format!("{}.txt", "a".repeat(100));
+        tokio::fs::write(source.path().join("b.txt"), b"fits").await.unwrap();
+        tokio::fs::write(source.path().join(&long_name), b"too long").await.unwrap();
+
+        let backup_path = target.path().join(BackupOrchestrator::generate_backup_name(source.path()));
+        let options = CopyOptions {
+            max_path_length: Some(backup_path.as_os_str().len() + 50),
+            ..CopyOptions::default()
+        };
+        let metadata = BackupOrchestrator::new().execute_backup_with(
+            "job", source.path(), target.path(), &options, CancellationToken::new(),
+        ).await.unwrap();
+
+        assert_eq!(metadata.files_copied, 1);
+        assert_eq!(metadata.files_skipped, 1);
+        assert_eq!(metadata.skip_reasons.path_too_long, 1);
+        let manifest = BackupManifest::load(&metadata.backup_path).await.unwrap();
+        assert_eq!(manifest.skipped[0].path, long_name);
+    }
+
+    #[tokio::test]
+    async fn test_previously_skipped_files_are_retried_once() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::create_dir_all(source.path().join("docs")).await.unwrap();
+        tokio::fs::write(source.path().join("docs/a.txt"), b"abc").await.unwrap();
+        tokio::fs::write(source.path().join("docs/b.txt"), b"was locked").await.unwrap();
+
+        let outside = tempdir().unwrap();
+        tokio::fs::write(outside.path().join("secret.txt"), b"not part of the job").await.unwrap();
+
+        // An edited manifest must not make the retry read or write outside both folders
+        let previous = create_backup_dir(target.path(), &job_backup_name(source.path(), 1), true).await;
+        let escaping = outside.path().join("secret.txt");
+        BackupManifest::new("previous".into(), Vec::new())
+            .with_skipped(vec![
+                SkippedEntry::new(Path::new("docs/b.txt"), Path::new("docs/b.txt"), None, CopyFailure::SourceUnreadable),
+                SkippedEntry::new(Path::new("../stolen.txt"), &escaping, None, CopyFailure::SourceUnreadable),
+            ])
+            .write(&previous)
+            .await
+            .unwrap();
+
+        // Another job's backup in the same target is not this job's previous run
+        let other = create_backup_dir(target.path(), "photos_2024-01-02_000000_000", true).await;
+        BackupManifest::new("other".into(), Vec::new())
+            .with_skipped(vec![SkippedEntry::new(Path::new("c.txt"), Path::new("c.txt"), None, CopyFailure::SourceUnreadable)])
+            .write(&other)
+            .await
+            .unwrap();
+
+        let orchestrator = BackupOrchestrator::new();
+        let metadata = orchestrator.execute_backup(
+            "job", source.path(), target.path(), CancellationToken::new(),
+        ).await.unwrap();
+
+        let manifest = BackupManifest::load(&metadata.backup_path).await.unwrap();
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["docs/a.txt", "docs/b.txt"]);
+        assert!(manifest.skipped.is_empty());
+        assert_eq!(metadata.files_copied, 2);
+        assert!(!target.path().join("stolen.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_backup_links_unchanged_files() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("same.txt"), b"same").await.unwrap();
+        tokio::fs::write(source.path().join("changed.txt"), b"old").await.unwrap();
+
+        let orchestrator = BackupOrchestrator::new().with_checksums(true);
+        let options = CopyOptions { mode: BackupMode::Incremental, ..CopyOptions::default() };
+        let first = orchestrator.execute_backup_with(
+            "job", source.path(), target.path(), &options, CancellationToken::new(),
+        ).await.unwrap();
+        assert_eq!((first.files_copied, first.files_unchanged), (2, 0));
+
+        tokio::fs::write(source.path().join("changed.txt"), b"new contents").await.unwrap();
+        tokio::fs::write(source.path().join("added.txt"), b"added").await.unwrap();
+
+        // A newer backup of another job sharing the target is not a baseline
+        let other = create_backup_dir(target.path(), "photos_2099-01-01_000000_000", true).await;
+        let newer = std::time::SystemTime::now() + Duration::from_secs(3600);
+        std::fs::File::open(&other).unwrap().set_modified(newer).unwrap();
+
+        let second = orchestrator.execute_backup_with(
+            "job", source.path(), target.path(), &options, CancellationToken::new(),
+        ).await.unwrap();
+        assert_eq!((second.files_copied, second.files_unchanged), (2, 1));
+        assert_eq!(tokio::fs::read(second.backup_path.join("changed.txt")).await.unwrap(), b"new contents");
+
+        let first_manifest = BackupManifest::load(&first.backup_path).await.unwrap();
+        let second_manifest = BackupManifest::load(&second.backup_path).await.unwrap();
+        assert_eq!(second_manifest.files.len(), 3);
+        let same = |m: &BackupManifest| m.files.iter().find(|f| f.path == "same.txt").cloned().unwrap();
+        assert_eq!(same(&second_manifest), same(&first_manifest));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |backup: &Path| std::fs::metadata(backup.join("same.txt")).unwrap().ino();
+            assert_eq!(inode(&first.backup_path), inode(&second.backup_path));
+        }
+
+        // Removing the first backup leaves the second one whole
+        tokio::fs::remove_dir_all(&first.backup_path).await.unwrap();
+        assert_eq!(tokio::fs::read(second.backup_path.join("same.txt")).await.unwrap(), b"same");
+    }
+
+    #[tokio::test]
+    async fn test_injected_read_failure_is_recovered_on_second_pass() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            tokio::fs::write(source.path().join(name), name).await.unwrap();
+        }
+
+        let faults = FaultInjector::new(FaultPlan { fail_nth: Some(2), ..FaultPlan::default() });
+        let orchestrator = BackupOrchestrator::new().with_faults(Some(Arc::new(faults)));
+        let metadata = orchestrator.execute_backup(
+            "job", source.path(), target.path(), CancellationToken::new(),
+        ).await.unwrap();
+
+        assert_eq!(metadata.files_copied, 3);
+        let manifest = BackupManifest::load(&metadata.backup_path).await.unwrap();
+        assert_eq!(manifest.files.len(), 3);
+        assert!(manifest.skipped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_injected_full_target_removes_the_backup() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            tokio::fs::write(source.path().join(name), name).await.unwrap();
+        }
+        let previous = create_backup_dir(target.path(), "docs_1", true).await;
+
+        let faults = FaultInjector::new(FaultPlan { full_from: Some(2), ..FaultPlan::default() });
+        let orchestrator = BackupOrchestrator::new().with_faults(Some(Arc::new(faults)));
+        let error = orchestrator.execute_backup(
+            "job", source.path(), target.path(), CancellationToken::new(),
+        ).await.unwrap_err();
+
+        assert_eq!(CopyError::classify(&error), CopyFailure::DiskFull);
+        let mut entries = tokio::fs::read_dir(target.path()).await.unwrap();
+        let mut remaining = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            if entry.file_name() != TARGET_MARKER {
+                remaining.push(entry.path());
+            }
+        }
+        assert_eq!(remaining, vec![previous]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_links_are_recorded_not_followed() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("a.txt"), b"abc").await.unwrap();
+        std::os::unix::fs::symlink(source.path(), source.path().join("Application Data")).unwrap();
+        std::os::unix::fs::symlink("a.txt", source.path().join("shortcut")).unwrap();
+
+        let orchestrator = BackupOrchestrator::new();
+        let metadata = orchestrator.execute_backup(
+            "job", source.path(), target.path(), CancellationToken::new(),
+        ).await.unwrap();
+
+        let manifest = BackupManifest::load(&metadata.backup_path).await.unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.links, vec![
+            LinkEntry::new(Path::new("Application Data"), Some(source.path()), true),
+            LinkEntry::new(Path::new("shortcut"), Some(Path::new("a.txt")), false),
+        ]);
+        assert!(!metadata.backup_path.join("Application Data").exists());
+    }
+
+    #[tokio::test]
+    async fn test_backup_areas_inside_the_source_are_not_copied() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("a.txt"), b"abc").await.unwrap();
+
+        // Another job's target, mounted inside the source
+        let orchestrator = BackupOrchestrator::new();
+        let other_target = source.path().join("mnt");
+        tokio::fs::create_dir_all(&other_target).await.unwrap();
+        orchestrator.execute_backup("other", target.path(), &other_target, CancellationToken::new()).await.unwrap();
+        assert!(other_target.join(TARGET_MARKER).exists());
+
+        // A backup folder copied into the source by hand
+        let old_backup = source.path().join("old");
+        tokio::fs::create_dir_all(&old_backup).await.unwrap();
+        tokio::fs::write(old_backup.join(COMPLETE_MARKER), b"{}").await.unwrap();
+
+        let metadata = orchestrator.execute_backup(
+            "job", source.path(), target.path(), CancellationToken::new(),
+        ).await.unwrap();
+
+        let manifest = BackupManifest::load(&metadata.backup_path).await.unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert!(!metadata.backup_path.join("mnt").exists());
+        assert!(!metadata.backup_path.join("old").exists());
+
+        // Recorded as skipped, so the run is partial rather than silently incomplete
+        assert_eq!(metadata.skip_reasons.backup_area, 2);
+        let mut skipped: Vec<&str> = manifest.skipped.iter().map(|s| s.path.as_str()).collect();
+        skipped.sort();
+        assert_eq!(skipped, ["mnt", "old"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_conflicting_names_are_renamed_and_restored() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::create_dir_all(source.path().join("aux")).await.unwrap();
+        tokio::fs::write(source.path().join("aux/a.txt"), b"lower").await.unwrap();
+        tokio::fs::write(source.path().join("aux/A.txt"), b"upper").await.unwrap();
+
+        let orchestrator = BackupOrchestrator::new().with_verification(VerifyConfig::Full);
+        let options = CopyOptions { name_conflicts: NameConflicts::Suffix, ..CopyOptions::default() };
+        let metadata = orchestrator.execute_backup_with(
+            "job", source.path(), target.path(), &options, CancellationToken::new(),
+        ).await.unwrap();
+
+        let backup = &metadata.backup_path;
+        assert_eq!(tokio::fs::read(backup.join("aux_/a (2).txt")).await.unwrap(), b"lower");
+        assert_eq!(tokio::fs::read(backup.join("aux_/A.txt")).await.unwrap(), b"upper");
+
+        let renames: Vec<Rename> = serde_json::from_slice(&tokio::fs::read(backup.join(RENAMES_FILE)).await.unwrap()).unwrap();
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames[0].reason, RenameReason::ReservedName);
+        assert_eq!((renames[1].original.as_str(), renames[1].renamed.as_str()), ("aux/a.txt", "aux_/a (2).txt"));
+
+        let destination = target.path().join("restored");
+        RestoreOrchestrator::new()
+            .restore(backup, &destination, RestoreOptions::default(), CancellationToken::new())
+            .await.unwrap();
+        assert_eq!(tokio::fs::read(destination.join("aux/a.txt")).await.unwrap(), b"lower");
+        assert_eq!(tokio::fs::read(destination.join("aux/A.txt")).await.unwrap(), b"upper");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_backup_latest_and_previous() {
+        let target = tempdir().unwrap();
+        let docs = Path::new("/home/me/docs");
+        let first = create_backup_dir(target.path(), &job_backup_name(docs, 1), true).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let second = create_backup_dir(target.path(), &job_backup_name(docs, 2), true).await;
+        create_backup_dir(target.path(), &job_backup_name(docs, 3), false).await;
+
+        // Written last by another job sharing the target
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let photos = create_backup_dir(target.path(), &job_backup_name(Path::new("/home/me/photos"), 4), true).await;
+
+        let latest = BackupOrchestrator::resolve_backup(target.path(), docs, "latest").await.unwrap();
+        let previous = BackupOrchestrator::resolve_backup(target.path(), docs, "previous").await.unwrap();
+        let by_name = BackupOrchestrator::resolve_backup(target.path(), docs, &job_backup_name(docs, 1)).await.unwrap();
+
+        assert_eq!(latest, second);
+        assert_eq!(previous, first);
+        assert_eq!(by_name, first);
+        assert_eq!(BackupOrchestrator::resolve_backup(target.path(), docs, photos.to_str().unwrap()).await.unwrap(), photos);
+        assert!(BackupOrchestrator::resolve_backup(target.path(), docs, "missing").await.is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,715 @@
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::{BackupJob, Durability, ReplicaServerConfig};
+use crate::core::backup::{BackupOrchestrator, COMPLETE_MARKER};
+use crate::core::replication::{backup_timestamp, list_files};
+use crate::core::{hmac_sha256, is_archive_backup, to_hex, BandwidthLimiter, Sha256};
+use crate::observability::format_bytes;
+use crate::platform::sync_directory;
+use crate::state::BackupMetadata;
+use crate::storage::TargetUrl;
+
+/// URL scheme of job sources that pull from another instance's replica server
+pub const PULL_SCHEME: &str = "keephive";
+
+/// Port used when a `keephive://` source does not name one
+pub const DEFAULT_PORT: u16 = 7443;
+
+const PROTOCOL_VERSION: u32 = 2;
+
+/// Largest message line accepted from the other side
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+
+/// Connections that stay silent this long are dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Shorter tokens are too easy to guess
+const MIN_TOKEN_LEN: usize = 16;
+
+const DOWNLOAD_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Jobs the replica server hands out, by id, with their targets
+pub type ExposedJobs = Arc<RwLock<HashMap<String, PathBuf>>>;
+
+/// First line sent by the server
+#[derive(Debug, Serialize, Deserialize)]
+struct Greeting {
+    version: u32,
+    nonce: String,
+}
+
+/// Client request (one JSON object per line)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Request {
+    /// Prove knowledge of the token: HMAC over both nonces
+    Hello { version: u32, nonce: String, proof: String },
+
+    /// Complete backups of a job, oldest first
+    List { job: String },
+
+    /// Files of a backup, followed by `length` [`FileEntry`] lines
+    Files { job: String, backup: String },
+
+    /// One file, followed by `length` raw bytes and a [`Response`] carrying their SHA-256
+    Get { job: String, backup: String, path: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+    ok: bool,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    message: String,
+
+    /// Server's proof of the token, in reply to `Hello`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proof: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    backups: Vec<String>,
+
+    /// Entries or bytes following this line
+    #[serde(default)]
+    length: u64,
+
+    /// Hex SHA-256 of the bytes just sent, in the trailer after a file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+impl Response {
+    fn error(message: impl Into<String>) -> Self {
+        Self { message: message.into(), ..Self::default() }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileEntry {
+    /// `/`-separated path inside the backup
+    path: String,
+    size: u64,
+}
+
+/// Line-delimited JSON over TCP, with raw file bytes in between
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self { reader: BufReader::new(reader), writer }
+    }
+
+    async fn send<T: Serialize>(&mut self, message: &T) -> Result<()> {
+        let mut json = serde_json::to_string(message)?;
+        json.push('\n');
+        self.writer.write_all(json.as_bytes()).await
+            .context("Failed to send to replica peer")
+    }
+
+    /// Next message, or None once the peer has closed the connection
+    async fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        let mut line = String::new();
+        let read = tokio::time::timeout(IDLE_TIMEOUT, (&mut self.reader).take(MAX_LINE_BYTES).read_line(&mut line))
+            .await
+            .context("Replica peer timed out")?
+            .context("Failed to read from replica peer")?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+        serde_json::from_str(line.trim())
+            .map(Some)
+            .context("Invalid message from replica peer")
+    }
+
+    /// Server reply to the last request, failing on errors it reports
+    async fn response(&mut self) -> Result<Response> {
+        let response: Response = self.receive().await?
+            .context("Replica server closed the connection")?;
+        if !response.ok {
+            bail!("Replica server: {}", response.message);
+        }
+        Ok(response)
+    }
+}
+
+/// Read the shared token from the environment
+pub fn read_token(variable: &str) -> Result<String> {
+    let token = std::env::var(variable)
+        .with_context(|| format!("Replica token variable {} is not set", variable))?;
+    if token.len() < MIN_TOKEN_LEN {
+        bail!("Replica token in {} is too short (at least {} characters)", variable, MIN_TOKEN_LEN);
+    }
+    Ok(token)
+}
+
+/// Targets of the jobs a replica server exposes
+pub fn exposed_jobs(server: &ReplicaServerConfig, jobs: &[BackupJob]) -> HashMap<String, PathBuf> {
+    jobs.iter()
+        .filter(|job| server.jobs.is_empty() || server.jobs.contains(&job.id))
+        .map(|job| (job.id.clone(), job.target.clone()))
+        .collect()
+}
+
+/// Serves completed backups to other instances that pull them.
+///
+/// Both sides prove they know the shared token with an HMAC over fresh nonces, so
+/// the token never crosses the network. Transfers are not encrypted, so the server only
+/// listens on loopback (for an SSH tunnel) unless `allow_lan` opts into a trusted LAN.
+pub struct ReplicaServer {
+    listen: String,
+    token: Arc<str>,
+    jobs: ExposedJobs,
+}
+
+impl ReplicaServer {
+    pub fn new(config: &ReplicaServerConfig, jobs: ExposedJobs) -> Result<Self> {
+        if !config.allow_lan && !is_loopback(&config.listen) {
+            bail!(
+                "Replica server would listen on {} without encryption; use a loopback address or set allow_lan on a trusted network",
+                config.listen
+            );
+        }
+
+        Ok(Self {
+            listen: config.listen.clone(),
+            token: read_token(&config.token_env)?.into(),
+            jobs,
+        })
+    }
+
+    /// Serve connections until cancelled
+    pub async fn serve(self, cancellation: CancellationToken) -> Result<()> {
+        let listener = TcpListener::bind(&self.listen).await
+            .with_context(|| format!("Failed to listen on {}", self.listen))?;
+
+        info!("Replica server listening on {}", self.listen);
+        self.accept(listener, cancellation).await;
+
+        debug!("Replica server stopped");
+        Ok(())
+    }
+
+    async fn accept(self, listener: TcpListener, cancellation: CancellationToken) {
+        loop {
+            tokio::select! {
+                _ = cancellation.cancelled() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        let token = self.token.clone();
+                        let jobs = self.jobs.clone();
+                        let cancellation = cancellation.clone();
+                        tokio::spawn(async move {
+                            tokio::select! {
+                                result = serve_client(stream, &token, &jobs) => {
+                                    if let Err(e) = result {
+                                        warn!("Replica client {}: {}", peer, e);
+                                    }
+                                }
+                                _ = cancellation.cancelled() => {}
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept replica connection: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Whether every address `listen` resolves to is a loopback address
+fn is_loopback(listen: &str) -> bool {
+    use std::net::ToSocketAddrs;
+
+    match listen.to_socket_addrs() {
+        Ok(addresses) => {
+            let addresses: Vec<_> = addresses.collect();
+            !addresses.is_empty() && addresses.iter().all(|address| address.ip().is_loopback())
+        }
+        Err(_) => false,
+    }
+}
+
+async fn serve_client(stream: TcpStream, token: &str, jobs: &ExposedJobs) -> Result<()> {
+    let mut connection = Connection::new(stream);
+
+    let server_nonce = nonce()?;
+    connection.send(&Greeting { version: PROTOCOL_VERSION, nonce: server_nonce.clone() }).await?;
+
+    let Some(Request::Hello { version, nonce: client_nonce, proof }) = connection.receive().await? else {
+        bail!("Client did not authenticate");
+    };
+    if version != PROTOCOL_VERSION {
+        connection.send(&Response::error(format!("Unsupported protocol version {}", version))).await?;
+        bail!("Unsupported protocol version {}", version);
+    }
+    if !same_proof(&proof, &make_proof(token, "client", &server_nonce, &client_nonce)) {
+        connection.send(&Response::error("Authentication failed")).await?;
+        bail!("Authentication failed");
+    }
+
+    connection.send(&Response {
+        ok: true,
+        proof: Some(make_proof(token, "server", &client_nonce, &server_nonce)),
+        ..Response::default()
+    }).await?;
+
+    while let Some(request) = connection.receive::<Request>().await? {
+        debug!("Replica request: {:?}", request);
+
+        match request {
+            Request::Hello { .. } => connection.send(&Response::error("Already authenticated")).await?,
+
+            Request::List { job } => match list_backups(jobs, &job).await {
+                Ok(backups) => connection.send(&Response { ok: true, backups, ..Response::default() }).await?,
+                Err(e) => connection.send(&Response::error(e.to_string())).await?,
+            },
+
+            Request::Files { job, backup } => match backup_files(jobs, &job, &backup).await {
+                Ok(files) => {
+                    connection.send(&Response { ok: true, length: files.len() as u64, ..Response::default() }).await?;
+                    for file in &files {
+                        connection.send(file).await?;
+                    }
+                }
+                Err(e) => connection.send(&Response::error(e.to_string())).await?,
+            },
+
+            Request::Get { job, backup, path } => match open_file(jobs, &job, &backup, &path).await {
+                Ok((file, size)) => {
+                    connection.send(&Response { ok: true, length: size, ..Response::default() }).await?;
+                    let sha256 = send_file(&mut connection, file, size).await
+                        .with_context(|| format!("Failed to send {}", path))?;
+                    connection.send(&Response { ok: true, sha256: Some(sha256), ..Response::default() }).await?;
+                }
+                Err(e) => connection.send(&Response::error(e.to_string())).await?,
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Stream `size` bytes of `file`, hashing what actually went over the wire
+async fn send_file(connection: &mut Connection, mut file: tokio::fs::File, size: u64) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; DOWNLOAD_BUFFER_SIZE];
+    let mut remaining = size;
+
+    while remaining > 0 {
+        let want = remaining.min(buffer.len() as u64) as usize;
+        let read = file.read(&mut buffer[..want]).await?;
+        if read == 0 {
+            bail!("File shrank while it was sent");
+        }
+
+        hasher.update(&buffer[..read]);
+        connection.writer.write_all(&buffer[..read]).await?;
+        remaining -= read as u64;
+    }
+
+    Ok(to_hex(&hasher.finish()))
+}
+
+async fn list_backups(jobs: &ExposedJobs, job: &str) -> Result<Vec<String>> {
+    let target = jobs.read().unwrap().get(job).cloned()
+        .with_context(|| format!("Job {} is not shared by this server", job))?;
+
+    // Archives are single files, which replicas do not pull
+    let mut backups: Vec<String> = BackupOrchestrator::list_complete_backups(&target).await?
+        .iter()
+        .filter(|path| !is_archive_backup(path))
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+
+    // Names carry the creation time, which survives copies that reset modification times
+    backups.sort_by(|a, b| backup_timestamp(a).cmp(backup_timestamp(b)));
+    Ok(backups)
+}
+
+/// Path of a complete backup of an exposed job
+async fn backup_path(jobs: &ExposedJobs, job: &str, backup: &str) -> Result<PathBuf> {
+    if !list_backups(jobs, job).await?.iter().any(|name| name == backup) {
+        bail!("No complete backup {} of job {}", backup, job);
+    }
+    let target = jobs.read().unwrap().get(job).cloned()
+        .with_context(|| format!("Job {} is not shared by this server", job))?;
+    Ok(target.join(backup))
+}
+
+async fn backup_files(jobs: &ExposedJobs, job: &str, backup: &str) -> Result<Vec<FileEntry>> {
+    let root = backup_path(jobs, job, backup).await?;
+
+    // The marker goes last, so an interrupted pull never looks complete
+    let mut files = list_files(&root).await?;
+    files.sort_by_key(|(_, relative)| relative == COMPLETE_MARKER);
+
+    let mut entries = Vec::with_capacity(files.len());
+    for (local, path) in files {
+        let size = tokio::fs::metadata(&local).await?.len();
+        entries.push(FileEntry { path, size });
+    }
+    Ok(entries)
+}
+
+async fn open_file(jobs: &ExposedJobs, job: &str, backup: &str, path: &str) -> Result<(tokio::fs::File, u64)> {
+    let local = safe_join(&backup_path(jobs, job, backup).await?, path)?;
+    let file = tokio::fs::File::open(&local).await
+        .with_context(|| format!("Failed to open {}", path))?;
+    let size = file.metadata().await?.len();
+    Ok((file, size))
+}
+
+/// Job source of the form `keephive://host[:port]/job`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullSource {
+    /// `host:port`
+    pub address: String,
+
+    /// Job id on the replica server
+    pub job: String,
+}
+
+impl PullSource {
+    pub fn parse(source: &Path) -> Option<Self> {
+        let url = TargetUrl::from_path(source).filter(|url| url.scheme == PULL_SCHEME)?;
+        let (authority, job) = url.location.split_once('/')?;
+        let job = job.trim_matches('/');
+
+        if authority.is_empty() || job.is_empty() {
+            return None;
+        }
+
+        let has_port = authority.rsplit_once(':')
+            .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+        let address = if has_port {
+            authority.to_string()
+        } else {
+            format!("{}:{}", authority, DEFAULT_PORT)
+        };
+
+        Some(Self { address, job: job.to_string() })
+    }
+}
+
+/// Authenticated connection to a replica server
+pub struct PullClient {
+    connection: Connection,
+}
+
+impl PullClient {
+    pub async fn connect(address: &str, token: &str) -> Result<Self> {
+        let stream = tokio::time::timeout(IDLE_TIMEOUT, TcpStream::connect(address)).await
+            .with_context(|| format!("Timed out connecting to replica server {}", address))?
+            .with_context(|| format!("Cannot reach replica server {}", address))?;
+        let mut connection = Connection::new(stream);
+
+        let greeting: Greeting = connection.receive().await?
+            .context("Replica server closed the connection")?;
+        if greeting.version != PROTOCOL_VERSION {
+            bail!("Replica server {} speaks protocol version {}, expected {}", address, greeting.version, PROTOCOL_VERSION);
+        }
+
+        let client_nonce = nonce()?;
+        connection.send(&Request::Hello {
+            version: PROTOCOL_VERSION,
+            nonce: client_nonce.clone(),
+            proof: make_proof(token, "client", &greeting.nonce, &client_nonce),
+        }).await?;
+
+        let response = connection.response().await?;
+        let expected = make_proof(token, "server", &client_nonce, &greeting.nonce);
+        if !response.proof.as_deref().is_some_and(|proof| same_proof(proof, &expected)) {
+            bail!("Replica server {} does not know the shared token", address);
+        }
+
+        Ok(Self { connection })
+    }
+
+    /// Complete backups of a remote job, oldest first
+    pub async fn list(&mut self, job: &str) -> Result<Vec<String>> {
+        self.connection.send(&Request::List { job: job.to_string() }).await?;
+        Ok(self.connection.response().await?.backups)
+    }
+
+    async fn files(&mut self, job: &str, backup: &str) -> Result<Vec<FileEntry>> {
+        self.connection.send(&Request::Files { job: job.to_string(), backup: backup.to_string() }).await?;
+        let count = self.connection.response().await?.length;
+
+        let mut files = Vec::new();
+        for _ in 0..count {
+            files.push(self.connection.receive().await?
+                .context("Replica server closed the connection")?);
+        }
+        Ok(files)
+    }
+
+    async fn download(
+        &mut self,
+        job: &str,
+        backup: &str,
+        path: &str,
+        local: &Path,
+        limiter: &BandwidthLimiter,
+    ) -> Result<u64> {
+        self.connection.send(&Request::Get {
+            job: job.to_string(),
+            backup: backup.to_string(),
+            path: path.to_string(),
+        }).await?;
+        let size = self.connection.response().await?.length;
+
+        let mut file = tokio::fs::File::create(local).await
+            .with_context(|| format!("Failed to create {}", local.display()))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; DOWNLOAD_BUFFER_SIZE];
+        let mut remaining = size;
+
+        while remaining > 0 {
+            let want = remaining.min(buffer.len() as u64) as usize;
+            let read = tokio::time::timeout(IDLE_TIMEOUT, self.connection.reader.read(&mut buffer[..want])).await
+                .context("Replica server timed out")??;
+            if read == 0 {
+                bail!("Replica server closed the connection during {}", path);
+            }
+
+            hasher.update(&buffer[..read]);
+            file.write_all(&buffer[..read]).await
+                .with_context(|| format!("Failed to write {}", local.display()))?;
+            limiter.consume(read as u64).await;
+            remaining -= read as u64;
+        }
+
+        let expected = self.connection.response().await?.sha256;
+        if expected.as_deref() != Some(to_hex(&hasher.finish()).as_str()) {
+            bail!("{} was corrupted in transit (checksum mismatch)", path);
+        }
+
+        file.sync_all().await?;
+        Ok(size)
+    }
+}
+
+/// Copy the newest complete backup of a remote job into `target`.
+///
+/// Returns None when `target` already holds it. The copy is staged as
+/// `<name>_PARTIAL` and renamed once every file arrived.
+pub async fn pull_latest(
+    source: &PullSource,
+    target: &Path,
+    token: &str,
+    limiter: &BandwidthLimiter,
+    durability: Durability,
+    cancellation: CancellationToken,
+) -> Result<Option<BackupMetadata>> {
+    let mut client = PullClient::connect(&source.address, token).await?;
+
+    let Some(latest) = client.list(&source.job).await?.pop() else {
+        bail!("Replica server {} has no complete backups of job {}", source.address, source.job);
+    };
+
+    // Names come from the other machine; never let them leave the target
+    let backup_path = safe_join(target, &latest)?;
+    if BackupOrchestrator::is_complete_backup(&backup_path).await {
+        debug!("Already have {} from {}", latest, source.address);
+        return Ok(None);
+    }
+
+    let staging = target.join(format!("{}_PARTIAL", latest));
+    if staging.exists() {
+        tokio::fs::remove_dir_all(&staging).await?;
+    }
+    tokio::fs::create_dir_all(&staging).await
+        .with_context(|| format!("Failed to create {}", staging.display()))?;
+
+    info!("Pulling {} from {}", latest, source.address);
+
+    let transfer = async {
+        let files = client.files(&source.job, &latest).await?;
+        let mut bytes = 0;
+
+        for entry in &files {
+            let local = safe_join(&staging, &entry.path)?;
+            if let Some(parent) = local.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            bytes += client.download(&source.job, &latest, &entry.path, &local, limiter).await?;
+        }
+        anyhow::Ok((files.len() as u64, bytes))
+    };
+
+    let result = tokio::select! {
+        result = transfer => result,
+        _ = cancellation.cancelled() => Err(anyhow::anyhow!("Pull cancelled")),
+    };
+
+    let (files, bytes) = match result {
+        Ok(totals) => totals,
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&staging).await;
+            return Err(e);
+        }
+    };
+
+    if durability == Durability::Strict {
+        sync_directory(&staging).await?;
+    }
+    if backup_path.exists() {
+        warn!("Replacing incomplete backup {}", backup_path.display());
+        tokio::fs::remove_dir_all(&backup_path).await?;
+    }
+    tokio::fs::rename(&staging, &backup_path).await
+        .context("Failed to finalize pulled backup")?;
+    if durability == Durability::Strict {
+        sync_directory(target).await?;
+    }
+
+    let mut metadata = BackupMetadata::new(latest.clone(), backup_path.clone());
+    metadata.files_copied = files;
+    metadata.bytes_copied = bytes;
+
+    // Carry over what the original run recorded about skipped files
+    if let Ok(json) = tokio::fs::read(backup_path.join(COMPLETE_MARKER)).await
+        && let Ok(original) = serde_json::from_slice::<BackupMetadata>(&json)
+    {
+        metadata.files_skipped = original.files_skipped;
+        metadata.skip_reasons = original.skip_reasons;
+        metadata.verification = original.verification;
+    }
+    metadata.mark_complete();
+
+    info!("Pulled {} from {} ({} files, {})", latest, source.address, files, format_bytes(bytes));
+    Ok(Some(metadata))
+}
+
+/// Join a `/`-separated relative path received from a peer, refusing anything that escapes `root`
+fn safe_join(root: &Path, relative: &str) -> Result<PathBuf> {
+    let mut path = root.to_path_buf();
+    for part in relative.split('/') {
+        if part.is_empty() || part == "." || part == ".." || part.contains(['\\', ':']) {
+            bail!("Invalid path from replica peer: {}", relative);
+        }
+        path.push(part);
+    }
+    Ok(path)
+}
+
+fn make_proof(token: &str, role: &str, first_nonce: &str, second_nonce: &str) -> String {
+    let message = format!("keephive-replica\n{}\n{}\n{}", role, first_nonce, second_nonce);
+    to_hex(&hmac_sha256(token.as_bytes(), message.as_bytes()))
+}
+
+/// Compare proofs without revealing where they differ
+fn same_proof(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// 32 bytes from the OS random source, hex-encoded
+fn nonce() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).context("Failed to get random bytes for a nonce")?;
+    Ok(to_hex(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const TOKEN: &str = "correct horse battery staple";
+
+    async fn start_server(target: &Path) -> (String, CancellationToken) {
+        let jobs: ExposedJobs = Arc::new(RwLock::new(HashMap::from([("docs".to_string(), target.to_path_buf())])));
+        let server = ReplicaServer { listen: String::new(), token: TOKEN.into(), jobs };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let cancellation = CancellationToken::new();
+        tokio::spawn(server.accept(listener, cancellation.clone()));
+
+        (address, cancellation)
+    }
+
+    #[tokio::test]
+    async fn test_pull_latest_backup() {
+        let primary = tempdir().unwrap();
+        let offsite = tempdir().unwrap();
+
+        for name in ["docs_2025-01-01_000000_000", "docs_2025-01-02_000000_000"] {
+            let backup = primary.path().join(name);
+            tokio::fs::create_dir_all(backup.join("sub")).await.unwrap();
+            tokio::fs::write(backup.join("sub/a.txt"), name).await.unwrap();
+            tokio::fs::write(backup.join(COMPLETE_MARKER), b"{}").await.unwrap();
+        }
+
+        let (address, cancellation) = start_server(primary.path()).await;
+        let source = PullSource { address: address.clone(), job: "docs".to_string() };
+        let limiter = BandwidthLimiter::default();
+
+        let metadata = pull_latest(&source, offsite.path(), TOKEN, &limiter, Durability::Normal, CancellationToken::new())
+            .await.unwrap().unwrap();
+        assert_eq!(metadata.backup_name, "docs_2025-01-02_000000_000");
+        assert_eq!(metadata.files_copied, 2);
+
+        let pulled = offsite.path().join("docs_2025-01-02_000000_000");
+        assert_eq!(tokio::fs::read_to_string(pulled.join("sub/a.txt")).await.unwrap(), "docs_2025-01-02_000000_000");
+        assert!(BackupOrchestrator::is_complete_backup(&pulled).await);
+        assert!(!offsite.path().join("docs_2025-01-01_000000_000").exists(), "Only the newest backup is pulled");
+
+        let again = pull_latest(&source, offsite.path(), TOKEN, &limiter, Durability::Normal, CancellationToken::new())
+            .await.unwrap();
+        assert!(again.is_none());
+
+        assert!(PullClient::connect(&address, "wrong token, same length!!!").await.is_err());
+
+        let mut client = PullClient::connect(&address, TOKEN).await.unwrap();
+        assert!(client.list("other").await.is_err());
+        assert_eq!(client.list("docs").await.unwrap().len(), 2);
+
+        cancellation.cancel();
+    }
+
+    #[test]
+    fn test_server_refuses_network_addresses_without_allow_lan() {
+        assert!(is_loopback("127.0.0.1:7443"));
+        assert!(is_loopback("[::1]:7443"));
+        assert!(!is_loopback("0.0.0.0:7443"));
+        assert!(!is_loopback("192.168.1.10:7443"));
+
+        assert_ne!(nonce().unwrap(), nonce().unwrap());
+    }
+
+    #[test]
+    fn test_parse_pull_source() {
+        let source = PullSource::parse(Path::new("keephive://nas.example.com/documents")).unwrap();
+        assert_eq!(source.address, "nas.example.com:7443");
+        assert_eq!(source.job, "documents");
+
+        let source = PullSource::parse(Path::new("keephive://10.0.0.2:9000/docs/")).unwrap();
+        assert_eq!(source.address, "10.0.0.2:9000");
+        assert_eq!(source.job, "docs");
+
+        assert_eq!(PullSource::parse(Path::new("keephive://nas")), None);
+        assert_eq!(PullSource::parse(Path::new(r"C:\Data")), None);
+
+        assert!(safe_join(Path::new("/t"), "../etc").is_err());
+        assert!(safe_join(Path::new("/t"), "a//b").is_err());
+        assert!(safe_join(Path::new("/t"), "a/b").is_ok());
+    }
+}
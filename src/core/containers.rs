@@ -0,0 +1,151 @@
+//! `wsl-distro` and `docker-volume` jobs. Copying the files of a running distribution
+//! or volume gives an inconsistent backup, so both are exported as a tar file by their
+//! own tools and backed up like the output of a database dump.
+
+use anyhow::{bail, Context, Result};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::config::{DockerVolumeConfig, DumpConfig, WslConfig};
+
+/// Export by `wsl --export`, written into the dump folder as `<distro>.tar`
+pub fn wsl_export(config: &WslConfig) -> Result<DumpConfig> {
+    check_name("WSL distribution", &config.distro)?;
+
+    let export = format!("wsl --export {0} {0}.tar", config.distro);
+    let command = if config.terminate {
+        format!("wsl --terminate {} && {}", config.distro, export)
+    } else {
+        export
+    };
+
+    Ok(DumpConfig {
+        command,
+        output: None,
+        staging: config.staging.clone(),
+        timeout_seconds: 4 * 3600,
+    })
+}
+
+/// Tar stream of the volume, mounted read-only into a throwaway container, as `<volume>.tar`
+pub fn docker_volume_export(config: &DockerVolumeConfig) -> Result<DumpConfig> {
+    check_name("Docker volume", &config.volume)?;
+    check_image(&config.image)?;
+
+    Ok(DumpConfig {
+        command: format!(
+            "docker run --rm --network none -v {}:/volume:ro {} tar -cf - -C /volume .",
+            config.volume, config.image
+        ),
+        output: Some(format!("{}.tar", config.volume)),
+        staging: config.staging.clone(),
+        timeout_seconds: 4 * 3600,
+    })
+}
+
+/// Pause the running containers that mount `volume`; returns their IDs for `unpause`
+pub async fn pause_volume_users(volume: &str) -> Result<Vec<String>> {
+    let output = Command::new("docker")
+        .args(["ps", "--quiet", "--filter", &format!("volume={}", volume)])
+        .output()
+        .await
+        .context("Failed to run docker")?;
+
+    if !output.status.success() {
+        bail!("docker ps failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let ids: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if ids.is_empty() {
+        return Ok(ids);
+    }
+
+    let status = Command::new("docker").arg("pause").args(&ids).status().await
+        .context("Failed to run docker pause")?;
+    if !status.success() {
+        // Some may have been paused before the failure
+        unpause(&ids).await;
+        bail!("docker pause failed for containers using volume {}", volume);
+    }
+
+    info!("Paused {} container(s) using volume {}", ids.len(), volume);
+    Ok(ids)
+}
+
+/// Resume containers paused by `pause_volume_users`
+pub async fn unpause(ids: &[String]) {
+    if ids.is_empty() {
+        return;
+    }
+
+    match Command::new("docker").arg("unpause").args(ids).status().await {
+        Ok(status) if status.success() => info!("Resumed {} container(s)", ids.len()),
+        Ok(status) => warn!("docker unpause exited with {}; resume {} by hand", status, ids.join(" ")),
+        Err(e) => warn!("Failed to run docker unpause ({}); resume {} by hand", e, ids.join(" ")),
+    }
+}
+
+/// Names end up in a shell command, so only plain names are accepted. `/` and `:` are
+/// refused too: a volume `/etc:/volume` would mount a host folder instead of a volume.
+fn check_name(what: &str, name: &str) -> Result<()> {
+    if !is_plain(name, "._-") {
+        bail!("{} name '{}' may only contain letters, digits and . _ -", what, name);
+    }
+    Ok(())
+}
+
+/// Image references need a registry, tag or digest (`registry:5000/busybox:1.36`)
+fn check_image(image: &str) -> Result<()> {
+    if !is_plain(image, "._-/:@") {
+        bail!("Docker image name '{}' may only contain letters, digits and . _ - / : @", image);
+    }
+    Ok(())
+}
+
+fn is_plain(name: &str, punctuation: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || punctuation.contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_commands() {
+        let wsl = WslConfig { distro: "Ubuntu-22.04".to_string(), terminate: true, staging: None };
+        assert_eq!(
+            wsl_export(&wsl).unwrap().command,
+            "wsl --terminate Ubuntu-22.04 && wsl --export Ubuntu-22.04 Ubuntu-22.04.tar"
+        );
+
+        let docker = DockerVolumeConfig {
+            volume: "pgdata".to_string(),
+            image: "busybox".to_string(),
+            keep_running: false,
+            staging: None,
+        };
+        let dump = docker_volume_export(&docker).unwrap();
+        assert_eq!(dump.command, "docker run --rm --network none -v pgdata:/volume:ro busybox tar -cf - -C /volume .");
+        assert_eq!(dump.output.as_deref(), Some("pgdata.tar"));
+
+        let registry_image = DockerVolumeConfig { image: "registry:5000/tools/busybox:1.36".to_string(), ..docker.clone() };
+        assert!(docker_volume_export(&registry_image).is_ok());
+
+        let host_folder = DockerVolumeConfig { volume: "/etc".to_string(), ..docker.clone() };
+        assert!(docker_volume_export(&host_folder).is_err(), "A path would bind-mount the host folder");
+
+        let with_mode = DockerVolumeConfig { volume: "pgdata:/volume".to_string(), ..docker.clone() };
+        assert!(docker_volume_export(&with_mode).is_err());
+
+        let unsafe_name = DockerVolumeConfig { volume: "data; rm -rf /".to_string(), ..docker };
+        assert!(docker_volume_export(&unsafe_name).is_err());
+
+        let distro_path = WslConfig { distro: "../Ubuntu".to_string(), ..wsl };
+        assert!(wsl_export(&distro_path).is_err());
+    }
+}
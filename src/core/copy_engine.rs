@@ -1,23 +1,149 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use tracing::warn;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::config::{ArchiveConfig, BackupJob, BackupMode, NameConflicts, NameNormalization};
+use crate::core::autotune::{Autotuner, CopyTuning};
+use crate::core::checksum::Crc32;
+use crate::core::copy_error::{CopyError, CopyFailure, FailureCounts};
+use crate::core::exclude::{is_backup_area, Exclusions};
+use crate::core::incremental::Baseline;
+use crate::core::manifest::{contained_path, LinkEntry, ManifestEntry, SkippedEntry};
+use crate::core::names::{safe_names, Rename};
+use crate::core::throttle::BandwidthLimiter;
+use crate::core::transform::TransformChain;
+use crate::observability::{record_bytes_copied, redacted};
 
 use crate::platform::traits::FileSystem;
+use crate::platform::FaultInjector;
 
+#[cfg(unix)]
+use crate::platform::UnixFileSystem;
 #[cfg(windows)]
 use crate::platform::WindowsFileSystem;
 
-#[derive(Debug, Clone)]
+/// Per-job settings of a directory copy
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    pub exclusions: Exclusions,
+    pub name_conflicts: NameConflicts,
+    pub normalization: Option<NameNormalization>,
+    pub max_path_length: Option<usize>,
+
+    /// Source paths, relative to the source root, too long for the target; they are
+    /// recorded as skipped instead of copied
+    pub too_long: HashSet<PathBuf>,
+
+    /// Files the previous backup could not copy, tried before the rest of the source
+    pub retry_first: Vec<SkippedEntry>,
+
+    /// Buffer size and parallel copies to use instead of the defaults
+    pub tuning: Option<CopyTuning>,
+
+    /// Measure candidate tunings at the start of the copy when `tuning` is not set
+    pub autotune: bool,
+
+    /// Compute each file's CRC32 while copying it, for verification and the manifest
+    pub hash: bool,
+
+    /// Copy only files changed since the latest backup
+    pub mode: BackupMode,
+
+    /// Latest backup unchanged files are linked from, in incremental mode
+    pub baseline: Option<Arc<Baseline>>,
+
+    /// Write the backup as a single archive instead of a folder
+    pub archive: Option<ArchiveConfig>,
+
+    /// Copy no faster than this, whatever the throttle calendar allows
+    pub max_bytes_per_sec: Option<u64>,
+
+    /// Read files that cannot be opened otherwise through `BackupRead` (Windows only)
+    pub backup_privilege: bool,
+}
+
+impl CopyOptions {
+    pub fn for_job(job: &BackupJob) -> Self {
+        Self {
+            exclusions: Exclusions::from_profiles(&job.exclude_profiles).with_patterns(&job.exclude),
+            name_conflicts: job.name_conflicts,
+            normalization: job.normalize_names,
+            max_path_length: job.max_path_length,
+            too_long: HashSet::new(),
+            retry_first: Vec::new(),
+            tuning: None,
+            autotune: false,
+            hash: false,
+            mode: job.mode,
+            baseline: None,
+            archive: job.archive.clone(),
+            max_bytes_per_sec: job.max_bytes_per_sec,
+            backup_privilege: job.backup_privilege,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct CopyProgress {
     pub bytes_copied: u64,
     pub files_copied: u64,
     pub files_skipped: u64,
     pub current_file: Option<PathBuf>,
+
+    /// Skipped files by failure class
+    pub failures: FailureCounts,
+
+    /// Files copied so far, recorded for the backup manifest
+    pub files: Vec<ManifestEntry>,
+
+    /// Junctions and symbolic links passed over, recorded for the backup manifest
+    pub links: Vec<LinkEntry>,
+
+    /// Files and folders stored under another name than in the source
+    pub renames: Vec<Rename>,
+
+    /// Source files that could not be copied, recorded for the backup manifest
+    pub skipped: Vec<SkippedEntry>,
+
+    /// Source paths copied ahead of the walk from `CopyOptions::retry_first`
+    pub retried: HashSet<PathBuf>,
+
+    /// Tuning trials of an autotuned copy
+    pub autotuner: Option<Autotuner>,
+
+    /// Files linked from the baseline backup instead of copied
+    pub files_unchanged: u64,
+}
+
+/// A file of the current folder waiting to be copied with its batch
+struct PendingFile {
+    source_path: PathBuf,
+    target_path: PathBuf,
+    relative_path: PathBuf,
+    backup_relative: PathBuf,
+    metadata: std::fs::Metadata,
 }
 
+/// Buffer size for throttled and transformed copies (1MB)
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Pause before files skipped as unreadable are tried a second time
+const SECOND_PASS_DELAY: Duration = Duration::from_secs(2);
+
 pub struct CopyEngine {
+    #[cfg(unix)]
+    fs: UnixFileSystem,
     #[cfg(windows)]
     fs: WindowsFileSystem,
+    limiter: Arc<BandwidthLimiter>,
+    transforms: TransformChain,
+    faults: Option<Arc<FaultInjector>>,
 }
 
 impl Default for CopyEngine {
@@ -28,9 +154,44 @@ impl Default for CopyEngine {
 
 impl CopyEngine {
     pub fn new() -> Self {
+        Self::with_bandwidth_limiter(Arc::new(BandwidthLimiter::default()))
+    }
+
+    /// Create engine whose copies share the given bandwidth limiter
+    pub fn with_bandwidth_limiter(limiter: Arc<BandwidthLimiter>) -> Self {
         Self {
+            #[cfg(unix)]
+            fs: UnixFileSystem::new(),
             #[cfg(windows)]
             fs: WindowsFileSystem::new(),
+            limiter,
+            transforms: TransformChain::default(),
+            faults: None,
+        }
+    }
+
+    /// Run file contents through `transforms` while copying
+    pub fn with_transforms(mut self, transforms: TransformChain) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    /// Fail or slow down file copies as `faults` plans, for testing failure handling
+    pub fn with_faults(mut self, faults: Option<Arc<FaultInjector>>) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Whether copies are changed by transforms, so they no longer match the source byte for byte
+    pub fn has_transforms(&self) -> bool {
+        !self.transforms.is_empty()
+    }
+
+    /// Limiter for one copy: the shared one, behind the job's own limit if it has one
+    pub fn limiter_for(&self, options: &CopyOptions) -> Arc<BandwidthLimiter> {
+        match options.max_bytes_per_sec {
+            Some(rate) => Arc::new(BandwidthLimiter::for_job(self.limiter.clone(), rate)),
+            None => self.limiter.clone(),
         }
     }
 
@@ -39,29 +200,280 @@ impl CopyEngine {
         &self,
         source: &Path,
         target: &Path,
+        progress_callback: F,
+    ) -> Result<CopyProgress>
+    where
+        F: FnMut(&CopyProgress) + Send,
+    {
+        self.copy_directory_with(source, target, &CopyOptions::default(), progress_callback).await
+    }
+
+    /// Copy a directory tree with a job's exclusions and name handling
+    pub async fn copy_directory_with<F>(
+        &self,
+        source: &Path,
+        target: &Path,
+        options: &CopyOptions,
         mut progress_callback: F,
     ) -> Result<CopyProgress>
     where
         F: FnMut(&CopyProgress) + Send,
     {
-        let mut progress = CopyProgress {
-            bytes_copied: 0,
-            files_copied: 0,
-            files_skipped: 0,
-            current_file: None,
-        };
+        let mut progress = CopyProgress::default();
+
+        if options.autotune && options.tuning.is_none() {
+            let mut autotuner = Autotuner::new();
+            autotuner.record(0, Instant::now());
+            progress.autotuner = Some(autotuner);
+        }
+
+        // One bucket for the whole copy, so the job's limit holds across files
+        let limiter = self.limiter_for(options);
 
-        self.copy_dir_recursive(source, target, source, &mut progress, &mut progress_callback).await?;
+        if !options.retry_first.is_empty() {
+            self.copy_retries(source, target, options, &limiter, &mut progress, &mut progress_callback).await?;
+        }
+
+        self.copy_dir_recursive(source, target, source, target, options, &limiter, &mut progress, &mut progress_callback).await?;
+
+        self.retry_skipped(source, target, options, &limiter, &mut progress, &mut progress_callback).await;
 
         Ok(progress)
     }
 
+    /// Copy the files the previous backup skipped before anything else, so a later
+    /// failure such as a full target does not keep them out again. Files that still
+    /// fail are left to the walk, which records them as skipped once more.
+    async fn copy_retries<F>(
+        &self,
+        source_root: &Path,
+        target_root: &Path,
+        options: &CopyOptions,
+        limiter: &BandwidthLimiter,
+        progress: &mut CopyProgress,
+        progress_callback: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(&CopyProgress) + Send,
+    {
+        info!("Retrying {} files skipped by the previous backup first", options.retry_first.len());
+
+        for skipped in &options.retry_first {
+            let (Some(relative_path), Some(backup_relative)) = (contained_path(skipped.source_path()), contained_path(&skipped.path)) else {
+                warn!("Not retrying {}: the previous manifest points outside the source or backup", skipped.path);
+                continue;
+            };
+            let relative_path = relative_path.to_path_buf();
+            let source_path = source_root.join(&relative_path);
+            let target_path = target_root.join(backup_relative);
+
+            let Ok(metadata) = tokio::fs::symlink_metadata(&source_path).await else {
+                continue;
+            };
+            if !metadata.is_file()
+                || options.exclusions.excludes(&relative_path, &metadata)
+                || !self.transforms.include(&relative_path)
+            {
+                continue;
+            }
+
+            if let Some(parent) = target_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+
+            match self.copy_one_file(limiter, &source_path, &target_path, &relative_path, options.tuning, options).await {
+                Ok((bytes, crc32)) => {
+                    progress.bytes_copied += bytes;
+                    progress.files_copied += 1;
+                    progress.files.push(ManifestEntry::new(
+                        Path::new(&skipped.path),
+                        bytes,
+                        metadata.modified().ok().map(DateTime::<Utc>::from),
+                    ).with_original_path(&relative_path).with_crc32(crc32));
+                    progress.retried.insert(relative_path);
+                    progress_callback(&*progress);
+                }
+                Err(e) => debug!("Previously skipped file still fails: {}: {}", redacted(&source_path), e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Second pass over files the walk skipped while their metadata was readable, which
+    /// is how briefly locked files (sharing violations) fail. Files that fail again stay
+    /// skipped.
+    async fn retry_skipped<F>(
+        &self,
+        source_root: &Path,
+        target_root: &Path,
+        options: &CopyOptions,
+        limiter: &BandwidthLimiter,
+        progress: &mut CopyProgress,
+        progress_callback: &mut F,
+    ) where
+        F: FnMut(&CopyProgress) + Send,
+    {
+        let (transient, rest): (Vec<SkippedEntry>, Vec<SkippedEntry>) = std::mem::take(&mut progress.skipped)
+            .into_iter()
+            .partition(|skipped| skipped.reason == CopyFailure::SourceUnreadable && skipped.size.is_some());
+        progress.skipped = rest;
+
+        if transient.is_empty() {
+            return;
+        }
+
+        info!("Trying {} skipped files once more", transient.len());
+        tokio::time::sleep(SECOND_PASS_DELAY).await;
+
+        let mut recovered = 0;
+        for skipped in transient {
+            let relative_path = PathBuf::from(skipped.source_path());
+            let source_path = source_root.join(&relative_path);
+            let target_path = target_root.join(&skipped.path);
+
+            match self.copy_one_file(limiter, &source_path, &target_path, &relative_path, options.tuning, options).await {
+                Ok((bytes, crc32)) => {
+                    let modified = tokio::fs::metadata(&source_path).await.ok()
+                        .and_then(|m| m.modified().ok())
+                        .map(DateTime::<Utc>::from);
+
+                    progress.bytes_copied += bytes;
+                    progress.files_copied += 1;
+                    progress.files_skipped = progress.files_skipped.saturating_sub(1);
+                    progress.failures.forget(skipped.reason);
+                    progress.files.push(
+                        ManifestEntry::new(Path::new(&skipped.path), bytes, modified)
+                            .with_original_path(&relative_path)
+                            .with_crc32(crc32)
+                    );
+                    recovered += 1;
+                    progress_callback(&*progress);
+                }
+                Err(e) => {
+                    debug!("Still cannot copy {}: {}", redacted(&source_path), e);
+                    progress.skipped.push(skipped);
+                }
+            }
+        }
+
+        info!("Recovered {} files on the second pass, {} still skipped", recovered, progress.skipped.len());
+    }
+
+    /// Copy one file the way the engine is configured (transforms, throttling, platform copy).
+    ///
+    /// With `options.hash`, the CRC32 of the bytes read is returned too, except for transformed copies.
+    async fn copy_one_file(
+        &self,
+        limiter: &BandwidthLimiter,
+        source_path: &Path,
+        target_path: &Path,
+        relative_path: &Path,
+        tuning: Option<CopyTuning>,
+        options: &CopyOptions,
+    ) -> Result<(u64, Option<u32>)> {
+        if let Some(faults) = &self.faults {
+            faults.before_copy(source_path).await?;
+        }
+
+        if self.has_transforms() {
+            let bytes = self.copy_file_transformed(limiter, source_path, target_path, relative_path).await?;
+            record_bytes_copied(bytes);
+            return Ok((bytes, None));
+        }
+
+        let buffer_size = tuning.map_or(COPY_BUFFER_SIZE, |t| t.buffer_size);
+        let mut crc = options.hash.then(Crc32::new);
+
+        #[cfg(windows)]
+        let bytes = if options.backup_privilege {
+            self.fs.copy_file_with_backup_read(source_path, target_path, limiter, buffer_size, crc.as_mut()).await?
+        } else {
+            self.fs.copy_file(source_path, target_path, limiter, buffer_size, crc.as_mut()).await?
+        };
+
+        // The OS fast path cannot hash, so hashing takes the buffered copy
+        #[cfg(unix)]
+        let bytes = if limiter.is_enabled() || tuning.is_some() || crc.is_some() {
+            self.fs.copy_file(source_path, target_path, limiter, buffer_size, crc.as_mut()).await?
+        } else {
+            copy_file_native(source_path, target_path).await?
+        };
+
+        record_bytes_copied(bytes);
+        Ok((bytes, crc.map(|crc| crc.finish())))
+    }
+
+    /// Copy the files of a batch at the same time, then record them in walk order
+    async fn copy_batch<F>(
+        &self,
+        batch: Vec<PendingFile>,
+        options: &CopyOptions,
+        limiter: &BandwidthLimiter,
+        progress: &mut CopyProgress,
+        progress_callback: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(&CopyProgress) + Send,
+    {
+        let tuning = current_tuning(options, progress);
+        let results = join_all(batch.iter().map(|file| {
+            self.copy_one_file(limiter, &file.source_path, &file.target_path, &file.relative_path, tuning, options)
+        })).await;
+
+        for (file, result) in batch.iter().zip(results) {
+            match result {
+                Ok((bytes, crc32)) => {
+                    progress.bytes_copied += bytes;
+                    progress.files_copied += 1;
+                    progress.files.push(ManifestEntry::new(
+                        &file.backup_relative,
+                        bytes,
+                        file.metadata.modified().ok().map(DateTime::<Utc>::from),
+                    ).with_original_path(&file.relative_path).with_crc32(crc32));
+                    progress_callback(&*progress);
+                }
+                Err(e) => {
+                    let failure = CopyError::classify(&e);
+
+                    // Every following file would fail the same way
+                    if failure == CopyFailure::DiskFull {
+                        return Err(e.context(format!(
+                            "Target is full, aborting at {}",
+                            redacted(&file.source_path)
+                        )));
+                    }
+
+                    warn!("Failed to copy file {} ({}): {}", redacted(&file.source_path), failure, e);
+                    progress.files_skipped += 1;
+                    progress.failures.record(failure);
+                    progress.skipped.push(SkippedEntry::new(
+                        &file.backup_relative,
+                        &file.relative_path,
+                        Some(&file.metadata),
+                        failure,
+                    ));
+                }
+            }
+        }
+
+        if let Some(autotuner) = &mut progress.autotuner {
+            autotuner.record(progress.bytes_copied, Instant::now());
+        }
+
+        Ok(())
+    }
+
     /// Recursive directory copy
+    #[allow(clippy::too_many_arguments)]
     fn copy_dir_recursive<'a, F>(
         &'a self,
         source_root: &'a Path,
         target_root: &'a Path,
         current_source: &'a Path,
+        current_target: &'a Path,
+        options: &'a CopyOptions,
+        limiter: &'a BandwidthLimiter,
         progress: &'a mut CopyProgress,
         progress_callback: &'a mut F,
     ) -> std::pin::Pin<Box<dyn Future<Output=Result<()>> + Send + 'a>>
@@ -72,23 +484,81 @@ impl CopyEngine {
             let mut entries = tokio::fs::read_dir(current_source).await
                 .context("Failed to read source directory")?;
 
+            // Names can only conflict with their siblings, so a folder is listed before copying
+            let mut listing = Vec::new();
             while let Some(entry) = entries.next_entry().await? {
+                listing.push(entry);
+            }
+
+            let names: Vec<String> = listing.iter()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            let target_names = safe_names(&names, options.name_conflicts, options.normalization);
+            let mut batch = Vec::new();
+
+            for (entry, (target_name, rename)) in listing.into_iter().zip(target_names) {
                 let source_path = entry.path();
+                let target_path = match rename {
+                    Some(_) => current_target.join(&target_name),
+                    None => current_target.join(entry.file_name()),
+                };
 
-                // Calculate relative path for target
+                // Calculate relative paths in the source and the backup
                 let relative_path = source_path.strip_prefix(source_root)
                     .context("Failed to calculate relative path")?;
-                let target_path = target_root.join(relative_path);
+                let backup_relative = target_path.strip_prefix(target_root)
+                    .context("Failed to calculate relative path")?;
 
                 let metadata = match entry.metadata().await {
                     Ok(m) => m,
                     Err(e) => {
-                        warn!("Cannot access file metadata {}: {}", source_path.display(), e);
+                        warn!("Cannot access file metadata {}: {}", redacted(&source_path), e);
+                        let failure = CopyError::source_side(e).failure;
                         progress.files_skipped += 1;
+                        progress.failures.record(failure);
+                        progress.skipped.push(SkippedEntry::new(backup_relative, relative_path, None, failure));
                         continue;
                     }
                 };
 
+                if options.exclusions.excludes(relative_path, &metadata) {
+                    debug!("Excluded by profile: {}", redacted(&source_path));
+                    continue;
+                }
+
+                if options.too_long.contains(relative_path) {
+                    debug!("Path too long for the target: {}", redacted(&source_path));
+                    progress.files_skipped += 1;
+                    progress.failures.record(CopyFailure::PathTooLong);
+                    progress.skipped.push(SkippedEntry::new(backup_relative, relative_path, Some(&metadata), CopyFailure::PathTooLong));
+                    continue;
+                }
+
+                if metadata.is_dir() && is_backup_area(&source_path).await {
+                    warn!("Not backing up {}: it holds KeepHive backups", redacted(&source_path));
+                    progress.files_skipped += 1;
+                    progress.failures.record(CopyFailure::BackupArea);
+                    progress.skipped.push(SkippedEntry::new(backup_relative, relative_path, None, CopyFailure::BackupArea));
+                    continue;
+                }
+
+                // Never followed: profile folders hold legacy junctions pointing back into
+                // themselves ("Application Data" -> AppData\Roaming) that would recurse forever
+                if metadata.is_symlink() {
+                    let target = tokio::fs::read_link(&source_path).await.ok();
+                    let directory = tokio::fs::metadata(&source_path).await.is_ok_and(|m| m.is_dir());
+                    debug!("Not following link: {} -> {:?}", redacted(&source_path), target);
+                    progress.links.push(LinkEntry::new(relative_path, target.as_deref(), directory));
+                    continue;
+                }
+
+                if let Some(reason) = rename
+                    && (metadata.is_dir() || self.transforms.include(relative_path))
+                {
+                    debug!("Storing {} as {} ({:?})", redacted(relative_path), redacted(backup_relative), reason);
+                    progress.renames.push(Rename::new(relative_path, backup_relative, reason));
+                }
+
                 if metadata.is_dir() {
                     // Create target directory
                     tokio::fs::create_dir_all(&target_path).await
@@ -99,10 +569,22 @@ impl CopyEngine {
                         source_root,
                         target_root,
                         &source_path,
+                        &target_path,
+                        options,
+                        limiter,
                         progress,
                         progress_callback,
                     ).await?;
                 } else if metadata.is_file() {
+                    if !self.transforms.include(relative_path) {
+                        debug!("Excluded by copy transform: {}", redacted(&source_path));
+                        continue;
+                    }
+
+                    if progress.retried.contains(relative_path) {
+                        continue;
+                    }
+
                     // Copy file
                     progress.current_file = Some(source_path.clone());
 
@@ -111,29 +593,179 @@ impl CopyEngine {
                         tokio::fs::create_dir_all(parent).await?;
                     }
 
-                    // Use platform-specific FileSystem trait
-                    #[cfg(windows)]
-                    let copy_result = self.fs.copy_file(&source_path, &target_path).await;
+                    if let Some(baseline) = &options.baseline
+                        && let Some(previous) = baseline.unchanged(relative_path, &metadata)
+                    {
+                        match baseline.link(previous, &target_path).await {
+                            Ok(()) => {
+                                progress.files_unchanged += 1;
+                                progress.files.push(
+                                    ManifestEntry::new(backup_relative, previous.size, previous.modified)
+                                        .with_original_path(relative_path)
+                                        .with_crc32(previous.crc32)
+                                );
+                                progress_callback(&*progress);
+                                continue;
+                            }
+                            Err(e) => debug!("Copying unchanged {} instead of linking it: {}", redacted(&source_path), e),
+                        }
+                    }
 
-                    #[cfg(not(windows))]
-                    let copy_result = tokio::fs::copy(&source_path, &target_path).await
-                        .map_err(|e| anyhow::anyhow!("Failed to copy file: {}", e));
+                    batch.push(PendingFile {
+                        relative_path: relative_path.to_path_buf(),
+                        backup_relative: backup_relative.to_path_buf(),
+                        source_path,
+                        target_path,
+                        metadata,
+                    });
 
-                    match copy_result {
-                        Ok(bytes) => {
-                            progress.bytes_copied += bytes;
-                            progress.files_copied += 1;
-                            progress_callback(&*progress);
-                        }
-                        Err(e) => {
-                            warn!("Failed to copy file {}: {}", source_path.display(), e);
-                            progress.files_skipped += 1;
-                        }
+                    if batch.len() >= current_tuning(options, progress).map_or(1, |t| t.parallel_files) {
+                        self.copy_batch(std::mem::take(&mut batch), options, limiter, progress, progress_callback).await?;
                     }
                 }
             }
 
+            if !batch.is_empty() {
+                self.copy_batch(batch, options, limiter, progress, progress_callback).await?;
+            }
+
             Ok(())
         })
     }
-}
\ No newline at end of file
+
+    /// Chunked copy through the transform chain, returning the bytes written
+    async fn copy_file_transformed(&self, limiter: &BandwidthLimiter, src: &Path, dst: &Path, relative_path: &Path) -> Result<u64> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        #[cfg(windows)]
+        let (src, dst) = {
+            use crate::platform::traits::PathNormalizer;
+            use crate::platform::windows::WindowsPathNormalizer;
+            (WindowsPathNormalizer.normalize(src), WindowsPathNormalizer.normalize(dst))
+        };
+
+        let mut file_transform = self.transforms.begin(relative_path)
+            .with_context(|| format!("Copy transform failed to start for {}", relative_path.display()))?;
+
+        let mut src_file = tokio::fs::File::open(&src).await
+            .map_err(CopyError::source_side)?;
+
+        let mut dst_file = tokio::fs::File::create(&dst).await
+            .map_err(CopyError::target_side)?;
+
+        let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+        let mut total_bytes = 0u64;
+
+        loop {
+            let bytes_read = src_file.read(&mut buffer).await
+                .map_err(CopyError::source_side)?;
+
+            let output = if bytes_read == 0 {
+                file_transform.finish()
+            } else {
+                file_transform.process(&buffer[..bytes_read])
+            }.with_context(|| format!("Copy transform failed on {}", relative_path.display()))?;
+
+            dst_file.write_all(&output).await
+                .map_err(CopyError::target_side)?;
+
+            total_bytes += output.len() as u64;
+            limiter.consume(bytes_read as u64).await;
+
+            if bytes_read == 0 {
+                break;
+            }
+        }
+
+        dst_file.sync_all().await
+            .map_err(CopyError::target_side)?;
+
+        Ok(total_bytes)
+    }
+}
+
+/// Tuning in effect: the autotuner's candidate or pick, else the configured one
+fn current_tuning(options: &CopyOptions, progress: &CopyProgress) -> Option<CopyTuning> {
+    progress.autotuner.as_ref().map(Autotuner::current).or(options.tuning)
+}
+
+/// Drive `futures` concurrently on the current task, returning their outputs in order
+async fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output> {
+    let mut futures: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+
+    std::future::poll_fn(|cx| {
+        let mut pending = false;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_some() {
+                continue;
+            }
+            match future.as_mut().poll(cx) {
+                Poll::Ready(value) => *output = Some(value),
+                Poll::Pending => pending = true,
+            }
+        }
+
+        if pending { Poll::Pending } else { Poll::Ready(()) }
+    }).await;
+
+    outputs.into_iter().map(|output| output.expect("every future completed")).collect()
+}
+
+/// Copy using the OS fast path; the source is opened first so that failure is attributed to it
+#[cfg(unix)]
+async fn copy_file_native(src: &Path, dst: &Path) -> Result<u64> {
+    drop(tokio::fs::File::open(src).await.map_err(CopyError::source_side)?);
+
+    let bytes = tokio::fs::copy(src, dst).await
+        .map_err(CopyError::target_side)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_file_locked_during_the_walk_is_copied_on_the_second_pass() {
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("locked.db"), b"in use").await.unwrap();
+
+        // Held open without sharing, released before the second pass starts
+        #[cfg(windows)]
+        let engine = {
+            use std::os::windows::fs::OpenOptionsExt;
+
+            let lock = std::fs::OpenOptions::new()
+                .read(true)
+                .share_mode(0)
+                .open(source.path().join("locked.db"))
+                .unwrap();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(500));
+                drop(lock);
+            });
+            CopyEngine::new()
+        };
+
+        // Permissions do not stop a test running as root, so the lock is injected: the first copy fails
+        #[cfg(not(windows))]
+        let engine = {
+            use crate::platform::FaultPlan;
+
+            let faults = FaultInjector::new(FaultPlan { fail_nth: Some(1), ..FaultPlan::default() });
+            CopyEngine::new().with_faults(Some(Arc::new(faults)))
+        };
+
+        let progress = engine.copy_directory(source.path(), target.path(), |_| {}).await.unwrap();
+
+        assert_eq!(progress.files_copied, 1);
+        assert_eq!(progress.files_skipped, 0);
+        assert!(progress.skipped.is_empty(), "{:?}", progress.skipped);
+        assert_eq!(progress.failures.total(), 0);
+        assert_eq!(tokio::fs::read(target.path().join("locked.db")).await.unwrap(), b"in use");
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::ErrorKind;
+
+/// Why a file could not be copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyFailure {
+    /// The source file or its metadata could not be read (locked, permissions, I/O error)
+    SourceUnreadable,
+    /// Creating or writing the backup copy failed
+    TargetWriteFailed,
+    /// The target volume or quota is full
+    DiskFull,
+    /// The path exceeds what the source or target filesystem accepts
+    PathTooLong,
+    /// A folder below the source holds KeepHive backups and was left out
+    BackupArea,
+}
+
+impl fmt::Display for CopyFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            CopyFailure::SourceUnreadable => "source unreadable",
+            CopyFailure::TargetWriteFailed => "target write failed",
+            CopyFailure::DiskFull => "target disk full",
+            CopyFailure::PathTooLong => "path too long",
+            CopyFailure::BackupArea => "holds KeepHive backups",
+        };
+        f.write_str(text)
+    }
+}
+
+/// I/O error from copying a single file, tagged with the side that failed
+#[derive(Debug)]
+pub struct CopyError {
+    pub failure: CopyFailure,
+    pub error: std::io::Error,
+}
+
+impl CopyError {
+    /// Error while opening or reading the source file
+    pub fn source_side(error: std::io::Error) -> Self {
+        let failure = match error.kind() {
+            ErrorKind::InvalidFilename => CopyFailure::PathTooLong,
+            _ => CopyFailure::SourceUnreadable,
+        };
+        Self { failure, error }
+    }
+
+    /// Error while creating, writing or syncing the backup copy
+    pub fn target_side(error: std::io::Error) -> Self {
+        Self {
+            failure: target_failure(error.kind()),
+            error,
+        }
+    }
+
+    /// Classify an error returned by a file copy; untagged errors count as target failures
+    pub fn classify(error: &anyhow::Error) -> CopyFailure {
+        if let Some(copy_error) = error.downcast_ref::<CopyError>() {
+            return copy_error.failure;
+        }
+
+        match error.downcast_ref::<std::io::Error>() {
+            Some(io_error) => target_failure(io_error.kind()),
+            None => CopyFailure::TargetWriteFailed,
+        }
+    }
+}
+
+fn target_failure(kind: ErrorKind) -> CopyFailure {
+    match kind {
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded => CopyFailure::DiskFull,
+        ErrorKind::InvalidFilename => CopyFailure::PathTooLong,
+        _ => CopyFailure::TargetWriteFailed,
+    }
+}
+
+impl fmt::Display for CopyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.failure, self.error)
+    }
+}
+
+impl std::error::Error for CopyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Number of skipped files per failure class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureCounts {
+    #[serde(default)]
+    pub source_unreadable: u64,
+    #[serde(default)]
+    pub target_write_failed: u64,
+    #[serde(default)]
+    pub disk_full: u64,
+    #[serde(default)]
+    pub path_too_long: u64,
+    #[serde(default)]
+    pub backup_area: u64,
+}
+
+impl FailureCounts {
+    pub fn record(&mut self, failure: CopyFailure) {
+        match failure {
+            CopyFailure::SourceUnreadable => self.source_unreadable += 1,
+            CopyFailure::TargetWriteFailed => self.target_write_failed += 1,
+            CopyFailure::DiskFull => self.disk_full += 1,
+            CopyFailure::PathTooLong => self.path_too_long += 1,
+            CopyFailure::BackupArea => self.backup_area += 1,
+        }
+    }
+
+    /// Take back a failure recorded for a file that was copied after all
+    pub fn forget(&mut self, failure: CopyFailure) {
+        let count = match failure {
+            CopyFailure::SourceUnreadable => &mut self.source_unreadable,
+            CopyFailure::TargetWriteFailed => &mut self.target_write_failed,
+            CopyFailure::DiskFull => &mut self.disk_full,
+            CopyFailure::PathTooLong => &mut self.path_too_long,
+            CopyFailure::BackupArea => &mut self.backup_area,
+        };
+        *count = count.saturating_sub(1);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.source_unreadable + self.target_write_failed + self.disk_full + self.path_too_long + self.backup_area
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_target_side_classification() {
+        assert_eq!(CopyError::target_side(io::Error::from(ErrorKind::StorageFull)).failure, CopyFailure::DiskFull);
+        assert_eq!(CopyError::target_side(io::Error::from(ErrorKind::QuotaExceeded)).failure, CopyFailure::DiskFull);
+        assert_eq!(CopyError::target_side(io::Error::from(ErrorKind::InvalidFilename)).failure, CopyFailure::PathTooLong);
+        assert_eq!(
+            CopyError::target_side(io::Error::from(ErrorKind::PermissionDenied)).failure,
+            CopyFailure::TargetWriteFailed
+        );
+    }
+
+    #[test]
+    fn test_classify_through_anyhow() {
+        let source: anyhow::Error = CopyError::source_side(io::Error::from(ErrorKind::PermissionDenied)).into();
+        assert_eq!(CopyError::classify(&source), CopyFailure::SourceUnreadable);
+
+        let untagged: anyhow::Error = io::Error::from(ErrorKind::StorageFull).into();
+        assert_eq!(CopyError::classify(&untagged), CopyFailure::DiskFull);
+
+        assert_eq!(CopyError::classify(&anyhow::anyhow!("boom")), CopyFailure::TargetWriteFailed);
+    }
+
+    #[test]
+    fn test_forget_takes_back_a_recorded_failure() {
+        let mut counts = FailureCounts::default();
+        counts.record(CopyFailure::SourceUnreadable);
+        counts.record(CopyFailure::SourceUnreadable);
+
+        counts.forget(CopyFailure::SourceUnreadable);
+        counts.forget(CopyFailure::PathTooLong);
+
+        assert_eq!(counts.source_unreadable, 1);
+        assert_eq!(counts.total(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_os_error_codes_map_to_classes() {
+        // ENOSPC and ENAMETOOLONG as reported by the OS
+        assert_eq!(CopyError::target_side(io::Error::from_raw_os_error(28)).failure, CopyFailure::DiskFull);
+        assert_eq!(CopyError::source_side(io::Error::from_raw_os_error(36)).failure, CopyFailure::PathTooLong);
+    }
+}
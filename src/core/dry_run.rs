@@ -0,0 +1,279 @@
+//! Dry runs: walk a job's source the way a backup would and report what it would copy,
+//! leave out and let retention remove, without writing anything.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::path::PathBuf;
+
+use crate::config::{BackupJob, BackupMode};
+use crate::core::analysis::{project_retention, RetentionRules};
+use crate::core::copy_engine::CopyOptions;
+use crate::core::exclude::is_backup_area;
+use crate::core::incremental::Baseline;
+use crate::core::manifest::BackupManifest;
+use crate::core::validation::calculate_dir_size;
+use crate::core::BackupOrchestrator;
+use crate::observability::format_bytes;
+use crate::storage::TargetUrl;
+
+/// Excluded paths listed in the report; the totals cover all of them
+const REPORTED_EXCLUSIONS: usize = 10;
+
+/// Number and size of a set of files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileTally {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl FileTally {
+    fn add(&mut self, files: u64, bytes: u64) {
+        self.files += files;
+        self.bytes += bytes;
+    }
+}
+
+/// What a run of a job would do
+#[derive(Debug, Clone, Default)]
+pub struct DryRunReport {
+    /// Files that would be copied
+    pub copied: FileTally,
+
+    /// Files an incremental run would link from the latest backup
+    pub unchanged: FileTally,
+
+    /// Files and folders left out by the job's exclusions, with their size
+    pub excluded: Vec<(PathBuf, u64)>,
+
+    /// Folders holding KeepHive backups, which are never copied
+    pub backup_areas: Vec<PathBuf>,
+
+    /// Folders that could not be listed, which a run would record as skipped
+    pub unreadable: Vec<PathBuf>,
+
+    /// Links that are recorded instead of followed
+    pub links: u64,
+
+    /// Backups retention would remove after the run, oldest first
+    pub removed_backups: Vec<String>,
+}
+
+impl DryRunReport {
+    pub fn excluded_bytes(&self) -> u64 {
+        self.excluded.iter().map(|(_, bytes)| bytes).sum()
+    }
+
+    /// One line for the log and the run history
+    pub fn summary(&self) -> String {
+        let mut summary = format!("would copy {} files, {}", self.copied.files, format_bytes(self.copied.bytes));
+        if self.unchanged.files > 0 {
+            summary.push_str(&format!(", link {} unchanged files", self.unchanged.files));
+        }
+        if !self.excluded.is_empty() {
+            summary.push_str(&format!(", exclude {} paths ({})", self.excluded.len(), format_bytes(self.excluded_bytes())));
+        }
+        if !self.unreadable.is_empty() {
+            summary.push_str(&format!(", skip {} unreadable folders", self.unreadable.len()));
+        }
+        if !self.removed_backups.is_empty() {
+            summary.push_str(&format!(", remove {} old backups", self.removed_backups.len()));
+        }
+        summary
+    }
+
+    /// The full report, one line each
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Copy:       {} files, {}", self.copied.files, format_bytes(self.copied.bytes))];
+        if self.unchanged.files > 0 {
+            lines.push(format!(
+                "Unchanged:  {} files, {} (linked from the latest backup)",
+                self.unchanged.files, format_bytes(self.unchanged.bytes)
+            ));
+        }
+
+        if self.excluded.is_empty() {
+            lines.push("Excluded:   nothing".to_string());
+        } else {
+            lines.push(format!("Excluded:   {} paths, {}", self.excluded.len(), format_bytes(self.excluded_bytes())));
+
+            let mut largest = self.excluded.clone();
+            largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            for (path, bytes) in largest.iter().take(REPORTED_EXCLUSIONS) {
+                lines.push(format!("  {:>10}  {}", format_bytes(*bytes), path.display()));
+            }
+            if largest.len() > REPORTED_EXCLUSIONS {
+                lines.push(format!("  ... and {} more", largest.len() - REPORTED_EXCLUSIONS));
+            }
+        }
+
+        for area in &self.backup_areas {
+            lines.push(format!("Skipped:    {} (holds KeepHive backups)", area.display()));
+        }
+        for folder in &self.unreadable {
+            lines.push(format!("Skipped:    {} (cannot be read)", folder.display()));
+        }
+        if self.links > 0 {
+            lines.push(format!("Links:      {} recorded, not followed", self.links));
+        }
+
+        if self.removed_backups.is_empty() {
+            lines.push("Retention:  no backup removed".to_string());
+        } else {
+            lines.push(format!("Retention:  {} backups removed", self.removed_backups.len()));
+            for name in &self.removed_backups {
+                lines.push(format!("  - {}", name));
+            }
+        }
+
+        lines
+    }
+}
+
+/// Walk the source of `job` and project the retention of its target under `rules`
+pub async fn dry_run(job: &BackupJob, rules: &RetentionRules) -> Result<DryRunReport> {
+    let options = CopyOptions::for_job(job);
+    let baseline = match job.mode {
+        BackupMode::Incremental => latest_baseline(job).await,
+        BackupMode::Full => None,
+    };
+
+    let mut report = DryRunReport::default();
+    let mut stack = vec![job.source.clone()];
+
+    while let Some(current) = stack.pop() {
+        let entries = tokio::fs::read_dir(&current).await;
+        let mut entries = match entries {
+            Ok(entries) => entries,
+            Err(e) if current == job.source => {
+                return Err(e).with_context(|| format!("Failed to read {}", current.display()));
+            }
+            Err(_) => {
+                report.unreadable.push(current.strip_prefix(&job.source).unwrap_or(&current).to_path_buf());
+                continue;
+            }
+        };
+
+        loop {
+            let entry = match entries.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(_) => {
+                    report.unreadable.push(current.strip_prefix(&job.source).unwrap_or(&current).to_path_buf());
+                    break;
+                }
+            };
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let relative = path.strip_prefix(&job.source).unwrap_or(&path).to_path_buf();
+
+            if options.exclusions.excludes(&relative, &metadata) {
+                let bytes = if metadata.is_dir() { calculate_dir_size(&path).await.unwrap_or(0) } else { metadata.len() };
+                report.excluded.push((relative, bytes));
+            } else if metadata.is_symlink() {
+                report.links += 1;
+            } else if metadata.is_dir() {
+                if is_backup_area(&path).await {
+                    report.backup_areas.push(relative);
+                } else {
+                    stack.push(path);
+                }
+            } else if baseline.as_ref().is_some_and(|baseline| baseline.unchanged(&relative, &metadata).is_some()) {
+                report.unchanged.add(1, metadata.len());
+            } else {
+                report.copied.add(1, metadata.len());
+            }
+        }
+    }
+
+    report.removed_backups = retention_removals(job, rules).await?;
+    Ok(report)
+}
+
+/// The backup of `job` an incremental run would build on
+async fn latest_baseline(job: &BackupJob) -> Option<Baseline> {
+    let latest = BackupOrchestrator::latest_job_backup(&job.target, &job.source).await?;
+    let manifest = BackupManifest::load(&latest).await.ok()?;
+    Some(Baseline::new(latest, manifest))
+}
+
+/// Existing backups of the job the next run's retention would remove; remote targets are not listed
+async fn retention_removals(job: &BackupJob, rules: &RetentionRules) -> Result<Vec<String>> {
+    if TargetUrl::from_path(&job.target).is_some() || !job.target.exists() {
+        return Ok(Vec::new());
+    }
+
+    let backups = BackupOrchestrator::measure_backups(&job.target, &job.source).await?;
+    let projection = project_retention(&backups, rules, 1, Utc::now());
+
+    Ok(projection.removals.into_iter()
+        .map(|removal| removal.name)
+        .filter(|name| backups.iter().any(|b| &b.name == name))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn job(source: &Path, target: &Path, exclude: &[&str]) -> BackupJob {
+        serde_json::from_value(serde_json::json!({
+            "id": "docs",
+            "source": source,
+            "target": target,
+            "exclude": exclude,
+            "schedule": { "type": "interval", "seconds": 3600 }
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let target = dir.path().join("dst");
+        std::fs::create_dir_all(source.join("node_modules/lib")).unwrap();
+        std::fs::create_dir_all(source.join("docs")).unwrap();
+        std::fs::write(source.join("docs/a.txt"), vec![0u8; 100]).unwrap();
+        std::fs::write(source.join("b.log"), vec![0u8; 50]).unwrap();
+        std::fs::write(source.join("node_modules/lib/x.js"), vec![0u8; 700]).unwrap();
+
+        let report = dry_run(&job(&source, &target, &["node_modules", "*.log"]), &RetentionRules::default()).await.unwrap();
+
+        assert_eq!(report.copied, FileTally { files: 1, bytes: 100 });
+        assert_eq!(report.excluded_bytes(), 750);
+        assert_eq!(report.excluded.len(), 2);
+        assert!(report.removed_backups.is_empty());
+        assert!(!target.exists(), "A dry run writes nothing");
+        assert_eq!(report.summary(), "would copy 1 files, 100 B, exclude 2 paths (750 B)");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_lists_retention_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let target = dir.path().join("dst");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("a.txt"), b"a").unwrap();
+
+        // A backup of another job sharing the target is not counted
+        let photos = dir.path().join("photos");
+        std::fs::create_dir_all(&photos).unwrap();
+        let orchestrator = BackupOrchestrator::new();
+        orchestrator.execute_backup("photos", &photos, &target, tokio_util::sync::CancellationToken::new()).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        for _ in 0..2 {
+            orchestrator.execute_backup("docs", &source, &target, tokio_util::sync::CancellationToken::new()).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let oldest = BackupOrchestrator::list_job_backups(&target, &source).await.unwrap()[0].clone();
+
+        let rules = RetentionRules { count: Some(2), ..Default::default() };
+        let report = dry_run(&job(&source, &target, &[]), &rules).await.unwrap();
+
+        assert_eq!(report.removed_backups, [oldest.file_name().unwrap().to_string_lossy().into_owned()]);
+        assert_eq!(BackupOrchestrator::list_complete_backups(&target).await.unwrap().len(), 3);
+    }
+}
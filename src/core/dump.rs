@@ -0,0 +1,173 @@
+//! `database-dump` jobs: a dump command writes into an empty folder, which is then
+//! backed up like any other source.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::DumpConfig;
+use crate::core::hooks::shell_command;
+use crate::observability::{format_bytes, format_duration};
+use crate::platform::PrivateDir;
+
+/// Bytes of standard output and error kept in the metadata, from the end
+const OUTPUT_TAIL: usize = 4096;
+
+/// Result of a dump command, kept in the backup's metadata
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpOutcome {
+    pub command: String,
+
+    /// None if the command was stopped by a signal
+    pub exit_code: Option<i32>,
+
+    pub duration_secs: u64,
+
+    /// Size of the dump folder after the command finished
+    pub dump_bytes: u64,
+
+    /// End of the standard output, unless it was written to the dump file
+    #[serde(default)]
+    pub stdout: String,
+
+    /// End of the standard error output
+    #[serde(default)]
+    pub stderr: String,
+}
+
+impl DumpOutcome {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Fresh private folder the dump is written to before it is backed up, in `staging` or
+/// the temp folder
+pub fn dump_dir(config: &DumpConfig) -> Result<PrivateDir> {
+    let parent = config.staging.clone().unwrap_or_else(std::env::temp_dir);
+    PrivateDir::create_in(&parent, "keephive_dump")
+}
+
+/// Run the dump command into the empty folder `dir`, created if missing. A command that
+/// exits with an error or produces nothing fails with its exit code and error output.
+pub async fn run_dump(config: &DumpConfig, dir: &Path, cancellation: &CancellationToken) -> Result<DumpOutcome> {
+    tokio::fs::create_dir_all(dir).await
+        .context("Failed to create dump folder")?;
+
+    let stdout = match &config.output {
+        Some(file) => Stdio::from(std::fs::File::create(dir.join(file))
+            .with_context(|| format!("Failed to create dump file {}", file))?),
+        None => Stdio::piped(),
+    };
+
+    info!("Running dump command: {}", config.command);
+    let started = Instant::now();
+
+    let child = shell_command(&config.command)
+        .current_dir(dir)
+        .env("KEEPHIVE_DUMP_DIR", dir)
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start dump command")?;
+
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    let output = tokio::select! {
+        output = tokio::time::timeout(timeout, child.wait_with_output()) => match output {
+            Ok(output) => output.context("Failed to wait for dump command")?,
+            Err(_) => bail!("Dump command did not finish within {} seconds", config.timeout_seconds),
+        },
+        _ = cancellation.cancelled() => bail!("Backup cancelled"),
+    };
+
+    let outcome = DumpOutcome {
+        command: config.command.clone(),
+        exit_code: output.status.code(),
+        duration_secs: started.elapsed().as_secs(),
+        dump_bytes: crate::core::calculate_dir_size(dir).await.unwrap_or(0),
+        stdout: tail(&output.stdout),
+        stderr: tail(&output.stderr),
+    };
+
+    if !outcome.succeeded() {
+        bail!(
+            "Dump command failed ({}): {}",
+            match outcome.exit_code {
+                Some(code) => format!("exit code {}", code),
+                None => "terminated".to_string(),
+            },
+            outcome.stderr.trim()
+        );
+    }
+
+    if outcome.dump_bytes == 0 {
+        bail!("Dump command succeeded but wrote nothing to {}", dir.display());
+    }
+
+    if !outcome.stderr.trim().is_empty() {
+        warn!("Dump command reported: {}", outcome.stderr.trim());
+    }
+
+    info!("Dump finished in {} ({})",
+        format_duration(Duration::from_secs(outcome.duration_secs)), format_bytes(outcome.dump_bytes));
+    Ok(outcome)
+}
+
+/// Last `OUTPUT_TAIL` bytes of command output as text
+fn tail(output: &[u8]) -> String {
+    let start = output.len().saturating_sub(OUTPUT_TAIL);
+    String::from_utf8_lossy(&output[start..]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config(command: &str, output: Option<&str>) -> DumpConfig {
+        DumpConfig {
+            command: command.to_string(),
+            output: output.map(str::to_string),
+            staging: None,
+            timeout_seconds: 60,
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_dump_output_is_written_and_recorded() {
+        let staging = tempdir().unwrap();
+        let dir = staging.path().join("dump");
+
+        let outcome = run_dump(&config("echo 'CREATE TABLE t;'; echo 'warning: old server' >&2", Some("db.sql")), &dir, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(dir.join("db.sql")).unwrap(), "CREATE TABLE t;\n");
+        assert_eq!(outcome.exit_code, Some(0));
+        assert_eq!(outcome.stdout, "");
+        assert_eq!(outcome.stderr, "warning: old server\n");
+        assert_eq!(outcome.dump_bytes, 16);
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_failed_or_empty_dumps_fail() {
+        let staging = tempdir().unwrap();
+        let dir = staging.path().join("dump");
+
+        let error = run_dump(&config("echo 'access denied' >&2; exit 2", None), &dir, &CancellationToken::new())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Dump command failed (exit code 2): access denied");
+
+        let error = run_dump(&config("true", None), &dir, &CancellationToken::new()).await.unwrap_err();
+        assert!(error.to_string().contains("wrote nothing"));
+    }
+}
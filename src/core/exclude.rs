@@ -0,0 +1,183 @@
+use std::fs::Metadata;
+use std::path::{Component, Path};
+
+use crate::config::ExcludeProfile;
+use crate::core::backup::{COMPLETE_MARKER, TARGET_MARKER};
+
+/// Windows system files, recycle bins and browser caches
+const WINDOWS_NOISE: &[&str] = &[
+    "thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    "pagefile.sys",
+    "hiberfil.sys",
+    "swapfile.sys",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    "~$*",
+    "Google/Chrome/User Data/*/Cache",
+    "Google/Chrome/User Data/*/Code Cache",
+    "Google/Chrome/User Data/*/GPUCache",
+    "Microsoft/Edge/User Data/*/Cache",
+    "Microsoft/Edge/User Data/*/Code Cache",
+    "Microsoft/Edge/User Data/*/GPUCache",
+    "Mozilla/Firefox/Profiles/*/cache2",
+    "Microsoft/Windows/INetCache",
+];
+
+/// Dependency folders and build caches that can be restored by the tools that made them
+const DEV_CACHES: &[&str] = &[
+    "node_modules",
+    "bower_components",
+    "__pycache__",
+    "*.pyc",
+    ".pytest_cache",
+    ".mypy_cache",
+    ".tox",
+    ".gradle",
+    ".parcel-cache",
+    ".next",
+    ".nuxt",
+];
+
+/// Files and folders a job leaves out of its backups, from its `exclude_profiles` and `exclude`
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    patterns: Vec<String>,
+    cloud_placeholders: bool,
+}
+
+impl Exclusions {
+    pub fn from_profiles(profiles: &[ExcludeProfile]) -> Self {
+        let mut exclusions = Self::default();
+
+        for profile in profiles {
+            match profile {
+                ExcludeProfile::WindowsNoise => {
+                    exclusions.patterns.extend(WINDOWS_NOISE.iter().map(|p| p.to_string()));
+                    exclusions.cloud_placeholders = true;
+                }
+                ExcludeProfile::DevCaches => exclusions.patterns.extend(DEV_CACHES.iter().map(|p| p.to_string())),
+            }
+        }
+
+        exclusions
+    }
+
+    /// Add patterns of the job's own `exclude` list
+    pub fn with_patterns(mut self, patterns: &[String]) -> Self {
+        self.patterns.extend(patterns.iter().cloned());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && !self.cloud_placeholders
+    }
+
+    /// Whether the file or folder at `relative_path` (relative to the source root) is left out.
+    ///
+    /// Patterns match the end of the path, one component per `/` separated part, ignoring case;
+    /// `*` stands for any run of characters within a component. An excluded folder is not
+    /// entered at all.
+    pub fn excludes(&self, relative_path: &Path, metadata: &Metadata) -> bool {
+        if self.cloud_placeholders && is_cloud_placeholder(metadata) {
+            return true;
+        }
+
+        self.excludes_path(relative_path)
+    }
+
+    /// Whether `relative_path` matches a pattern, without looking at the file itself
+    pub fn excludes_path(&self, relative_path: &Path) -> bool {
+        let components: Vec<String> = relative_path.components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy().to_lowercase()),
+                _ => None,
+            })
+            .collect();
+
+        self.patterns.iter().any(|pattern| {
+            let parts: Vec<String> = pattern.split('/').map(str::to_lowercase).collect();
+
+            components.len() >= parts.len()
+                && components[components.len() - parts.len()..].iter()
+                    .zip(&parts)
+                    .all(|(name, part)| wildcard_match(part, name))
+        })
+    }
+}
+
+/// Whether the folder at `path` holds KeepHive backups: it is a target or a backup. Such
+/// folders below a source are never copied, since each run would copy the earlier
+/// backups again, and a target mounted inside the source even the backup being written.
+/// Backups record them as skipped, so the run ends up partial instead of silently
+/// missing the folder.
+pub async fn is_backup_area(path: &Path) -> bool {
+    for marker in [TARGET_MARKER, COMPLETE_MARKER] {
+        if tokio::fs::try_exists(path.join(marker)).await.unwrap_or(false) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| wildcard_match(rest, &text[i..]))
+        }
+    }
+}
+
+/// OneDrive and other cloud files whose content is not on disk; reading them would download it
+#[cfg(windows)]
+fn is_cloud_placeholder(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+
+    metadata.file_attributes()
+        & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+}
+
+#[cfg(not(windows))]
+fn is_cloud_placeholder(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_match_path_endings() {
+        let metadata = std::fs::metadata(".").unwrap();
+        let exclusions = Exclusions::from_profiles(&[ExcludeProfile::WindowsNoise, ExcludeProfile::DevCaches]);
+        let excluded = |path: &str| exclusions.excludes(Path::new(path), &metadata);
+
+        assert!(excluded("Pictures/Thumbs.db"));
+        assert!(excluded("$Recycle.Bin"));
+        assert!(excluded("Documents/~$report.docx"));
+        assert!(excluded("AppData/Local/Google/Chrome/User Data/Profile 1/Cache"));
+        assert!(excluded("src/app/node_modules"));
+        assert!(excluded("tools/__pycache__/util.cpython-312.pyc"));
+
+        assert!(!excluded("Documents/report.docx"));
+        assert!(!excluded("AppData/Local/Google/Chrome/User Data/Default/Bookmarks"));
+        assert!(!excluded("Cache"), "A plain Cache folder is not a browser cache");
+        assert!(!Exclusions::default().excludes(Path::new("thumbs.db"), &metadata));
+
+        let custom = Exclusions::default().with_patterns(&["world/session.lock".to_string()]);
+        assert!(custom.excludes(Path::new("world/session.lock"), &metadata));
+    }
+}
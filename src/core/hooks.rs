@@ -0,0 +1,296 @@
+//! What runs around a job's backup: its pre/post hook commands, the result commands
+//! told how the run went, and the check for lock files of an application that is still running.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::info;
+
+use crate::config::BackupJob;
+use crate::core::exclude::wildcard_match;
+use crate::observability::format_duration;
+use crate::platform::PrivateDir;
+use crate::state::BackupMetadata;
+
+/// Longest a hook may run before it is stopped and counted as failed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How a run ended, as passed to `on_success_command` and `on_failure_command`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobResult<'a> {
+    pub job_id: &'a str,
+    pub success: bool,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+
+    /// Why the run failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// The backup written by a successful run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<&'a BackupMetadata>,
+}
+
+/// Run one of the job's hook commands; `stage` is "pre" or "post"
+pub async fn run_hook(job: &BackupJob, stage: &str, command: &str) -> Result<()> {
+    run_shell(job, stage, command, None, None).await
+}
+
+/// Run the job's post hook, telling it how the backup ended in `KEEPHIVE_OUTCOME`
+/// (`success`, `skipped`, `failed` or `cancelled`)
+pub async fn run_post_hook(job: &BackupJob, command: &str, outcome: &str) -> Result<()> {
+    run_shell(job, "post", command, Some(outcome), None).await
+}
+
+/// Run the job's `on_success_command` or `on_failure_command`, if it has one, handing it
+/// `result` as JSON on stdin and in a file in a private folder
+pub async fn run_result_hook(job: &BackupJob, result: &JobResult<'_>) -> Result<()> {
+    let (stage, command) = if result.success {
+        ("on_success", &job.hooks.on_success_command)
+    } else {
+        ("on_failure", &job.hooks.on_failure_command)
+    };
+    let Some(command) = command else {
+        return Ok(());
+    };
+
+    let json = serde_json::to_vec_pretty(result).context("Failed to serialize job result")?;
+    let dir = PrivateDir::create("keephive_result")?;
+    let result_file = dir.path().join("result.json");
+    let mut file = tokio::fs::OpenOptions::new().write(true).create_new(true).open(&result_file).await
+        .with_context(|| format!("Failed to create {}", result_file.display()))?;
+    // Flushed, so the whole result is in the file before the command reads it
+    async {
+        file.write_all(&json).await?;
+        file.flush().await
+    }.await.with_context(|| format!("Failed to write {}", result_file.display()))?;
+    drop(file);
+
+    let outcome = run_shell(job, stage, command, None, Some((&json, &result_file))).await;
+    let _ = dir.remove().await;
+    outcome
+}
+
+/// Run `command` with the job's environment; `outcome` is set as `KEEPHIVE_OUTCOME`, and
+/// `input` is written to stdin and its file named in `KEEPHIVE_RESULT_FILE`
+async fn run_shell(
+    job: &BackupJob,
+    stage: &str,
+    command: &str,
+    outcome: Option<&str>,
+    input: Option<(&[u8], &Path)>,
+) -> Result<()> {
+    info!("Running {} hook of job {}: {}", stage, job.id, command);
+
+    let mut shell = shell_command(command);
+    shell.env("KEEPHIVE_JOB_ID", &job.id)
+        .env("KEEPHIVE_SOURCE", &job.source)
+        .env("KEEPHIVE_TARGET", &job.target)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(outcome) = outcome {
+        shell.env("KEEPHIVE_OUTCOME", outcome);
+    }
+    if let Some((_, result_file)) = input {
+        shell.env("KEEPHIVE_RESULT_FILE", result_file);
+    }
+    if job.source.is_dir() {
+        shell.current_dir(&job.source);
+    }
+
+    let run = async {
+        let mut child = shell.spawn()?;
+        let stdin = child.stdin.take();
+
+        // Written while the output is read, so a command filling its pipes cannot stall the write
+        let write = async {
+            if let (Some(mut stdin), Some((data, _))) = (stdin, input) {
+                // A command that only reads the file closes stdin early
+                match stdin.write_all(data).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+                    _ => {}
+                }
+            }
+            Ok(())
+        };
+
+        let (written, output) = tokio::join!(write, child.wait_with_output());
+        written?;
+        output
+    };
+
+    let output = tokio::time::timeout(HOOK_TIMEOUT, run).await
+        .map_err(|_| anyhow!("The {} hook did not finish within {}", stage, format_duration(HOOK_TIMEOUT)))?
+        .with_context(|| format!("Failed to run the {} hook", stage))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("The {} hook failed ({}): {}", stage, output.status, stderr.trim());
+    }
+
+    Ok(())
+}
+
+/// `command` run by the platform shell
+#[cfg(windows)]
+pub(crate) fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    // cmd parses the rest of the line itself; quoting it again would break quoted arguments
+    shell.raw_arg("/C").raw_arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+pub(crate) fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+/// First lock file matching `patterns` below `source` that an application holds.
+///
+/// Patterns are relative to the source; `*` matches within one path component.
+pub async fn held_lock_file(source: &Path, patterns: &[String]) -> Option<PathBuf> {
+    for pattern in patterns {
+        for candidate in expand_pattern(source, pattern).await {
+            if is_held(&candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+
+    None
+}
+
+/// Existing paths below `root` matching `pattern`
+async fn expand_pattern(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut matches = vec![root.to_path_buf()];
+
+    for part in pattern.split('/').filter(|p| !p.is_empty()) {
+        let mut next = Vec::new();
+
+        for dir in matches {
+            if !part.contains('*') {
+                next.push(dir.join(part));
+                continue;
+            }
+
+            let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+                continue;
+            };
+            let part = part.to_lowercase();
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if wildcard_match(&part, &entry.file_name().to_string_lossy().to_lowercase()) {
+                    next.push(entry.path());
+                }
+            }
+        }
+
+        matches = next;
+    }
+
+    matches
+}
+
+/// Windows applications keep their lock file open without sharing
+#[cfg(windows)]
+fn is_held(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    match std::fs::OpenOptions::new().read(true).share_mode(0).open(path) {
+        Ok(_) => false,
+        Err(e) => matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)),
+    }
+}
+
+/// Elsewhere lock files (often dangling symlinks) exist only while the application runs
+#[cfg(not(windows))]
+fn is_held(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_lock_files_are_found_by_pattern() {
+        let source = tempdir().unwrap();
+        tokio::fs::create_dir_all(source.path().join("abc.default")).await.unwrap();
+        tokio::fs::create_dir_all(source.path().join("xyz.work")).await.unwrap();
+        let patterns = vec!["*/lock".to_string()];
+
+        assert_eq!(held_lock_file(source.path(), &patterns).await, None);
+
+        std::os::unix::fs::symlink("127.0.0.1:+4242", source.path().join("xyz.work/lock")).unwrap();
+        assert_eq!(held_lock_file(source.path(), &patterns).await, Some(source.path().join("xyz.work/lock")));
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_failing_hook_fails_with_its_output() {
+        let job: BackupJob = serde_json::from_str(r#"{
+            "id": "hooked", "source": "/", "target": "/tmp",
+            "schedule": { "type": "interval", "seconds": 60 }
+        }"#).unwrap();
+
+        run_hook(&job, "pre", "test \"$KEEPHIVE_JOB_ID\" = hooked").await.unwrap();
+
+        let error = run_post_hook(&job, "echo paused >&2; exit 3", "success").await.unwrap_err();
+        assert!(error.to_string().contains("paused"), "{}", error);
+
+        run_post_hook(&job, "test \"$KEEPHIVE_OUTCOME\" = cancelled", "cancelled").await.unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_result_hooks_receive_json() {
+        let output = tempdir().unwrap();
+        let job: BackupJob = serde_json::from_str(&format!(r#"{{
+            "id": "reported", "source": "/", "target": "/tmp",
+            "schedule": {{ "type": "interval", "seconds": 60 }},
+            "hooks": {{
+                "on_success_command": "cat > {dir}/stdin.json",
+                "on_failure_command": "cp \"$KEEPHIVE_RESULT_FILE\" {dir}/file.json"
+            }}
+        }}"#, dir = output.path().display())).unwrap();
+
+        let metadata = BackupMetadata::new("reported_2025".to_string(), PathBuf::from("/tmp/reported_2025"));
+        let mut result = JobResult {
+            job_id: &job.id,
+            success: true,
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            error: None,
+            backup: Some(&metadata),
+        };
+        run_result_hook(&job, &result).await.unwrap();
+
+        result.success = false;
+        result.error = Some("Target full".to_string());
+        result.backup = None;
+        run_result_hook(&job, &result).await.unwrap();
+
+        let success: serde_json::Value = serde_json::from_slice(&std::fs::read(output.path().join("stdin.json")).unwrap()).unwrap();
+        assert_eq!(success["job_id"], "reported");
+        assert_eq!(success["backup"]["backup_name"], "reported_2025");
+
+        let failure: serde_json::Value = serde_json::from_slice(&std::fs::read(output.path().join("file.json")).unwrap()).unwrap();
+        assert_eq!(failure["success"], false);
+        assert_eq!(failure["error"], "Target full");
+        assert!(failure.get("backup").is_none());
+    }
+}
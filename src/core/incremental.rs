@@ -0,0 +1,108 @@
+//! Incremental backups: files whose size and modification time match the latest backup
+//! are hard-linked from it instead of copied, so every backup stays a complete tree that
+//! restore, verification and retention handle like any other.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::core::manifest::{contained_path, normalize_relative_path, BackupManifest, ManifestEntry};
+use crate::core::CopyError;
+
+/// Files of the backup an incremental backup builds on, by source path
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    backup_path: PathBuf,
+    files: HashMap<String, ManifestEntry>,
+}
+
+impl Baseline {
+    pub fn new(backup_path: PathBuf, manifest: BackupManifest) -> Self {
+        let files = manifest.files.into_iter()
+            .map(|entry| (entry.source_path().to_string(), entry))
+            .collect();
+
+        Self { backup_path, files }
+    }
+
+    /// Backup directory the unchanged files are linked from
+    pub fn backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    /// The earlier copy of `relative_path`, if the source file kept its size and modification time
+    pub fn unchanged(&self, relative_path: &Path, metadata: &std::fs::Metadata) -> Option<&ManifestEntry> {
+        let previous = self.files.get(&normalize_relative_path(relative_path))?;
+        let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+
+        (previous.size == metadata.len() && previous.modified.is_some() && previous.modified == modified)
+            .then_some(previous)
+    }
+
+    /// Hard-link the earlier copy `previous` to `target`; fails on filesystems without hard links,
+    /// and when the earlier manifest points outside its backup
+    pub async fn link(&self, previous: &ManifestEntry, target: &Path) -> Result<()> {
+        let Some(relative) = contained_path(&previous.path) else {
+            bail!("The previous manifest points outside its backup: {}", previous.path);
+        };
+        let original = self.backup_path.join(relative);
+
+        #[cfg(windows)]
+        let (original, target) = {
+            use crate::platform::traits::PathNormalizer;
+            use crate::platform::windows::WindowsPathNormalizer;
+            (WindowsPathNormalizer.normalize(&original), WindowsPathNormalizer.normalize(target))
+        };
+
+        tokio::fs::hard_link(&original, &target).await
+            .map_err(CopyError::target_side)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_only_matching_files_are_unchanged() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        tokio::fs::write(&path, b"abc").await.unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+
+        let manifest = BackupManifest::new("previous".into(), vec![
+            ManifestEntry::new(Path::new("docs/a.txt"), 3, modified),
+            ManifestEntry::new(Path::new("docs/b.txt"), 4, modified),
+            ManifestEntry::new(Path::new("docs/c.txt"), 3, None),
+        ]);
+        let baseline = Baseline::new(dir.path().to_path_buf(), manifest);
+
+        assert!(baseline.unchanged(Path::new("docs/a.txt"), &metadata).is_some());
+        assert!(baseline.unchanged(Path::new("docs/b.txt"), &metadata).is_none());
+        assert!(baseline.unchanged(Path::new("docs/c.txt"), &metadata).is_none());
+        assert!(baseline.unchanged(Path::new("docs/new.txt"), &metadata).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_link_stays_inside_the_previous_backup() {
+        let dir = tempdir().unwrap();
+        let previous = dir.path().join("previous");
+        tokio::fs::create_dir_all(&previous).await.unwrap();
+        tokio::fs::write(dir.path().join("secret.txt"), b"secret").await.unwrap();
+        tokio::fs::write(previous.join("a.txt"), b"abc").await.unwrap();
+
+        let baseline = Baseline::new(previous, BackupManifest::new("previous".into(), Vec::new()));
+        let escaping = ManifestEntry::new(Path::new("../secret.txt"), 6, None);
+        let linked = dir.path().join("linked.txt");
+
+        assert!(baseline.link(&escaping, &linked).await.is_err());
+        assert!(!linked.exists());
+
+        baseline.link(&ManifestEntry::new(Path::new("a.txt"), 3, None), &linked).await.unwrap();
+        assert_eq!(tokio::fs::read(&linked).await.unwrap(), b"abc");
+    }
+}
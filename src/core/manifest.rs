@@ -0,0 +1,395 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+use crate::core::copy_error::CopyFailure;
+use crate::state::json_file::{read_json_file, write_json_file};
+
+/// `path` from a manifest as a relative path that stays below the folder it is joined to;
+/// `None` for absolute paths, drive prefixes and `..`. Manifests live on the target, where
+/// anyone who can write there could edit them, so every path read back is checked.
+pub(crate) fn contained_path(path: &str) -> Option<&Path> {
+    let path = Path::new(path);
+    let contained = path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    (contained && !path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Manifest file written into each backup directory, listing every copied file.
+pub const MANIFEST_FILE: &str = ".keephive_manifest.json";
+
+/// Current manifest schema version
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// One file recorded in a backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the backup root, always `/`-separated
+    pub path: String,
+
+    /// File size in bytes
+    pub size: u64,
+
+    /// Source modification time at copy time
+    pub modified: Option<DateTime<Utc>>,
+
+    /// CRC-32 of the backup copy, when manifest checksums are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crc32: Option<u32>,
+
+    /// Path in the source, when the file was stored under another name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_path: Option<String>,
+}
+
+impl ManifestEntry {
+    /// Build an entry from a path relative to the backup root
+    pub fn new(relative_path: &Path, size: u64, modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            path: normalize_relative_path(relative_path),
+            size,
+            modified,
+            crc32: None,
+            original_path: None,
+        }
+    }
+
+    /// Record the CRC32 computed while the file was copied
+    pub fn with_crc32(mut self, crc32: Option<u32>) -> Self {
+        self.crc32 = crc32;
+        self
+    }
+
+    /// Record the path the file has in the source, if it differs from `path`
+    pub fn with_original_path(mut self, relative_path: &Path) -> Self {
+        let original = normalize_relative_path(relative_path);
+        self.original_path = (original != self.path).then_some(original);
+        self
+    }
+
+    /// Path relative to the source root, where the file is restored to
+    pub fn source_path(&self) -> &str {
+        self.original_path.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// A junction or symbolic link found in the source, recorded instead of followed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkEntry {
+    /// Path of the link relative to the backup root, always `/`-separated
+    pub path: String,
+
+    /// Where the link points, if it could be read
+    pub target: Option<String>,
+
+    /// Whether the link points to a directory (junctions always do)
+    pub directory: bool,
+}
+
+impl LinkEntry {
+    pub fn new(relative_path: &Path, target: Option<&Path>, directory: bool) -> Self {
+        Self {
+            path: normalize_relative_path(relative_path),
+            target: target.map(|t| t.display().to_string()),
+            directory,
+        }
+    }
+}
+
+/// A source file the backup could not copy, kept so restores can report it and the
+/// next backup can try it first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedEntry {
+    /// Path the file would have in the backup, always `/`-separated
+    pub path: String,
+
+    /// Path in the source, when it differs from `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_path: Option<String>,
+
+    /// Source size, if its metadata could be read
+    pub size: Option<u64>,
+
+    /// Source modification time, if its metadata could be read
+    pub modified: Option<DateTime<Utc>>,
+
+    pub reason: CopyFailure,
+}
+
+impl SkippedEntry {
+    pub fn new(backup_relative: &Path, source_relative: &Path, metadata: Option<&std::fs::Metadata>, reason: CopyFailure) -> Self {
+        let path = normalize_relative_path(backup_relative);
+        let original = normalize_relative_path(source_relative);
+
+        Self {
+            original_path: (original != path).then_some(original),
+            path,
+            size: metadata.map(|m| m.len()),
+            modified: metadata.and_then(|m| m.modified().ok()).map(DateTime::<Utc>::from),
+            reason,
+        }
+    }
+
+    /// Path relative to the source root
+    pub fn source_path(&self) -> &str {
+        self.original_path.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// List of files contained in a single backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+
+    /// Backup directory name
+    pub backup_name: String,
+
+    /// When the manifest was created
+    pub created_at: DateTime<Utc>,
+
+    pub files: Vec<ManifestEntry>,
+
+    /// Junctions and symbolic links of the source, which are not copied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<LinkEntry>,
+
+    /// Source files that existed but could not be copied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl BackupManifest {
+    pub fn new(backup_name: String, mut files: Vec<ManifestEntry>) -> Self {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Self {
+            version: MANIFEST_VERSION,
+            backup_name,
+            created_at: Utc::now(),
+            files,
+            links: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Record the links found in the source
+    pub fn with_links(mut self, mut links: Vec<LinkEntry>) -> Self {
+        links.sort_by(|a, b| a.path.cmp(&b.path));
+        self.links = links;
+        self
+    }
+
+    /// Record the source files that could not be copied
+    pub fn with_skipped(mut self, mut skipped: Vec<SkippedEntry>) -> Self {
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self.skipped = skipped;
+        self
+    }
+
+    /// Total size of all files in the backup
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+
+    /// Write the manifest into the backup directory and sync it. It is serialized
+    /// straight into the file, so manifests of millions of files need no second copy in memory.
+    pub async fn write(self, backup_path: &Path) -> Result<()> {
+        write_json_file(&backup_path.join(MANIFEST_FILE), self, false).await
+            .context("Failed to write backup manifest")
+    }
+
+    /// Load the manifest stored in a backup directory
+    pub async fn load(backup_path: &Path) -> Result<Self> {
+        read_json_file(&backup_path.join(MANIFEST_FILE)).await
+            .context("Failed to load manifest")
+    }
+
+    /// Load the stored manifest, or build one by scanning backups made before manifests existed
+    pub async fn load_or_scan(backup_path: &Path) -> Result<Self> {
+        if tokio::fs::try_exists(backup_path.join(MANIFEST_FILE)).await.unwrap_or(false) {
+            return Self::load(backup_path).await;
+        }
+
+        Self::scan(backup_path).await
+    }
+
+    /// Build a manifest by walking a backup directory.
+    ///
+    /// Modification times come from the backup copies, which may differ from the source.
+    pub async fn scan(backup_path: &Path) -> Result<Self> {
+        let backup_name = backup_path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("backup")
+            .to_string();
+
+        let mut files = Vec::new();
+        let mut pending = vec![backup_path.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await
+                .with_context(|| format!("Failed to read backup directory: {}", dir.display()))?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let metadata = entry.metadata().await?;
+
+                // KeepHive's own files live at the backup root
+                if dir == backup_path
+                    && entry.file_name().to_string_lossy().starts_with(".keephive")
+                {
+                    continue;
+                }
+
+                if metadata.is_dir() {
+                    pending.push(path);
+                } else if metadata.is_file() {
+                    let relative = path.strip_prefix(backup_path)
+                        .context("Failed to calculate relative path")?;
+                    let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+                    files.push(ManifestEntry::new(relative, metadata.len(), modified));
+                }
+            }
+        }
+
+        Ok(Self::new(backup_name, files))
+    }
+}
+
+/// Files that differ between two manifests
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    pub added: Vec<ManifestEntry>,
+    pub removed: Vec<ManifestEntry>,
+
+    /// (old, new) pairs whose size or modification time changed
+    pub modified: Vec<(ManifestEntry, ManifestEntry)>,
+}
+
+impl ManifestDiff {
+    /// Compare an older manifest against a newer one
+    pub fn between(old: &BackupManifest, new: &BackupManifest) -> Self {
+        let old_files: HashMap<&str, &ManifestEntry> = old.files.iter()
+            .map(|f| (f.path.as_str(), f))
+            .collect();
+
+        let new_paths: HashMap<&str, &ManifestEntry> = new.files.iter()
+            .map(|f| (f.path.as_str(), f))
+            .collect();
+
+        let mut diff = Self::default();
+
+        for entry in &new.files {
+            match old_files.get(entry.path.as_str()) {
+                None => diff.added.push(entry.clone()),
+                Some(previous) if previous.size != entry.size || previous.modified != entry.modified => {
+                    diff.modified.push(((*previous).clone(), entry.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+
+        diff.removed = old.files.iter()
+            .filter(|f| !new_paths.contains_key(f.path.as_str()))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Size change from old to new, in bytes
+    pub fn net_bytes(&self) -> i64 {
+        let added: i64 = self.added.iter().map(|f| f.size as i64).sum();
+        let removed: i64 = self.removed.iter().map(|f| f.size as i64).sum();
+        let modified: i64 = self.modified.iter()
+            .map(|(old, new)| new.size as i64 - old.size as i64)
+            .sum();
+
+        added - removed + modified
+    }
+}
+
+/// Render a relative path with `/` separators so manifests compare across platforms
+pub(crate) fn normalize_relative_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(path: &str, size: u64) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            size,
+            modified: None,
+            crc32: None,
+            original_path: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_added_removed_modified() {
+        let old = BackupManifest::new("a".into(), vec![
+            entry("keep.txt", 10),
+            entry("gone.txt", 5),
+            entry("grow.txt", 100),
+        ]);
+        let new = BackupManifest::new("b".into(), vec![
+            entry("keep.txt", 10),
+            entry("grow.txt", 300),
+            entry("docs/new.txt", 7),
+        ]);
+
+        let diff = ManifestDiff::between(&old, &new);
+
+        assert_eq!(diff.added, vec![entry("docs/new.txt", 7)]);
+        assert_eq!(diff.removed, vec![entry("gone.txt", 5)]);
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].1.path, "grow.txt");
+        assert_eq!(diff.net_bytes(), 7 - 5 + 200);
+    }
+
+    #[test]
+    fn test_diff_identical_is_empty() {
+        let files = vec![entry("a.txt", 1), entry("b/c.txt", 2)];
+        let old = BackupManifest::new("a".into(), files.clone());
+        let new = BackupManifest::new("b".into(), files);
+
+        assert!(ManifestDiff::between(&old, &new).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_write_and_load_roundtrip() {
+        let dir = tempdir().unwrap();
+        let manifest = BackupManifest::new("backup".into(), vec![entry("x/y.txt", 3)]);
+
+        manifest.clone().write(dir.path()).await.unwrap();
+        let loaded = BackupManifest::load(dir.path()).await.unwrap();
+
+        assert_eq!(loaded.files, manifest.files);
+        assert_eq!(loaded.version, MANIFEST_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_scan_skips_keephive_files() {
+        let dir = tempdir().unwrap();
+        tokio::fs::create_dir_all(dir.path().join("sub")).await.unwrap();
+        tokio::fs::write(dir.path().join("sub/file.txt"), b"hello").await.unwrap();
+        tokio::fs::write(dir.path().join(".keephive_complete"), b"{}").await.unwrap();
+
+        let manifest = BackupManifest::load_or_scan(dir.path()).await.unwrap();
+
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].path, "sub/file.txt");
+        assert_eq!(manifest.files[0].size, 5);
+    }
+}
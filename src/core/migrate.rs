@@ -0,0 +1,200 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::Durability;
+use crate::core::backup::BackupOrchestrator;
+use crate::core::manifest::BackupManifest;
+use crate::core::replication::Replicator;
+use crate::core::verify::files_equal;
+use crate::core::BandwidthLimiter;
+use crate::platform::sync_directory;
+
+/// Outcome of moving a job's backups to a new target
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    /// Old and new path of every migrated backup, oldest first
+    pub backups: Vec<(PathBuf, PathBuf)>,
+
+    /// Files compared byte for byte against the original backup
+    pub files_verified: u64,
+    pub bytes_verified: u64,
+}
+
+impl MigrationReport {
+    /// New location of a backup that was at `old_path`
+    pub fn new_path_of(&self, old_path: &Path) -> Option<&Path> {
+        self.backups.iter()
+            .find(|(old, _)| old == old_path)
+            .map(|(_, new)| new.as_path())
+    }
+}
+
+/// Copy every completed backup of the job backing up `source` from `old_target` to
+/// `new_target` and verify each copy. Backups of other jobs sharing the target stay put.
+///
+/// The originals are left in place; remove them with [`remove_originals`] once the job
+/// has been pointed at the new target.
+pub async fn migrate_target(
+    old_target: &Path,
+    source: &Path,
+    new_target: &Path,
+    cancellation: CancellationToken,
+) -> Result<MigrationReport> {
+    if old_target == new_target {
+        bail!("New target is the same as the current one: {}", new_target.display());
+    }
+
+    if new_target.starts_with(old_target) {
+        bail!("New target must not be inside the current one: {}", new_target.display());
+    }
+
+    // Oldest first, so directory times on the new target keep the same order for retention
+    let backups = BackupOrchestrator::list_job_backups(old_target, source).await
+        .with_context(|| format!("Failed to list backups in {}", old_target.display()))?;
+
+    info!(
+        "Migrating {} backups from {} to {}",
+        backups.len(),
+        old_target.display(),
+        new_target.display()
+    );
+
+    let replicator = Replicator::new(Durability::Strict, Arc::new(BandwidthLimiter::default()));
+    let mut report = MigrationReport::default();
+
+    for backup in &backups {
+        if cancellation.is_cancelled() {
+            bail!("Migration cancelled after {} backups", report.backups.len());
+        }
+
+        if backup.is_file() {
+            let copy = migrate_archive(backup, new_target).await?;
+            report.files_verified += 1;
+            report.bytes_verified += tokio::fs::metadata(&copy).await?.len();
+            info!("Migrated and verified archive {}", copy.display());
+            report.backups.push((backup.clone(), copy));
+            continue;
+        }
+
+        let copy = replicator.replicate(backup, new_target, cancellation.clone()).await
+            .with_context(|| format!("Failed to copy {}", backup.display()))?;
+
+        let manifest = BackupManifest::load_or_scan(backup).await?;
+        for entry in &manifest.files {
+            let equal = files_equal(&backup.join(&entry.path), &copy.join(&entry.path)).await
+                .with_context(|| format!("Failed to verify {} in {}", entry.path, copy.display()))?;
+
+            if !equal {
+                bail!("Copy of {} differs from the original: {}", backup.display(), entry.path);
+            }
+
+            report.files_verified += 1;
+            report.bytes_verified += entry.size;
+        }
+
+        info!("Migrated and verified {} ({} files)", copy.display(), manifest.files.len());
+        report.backups.push((backup.clone(), copy));
+    }
+
+    Ok(report)
+}
+
+/// Copy an archive backup under a `_PARTIAL` name, so it is only listed once it is verified
+async fn migrate_archive(archive: &Path, new_target: &Path) -> Result<PathBuf> {
+    let name = archive.file_name().context("Archive has no file name")?;
+    let copy = new_target.join(name);
+    let partial = new_target.join(format!("{}_PARTIAL", name.to_string_lossy()));
+
+    tokio::fs::create_dir_all(new_target).await
+        .with_context(|| format!("Failed to create {}", new_target.display()))?;
+    tokio::fs::copy(archive, &partial).await
+        .with_context(|| format!("Failed to copy {}", archive.display()))?;
+    tokio::fs::File::open(&partial).await?.sync_all().await
+        .with_context(|| format!("Failed to sync {}", partial.display()))?;
+
+    if !files_equal(archive, &partial).await? {
+        bail!("Copy of {} differs from the original", archive.display());
+    }
+
+    tokio::fs::rename(&partial, &copy).await
+        .with_context(|| format!("Failed to finalize {}", copy.display()))?;
+    sync_directory(new_target).await?;
+    Ok(copy)
+}
+
+/// Delete the original backups of a finished migration, returning how many were removed
+pub async fn remove_originals(report: &MigrationReport) -> usize {
+    let mut removed = 0;
+
+    for (old, _) in &report.backups {
+        let result = if old.is_file() {
+            tokio::fs::remove_file(old).await
+        } else {
+            tokio::fs::remove_dir_all(old).await
+        };
+
+        match result {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove migrated backup {}: {}", old.display(), e),
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::backup::COMPLETE_MARKER;
+    use tempfile::tempdir;
+
+    async fn create_backup(target: &Path, name: &str, content: &[u8]) -> PathBuf {
+        let backup = target.join(name);
+        tokio::fs::create_dir_all(backup.join("sub")).await.unwrap();
+        tokio::fs::write(backup.join("sub/a.txt"), content).await.unwrap();
+        tokio::fs::write(backup.join(COMPLETE_MARKER), b"{}").await.unwrap();
+        backup
+    }
+
+    #[tokio::test]
+    async fn test_migrate_copies_then_removes_originals() {
+        let old = tempdir().unwrap();
+        let new = tempdir().unwrap();
+        let first = create_backup(old.path(), "docs_2025-01-01_000000_000", b"one").await;
+        create_backup(old.path(), "docs_2025-01-02_000000_000", b"two").await;
+        tokio::fs::create_dir_all(old.path().join("docs_2025-01-03_000000_000_PARTIAL")).await.unwrap();
+        let other_job = create_backup(old.path(), "photos_2025-01-02_000000_000", b"other").await;
+
+        let report = migrate_target(old.path(), Path::new("/data/docs"), new.path(), CancellationToken::new()).await.unwrap();
+
+        assert_eq!(report.backups.len(), 2);
+        assert_eq!(report.files_verified, 2);
+        assert!(first.exists(), "Originals are kept until removed explicitly");
+        assert_eq!(
+            report.new_path_of(&first),
+            Some(new.path().join("docs_2025-01-01_000000_000").as_path())
+        );
+
+        let migrated = BackupOrchestrator::list_complete_backups(new.path()).await.unwrap();
+        assert_eq!(migrated.len(), 2);
+
+        let second = new.path().join("docs_2025-01-02_000000_000");
+        assert_eq!(tokio::fs::read(second.join("sub/a.txt")).await.unwrap(), b"two");
+
+        assert_eq!(remove_originals(&report).await, 2);
+        assert!(!first.exists());
+        assert!(other_job.exists(), "Backups of other jobs are neither moved nor removed");
+    }
+
+    #[tokio::test]
+    async fn test_migrate_rejects_nested_target() {
+        let old = tempdir().unwrap();
+
+        let result = migrate_target(old.path(), Path::new("/data/docs"), &old.path().join("nested"), CancellationToken::new()).await;
+
+        assert!(result.is_err());
+    }
+}
@@ -1,7 +1,61 @@
+pub mod analysis;
+pub mod archive;
+pub mod autotune;
 pub mod backup;
+pub mod chain;
+pub mod checksum;
 pub mod copy_engine;
+pub mod containers;
+pub mod copy_error;
+pub mod dry_run;
+pub mod dump;
+pub mod exclude;
+pub mod hooks;
+pub mod incremental;
+pub mod manifest;
+pub mod migrate;
+pub mod names;
+pub mod normalize;
+pub mod path_check;
+pub mod processes;
+pub mod replication;
+pub mod restore;
+pub mod shadow_copy;
+pub mod system_state;
+pub mod throttle;
+pub mod transform;
 pub mod validation;
+pub mod verify;
+pub mod volume;
 
+pub use analysis::{
+    largest_deltas, largest_directories, largest_files, project_retention, DirectorySize, ProjectedRemoval, RemovalReason,
+    RetainedBackup, RetentionProjection, RetentionRules, SizeDelta,
+};
+pub use archive::{is_archive_backup, ArchiveWriter};
+pub use autotune::{Autotuner, CopyTuning};
 pub use backup::BackupOrchestrator;
-pub use copy_engine::{CopyEngine, CopyProgress};
-pub use validation::validate_backup_job;
+pub use chain::{exposed_jobs, pull_latest, ExposedJobs, PullClient, PullSource, ReplicaServer};
+pub use checksum::{crc32_file, hmac_sha256, md5_file, sha256_file, to_hex, Crc32, Md5, Sha256};
+pub use containers::{docker_volume_export, pause_volume_users, unpause, wsl_export};
+pub use copy_engine::{CopyEngine, CopyOptions, CopyProgress};
+pub use copy_error::{CopyError, CopyFailure, FailureCounts};
+pub use dry_run::{dry_run, DryRunReport, FileTally};
+pub use dump::{dump_dir, run_dump, DumpOutcome};
+pub use exclude::Exclusions;
+pub use hooks::{held_lock_file, run_hook, run_post_hook, run_result_hook, JobResult};
+pub use incremental::Baseline;
+pub use manifest::{BackupManifest, LinkEntry, ManifestDiff, ManifestEntry, SkippedEntry, MANIFEST_FILE};
+pub use migrate::{migrate_target, remove_originals, MigrationReport};
+pub use names::{write_renames, Rename, RenameReason, RENAMES_FILE};
+pub use path_check::{check_path_lengths, path_limit, LongPath, PathReport};
+pub use processes::running_processes;
+pub use replication::{replica_location, Replicator};
+pub use restore::{ConflictPolicy, RestoreOptions, RestoreOrchestrator, RestoreProgress, RestoreReport};
+pub use shadow_copy::{snapshot_source, SourceSnapshot};
+pub use system_state::export_system_state;
+pub use throttle::{active_window, BandwidthLimiter};
+pub use transform::{CopyTransform, Deflate, FileTransform, TransformChain};
+pub use validation::{calculate_dir_size, probe_share, probe_target, unc_share, validate_backup_job, validate_source};
+pub use verify::{verify_backup, VerifySummary};
+pub use volume::{physical_disks, relocate_target, volume_id, VolumeId};
@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::config::{NameConflicts, NameNormalization};
+use crate::core::checksum::Crc32;
+use crate::core::manifest::normalize_relative_path;
+use crate::core::normalize::normalize;
+
+/// Mapping file written into a backup whose copy renamed anything
+pub const RENAMES_FILE: &str = ".keephive_renames.json";
+
+/// Characters Windows, SMB shares and exFAT reject in names
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves regardless of extension
+pub(crate) const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul",
+    "com0", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt0", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Why a name was changed on the way into a backup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenameReason {
+    /// Only differs in case from another name in the same folder
+    CaseConflict,
+    /// Contains characters the target does not accept
+    InvalidCharacters,
+    /// A Windows device name such as `CON` or `LPT1`
+    ReservedName,
+    /// Converted to the configured Unicode normalization form
+    Normalized,
+}
+
+/// A file or folder stored under another name than in the source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rename {
+    /// Path in the source, relative to the source root and `/`-separated
+    pub original: String,
+
+    /// Path in the backup, relative to the backup root and `/`-separated
+    pub renamed: String,
+
+    pub reason: RenameReason,
+}
+
+impl Rename {
+    pub fn new(original: &Path, renamed: &Path, reason: RenameReason) -> Self {
+        Self {
+            original: normalize_relative_path(original),
+            renamed: normalize_relative_path(renamed),
+            reason,
+        }
+    }
+}
+
+/// Write the renames of a backup into its directory
+pub async fn write_renames(backup_path: &Path, renames: &[Rename]) -> Result<()> {
+    let json = serde_json::to_vec_pretty(renames)
+        .context("Failed to serialize rename mapping")?;
+
+    tokio::fs::write(backup_path.join(RENAMES_FILE), json).await
+        .context("Failed to write rename mapping")
+}
+
+/// Names for the entries of one folder as stored in the backup, in the order of `names`:
+/// in the `normalization` form if one is set and, unless `strategy` is off, holdable by
+/// a case-insensitive, Windows-compatible target.
+///
+/// Names are taken in sorted order, so the first of several names that end up equal
+/// keeps it and the same source always maps to the same backup names.
+pub fn safe_names(
+    names: &[String],
+    strategy: NameConflicts,
+    normalization: Option<NameNormalization>,
+) -> Vec<(String, Option<RenameReason>)> {
+    if strategy == NameConflicts::Off && normalization.is_none() {
+        return names.iter().map(|name| (name.clone(), None)).collect();
+    }
+
+    let mut result: Vec<(String, Option<RenameReason>)> = names.iter()
+        .map(|name| {
+            let normalized = normalization.map(|form| normalize(name, form))
+                .filter(|normalized| normalized != name);
+            let current = normalized.as_deref().unwrap_or(name);
+
+            match sanitize(current) {
+                Some((safe, reason)) if strategy != NameConflicts::Off => (safe, Some(reason)),
+                _ => match normalized {
+                    Some(normalized) => (normalized, Some(RenameReason::Normalized)),
+                    None => (name.clone(), None),
+                },
+            }
+        })
+        .collect();
+
+    // Without conflict handling the target is taken to be case-sensitive
+    let key = |name: &str| match strategy {
+        NameConflicts::Off => name.to_string(),
+        _ => name.to_lowercase(),
+    };
+
+    let mut order: Vec<usize> = (0..names.len()).collect();
+    order.sort_by(|&a, &b| names[a].cmp(&names[b]));
+
+    // Unchanged names are kept first, so a renamed one never displaces them
+    let mut taken = HashSet::new();
+    let mut pending = Vec::new();
+    for &i in &order {
+        if result[i].1.is_none() && taken.insert(key(&result[i].0)) {
+            continue;
+        }
+        pending.push(i);
+    }
+
+    for i in pending {
+        let (name, reason) = result[i].clone();
+
+        let mut candidate = name.clone();
+        let mut attempt = 1;
+        while !taken.insert(key(&candidate)) {
+            attempt += 1;
+            candidate = disambiguate(&name, &names[i], strategy, attempt);
+        }
+
+        result[i] = (candidate, Some(reason.unwrap_or(RenameReason::CaseConflict)));
+    }
+
+    result
+}
+
+/// A Windows-compatible replacement for `name`, if it needs one
+fn sanitize(name: &str) -> Option<(String, RenameReason)> {
+    let mut safe: String = name.chars()
+        .map(|c| if INVALID_CHARS.contains(&c) || c.is_control() || c == '\u{FFFD}' { '_' } else { c })
+        .collect();
+
+    // Windows silently drops a trailing dot or space
+    if safe.ends_with(['.', ' ']) {
+        safe.pop();
+        safe.push('_');
+    }
+
+    let base = safe.split('.').next().unwrap_or(&safe).trim_end().to_lowercase();
+    if RESERVED_NAMES.contains(&base.as_str()) {
+        let (stem, extension) = split_extension(&safe);
+        return Some((format!("{}_{}", stem, extension), RenameReason::ReservedName));
+    }
+
+    (safe != name).then_some((safe, RenameReason::InvalidCharacters))
+}
+
+/// Variant `attempt` (2 or more) of a name that is already taken
+fn disambiguate(name: &str, original: &str, strategy: NameConflicts, attempt: u32) -> String {
+    let (stem, extension) = split_extension(name);
+
+    match strategy {
+        // The hash of the source name keeps the backup name stable when other names come and go
+        NameConflicts::Hash if attempt == 2 => {
+            let mut crc = Crc32::new();
+            crc.update(original.as_bytes());
+            format!("{}~{:08x}{}", stem, crc.finish(), extension)
+        }
+        _ => format!("{} ({}){}", stem, attempt, extension),
+    }
+}
+
+/// `("report", ".txt")`; dot files and names without a dot have no extension
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_case_conflicts_and_invalid_names() {
+        let result = safe_names(&names(&["Readme.md", "README.md", "a:b?.txt", "con.txt", "notes.", "ok"]), NameConflicts::Suffix, None);
+
+        assert_eq!(result, vec![
+            ("Readme (2).md".to_string(), Some(RenameReason::CaseConflict)),
+            ("README.md".to_string(), None),
+            ("a_b_.txt".to_string(), Some(RenameReason::InvalidCharacters)),
+            ("con_.txt".to_string(), Some(RenameReason::ReservedName)),
+            ("notes_".to_string(), Some(RenameReason::InvalidCharacters)),
+            ("ok".to_string(), None),
+        ]);
+    }
+
+    #[test]
+    fn test_normalized_names_stay_apart() {
+        let result = safe_names(&names(&["Cafe\u{301}", "Caf\u{E9}", "Cafe"]), NameConflicts::Off, Some(NameNormalization::Nfc));
+
+        assert_eq!(result, vec![
+            ("Caf\u{E9} (2)".to_string(), Some(RenameReason::Normalized)),
+            ("Caf\u{E9}".to_string(), None),
+            ("Cafe".to_string(), None),
+        ]);
+    }
+
+    #[test]
+    fn test_hash_strategy_is_stable_and_off_keeps_names() {
+        let list = names(&["a.txt", "A.txt"]);
+
+        let first = safe_names(&list, NameConflicts::Hash, None);
+        assert_eq!(first[1].0, "A.txt");
+        assert!(first[0].0.starts_with("a~") && first[0].0.ends_with(".txt"));
+        assert_eq!(safe_names(&names(&["A.txt", "a.txt", "b.txt"]), NameConflicts::Hash, None)[1].0, first[0].0);
+
+        assert_eq!(safe_names(&list, NameConflicts::Off, None), vec![
+            ("a.txt".to_string(), None),
+            ("A.txt".to_string(), None),
+        ]);
+    }
+}
@@ -0,0 +1,34 @@
+//! Unicode normalization of file names.
+//!
+//! macOS and some NAS systems store names decomposed (NFD) while Windows and Linux
+//! usually use composed names (NFC), so the same name can arrive in either form.
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::NameNormalization;
+
+/// `name` in the given normalization form
+pub fn normalize(name: &str, form: NameNormalization) -> String {
+    match form {
+        NameNormalization::Nfd => name.nfd().collect(),
+        NameNormalization::Nfc => name.nfc().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_forms() {
+        use NameNormalization::{Nfc, Nfd};
+
+        assert_eq!(normalize("Cafe\u{301}.txt", Nfc), "Caf\u{E9}.txt");
+        assert_eq!(normalize("Caf\u{E9}.txt", Nfd), "Cafe\u{301}.txt");
+        assert_eq!(normalize("\u{1EA4}", Nfd), "A\u{302}\u{301}", "Vietnamese letters decompose fully");
+        assert_eq!(normalize("a\u{301}\u{323}", Nfc), "\u{1EA1}\u{301}", "Marks are put in canonical order first");
+        assert_eq!(normalize("\u{D55C}\u{AE00}", Nfd), "\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}\u{11AF}");
+        assert_eq!(normalize("\u{1112}\u{1161}\u{11AB}", Nfc), "\u{D55C}");
+        assert_eq!(normalize("report.txt", Nfc), "report.txt");
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+
+use crate::core::copy_engine::CopyOptions;
+use crate::core::exclude::is_backup_area;
+use crate::core::names::safe_names;
+
+/// Longest name a single path component may have on NTFS, exFAT, ext4 and SMB
+pub const MAX_COMPONENT_LENGTH: usize = 255;
+
+/// `MAX_PATH`: the limit of network shares that reject the `\\?\UNC\` long path prefix
+pub const SHARE_PATH_LIMIT: usize = 260;
+
+/// Offending paths listed in logs and errors; the count covers all of them
+const REPORTED_PATHS: usize = 20;
+
+/// A file or folder whose backup path would be too long
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LongPath {
+    /// Path relative to the source root
+    pub relative: PathBuf,
+
+    /// Length of the full path in the backup
+    pub length: usize,
+
+    /// Characters over the limit; for a name over the component limit, over that one
+    pub excess: usize,
+}
+
+/// Paths that would not fit on the target, found before anything is copied
+#[derive(Debug, Clone, Default)]
+pub struct PathReport {
+    pub limit: usize,
+
+    /// Length of the backup directory every path starts with
+    pub base_length: usize,
+
+    pub too_long: Vec<LongPath>,
+}
+
+impl PathReport {
+    pub fn is_clean(&self) -> bool {
+        self.too_long.is_empty()
+    }
+
+    /// How to make the paths fit: the longest name in each offending path, grouped by
+    /// the folder it belongs to, with how far it has to shrink to fix all of them
+    pub fn suggestions(&self) -> Vec<String> {
+        let mut groups: BTreeMap<PathBuf, (usize, usize)> = BTreeMap::new();
+
+        for long in &self.too_long {
+            let longest = long.relative.ancestors()
+                .filter(|p| !p.as_os_str().is_empty())
+                .max_by_key(|p| p.file_name().map_or(0, |n| path_length(Path::new(n))))
+                .unwrap_or(&long.relative)
+                .to_path_buf();
+
+            let group = groups.entry(longest).or_default();
+            group.0 += 1;
+            group.1 = group.1.max(long.excess);
+        }
+
+        let mut suggestions: Vec<(usize, String)> = groups.into_iter()
+            .map(|(path, (count, excess))| {
+                let name_length = path.file_name().map_or(0, |n| path_length(Path::new(n)));
+                let text = if excess < name_length {
+                    format!("shorten \"{}\" from {} to {} characters ({} paths)",
+                        path.display(), name_length, name_length - excess, count)
+                } else {
+                    format!("move \"{}\" higher up or choose a shorter target ({} paths)", path.display(), count)
+                };
+                (count, text)
+            })
+            .collect();
+
+        suggestions.sort_by_key(|s| std::cmp::Reverse(s.0));
+        let mut suggestions: Vec<String> = suggestions.into_iter().map(|(_, text)| text).collect();
+
+        if self.base_length > self.limit / 2 {
+            suggestions.push(format!(
+                "the backup folder alone takes {} of the {} characters; a target closer to the share root gives every path more room",
+                self.base_length, self.limit
+            ));
+        }
+
+        suggestions
+    }
+
+    /// One-line summary for errors and the job's state
+    pub fn summary(&self) -> String {
+        let examples: Vec<String> = self.too_long.iter()
+            .take(3)
+            .map(|l| l.relative.display().to_string())
+            .collect();
+
+        format!(
+            "{} paths would exceed the target's {}-character limit (e.g. {})",
+            self.too_long.len(), self.limit, examples.join(", ")
+        )
+    }
+
+    /// Offending paths and suggestions, one per line, for logs and `--check-paths`
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.too_long.iter()
+            .take(REPORTED_PATHS)
+            .map(|l| format!("{} ({} characters, {} too many)", l.relative.display(), l.length, l.excess))
+            .collect();
+
+        if self.too_long.len() > REPORTED_PATHS {
+            lines.push(format!("... and {} more", self.too_long.len() - REPORTED_PATHS));
+        }
+
+        lines.extend(self.suggestions().into_iter().map(|s| format!("Suggestion: {}", s)));
+        lines
+    }
+}
+
+/// Path length limit of `target`: `max_path_length` if set, `MAX_PATH` for network shares
+/// that cannot be reached through the `\\?\UNC\` prefix needed for more, and the platform
+/// limit otherwise
+pub async fn path_limit(target: &Path, max_path_length: Option<usize>) -> usize {
+    if let Some(limit) = max_path_length {
+        return limit;
+    }
+
+    let text = target.to_string_lossy();
+    if let Some(share) = text.strip_prefix(r"\\")
+        && !share.starts_with(r"?\")
+        && !accepts_long_paths(share).await
+    {
+        return SHARE_PATH_LIMIT;
+    }
+
+    if cfg!(windows) { 32767 } else { 4096 }
+}
+
+/// Whether the share path `\\<share>` can be opened through the `\\?\UNC\` prefix; some
+/// servers reject it, which limits their paths to `MAX_PATH`
+async fn accepts_long_paths(share: &str) -> bool {
+    let prefixed = PathBuf::from(format!(r"\\?\UNC\{}", share));
+    tokio::fs::metadata(prefixed).await.is_ok()
+}
+
+/// Walk `source` the way a backup into `backup_path` would and list every path that
+/// would not fit, honoring the job's exclusions and renames
+pub async fn check_path_lengths(source: &Path, backup_path: &Path, options: &CopyOptions, limit: usize) -> Result<PathReport> {
+    let mut report = PathReport {
+        limit,
+        base_length: path_length(backup_path),
+        too_long: Vec::new(),
+    };
+    let mut pending = vec![(source.to_path_buf(), backup_path.to_path_buf())];
+
+    while let Some((dir, target_dir)) = pending.pop() {
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            // The copy reports unreadable folders itself
+            Err(_) => continue,
+        };
+
+        let mut listing = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            listing.push(entry);
+        }
+
+        let names: Vec<String> = listing.iter()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        let target_names = safe_names(&names, options.name_conflicts, options.normalization);
+
+        for (entry, (target_name, rename)) in listing.into_iter().zip(target_names) {
+            let Ok(metadata) = entry.metadata().await else { continue };
+            let source_path = entry.path();
+            let relative = source_path.strip_prefix(source)
+                .context("Failed to calculate relative path")?;
+
+            if metadata.is_symlink() || options.exclusions.excludes(relative, &metadata) {
+                continue;
+            }
+            if metadata.is_dir() && is_backup_area(&source_path).await {
+                continue;
+            }
+
+            let target_path = match rename {
+                Some(_) => target_dir.join(&target_name),
+                None => target_dir.join(entry.file_name()),
+            };
+
+            let length = path_length(&target_path);
+            let name_length = path_length(Path::new(&entry.file_name()));
+            let excess = if name_length > MAX_COMPONENT_LENGTH {
+                name_length - MAX_COMPONENT_LENGTH
+            } else {
+                length.saturating_sub(limit)
+            };
+
+            if excess > 0 {
+                report.too_long.push(LongPath { relative: relative.to_path_buf(), length, excess });
+                // Everything below is too long as well and would only repeat the folder
+                continue;
+            }
+
+            if metadata.is_dir() {
+                pending.push((source_path, target_path));
+            }
+        }
+    }
+
+    report.too_long.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(report)
+}
+
+/// Length as the target counts it: UTF-16 units on Windows, bytes elsewhere
+fn path_length(path: &Path) -> usize {
+    #[cfg(windows)]
+    {
+        path.as_os_str().encode_wide().count()
+    }
+
+    #[cfg(not(windows))]
+    {
+        path.as_os_str().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_shares_rejecting_long_paths_get_max_path() {
+        // Cannot be reached through \\?\UNC\ here, as when the server rejects the prefix
+        assert_eq!(path_limit(Path::new(r"\\nas\backups"), None).await, SHARE_PATH_LIMIT);
+        assert_eq!(path_limit(Path::new(r"\\nas\backups"), Some(400)).await, 400);
+        assert!(path_limit(Path::new("/mnt/backups"), None).await > SHARE_PATH_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn test_reports_too_long_paths_with_suggestions() {
+        let source = tempdir().unwrap();
+        let long_folder = "a".repeat(60);
+        tokio::fs::create_dir_all(source.path().join(&long_folder).join("deeper")).await.unwrap();
+        tokio::fs::write(source.path().join(&long_folder).join("deeper/file.txt"), b"x").await.unwrap();
+        tokio::fs::write(source.path().join(&long_folder).join("f.txt"), b"x").await.unwrap();
+        tokio::fs::write(source.path().join("short.txt"), b"x").await.unwrap();
+
+        let backup = Path::new("/backups/docs_2026-01-01_000000");
+        let limit = path_length(&backup.join(&long_folder).join("deeper/file.txt")) - 1;
+        let report = check_path_lengths(source.path(), backup, &CopyOptions::default(), limit).await.unwrap();
+
+        assert_eq!(report.too_long.len(), 1, "{:?}", report.too_long);
+        assert_eq!(report.too_long[0].relative, PathBuf::from(&long_folder).join("deeper/file.txt"));
+        assert_eq!(report.too_long[0].excess, 1);
+        assert_eq!(report.suggestions()[0], format!("shorten \"{}\" from 60 to 59 characters (1 paths)", long_folder));
+        assert!(report.summary().starts_with("1 paths would exceed"));
+    }
+}
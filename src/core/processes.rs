@@ -0,0 +1,114 @@
+//! Check for applications whose open data files would be inconsistent in a backup.
+
+use std::collections::HashSet;
+
+/// Which of `names` are running, as configured. Names are compared without case and
+/// without a trailing `.exe`, so `outlook.exe` and `OUTLOOK` are the same process.
+pub async fn running_processes(names: &[String]) -> Vec<String> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+
+    let running: HashSet<String> = process_names().await
+        .iter()
+        .map(|name| comparable(name))
+        .collect();
+
+    names.iter()
+        .filter(|name| running.contains(&comparable(name)))
+        .cloned()
+        .collect()
+}
+
+fn comparable(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+/// Image names of all processes, from `tasklist`
+#[cfg(windows)]
+async fn process_names() -> Vec<String> {
+    // Nano Server and most container images have no tasklist
+    if crate::platform::slim_mode() {
+        tracing::debug!("Slim mode: busy processes are not checked");
+        return Vec::new();
+    }
+
+    let output = match tokio::process::Command::new("tasklist")
+        .args(["/FO", "CSV", "/NH"])
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            tracing::warn!("tasklist failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+            return Vec::new();
+        }
+        Err(e) => {
+            tracing::warn!("Failed to run tasklist: {}", e);
+            return Vec::new();
+        }
+    };
+
+    // "outlook.exe","1234","Console","1","250,000 K"
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split("\",\"").next())
+        .map(|name| name.trim_matches('"').to_string())
+        .collect()
+}
+
+/// Executable names of all processes, from `/proc`
+#[cfg(not(windows))]
+async fn process_names() -> Vec<String> {
+    let Ok(mut entries) = tokio::fs::read_dir("/proc").await else {
+        return Vec::new();
+    };
+
+    let mut names = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if !entry.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+
+        // `comm` is cut to 15 characters, the first argument is not
+        let command = tokio::fs::read(entry.path().join("cmdline")).await.unwrap_or_default();
+        let program = command.split(|&b| b == 0).next().unwrap_or_default();
+        let program = String::from_utf8_lossy(program);
+        match program.rsplit(['/', '\\']).next().filter(|name| !name.is_empty()) {
+            Some(name) => names.push(name.to_string()),
+            None => {
+                if let Ok(comm) = tokio::fs::read_to_string(entry.path().join("comm")).await {
+                    names.push(comm.trim().to_string());
+                }
+            }
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_compare_without_case_and_extension() {
+        assert_eq!(comparable("OUTLOOK.EXE"), comparable("outlook"));
+        assert_ne!(comparable("devenv.exe"), comparable("devenv.com"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_finds_the_running_test_binary() {
+        let exe = std::env::current_exe().unwrap();
+        let own = exe.file_name().unwrap().to_string_lossy().to_uppercase();
+
+        let running = running_processes(&[own.clone(), "surely-not-running.exe".to_string()]).await;
+
+        assert_eq!(running, vec![own]);
+    }
+}
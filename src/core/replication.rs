@@ -0,0 +1,514 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::Durability;
+use crate::core::backup::{BackupOrchestrator, COMPLETE_MARKER};
+use crate::core::{sha256_file, BandwidthLimiter, CopyEngine, CopyProgress};
+use crate::observability::format_bytes;
+use crate::platform::{sync_directory, PrivateDir};
+use crate::state::UsageStore;
+use crate::storage::{BackendRegistry, DirectoryUpload, StorageBackend, TargetUrl};
+
+/// Copies completed backups to secondary targets
+pub struct Replicator {
+    copy_engine: CopyEngine,
+    durability: Durability,
+    limiter: Arc<BandwidthLimiter>,
+    backends: Arc<BackendRegistry>,
+    verify_sample: usize,
+    /// Where remote transfers are accounted, and for which job
+    usage: Option<(Arc<UsageStore>, String)>,
+}
+
+impl Replicator {
+    pub fn new(durability: Durability, limiter: Arc<BandwidthLimiter>) -> Self {
+        Self {
+            copy_engine: CopyEngine::with_bandwidth_limiter(limiter.clone()),
+            durability,
+            limiter,
+            backends: Arc::new(BackendRegistry::new()),
+            verify_sample: 0,
+            usage: None,
+        }
+    }
+
+    /// Account bytes sent to and read back from remote backends to `job_id`
+    pub fn with_usage(mut self, usage: Arc<UsageStore>, job_id: &str) -> Self {
+        self.usage = Some((usage, job_id.to_string()));
+        self
+    }
+
+    /// Download this many files of each remote upload again and compare them with the
+    /// local copy, for backends that do not checksum uploads themselves
+    pub fn with_verify_sample(mut self, files: usize) -> Self {
+        self.verify_sample = files;
+        self
+    }
+
+    /// Resolve `scheme://` replica targets through a shared registry
+    pub fn with_backends(mut self, backends: Arc<BackendRegistry>) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Copy a completed backup directory into `replica_target`, returning the replica path.
+    ///
+    /// The copy is staged as `<name>_PARTIAL` and renamed once every file made it,
+    /// so an interrupted copy is never counted as a backup by retention.
+    pub async fn replicate(
+        &self,
+        backup_path: &Path,
+        replica_target: &Path,
+        cancellation: CancellationToken,
+    ) -> Result<PathBuf> {
+        let backup_name = backup_path.file_name()
+            .context("Backup path has no directory name")?
+            .to_string_lossy()
+            .into_owned();
+
+        if TargetUrl::from_path(replica_target).is_some() {
+            let backend = self.backends.open(replica_target)?;
+            self.upload(backend.as_ref(), backup_path, &backup_name, replica_target, cancellation).await?;
+            return Ok(replica_target.join(&backup_name));
+        }
+
+        let final_path = replica_target.join(&backup_name);
+        let staging_path = replica_target.join(format!("{}_PARTIAL", backup_name));
+
+        if BackupOrchestrator::is_complete_backup(&final_path).await {
+            info!("Replica already holds {}: {}", backup_name, replica_target.display());
+            return Ok(final_path);
+        }
+
+        tokio::fs::create_dir_all(replica_target).await
+            .with_context(|| format!("Failed to create replica target: {}", replica_target.display()))?;
+
+        // Leftovers from an earlier failed attempt
+        for stale in [&staging_path, &final_path] {
+            if stale.exists() {
+                tokio::fs::remove_dir_all(stale).await
+                    .with_context(|| format!("Failed to remove incomplete replica: {}", stale.display()))?;
+            }
+        }
+
+        tokio::fs::create_dir_all(&staging_path).await
+            .context("Failed to create replica staging directory")?;
+
+        let progress = tokio::select! {
+            result = self.copy_engine.copy_directory(backup_path, &staging_path, |_| {}) => result,
+            _ = cancellation.cancelled() => {
+                warn!("Replication to {} cancelled", replica_target.display());
+                let _ = tokio::fs::remove_dir_all(&staging_path).await;
+                bail!("Replication cancelled");
+            }
+        }?;
+
+        // A replica missing files is not a usable copy
+        if progress.files_skipped > 0 {
+            bail!("{} files could not be copied to {}", progress.files_skipped, replica_target.display());
+        }
+
+        tokio::fs::rename(&staging_path, &final_path).await
+            .context("Failed to finalize replica")?;
+
+        if self.durability == Durability::Strict {
+            sync_directory(replica_target).await?;
+        }
+
+        info!("Replicated {} to {} ({} files, {})",
+            backup_name, replica_target.display(), progress.files_copied, format_bytes(progress.bytes_copied));
+
+        Ok(final_path)
+    }
+
+    /// Upload a backup through a storage backend.
+    ///
+    /// Backends that can rename get the same `_PARTIAL` staging as local replicas;
+    /// otherwise the completion marker is uploaded last, so retention ignores
+    /// an interrupted upload either way.
+    async fn upload(
+        &self,
+        backend: &dyn StorageBackend,
+        backup_path: &Path,
+        backup_name: &str,
+        replica_target: &Path,
+        cancellation: CancellationToken,
+    ) -> Result<()> {
+        let marker = format!("{}/{}", backup_name, COMPLETE_MARKER);
+        if backend.exists(&marker).await? {
+            info!("Replica already holds {}: {}", backup_name, replica_target.display());
+            return Ok(());
+        }
+
+        let staging_name = format!("{}_PARTIAL", backup_name);
+        backend.remove(&staging_name).await?;
+        backend.remove(backup_name).await?;
+
+        let upload_name = if backend.capabilities().rename { &staging_name } else { backup_name };
+        let staged = upload_name != backup_name;
+
+        let mut sent = 0;
+        let mut received = 0;
+        let transfer = async {
+            // Backends that sync whole trees get the directory in one go
+            let report = |progress: &CopyProgress| {
+                debug!("Uploading {}: {} files, {}", backup_name, progress.files_copied, format_bytes(progress.bytes_copied));
+            };
+            let options = DirectoryUpload {
+                exclude: if staged { &[] } else { &[COMPLETE_MARKER] },
+                rate_limit: self.limiter.current_rate(),
+                progress: &report,
+            };
+
+            if let Some(upload) = backend.put_directory(backup_path, upload_name, options) {
+                let mut progress = upload.await?;
+                sent += progress.bytes_copied;
+                if !staged {
+                    sent += backend.put_file(&backup_path.join(COMPLETE_MARKER), &marker).await?;
+                    progress.files_copied += 1;
+                }
+                return anyhow::Ok((progress.files_copied, sent));
+            }
+
+            let mut files = list_files(backup_path).await?;
+            files.sort_by_key(|(_, relative)| relative == COMPLETE_MARKER);
+
+            for (local, relative) in &files {
+                // Spot-check before the marker makes the copy count as complete
+                if relative == COMPLETE_MARKER {
+                    received += self.spot_check(backend, upload_name, &files).await?;
+                }
+
+                let size = tokio::fs::metadata(local).await?.len();
+                self.limiter.consume(size).await;
+                sent += backend.put_file(local, &format!("{}/{}", upload_name, relative)).await
+                    .with_context(|| format!("Failed to upload {}", relative))?;
+            }
+            anyhow::Ok((files.len() as u64, sent))
+        };
+
+        let result = tokio::select! {
+            result = transfer => Some(result),
+            _ = cancellation.cancelled() => None,
+        };
+
+        // Failed attempts used the link as well
+        if let Some((usage, job_id)) = &self.usage
+            && let Err(e) = usage.record(job_id, backend.name(), sent, received).await
+        {
+            warn!("Failed to record transfer usage for job {}: {}", job_id, e);
+        }
+
+        let Some(result) = result else {
+            warn!("Replication to {} cancelled", replica_target.display());
+            let _ = backend.remove(upload_name).await;
+            bail!("Replication cancelled");
+        };
+        let (files, bytes) = result?;
+
+        if staged {
+            backend.rename(upload_name, backup_name).await
+                .context("Failed to finalize replica")?;
+        }
+
+        info!("Replicated {} to {} via {} ({} files, {})",
+            backup_name, replica_target.display(), backend.name(), files, format_bytes(bytes));
+
+        Ok(())
+    }
+
+    /// Download a spread-out sample of uploaded files and compare them with the originals
+    ///
+    /// Returns the bytes downloaded.
+    async fn spot_check(&self, backend: &dyn StorageBackend, upload_name: &str, files: &[(PathBuf, String)]) -> Result<u64> {
+        if self.verify_sample == 0 || backend.capabilities().checksums {
+            return Ok(0);
+        }
+
+        let candidates: Vec<_> = files.iter()
+            .filter(|(_, relative)| relative != COMPLETE_MARKER)
+            .collect();
+        let count = self.verify_sample.min(candidates.len());
+        // Fresh for each check, so concurrent replicas never share a download
+        let staging = PrivateDir::create("keephive_verify")?;
+        let download = staging.path().join("download");
+        let mut received = 0;
+
+        for i in 0..count {
+            let (local, relative) = candidates[i * candidates.len() / count];
+            let remote = format!("{}/{}", upload_name, relative);
+
+            let Some(get) = backend.get_file(&remote, &download) else {
+                debug!("{} cannot download, uploads are not spot-checked", backend.name());
+                return Ok(received);
+            };
+
+            let result = async {
+                received += get.await?;
+                anyhow::Ok(sha256_file(&download).await? == sha256_file(local).await?)
+            }.await;
+            let _ = tokio::fs::remove_file(&download).await;
+
+            if !result.with_context(|| format!("Failed to verify {}", relative))? {
+                bail!("{} was corrupted in transit to {} (checksum mismatch)", relative, backend.name());
+            }
+        }
+
+        debug!("Spot-checked {} uploaded files of {}", count, upload_name);
+        Ok(received)
+    }
+
+    /// Remove the oldest complete backups of the job backing up `source` on a replica beyond
+    /// `retention_count`; like on the job's own target, the newest backup is always kept and
+    /// backups of other jobs sharing the replica are left alone
+    pub async fn cleanup(&self, replica_target: &Path, source: &Path, retention_count: usize) -> Result<usize> {
+        let retention_count = retention_count.max(1);
+        if TargetUrl::from_path(replica_target).is_none() {
+            return BackupOrchestrator::cleanup_old_backups(replica_target, source, retention_count, None).await;
+        }
+
+        let backend = self.backends.open(replica_target)?;
+        if backend.manages_retention() {
+            debug!("Retention of {} is left to the storage", replica_target.display());
+            return Ok(0);
+        }
+
+        let mut backups = Vec::new();
+
+        for name in backend.list("").await? {
+            if name.ends_with("_PARTIAL") || !BackupOrchestrator::is_backup_of(Path::new(&name), source) {
+                continue;
+            }
+            if backend.exists(&format!("{}/{}", name, COMPLETE_MARKER)).await? {
+                backups.push(name);
+            }
+        }
+
+        // Remote listings carry no reliable times; backup names end in their creation time
+        backups.sort_by(|a, b| backup_timestamp(a).cmp(backup_timestamp(b)));
+
+        let excess = backups.len().saturating_sub(retention_count);
+        for name in &backups[..excess] {
+            info!("Removing old backup: {}/{}", replica_target.display(), name);
+            backend.remove(name).await
+                .context("Failed to remove old backup")?;
+        }
+
+        Ok(excess)
+    }
+}
+
+/// Replica target of a job, with `{job}` replaced by the job ID so jobs can share a target
+pub fn replica_location(replica: &Path, job_id: &str) -> PathBuf {
+    PathBuf::from(replica.to_string_lossy().replace("{job}", job_id))
+}
+
+/// `YYYY-MM-DD_HHMMSS_mmm` suffix of a backup directory name
+pub(crate) fn backup_timestamp(name: &str) -> &str {
+    const LEN: usize = "2025-01-01_000000_000".len();
+    name.get(name.len().saturating_sub(LEN)..).unwrap_or(name)
+}
+
+/// Every file below `root` with its `/`-separated path relative to `root`
+pub(crate) async fn list_files(root: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), String::new())];
+
+    while let Some((dir, prefix)) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await
+            .with_context(|| format!("Failed to read {}", dir.display()))?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let relative = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+            if entry.file_type().await?.is_dir() {
+                pending.push((entry.path(), format!("{}/", relative)));
+            } else {
+                files.push((entry.path(), relative));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalBackend;
+    use tempfile::tempdir;
+
+    async fn create_backup(root: &Path) -> PathBuf {
+        let backup = root.join("docs_2025-01-01_000000_000");
+        tokio::fs::create_dir_all(backup.join("sub")).await.unwrap();
+        tokio::fs::write(backup.join("sub/a.txt"), b"abc").await.unwrap();
+        tokio::fs::write(backup.join(COMPLETE_MARKER), b"{}").await.unwrap();
+        backup
+    }
+
+    #[tokio::test]
+    async fn test_replicate_copies_backup() {
+        let primary = tempdir().unwrap();
+        let replica = tempdir().unwrap();
+        let backup = create_backup(primary.path()).await;
+
+        let replicator = Replicator::new(Durability::Normal, Arc::new(BandwidthLimiter::default()));
+        let path = replicator.replicate(&backup, replica.path(), CancellationToken::new()).await.unwrap();
+
+        assert_eq!(path, replica.path().join("docs_2025-01-01_000000_000"));
+        assert!(BackupOrchestrator::is_complete_backup(&path).await);
+        assert_eq!(tokio::fs::read(path.join("sub/a.txt")).await.unwrap(), b"abc");
+        assert!(!replica.path().join("docs_2025-01-01_000000_000_PARTIAL").exists());
+    }
+
+    #[tokio::test]
+    async fn test_replicate_replaces_incomplete_copy() {
+        let primary = tempdir().unwrap();
+        let replica = tempdir().unwrap();
+        let backup = create_backup(primary.path()).await;
+
+        // Interrupted earlier attempt: directory without marker plus a staging dir
+        let stale = replica.path().join("docs_2025-01-01_000000_000");
+        tokio::fs::create_dir_all(&stale).await.unwrap();
+        tokio::fs::write(stale.join("junk"), b"x").await.unwrap();
+        tokio::fs::create_dir_all(replica.path().join("docs_2025-01-01_000000_000_PARTIAL")).await.unwrap();
+
+        let replicator = Replicator::new(Durability::Normal, Arc::new(BandwidthLimiter::default()));
+        let path = replicator.replicate(&backup, replica.path(), CancellationToken::new()).await.unwrap();
+
+        assert!(!path.join("junk").exists());
+        assert!(path.join("sub/a.txt").exists());
+    }
+
+    /// Local directory that pretends it cannot rename, like most object stores
+    struct NoRename(LocalBackend);
+
+    impl StorageBackend for NoRename {
+        fn name(&self) -> &str {
+            "norename"
+        }
+
+        fn capabilities(&self) -> crate::storage::Capabilities {
+            crate::storage::Capabilities::default()
+        }
+
+        fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> crate::storage::BackendFuture<'a, u64> {
+            self.0.put_file(local, remote_path)
+        }
+
+        fn list<'a>(&'a self, remote_dir: &'a str) -> crate::storage::BackendFuture<'a, Vec<String>> {
+            self.0.list(remote_dir)
+        }
+
+        fn exists<'a>(&'a self, remote_path: &'a str) -> crate::storage::BackendFuture<'a, bool> {
+            self.0.exists(remote_path)
+        }
+
+        fn remove<'a>(&'a self, remote_path: &'a str) -> crate::storage::BackendFuture<'a, ()> {
+            self.0.remove(remote_path)
+        }
+
+        fn rename<'a>(&'a self, _from: &'a str, _to: &'a str) -> crate::storage::BackendFuture<'a, ()> {
+            Box::pin(async { bail!("rename is not supported") })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replicate_through_backend() {
+        let primary = tempdir().unwrap();
+        let replica = tempdir().unwrap();
+        let backup = create_backup(primary.path()).await;
+
+        // An older copy with the same job name, already complete on the replica
+        let old = replica.path().join("docs_2024-12-31_000000_000");
+        tokio::fs::create_dir_all(&old).await.unwrap();
+        tokio::fs::write(old.join(COMPLETE_MARKER), b"{}").await.unwrap();
+
+        let backends = Arc::new(BackendRegistry::new());
+        let root = replica.path().to_path_buf();
+        backends.register("norename", move |_: &TargetUrl| {
+            Ok(Arc::new(NoRename(LocalBackend::new(root.clone()))) as Arc<dyn StorageBackend>)
+        });
+
+        let replicator = Replicator::new(Durability::Normal, Arc::new(BandwidthLimiter::default()))
+            .with_backends(backends);
+        let target = Path::new("norename://replica");
+        replicator.replicate(&backup, target, CancellationToken::new()).await.unwrap();
+
+        let copy = replica.path().join("docs_2025-01-01_000000_000");
+        assert!(BackupOrchestrator::is_complete_backup(&copy).await);
+        assert_eq!(tokio::fs::read(copy.join("sub/a.txt")).await.unwrap(), b"abc");
+
+        assert_eq!(replicator.cleanup(target, Path::new("/home/me/docs"), 1).await.unwrap(), 1);
+        assert!(!old.exists());
+        assert!(copy.exists());
+    }
+
+    /// Backend whose uploads of `a.txt` arrive damaged, as after a flaky proxy
+    struct Corrupting(LocalBackend, PathBuf);
+
+    impl StorageBackend for Corrupting {
+        fn name(&self) -> &str {
+            "corrupting"
+        }
+
+        fn capabilities(&self) -> crate::storage::Capabilities {
+            crate::storage::Capabilities::default()
+        }
+
+        fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> crate::storage::BackendFuture<'a, u64> {
+            Box::pin(async move {
+                let written = self.0.put_file(local, remote_path).await?;
+                if remote_path.ends_with("a.txt") {
+                    tokio::fs::write(self.1.join(remote_path), b"abd").await?;
+                }
+                Ok(written)
+            })
+        }
+
+        fn get_file<'a>(&'a self, remote_path: &'a str, local: &'a Path) -> Option<crate::storage::BackendFuture<'a, u64>> {
+            self.0.get_file(remote_path, local)
+        }
+
+        fn list<'a>(&'a self, remote_dir: &'a str) -> crate::storage::BackendFuture<'a, Vec<String>> {
+            self.0.list(remote_dir)
+        }
+
+        fn exists<'a>(&'a self, remote_path: &'a str) -> crate::storage::BackendFuture<'a, bool> {
+            self.0.exists(remote_path)
+        }
+
+        fn remove<'a>(&'a self, remote_path: &'a str) -> crate::storage::BackendFuture<'a, ()> {
+            self.0.remove(remote_path)
+        }
+
+        fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> crate::storage::BackendFuture<'a, ()> {
+            self.0.rename(from, to)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spot_check_rejects_corrupted_upload() {
+        let primary = tempdir().unwrap();
+        let replica = tempdir().unwrap();
+        let backup = create_backup(primary.path()).await;
+
+        let root = replica.path().to_path_buf();
+        let backends = Arc::new(BackendRegistry::new());
+        backends.register("corrupting", move |_: &TargetUrl| {
+            Ok(Arc::new(Corrupting(LocalBackend::new(root.clone()), root.clone())) as Arc<dyn StorageBackend>)
+        });
+
+        let replicator = Replicator::new(Durability::Normal, Arc::new(BandwidthLimiter::default()))
+            .with_backends(backends)
+            .with_verify_sample(3);
+        let target = Path::new("corrupting://replica");
+        let error = replicator.replicate(&backup, target, CancellationToken::new()).await.unwrap_err();
+
+        assert!(format!("{:#}", error).contains("checksum mismatch"));
+        assert!(!BackupOrchestrator::is_complete_backup(&replica.path().join("docs_2025-01-01_000000_000")).await);
+    }
+}
@@ -0,0 +1,656 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::core::checksum::Crc32;
+use crate::core::manifest::{contained_path, BackupManifest, ManifestEntry};
+
+/// Buffer size for restoring files (1MB)
+const RESTORE_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Suffix of the temporary file a restored file is written to before it is moved into place
+const RESTORE_TEMP_SUFFIX: &str = ".keephive_restore";
+
+/// What to do when a restored file already exists at the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the existing file
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Rename the existing file to `<name>.bak`, then restore
+    Rename,
+    /// Keep whichever of the two was modified last
+    Newer,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "rename" => Ok(Self::Rename),
+            "newer" => Ok(Self::Newer),
+            _ => bail!("Unknown conflict policy '{}' (expected skip, overwrite, rename or newer)", s),
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Skip => "skip",
+            Self::Overwrite => "overwrite",
+            Self::Rename => "rename",
+            Self::Newer => "newer",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Options controlling a restore
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RestoreOptions {
+    /// Keep going past corrupted or unreadable files and report them at the end
+    pub ignore_errors: bool,
+
+    /// How to handle files that already exist; `None` treats them as errors
+    pub conflict: Option<ConflictPolicy>,
+
+    /// Re-apply the attributes stored on the backup copy (read-only flag, mode bits) instead of
+    /// the destination defaults; security descriptors are not part of a backup
+    pub restore_attributes: bool,
+}
+
+/// Outcome of a restore
+#[derive(Debug, Clone, Default)]
+pub struct RestoreReport {
+    pub files_restored: u64,
+    pub bytes_restored: u64,
+
+    /// Files restored without a checksum to compare against (older backups)
+    pub files_unverified: u64,
+
+    /// Existing files left untouched by the conflict policy
+    pub files_skipped: u64,
+
+    /// Existing files replaced by the backup copy
+    pub files_overwritten: u64,
+
+    /// Existing files moved aside to `.bak` before restoring
+    pub files_renamed: u64,
+
+    /// Backup files whose content does not match the manifest
+    pub corrupted: Vec<String>,
+
+    /// Files listed in the manifest that could not be restored
+    pub failed: Vec<(String, String)>,
+
+    /// Source files the backup could not copy, with the reason; not restored
+    pub not_captured: Vec<(String, String)>,
+}
+
+impl RestoreReport {
+    pub fn is_clean(&self) -> bool {
+        self.corrupted.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// Files and bytes of the manifest handled so far, reported while a restore runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestoreProgress {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+/// Copies a backup back out, checking every file against the backup manifest
+#[derive(Default)]
+pub struct RestoreOrchestrator;
+
+impl RestoreOrchestrator {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Restore a backup into `destination`, resolving existing files with the conflict policy
+    pub async fn restore(
+        &self,
+        backup_path: &Path,
+        destination: &Path,
+        options: RestoreOptions,
+        cancellation: CancellationToken,
+    ) -> Result<RestoreReport> {
+        self.restore_with_progress(backup_path, destination, options, cancellation, |_| {}).await
+    }
+
+    /// Restore like [`restore`](Self::restore), calling `progress_callback` before each file and once at the end
+    pub async fn restore_with_progress<F>(
+        &self,
+        backup_path: &Path,
+        destination: &Path,
+        options: RestoreOptions,
+        cancellation: CancellationToken,
+        mut progress_callback: F,
+    ) -> Result<RestoreReport>
+    where
+        F: FnMut(&RestoreProgress) + Send,
+    {
+        let manifest = BackupManifest::load_or_scan(backup_path).await
+            .context("Failed to read backup manifest")?;
+
+        info!(
+            "Restoring {} ({} files) to {}",
+            manifest.backup_name,
+            manifest.files.len(),
+            destination.display()
+        );
+
+        if let Some(policy) = options.conflict {
+            info!("Existing files: {}", policy);
+        }
+
+        tokio::fs::create_dir_all(destination).await
+            .with_context(|| format!("Failed to create restore destination: {}", destination.display()))?;
+
+        let mut report = RestoreReport {
+            not_captured: manifest.skipped.iter()
+                .map(|skipped| (skipped.source_path().to_string(), skipped.reason.to_string()))
+                .collect(),
+            ..RestoreReport::default()
+        };
+        for (path, reason) in &report.not_captured {
+            warn!("Not in this backup: {} ({})", path, reason);
+        }
+
+        let mut progress = RestoreProgress {
+            files_total: manifest.files.len() as u64,
+            bytes_total: manifest.total_bytes(),
+            ..RestoreProgress::default()
+        };
+
+        for (index, entry) in manifest.files.iter().enumerate() {
+            if cancellation.is_cancelled() {
+                bail!("Restore cancelled after {} files", report.files_restored);
+            }
+
+            progress.files_done = index as u64;
+            progress_callback(&progress);
+            progress.bytes_done += entry.size;
+
+            // A manifest naming `..` or an absolute path would read or write outside the folders
+            let (Some(source), Some(target)) = (
+                contained_path(&entry.path).map(|path| backup_path.join(path)),
+                contained_path(entry.source_path()).map(|path| destination.join(path)),
+            ) else {
+                let error = anyhow::anyhow!("The manifest names a path outside the backup or destination folder");
+                if !options.ignore_errors {
+                    return Err(error.context(format!("Failed to restore {}", entry.path)));
+                }
+
+                warn!("Failed to restore {}: {:#}", entry.path, error);
+                report.failed.push((entry.path.clone(), format!("{:#}", error)));
+                continue;
+            };
+
+            let placed = match restore_entry(&source, &target, entry, &options).await {
+                Ok(placed) => placed,
+                Err(e) => {
+                    if !options.ignore_errors {
+                        return Err(e.context(format!("Failed to restore {}", entry.path)));
+                    }
+
+                    warn!("Failed to restore {}: {:#}", entry.path, e);
+                    report.failed.push((entry.path.clone(), format!("{:#}", e)));
+                    continue;
+                }
+            };
+
+            let check = match placed {
+                Placement::Kept { reason } => {
+                    info!("Kept existing {} ({})", entry.path, reason);
+                    report.files_skipped += 1;
+                    continue;
+                }
+                Placement::Discarded { reason } => {
+                    warn!("Corrupted file in backup, kept existing {} ({})", entry.path, reason);
+                    report.corrupted.push(entry.path.clone());
+
+                    if !options.ignore_errors {
+                        return Err(corruption_error(&entry.path, &reason));
+                    }
+                    continue;
+                }
+                Placement::New(check) => check,
+                Placement::Overwritten(check) => {
+                    info!("Overwrote existing {}", entry.path);
+                    report.files_overwritten += 1;
+                    check
+                }
+                Placement::Renamed { check, backup } => {
+                    info!("Renamed existing {} to {}", entry.path, backup.display());
+                    report.files_renamed += 1;
+                    check
+                }
+            };
+
+            match check {
+                FileCheck::Verified => {}
+                FileCheck::Unverified => report.files_unverified += 1,
+                FileCheck::Corrupted { reason } => {
+                    warn!("Corrupted file in backup: {} ({})", entry.path, reason);
+                    report.corrupted.push(entry.path.clone());
+
+                    if !options.ignore_errors {
+                        return Err(corruption_error(&entry.path, &reason));
+                    }
+                }
+            }
+
+            report.files_restored += 1;
+            report.bytes_restored += entry.size;
+        }
+
+        progress.files_done = progress.files_total;
+        progress_callback(&progress);
+
+        info!(
+            "Restore finished: {} files, {} kept, {} overwritten, {} renamed, {} corrupted, {} failed",
+            report.files_restored,
+            report.files_skipped,
+            report.files_overwritten,
+            report.files_renamed,
+            report.corrupted.len(),
+            report.failed.len()
+        );
+
+        Ok(report)
+    }
+}
+
+fn corruption_error(path: &str, reason: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Restored file does not match the backup manifest: {} ({}). \
+         Use --ignore-errors to restore what is readable",
+        path,
+        reason
+    )
+}
+
+enum FileCheck {
+    Verified,
+    Unverified,
+    Corrupted { reason: String },
+}
+
+enum Placement {
+    /// Nothing existed at the destination
+    New(FileCheck),
+    /// An existing file was replaced
+    Overwritten(FileCheck),
+    /// An existing file was moved aside first
+    Renamed { check: FileCheck, backup: PathBuf },
+    /// An existing file was kept and nothing was restored
+    Kept { reason: &'static str },
+    /// An existing file was kept because the backup copy is corrupted
+    Discarded { reason: String },
+}
+
+/// Restore one file, applying the conflict policy if something already exists at `target`.
+/// The file is written next to the target first, so an existing file is never replaced
+/// by a corrupted copy.
+async fn restore_entry(
+    source: &Path,
+    target: &Path,
+    entry: &ManifestEntry,
+    options: &RestoreOptions,
+) -> Result<Placement> {
+    let conflict = options.conflict;
+    let existing = tokio::fs::symlink_metadata(target).await.ok();
+
+    if let Some(existing) = &existing {
+        let Some(policy) = conflict else {
+            bail!("Destination already exists: {}", target.display());
+        };
+
+        match policy {
+            ConflictPolicy::Skip => return Ok(Placement::Kept { reason: "skip" }),
+            ConflictPolicy::Newer => {
+                let existing_modified = existing.modified().ok().map(DateTime::<Utc>::from);
+                let backup_is_newer = matches!(
+                    (existing_modified, entry.modified),
+                    (Some(current), Some(backed_up)) if backed_up > current
+                );
+
+                if !backup_is_newer {
+                    return Ok(Placement::Kept { reason: "existing file is newer" });
+                }
+            }
+            ConflictPolicy::Overwrite | ConflictPolicy::Rename => {}
+        }
+    }
+
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await
+            .context("Failed to create destination directory")?;
+    }
+
+    let temp = temp_path(target);
+    let check = match copy_checked(source, &temp, entry).await {
+        Ok(check) => check,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e);
+        }
+    };
+
+    if options.restore_attributes
+        && !matches!(check, FileCheck::Corrupted { .. })
+        && let Err(e) = copy_permissions(source, &temp).await
+    {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e);
+    }
+
+    if existing.is_none() {
+        tokio::fs::rename(&temp, target).await
+            .context("Failed to move restored file into place")?;
+        return Ok(Placement::New(check));
+    }
+
+    if let FileCheck::Corrupted { reason } = check {
+        tokio::fs::remove_file(&temp).await
+            .context("Failed to remove corrupted restore copy")?;
+        return Ok(Placement::Discarded { reason });
+    }
+
+    if conflict == Some(ConflictPolicy::Rename) {
+        let backup = bak_path(target).await;
+        tokio::fs::rename(target, &backup).await
+            .with_context(|| format!("Failed to rename existing file to {}", backup.display()))?;
+        tokio::fs::rename(&temp, target).await
+            .context("Failed to move restored file into place")?;
+        return Ok(Placement::Renamed { check, backup });
+    }
+
+    tokio::fs::rename(&temp, target).await
+        .context("Failed to replace existing file")?;
+    Ok(Placement::Overwritten(check))
+}
+
+/// Copy one file out of the backup, checking size and checksum while copying
+async fn copy_checked(source: &Path, target: &Path, entry: &ManifestEntry) -> Result<FileCheck> {
+    let mut src_file = tokio::fs::File::open(source).await
+        .context("Failed to open backup file")?;
+    let mut dst_file = tokio::fs::File::create(target).await
+        .context("Failed to create restored file")?;
+
+    let mut buffer = vec![0u8; RESTORE_BUFFER_SIZE];
+    let mut crc = Crc32::new();
+    let mut size = 0u64;
+
+    loop {
+        let read = src_file.read(&mut buffer).await
+            .context("Failed to read backup file")?;
+
+        if read == 0 {
+            break;
+        }
+
+        crc.update(&buffer[..read]);
+        dst_file.write_all(&buffer[..read]).await
+            .context("Failed to write restored file")?;
+        size += read as u64;
+    }
+
+    dst_file.sync_all().await
+        .context("Failed to sync restored file")?;
+
+    if let Some(modified) = entry.modified {
+        let std_file = dst_file.into_std().await;
+        if let Err(e) = std_file.set_modified(modified.into()) {
+            warn!("Failed to restore modification time of {}: {}", entry.path, e);
+        }
+    }
+
+    if size != entry.size {
+        return Ok(FileCheck::Corrupted {
+            reason: format!("size {} bytes, manifest says {}", size, entry.size),
+        });
+    }
+
+    Ok(match entry.crc32 {
+        Some(expected) if crc.finish() != expected => FileCheck::Corrupted {
+            reason: format!("checksum {:08x}, manifest says {:08x}", crc.finish(), expected),
+        },
+        Some(_) => FileCheck::Verified,
+        None => FileCheck::Unverified,
+    })
+}
+
+/// Apply the permissions of the backup copy (mode bits, or the read-only attribute on Windows)
+async fn copy_permissions(source: &Path, target: &Path) -> Result<()> {
+    let permissions = tokio::fs::metadata(source).await
+        .context("Failed to read permissions of backup file")?
+        .permissions();
+
+    tokio::fs::set_permissions(target, permissions).await
+        .context("Failed to restore permissions")
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(RESTORE_TEMP_SUFFIX);
+    target.with_file_name(name)
+}
+
+/// First free `<name>.bak`, `<name>.bak.1`, ... next to `target`
+async fn bak_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_os_string();
+    let mut attempt = 0u32;
+
+    loop {
+        let mut candidate = name.clone();
+        candidate.push(".bak");
+        if attempt > 0 {
+            candidate.push(format!(".{}", attempt));
+        }
+
+        let candidate = target.with_file_name(candidate);
+        if !tokio::fs::try_exists(&candidate).await.unwrap_or(true) {
+            return candidate;
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::checksum::crc32_file;
+    use tempfile::tempdir;
+
+    async fn backup_with_checksums(dir: &Path) -> PathBuf {
+        let backup = dir.join("docs_2025-01-01_000000_000");
+        tokio::fs::create_dir_all(backup.join("sub")).await.unwrap();
+        tokio::fs::write(backup.join("a.txt"), b"alpha").await.unwrap();
+        tokio::fs::write(backup.join("sub/b.txt"), b"bravo").await.unwrap();
+
+        let modified = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let mut files = Vec::new();
+        for path in ["a.txt", "sub/b.txt"] {
+            let mut entry = ManifestEntry::new(Path::new(path), 5, Some(modified));
+            entry.crc32 = Some(crc32_file(&backup.join(path)).await.unwrap());
+            files.push(entry);
+        }
+
+        BackupManifest::new("docs".into(), files).write(&backup).await.unwrap();
+        backup
+    }
+
+    async fn restore_with(backup: &Path, destination: &Path, conflict: Option<ConflictPolicy>) -> Result<RestoreReport> {
+        let options = RestoreOptions { conflict, ..RestoreOptions::default() };
+        RestoreOrchestrator::new()
+            .restore(backup, destination, options, CancellationToken::new())
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_restore_verifies_checksums() {
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+        let destination = dir.path().join("restored");
+
+        let report = restore_with(&backup, &destination, None).await.unwrap();
+
+        assert!(report.is_clean());
+        assert_eq!(report.files_restored, 2);
+        assert_eq!(report.files_unverified, 0);
+        assert_eq!(tokio::fs::read(destination.join("sub/b.txt")).await.unwrap(), b"bravo");
+        assert!(!temp_path(&destination.join("a.txt")).exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_reports_progress() {
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+
+        let mut updates = Vec::new();
+        RestoreOrchestrator::new()
+            .restore_with_progress(&backup, &dir.path().join("restored"), RestoreOptions::default(), CancellationToken::new(), |p| updates.push(*p))
+            .await
+            .unwrap();
+
+        let done: Vec<(u64, u64)> = updates.iter().map(|p| (p.files_done, p.bytes_done)).collect();
+        assert_eq!(done, [(0, 0), (1, 5), (2, 10)]);
+        assert!(updates.iter().all(|p| p.files_total == 2 && p.bytes_total == 10));
+    }
+
+    #[tokio::test]
+    async fn test_corruption_stops_restore_unless_ignored() {
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+
+        // Same size, different content: only the checksum can tell
+        tokio::fs::write(backup.join("a.txt"), b"alphA").await.unwrap();
+
+        let strict = restore_with(&backup, &dir.path().join("strict"), None).await;
+        assert!(strict.unwrap_err().to_string().contains("a.txt"));
+
+        let options = RestoreOptions { ignore_errors: true, ..RestoreOptions::default() };
+        let report = RestoreOrchestrator::new()
+            .restore(&backup, &dir.path().join("lenient"), options, CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(report.corrupted, vec!["a.txt".to_string()]);
+        assert_eq!(report.files_restored, 2, "Corrupted files are still restored best-effort");
+    }
+
+    #[tokio::test]
+    async fn test_manifest_paths_cannot_leave_the_folders() {
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+        let outside = dir.path().join("outside.txt");
+
+        let mut manifest = BackupManifest::load(&backup).await.unwrap();
+        manifest.files[0].path = "../outside.txt".to_string();
+        manifest.files[1].original_path = Some(outside.to_string_lossy().into_owned());
+        manifest.write(&backup).await.unwrap();
+        tokio::fs::write(&outside, b"alpha").await.unwrap();
+
+        let destination = dir.path().join("restored");
+        let strict = restore_with(&backup, &destination, None).await;
+        assert!(strict.unwrap_err().to_string().contains("outside.txt"));
+
+        let options = RestoreOptions { ignore_errors: true, ..RestoreOptions::default() };
+        let report = RestoreOrchestrator::new()
+            .restore(&backup, &destination, options, CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(report.failed.len(), 2);
+        assert_eq!(report.files_restored, 0);
+        assert_eq!(tokio::fs::read(&outside).await.unwrap(), b"alpha");
+    }
+
+    #[tokio::test]
+    async fn test_conflict_policies() {
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+
+        for policy in [ConflictPolicy::Skip, ConflictPolicy::Overwrite, ConflictPolicy::Rename, ConflictPolicy::Newer] {
+            let destination = dir.path().join(policy.to_string());
+            tokio::fs::create_dir_all(&destination).await.unwrap();
+            tokio::fs::write(destination.join("a.txt"), b"live").await.unwrap();
+
+            let report = restore_with(&backup, &destination, Some(policy)).await.unwrap();
+            let content = tokio::fs::read(destination.join("a.txt")).await.unwrap();
+
+            match policy {
+                ConflictPolicy::Skip | ConflictPolicy::Newer => {
+                    // The live file was written after the backed up copy
+                    assert_eq!(content, b"live", "{}", policy);
+                    assert_eq!(report.files_skipped, 1);
+                }
+                ConflictPolicy::Overwrite => {
+                    assert_eq!(content, b"alpha");
+                    assert_eq!(report.files_overwritten, 1);
+                }
+                ConflictPolicy::Rename => {
+                    assert_eq!(content, b"alpha");
+                    assert_eq!(tokio::fs::read(destination.join("a.txt.bak")).await.unwrap(), b"live");
+                    assert_eq!(report.files_renamed, 1);
+                }
+            }
+        }
+
+        let refused = restore_with(&backup, &dir.path().join("skip"), None).await;
+        assert!(refused.unwrap_err().to_string().contains("a.txt"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restore_attributes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+        std::fs::set_permissions(backup.join("a.txt"), std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let options = RestoreOptions { restore_attributes: true, ..RestoreOptions::default() };
+        let destination = dir.path().join("restored");
+        RestoreOrchestrator::new()
+            .restore(&backup, &destination, options, CancellationToken::new())
+            .await
+            .unwrap();
+
+        let mode = std::fs::metadata(destination.join("a.txt")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_copy_never_replaces_existing_file() {
+        let dir = tempdir().unwrap();
+        let backup = backup_with_checksums(dir.path()).await;
+        tokio::fs::write(backup.join("a.txt"), b"alphA").await.unwrap();
+
+        let destination = dir.path().join("live");
+        tokio::fs::create_dir_all(&destination).await.unwrap();
+        tokio::fs::write(destination.join("a.txt"), b"live").await.unwrap();
+
+        let result = restore_with(&backup, &destination, Some(ConflictPolicy::Overwrite)).await;
+
+        assert!(result.is_err());
+        assert_eq!(tokio::fs::read(destination.join("a.txt")).await.unwrap(), b"live");
+        assert!(!temp_path(&destination.join("a.txt")).exists());
+    }
+}
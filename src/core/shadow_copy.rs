@@ -0,0 +1,47 @@
+//! Shadow copy of a job's source volume while the job copies it (`shadow_copy`), so
+//! files that are open or locked are read from the snapshot instead of being skipped.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// The source as seen in a shadow copy; the snapshot is released when dropped
+pub struct SourceSnapshot {
+    path: PathBuf,
+    #[cfg(windows)]
+    _snapshot: crate::platform::windows::vss::VolumeSnapshot,
+}
+
+impl SourceSnapshot {
+    /// Where to copy the source from while the snapshot exists
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Snapshot the volume holding `source`
+pub async fn snapshot_source(source: &Path) -> Result<SourceSnapshot> {
+    #[cfg(windows)]
+    {
+        use anyhow::Context;
+        use crate::platform::windows::{volume::volume_root, vss::VolumeSnapshot};
+
+        // Shadow copies are not available in containers
+        crate::platform::require_full_mode("A shadow copy")?;
+
+        let root = volume_root(source)
+            .with_context(|| format!("{} is not on a local drive", source.display()))?;
+
+        // VSS calls are blocking COM calls
+        let snapshot = tokio::task::spawn_blocking(move || VolumeSnapshot::create(&root))
+            .await
+            .context("Shadow copy creation did not finish")??;
+
+        Ok(SourceSnapshot { path: snapshot.path_of(source)?, _snapshot: snapshot })
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = source;
+        anyhow::bail!("Shadow copies are only supported on Windows")
+    }
+}
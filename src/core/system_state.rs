@@ -0,0 +1,86 @@
+//! `system-state` jobs: registry hives and system files are exported into a private
+//! staging folder, which is then backed up like any other source.
+
+use anyhow::Result;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+use crate::config::SystemStateConfig;
+
+/// Folder of the staging area holding the saved registry hives
+pub const HIVES_DIR: &str = "registry";
+
+/// Folder of the staging area holding the copied system files, one subfolder per drive
+pub const FILES_DIR: &str = "files";
+
+/// Where `original` is stored below the files folder: `C:\Windows\System32\Tasks`
+/// becomes `C\Windows\System32\Tasks`
+pub fn staged_path(files_dir: &Path, original: &Path) -> PathBuf {
+    let mut staged = files_dir.to_path_buf();
+
+    for component in original.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                    staged.push((letter as char).to_string());
+                }
+                _ => staged.push(prefix.as_os_str().to_string_lossy().replace(['\\', '?', ':'], "_").trim_matches('_')),
+            },
+            Component::Normal(name) => staged.push(name),
+            Component::RootDir | Component::CurDir | Component::ParentDir => {}
+        }
+    }
+
+    staged
+}
+
+/// Export the configured hives and files into `staging`, a fresh private folder.
+///
+/// Hives are saved with `RegSaveKeyEx`, files are copied from a shadow copy of their
+/// volume so files held open by Windows are read in a consistent state.
+pub async fn export_system_state(config: &SystemStateConfig, staging: &Path) -> Result<()> {
+    #[cfg(windows)]
+    {
+        use anyhow::Context;
+
+        tokio::fs::create_dir_all(staging.join(HIVES_DIR)).await
+            .context("Failed to create system state staging folder")?;
+        tokio::fs::create_dir_all(staging.join(FILES_DIR)).await
+            .context("Failed to create system state staging folder")?;
+
+        // VSS and the registry calls are blocking COM and Win32 calls
+        let config = config.clone();
+        let staging = staging.to_path_buf();
+        tokio::task::spawn_blocking(move || crate::platform::windows::system_state::export(&config, &staging))
+            .await
+            .context("System state export did not finish")?
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (config, staging);
+        anyhow::bail!("System state backups are only supported on Windows")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staged_paths_keep_the_original_layout() {
+        let files = Path::new("staging").join(FILES_DIR);
+
+        assert_eq!(staged_path(&files, Path::new("/etc/hosts")), files.join("etc").join("hosts"));
+        assert_eq!(staged_path(&files, Path::new("/var/../lib")), files.join("var").join("lib"));
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_export_requires_windows() {
+        let staging = tempfile::tempdir().unwrap();
+
+        let result = export_system_state(&SystemStateConfig::default(), staging.path()).await;
+
+        assert!(result.unwrap_err().to_string().contains("only supported on Windows"));
+    }
+}
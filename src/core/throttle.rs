@@ -0,0 +1,238 @@
+use chrono::{Local, NaiveDateTime};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::config::ThrottleWindow;
+
+/// Longest single wait, so a rate change at a window boundary applies within a second
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// Window in effect at `now`; the first listed window wins when several overlap
+pub fn active_window(windows: &[ThrottleWindow], now: NaiveDateTime) -> Option<&ThrottleWindow> {
+    windows.iter().find(|w| w.is_active_at(now))
+}
+
+/// Token bucket shared by every copy, with a rate that follows the throttle calendar.
+///
+/// The calendar is consulted on each chunk, so a long copy speeds up or slows down
+/// as soon as it crosses a window boundary. A job with its own limit copies through a
+/// limiter of its own (`for_job`) that also waits on the shared one.
+pub struct BandwidthLimiter {
+    calendar: RwLock<Vec<ThrottleWindow>>,
+    bucket: Mutex<Bucket>,
+    /// Fixed rate of a job's limiter, in place of the calendar
+    job_rate: Option<u64>,
+    /// Limiter of all jobs that a job's limiter waits on as well
+    shared: Option<Arc<BandwidthLimiter>>,
+}
+
+struct Bucket {
+    /// Bytes that may be sent without waiting (at most one second's worth)
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Default for BandwidthLimiter {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl BandwidthLimiter {
+    pub fn new(calendar: Vec<ThrottleWindow>) -> Self {
+        Self {
+            calendar: RwLock::new(calendar),
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                updated: Instant::now(),
+            }),
+            job_rate: None,
+            shared: None,
+        }
+    }
+
+    /// Limiter for the copies of one job: at most `max_bytes_per_sec`, and never faster
+    /// than the calendar of `shared` allows
+    pub fn for_job(shared: Arc<BandwidthLimiter>, max_bytes_per_sec: u64) -> Self {
+        Self {
+            job_rate: Some(max_bytes_per_sec).filter(|&rate| rate > 0),
+            shared: Some(shared),
+            ..Self::default()
+        }
+    }
+
+    /// Replace the calendar (called when config changes); running copies pick it up on their next chunk
+    pub fn set_calendar(&self, calendar: Vec<ThrottleWindow>) {
+        *self.calendar.write().unwrap() = calendar;
+    }
+
+    /// Whether any limit is configured; without one, copies skip the limiter entirely
+    pub fn is_enabled(&self) -> bool {
+        self.job_rate.is_some()
+            || !self.calendar.read().unwrap().is_empty()
+            || self.shared.as_ref().is_some_and(|shared| shared.is_enabled())
+    }
+
+    /// Current rate limit in bytes per second (None = unlimited)
+    pub fn current_rate(&self) -> Option<u64> {
+        let shared = self.shared.as_ref().and_then(|shared| shared.current_rate());
+        match (self.own_rate(), shared) {
+            (Some(own), Some(shared)) => Some(own.min(shared)),
+            (own, shared) => own.or(shared),
+        }
+    }
+
+    /// Rate of this limiter's own bucket, leaving out the shared limiter
+    fn own_rate(&self) -> Option<u64> {
+        self.job_rate.or_else(|| self.rate_at(Local::now().naive_local()))
+    }
+
+    fn rate_at(&self, now: NaiveDateTime) -> Option<u64> {
+        let calendar = self.calendar.read().unwrap();
+        active_window(&calendar, now)
+            .and_then(|w| w.max_bytes_per_sec)
+            .filter(|&rate| rate > 0)
+    }
+
+    /// Wait until `bytes` may be transferred under the current rate
+    pub async fn consume(&self, bytes: u64) {
+        self.consume_own(bytes).await;
+        if let Some(shared) = &self.shared {
+            shared.consume_own(bytes).await;
+        }
+    }
+
+    async fn consume_own(&self, bytes: u64) {
+        let mut owed = bytes as f64;
+
+        loop {
+            let Some(rate) = self.own_rate() else {
+                return;
+            };
+
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let rate = rate as f64;
+                let now = Instant::now();
+
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+                bucket.updated = now;
+
+                if bucket.tokens >= owed {
+                    bucket.tokens -= owed;
+                    return;
+                }
+
+                owed -= bucket.tokens;
+                bucket.tokens = 0.0;
+
+                Duration::from_secs_f64(owed / rate).min(MAX_WAIT)
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn window(days: Vec<u32>, start_hour: u32, end_hour: u32, rate: Option<u64>) -> ThrottleWindow {
+        ThrottleWindow {
+            days,
+            start_hour,
+            start_minute: 0,
+            end_hour,
+            end_minute: 0,
+            max_bytes_per_sec: rate,
+            max_concurrent_jobs: None,
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2024-01-01 was a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_weekday_business_hours() {
+        let w = window(vec![1, 2, 3, 4, 5], 8, 18, Some(10_000_000));
+
+        assert!(w.is_active_at(at(1, 8, 0)));
+        assert!(w.is_active_at(at(5, 17, 59)));
+        assert!(!w.is_active_at(at(1, 18, 0)), "End is exclusive");
+        assert!(!w.is_active_at(at(1, 7, 59)));
+        assert!(!w.is_active_at(at(6, 12, 0)), "Saturday is not listed");
+    }
+
+    #[test]
+    fn test_overnight_window_belongs_to_start_day() {
+        // Friday 22:00 until Saturday 06:00
+        let w = window(vec![5], 22, 6, None);
+
+        assert!(w.is_active_at(at(5, 23, 0)));
+        assert!(w.is_active_at(at(6, 5, 59)));
+        assert!(!w.is_active_at(at(5, 5, 0)), "Thursday night is not covered");
+        assert!(!w.is_active_at(at(6, 22, 30)));
+    }
+
+    #[test]
+    fn test_first_matching_window_wins() {
+        let calendar = vec![
+            window(vec![], 12, 13, Some(1_000)),
+            window(vec![], 8, 18, Some(10_000)),
+        ];
+        let limiter = BandwidthLimiter::new(calendar);
+
+        assert_eq!(limiter.rate_at(at(2, 12, 30)), Some(1_000));
+        assert_eq!(limiter.rate_at(at(2, 9, 0)), Some(10_000));
+        assert_eq!(limiter.rate_at(at(2, 20, 0)), None);
+    }
+
+    #[tokio::test]
+    async fn test_consume_paces_to_rate() {
+        let limiter = BandwidthLimiter::new(vec![window(vec![], 0, 0, Some(200_000))]);
+
+        let started = std::time::Instant::now();
+        limiter.consume(100_000).await;
+
+        assert!(started.elapsed() >= Duration::from_millis(400), "Took {:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_job_limit_applies_without_a_calendar() {
+        let shared = Arc::new(BandwidthLimiter::default());
+        let limiter = BandwidthLimiter::for_job(shared.clone(), 200_000);
+
+        assert!(limiter.is_enabled());
+        assert!(!shared.is_enabled());
+        assert_eq!(limiter.current_rate(), Some(200_000));
+
+        let started = std::time::Instant::now();
+        limiter.consume(100_000).await;
+
+        assert!(started.elapsed() >= Duration::from_millis(400), "Took {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_job_limit_never_exceeds_the_calendar() {
+        let shared = Arc::new(BandwidthLimiter::new(vec![window(vec![], 0, 0, Some(1_000))]));
+
+        assert_eq!(BandwidthLimiter::for_job(shared.clone(), 5_000).current_rate(), Some(1_000));
+        assert_eq!(BandwidthLimiter::for_job(shared, 500).current_rate(), Some(500));
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_does_not_wait() {
+        let limiter = BandwidthLimiter::default();
+
+        let started = std::time::Instant::now();
+        limiter.consume(u64::MAX).await;
+
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+}
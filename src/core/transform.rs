@@ -0,0 +1,273 @@
+use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::core::archive::deflate::Deflater;
+
+/// Hook into the copy engine to process file contents while they are copied.
+///
+/// Implement this to add compression, scanning or filtering without changing the engine.
+/// Transforms run in registration order: the output of one is the input of the next.
+pub trait CopyTransform: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &str;
+
+    /// Whether a file is copied at all; `relative_path` is relative to the source root
+    fn include(&self, _relative_path: &Path) -> bool {
+        true
+    }
+
+    /// Start processing one file
+    fn begin(&self, relative_path: &Path) -> Result<Box<dyn FileTransform>>;
+}
+
+/// Per-file state of a [`CopyTransform`]
+pub trait FileTransform: Send {
+    /// Process a chunk read from the source, appending what should be written to `output`
+    fn process(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<()>;
+
+    /// Called once the source is exhausted, to write out anything still buffered
+    fn finish(&mut self, _output: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Built-in DEFLATE compression (RFC 1951), the one zip archives store entries with.
+///
+/// Pushed onto a [`TransformChain`], it compresses every copied file after the transforms
+/// registered before it.
+pub struct Deflate {
+    level: u32,
+}
+
+impl Deflate {
+    /// `level` 1-9 trades speed for size
+    pub fn new(level: u32) -> Self {
+        Self { level }
+    }
+}
+
+impl CopyTransform for Deflate {
+    fn name(&self) -> &str {
+        "deflate"
+    }
+
+    fn begin(&self, _relative_path: &Path) -> Result<Box<dyn FileTransform>> {
+        Ok(Box::new(DeflateFile(Some(Deflater::new(self.level)))))
+    }
+}
+
+/// Compressor of one file; taken when the stream is finished
+struct DeflateFile(Option<Deflater>);
+
+impl FileTransform for DeflateFile {
+    fn process(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        let Some(deflater) = self.0.as_mut() else {
+            anyhow::bail!("Deflate stream already finished");
+        };
+        deflater.write(chunk, output);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        if let Some(deflater) = self.0.take() {
+            deflater.finish(output);
+        }
+        Ok(())
+    }
+}
+
+/// Ordered list of transforms applied by a copy engine
+#[derive(Clone, Default)]
+pub struct TransformChain {
+    transforms: Vec<Arc<dyn CopyTransform>>,
+}
+
+impl TransformChain {
+    pub fn push(&mut self, transform: Arc<dyn CopyTransform>) {
+        self.transforms.push(transform);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.transforms.iter().map(|t| t.name()).collect()
+    }
+
+    /// Whether every transform wants the file copied
+    pub fn include(&self, relative_path: &Path) -> bool {
+        self.transforms.iter().all(|t| t.include(relative_path))
+    }
+
+    /// Start every transform for one file
+    pub fn begin(&self, relative_path: &Path) -> Result<FileTransformChain> {
+        let stages = self.transforms.iter()
+            .map(|t| t.begin(relative_path))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(FileTransformChain { stages })
+    }
+}
+
+/// The transforms of a [`TransformChain`] started for one file
+pub struct FileTransformChain {
+    stages: Vec<Box<dyn FileTransform>>,
+}
+
+impl FileTransformChain {
+    /// Run a chunk through every stage, returning the bytes to write
+    pub fn process(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        let mut data = chunk.to_vec();
+
+        for stage in &mut self.stages {
+            let mut output = Vec::with_capacity(data.len());
+            stage.process(&data, &mut output)?;
+            data = output;
+        }
+
+        Ok(data)
+    }
+
+    /// Flush every stage; what an earlier stage flushes still passes through the later ones
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+
+        for stage in &mut self.stages {
+            let mut output = Vec::new();
+            if !data.is_empty() {
+                stage.process(&data, &mut output)?;
+            }
+            stage.finish(&mut output)?;
+            data = output;
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uppercases ASCII and skips `.tmp` files
+    struct Upper;
+
+    impl CopyTransform for Upper {
+        fn name(&self) -> &str {
+            "upper"
+        }
+
+        fn include(&self, relative_path: &Path) -> bool {
+            relative_path.extension().is_none_or(|ext| ext != "tmp")
+        }
+
+        fn begin(&self, _relative_path: &Path) -> Result<Box<dyn FileTransform>> {
+            Ok(Box::new(UpperFile))
+        }
+    }
+
+    struct UpperFile;
+
+    impl FileTransform for UpperFile {
+        fn process(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<()> {
+            output.extend(chunk.iter().map(u8::to_ascii_uppercase));
+            Ok(())
+        }
+    }
+
+    /// Holds everything back until the end, then appends a trailer
+    struct Trailer;
+
+    impl CopyTransform for Trailer {
+        fn name(&self) -> &str {
+            "trailer"
+        }
+
+        fn begin(&self, _relative_path: &Path) -> Result<Box<dyn FileTransform>> {
+            Ok(Box::new(TrailerFile(Vec::new())))
+        }
+    }
+
+    struct TrailerFile(Vec<u8>);
+
+    impl FileTransform for TrailerFile {
+        fn process(&mut self, chunk: &[u8], _output: &mut Vec<u8>) -> Result<()> {
+            self.0.extend_from_slice(chunk);
+            Ok(())
+        }
+
+        fn finish(&mut self, output: &mut Vec<u8>) -> Result<()> {
+            output.append(&mut self.0);
+            output.extend_from_slice(b"!");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_chain_runs_in_order() {
+        let mut chain = TransformChain::default();
+        chain.push(Arc::new(Trailer));
+        chain.push(Arc::new(Upper));
+
+        let mut file = chain.begin(Path::new("a.txt")).unwrap();
+        let mut written = file.process(b"hello ").unwrap();
+        written.extend(file.process(b"world").unwrap());
+        written.extend(file.finish().unwrap());
+
+        assert_eq!(written, b"HELLO WORLD!");
+        assert_eq!(chain.names(), vec!["trailer", "upper"]);
+    }
+
+    #[tokio::test]
+    async fn test_copy_engine_applies_transforms() {
+        use crate::core::CopyEngine;
+        use tempfile::tempdir;
+
+        let source = tempdir().unwrap();
+        let target = tempdir().unwrap();
+        tokio::fs::write(source.path().join("a.txt"), b"hello").await.unwrap();
+        tokio::fs::write(source.path().join("b.tmp"), b"scratch").await.unwrap();
+
+        let mut chain = TransformChain::default();
+        chain.push(Arc::new(Upper));
+        let engine = CopyEngine::new().with_transforms(chain);
+
+        let progress = engine.copy_directory(source.path(), target.path(), |_| {}).await.unwrap();
+
+        assert_eq!(progress.files_copied, 1);
+        assert_eq!(progress.files_skipped, 0, "Excluded files are not failures");
+        assert_eq!(tokio::fs::read(target.path().join("a.txt")).await.unwrap(), b"HELLO");
+        assert!(!target.path().join("b.tmp").exists());
+    }
+
+    #[test]
+    fn test_deflate_chains_after_other_transforms() {
+        use crate::core::archive::deflate::tests::inflate;
+
+        let mut chain = TransformChain::default();
+        chain.push(Arc::new(Upper));
+        chain.push(Arc::new(Deflate::new(6)));
+
+        let mut file = chain.begin(Path::new("a.txt")).unwrap();
+        let text = b"keephive keephive keephive ".repeat(200);
+        let mut written = file.process(&text[..1000]).unwrap();
+        written.extend(file.process(&text[1000..]).unwrap());
+        written.extend(file.finish().unwrap());
+
+        assert!(written.len() < text.len() / 4, "Repeated text compresses: {} bytes", written.len());
+        assert_eq!(inflate(&written), text.to_ascii_uppercase());
+        assert_eq!(chain.names(), vec!["upper", "deflate"]);
+    }
+
+    #[test]
+    fn test_any_transform_can_exclude() {
+        let mut chain = TransformChain::default();
+        chain.push(Arc::new(Trailer));
+        chain.push(Arc::new(Upper));
+
+        assert!(chain.include(Path::new("a.txt")));
+        assert!(!chain.include(Path::new("cache/b.tmp")));
+    }
+}
@@ -1,7 +1,10 @@
 use anyhow::{bail, Context, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::{debug, warn};
 
+use crate::storage::TargetUrl;
+
 #[derive(Debug)]
 pub struct ValidationResult {
     pub is_valid: bool,
@@ -13,23 +16,12 @@ pub async fn validate_backup_job(source: &Path, target: &Path) -> Result<Validat
 
     debug!("Validating backup job: {:?} -> {:?}", source, target);
 
-    // 1. Source exists and is readable
-    if !source.exists() {
-        bail!("Source path does not exist: {}", source.display());
-    }
+    // 1-2. Source exists, is readable and does not hold the target
+    validate_source(source, target).await?;
 
-    if !source.is_dir() {
-        bail!("Source path is not a directory: {}", source.display());
-    }
-
-    if source == target {
-        bail!("Source and target directories cannot be the same");
-    }
-
-    // 2. Test read access on source
-    match tokio::fs::read_dir(source).await {
-        Ok(_) => debug!("Source is readable"),
-        Err(e) => bail!("Cannot read source directory: {}", e),
+    // Backups are written locally first; remote storage only takes copies
+    if let Some(url) = TargetUrl::from_path(target) {
+        bail!("Target cannot be a {}:// URL; add it to the job's replicas instead", url.scheme);
     }
 
     // 3. Target directory checks
@@ -52,12 +44,7 @@ pub async fn validate_backup_job(source: &Path, target: &Path) -> Result<Validat
         Err(e) => bail!("Cannot write to target directory: {}", e),
     }
 
-    // 5. Check for circular paths (target inside source)
-    if target.starts_with(source) {
-        bail!("Target directory cannot be inside source directory");
-    }
-
-    // 6. Check available disk space
+    // 5. Check available disk space
     match check_disk_space(source, target).await {
         Ok(true) => debug!("Sufficient disk space available"),
         Ok(false) => warnings.push("Target disk space may be insufficient".to_string()),
@@ -67,7 +54,7 @@ pub async fn validate_backup_job(source: &Path, target: &Path) -> Result<Validat
         }
     }
 
-    // 7. Path length validation (Windows long path awareness)
+    // 6. Path length validation (Windows long path awareness)
     #[cfg(windows)]
     if source.as_os_str().len() > 200 || target.as_os_str().len() > 200 {
         debug!("Long paths detected - will use Windows extended path prefix");
@@ -80,6 +67,34 @@ pub async fn validate_backup_job(source: &Path, target: &Path) -> Result<Validat
     })
 }
 
+/// Check the source folder of a job: it must be a readable folder that does not hold the
+/// target. A job copying from a snapshot checks its own source before switching to it.
+pub async fn validate_source(source: &Path, target: &Path) -> Result<()> {
+    if !source.exists() {
+        bail!("Source path does not exist: {}", source.display());
+    }
+
+    if !source.is_dir() {
+        bail!("Source path is not a directory: {}", source.display());
+    }
+
+    if source == target {
+        bail!("Source and target directories cannot be the same");
+    }
+
+    match tokio::fs::read_dir(source).await {
+        Ok(_) => debug!("Source is readable"),
+        Err(e) => bail!("Cannot read source directory: {}", e),
+    }
+
+    // Circular paths
+    if target.starts_with(source) {
+        bail!("Target directory cannot be inside source directory");
+    }
+
+    Ok(())
+}
+
 async fn check_disk_space(source: &Path, target: &Path) -> Result<bool> {
     let source_size = calculate_dir_size(source).await?;
 
@@ -100,8 +115,59 @@ async fn check_disk_space(source: &Path, target: &Path) -> Result<bool> {
     }
 }
 
+/// The share (`\\server\share`) a network path is on, also for `\\?\UNC\server\share\...`
+pub fn unc_share(path: &Path) -> Option<PathBuf> {
+    let text = path.to_string_lossy();
+    let rest = text.strip_prefix(r"\\?\UNC\").or_else(|| text.strip_prefix(r"\\"))?;
+
+    // `\\?\C:\...` and `\\.\...` are local device paths
+    if rest.starts_with(['?', '.']) {
+        return None;
+    }
+
+    let mut parts = rest.split(['\\', '/']).filter(|part| !part.is_empty());
+    let server = parts.next()?;
+    let share = parts.next()?;
+    Some(PathBuf::from(format!(r"\\{}\{}", server, share)))
+}
+
+/// Check that a network share answers within `timeout`; an offline server can otherwise
+/// hold a file system call for a minute or more
+pub async fn probe_share(share: &Path, timeout: Duration) -> Result<()> {
+    match tokio::time::timeout(timeout, tokio::fs::metadata(share)).await {
+        Ok(Ok(metadata)) if metadata.is_dir() => Ok(()),
+        Ok(Ok(_)) => bail!("{} is not a folder", share.display()),
+        Ok(Err(e)) => bail!("Cannot reach {}: {}", share.display(), e),
+        Err(_) => bail!("{} did not answer within {}s", share.display(), timeout.as_secs()),
+    }
+}
+
+/// File the target probe writes and removes again
+const PROBE_FILE: &str = ".keephive_probe";
+
+/// Check that a target folder can be listed and written within `timeout`
+pub async fn probe_target(target: &Path, timeout: Duration) -> Result<()> {
+    let probe = async {
+        let mut entries = tokio::fs::read_dir(target).await
+            .with_context(|| format!("Cannot list {}", target.display()))?;
+        entries.next_entry().await
+            .with_context(|| format!("Cannot list {}", target.display()))?;
+
+        let file = target.join(PROBE_FILE);
+        tokio::fs::write(&file, b"keephive").await
+            .with_context(|| format!("Cannot write to {}", target.display()))?;
+        tokio::fs::remove_file(&file).await
+            .with_context(|| format!("Cannot remove {}", file.display()))
+    };
+
+    match tokio::time::timeout(timeout, probe).await {
+        Ok(result) => result,
+        Err(_) => bail!("{} did not answer within {}s", target.display(), timeout.as_secs()),
+    }
+}
+
 /// Calculate total size of directory
-async fn calculate_dir_size(path: &Path) -> Result<u64> {
+pub async fn calculate_dir_size(path: &Path) -> Result<u64> {
     let mut total_size = 0u64;
     let mut stack = vec![path.to_path_buf()];
 
@@ -126,4 +192,50 @@ async fn calculate_dir_size(path: &Path) -> Result<u64> {
     }
 
     Ok(total_size)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unc_share_of_network_paths() {
+        assert_eq!(unc_share(Path::new(r"\\nas\backups\docs")), Some(PathBuf::from(r"\\nas\backups")));
+        assert_eq!(unc_share(Path::new(r"\\?\UNC\nas\backups\docs")), Some(PathBuf::from(r"\\nas\backups")));
+        assert_eq!(unc_share(Path::new(r"\\nas\backups")), Some(PathBuf::from(r"\\nas\backups")));
+
+        assert_eq!(unc_share(Path::new(r"\\nas")), None);
+        assert_eq!(unc_share(Path::new(r"\\?\C:\Backups")), None);
+        assert_eq!(unc_share(Path::new(r"C:\Backups")), None);
+        assert_eq!(unc_share(Path::new("/mnt/nas/backups")), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_share_reports_missing_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(probe_share(dir.path(), Duration::from_secs(5)).await.is_ok());
+
+        let missing = dir.path().join("missing");
+        let error = probe_share(&missing, Duration::from_secs(5)).await.unwrap_err();
+        assert!(error.to_string().starts_with("Cannot reach"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_probe_target_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        probe_target(dir.path(), Duration::from_secs(5)).await.unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let missing = dir.path().join("missing");
+        let error = probe_target(&missing, Duration::from_secs(5)).await.unwrap_err();
+        assert!(error.to_string().starts_with("Cannot list"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_validate_source_refuses_a_target_inside_it() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(validate_source(dir.path(), &dir.path().join("backups")).await.is_err());
+        assert!(validate_source(dir.path(), Path::new("/elsewhere")).await.is_ok());
+        assert!(validate_source(&dir.path().join("missing"), Path::new("/elsewhere")).await.is_err());
+    }
+}
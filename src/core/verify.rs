@@ -0,0 +1,278 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+use crate::config::VerifyConfig;
+use crate::core::checksum::crc32_file;
+use crate::core::manifest::ManifestEntry;
+
+/// Buffer size for comparing files (1MB)
+const VERIFY_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Result of re-reading copied files and comparing them with the source
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifySummary {
+    /// Files compared byte for byte
+    pub files_checked: u64,
+
+    /// Files not compared because the source changed after it was copied
+    pub files_changed_at_source: u64,
+
+    /// Backup paths whose content differs from the source
+    pub mismatched: Vec<String>,
+
+    /// Whether a sample mismatch escalated to full verification
+    pub escalated: bool,
+}
+
+/// Number of files a sample of `percent` covers (at least one when there are files)
+pub fn sample_size(total: usize, percent: u8) -> usize {
+    if total == 0 {
+        return 0;
+    }
+
+    (total * percent.min(100) as usize).div_ceil(100).clamp(1, total)
+}
+
+/// Compare copied files with their source according to the verification mode
+pub async fn verify_backup(
+    source_root: &Path,
+    backup_root: &Path,
+    files: &[ManifestEntry],
+    config: VerifyConfig,
+) -> Result<VerifySummary> {
+    let mut summary = VerifySummary::default();
+
+    match config {
+        VerifyConfig::Off => {}
+        VerifyConfig::Full => {
+            verify_files(source_root, backup_root, files.iter(), &mut summary).await?;
+        }
+        VerifyConfig::Sample { percent } => {
+            let count = sample_size(files.len(), percent);
+            let sample = random_sample(files.len(), count);
+
+            verify_files(source_root, backup_root, sample.iter().map(|&i| &files[i]), &mut summary).await?;
+
+            if !summary.mismatched.is_empty() {
+                warn!(
+                    "{} of {} sampled files differ from the source, verifying all {} files",
+                    summary.mismatched.len(),
+                    count,
+                    files.len()
+                );
+
+                summary = VerifySummary {
+                    escalated: true,
+                    ..VerifySummary::default()
+                };
+                verify_files(source_root, backup_root, files.iter(), &mut summary).await?;
+            }
+        }
+    }
+
+    info!(
+        "Verified {} files ({} changed at source, {} mismatched)",
+        summary.files_checked,
+        summary.files_changed_at_source,
+        summary.mismatched.len()
+    );
+
+    Ok(summary)
+}
+
+async fn verify_files<'a>(
+    source_root: &Path,
+    backup_root: &Path,
+    files: impl Iterator<Item = &'a ManifestEntry>,
+    summary: &mut VerifySummary,
+) -> Result<()> {
+    for entry in files {
+        let source = source_root.join(entry.source_path());
+        let copy = backup_root.join(&entry.path);
+
+        // Hashed while copying: only the copy has to be read again
+        if let Some(expected) = entry.crc32 {
+            summary.files_checked += 1;
+
+            match crc32_file(&copy).await {
+                Ok(actual) if actual == expected => {}
+                Ok(actual) => {
+                    warn!("Backup copy differs from what was read: {} ({:08x}, expected {:08x})", entry.path, actual, expected);
+                    summary.mismatched.push(entry.path.clone());
+                }
+                Err(e) => {
+                    warn!("Failed to verify {}: {}", entry.path, e);
+                    summary.mismatched.push(entry.path.clone());
+                }
+            }
+            continue;
+        }
+
+        // A file modified since it was copied cannot be compared
+        let source_metadata = tokio::fs::metadata(&source).await;
+        let unchanged = source_metadata.as_ref().is_ok_and(|m| {
+            m.len() == entry.size && m.modified().ok().map(DateTime::<Utc>::from) == entry.modified
+        });
+
+        if !unchanged {
+            summary.files_changed_at_source += 1;
+            continue;
+        }
+
+        summary.files_checked += 1;
+
+        match files_equal(&source, &copy).await {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Backup copy differs from source: {}", entry.path);
+                summary.mismatched.push(entry.path.clone());
+            }
+            Err(e) => {
+                warn!("Failed to verify {}: {}", entry.path, e);
+                summary.mismatched.push(entry.path.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn files_equal(a: &Path, b: &Path) -> Result<bool> {
+    let mut file_a = tokio::fs::File::open(a).await?;
+    let mut file_b = tokio::fs::File::open(b).await?;
+
+    if file_a.metadata().await?.len() != file_b.metadata().await?.len() {
+        return Ok(false);
+    }
+
+    let mut buffer_a = vec![0u8; VERIFY_BUFFER_SIZE];
+    let mut buffer_b = vec![0u8; VERIFY_BUFFER_SIZE];
+
+    loop {
+        let read = file_a.read(&mut buffer_a).await?;
+        if read == 0 {
+            return Ok(true);
+        }
+
+        file_b.read_exact(&mut buffer_b[..read]).await?;
+
+        if buffer_a[..read] != buffer_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Pick `count` distinct indices below `total` (partial Fisher-Yates with xorshift)
+fn random_sample(total: usize, count: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..total).collect();
+
+    let mut state = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+        | 1;
+
+    for i in 0..count.min(total) {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        let j = i + (state % (total - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+
+    indices.truncate(count);
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn copied_file(source: &Path, backup: &Path, name: &str, content: &[u8]) -> ManifestEntry {
+        tokio::fs::write(source.join(name), content).await.unwrap();
+        tokio::fs::write(backup.join(name), content).await.unwrap();
+
+        let metadata = tokio::fs::metadata(source.join(name)).await.unwrap();
+        ManifestEntry::new(Path::new(name), metadata.len(), metadata.modified().ok().map(DateTime::<Utc>::from))
+    }
+
+    #[test]
+    fn test_sample_size() {
+        assert_eq!(sample_size(0, 5), 0);
+        assert_eq!(sample_size(10, 5), 1, "At least one file is checked");
+        assert_eq!(sample_size(1000, 5), 50);
+        assert_eq!(sample_size(10, 150), 10);
+    }
+
+    #[test]
+    fn test_random_sample_is_distinct() {
+        let mut sample = random_sample(100, 30);
+        sample.sort();
+        sample.dedup();
+
+        assert_eq!(sample.len(), 30);
+        assert!(sample.iter().all(|&i| i < 100));
+    }
+
+    #[tokio::test]
+    async fn test_sample_mismatch_escalates_to_full() {
+        let source = tempdir().unwrap();
+        let backup = tempdir().unwrap();
+
+        let good = copied_file(source.path(), backup.path(), "good.txt", b"hello").await;
+        let bad = copied_file(source.path(), backup.path(), "bad.txt", b"hello").await;
+        tokio::fs::write(backup.path().join("bad.txt"), b"jello").await.unwrap();
+
+        let summary = verify_backup(
+            source.path(),
+            backup.path(),
+            &[good, bad],
+            VerifyConfig::Sample { percent: 100 },
+        ).await.unwrap();
+
+        assert!(summary.escalated);
+        assert_eq!(summary.mismatched, vec!["bad.txt".to_string()]);
+        assert_eq!(summary.files_checked, 2);
+    }
+
+    #[tokio::test]
+    async fn test_changed_source_is_not_a_mismatch() {
+        let source = tempdir().unwrap();
+        let backup = tempdir().unwrap();
+
+        let entry = copied_file(source.path(), backup.path(), "log.txt", b"one").await;
+        tokio::fs::write(source.path().join("log.txt"), b"one two").await.unwrap();
+
+        let summary = verify_backup(source.path(), backup.path(), &[entry], VerifyConfig::Full).await.unwrap();
+
+        assert_eq!(summary.files_changed_at_source, 1);
+        assert!(summary.mismatched.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hashed_copies_are_checked_without_the_source() {
+        let source = tempdir().unwrap();
+        let backup = tempdir().unwrap();
+
+        let mut crc = crate::core::Crc32::new();
+        crc.update(b"hello");
+        let good = copied_file(source.path(), backup.path(), "good.txt", b"hello").await.with_crc32(Some(crc.finish()));
+        let bad = copied_file(source.path(), backup.path(), "bad.txt", b"hello").await.with_crc32(Some(crc.finish()));
+        tokio::fs::write(backup.path().join("bad.txt"), b"jello").await.unwrap();
+
+        // Changing or removing the source afterwards does not matter
+        tokio::fs::remove_file(source.path().join("good.txt")).await.unwrap();
+
+        let summary = verify_backup(source.path(), backup.path(), &[good, bad], VerifyConfig::Full).await.unwrap();
+
+        assert_eq!(summary.files_checked, 2);
+        assert_eq!(summary.files_changed_at_source, 0);
+        assert_eq!(summary.mismatched, vec!["bad.txt".to_string()]);
+    }
+}
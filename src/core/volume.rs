@@ -0,0 +1,222 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Identity of the volume a target lives on, so it can be found again under another drive letter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeId {
+    /// Volume serial number
+    pub serial: u32,
+
+    /// Volume label (may be empty)
+    pub label: String,
+
+    /// Root the volume was mounted at when it was recorded, e.g. `E:\`
+    pub root: PathBuf,
+}
+
+impl fmt::Display for VolumeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' ({:04X}-{:04X})", self.label, self.serial >> 16, self.serial & 0xFFFF)
+    }
+}
+
+/// Identity of the volume holding `path`, if the platform can tell
+pub fn volume_id(path: &Path) -> Option<VolumeId> {
+    #[cfg(windows)]
+    {
+        crate::platform::windows::volume::volume_id(path).ok()
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Physical disks holding `path` (more than one for spanned or RAID volumes); empty for
+/// network paths and when the platform cannot tell. A path that does not exist yet is
+/// looked up through its nearest existing parent.
+pub fn physical_disks(path: &Path) -> Vec<String> {
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return Vec::new();
+    };
+
+    #[cfg(windows)]
+    {
+        crate::platform::windows::volume::physical_disks(existing)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux_disks(existing)
+    }
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        let _ = existing;
+        Vec::new()
+    }
+}
+
+/// Whole-disk block devices behind the filesystem holding `path`, from sysfs
+#[cfg(target_os = "linux")]
+fn linux_disks(path: &Path) -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return Vec::new();
+    };
+    let dev = metadata.dev();
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0fff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0x00ff);
+
+    // tmpfs, overlay and network filesystems have no block device
+    match std::fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)) {
+        Ok(device) => {
+            let mut disks = Vec::new();
+            collect_linux_disks(&device, &mut disks);
+            disks.sort();
+            disks.dedup();
+            disks
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Follow partitions to their disk and device-mapper/md devices to their members
+#[cfg(target_os = "linux")]
+fn collect_linux_disks(device: &Path, disks: &mut Vec<String>) {
+    let device = match device.parent() {
+        Some(parent) if device.join("partition").exists() => parent,
+        _ => device,
+    };
+
+    let members: Vec<PathBuf> = std::fs::read_dir(device.join("slaves"))
+        .map(|entries| entries.filter_map(|e| e.ok()).filter_map(|e| std::fs::canonicalize(e.path()).ok()).collect())
+        .unwrap_or_default();
+
+    if members.is_empty() {
+        if let Some(name) = device.file_name() {
+            disks.push(name.to_string_lossy().into_owned());
+        }
+        return;
+    }
+
+    for member in members {
+        collect_linux_disks(&member, disks);
+    }
+}
+
+/// Where `target` is now, if its volume moved away from the root it was recorded at.
+///
+/// Returns `None` when the target is where the config says, and an error when the
+/// volume cannot be found on any drive.
+pub fn relocate_target(target: &Path, known: &VolumeId) -> Result<Option<PathBuf>> {
+    #[cfg(windows)]
+    {
+        use crate::platform::windows::volume;
+        relocate_with(target, known, volume::serial_at, volume::find_volume)
+    }
+
+    #[cfg(not(windows))]
+    {
+        relocate_with(target, known, |_| None, |_| None)
+    }
+}
+
+fn relocate_with(
+    target: &Path,
+    known: &VolumeId,
+    serial_at: impl Fn(&Path) -> Option<u32>,
+    find_volume: impl Fn(u32) -> Option<PathBuf>,
+) -> Result<Option<PathBuf>> {
+    // Recorded for a different drive: the target was changed in the config since
+    let Ok(relative) = target.strip_prefix(&known.root) else {
+        return Ok(None);
+    };
+
+    let current = serial_at(&known.root);
+    if current == Some(known.serial) {
+        return Ok(None);
+    }
+
+    if let Some(root) = find_volume(known.serial) {
+        return Ok(Some(root.join(relative)));
+    }
+
+    match current {
+        Some(other) => bail!(
+            "{} now holds a different volume ({:04X}-{:04X}); backup volume {} is not connected \
+             (run --adopt-volume if it was replaced)",
+            known.root.display(),
+            other >> 16,
+            other & 0xFFFF,
+            known
+        ),
+        None => bail!(
+            "Backup volume {} is not connected (last seen at {})",
+            known,
+            known.root.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usb_drive() -> VolumeId {
+        VolumeId {
+            serial: 0x1234_ABCD,
+            label: "BACKUP".to_string(),
+            root: PathBuf::from("/mnt/e"),
+        }
+    }
+
+    #[test]
+    fn test_target_in_place() {
+        let known = usb_drive();
+        let target = Path::new("/mnt/e/backups/docs");
+
+        let result = relocate_with(target, &known, |_| Some(0x1234_ABCD), |_| unreachable!()).unwrap();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_target_found_on_other_root() {
+        let known = usb_drive();
+        let target = Path::new("/mnt/e/backups/docs");
+
+        let result = relocate_with(target, &known, |_| None, |_| Some(PathBuf::from("/mnt/f"))).unwrap();
+
+        assert_eq!(result, Some(PathBuf::from("/mnt/f/backups/docs")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_disks_of_missing_path_come_from_its_parent() {
+        let dir = std::env::temp_dir();
+
+        assert_eq!(physical_disks(&dir.join("not/created/yet")), physical_disks(&dir));
+    }
+
+    #[test]
+    fn test_missing_volume_is_reported() {
+        let known = usb_drive();
+        let target = Path::new("/mnt/e/backups/docs");
+
+        let missing = relocate_with(target, &known, |_| None, |_| None).unwrap_err();
+        assert!(missing.to_string().contains("'BACKUP' (1234-ABCD) is not connected"));
+
+        let replaced = relocate_with(target, &known, |_| Some(0x5555_0000), |_| None).unwrap_err();
+        assert!(replaced.to_string().contains("different volume (5555-0000)"));
+
+        // A target moved to another drive in the config is not checked against the old volume
+        let result = relocate_with(Path::new("/mnt/g/docs"), &known, |_| None, |_| None).unwrap();
+        assert_eq!(result, None);
+    }
+}
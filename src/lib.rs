@@ -5,5 +5,6 @@ pub mod scheduler;
 pub mod service;
 pub mod config;
 pub mod observability;
+pub mod storage;
 
 pub use anyhow::{Context, Result};
\ No newline at end of file
@@ -1,14 +1,25 @@
 use anyhow::{Context, Result};
 use keephive::{
     config::ServiceConfig,
-    observability::{init_logging, Rotation},
+    observability::{format_bytes, format_duration, format_elapsed, format_local_time, init_logging, set_path_redaction, Rotation},
+    platform::FaultPlan,
     service::ServiceDaemon,
 };
 use std::path::PathBuf;
 use tracing::info;
 
-#[cfg(windows)]
-use keephive::platform::windows::service::WindowsService;
+use keephive::platform::SystemService;
+
+/// Commands managing the service or scheduled task, which slim mode (containers) lacks
+const SERVICE_COMMANDS: [&str; 7] = [
+    "--install",
+    "--uninstall",
+    "--start",
+    "--stop",
+    "--upgrade-service",
+    "--install-task",
+    "--uninstall-task",
+];
 
 fn main() -> Result<()> {
     // Parse command line arguments
@@ -16,40 +27,155 @@ fn main() -> Result<()> {
 
     // Check for service-related commands
     if args.len() > 1 {
+        if SERVICE_COMMANDS.contains(&args[1].as_str()) {
+            keephive::platform::require_full_mode(&args[1])?;
+        }
+
         match args[1].as_str() {
             "--install" => {
-                let config_path = if args.len() > 2 {
-                    Some(PathBuf::from(&args[2]))
-                } else {
-                    None
+                let config_path = args.get(2)
+                    .filter(|a| !a.starts_with("--"))
+                    .map(PathBuf::from);
+                let shell_integration = args.iter().any(|a| a == "--shell-integration");
+                return SystemService::install(config_path, shell_integration);
+            }
+            #[cfg(windows)]
+            "--install-task" => {
+                use keephive::platform::windows::task::{ScheduledTask, DEFAULT_TASK_INTERVAL_MINUTES};
+
+                let config_path = args.get(2)
+                    .filter(|a| !a.starts_with("--"))
+                    .map(PathBuf::from);
+                let interval = match option_value(&args, "--every")? {
+                    Some(n) => n.parse::<u32>()
+                        .with_context(|| format!("Invalid --every value: {}", n))?,
+                    None => DEFAULT_TASK_INTERVAL_MINUTES,
                 };
-                return WindowsService::install(config_path);
+                return ScheduledTask::install(config_path, interval);
+            }
+            #[cfg(windows)]
+            "--uninstall-task" => {
+                return keephive::platform::windows::task::ScheduledTask::uninstall();
+            }
+            "--run-pending-and-exit" => {
+                let config_path = args.get(2)
+                    .filter(|a| !a.starts_with("--"))
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from("keephive_config.json"));
+                let chaos = chaos_plan(&args)?;
+                return run_pending_and_exit(config_path, chaos);
             }
             "--uninstall" => {
-                return WindowsService::uninstall();
+                return SystemService::uninstall();
             }
             "--start" => {
-                return WindowsService::start();
+                return SystemService::start();
             }
             "--stop" => {
-                return WindowsService::stop();
+                return SystemService::stop();
+            }
+            "--upgrade-service" => {
+                let new_binary = args.get(2).map(PathBuf::from);
+                return SystemService::upgrade(new_binary);
             }
             #[cfg(windows)]
-            "--service" => unsafe {
-                if args.len() < 3 {
-                    eprintln!("Error: --service requires config path argument");
-                    eprintln!("This should be set automatically during installation");
-                    std::process::exit(1);
-                }
+            "--service" => {
+                // Older installs pass the config path in binPath; newer ones
+                // store it in the registry and service_impl reads it from there
+                if args.len() > 2 {
+                    let config_path = PathBuf::from(&args[2]);
 
-                let config_path = PathBuf::from(&args[2]);
-
-                // Set config path in environment for service_impl to read
-                std::env::set_var("KEEPHIVE_CONFIG", config_path.to_str().unwrap());
+                    // Set config path in environment for service_impl to read
+                    unsafe {
+                        std::env::set_var("KEEPHIVE_CONFIG", config_path.to_str().unwrap());
+                    }
+                }
 
                 use keephive::platform::windows::service_impl;
                 return service_impl::get_service_dispatcher_entry();
             }
+            #[cfg(unix)]
+            "--service" => {
+                let config_path = args.get(2)
+                    .filter(|a| !a.starts_with("--"))
+                    .map(PathBuf::from)
+                    .or_else(SystemService::installed_config_path)
+                    .unwrap_or_else(|| PathBuf::from("keephive_config.json"));
+                return run_unix_service(config_path);
+            }
+            "--backup-now" => {
+                return run_backup_now(&args[2..]);
+            }
+            "--submit" => {
+                return run_submit(&args[2..]);
+            }
+            "--run-now" => {
+                return run_run_now(&args[2..]);
+            }
+            "--tail" => {
+                return run_tail(&args[2..]);
+            }
+            "--confirm" | "--reject" => {
+                return run_confirm(&args[1..]);
+            }
+            "--adopt-volume" => {
+                return run_adopt_volume(&args[2..]);
+            }
+            "--status" => {
+                return run_status(&args[2..]);
+            }
+            "--history" => {
+                return run_history(&args[2..]);
+            }
+            "--usage" => {
+                return run_usage(&args[2..]);
+            }
+            "--simulate" => {
+                return run_simulate(&args[2..]);
+            }
+            "--diff" => {
+                return run_diff(&args[2..]);
+            }
+            "--analyze" => {
+                return run_analyze(&args[2..]);
+            }
+            "--check-paths" => {
+                return run_check_paths(&args[2..]);
+            }
+            "--dry-run" => {
+                return run_dry_run(&args[2..]);
+            }
+            "--collect-diagnostics" => {
+                return run_collect_diagnostics(&args[2..]);
+            }
+            "--check-config" => {
+                return run_check_config(&args[2..]);
+            }
+            "--test-notification" => {
+                return run_test_notification(&args[2..]);
+            }
+            "--restore" => {
+                return run_restore(&args[2..]);
+            }
+            "--migrate-target" => {
+                return run_migrate_target(&args[2..]);
+            }
+            "--gdrive-login" => {
+                return run_gdrive_login(&args[2..]);
+            }
+            "--wizard" => {
+                return run_wizard(&args[2..]);
+            }
+            #[cfg(windows)]
+            "--mount" => {
+                return run_mount(&args[2..]);
+            }
+            #[cfg(windows)]
+            "--unmount" => {
+                let drive = args.get(2)
+                    .context("Usage: keephive --unmount <DRIVE>")?;
+                return keephive::platform::windows::mount::unmount_backup(drive);
+            }
             "--help" | "-h" => {
                 print_help();
                 return Ok(());
@@ -74,78 +200,1398 @@ async fn run_console_mode() -> Result<()> {
     } else {
         PathBuf::from("keephive_config.json")
     };
+    let chaos = chaos_plan(&args)?;
 
     // Load configuration
     let config = load_config(&config_path).await
         .context("Failed to load configuration")?;
 
-    // Initialize logging with console + optional file output
+    init_config_logging(&config)?;
+
+    info!("KeepHive v{} - Console Mode", env!("CARGO_PKG_VERSION"));
+    info!("Configuration loaded from: {}", config_path.display());
+
+    if let Some(log_dir) = &config.log_directory {
+        info!("File logging enabled: {}", log_dir.display());
+    } else {
+        info!("Console logging only (no log file configured)");
+    }
+
+    info!("Press Ctrl+C to stop");
+
+    // Create and run service daemon
+    let mut daemon = ServiceDaemon::new(config).await?;
+    if let Some(plan) = chaos {
+        daemon.inject_faults(plan);
+    }
+    daemon.run(config_path).await?;
+
+    Ok(())
+}
+
+/// Run as a systemd `Type=notify` service or under FreeBSD's daemon(8): --service [CONFIG_FILE]
+#[cfg(unix)]
+#[tokio::main]
+async fn run_unix_service(config_path: PathBuf) -> Result<()> {
+    use keephive::platform::unix::notify_systemd;
+
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    init_config_logging(&config)?;
+
+    info!("KeepHive v{} - Unix service", env!("CARGO_PKG_VERSION"));
+    info!("Configuration loaded from: {}", config_path.display());
+
+    let daemon = ServiceDaemon::new(config).await?;
+    notify_systemd("READY=1");
+
+    let result = daemon.run(config_path).await;
+    notify_systemd("STOPPING=1");
+    result
+}
+
+/// Run due jobs once and exit, for Task Scheduler or cron: --run-pending-and-exit [CONFIG_FILE]
+#[tokio::main]
+async fn run_pending_and_exit(config_path: PathBuf, chaos: Option<FaultPlan>) -> Result<()> {
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    init_config_logging(&config)?;
+
+    info!("KeepHive v{} - Run Pending", env!("CARGO_PKG_VERSION"));
+    info!("Configuration loaded from: {}", config_path.display());
+
+    let mut daemon = ServiceDaemon::new(config).await?;
+    if let Some(plan) = chaos {
+        daemon.inject_faults(plan);
+    }
+    daemon.run_pending().await
+}
+
+/// Copy faults from the hidden `--chaos fail=N,slow=MS,full=N` option, for testing
+fn chaos_plan(args: &[String]) -> Result<Option<FaultPlan>> {
+    option_value(args, "--chaos")?
+        .map(|spec| spec.parse::<FaultPlan>().context("Invalid --chaos faults"))
+        .transpose()
+}
+
+/// Initialize logging with console + optional file output
+fn init_config_logging(config: &ServiceConfig) -> Result<()> {
     let rotation = match config.log_rotation {
         keephive::config::LogRotation::Daily => Rotation::Daily,
         keephive::config::LogRotation::Hourly => Rotation::Hourly,
         keephive::config::LogRotation::Never => Rotation::Never,
     };
 
+    set_path_redaction(config.log_path_redaction);
     init_logging(
         &config.log_level,
         config.log_directory.as_deref(),
         rotation,
-    )?;
+    )
+}
 
-    info!("KeepHive v{} - Console Mode", env!("CARGO_PKG_VERSION"));
-    info!("Configuration loaded from: {}", config_path.display());
+/// Ask the running daemon to back up a folder once: --backup-now <FOLDER> [--config FILE]
+#[tokio::main]
+async fn run_backup_now(args: &[String]) -> Result<()> {
+    use keephive::service::control::send_request;
+    use keephive::service::ControlRequest;
 
-    if let Some(log_dir) = &config.log_directory {
-        info!("File logging enabled: {}", log_dir.display());
-    } else {
-        info!("Console logging only (no log file configured)");
+    let folder = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context("Usage: keephive --backup-now <FOLDER> [--config FILE]")?;
+
+    // The daemon runs elsewhere, so send it an absolute path
+    let folder = dunce::canonicalize(folder)
+        .with_context(|| format!("Folder not found: {}", folder))?;
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    let response = send_request(&endpoint, &ControlRequest::BackupFolder { path: folder }).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
     }
 
-    info!("Press Ctrl+C to stop");
+    println!("{}", response.message);
+    Ok(())
+}
 
-    // Create and run service daemon
-    let daemon = ServiceDaemon::new(config).await?;
-    daemon.run(config_path).await?;
+/// Submit a one-off job to the running daemon:
+/// --submit <SOURCE> <TARGET> [--retention N] [--description TEXT] [--config FILE]
+#[tokio::main]
+async fn run_submit(args: &[String]) -> Result<()> {
+    use keephive::service::control::send_request;
+    use keephive::service::ControlRequest;
+
+    let positional: Vec<&String> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .collect();
+
+    let [source, target] = positional[..] else {
+        anyhow::bail!("Usage: keephive --submit <SOURCE> <TARGET> [--retention N] [--description TEXT] [--config FILE]");
+    };
 
+    let source = dunce::canonicalize(source)
+        .with_context(|| format!("Source not found: {}", source))?;
+    let target = std::path::absolute(target)
+        .with_context(|| format!("Invalid target: {}", target))?;
+
+    let retention_count = option_value(args, "--retention")?
+        .map(|n| n.parse::<usize>().with_context(|| format!("Invalid --retention value: {}", n)))
+        .transpose()?;
+    let description = option_value(args, "--description")?.map(str::to_string);
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    let response = send_request(&endpoint, &ControlRequest::SubmitJob {
+        source,
+        target,
+        retention_count,
+        description,
+    }).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+
+    println!("{}", response.message);
     Ok(())
 }
 
-async fn load_config(path: &PathBuf) -> Result<ServiceConfig> {
-    if !path.exists() {
-        anyhow::bail!(
-            "Configuration file not found: {}\n\nCreate a config file first. Example:\n{}",
-            path.display(),
-            get_example_config()
+/// Show the state of every job: --status [--config FILE]
+#[tokio::main]
+async fn run_status(args: &[String]) -> Result<()> {
+    use chrono::Utc;
+    use keephive::service::control::{default_endpoint, send_request};
+    use keephive::service::ControlRequest;
+    use keephive::state::{BackupState, JobState, JobStatus, StateManager};
+
+    let config_path = match option_value(args, "--config")? {
+        Some(path) => PathBuf::from(path),
+        None => installed_config_path().unwrap_or_else(|| PathBuf::from("keephive_config.json")),
+    };
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    // A running daemon saves its pending updates first; otherwise the file is as it left it
+    let endpoint = config.control_endpoint.clone().unwrap_or_else(default_endpoint);
+    match send_request(&endpoint, &ControlRequest::Status).await {
+        Ok(response) if response.ok => println!("{}", response.message),
+        Ok(response) => println!("{}; state may be out of date", response.message),
+        Err(_) => println!("KeepHive is not running; showing the last saved state"),
+    }
+    println!();
+
+    let state = if config.state_path.exists() {
+        StateManager::load_read_only(&config.state_path, &StateManager::job_files(&config)?).await?
+    } else {
+        BackupState::new()
+    };
+
+    // Configured jobs in config order, then ad-hoc jobs
+    let mut jobs: Vec<&JobState> = config.jobs.iter()
+        .filter_map(|job| state.get_job(&job.id))
+        .collect();
+    jobs.extend(state.jobs.iter().filter(|js| !config.jobs.iter().any(|job| job.id == js.id)));
+    let never_run = config.jobs.iter().filter(|job| state.get_job(&job.id).is_none());
+
+    let time = |t: Option<chrono::DateTime<Utc>>| t
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string());
+
+    let width = jobs.iter().map(|js| js.id.len())
+        .chain(config.jobs.iter().map(|job| job.id.len()))
+        .max()
+        .unwrap_or(0)
+        .max(3);
+
+    println!("{:<width$}  {:<12}  {:<16}  {:<16}  {:<10}  LAST BACKUP", "JOB", "STATUS", "LAST RUN", "NEXT RUN", "RECENT");
+    let mut failures = Vec::new();
+    for js in &jobs {
+        let status = match &js.status {
+            JobStatus::Running { .. } => "running",
+            JobStatus::Failed { error, .. } => {
+                failures.push((js.id.as_str(), error.as_str()));
+                "failed"
+            }
+            JobStatus::Idle if js.awaiting_confirmation.is_some() => "confirm",
+            JobStatus::Idle if js.first_run_review.as_ref().is_some_and(|review| !review.confirmed) => "review",
+            JobStatus::Idle if js.queued_since.is_some() => "queued",
+            JobStatus::Idle if js.deferred_since.is_some() => "deferred",
+            JobStatus::Idle if js.failed_attempts > 0 => "retrying",
+            JobStatus::Idle => "idle",
+        };
+        let backup = js.last_backup.as_ref()
+            .map(|b| format!("{} files, {}", b.files_copied + b.files_unchanged, format_bytes(b.bytes_copied)))
+            .unwrap_or_else(|| "-".to_string());
+
+        let recent: String = js.recent_results.iter().map(|result| result.symbol()).collect();
+        let recent = if recent.is_empty() { "-".to_string() } else { recent };
+
+        println!(
+            "{:<width$}  {:<12}  {:<16}  {:<16}  {:<10}  {}",
+            js.id, status, time(js.last_run), time(js.next_run), recent, backup
         );
     }
+    for job in never_run {
+        println!("{:<width$}  {:<12}  {:<16}  {:<16}  {:<10}  -", job.id, "new", "-", "-", "-");
+    }
 
-    let content = tokio::fs::read_to_string(path).await
-        .context("Failed to read config file")?;
+    for (job_id, error) in failures {
+        println!();
+        println!("{} failed: {}", job_id, error);
+    }
 
-    let config: ServiceConfig = serde_json::from_str(&content)
-        .context("Failed to parse config file")?;
+    for health in &state.target_health {
+        if let Some(since) = health.unreachable_since {
+            println!();
+            println!(
+                "Target {} unreachable since {}: {}",
+                health.target.display(), time(Some(since)), health.error.as_deref().unwrap_or("-")
+            );
+        }
+    }
 
-    Ok(config)
+    Ok(())
 }
 
-fn print_help() {
-    println!("KeepHive v{} - Enterprise Backup Daemon", env!("CARGO_PKG_VERSION"));
-    println!();
-    println!("USAGE:");
-    println!("  keephive.exe [CONFIG_FILE]              Run in console mode");
-    println!("  keephive.exe --install [CONFIG_FILE]    Install as Windows Service");
-    println!("  keephive.exe --uninstall                Uninstall Windows Service");
-    println!("  keephive.exe --start                    Start Windows Service");
-    println!("  keephive.exe --stop                     Stop Windows Service");
-    println!("  keephive.exe --help                     Show this help");
-    println!();
-    println!("EXAMPLES:");
-    println!("  # Run in console mode (interactive)");
-    println!("  keephive.exe config.json");
+/// Show recent runs from the history file: --history [JOB_ID] [--limit N] [--config FILE]
+#[tokio::main]
+async fn run_history(args: &[String]) -> Result<()> {
+    use keephive::state::{HistoryStore, RunOutcome};
+
+    let job_id = args.first().filter(|a| !a.starts_with("--")).map(String::as_str);
+
+    let limit = match option_value(args, "--limit")? {
+        Some(n) => n.parse::<usize>()
+            .with_context(|| format!("Invalid --limit value: {}", n))?,
+        None => 20,
+    };
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let history_path = HistoryStore::path_for_state_file(&config.state_path);
+    let records = HistoryStore::read_recent(&history_path, job_id, limit).await?;
+
+    if records.is_empty() {
+        println!("No runs recorded");
+        return Ok(());
+    }
+
+    for record in records {
+        let finished = format_local_time(record.finished_at);
+        let took = format_elapsed(record.started_at, record.finished_at);
+        let kind = if record.adhoc { " (ad-hoc)" } else { "" };
+
+        match record.outcome {
+            RunOutcome::Success { files_copied, bytes_copied, files_skipped, .. } => {
+                println!("{}  ✓ {}{}: {} files, {} in {}{}",
+                    finished, record.job_id, kind, files_copied, format_bytes(bytes_copied), took,
+                    if files_skipped > 0 { format!(", {} skipped", files_skipped) } else { String::new() });
+            }
+            RunOutcome::Failed { error } => {
+                println!("{}  ✗ {}{}: {}", finished, record.job_id, kind, error);
+            }
+            RunOutcome::Skipped { reason } => {
+                println!("{}  - {}{}: skipped, {}", finished, record.job_id, kind, reason);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Show network transfer totals: --usage [JOB] [--days N] [--config FILE]
+#[tokio::main]
+async fn run_usage(args: &[String]) -> Result<()> {
+    use keephive::state::UsageStore;
+    use std::collections::BTreeMap;
+
+    let job_id = args.first().filter(|a| !a.starts_with("--")).map(String::as_str);
+
+    let days = match option_value(args, "--days")? {
+        Some(n) => n.parse::<u64>().ok()
+            .filter(|days| *days > 0)
+            .with_context(|| format!("Invalid --days value: {} (expected a number of days, at least 1)", n))?,
+        None => 30,
+    };
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let since = chrono::Local::now().date_naive()
+        .checked_sub_days(chrono::Days::new(days - 1))
+        .unwrap_or(chrono::NaiveDate::MIN);
+    let buckets: Vec<_> = UsageStore::read(&UsageStore::path_for_state_file(&config.state_path)).await?
+        .into_iter()
+        .filter(|b| b.date >= since && job_id.is_none_or(|id| b.job_id == id))
+        .collect();
+
+    if buckets.is_empty() {
+        println!("No network transfers recorded in the last {} days", days);
+        return Ok(());
+    }
+
+    let mut totals: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    for bucket in &buckets {
+        println!("{}  {} via {}: {} sent, {} received",
+            bucket.date, bucket.job_id, bucket.backend,
+            format_bytes(bucket.bytes_sent), format_bytes(bucket.bytes_received));
+
+        let total = totals.entry(&bucket.backend).or_default();
+        total.0 += bucket.bytes_sent;
+        total.1 += bucket.bytes_received;
+    }
+
     println!();
-    println!("  # Install and run as Windows Service");
-    println!("  keephive.exe --install config.json");
-    println!("  sc start KeepHive");
+    println!("Last {} days:", days);
+    for (backend, (sent, received)) in totals {
+        println!("  {}: {} sent, {} received", backend, format_bytes(sent), format_bytes(received));
+    }
+
+    Ok(())
+}
+
+/// Ask the running daemon to run a configured job now: --run-now <JOB> [--config FILE]
+#[tokio::main]
+async fn run_run_now(args: &[String]) -> Result<()> {
+    use keephive::service::control::send_request;
+    use keephive::service::ControlRequest;
+
+    let job_id = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context("Usage: keephive --run-now <JOB> [--config FILE]")?;
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    let response = send_request(&endpoint, &ControlRequest::RunJob { job_id: job_id.clone() }).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+
+    println!("{}", response.message);
+    Ok(())
+}
+
+/// Let a job back up to the volume now mounted at its target: --adopt-volume <JOB> [--config FILE]
+#[tokio::main]
+async fn run_adopt_volume(args: &[String]) -> Result<()> {
+    use keephive::service::control::send_request;
+    use keephive::service::ControlRequest;
+
+    let job_id = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context("Usage: keephive --adopt-volume <JOB> [--config FILE]")?;
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    let response = send_request(&endpoint, &ControlRequest::AdoptVolume { job_id: job_id.clone() }).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+
+    println!("{}", response.message);
+    Ok(())
+}
+
+/// Print the live log of a job from the running daemon: --tail <JOB> [--config FILE]
+#[tokio::main]
+async fn run_tail(args: &[String]) -> Result<()> {
+    use keephive::service::control::tail_job;
+
+    let job_id = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context("Usage: keephive --tail <JOB> [--config FILE]")?;
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    tail_job(&endpoint, job_id, |line| println!("{}", line)).await?;
+
+    eprintln!("Daemon closed the connection");
+    Ok(())
+}
+
+/// Answer a large run or first run estimate waiting for confirmation: --confirm|--reject <JOB> [--config FILE]
+#[tokio::main]
+async fn run_confirm(args: &[String]) -> Result<()> {
+    use keephive::service::control::send_request;
+    use keephive::service::ControlRequest;
+
+    let approve = args[0] == "--confirm";
+    let job_id = args.get(1)
+        .filter(|a| !a.starts_with("--"))
+        .with_context(|| format!("Usage: keephive {} <JOB> [--config FILE]", args[0]))?;
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    let request = ControlRequest::ConfirmRun { job_id: job_id.clone(), approve };
+    let response = send_request(&endpoint, &request).await?;
+
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+
+    println!("{}", response.message);
+    Ok(())
+}
+
+/// Control endpoint from --config, the installed service's config, or the default
+async fn resolve_control_endpoint(args: &[String]) -> Result<String> {
+    use keephive::service::control::default_endpoint;
+
+    let config_path = match option_value(args, "--config")? {
+        Some(path) => Some(PathBuf::from(path)),
+        None => installed_config_path(),
+    };
+
+    let endpoint = match config_path {
+        Some(path) => load_config(&path).await
+            .context("Failed to load configuration")?
+            .control_endpoint,
+        None => None,
+    };
+
+    Ok(endpoint.unwrap_or_else(default_endpoint))
+}
+
+/// Config path recorded when the service was installed
+#[cfg(windows)]
+fn installed_config_path() -> Option<PathBuf> {
+    keephive::platform::windows::registry::read_service_registration()
+        .ok()
+        .flatten()
+        .map(|r| r.config_path)
+}
+
+#[cfg(unix)]
+fn installed_config_path() -> Option<PathBuf> {
+    SystemService::installed_config_path()
+}
+
+/// Write a zip for bug reports: --collect-diagnostics [OUTPUT] [--config FILE]
+#[tokio::main]
+async fn run_collect_diagnostics(args: &[String]) -> Result<()> {
+    use keephive::observability::collect_diagnostics;
+    use keephive::service::control::{default_endpoint, send_request};
+    use keephive::service::ControlRequest;
+
+    let output = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!(
+            "keephive-diagnostics-{}.zip",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )));
+
+    let config_path = match option_value(args, "--config")? {
+        Some(path) => PathBuf::from(path),
+        None => installed_config_path().unwrap_or_else(|| PathBuf::from("keephive_config.json")),
+    };
+
+    // A broken config is worth collecting too, so it only decides where the daemon listens
+    let endpoint = load_config(&config_path).await.ok()
+        .and_then(|config| config.control_endpoint)
+        .unwrap_or_else(default_endpoint);
+    let daemon_status = match send_request(&endpoint, &ControlRequest::Status).await {
+        Ok(response) => response.message,
+        Err(e) => format!("not reachable ({})", e),
+    };
+
+    let entries = collect_diagnostics(&config_path, &daemon_status, &output).await?;
+
+    println!("Wrote {} ({})", output.display(), entries.join(", "));
+    println!("Secrets were removed; check the files before sharing them.");
+    Ok(())
+}
+
+/// Print upcoming runs of a job: --simulate <JOB_ID> [--for 30d] [--count N] [--config FILE]
+#[tokio::main]
+async fn run_simulate(args: &[String]) -> Result<()> {
+    use keephive::scheduler::{parse_duration_spec, simulate_runs, MAX_SIMULATED_RUNS};
+    use keephive::state::StateManager;
+
+    let job_id = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context("Usage: keephive --simulate <JOB_ID> [--for 30d] [--count N] [--config FILE]")?;
+
+    let horizon = match option_value(args, "--for")? {
+        Some(spec) => parse_duration_spec(spec)?,
+        None => chrono::Duration::days(7),
+    };
+
+    let count = match option_value(args, "--count")? {
+        Some(n) => n.parse::<usize>()
+            .with_context(|| format!("Invalid --count value: {}", n))?,
+        None => MAX_SIMULATED_RUNS,
+    }.min(MAX_SIMULATED_RUNS);
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    // Missing or unreadable state just means the job has never run
+    let last_run = if config.state_path.exists() {
+        StateManager::load_read_only(&config.state_path, &StateManager::job_files(&config)?).await
+            .ok()
+            .and_then(|state| state.get_job(&job.id).and_then(|js| js.last_run))
+    } else {
+        None
+    };
+
+    let now = chrono::Local::now();
+    let runs = simulate_runs(&job.schedule, last_run, now, horizon, count);
+
+    println!("Job:      {}", job.id);
+    println!("Schedule: {}", job.schedule);
+    match last_run {
+        Some(t) => println!("Last run: {}", format_local_time(t)),
+        None => println!("Last run: never"),
+    }
+    println!("Window:   {} -> {}", now.format("%Y-%m-%d %H:%M"), (now + horizon).format("%Y-%m-%d %H:%M"));
+    println!();
+
+    if runs.is_empty() {
+        println!("No runs scheduled in this window");
+        return Ok(());
+    }
+
+    for (i, run) in runs.iter().enumerate() {
+        println!("{:>4}. {}", i + 1, run.format("%a %Y-%m-%d %H:%M:%S"));
+    }
+
+    if runs.len() == count {
+        println!();
+        println!("(stopped after {} runs)", count);
+    }
+
+    Ok(())
+}
+
+/// Compare two backups of a job: --diff <JOB_ID> <BACKUP_A> <BACKUP_B> [--config FILE]
+#[tokio::main]
+async fn run_diff(args: &[String]) -> Result<()> {
+    use keephive::core::{BackupManifest, BackupOrchestrator, ManifestDiff};
+
+    let positional: Vec<&String> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .collect();
+
+    let [job_id, backup_a, backup_b] = positional[..] else {
+        anyhow::bail!("Usage: keephive --diff <JOB_ID> <BACKUP_A> <BACKUP_B> [--config FILE]\n\
+            Backups are directory names, paths, or 'latest' / 'previous'");
+    };
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    let path_a = BackupOrchestrator::resolve_backup(&job.target, &job.source, backup_a).await?;
+    let path_b = BackupOrchestrator::resolve_backup(&job.target, &job.source, backup_b).await?;
+
+    let manifest_a = BackupManifest::load_or_scan(&path_a).await?;
+    let manifest_b = BackupManifest::load_or_scan(&path_b).await?;
+    let diff = ManifestDiff::between(&manifest_a, &manifest_b);
+
+    println!("Comparing {} -> {}", manifest_a.backup_name, manifest_b.backup_name);
+    println!();
+
+    for entry in &diff.added {
+        println!("  + {} ({})", entry.path, format_bytes(entry.size));
+    }
+    for entry in &diff.removed {
+        println!("  - {} ({})", entry.path, format_bytes(entry.size));
+    }
+    for (old, new) in &diff.modified {
+        println!("  ~ {} ({} -> {})", new.path, format_bytes(old.size), format_bytes(new.size));
+    }
+
+    if diff.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+
+    let net = diff.net_bytes();
+    println!();
+    println!("{} added, {} removed, {} modified; net {}{}",
+        diff.added.len(), diff.removed.len(), diff.modified.len(),
+        if net < 0 { "-" } else { "+" }, format_bytes(net.unsigned_abs()));
+
+    Ok(())
+}
+
+/// List source paths that would be too long on a job's target: --check-paths <JOB_ID> [--config FILE]
+#[tokio::main]
+async fn run_check_paths(args: &[String]) -> Result<()> {
+    use keephive::core::{BackupOrchestrator, CopyOptions};
+
+    let job_id = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context("Usage: keephive --check-paths <JOB_ID> [--config FILE]")?;
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    let report = BackupOrchestrator::check_paths(&job.source, &job.target, &CopyOptions::for_job(job)).await?;
+
+    if report.is_clean() {
+        println!("All paths fit within the target's {}-character limit", report.limit);
+        return Ok(());
+    }
+
+    for line in report.lines() {
+        println!("  {}", line);
+    }
+    println!();
+    println!("{}", report.summary());
+
+    Ok(())
+}
+
+/// Report what runs of one or every folder job would copy, exclude and remove, without
+/// writing anything: --dry-run [JOB_ID] [--config FILE]
+#[tokio::main]
+async fn run_dry_run(args: &[String]) -> Result<()> {
+    use keephive::config::JobKind;
+    use keephive::core::{dry_run, RetentionRules};
+
+    let job_id = args.first().filter(|a| !a.starts_with("--"));
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let jobs: Vec<_> = match job_id {
+        Some(job_id) => vec![config.jobs.iter()
+            .find(|j| &j.id == job_id)
+            .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?],
+        None => config.jobs.iter().filter(|j| j.kind == JobKind::Files).collect(),
+    };
+
+    for (i, job) in jobs.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}: {} -> {}", job.id, job.source.display(), job.target.display());
+
+        match dry_run(job, &RetentionRules::for_job(job, config.retention_count)).await {
+            Ok(report) => {
+                for line in report.lines() {
+                    println!("  {}", line);
+                }
+            }
+            Err(e) => println!("  Failed: {:#}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a config without starting the service and list its errors and warnings:
+/// --check-config [FILE] [--target-os OS] [--config FILE]. Exits with 0 when nothing is
+/// found, 1 on errors (including a config that cannot be read) and 2 on warnings only.
+#[tokio::main]
+async fn run_check_config(args: &[String]) -> Result<()> {
+    use keephive::config::{check_config, check_unknown_keys, Severity, TargetOs};
+
+    let config_path = match option_value(args, "--config")? {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(args.first().filter(|a| !a.starts_with("--")).map_or("keephive_config.json", String::as_str)),
+    };
+    let target: TargetOs = match option_value(args, "--target-os")? {
+        Some(os) => os.parse()?,
+        None => TargetOs::current(),
+    };
+
+    let config = match load_config(&config_path).await {
+        Ok(config) => config,
+        Err(e) => {
+            println!("error: {}: {:#}", config_path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut issues = check_config(&config, target).await;
+    if let Ok(text) = tokio::fs::read_to_string(&config_path).await {
+        issues.extend(check_unknown_keys(&config_path, &text, &config));
+    }
+    if issues.is_empty() {
+        println!("{} is valid, no issues for {}", config_path.display(), target);
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+    println!();
+    println!("{}: {} errors, {} warnings for {}", config_path.display(), errors, issues.len() - errors, target);
+    std::process::exit(if errors > 0 { 1 } else { 2 })
+}
+
+/// Send a test email through the configured SMTP server: --test-notification [--config FILE]
+#[tokio::main]
+async fn run_test_notification(args: &[String]) -> Result<()> {
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+    let notifications = config.notifications
+        .context("No notifications section in the configuration")?;
+
+    keephive::observability::send_mail(
+        &notifications,
+        "KeepHive: test notification",
+        "This is a test email from KeepHive. Run reports will be sent to this address.",
+    ).await?;
+
+    println!("Test email sent to {}", notifications.to.join(", "));
+    Ok(())
+}
+
+/// Report the largest files, directories and changes of a backup:
+/// --analyze <JOB_ID> [BACKUP] [--top N] [--runs N] [--config FILE]
+#[tokio::main]
+async fn run_analyze(args: &[String]) -> Result<()> {
+    use keephive::core::{
+        largest_deltas, largest_directories, largest_files, BackupManifest, BackupOrchestrator,
+        ManifestDiff,
+    };
+
+    let positional: Vec<&String> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .collect();
+
+    let (job_id, reference) = match positional[..] {
+        [job_id] => (job_id, "latest"),
+        [job_id, backup] => (job_id, backup.as_str()),
+        _ => anyhow::bail!("Usage: keephive --analyze <JOB_ID> [BACKUP] [--top N] [--runs N] [--config FILE]"),
+    };
+
+    let top = match option_value(args, "--top")? {
+        Some(n) => n.parse::<usize>()
+            .with_context(|| format!("Invalid --top value: {}", n))?,
+        None => 10,
+    };
+
+    let runs = match option_value(args, "--runs")? {
+        Some(n) => n.parse::<usize>()
+            .with_context(|| format!("Invalid --runs value: {}", n))?,
+        None => 10,
+    };
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    let backup_path = BackupOrchestrator::resolve_backup(&job.target, &job.source, reference).await?;
+    let manifest = BackupManifest::load_or_scan(&backup_path).await?;
+
+    println!("Backup: {} ({} files, {})",
+        manifest.backup_name, manifest.files.len(), format_bytes(manifest.total_bytes()));
+
+    println!();
+    println!("Largest files:");
+    for file in largest_files(&manifest, top) {
+        println!("  {:>10}  {}", format_bytes(file.size), file.path);
+    }
+
+    println!();
+    println!("Largest directories:");
+    for dir in largest_directories(&manifest, top) {
+        println!("  {:>10}  {} ({} files)", format_bytes(dir.size), dir.path, dir.files);
+    }
+
+    // Compare against the completed backup that came right before this one
+    let backups = BackupOrchestrator::list_job_backups(&job.target, &job.source).await?;
+    let previous = backups.iter()
+        .position(|b| b == &backup_path)
+        .and_then(|i| i.checked_sub(1))
+        .map(|i| &backups[i]);
+
+    println!();
+    match previous {
+        Some(previous) => {
+            let previous_manifest = BackupManifest::load_or_scan(previous).await?;
+            let diff = ManifestDiff::between(&previous_manifest, &manifest);
+            let net = diff.net_bytes();
+
+            println!("Biggest changes since {} (net {}{}):",
+                previous_manifest.backup_name, if net < 0 { "-" } else { "+" }, format_bytes(net.unsigned_abs()));
+            for delta in largest_deltas(&diff, top) {
+                let change = delta.delta();
+                println!("  {}{:>9}  {}",
+                    if change < 0 { "-" } else { "+" }, format_bytes(change.unsigned_abs()), delta.path);
+            }
+        }
+        None => println!("No previous backup to compare against"),
+    }
+
+    println!();
+    print_retention_projection(job, &config, runs).await
+}
+
+/// What retention will remove over the next `runs` runs, at the rate the target has grown so far
+async fn print_retention_projection(job: &keephive::config::BackupJob, config: &ServiceConfig, runs: usize) -> Result<()> {
+    use keephive::core::{project_retention, BackupOrchestrator, RemovalReason, RetentionRules};
+
+    let rules = RetentionRules::for_job(job, config.retention_count);
+
+    let backups = BackupOrchestrator::measure_backups(&job.target, &job.source).await?;
+    let projection = project_retention(&backups, &rules, runs, chrono::Utc::now());
+
+    println!("Retention: keep {}{}{}",
+        rules.count.unwrap_or_default(),
+        job.retention_max_age_days.map(|days| format!(", at most {} days", days)).unwrap_or_default(),
+        job.retention_max_total_gb.map(|gb| format!(", at most {} GB", gb)).unwrap_or_default());
+    println!("  {} backups use {}, each run adds about {}{}",
+        backups.len(),
+        format_bytes(projection.total_bytes),
+        format_bytes(projection.added_per_run),
+        projection.interval
+            .map(|interval| format!(" (one run every {})", format_duration(interval.to_std().unwrap_or_default())))
+            .unwrap_or_default());
+
+    let existing: Vec<_> = projection.removals.iter()
+        .filter(|removal| backups.iter().any(|b| b.name == removal.name))
+        .collect();
+    if existing.is_empty() {
+        println!("  No backup is removed in the next {} runs", runs);
+    } else {
+        println!("  Removed in the next {} runs:", runs);
+        for removal in existing {
+            let when = match removal.at {
+                Some(at) => at.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string(),
+                None => format!("run {}", removal.run),
+            };
+            let reason = match removal.reason {
+                RemovalReason::Count => "count",
+                RemovalReason::Age => "age",
+                RemovalReason::Size => "size",
+            };
+            println!("    {}  {} ({})", when, removal.name, reason);
+        }
+    }
+    println!("  Expected use after {} runs: {}", runs, format_bytes(projection.projected_bytes));
+
+    Ok(())
+}
+
+/// Copy a backup back out, checking files against the manifest:
+/// --restore <JOB_ID> [BACKUP] (--to <PATH> | --in-place) [--on-conflict POLICY] [--restore-attributes]
+/// [--ignore-errors] [--config FILE]
+#[tokio::main]
+async fn run_restore(args: &[String]) -> Result<()> {
+    use keephive::core::{BackupOrchestrator, ConflictPolicy, RestoreOptions, RestoreOrchestrator};
+    use tokio_util::sync::CancellationToken;
+
+    let positional: Vec<&String> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .collect();
+
+    let usage = "Usage: keephive --restore <JOB_ID> [BACKUP | --backup NAME] (--to <PATH> | --in-place) \
+        [--on-conflict skip|overwrite|rename|newer] [--restore-attributes] [--ignore-errors] [--config FILE]";
+    let (job_id, reference) = match (&positional[..], option_value(args, "--backup")?) {
+        ([job_id], None) => (*job_id, "latest"),
+        ([job_id], Some(backup)) => (*job_id, backup),
+        ([job_id, backup], None) => (*job_id, backup.as_str()),
+        _ => anyhow::bail!(usage),
+    };
+
+    let in_place = args.iter().any(|a| a == "--in-place");
+    let to = option_value(args, "--to")?;
+    if in_place == to.is_some() {
+        anyhow::bail!(usage);
+    }
+
+    // Restoring over the live source always needs a policy; skip leaves every existing file alone
+    let conflict = match option_value(args, "--on-conflict")? {
+        Some(policy) => Some(policy.parse::<ConflictPolicy>()?),
+        None if in_place => Some(ConflictPolicy::Skip),
+        None => None,
+    };
+
+    let options = RestoreOptions {
+        ignore_errors: args.iter().any(|a| a == "--ignore-errors"),
+        conflict,
+        restore_attributes: args.iter().any(|a| a == "--restore-attributes"),
+    };
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    let backup_path = BackupOrchestrator::resolve_backup(&job.target, &job.source, reference).await?;
+    let destination = match to {
+        Some(path) => PathBuf::from(path),
+        None => job.source.clone(),
+    };
+
+    let cancellation = CancellationToken::new();
+    let ctrl_c = cancellation.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            ctrl_c.cancel();
+        }
+    });
+
+    println!("Restoring {} to {}", backup_path.display(), destination.display());
+    if let Some(policy) = conflict {
+        println!("Existing files: {}", policy);
+    }
+
+    let mut last_shown = std::time::Instant::now();
+    let report = RestoreOrchestrator::new()
+        .restore_with_progress(&backup_path, &destination, options, cancellation, |p| {
+            if p.files_done == p.files_total || last_shown.elapsed() >= std::time::Duration::from_secs(1) {
+                last_shown = std::time::Instant::now();
+                eprint!("\r  {}/{} files, {} of {}   ",
+                    p.files_done, p.files_total, format_bytes(p.bytes_done), format_bytes(p.bytes_total));
+            }
+        })
+        .await;
+    eprintln!();
+    let report = report?;
+
+    println!("Restored {} files ({})", report.files_restored, format_bytes(report.bytes_restored));
+
+    if report.files_skipped + report.files_overwritten + report.files_renamed > 0 {
+        println!("Existing files: {} kept, {} overwritten, {} renamed to .bak",
+            report.files_skipped, report.files_overwritten, report.files_renamed);
+    }
+
+    if report.files_unverified > 0 {
+        println!("{} files have no checksum in the manifest and were only checked by size",
+            report.files_unverified);
+    }
+
+    if !report.not_captured.is_empty() {
+        println!("{} files could not be copied when this backup was made:", report.not_captured.len());
+        for (path, reason) in &report.not_captured {
+            println!("  NOT CAPTURED {} ({})", path, reason);
+        }
+    }
+
+    if report.is_clean() {
+        return Ok(());
+    }
+
+    println!();
+    for path in &report.corrupted {
+        println!("  CORRUPTED  {}", path);
+    }
+    for (path, error) in &report.failed {
+        println!("  FAILED     {} ({})", path, error);
+    }
+
+    anyhow::bail!(
+        "{} corrupted and {} unreadable files; do not trust the restored copies of these files",
+        report.corrupted.len(),
+        report.failed.len()
+    )
+}
+
+/// Move a job's backups to a new target and point the job at it:
+/// --migrate-target <JOB_ID> --to <NEW_TARGET> [--move] [--config FILE]
+#[tokio::main]
+async fn run_migrate_target(args: &[String]) -> Result<()> {
+    use keephive::core::{migrate_target, remove_originals};
+    use keephive::service::control::send_request;
+    use keephive::service::ControlRequest;
+    use keephive::state::StateManager;
+    use tokio_util::sync::CancellationToken;
+
+    let usage = "Usage: keephive --migrate-target <JOB_ID> --to <NEW_TARGET> [--move] [--config FILE]";
+    let job_id = args.first()
+        .filter(|a| !a.starts_with("--"))
+        .context(usage)?;
+    let new_target = PathBuf::from(option_value(args, "--to")?.context(usage)?);
+    let remove_old = args.iter().any(|a| a == "--move");
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    // The daemon owns the state file and would start backups into the old target meanwhile
+    let endpoint = resolve_control_endpoint(args).await?;
+    if send_request(&endpoint, &ControlRequest::Ping).await.is_ok() {
+        anyhow::bail!("KeepHive is running; stop the service before migrating a target");
+    }
+
+    let cancellation = CancellationToken::new();
+    let ctrl_c = cancellation.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            ctrl_c.cancel();
+        }
+    });
+
+    println!("Copying backups of {} from {} to {}", job.id, job.target.display(), new_target.display());
+
+    let report = migrate_target(&job.target, &job.source, &new_target, cancellation).await?;
+
+    println!("Copied and verified {} backups ({} files, {})",
+        report.backups.len(), report.files_verified, format_bytes(report.bytes_verified));
+
+    // Point the job at the new target: config first, so a restarted daemon agrees with the state
+    let format = keephive::config::ConfigFormat::from_path(&config_path);
+    let content = tokio::fs::read_to_string(&config_path).await
+        .context("Failed to read config file")?;
+    let mut document: serde_json::Value = format.parse(&content)
+        .context("Failed to parse config file")?;
+
+    let job_entry = document["jobs"].as_array_mut()
+        .and_then(|jobs| jobs.iter_mut().find(|j| j["id"] == job.id.as_str()))
+        .context("Job not found in config file")?;
+    job_entry["target"] = serde_json::Value::String(new_target.to_string_lossy().into_owned());
+
+    let temp_path = config_path.with_file_name(format!(
+        "{}.tmp",
+        config_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+    ));
+    tokio::fs::write(&temp_path, format.to_string(&document)?).await
+        .context("Failed to write config file")?;
+    tokio::fs::rename(&temp_path, &config_path).await
+        .context("Failed to replace config file")?;
+
+    let state_manager = StateManager::new(config.state_path.clone()).await
+        .context("Failed to open state file")?;
+    state_manager.set_job_files(StateManager::job_files(&config)?).await?;
+    state_manager.update_job_state(&job.id, |js| {
+        js.target = new_target.clone();
+
+        for metadata in [&mut js.last_backup, &mut js.active_backup].into_iter().flatten() {
+            if let Some(path) = report.new_path_of(&metadata.backup_path) {
+                metadata.backup_path = path.to_path_buf();
+            }
+        }
+    }).await?;
+    state_manager.flush().await?;
+
+    println!("Updated {} and {}", config_path.display(), config.state_path.display());
+
+    if remove_old {
+        let removed = remove_originals(&report).await;
+        println!("Removed {} of {} original backups", removed, report.backups.len());
+    } else {
+        println!("Original backups were kept in {}", job.target.display());
+    }
+
+    Ok(())
+}
+
+/// Authorize Google Drive replicas and hand the refresh token to the running service,
+/// which stores it encrypted for its own account: --gdrive-login [--config FILE]
+#[tokio::main]
+async fn run_gdrive_login(args: &[String]) -> Result<()> {
+    use keephive::service::control::send_request;
+    use keephive::service::{ControlRequest, SecretValue};
+    use keephive::storage::gdrive::{device_login, REFRESH_TOKEN_SECRET};
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let endpoint = resolve_control_endpoint(args).await?;
+    if send_request(&endpoint, &ControlRequest::Ping).await.is_err() {
+        anyhow::bail!("KeepHive is not running; start the service first, it stores the credentials for its own account");
+    }
+
+    let refresh_token = device_login(&config.storage.gdrive, |code| {
+        println!("To let KeepHive store backups in your Google Drive, open");
+        println!();
+        println!("    {}", code.verification_url);
+        println!();
+        println!("and enter the code {} within {} minutes.", code.user_code, code.expires_in / 60);
+        println!("Waiting for authorization...");
+    }).await?;
+
+    let request = ControlRequest::StoreSecret {
+        name: REFRESH_TOKEN_SECRET.to_string(),
+        value: SecretValue(refresh_token),
+    };
+    let response = send_request(&endpoint, &request).await?;
+    if !response.ok {
+        anyhow::bail!("{}", response.message);
+    }
+
+    println!("Google Drive authorized; credentials stored by the service");
+    Ok(())
+}
+
+/// Detect user folders and target drives and write a config: --wizard [CONFIG_FILE]
+#[tokio::main]
+async fn run_wizard(args: &[String]) -> Result<()> {
+    use keephive::config::wizard::{
+        build_config, detect_target_drives, detect_user_folders, shares_disk, WIZARD_TARGET_FOLDER,
+    };
+    use keephive::config::DEFAULT_RETENTION_COUNT;
+    use keephive::core::calculate_dir_size;
+
+    let config_path = std::path::absolute(args.first().map(String::as_str).unwrap_or("keephive_config.json"))?;
+    if config_path.exists() && !ask_yes_no(&format!("{} exists. Overwrite it?", config_path.display()), false)? {
+        return Ok(());
+    }
+
+    println!("Looking for folders to back up...");
+    let mut folders = Vec::new();
+    let mut total_size = 0;
+
+    for folder in detect_user_folders() {
+        let size = calculate_dir_size(&folder.path).await.unwrap_or(0);
+        let question = format!("Back up {} ({}, {}) {}?", folder.name, folder.path.display(), format_bytes(size), folder.schedule);
+
+        if ask_yes_no(&question, true)? {
+            total_size += size;
+            folders.push(folder);
+        }
+    }
+
+    if folders.is_empty() {
+        anyhow::bail!("No folders selected; create the config by hand as described in the README");
+    }
+
+    let drives = detect_target_drives();
+    println!();
+    println!("Where should backups go? They need about {} per copy.", format_bytes(total_size));
+    for (i, drive) in drives.iter().enumerate() {
+        let mut notes = Vec::new();
+        if drive.removable {
+            notes.push("removable");
+        }
+        if shares_disk(drive, &folders) {
+            notes.push("same disk as your files");
+        }
+        if drive.free_bytes < total_size {
+            notes.push("not enough space");
+        }
+
+        println!("  {}. {} {:<16} {} free{}", i + 1, drive.root.display(), drive.label, format_bytes(drive.free_bytes),
+            if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) });
+    }
+
+    // Default to the roomiest drive that does not hold the files themselves
+    let default_drive = drives.iter()
+        .position(|d| !shares_disk(d, &folders) && d.free_bytes >= total_size);
+    let question = if drives.is_empty() { "Target folder" } else { "Drive number or target folder" };
+    let answer = ask(question, default_drive.map(|i| (i + 1).to_string()).as_deref())?;
+
+    let target_root = match answer.parse::<usize>() {
+        Ok(n) if (1..=drives.len()).contains(&n) => drives[n - 1].root.join(WIZARD_TARGET_FOLDER),
+        Ok(_) => anyhow::bail!("No drive number {}", answer),
+        Err(_) => PathBuf::from(answer),
+    };
+
+    let retention = ask("Backups to keep per folder", Some(&DEFAULT_RETENTION_COUNT.to_string()))?;
+    let retention_count = retention.parse::<usize>()
+        .with_context(|| format!("Invalid number: {}", retention))?;
+
+    let data_dir = config_path.parent().unwrap_or(std::path::Path::new("."));
+    let config = build_config(&folders, &target_root, retention_count, data_dir);
+    let text = keephive::config::ConfigFormat::from_path(&config_path).to_string(&config)?;
+    tokio::fs::write(&config_path, text).await
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+
+    println!();
+    println!("Wrote {}, backing up {} to {}", config_path.display(),
+        folders.iter().map(|f| f.name.as_str()).collect::<Vec<_>>().join(", "), target_root.display());
+    println!("Try it in the console:     keephive.exe \"{}\"", config_path.display());
+    println!("Install as a service:      keephive.exe --install \"{}\"", config_path.display());
+    Ok(())
+}
+
+/// Read an answer from the console, falling back to `default` on an empty line
+fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    use std::io::Write;
+
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{}: ", question),
+    }
+    std::io::stdout().flush()?;
+
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line)? == 0 {
+        anyhow::bail!("No answer given");
+    }
+
+    match (line.trim(), default) {
+        ("", Some(default)) => Ok(default.to_string()),
+        ("", None) => ask(question, None),
+        (answer, _) => Ok(answer.to_string()),
+    }
+}
+
+fn ask_yes_no(question: &str, default: bool) -> Result<bool> {
+    let answer = ask(&format!("{} (y/n)", question), Some(if default { "y" } else { "n" }))?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Map a backup to a drive letter: --mount <JOB_ID> <BACKUP> <DRIVE> [--config FILE]
+#[cfg(windows)]
+#[tokio::main]
+async fn run_mount(args: &[String]) -> Result<()> {
+    use keephive::core::BackupOrchestrator;
+    use keephive::platform::windows::mount::mount_backup;
+
+    let positional: Vec<&String> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .collect();
+
+    let [job_id, reference, drive] = positional[..] else {
+        anyhow::bail!("Usage: keephive --mount <JOB_ID> <BACKUP> <DRIVE> [--config FILE]");
+    };
+
+    let config_path = PathBuf::from(option_value(args, "--config")?.unwrap_or("keephive_config.json"));
+    let config = load_config(&config_path).await
+        .context("Failed to load configuration")?;
+
+    let job = config.jobs.iter()
+        .find(|j| &j.id == job_id)
+        .with_context(|| format!("Job '{}' not found in {}", job_id, config_path.display()))?;
+
+    let backup_path = BackupOrchestrator::resolve_backup(&job.target, &job.source, reference).await?;
+
+    if !BackupOrchestrator::is_complete_backup(&backup_path).await {
+        anyhow::bail!("Refusing to mount incomplete backup: {}", backup_path.display());
+    }
+
+    mount_backup(drive, &backup_path)?;
+
+    println!("Mounted {} as {}", backup_path.display(), drive);
+    println!("The drive is read-only; copy files out to work with them.");
+    println!("Remove it with: keephive.exe --unmount {}", drive);
+
+    Ok(())
+}
+
+/// Value following `flag` in `args`, if the flag is present
+fn option_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => args.get(i + 1)
+            .map(|v| Some(v.as_str()))
+            .with_context(|| format!("Missing value for {}", flag)),
+        None => Ok(None),
+    }
+}
+
+async fn load_config(path: &PathBuf) -> Result<ServiceConfig> {
+    if !path.exists() {
+        anyhow::bail!(
+            "Configuration file not found: {}\n\nCreate a config file first. Example:\n{}",
+            path.display(),
+            get_example_config()
+        );
+    }
+
+    let content = tokio::fs::read_to_string(path).await
+        .context("Failed to read config file")?;
+
+    keephive::config::parse_config(path, &content)
+}
+
+fn print_help() {
+    println!("KeepHive v{} - Enterprise Backup Daemon", env!("CARGO_PKG_VERSION"));
+    println!();
+    println!("USAGE:");
+    println!("  keephive.exe [CONFIG_FILE]              Run in console mode");
+    println!("  keephive.exe --install [CONFIG_FILE] [--shell-integration]");
+    println!("                                          Install as Windows Service (systemd unit on Linux, rc.d script on FreeBSD)");
+    println!("  keephive.exe --uninstall                Uninstall the service");
+    println!("  keephive.exe --start                    Start the service");
+    println!("  keephive.exe --stop                     Stop the service");
+    println!("  keephive.exe --upgrade-service [EXE]    Replace the service binary and restart");
+    println!("  keephive.exe --install-task [CONFIG_FILE] [--every MINUTES]");
+    println!("                                          Run due backups at logon and on a timer, without admin rights");
+    println!("  keephive.exe --uninstall-task           Remove the scheduled task");
+    println!("  keephive.exe --run-pending-and-exit [CONFIG_FILE]");
+    println!("                                          Run due backups once and exit");
+    println!("  keephive.exe --backup-now FOLDER [--config FILE]");
+    println!("                                          Back up a folder once via the running service");
+    println!("  keephive.exe --submit SOURCE TARGET [--retention N] [--description TEXT]");
+    println!("                                          Run a one-off backup via the running service");
+    println!("  keephive.exe --run-now JOB [--config FILE]");
+    println!("                                          Run a configured job now via the running service");
+    println!("  keephive.exe --tail JOB [--config FILE] Stream the live log of a job from the running service");
+    println!("  keephive.exe --confirm JOB [--config FILE]");
+    println!("  keephive.exe --reject JOB [--config FILE]");
+    println!("                                          Answer a large run or first run estimate waiting for confirmation");
+    println!("  keephive.exe --adopt-volume JOB [--config FILE]");
+    println!("                                          Back up to the volume now at the job's target (replaced drive)");
+    println!("  keephive.exe --status [--config FILE]   Show the state of every job");
+    println!("  keephive.exe --history [JOB] [--limit N] [--config FILE]");
+    println!("                                          Show recent runs");
+    println!("  keephive.exe --usage [JOB] [--days N] [--config FILE]");
+    println!("                                          Show data sent to and received from remote storage");
+    println!("  keephive.exe --simulate JOB [--for 30d] [--count N] [--config FILE]");
+    println!("                                          List upcoming runs of a job");
+    println!("  keephive.exe --diff JOB A B [--config FILE]");
+    println!("                                          List files changed between two backups");
+    println!("  keephive.exe --analyze JOB [BACKUP] [--top N] [--runs N] [--config FILE]");
+    println!("                                          Show largest files, growth and upcoming retention");
+    println!("  keephive.exe --check-paths JOB [--config FILE]");
+    println!("                                          List paths too long for a job's target");
+    println!("  keephive.exe --dry-run [JOB] [--config FILE]");
+    println!("                                          Show what a run would copy, exclude and remove");
+    println!("  keephive.exe --collect-diagnostics [OUTPUT] [--config FILE]");
+    println!("                                          Collect logs, config and state into a zip for bug reports");
+    println!("  keephive.exe --check-config [FILE] [--target-os OS] [--config FILE]");
+    println!("                                          Check a config without starting the service, also for a move to linux or freebsd");
+    println!("  keephive.exe --test-notification [--config FILE]");
+    println!("                                          Send a test email with the notification settings");
+    println!("  keephive.exe --restore JOB [BACKUP | --backup NAME] (--to PATH | --in-place) [--on-conflict POLICY]");
+    println!("                        [--restore-attributes] [--ignore-errors] [--config FILE]");
+    println!("                                          Restore a backup, checking files against its manifest");
+    println!("  keephive.exe --migrate-target JOB --to NEW_TARGET [--move] [--config FILE]");
+    println!("                                          Copy a job's backups to a new target and switch to it");
+    println!("  keephive.exe --gdrive-login [--config FILE]");
+    println!("                                          Authorize gdrive:// replicas");
+    println!("  keephive.exe --wizard [CONFIG_FILE]     Create a config for your user folders step by step");
+    println!("  keephive.exe --mount JOB BACKUP DRIVE [--config FILE]");
+    println!("                                          Browse a backup as a drive letter");
+    println!("  keephive.exe --unmount DRIVE            Remove a backup drive mapping");
+    println!("  keephive.exe --help                     Show this help");
+    println!();
+    println!("EXAMPLES:");
+    println!("  # Run in console mode (interactive)");
+    println!("  keephive.exe config.json");
+    println!();
+    println!("  # Install and run as Windows Service");
+    println!("  keephive.exe --install config.json");
+    println!("  sc start KeepHive");
+    println!();
+    println!("  # Show when a job will run over the next 30 days");
+    println!("  keephive.exe --simulate my_backup --for 30d --config config.json");
+    println!();
+    println!("  # See what changed in the most recent backup");
+    println!("  keephive.exe --diff my_backup previous latest --config config.json");
+    println!();
+    println!("  # Upgrade the installed service to this binary");
+    println!("  keephive.exe --upgrade-service");
     println!();
     println!("  # Uninstall service");
     println!("  sc stop KeepHive");
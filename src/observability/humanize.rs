@@ -0,0 +1,86 @@
+//! Human-readable sizes, durations and times for logs, status output and reports,
+//! so they read "1.4 GiB" and "2h 13m" rather than raw byte and second counts.
+
+use chrono::{DateTime, Local, Utc};
+use std::time::Duration;
+
+/// Byte size in binary units (1.4 GiB)
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Duration in its two largest units (3d 4h, 2h 13m, 5m 7s, 45s), or milliseconds
+/// below a second
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: [(&str, u64); 4] = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return format!("{}ms", duration.subsec_millis());
+    }
+
+    let first = UNITS.iter().position(|(_, size)| secs >= *size).unwrap_or(UNITS.len() - 1);
+
+    let (name, size) = UNITS[first];
+    let mut text = format!("{}{}", secs / size, name);
+    if let Some((next_name, next_size)) = UNITS.get(first + 1) {
+        let rest = secs % size / next_size;
+        if rest > 0 {
+            text.push_str(&format!(" {}{}", rest, next_name));
+        }
+    }
+    text
+}
+
+/// Duration between two points in time, as `format_duration`; negative spans are zero
+pub fn format_elapsed(from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    format_duration((to - from).to_std().unwrap_or_default())
+}
+
+/// Point in time in the machine's local time zone (2025-03-14 02:00:07)
+pub fn format_local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_sizes_use_binary_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(1_503_238_554), "1.4 GiB");
+    }
+
+    #[test]
+    fn test_durations_show_the_two_largest_units() {
+        assert_eq!(format_duration(Duration::from_millis(350)), "350ms");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(307)), "5m 7s");
+        assert_eq!(format_duration(Duration::from_secs(7980)), "2h 13m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(273_600)), "3d 4h");
+    }
+
+    #[test]
+    fn test_negative_elapsed_time_is_zero() {
+        let now = Utc::now();
+        assert_eq!(format_elapsed(now, now - chrono::Duration::seconds(5)), "0ms");
+        assert_eq!(format_elapsed(now, now + chrono::Duration::seconds(90)), "1m 30s");
+    }
+}
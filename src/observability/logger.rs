@@ -1,10 +1,13 @@
 use std::path::Path;
 use std::sync::{Mutex, OnceLock};
+use super::redact::RedactingMakeWriter;
+use super::tail::{tail_interest, JobLogLayer};
 use tracing_subscriber::{
+    filter,
     layer::SubscriberExt,
     reload,
     util::SubscriberInitExt,
-    EnvFilter,
+    EnvFilter, Layer,
 };
 
 /// Must be kept alive for the entire application lifetime
@@ -13,6 +16,15 @@ static LOG_GUARD: OnceLock<Mutex<Option<tracing_appender::non_blocking::WorkerGu
 /// Reload handle for dynamically changing the log filter at runtime
 static RELOAD_HANDLE: OnceLock<Mutex<reload::Handle<EnvFilter, tracing_subscriber::Registry>>> = OnceLock::new();
 
+/// Target of the summary event logged once per run; it is logged at `info` whatever the
+/// configured level, so alerting on finished runs keeps working with `log_level: "warn"`
+pub const RUN_SUMMARY_TARGET: &str = "keephive::run_summary";
+
+/// Filter for the configured level, letting run summaries through
+fn level_filter(level: &str) -> EnvFilter {
+    EnvFilter::new(format!("{},{}=info", level, RUN_SUMMARY_TARGET))
+}
+
 /// Log rotation strategy
 #[derive(Debug, Clone, Copy)]
 pub enum Rotation {
@@ -27,57 +39,61 @@ pub fn init_logging(
     rotation: Rotation,
 ) -> anyhow::Result<()> {
     let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new(level));
+        .unwrap_or_else(|_| level_filter(level));
 
     // Create a reloadable filter layer
     let (filter_layer, reload_handle) = reload::Layer::new(filter);
 
     // Console layer - always enabled
     let console_layer = tracing_subscriber::fmt::layer()
+        .with_writer(RedactingMakeWriter(std::io::stdout))
         .with_target(true)
         .with_thread_ids(true)
         .with_file(true)
         .with_line_number(true);
 
-    // Build subscriber with reloadable filter and console layer
-    let subscriber = tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(console_layer);
-
     // Add file layer if log directory is specified
-    if let Some(dir) = log_dir {
-        // Ensure log directory exists
-        std::fs::create_dir_all(dir)?;
-
-        let file_appender = match rotation {
-            Rotation::Daily => {
-                tracing_appender::rolling::daily(dir, "keephive.log")
-            }
-            Rotation::Hourly => {
-                tracing_appender::rolling::hourly(dir, "keephive.log")
-            }
-            Rotation::Never => {
-                tracing_appender::rolling::never(dir, "keephive.log")
-            }
-        };
-
-        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
-
-        let file_layer = tracing_subscriber::fmt::layer()
-            .with_writer(non_blocking)
-            .with_target(true)
-            .with_thread_ids(true)
-            .with_file(true)
-            .with_line_number(true)
-            .with_ansi(false); // No ANSI colors in file
-
-        subscriber.with(file_layer).init();
+    let file_layer = match log_dir {
+        Some(dir) => {
+            // Ensure log directory exists
+            std::fs::create_dir_all(dir)?;
+
+            let file_appender = match rotation {
+                Rotation::Daily => {
+                    tracing_appender::rolling::daily(dir, "keephive.log")
+                }
+                Rotation::Hourly => {
+                    tracing_appender::rolling::hourly(dir, "keephive.log")
+                }
+                Rotation::Never => {
+                    tracing_appender::rolling::never(dir, "keephive.log")
+                }
+            };
+
+            let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+
+            LOG_GUARD.set(Mutex::new(Some(guard)))
+                .map_err(|_| anyhow::anyhow!("Logger already initialized"))?;
+
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(RedactingMakeWriter(non_blocking))
+                    .with_target(true)
+                    .with_thread_ids(true)
+                    .with_file(true)
+                    .with_line_number(true)
+                    .with_ansi(false), // No ANSI colors in file
+            )
+        }
+        None => None,
+    };
 
-        LOG_GUARD.set(Mutex::new(Some(guard)))
-            .map_err(|_| anyhow::anyhow!("Logger already initialized"))?;
-    } else {
-        subscriber.init();
-    }
+    // The configured level only filters the console and file output; job tails
+    // see every level of the job they follow
+    tracing_subscriber::registry()
+        .with(console_layer.and_then(file_layer).with_filter(filter_layer))
+        .with(JobLogLayer.with_filter(filter::filter_fn(tail_interest)))
+        .init();
 
     // Store the reload handle for runtime reconfiguration
     RELOAD_HANDLE.set(Mutex::new(reload_handle))
@@ -96,7 +112,7 @@ pub fn reload_logging(
     if let Some(handle_mutex) = RELOAD_HANDLE.get() {
         if let Ok(handle) = handle_mutex.lock() {
             let new_filter = EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| EnvFilter::new(level));
+                .unwrap_or_else(|_| level_filter(level));
 
             match handle.reload(new_filter) {
                 Ok(_) => {
@@ -0,0 +1,48 @@
+//! Resident memory of the daemon process, checked against `memory_limit_mb`.
+
+/// Resident set size of this process in bytes, None where it cannot be read
+#[cfg(target_os = "linux")]
+pub fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+/// Working set size of this process in bytes
+#[cfg(windows)]
+pub fn resident_bytes() -> Option<u64> {
+    use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows::Win32::System::Threading::GetCurrentProcess;
+
+    let mut counters = PROCESS_MEMORY_COUNTERS::default();
+    let size = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+
+    // SAFETY: the counters struct is valid for writes of `size` bytes
+    unsafe { GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, size) }.ok()?;
+    Some(counters.WorkingSetSize as u64)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn resident_bytes() -> Option<u64> {
+    None
+}
+
+/// `VmRSS:    123456 kB` from `/proc/self/status`
+#[cfg(target_os = "linux")]
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line["VmRSS:".len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_reads_resident_memory() {
+        use super::*;
+
+        assert_eq!(parse_vm_rss("Name:\tkeephive\nVmRSS:\t   2048 kB\nThreads:\t4\n"), Some(2048 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tkeephive\n"), None);
+        assert!(resident_bytes().is_some_and(|bytes| bytes > 0));
+    }
+}
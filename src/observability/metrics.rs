@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::scheduler::is_adhoc_job;
+use crate::state::{BackupState, JobStatus};
+
+/// Bytes copied by every backup in this process, fed by the copy engine
+static BYTES_COPIED: AtomicU64 = AtomicU64::new(0);
+
+/// Count bytes written to a backup target
+pub fn record_bytes_copied(bytes: u64) {
+    BYTES_COPIED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Counters describing the daemon main loop
+#[derive(Debug, Default)]
+pub struct DaemonMetrics {
+    loop_iterations: AtomicU64,
+    last_iteration_micros: AtomicU64,
+    max_iteration_micros: AtomicU64,
+    total_iteration_micros: AtomicU64,
+    jobs_spawned: AtomicU64,
+    config_reloads: AtomicU64,
+    queue_length: AtomicU64,
+    jobs_queued: AtomicU64,
+    max_queue_wait_micros: AtomicU64,
+    jobs_running: AtomicU64,
+    job_panics: AtomicU64,
+}
+
+/// Point-in-time copy of the daemon metrics
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetricsSnapshot {
+    pub loop_iterations: u64,
+    pub last_iteration_micros: u64,
+    pub max_iteration_micros: u64,
+    pub avg_iteration_micros: u64,
+    pub jobs_spawned: u64,
+    pub config_reloads: u64,
+    pub queue_length: u64,
+    pub jobs_queued: u64,
+    pub max_queue_wait_micros: u64,
+    pub jobs_running: u64,
+    /// Job tasks that panicked instead of returning
+    pub job_panics: u64,
+    pub bytes_copied: u64,
+}
+
+impl DaemonMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record how long one pass of the main loop spent handling its event
+    pub fn record_iteration(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros().min(u64::MAX as u128) as u64;
+        self.loop_iterations.fetch_add(1, Ordering::Relaxed);
+        self.last_iteration_micros.store(micros, Ordering::Relaxed);
+        self.max_iteration_micros.fetch_max(micros, Ordering::Relaxed);
+        self.total_iteration_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_job_spawned(&self) {
+        self.jobs_spawned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_config_reload(&self) {
+        self.config_reloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_job_panic(&self) {
+        self.job_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_job_queued(&self) {
+        self.jobs_queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how long a job waited in the queue before it got a slot
+    pub fn record_queue_wait(&self, waited: Duration) {
+        let micros = waited.as_micros().min(u64::MAX as u128) as u64;
+        self.max_queue_wait_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn set_queue_length(&self, length: usize) {
+        self.queue_length.store(length as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_jobs_running(&self, running: usize) {
+        self.jobs_running.store(running as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let loop_iterations = self.loop_iterations.load(Ordering::Relaxed);
+        let total = self.total_iteration_micros.load(Ordering::Relaxed);
+
+        MetricsSnapshot {
+            loop_iterations,
+            last_iteration_micros: self.last_iteration_micros.load(Ordering::Relaxed),
+            max_iteration_micros: self.max_iteration_micros.load(Ordering::Relaxed),
+            avg_iteration_micros: total.checked_div(loop_iterations).unwrap_or(0),
+            jobs_spawned: self.jobs_spawned.load(Ordering::Relaxed),
+            config_reloads: self.config_reloads.load(Ordering::Relaxed),
+            queue_length: self.queue_length.load(Ordering::Relaxed),
+            jobs_queued: self.jobs_queued.load(Ordering::Relaxed),
+            max_queue_wait_micros: self.max_queue_wait_micros.load(Ordering::Relaxed),
+            jobs_running: self.jobs_running.load(Ordering::Relaxed),
+            job_panics: self.job_panics.load(Ordering::Relaxed),
+            bytes_copied: BYTES_COPIED.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Values published as Windows performance counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterValues {
+    pub jobs_running: u64,
+    pub jobs_queued: u64,
+    pub jobs_failed: u64,
+    /// Total so far; perfmon shows it as a rate
+    pub bytes_copied: u64,
+    /// Seconds since the configured job that succeeded longest ago last succeeded
+    /// (0 until a job has succeeded)
+    pub last_success_age_secs: u64,
+}
+
+impl CounterValues {
+    pub fn collect(snapshot: &MetricsSnapshot, state: &BackupState, now: DateTime<Utc>) -> Self {
+        let jobs = || state.jobs.iter().filter(|js| !is_adhoc_job(&js.id));
+
+        Self {
+            jobs_running: snapshot.jobs_running,
+            jobs_queued: snapshot.queue_length,
+            jobs_failed: jobs().filter(|js| matches!(js.status, JobStatus::Failed { .. })).count() as u64,
+            bytes_copied: snapshot.bytes_copied,
+            last_success_age_secs: jobs()
+                .filter_map(|js| js.last_run)
+                .map(|last| (now - last).num_seconds().max(0) as u64)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iteration_latency_tracking() {
+        let metrics = DaemonMetrics::new();
+
+        metrics.record_iteration(Duration::from_millis(10));
+        metrics.record_iteration(Duration::from_millis(30));
+        metrics.record_iteration(Duration::from_millis(20));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.loop_iterations, 3);
+        assert_eq!(snapshot.last_iteration_micros, 20_000);
+        assert_eq!(snapshot.max_iteration_micros, 30_000);
+        assert_eq!(snapshot.avg_iteration_micros, 20_000);
+    }
+
+    #[test]
+    fn test_counters() {
+        let metrics = DaemonMetrics::new();
+
+        metrics.record_job_spawned();
+        metrics.record_job_spawned();
+        metrics.record_config_reload();
+        metrics.record_job_queued();
+        metrics.record_job_panic();
+        metrics.set_queue_length(4);
+        metrics.record_queue_wait(Duration::from_secs(2));
+        metrics.record_queue_wait(Duration::from_secs(1));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.jobs_spawned, 2);
+        assert_eq!(snapshot.config_reloads, 1);
+        assert_eq!(snapshot.jobs_queued, 1);
+        assert_eq!(snapshot.job_panics, 1);
+        assert_eq!(snapshot.queue_length, 4);
+        assert_eq!(snapshot.max_queue_wait_micros, 2_000_000);
+        assert_eq!(snapshot.avg_iteration_micros, 0);
+    }
+
+    #[test]
+    fn test_counter_values() {
+        use crate::state::JobState;
+        use std::path::PathBuf;
+
+        let now = Utc::now();
+        let mut state = BackupState::new();
+        for (id, hours_ago, failed) in [("docs", 2, false), ("photos", 30, true), ("adhoc-1", 100, false)] {
+            let mut js = JobState::new(id.to_string(), PathBuf::from("src"), PathBuf::from("dst"));
+            js.last_run = Some(now - chrono::Duration::hours(hours_ago));
+            if failed {
+                js.status = JobStatus::Failed { error: "share offline".to_string(), timestamp: now };
+            }
+            state.upsert_job(js);
+        }
+
+        let metrics = DaemonMetrics::new();
+        metrics.set_jobs_running(1);
+        metrics.set_queue_length(2);
+
+        let values = CounterValues::collect(&metrics.snapshot(), &state, now);
+        assert_eq!((values.jobs_running, values.jobs_queued, values.jobs_failed), (1, 2, 1));
+        // Ad-hoc jobs do not count towards the age
+        assert_eq!(values.last_success_age_secs, 30 * 3600);
+    }
+}
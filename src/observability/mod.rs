@@ -1,3 +1,17 @@
+pub mod diagnostics;
+pub mod humanize;
 pub mod logger;
+pub mod memory;
+pub mod metrics;
+pub mod notify;
+pub mod redact;
+pub mod tail;
 
-pub use logger::{init_logging, reload_logging, shutdown_logging, Rotation};
+pub use diagnostics::collect_diagnostics;
+pub use humanize::{format_bytes, format_duration, format_elapsed, format_local_time};
+pub use logger::{init_logging, reload_logging, shutdown_logging, Rotation, RUN_SUMMARY_TARGET};
+pub use memory::resident_bytes;
+pub use notify::{raise_alert, send_mail, send_run_report, should_notify};
+pub use redact::{load_redaction_key, redact_line, redact_paths, redacted, set_path_redaction};
+pub use metrics::{record_bytes_copied, CounterValues, DaemonMetrics, MetricsSnapshot};
+pub use tail::{subscribe_job_log, JobLog, JOB_SPAN};
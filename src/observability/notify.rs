@@ -0,0 +1,327 @@
+//! Email reports of finished runs (`notifications`), and alerts about events that need
+//! attention between runs. Mail is handed to an SMTP server by the `curl` executable,
+//! like the requests of the remote backends, so no mail or TLS library is linked into
+//! the service.
+
+use anyhow::{bail, Context, Result};
+use chrono::{Local, Utc};
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+use crate::config::{NotificationConfig, NotifyTriggers, SmtpSecurity};
+use crate::observability::{format_bytes, format_duration};
+use crate::platform::PrivateDir;
+use crate::state::{RunOutcome, RunRecord};
+use crate::storage::http::{base64, quote};
+
+/// Seconds to wait for the SMTP server to accept a connection
+const CONNECT_TIMEOUT_SECONDS: u64 = 30;
+
+/// Seconds a whole delivery may take
+const SEND_TIMEOUT_SECONDS: u64 = 120;
+
+/// Whether a finished run sends an email under `triggers`; runs that did not start never do
+pub fn should_notify(record: &RunRecord, triggers: NotifyTriggers) -> bool {
+    match &record.outcome {
+        RunOutcome::Success { files_skipped: 0, .. } => triggers.on_success,
+        RunOutcome::Success { .. } => triggers.on_partial,
+        RunOutcome::Failed { .. } => triggers.on_failure,
+        RunOutcome::Skipped { .. } => false,
+    }
+}
+
+/// Email the result of a finished run to the configured recipients
+pub async fn send_run_report(config: &NotificationConfig, record: &RunRecord) -> Result<()> {
+    let (subject, body) = run_report(record, &host_name());
+    send_mail(config, &subject, &body).await
+}
+
+/// Subject and text of the email reporting a run
+fn run_report(record: &RunRecord, host: &str) -> (String, String) {
+    let result = match &record.outcome {
+        RunOutcome::Success { files_skipped: 0, .. } => "succeeded".to_string(),
+        RunOutcome::Success { files_skipped, .. } => format!("completed with {} skipped files", files_skipped),
+        RunOutcome::Failed { .. } => "failed".to_string(),
+        RunOutcome::Skipped { .. } => "was skipped".to_string(),
+    };
+    let subject = format!("KeepHive: backup of {} {} on {}", record.job_id, result, host);
+
+    let mut lines = vec![
+        format!("Job:       {}", record.job_id),
+        format!("Machine:   {}", host),
+        format!("Source:    {}", record.source.display()),
+        format!("Target:    {}", record.target.display()),
+        format!("Started:   {}", record.started_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")),
+        format!("Duration:  {}", format_duration((record.finished_at - record.started_at).to_std().unwrap_or_default())),
+        format!("Result:    {}", result),
+    ];
+
+    match &record.outcome {
+        RunOutcome::Success { backup_name, files_copied, bytes_copied, files_skipped } => {
+            lines.push(format!("Backup:    {}", backup_name));
+            lines.push(format!("Copied:    {} files, {}", files_copied, format_bytes(*bytes_copied)));
+            if *files_skipped > 0 {
+                lines.push(format!("Skipped:   {} files (listed in the backup manifest)", files_skipped));
+            }
+        }
+        RunOutcome::Failed { error } => lines.push(format!("Error:     {}", error)),
+        RunOutcome::Skipped { reason } => lines.push(format!("Reason:    {}", reason)),
+    }
+
+    lines.push(String::new());
+    lines.push(format!("Earlier runs: keephive --history {}", record.job_id));
+
+    (subject, lines.join("\n"))
+}
+
+/// Raise an alert about something that needs attention outside a run report, such as a
+/// recovered state file or a paused replica: written to the Windows Application event log
+/// and emailed when notifications are set up. The caller logs it; failed deliveries are
+/// logged and otherwise ignored.
+pub async fn raise_alert(notifications: Option<&NotificationConfig>, subject: &str, body: &str) {
+    #[cfg(windows)]
+    if let Err(e) = crate::platform::windows::event_log::report_warning(&format!("{}\n\n{}", subject, body)).await {
+        // Missing in containers and on Nano Server
+        debug!("Failed to write alert to the event log: {:#}", e);
+    }
+    #[cfg(not(windows))]
+    debug!("Alert: {}", subject);
+
+    let Some(config) = notifications else {
+        return;
+    };
+    let body = format!("Machine:   {}\n{}", host_name(), body);
+    if let Err(e) = send_mail(config, subject, &body).await {
+        warn!("Failed to email alert '{}': {:#}", subject, e);
+    }
+}
+
+/// Send a plain text email through the configured SMTP server
+pub async fn send_mail(config: &NotificationConfig, subject: &str, body: &str) -> Result<()> {
+    if config.to.is_empty() {
+        bail!("No notification recipients configured");
+    }
+
+    let password = match &config.password_env {
+        Some(var) => Some(std::env::var(var)
+            .with_context(|| format!("SMTP password variable {} is not set", var))?),
+        None => None,
+    };
+
+    let message = MessageFile::write(&compose(config, subject, body)).await?;
+    let curl_config = curl_config(config, password.as_deref(), &message.path());
+
+    let mut child = tokio::process::Command::new("curl")
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow::anyhow!(
+                "curl was not found; email notifications need curl.exe (included with Windows 10 and later) on the PATH"
+            ),
+            _ => anyhow::Error::new(e).context("Failed to start curl"),
+        })?;
+
+    // Passed on stdin so the password never shows up in process listings
+    let mut stdin = child.stdin.take().context("curl stdin unavailable")?;
+    stdin.write_all(curl_config.as_bytes()).await
+        .context("Failed to pass email to curl")?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await
+        .context("Failed to wait for curl")?;
+
+    if !output.status.success() {
+        bail!(
+            "Sending email through {}:{} failed: {}",
+            config.smtp_server,
+            config.smtp_port,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// curl options delivering `message`, in curl's config file syntax
+fn curl_config(config: &NotificationConfig, password: Option<&str>, message: &std::path::Path) -> String {
+    let scheme = match config.security {
+        SmtpSecurity::Tls => "smtps",
+        SmtpSecurity::Starttls | SmtpSecurity::None => "smtp",
+    };
+
+    let mut options = format!(
+        "silent\nshow-error\nconnect-timeout = {}\nmax-time = {}\n",
+        CONNECT_TIMEOUT_SECONDS, SEND_TIMEOUT_SECONDS
+    );
+    // The URL's path is the name curl greets the server with; without one, curl would
+    // append the message's file name
+    let greeting: String = host_name().chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '.')
+        .collect();
+    let greeting = if greeting.is_empty() { "localhost".to_string() } else { greeting };
+    options.push_str(&format!(
+        "url = {}\n",
+        quote(&format!("{}://{}:{}/{}", scheme, config.smtp_server, config.smtp_port, greeting))
+    ));
+
+    // Without it, curl carries on in plain text when the server offers no STARTTLS
+    if config.security == SmtpSecurity::Starttls {
+        options.push_str("ssl-reqd\n");
+    }
+
+    if let Some(username) = &config.username {
+        options.push_str(&format!("user = {}\n", quote(&format!("{}:{}", username, password.unwrap_or_default()))));
+    }
+
+    options.push_str(&format!("mail-from = {}\n", quote(address(&config.from))));
+    for recipient in &config.to {
+        options.push_str(&format!("mail-rcpt = {}\n", quote(address(recipient))));
+    }
+    options.push_str(&format!("upload-file = {}\n", quote(&message.to_string_lossy())));
+
+    options
+}
+
+/// The email as sent: headers and text with CRLF line endings
+fn compose(config: &NotificationConfig, subject: &str, body: &str) -> String {
+    let headers = [
+        format!("From: {}", config.from),
+        format!("To: {}", config.to.join(", ")),
+        format!("Subject: {}", encode_header(subject)),
+        format!("Date: {}", Utc::now().to_rfc2822()),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Content-Transfer-Encoding: 8bit".to_string(),
+    ];
+
+    let body = body.replace("\r\n", "\n").replace('\n', "\r\n");
+    format!("{}\r\n\r\n{}\r\n", headers.join("\r\n"), body)
+}
+
+/// The address alone of `Name <address>`, for the SMTP envelope
+fn address(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Header text as is when it is ASCII, otherwise as an RFC 2047 encoded word
+fn encode_header(text: &str) -> String {
+    if text.is_ascii() {
+        text.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64(text.as_bytes()))
+    }
+}
+
+/// Name of this machine for the subject line
+fn host_name() -> String {
+    ["COMPUTERNAME", "HOSTNAME"].iter()
+        .find_map(|var| std::env::var(var).ok().filter(|name| !name.is_empty()))
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok().map(|name| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown host".to_string())
+}
+
+/// The message staged for curl to upload, in a private folder removed again when dropped.
+/// curl's stdin already carries its options with the password.
+struct MessageFile {
+    dir: PrivateDir,
+}
+
+impl MessageFile {
+    async fn write(message: &str) -> Result<Self> {
+        let dir = PrivateDir::create("keephive_mail")?;
+        let mut file = tokio::fs::OpenOptions::new().write(true).create_new(true).open(dir.path().join("message.eml")).await
+            .context("Failed to stage email")?;
+        file.write_all(message.as_bytes()).await
+            .context("Failed to stage email")?;
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.path().join("message.eml")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(outcome: RunOutcome) -> RunRecord {
+        let started_at = Utc.with_ymd_and_hms(2026, 3, 1, 2, 0, 0).unwrap();
+        RunRecord {
+            job_id: "docs".to_string(),
+            adhoc: false,
+            source: PathBuf::from("/srv/docs"),
+            target: PathBuf::from("/mnt/backups"),
+            started_at,
+            finished_at: started_at + chrono::Duration::seconds(134),
+            outcome,
+        }
+    }
+
+    fn config(json: &str) -> NotificationConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_triggers_match_run_outcomes() {
+        let success = record(RunOutcome::Success { backup_name: "docs_1".to_string(), files_copied: 3, bytes_copied: 2048, files_skipped: 0 });
+        let partial = record(RunOutcome::Success { backup_name: "docs_1".to_string(), files_copied: 3, bytes_copied: 2048, files_skipped: 2 });
+        let failed = record(RunOutcome::Failed { error: "Target is full".to_string() });
+        let skipped = record(RunOutcome::Skipped { reason: "Application still running".to_string() });
+
+        let defaults = NotifyTriggers::default();
+        assert!(!should_notify(&success, defaults));
+        assert!(should_notify(&partial, defaults));
+        assert!(should_notify(&failed, defaults));
+        assert!(!should_notify(&skipped, defaults));
+
+        let everything = NotifyTriggers { on_failure: true, on_success: true, on_partial: true };
+        assert!(should_notify(&success, everything));
+        assert!(!should_notify(&skipped, everything));
+    }
+
+    #[test]
+    fn test_report_of_failed_run() {
+        let (subject, body) = run_report(&record(RunOutcome::Failed { error: "Target is full".to_string() }), "NAS");
+
+        assert_eq!(subject, "KeepHive: backup of docs failed on NAS");
+        assert!(body.contains("Duration:  2m 14s"), "{}", body);
+        assert!(body.contains("Error:     Target is full"), "{}", body);
+    }
+
+    #[test]
+    fn test_message_and_curl_options() {
+        let config = config(r#"{
+            "smtp_server": "mail.example.com",
+            "username": "backup@example.com",
+            "from": "KeepHive <backup@example.com>",
+            "to": ["admin@example.com", "oncall@example.com"],
+            "on_success": true
+        }"#);
+        assert_eq!(config.smtp_port, 587);
+        assert!(config.triggers.on_success && config.triggers.on_failure);
+
+        let message = compose(&config, "Sicherung von Dokumente fehlgeschlagen ü", "line one\nline two");
+        assert!(message.starts_with("From: KeepHive <backup@example.com>\r\nTo: admin@example.com, oncall@example.com\r\nSubject: =?UTF-8?B?"));
+        assert!(message.ends_with("\r\n\r\nline one\r\nline two\r\n"));
+
+        let options = curl_config(&config, Some("secret"), std::path::Path::new("/tmp/mail.eml"));
+        assert!(options.contains("url = \"smtp://mail.example.com:587/"), "{}", options);
+        assert!(options.contains("\"\nssl-reqd\n"), "{}", options);
+        assert!(options.contains("user = \"backup@example.com:secret\"\n"));
+        assert!(options.contains("mail-from = \"backup@example.com\"\n"));
+        assert!(options.contains("mail-rcpt = \"admin@example.com\"\nmail-rcpt = \"oncall@example.com\"\n"));
+    }
+}
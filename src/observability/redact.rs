@@ -0,0 +1,348 @@
+//! Path redaction for log output (`log_path_redaction`). Formatted log lines are
+//! rewritten on their way to the console, the log file and `--tail`: paths keep their
+//! root and file name, and the folders in between are hashed or left out.
+//!
+//! Paths are recognized in the text (`C:\...`, `\\server\...`, `/...` after a space, a
+//! quote or `=`). A quoted path runs to its closing quote; any other path ends at the first
+//! space. Paths of backed up files are passed through `redacted` where they are logged,
+//! which covers the whole path, relative ones included.
+//!
+//! `hash` is keyed with a secret created once per install, so folder names cannot be
+//! found by hashing guesses. Hashed folders start with `#` and are not hashed again.
+
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::PathRedaction;
+use crate::core::{hmac_sha256, to_hex};
+use crate::state::SecretStore;
+
+/// Mode in effect, changed on config reload
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Key of `hash` redaction; until it is loaded, folders are left out as with `truncate`
+static KEY: OnceLock<String> = OnceLock::new();
+
+/// Name of the stored secret holding the key
+const KEY_SECRET: &str = "log_redaction_key";
+
+/// Hex digits of a folder's hash
+const HASH_LEN: usize = 8;
+
+pub fn set_path_redaction(mode: PathRedaction) {
+    let value = match mode {
+        PathRedaction::Off => 0,
+        PathRedaction::Hash => 1,
+        PathRedaction::Truncate => 2,
+    };
+    MODE.store(value, Ordering::Relaxed);
+}
+
+fn path_redaction() -> PathRedaction {
+    match MODE.load(Ordering::Relaxed) {
+        1 => PathRedaction::Hash,
+        2 => PathRedaction::Truncate,
+        _ => PathRedaction::Off,
+    }
+}
+
+/// Load the key of `hash` redaction from `secrets`, creating it on first use
+pub async fn load_redaction_key(secrets: &SecretStore) -> Result<()> {
+    if KEY.get().is_some() {
+        return Ok(());
+    }
+
+    let key = match secrets.get(KEY_SECRET).await? {
+        Some(key) => key,
+        None => {
+            let mut random = [0u8; 32];
+            getrandom::fill(&mut random).context("Failed to get random bytes for the log redaction key")?;
+            let key = to_hex(&random);
+            secrets.set(KEY_SECRET, &key).await?;
+            key
+        }
+    };
+
+    let _ = KEY.set(key);
+    Ok(())
+}
+
+/// A path to log, redacted as a whole with the configured mode
+pub fn redacted(path: &Path) -> Redacted<'_> {
+    Redacted(path)
+}
+
+pub struct Redacted<'a>(&'a Path);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.0.to_string_lossy();
+        match path_redaction() {
+            PathRedaction::Off => f.write_str(&path),
+            mode => {
+                let root_len = path_root(&path, 0).unwrap_or(0);
+                f.write_str(&redact_path(&path, root_len, mode))
+            }
+        }
+    }
+}
+
+/// Redact the paths in a log line with the configured mode
+pub fn redact_line(line: &str) -> Cow<'_, str> {
+    redact_paths(line, path_redaction())
+}
+
+/// Redact every path found in `text`
+pub fn redact_paths(text: &str, mode: PathRedaction) -> Cow<'_, str> {
+    if mode == PathRedaction::Off {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut changed = false;
+    let mut i = 0;
+
+    while i < text.len() {
+        let Some(root_len) = path_root(text, i) else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+
+        let end = match text[..i].chars().last() {
+            Some(quote @ ('"' | '\'')) => quoted_end(text, i + root_len, quote),
+            _ => path_end(text, i + root_len),
+        };
+        let redacted = redact_path(&text[i..end], root_len, mode);
+        if redacted != text[i..end] {
+            output.push_str(&text[copied..i]);
+            output.push_str(&redacted);
+            copied = end;
+            changed = true;
+        }
+        i = end.max(i + 1);
+    }
+
+    if !changed {
+        return Cow::Borrowed(text);
+    }
+    output.push_str(&text[copied..]);
+    Cow::Owned(output)
+}
+
+fn is_separator(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+/// Length of the root of a path starting at `i` (`C:\`, `\\`, `/`), if one starts there
+fn path_root(text: &str, i: usize) -> Option<usize> {
+    if !starts_token(text, i) {
+        return None;
+    }
+
+    let rest = &text[i..];
+    let mut chars = rest.chars();
+    let root = match (chars.next()?, chars.next(), chars.next()) {
+        (drive, Some(':'), Some(sep)) if drive.is_ascii_alphabetic() && is_separator(sep) => 2,
+        ('\\', Some('\\'), _) => 0,
+        ('/', Some(next), _) if next.is_alphanumeric() || "._~-".contains(next) => 0,
+        _ => return None,
+    };
+
+    // The separators after a drive, and an escaped `\\\\` UNC prefix, belong to the root
+    let separators = rest[root..].chars().take_while(|&c| is_separator(c)).count();
+    Some(root + separators)
+}
+
+/// Whether a path may start at `i`: at the start, after a delimiter, or after an ANSI
+/// color code of the console output
+fn starts_token(text: &str, i: usize) -> bool {
+    let before = &text[..i];
+    match before.chars().last() {
+        None => true,
+        Some(c) if c.is_whitespace() || "\"'([<=,".contains(c) => true,
+        Some('m') => before
+            .rfind('\x1b')
+            .is_some_and(|esc| before[esc + 1..before.len() - 1].chars().all(|c| c == '[' || c == ';' || c.is_ascii_digit())),
+        _ => false,
+    }
+}
+
+/// End of the path that continues at `from`; trailing `:` and `.` are left to the text
+fn path_end(text: &str, from: usize) -> usize {
+    let end = text[from..]
+        .find(|c: char| c.is_whitespace() || c.is_control() || "\"'()[]<>,;".contains(c))
+        .map_or(text.len(), |n| from + n);
+
+    from + text[from..end].trim_end_matches([':', '.']).len()
+}
+
+/// End of a quoted path that continues at `from`: its closing quote on the same line, or
+/// where an unquoted path would end
+fn quoted_end(text: &str, from: usize, quote: char) -> usize {
+    match text[from..].find([quote, '\n']) {
+        Some(n) if text[from + n..].starts_with(quote) => from + n,
+        _ => path_end(text, from),
+    }
+}
+
+/// Whether a folder is already a hash from `hash` redaction
+fn is_hashed(folder: &str) -> bool {
+    folder.strip_prefix('#')
+        .is_some_and(|hash| hash.len() == HASH_LEN && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Redact the folders of one path, keeping its root, separators and last component
+fn redact_path(path: &str, root_len: usize, mode: PathRedaction) -> String {
+    if mode == PathRedaction::Off {
+        return path.to_string();
+    }
+    let (root, rest) = path.split_at(root_len);
+
+    // Components with the separators that follow them
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < rest.len() {
+        let name_end = rest[start..].find(is_separator).map_or(rest.len(), |n| start + n);
+        let sep_end = rest[name_end..].find(|c| !is_separator(c)).map_or(rest.len(), |n| name_end + n);
+        parts.push((&rest[start..name_end], &rest[name_end..sep_end]));
+        start = sep_end;
+    }
+
+    let Some(((last, last_sep), folders)) = parts.split_last() else {
+        return path.to_string();
+    };
+    if folders.is_empty() {
+        return path.to_string();
+    }
+
+    let mut redacted = root.to_string();
+    match (mode, KEY.get()) {
+        (PathRedaction::Hash, Some(key)) => {
+            for (folder, separator) in folders {
+                if is_hashed(folder) {
+                    redacted.push_str(folder);
+                } else {
+                    redacted.push('#');
+                    redacted.push_str(&to_hex(&hmac_sha256(key.as_bytes(), folder.as_bytes()))[..HASH_LEN]);
+                }
+                redacted.push_str(separator);
+            }
+        }
+        _ => {
+            redacted.push('…');
+            redacted.push_str(folders[folders.len() - 1].1);
+        }
+    }
+    redacted.push_str(last);
+    redacted.push_str(last_sep);
+    redacted
+}
+
+/// Writer factory for the log layers, redacting each formatted event on its way out
+pub struct RedactingMakeWriter<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+/// Writer passing whole log lines through `redact_line`; the log layers write each
+/// event in one call
+pub struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if path_redaction() == PathRedaction::Off {
+            return self.0.write(buf);
+        }
+
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact_line(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_keeps_root_and_file_name() {
+        let line = r#"Failed to copy file C:\Users\alice\Documents\tax.pdf (access denied): "/home/alice/notes.txt""#;
+        assert_eq!(
+            redact_paths(line, PathRedaction::Truncate),
+            r#"Failed to copy file C:\…\tax.pdf (access denied): "/…/notes.txt""#
+        );
+
+        assert_eq!(redact_paths(r"target=\\nas\backups\alice", PathRedaction::Truncate), r"target=\\…\alice");
+        assert_eq!(redact_paths("Removing /srv/backups/docs_1/.", PathRedaction::Truncate), "Removing /…/docs_1/.");
+    }
+
+    #[test]
+    fn test_hash_is_stable_per_folder() {
+        let _ = KEY.set("test key".to_string());
+        let first = redact_paths("/home/alice/a.txt", PathRedaction::Hash).into_owned();
+        let second = redact_paths("/home/alice/b.txt", PathRedaction::Hash).into_owned();
+
+        assert!(!first.contains("alice"), "{}", first);
+        assert_eq!(first.len(), "/".len() + 2 * (HASH_LEN + 2) + "a.txt".len());
+        assert_eq!(first.trim_end_matches("a.txt"), second.trim_end_matches("b.txt"));
+        assert_eq!(redact_paths(&first, PathRedaction::Hash), first, "Hashed folders are kept");
+
+        // Keyed, so not the plain SHA-256 of the name
+        let mut plain = crate::core::Sha256::new();
+        plain.update(b"alice");
+        assert!(!first.contains(&to_hex(&plain.finish())[..HASH_LEN]));
+
+        // Escaped debug output keeps its doubled separators
+        let escaped = redact_paths(r#"path "C:\\Users\\alice\\a.txt""#, PathRedaction::Hash);
+        assert!(escaped.starts_with(r#"path "C:\\"#) && escaped.ends_with(r#"\\a.txt""#), "{}", escaped);
+        assert!(!escaped.contains("alice"));
+    }
+
+    #[test]
+    fn test_text_that_is_not_a_path_is_left_alone() {
+        for text in [
+            "Copied 10 MB/s to https://example.com/dav/backups",
+            "Job docs finished: success in 2m 14s",
+            "/single",
+            "and/or",
+        ] {
+            assert!(matches!(redact_paths(text, PathRedaction::Hash), Cow::Borrowed(_)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_quoted_paths_and_logged_paths_are_redacted_whole() {
+        let line = r#"Failed to copy "C:\Users\alice\My Documents\tax return.pdf": locked"#;
+        assert_eq!(redact_paths(line, PathRedaction::Truncate), r#"Failed to copy "C:\…\tax return.pdf": locked"#);
+
+        MODE.store(2, Ordering::Relaxed);
+        let relative = redacted(Path::new("alice/My Documents/tax.pdf")).to_string();
+        let absolute = redacted(Path::new("/home/alice/My Documents/tax.pdf")).to_string();
+        MODE.store(0, Ordering::Relaxed);
+        assert_eq!(relative, "…/tax.pdf");
+        assert_eq!(absolute, "/…/tax.pdf");
+        assert_eq!(redacted(Path::new("/home/alice/a.txt")).to_string(), "/home/alice/a.txt");
+    }
+
+    #[test]
+    fn test_paths_after_console_colors_are_found() {
+        let line = "\x1b[3msource\x1b[0m\x1b[2m=\x1b[0m/home/alice/a.txt";
+        assert_eq!(redact_paths(line, PathRedaction::Truncate), "\x1b[3msource\x1b[0m\x1b[2m=\x1b[0m/…/a.txt");
+    }
+}
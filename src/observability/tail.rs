@@ -0,0 +1,193 @@
+//! Live log events of a single job, for `--tail`. Jobs run inside a `job` span carrying
+//! their ID; while anyone is tailing, events below such a span are formatted and handed
+//! to the subscribers of that job, at every level and whatever the configured log level.
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::redact::redact_line;
+
+/// Name of the span jobs run in; its `job_id` field routes events to subscribers
+pub const JOB_SPAN: &str = "job";
+
+/// Lines buffered per subscriber; a reader that falls further behind loses lines
+const TAIL_BUFFER: usize = 1024;
+
+/// Whether anyone is tailing, checked before an event is looked at
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+static SUBSCRIBERS: Mutex<Vec<Subscription>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct Subscription {
+    id: u64,
+    job_id: String,
+    lines: mpsc::Sender<String>,
+}
+
+/// Log lines of one job, until dropped
+pub struct JobLog {
+    id: u64,
+    lines: mpsc::Receiver<String>,
+}
+
+impl JobLog {
+    /// Next formatted log line
+    pub async fn next(&mut self) -> Option<String> {
+        self.lines.recv().await
+    }
+}
+
+impl Drop for JobLog {
+    fn drop(&mut self) {
+        let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain(|s| s.id != self.id);
+        ACTIVE.store(!subscribers.is_empty(), Ordering::Relaxed);
+    }
+}
+
+/// Start receiving the log events of `job_id`
+pub fn subscribe_job_log(job_id: &str) -> JobLog {
+    let (sender, lines) = mpsc::channel(TAIL_BUFFER);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+    let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+    subscribers.push(Subscription { id, job_id: job_id.to_string(), lines: sender });
+    ACTIVE.store(true, Ordering::Relaxed);
+
+    JobLog { id, lines }
+}
+
+/// Whether events should reach the layer at all; spans always do, so a job that
+/// started before the tail is still recognized
+pub fn tail_interest(metadata: &tracing::Metadata<'_>) -> bool {
+    metadata.is_span() || ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Job ID stored on a `job` span
+struct JobId(String);
+
+/// Tracing layer forwarding events inside `job` spans to their subscribers
+pub struct JobLogLayer;
+
+impl<S> Layer<S> for JobLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != JOB_SPAN {
+            return;
+        }
+
+        let mut fields = FieldText::default();
+        attrs.record(&mut fields);
+        if let (Some(job_id), Some(span)) = (fields.job_id, ctx.span(id)) {
+            span.extensions_mut().insert(JobId(job_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !ACTIVE.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let Some(job_id) = ctx.event_scope(event).and_then(|scope| {
+            scope.from_root().find_map(|span| span.extensions().get::<JobId>().map(|job| job.0.clone()))
+        }) else {
+            return;
+        };
+
+        let subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+        if !subscribers.iter().any(|s| s.job_id == job_id) {
+            return;
+        }
+
+        let mut fields = FieldText::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {:>5} {}: {}{}",
+            chrono::Local::now().format("%H:%M:%S%.3f"),
+            metadata.level(),
+            metadata.target(),
+            fields.message,
+            fields.rest
+        );
+        let line = redact_line(&line).into_owned();
+
+        for subscriber in subscribers.iter().filter(|s| s.job_id == job_id) {
+            // Never block the job on a slow reader
+            let _ = subscriber.lines.try_send(line.clone());
+        }
+    }
+}
+
+/// Event message and other fields as text, and a `job_id` field if present
+#[derive(Default)]
+struct FieldText {
+    message: String,
+    rest: String,
+    job_id: Option<String>,
+}
+
+impl Visit for FieldText {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "job_id" => self.job_id = Some(value.to_string()),
+            "message" => self.message.push_str(value),
+            name => {
+                let _ = write!(self.rest, " {}={}", name, value);
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "job_id" => self.job_id = Some(format!("{:?}", value).trim_matches('"').to_string()),
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            name => {
+                let _ = write!(self.rest, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_only_events_of_the_tailed_job_are_delivered() {
+        let subscriber = tracing_subscriber::registry()
+            .with(JobLogLayer.with_filter(tracing_subscriber::filter::filter_fn(tail_interest)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            // The span exists before anyone tails, like a job already running
+            let tailed = tracing::info_span!(JOB_SPAN, job_id = "tail_test_docs");
+            let other = tracing::info_span!(JOB_SPAN, job_id = %"tail_test_photos");
+
+            let mut log = subscribe_job_log("tail_test_docs");
+            tailed.in_scope(|| {
+                tracing::debug!(files = 3, "Copying {}", "a.txt");
+                tracing::info_span!("copy").in_scope(|| tracing::trace!("Nested"));
+            });
+            other.in_scope(|| tracing::info!("Other job"));
+            tracing::info!("Outside any job");
+
+            let first = log.lines.try_recv().unwrap();
+            assert!(first.contains("DEBUG") && first.ends_with("Copying a.txt files=3"), "{}", first);
+            assert!(log.lines.try_recv().unwrap().ends_with("Nested"));
+            assert!(log.lines.try_recv().is_err());
+        });
+    }
+}
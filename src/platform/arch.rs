@@ -0,0 +1,92 @@
+//! CPU architecture of the build and of the machine. An x64 build runs on ARM64 Windows
+//! under emulation, where some system components (shadow copies) only serve native
+//! processes, so features that need them check `emulated` first.
+
+use std::fmt;
+
+/// `IMAGE_FILE_MACHINE_*` values reported by Windows
+const MACHINE_I386: u16 = 0x014c;
+const MACHINE_AMD64: u16 = 0x8664;
+const MACHINE_ARM64: u16 = 0xaa64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    X86,
+    X64,
+    Arm64,
+    Other,
+}
+
+impl Architecture {
+    /// Architecture of an `IMAGE_FILE_MACHINE_*` value
+    pub fn from_machine(machine: u16) -> Self {
+        match machine {
+            MACHINE_I386 => Architecture::X86,
+            MACHINE_AMD64 => Architecture::X64,
+            MACHINE_ARM64 => Architecture::Arm64,
+            _ => Architecture::Other,
+        }
+    }
+
+    /// Architecture this binary was built for
+    pub const fn build() -> Self {
+        if cfg!(target_arch = "x86_64") {
+            Architecture::X64
+        } else if cfg!(target_arch = "aarch64") {
+            Architecture::Arm64
+        } else if cfg!(target_arch = "x86") {
+            Architecture::X86
+        } else {
+            Architecture::Other
+        }
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Architecture::X86 => "x86",
+            Architecture::X64 => "x64",
+            Architecture::Arm64 => "ARM64",
+            Architecture::Other => "unknown",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Architecture of the machine, which differs from the build's under emulation
+pub fn native() -> Architecture {
+    #[cfg(windows)]
+    if let Some(machine) = crate::platform::windows::arch::native_machine() {
+        return Architecture::from_machine(machine);
+    }
+
+    Architecture::build()
+}
+
+/// The native architecture when this build runs emulated on it
+pub fn emulated() -> Option<Architecture> {
+    emulated_on(native())
+}
+
+fn emulated_on(native: Architecture) -> Option<Architecture> {
+    (native != Architecture::build() && native != Architecture::Other).then_some(native)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_values_and_emulation() {
+        assert_eq!(Architecture::from_machine(0xaa64), Architecture::Arm64);
+        assert_eq!(Architecture::from_machine(0x8664), Architecture::X64);
+        assert_eq!(Architecture::from_machine(0x1c4), Architecture::Other);
+
+        assert_eq!(emulated_on(Architecture::build()), None);
+        assert_eq!(emulated_on(Architecture::Other), None);
+
+        let foreign = if Architecture::build() == Architecture::Arm64 { Architecture::X64 } else { Architecture::Arm64 };
+        assert_eq!(emulated_on(foreign), Some(foreign));
+    }
+}
@@ -0,0 +1,123 @@
+//! Fault injection for file copies, to exercise retry, skip and disk-full handling
+//! deterministically from tests and the hidden `--chaos` flag.
+
+use anyhow::{bail, Context, Result};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::core::CopyError;
+
+/// Faults to inject, parsed from `fail=N,slow=MS,full=N`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultPlan {
+    /// The Nth file copy (from 1) fails once as an unreadable source
+    pub fail_nth: Option<u64>,
+    /// Pause before every file copy
+    pub slow: Option<Duration>,
+    /// The Nth file copy and every later one fail with the target full
+    pub full_from: Option<u64>,
+}
+
+impl FromStr for FaultPlan {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut plan = FaultPlan::default();
+
+        for fault in spec.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let (name, value) = fault.split_once('=')
+                .with_context(|| format!("Fault '{}' needs a value, like fail=3", fault))?;
+            let number = |value: &str| value.parse::<u64>()
+                .with_context(|| format!("Invalid number in fault '{}'", fault));
+
+            match name {
+                "fail" => plan.fail_nth = Some(number(value)?),
+                "slow" => plan.slow = Some(Duration::from_millis(number(value.trim_end_matches("ms"))?)),
+                "full" => plan.full_from = Some(number(value)?),
+                _ => bail!("Unknown fault '{}' (expected fail, slow or full)", name),
+            }
+        }
+
+        Ok(plan)
+    }
+}
+
+/// Applies a `FaultPlan` to the file copies of every job that shares it
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    plan: FaultPlan,
+    copies: AtomicU64,
+}
+
+impl FaultInjector {
+    pub fn new(plan: FaultPlan) -> Self {
+        Self {
+            plan,
+            copies: AtomicU64::new(0),
+        }
+    }
+
+    /// Called before each file copy; fails it or holds it back as planned
+    pub async fn before_copy(&self, src: &Path) -> Result<()> {
+        let copy = self.copies.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some(delay) = self.plan.slow {
+            tokio::time::sleep(delay).await;
+        }
+
+        if self.plan.full_from.is_some_and(|n| copy >= n) {
+            warn!("Injected fault: target full at {}", src.display());
+            return Err(CopyError::target_side(std::io::Error::from(ErrorKind::StorageFull)).into());
+        }
+
+        if self.plan.fail_nth == Some(copy) {
+            warn!("Injected fault: cannot read {}", src.display());
+            return Err(CopyError::source_side(std::io::Error::other("injected read failure")).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::CopyFailure;
+
+    #[test]
+    fn test_parse_plan() {
+        let plan: FaultPlan = "fail=3, slow=20ms,full=10".parse().unwrap();
+        assert_eq!(plan, FaultPlan {
+            fail_nth: Some(3),
+            slow: Some(Duration::from_millis(20)),
+            full_from: Some(10),
+        });
+
+        assert_eq!("".parse::<FaultPlan>().unwrap(), FaultPlan::default());
+        assert!("fail".parse::<FaultPlan>().is_err());
+        assert!("explode=1".parse::<FaultPlan>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_faults_hit_the_planned_copies() {
+        let injector = FaultInjector::new(FaultPlan { fail_nth: Some(2), full_from: Some(4), ..FaultPlan::default() });
+        let path = Path::new("file");
+
+        let mut results = Vec::new();
+        for _ in 0..5 {
+            results.push(injector.before_copy(path).await.err().map(|e| CopyError::classify(&e)));
+        }
+
+        assert_eq!(results, vec![
+            None,
+            Some(CopyFailure::SourceUnreadable),
+            None,
+            Some(CopyFailure::DiskFull),
+            Some(CopyFailure::DiskFull),
+        ]);
+    }
+}
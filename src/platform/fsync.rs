@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::debug;
+
+/// Sync a directory so that entries created or renamed inside it are durable
+pub async fn sync_directory(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = tokio::fs::File::open(path).await
+            .with_context(|| format!("Failed to open directory for sync: {}", path.display()))?;
+
+        dir.sync_all().await
+            .with_context(|| format!("Failed to sync directory: {}", path.display()))?;
+
+        debug!("Synced directory: {}", path.display());
+    }
+
+    // Directories only open with backup semantics; flushing the handle writes the
+    // directory's entries, renames included, through to the disk
+    #[cfg(windows)]
+    {
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        const ERROR_INVALID_FUNCTION: i32 = 1;
+
+        let dir = tokio::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path).await
+            .with_context(|| format!("Failed to open directory for sync: {}", path.display()))?;
+
+        match dir.sync_all().await {
+            Ok(()) => debug!("Synced directory: {}", path.display()),
+            // FAT and some network shares cannot flush directories
+            Err(e) if e.raw_os_error() == Some(ERROR_INVALID_FUNCTION) => {
+                debug!("Directory sync not supported by the filesystem of {}", path.display());
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to sync directory: {}", path.display())),
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    debug!("Directory sync not required on this platform: {}", path.display());
+
+    Ok(())
+}
+
+/// Sync the parent directory of a path (no-op for paths without a parent)
+pub async fn sync_parent_directory(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => sync_directory(parent).await,
+        _ => sync_directory(Path::new(".")).await,
+    }
+}
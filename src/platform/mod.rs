@@ -1,9 +1,22 @@
+pub mod arch;
+pub mod faults;
+pub mod fsync;
+pub mod private_dir;
+pub mod slim;
 pub mod traits;
 
+#[cfg(unix)]
+pub mod unix;
 #[cfg(windows)]
 pub mod windows;
 
+pub use faults::{FaultInjector, FaultPlan};
+pub use fsync::{sync_directory, sync_parent_directory};
+pub use private_dir::PrivateDir;
+pub use slim::{require_full_mode, slim_mode};
 pub use traits::{FileSystem, PathNormalizer};
 
+#[cfg(unix)]
+pub use unix::{UnixFileSystem, UnixService as SystemService};
 #[cfg(windows)]
-pub use windows::WindowsFileSystem;
+pub use windows::{service::WindowsService as SystemService, WindowsFileSystem};
@@ -0,0 +1,91 @@
+//! Randomly named working folders that other accounts can neither open nor create in
+//! advance. Data staged while the service runs as SYSTEM or root, such as registry hives,
+//! database dumps, run results and emails, goes there instead of a predictable name in
+//! the shared temp folder, where another user could plant a link or read it.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Folder only this account, SYSTEM and administrators (root elsewhere) can open;
+/// removed with its contents when dropped
+#[derive(Debug)]
+pub struct PrivateDir {
+    path: PathBuf,
+}
+
+impl PrivateDir {
+    /// Create a fresh folder named `<prefix>_<random>` in the temp folder
+    pub fn create(prefix: &str) -> Result<Self> {
+        Self::create_in(&std::env::temp_dir(), prefix)
+    }
+
+    /// Create a fresh folder named `<prefix>_<random>` in `parent`, which is created if missing
+    pub fn create_in(parent: &Path, prefix: &str) -> Result<Self> {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+
+        let mut random = [0u8; 12];
+        getrandom::fill(&mut random).context("Failed to get random bytes for a folder name")?;
+        let name: String = random.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let path = parent.join(format!("{}_{}", prefix, name));
+
+        // Creating fails on anything already there, links included
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            std::fs::DirBuilder::new().mode(0o700).create(&path)
+                .with_context(|| format!("Failed to create private folder {}", path.display()))?;
+        }
+
+        #[cfg(windows)]
+        crate::platform::windows::acl::create_private_dir(&path)?;
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Remove the folder and its contents now, without blocking
+    pub async fn remove(mut self) -> std::io::Result<()> {
+        let path = std::mem::take(&mut self.path);
+        tokio::fs::remove_dir_all(path).await
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_private_dirs_are_fresh_and_removed() {
+        let parent = tempfile::tempdir().unwrap();
+        let first = PrivateDir::create_in(parent.path(), "keephive_test").unwrap();
+        let second = PrivateDir::create_in(parent.path(), "keephive_test").unwrap();
+
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().file_name().unwrap().to_string_lossy().starts_with("keephive_test_"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(first.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        std::fs::write(first.path().join("hive"), b"secret").unwrap();
+        let (first_path, second_path) = (first.path().to_path_buf(), second.path().to_path_buf());
+        first.remove().await.unwrap();
+        drop(second);
+        assert!(!first_path.exists() && !second_path.exists());
+    }
+}
@@ -0,0 +1,58 @@
+//! Slim mode for Windows containers and Nano Server, where the service control manager,
+//! Task Scheduler, shadow copies and most system tools are missing. KeepHive then runs
+//! as a plain foreground agent and leaves out the features that need them.
+
+use anyhow::{bail, Result};
+use std::sync::OnceLock;
+
+/// Overrides detection: `1` forces slim mode, `0` turns it off
+pub const SLIM_MODE_ENV: &str = "KEEPHIVE_SLIM";
+
+/// Whether KeepHive runs in slim mode, detected once per process
+pub fn slim_mode() -> bool {
+    static SLIM: OnceLock<bool> = OnceLock::new();
+
+    *SLIM.get_or_init(|| {
+        let slim = match std::env::var(SLIM_MODE_ENV).as_deref() {
+            Ok("1") | Ok("true") => true,
+            Ok("0") | Ok("false") => false,
+            _ => detect_container(),
+        };
+        if slim {
+            tracing::info!("Running in slim mode (container or Nano Server)");
+        }
+        slim
+    })
+}
+
+/// Fail with an explanation when `feature` needs components slim mode goes without
+pub fn require_full_mode(feature: &str) -> Result<()> {
+    if slim_mode() {
+        bail!(
+            "{} is not available in a Windows container or on Nano Server; \
+             run `keephive CONFIG_FILE` as the container's entry point instead \
+             (set {}=0 to override)",
+            feature,
+            SLIM_MODE_ENV
+        );
+    }
+    Ok(())
+}
+
+/// Windows containers set `ContainerType` under the Control key; Nano Server has its own server level
+#[cfg(windows)]
+fn detect_container() -> bool {
+    use windows_registry::LOCAL_MACHINE;
+
+    let container = LOCAL_MACHINE.open(r"SYSTEM\CurrentControlSet\Control")
+        .is_ok_and(|key| key.get_u32("ContainerType").is_ok());
+    let nano_server = LOCAL_MACHINE.open(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Server\ServerLevels")
+        .is_ok_and(|key| key.get_u32("NanoServer").is_ok_and(|level| level != 0));
+
+    container || nano_server
+}
+
+#[cfg(not(windows))]
+fn detect_container() -> bool {
+    false
+}
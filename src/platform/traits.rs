@@ -1,6 +1,8 @@
 use anyhow::Result;
 use std::path::{Path, PathBuf};
 
+use crate::core::{BandwidthLimiter, Crc32};
+
 /// Path normalization for platform-specific requirements
 pub trait PathNormalizer {
     /// Normalize path for the platform (e.g., Windows long path support)
@@ -9,6 +11,14 @@ pub trait PathNormalizer {
 
 /// File system operations abstraction
 pub trait FileSystem {
-    /// Copy file with platform-specific optimizations(not yet, but planned)
-    fn copy_file(&self, src: &Path, dst: &Path) -> impl Future<Output=Result<u64>> + Send;
+    /// Copy file with platform-specific optimizations(not yet, but planned), paced by `limiter`,
+    /// reading `buffer_size` bytes at a time and feeding them to `crc` when given
+    fn copy_file(
+        &self,
+        src: &Path,
+        dst: &Path,
+        limiter: &BandwidthLimiter,
+        buffer_size: usize,
+        crc: Option<&mut Crc32>,
+    ) -> impl Future<Output=Result<u64>> + Send;
 }
\ No newline at end of file
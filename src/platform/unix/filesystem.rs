@@ -0,0 +1,125 @@
+use crate::core::{BandwidthLimiter, Crc32, CopyError};
+use crate::platform::traits::{FileSystem, PathNormalizer};
+use anyhow::Result;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::debug;
+
+/// Paths need no rewriting on Unix
+pub struct UnixPathNormalizer;
+
+impl PathNormalizer for UnixPathNormalizer {
+    fn normalize(&self, path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+}
+
+/// Unix filesystem implementation (Linux, FreeBSD and other Unix systems): chunked copy
+/// keeping the source's permissions where the target allows it
+#[derive(Default)]
+pub struct UnixFileSystem;
+
+impl UnixFileSystem {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl FileSystem for UnixFileSystem {
+    async fn copy_file(
+        &self,
+        src: &Path,
+        dst: &Path,
+        limiter: &BandwidthLimiter,
+        buffer_size: usize,
+        mut crc: Option<&mut Crc32>,
+    ) -> Result<u64> {
+        // Links are recorded by the copy engine, never copied through
+        let link = tokio::fs::symlink_metadata(src).await
+            .map_err(CopyError::source_side)?;
+        if link.file_type().is_symlink() {
+            return Err(CopyError::source_side(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "source is a symbolic link",
+            )).into());
+        }
+
+        let mut src_file = tokio::fs::File::open(src).await
+            .map_err(CopyError::source_side)?;
+
+        let mut dst_file = tokio::fs::File::create(dst).await
+            .map_err(CopyError::target_side)?;
+
+        let mut buffer = vec![0u8; buffer_size];
+        let mut total_bytes = 0u64;
+
+        loop {
+            let bytes_read = src_file.read(&mut buffer).await
+                .map_err(CopyError::source_side)?;
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            if let Some(crc) = crc.as_deref_mut() {
+                crc.update(&buffer[..bytes_read]);
+            }
+
+            dst_file.write_all(&buffer[..bytes_read]).await
+                .map_err(CopyError::target_side)?;
+
+            total_bytes += bytes_read as u64;
+            limiter.consume(bytes_read as u64).await;
+        }
+
+        dst_file.flush().await
+            .map_err(CopyError::target_side)?;
+
+        // Keep permissions like tokio::fs::copy does. NAS shares mounted into a jail or
+        // container (SMB, NFS, ZFS datasets with restricted ACLs) may refuse chmod, which
+        // is no reason to lose the copied contents.
+        let permissions = src_file.metadata().await
+            .map_err(CopyError::source_side)?
+            .permissions();
+        match tokio::fs::set_permissions(dst, permissions).await {
+            Ok(()) => {}
+            Err(e) if matches!(e.kind(), ErrorKind::PermissionDenied | ErrorKind::Unsupported) => {
+                debug!("Target refused permissions for {}: {}", dst.display(), e);
+            }
+            Err(e) => return Err(CopyError::target_side(e).into()),
+        }
+
+        Ok(total_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_copy_keeps_mode_and_refuses_links() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("script.sh");
+        tokio::fs::write(&src, b"#!/bin/sh\n").await.unwrap();
+        std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        let fs = UnixFileSystem::new();
+        let limiter = BandwidthLimiter::default();
+        let mut crc = Crc32::new();
+        let dst = dir.path().join("copy.sh");
+        let bytes = fs.copy_file(&src, &dst, &limiter, 4, Some(&mut crc)).await.unwrap();
+
+        assert_eq!(bytes, 10);
+        assert_eq!(crc.finish(), crate::core::crc32_file(&src).await.unwrap());
+        assert_eq!(std::fs::metadata(&dst).unwrap().permissions().mode() & 0o777, 0o750);
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&src, &link).unwrap();
+        let error = fs.copy_file(&link, &dir.path().join("out"), &limiter, 4, None).await.unwrap_err();
+        assert_eq!(CopyError::classify(&error), crate::core::CopyFailure::SourceUnreadable);
+    }
+}
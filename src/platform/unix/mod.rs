@@ -0,0 +1,8 @@
+pub mod filesystem;
+pub mod notify;
+pub mod rcd;
+pub mod service;
+
+pub use filesystem::{UnixFileSystem, UnixPathNormalizer};
+pub use notify::notify_systemd;
+pub use service::UnixService;
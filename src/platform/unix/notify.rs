@@ -0,0 +1,51 @@
+//! Readiness notifications for systemd `Type=notify` units, without libsystemd.
+
+use std::os::unix::net::UnixDatagram;
+use tracing::debug;
+
+/// Send `state` (e.g. `READY=1`) to the socket in `NOTIFY_SOCKET`; does nothing outside systemd
+pub fn notify_systemd(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        let path = socket_path.to_string_lossy();
+
+        // `@name` is a socket in the abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.strip_prefix('@') {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            return socket.send_to_addr(state.as_bytes(), &address);
+        }
+
+        socket.send_to(state.as_bytes(), path.as_ref())
+    });
+
+    if let Err(e) = result {
+        debug!("Failed to notify systemd ({}): {}", state, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_state_reaches_the_notify_socket() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        // SAFETY: no other test reads NOTIFY_SOCKET
+        unsafe { std::env::set_var("NOTIFY_SOCKET", &path) };
+        notify_systemd("READY=1");
+        unsafe { std::env::remove_var("NOTIFY_SOCKET") };
+
+        let mut buffer = [0u8; 16];
+        let received = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..received], b"READY=1");
+    }
+}
@@ -0,0 +1,179 @@
+//! rc.d script for FreeBSD, including TrueNAS jails. The script starts `keephive --service`
+//! under daemon(8), which backgrounds it, writes its pid for `service keephive stop` and
+//! sends its output to a log file.
+
+use anyhow::{bail, Context, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+use super::service::SERVICE_NAME;
+
+/// Where the rc.d script is installed
+const SCRIPT_PATH: &str = "/usr/local/etc/rc.d/keephive";
+
+/// Config used when `--install` is given no path
+pub const DEFAULT_CONFIG_PATH: &str = "/usr/local/etc/keephive/keephive_config.json";
+
+/// Output of the daemon, as there is no journal
+const LOG_PATH: &str = "/var/log/keephive.log";
+
+/// Write the rc.d script and enable it in rc.conf
+pub fn install(exe_path: &Path, config_path: &Path) -> Result<()> {
+    std::fs::write(SCRIPT_PATH, rc_script(exe_path, config_path))
+        .with_context(|| format!("Failed to write {} (run as root)", SCRIPT_PATH))?;
+    std::fs::set_permissions(SCRIPT_PATH, std::fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {} executable", SCRIPT_PATH))?;
+
+    run("sysrc", &[&format!("{}_enable=YES", SERVICE_NAME)])?;
+
+    info!("✓ Service installed successfully");
+    info!("  Config: {}", config_path.display());
+    info!("  Start:  service {} start", SERVICE_NAME);
+    info!("  Stop:   service {} stop", SERVICE_NAME);
+    info!("  Status: service {} status", SERVICE_NAME);
+    info!("  Logs:   {}", LOG_PATH);
+    Ok(())
+}
+
+/// Stop the service, remove the script and its rc.conf entry
+pub fn uninstall() -> Result<()> {
+    if let Err(e) = stop() {
+        warn!("Failed to stop service: {}", e);
+    }
+
+    match std::fs::remove_file(SCRIPT_PATH) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!("KeepHive service is not installed"),
+        Err(e) => return Err(e).with_context(|| format!("Failed to remove {}", SCRIPT_PATH)),
+    }
+
+    if let Err(e) = run("sysrc", &["-x", &format!("{}_enable", SERVICE_NAME)]) {
+        warn!("Failed to remove {}_enable from rc.conf: {}", SERVICE_NAME, e);
+    }
+    Ok(())
+}
+
+pub fn start() -> Result<()> {
+    run("service", &[SERVICE_NAME, "start"])
+}
+
+/// Stop the service; succeeds when it is not running, like `systemctl stop`
+pub fn stop() -> Result<()> {
+    let running = Command::new("service")
+        .args([SERVICE_NAME, "status"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+
+    if !running {
+        return Ok(());
+    }
+    run("service", &[SERVICE_NAME, "stop"])
+}
+
+/// Binary and config path recorded in the installed script
+pub fn installed_paths() -> Option<(PathBuf, PathBuf)> {
+    let script = std::fs::read_to_string(SCRIPT_PATH).ok()?;
+    parse_rc_script(&script)
+}
+
+/// Script for rc.subr; paths are defaults that rc.conf can override. The binary variable
+/// is not `keephive_program`, since rc.subr would run it in place of daemon(8).
+fn rc_script(exe_path: &Path, config_path: &Path) -> String {
+    let working_directory = config_path.parent().unwrap_or(Path::new("/"));
+
+    format!(
+        "#!/bin/sh\n\
+         \n\
+         # PROVIDE: {name}\n\
+         # REQUIRE: LOGIN NETWORKING\n\
+         # KEYWORD: shutdown\n\
+         \n\
+         . /etc/rc.subr\n\
+         \n\
+         name=\"{name}\"\n\
+         rcvar=\"{name}_enable\"\n\
+         \n\
+         load_rc_config $name\n\
+         \n\
+         : ${{{name}_enable:=\"NO\"}}\n\
+         : ${{{name}_binary:={binary}}}\n\
+         : ${{{name}_config:={config}}}\n\
+         : ${{{name}_chdir:={chdir}}}\n\
+         \n\
+         pidfile=\"/var/run/${{name}}.pid\"\n\
+         procname=\"${{{name}_binary}}\"\n\
+         command=\"/usr/sbin/daemon\"\n\
+         command_args=\"-p ${{pidfile}} -o {log} \\\"${{{name}_binary}}\\\" --service \\\"${{{name}_config}}\\\"\"\n\
+         \n\
+         run_rc_command \"$1\"\n",
+        name = SERVICE_NAME,
+        binary = quote(exe_path),
+        config = quote(config_path),
+        chdir = quote(working_directory),
+        log = LOG_PATH,
+    )
+}
+
+/// Path as a double-quoted shell word
+fn quote(path: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in path.display().to_string().chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Binary and config path from the defaults written by `rc_script`
+fn parse_rc_script(script: &str) -> Option<(PathBuf, PathBuf)> {
+    let default = |variable: &str| -> Option<PathBuf> {
+        let prefix = format!(": ${{{}_{}:=\"", SERVICE_NAME, variable);
+        let quoted = script.lines().find_map(|line| line.strip_prefix(prefix.as_str()))?;
+        let quoted = quoted.strip_suffix("\"}")?;
+
+        let mut value = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            value.push(if c == '\\' { chars.next()? } else { c });
+        }
+        Some(PathBuf::from(value))
+    };
+
+    Some((default("binary")?, default("config")?))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+
+    if !output.status.success() {
+        bail!("{} {} failed: {}", program, args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_round_trips_binary_and_config() {
+        let exe = Path::new("/usr/local/bin/keep hive");
+        let config = Path::new("/mnt/tank/$backup/\"odd\".json");
+
+        let script = rc_script(exe, config);
+        assert!(script.contains("rcvar=\"keephive_enable\"\n"));
+        assert!(script.contains(": ${keephive_chdir:=\"/mnt/tank/\\$backup\"}\n"));
+        assert!(!script.contains("keephive_program"));
+
+        assert_eq!(parse_rc_script(&script), Some((exe.to_path_buf(), config.to_path_buf())));
+        assert_eq!(parse_rc_script("name=\"other\"\n"), None);
+    }
+}
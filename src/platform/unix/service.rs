@@ -0,0 +1,283 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+use super::rcd;
+
+/// Name of the systemd unit
+pub const SERVICE_NAME: &str = "keephive";
+
+/// Where the unit file is installed
+const UNIT_PATH: &str = "/etc/systemd/system/keephive.service";
+
+/// Config used when `--install` is given no path
+const DEFAULT_CONFIG_PATH: &str = "/etc/keephive/keephive_config.json";
+
+/// Service manager KeepHive is installed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitSystem {
+    Systemd,
+    /// FreeBSD rc.d, also inside TrueNAS jails
+    RcD,
+}
+
+impl InitSystem {
+    fn current() -> Self {
+        if cfg!(target_os = "freebsd") {
+            InitSystem::RcD
+        } else {
+            InitSystem::Systemd
+        }
+    }
+}
+
+/// KeepHive as a system service running `keephive --service <config>`: a systemd
+/// `Type=notify` unit on Linux, an rc.d script on FreeBSD
+pub struct UnixService;
+
+impl UnixService {
+    /// Write and enable the systemd unit or rc.d script
+    pub fn install(config_path: Option<PathBuf>, shell_integration: bool) -> Result<()> {
+        let exe_path = std::env::current_exe()
+            .context("Failed to get executable path")?;
+
+        let config_full_path = match config_path {
+            Some(path) if path.is_absolute() => path,
+            Some(path) => std::env::current_dir()?.join(path),
+            None if InitSystem::current() == InitSystem::RcD => PathBuf::from(rcd::DEFAULT_CONFIG_PATH),
+            None => PathBuf::from(DEFAULT_CONFIG_PATH),
+        };
+
+        if shell_integration {
+            warn!("--shell-integration is only available on Windows, ignoring it");
+        }
+
+        info!("Installing service: {}", SERVICE_NAME);
+        info!("Binary path: {}", exe_path.display());
+        info!("Config path: {}", config_full_path.display());
+
+        if InitSystem::current() == InitSystem::RcD {
+            return rcd::install(&exe_path, &config_full_path);
+        }
+
+        std::fs::write(UNIT_PATH, unit_file(&exe_path, &config_full_path))
+            .with_context(|| format!("Failed to write {} (run as root)", UNIT_PATH))?;
+
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", SERVICE_NAME])?;
+
+        info!("✓ Service installed successfully");
+        info!("  Config: {}", config_full_path.display());
+        info!("  Start:  systemctl start {}", SERVICE_NAME);
+        info!("  Stop:   systemctl stop {}", SERVICE_NAME);
+        info!("  Status: systemctl status {}", SERVICE_NAME);
+        info!("  Logs:   journalctl -u {}", SERVICE_NAME);
+        Ok(())
+    }
+
+    /// Stop, disable and remove the systemd unit or rc.d script
+    pub fn uninstall() -> Result<()> {
+        info!("Uninstalling service: {}", SERVICE_NAME);
+
+        if InitSystem::current() == InitSystem::RcD {
+            rcd::uninstall()?;
+            info!("✓ Service uninstalled successfully");
+            return Ok(());
+        }
+
+        if let Err(e) = systemctl(&["disable", "--now", SERVICE_NAME]) {
+            warn!("Failed to disable service: {}", e);
+        }
+
+        match std::fs::remove_file(UNIT_PATH) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!("KeepHive service is not installed"),
+            Err(e) => return Err(e).with_context(|| format!("Failed to remove {}", UNIT_PATH)),
+        }
+
+        systemctl(&["daemon-reload"])?;
+
+        info!("✓ Service uninstalled successfully");
+        Ok(())
+    }
+
+    /// Start the service
+    pub fn start() -> Result<()> {
+        info!("Starting KeepHive service...");
+        match InitSystem::current() {
+            InitSystem::Systemd => systemctl(&["start", SERVICE_NAME])?,
+            InitSystem::RcD => rcd::start()?,
+        }
+        info!("✓ Service started");
+        Ok(())
+    }
+
+    /// Stop the service
+    pub fn stop() -> Result<()> {
+        info!("Stopping KeepHive service...");
+        match InitSystem::current() {
+            InitSystem::Systemd => systemctl(&["stop", SERVICE_NAME])?,
+            InitSystem::RcD => rcd::stop()?,
+        }
+        info!("✓ Service stopped");
+        Ok(())
+    }
+
+    /// Replace the installed service binary with a new one and restart the service
+    ///
+    /// The new binary is staged next to the installed one and renamed over it, which
+    /// is atomic on one filesystem. Config and state are left untouched.
+    pub fn upgrade(new_binary: Option<PathBuf>) -> Result<()> {
+        let new_binary = match new_binary {
+            Some(path) => path,
+            None => std::env::current_exe().context("Failed to get executable path")?,
+        };
+        let new_binary = std::fs::canonicalize(&new_binary)
+            .with_context(|| format!("New binary not found: {}", new_binary.display()))?;
+
+        let installed = Self::installed_binary_path()?;
+        let installed = std::fs::canonicalize(&installed).unwrap_or(installed);
+
+        if new_binary == installed {
+            bail!(
+                "The installed service binary is the one running this command ({}). \
+                 Run --upgrade-service from the new keephive binary or pass its path.",
+                installed.display()
+            );
+        }
+
+        info!("Upgrading KeepHive service");
+        info!("  Installed: {}", installed.display());
+        info!("  New:       {}", new_binary.display());
+
+        let staged = installed.with_extension("new");
+        std::fs::copy(&new_binary, &staged)
+            .with_context(|| format!("Failed to stage new binary at {}", staged.display()))?;
+
+        Self::stop()?;
+
+        if let Err(e) = std::fs::rename(&staged, &installed) {
+            let _ = std::fs::remove_file(&staged);
+            Self::start()?;
+            return Err(e).context("Failed to move new binary into place");
+        }
+
+        Self::start()?;
+        info!("✓ Service upgraded successfully");
+        Ok(())
+    }
+
+    /// Config path recorded in the installed unit or script
+    pub fn installed_config_path() -> Option<PathBuf> {
+        Self::installed_paths().map(|(_, config)| config)
+    }
+
+    fn installed_binary_path() -> Result<PathBuf> {
+        Self::installed_paths()
+            .map(|(binary, _)| binary)
+            .context("KeepHive service is not installed")
+    }
+
+    fn installed_paths() -> Option<(PathBuf, PathBuf)> {
+        match InitSystem::current() {
+            InitSystem::Systemd => {
+                let unit = std::fs::read_to_string(UNIT_PATH).ok()?;
+                parse_exec_start(&unit)
+            }
+            InitSystem::RcD => rcd::installed_paths(),
+        }
+    }
+}
+
+/// Unit running the daemon in the foreground; relative paths in the config resolve
+/// against the config's folder, as in the Windows service
+fn unit_file(exe_path: &Path, config_path: &Path) -> String {
+    let working_directory = config_path.parent().unwrap_or(Path::new("/"));
+
+    format!(
+        "[Unit]\n\
+         Description=KeepHive Backup Service\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         ExecStart={} --service {}\n\
+         WorkingDirectory={}\n\
+         Restart=on-failure\n\
+         TimeoutStopSec=120\n\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+        quote(exe_path),
+        quote(config_path),
+        working_directory.display().to_string().replace('%', "%%"),
+    )
+}
+
+/// Path as a double-quoted systemd command line word
+fn quote(path: &Path) -> String {
+    let escaped = path.display().to_string()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+/// Binary and config path from the `ExecStart=` line written by `unit_file`
+fn parse_exec_start(unit: &str) -> Option<(PathBuf, PathBuf)> {
+    let line = unit.lines().find_map(|line| line.strip_prefix("ExecStart="))?;
+
+    let mut words = Vec::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut word = String::new();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => word.push(chars.next()?),
+                c => word.push(c),
+            }
+        }
+        words.push(word.replace("%%", "%"));
+    }
+
+    match words.as_slice() {
+        [binary, config] => Some((PathBuf::from(binary), PathBuf::from(config))),
+        _ => None,
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .args(args)
+        .output()
+        .context("Failed to run systemctl")?;
+
+    if !output.status.success() {
+        bail!("systemctl {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_round_trips_binary_and_config() {
+        let exe = Path::new("/opt/keep hive/keephive");
+        let config = Path::new("/etc/keephive/100%\"odd\".json");
+
+        let unit = unit_file(exe, config);
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("WorkingDirectory=/etc/keephive\n"));
+
+        assert_eq!(parse_exec_start(&unit), Some((exe.to_path_buf(), config.to_path_buf())));
+        assert_eq!(parse_exec_start("[Service]\nExecStart=/usr/bin/other\n"), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! Security descriptors for folders and files only the service and administrators may
+//! open, such as staged registry hives, dumps and stored secrets.
+
+use anyhow::{Context, Result};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{LocalFree, HLOCAL};
+use windows::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows::Win32::Security::{PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES};
+
+/// SYSTEM, administrators and the account that creates the object: full access, also to
+/// everything created inside; no other entries, and none inherited from the parent
+pub const PRIVATE_SDDL: &str = "D:P(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)(A;OICI;FA;;;OW)";
+
+/// Call `create` with security attributes holding the descriptor written as `sddl`
+pub fn with_security<T>(sddl: &str, create: impl FnOnce(&mut SECURITY_ATTRIBUTES) -> T) -> Result<T> {
+    let sddl: Vec<u16> = sddl.encode_utf16().chain(std::iter::once(0)).collect();
+
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+    unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            PCWSTR(sddl.as_ptr()),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        ).context("Failed to build security descriptor")?;
+    }
+
+    let mut attributes = SECURITY_ATTRIBUTES {
+        nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: descriptor.0,
+        bInheritHandle: false.into(),
+    };
+    let created = create(&mut attributes);
+
+    unsafe {
+        LocalFree(Some(HLOCAL(descriptor.0)));
+    }
+
+    Ok(created)
+}
+
+/// Create the folder `path`, failing if anything already exists there, readable only as
+/// `PRIVATE_SDDL` allows
+pub fn create_private_dir(path: &std::path::Path) -> Result<()> {
+    use windows::Win32::Storage::FileSystem::CreateDirectoryW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide_nul();
+    with_security(PRIVATE_SDDL, |attributes| unsafe {
+        CreateDirectoryW(PCWSTR(wide.as_ptr()), Some(attributes as *const SECURITY_ATTRIBUTES))
+    })?
+    .with_context(|| format!("Failed to create private folder {}", path.display()))
+}
+
+/// Create the file `path` with `contents`, failing if anything already exists there,
+/// readable only as `sddl` allows
+pub fn create_private_file(path: &std::path::Path, contents: &[u8], sddl: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::windows::io::FromRawHandle;
+    use windows::Win32::Storage::FileSystem::{
+        CreateFileW, CREATE_NEW, FILE_ATTRIBUTE_NORMAL, FILE_GENERIC_WRITE, FILE_SHARE_NONE,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide_nul();
+    let handle = with_security(sddl, |attributes| unsafe {
+        CreateFileW(
+            PCWSTR(wide.as_ptr()),
+            FILE_GENERIC_WRITE.0,
+            FILE_SHARE_NONE,
+            Some(attributes as *const SECURITY_ATTRIBUTES),
+            CREATE_NEW,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )
+    })?
+    .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    let mut file = unsafe { std::fs::File::from_raw_handle(handle.0) };
+    file.write_all(contents)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+trait EncodeWideNul {
+    fn encode_wide_nul(&self) -> Vec<u16>;
+}
+
+impl EncodeWideNul for std::ffi::OsStr {
+    fn encode_wide_nul(&self) -> Vec<u16> {
+        use std::os::windows::ffi::OsStrExt;
+        self.encode_wide().chain(std::iter::once(0)).collect()
+    }
+}
@@ -0,0 +1,16 @@
+use windows::Win32::System::SystemInformation::IMAGE_FILE_MACHINE;
+use windows::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+/// `IMAGE_FILE_MACHINE_*` value of the machine's own architecture.
+///
+/// `IsWow64Process2` reports the host even to processes under x64 emulation on ARM64,
+/// where `GetNativeSystemInfo` would return the emulated architecture.
+pub fn native_machine() -> Option<u16> {
+    let mut process = IMAGE_FILE_MACHINE(0);
+    let mut native = IMAGE_FILE_MACHINE(0);
+
+    // SAFETY: both out pointers are valid for the call
+    unsafe { IsWow64Process2(GetCurrentProcess(), &mut process, Some(&mut native)) }.ok()?;
+
+    Some(native.0)
+}
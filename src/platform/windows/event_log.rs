@@ -0,0 +1,27 @@
+//! Alerts written to the Windows Application event log, where administrators and
+//! monitoring tools look for problems of services that run unattended.
+
+use anyhow::{bail, Context, Result};
+use tokio::process::Command;
+
+/// Source the entries are filed under
+const EVENT_SOURCE: &str = "KeepHive";
+
+/// Event ID of KeepHive alerts (`eventcreate` accepts 1 to 1000)
+const ALERT_EVENT_ID: &str = "100";
+
+/// Write `message` as a warning to the Application log. `eventcreate` registers the
+/// source on first use, which needs administrator rights as the service has them.
+pub async fn report_warning(message: &str) -> Result<()> {
+    let output = Command::new("eventcreate")
+        .args(["/L", "APPLICATION", "/T", "WARNING", "/SO", EVENT_SOURCE, "/ID", ALERT_EVENT_ID, "/D", message])
+        .output()
+        .await
+        .context("Failed to execute eventcreate")?;
+
+    if !output.status.success() {
+        let message = if output.stderr.is_empty() { &output.stdout } else { &output.stderr };
+        bail!("eventcreate failed: {}", String::from_utf8_lossy(message).trim());
+    }
+    Ok(())
+}
@@ -1,41 +1,85 @@
 use anyhow::{Context, Result};
+use std::io::{self, Read};
+use std::os::windows::fs::OpenOptionsExt;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
 use tracing::debug;
 
-/// Buffer size for streaming copy (1MB)
-const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+use crate::core::{BandwidthLimiter, CopyError, Crc32};
 
-pub async fn copy_file(src: &Path, dst: &Path) -> Result<u64> {
+const FILE_SHARE_ALL: u32 = 0x1 | 0x2 | 0x4; // FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE
+const GENERIC_READ: u32 = 0x8000_0000;
+const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+const ERROR_ACCESS_DENIED: i32 = 5;
+const ERROR_SHARING_VIOLATION: i32 = 32;
+const ERROR_LOCK_VIOLATION: i32 = 33;
+
+/// `WIN32_STREAM_ID` stream types: file contents, and contents of a sparse file
+const BACKUP_DATA: u32 = 1;
+const BACKUP_SPARSE_BLOCK: u32 = 9;
+
+/// `WIN32_STREAM_ID` without the stream name that follows it
+const STREAM_HEADER_SIZE: usize = 20;
+
+/// Copy `src` to `dst`, feeding the bytes read to `crc` when given. With `backup_read`,
+/// a file that cannot be opened is read through `BackupRead` instead of being skipped.
+pub async fn copy_file(
+    src: &Path,
+    dst: &Path,
+    limiter: &BandwidthLimiter,
+    buffer_size: usize,
+    mut crc: Option<&mut Crc32>,
+    backup_read: bool,
+) -> Result<u64> {
     debug!("Copying file: {:?} -> {:?}", src, dst);
 
-    let mut src_file = tokio::fs::File::open(src).await
-        .context("Failed to open source file")?;
+    // Running executables and DLLs are open for reading and can be shared
+    let src_file = std::fs::OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_ALL)
+        .open(src);
+
+    let mut src_file = match src_file {
+        Ok(file) => tokio::fs::File::from_std(file),
+        Err(e) if backup_read && matches!(e.raw_os_error(), Some(ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)) => {
+            debug!("Plain open failed ({}), reading {:?} with BackupRead", e, src);
+            return copy_file_backup_read(src, dst, limiter, buffer_size, crc).await;
+        }
+        Err(e) => return Err(CopyError::source_side(e).into()),
+    };
 
     let mut dst_file = tokio::fs::File::create(dst).await
-        .context("Failed to create destination file")?;
+        .map_err(CopyError::target_side)?;
 
-    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+    let mut buffer = vec![0u8; buffer_size];
     let mut total_bytes = 0u64;
 
     loop {
         let bytes_read = src_file.read(&mut buffer).await
-            .context("Failed to read from source")?;
+            .map_err(CopyError::source_side)?;
 
         if bytes_read == 0 {
             break;
         }
 
+        if let Some(crc) = crc.as_deref_mut() {
+            crc.update(&buffer[..bytes_read]);
+        }
+
         dst_file.write_all(&buffer[..bytes_read]).await
-            .context("Failed to write to destination")?;
+            .map_err(CopyError::target_side)?;
 
         total_bytes += bytes_read as u64;
+        limiter.consume(bytes_read as u64).await;
     }
 
     // Sync destination file
     dst_file.sync_all().await
-        .context("Failed to sync destination file")?;
+        .map_err(CopyError::target_side)?;
 
     // Copy metadata (timestamps)
     copy_metadata(src, dst).await?;
@@ -43,6 +87,152 @@ pub async fn copy_file(src: &Path, dst: &Path) -> Result<u64> {
     Ok(total_bytes)
 }
 
+/// Copy the contents of `src` as read by `BackupRead`, which opens files with backup
+/// semantics: access checks are bypassed with the backup privilege
+async fn copy_file_backup_read(
+    src: &Path,
+    dst: &Path,
+    limiter: &BandwidthLimiter,
+    buffer_size: usize,
+    mut crc: Option<&mut Crc32>,
+) -> Result<u64> {
+    let (sender, mut chunks) = mpsc::channel::<io::Result<Vec<u8>>>(4);
+    let path = src.to_path_buf();
+    let reader = tokio::task::spawn_blocking(move || {
+        if let Err(e) = read_data_stream(&path, buffer_size, &sender) {
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+
+    let mut dst_file = tokio::fs::File::create(dst).await
+        .map_err(CopyError::target_side)?;
+    let mut total_bytes = 0u64;
+
+    while let Some(chunk) = chunks.recv().await {
+        let chunk = chunk.map_err(CopyError::source_side)?;
+
+        if let Some(crc) = crc.as_deref_mut() {
+            crc.update(&chunk);
+        }
+
+        dst_file.write_all(&chunk).await
+            .map_err(CopyError::target_side)?;
+
+        total_bytes += chunk.len() as u64;
+        limiter.consume(chunk.len() as u64).await;
+    }
+    reader.await?;
+
+    dst_file.sync_all().await
+        .map_err(CopyError::target_side)?;
+
+    copy_metadata(src, dst).await?;
+
+    Ok(total_bytes)
+}
+
+/// Send the file contents from the `BackupRead` stream of `path` in chunks, skipping
+/// alternate data streams and other stream types
+fn read_data_stream(path: &Path, buffer_size: usize, chunks: &mpsc::Sender<io::Result<Vec<u8>>>) -> io::Result<()> {
+    enable_backup_privilege();
+
+    let file = std::fs::OpenOptions::new()
+        .access_mode(GENERIC_READ)
+        .share_mode(FILE_SHARE_ALL)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?;
+    let mut stream = BackupStream { file, context: std::ptr::null_mut() };
+
+    loop {
+        let mut header = [0u8; STREAM_HEADER_SIZE];
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let stream_id = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let size = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let name_size = u32::from_le_bytes(header[16..20].try_into().unwrap());
+
+        io::copy(&mut (&mut stream).take(name_size as u64), &mut io::sink())?;
+
+        match stream_id {
+            BACKUP_DATA => {
+                let mut data = (&mut stream).take(size);
+                loop {
+                    let mut chunk = vec![0u8; buffer_size];
+                    let read = data.read(&mut chunk)?;
+                    if read == 0 {
+                        break;
+                    }
+                    chunk.truncate(read);
+                    if chunks.blocking_send(Ok(chunk)).is_err() {
+                        // The copy gave up on the target side
+                        return Ok(());
+                    }
+                }
+            }
+            BACKUP_SPARSE_BLOCK => {
+                return Err(io::Error::other("sparse files cannot be copied through BackupRead"));
+            }
+            _ => {
+                io::copy(&mut (&mut stream).take(size), &mut io::sink())?;
+            }
+        }
+    }
+}
+
+/// Raw `BackupRead` output of a file opened with backup semantics
+struct BackupStream {
+    file: std::fs::File,
+    context: *mut std::ffi::c_void,
+}
+
+impl Read for BackupStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::Storage::FileSystem::BackupRead;
+
+        let len = buf.len().min(u32::MAX as usize);
+        let mut read = 0u32;
+        unsafe {
+            BackupRead(HANDLE(self.file.as_raw_handle()), &mut buf[..len], &mut read, false, false, &mut self.context)?;
+        }
+        Ok(read as usize)
+    }
+}
+
+impl Drop for BackupStream {
+    fn drop(&mut self) {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::Storage::FileSystem::BackupRead;
+
+        if self.context.is_null() {
+            return;
+        }
+
+        // Aborting releases the context BackupRead allocated
+        let mut read = 0u32;
+        unsafe {
+            let _ = BackupRead(HANDLE(self.file.as_raw_handle()), &mut [], &mut read, true, false, &mut self.context);
+        }
+    }
+}
+
+/// The service holds the backup privilege disabled; enable it once per process
+fn enable_backup_privilege() {
+    static ENABLED: OnceLock<()> = OnceLock::new();
+
+    ENABLED.get_or_init(|| {
+        if let Err(e) = crate::platform::windows::system_state::enable_privilege(windows::core::w!("SeBackupPrivilege")) {
+            debug!("Reading without the backup privilege: {:#}", e);
+        }
+    });
+}
+
 async fn copy_metadata(src: &Path, dst: &Path) -> Result<()> {
     let metadata = tokio::fs::metadata(src).await?;
 
@@ -93,4 +283,39 @@ pub fn get_disk_free_space(path: &Path) -> Result<u64> {
     }
 
     Ok(free_bytes_available)
-}
\ No newline at end of file
+}
+
+/// Schedule a file move (or deletion when `dst` is None) for the next reboot
+pub fn move_file_on_reboot(src: &Path, dst: Option<&Path>) -> Result<()> {
+    use windows::Win32::Storage::FileSystem::{
+        MoveFileExW, MOVEFILE_DELAY_UNTIL_REBOOT, MOVEFILE_REPLACE_EXISTING,
+    };
+    use windows::core::PCWSTR;
+    use std::os::windows::ffi::OsStrExt;
+
+    let src_wide: Vec<u16> = src.as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let dst_wide: Option<Vec<u16>> = dst.map(|d| d.as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect());
+
+    let dst_ptr = match &dst_wide {
+        Some(wide) => PCWSTR(wide.as_ptr()),
+        None => PCWSTR::null(),
+    };
+
+    unsafe {
+        MoveFileExW(
+            PCWSTR(src_wide.as_ptr()),
+            dst_ptr,
+            MOVEFILE_DELAY_UNTIL_REBOOT | MOVEFILE_REPLACE_EXISTING,
+        ).context("Failed to schedule file move on reboot")?;
+    }
+
+    debug!("Scheduled move on reboot: {:?} -> {:?}", src, dst);
+    Ok(())
+}
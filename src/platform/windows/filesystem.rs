@@ -1,3 +1,4 @@
+use crate::core::{BandwidthLimiter, Crc32};
 use crate::platform::traits::{FileSystem, PathNormalizer};
 use crate::platform::windows::file_ops;
 use crate::platform::windows::long_path::WindowsPathNormalizer;
@@ -15,12 +16,34 @@ impl WindowsFileSystem {
             normalizer: WindowsPathNormalizer,
         }
     }
+
+    /// Copy like [`FileSystem::copy_file`], reading files that cannot be opened
+    /// otherwise through `BackupRead` with the backup privilege
+    pub async fn copy_file_with_backup_read(
+        &self,
+        src: &Path,
+        dst: &Path,
+        limiter: &BandwidthLimiter,
+        buffer_size: usize,
+        crc: Option<&mut Crc32>,
+    ) -> Result<u64> {
+        let src = self.normalizer.normalize(src);
+        let dst = self.normalizer.normalize(dst);
+        file_ops::copy_file(&src, &dst, limiter, buffer_size, crc, true).await
+    }
 }
 
 impl FileSystem for WindowsFileSystem {
-    async fn copy_file(&self, src: &Path, dst: &Path) -> Result<u64> {
+    async fn copy_file(
+        &self,
+        src: &Path,
+        dst: &Path,
+        limiter: &BandwidthLimiter,
+        buffer_size: usize,
+        crc: Option<&mut Crc32>,
+    ) -> Result<u64> {
         let src = self.normalizer.normalize(src);
         let dst = self.normalizer.normalize(dst);
-        file_ops::copy_file(&src, &dst).await
+        file_ops::copy_file(&src, &dst, limiter, buffer_size, crc, false).await
     }
 }
\ No newline at end of file
@@ -0,0 +1,26 @@
+//! User folders looked up the way Explorer does, so folders redirected to OneDrive or
+//! moved by Group Policy are found wherever they are.
+
+use std::path::PathBuf;
+use windows::core::GUID;
+use windows::Win32::System::Com::CoTaskMemFree;
+use windows::Win32::UI::Shell::{
+    SHGetKnownFolderPath, FOLDERID_Desktop, FOLDERID_Documents, FOLDERID_Pictures, KF_FLAG_DEFAULT,
+};
+
+/// Current location of the user's Documents, Desktop or Pictures folder
+pub fn known_folder(name: &str) -> Option<PathBuf> {
+    let id: GUID = match name {
+        "Documents" => FOLDERID_Documents,
+        "Desktop" => FOLDERID_Desktop,
+        "Pictures" => FOLDERID_Pictures,
+        _ => return None,
+    };
+
+    unsafe {
+        let path = SHGetKnownFolderPath(&id, KF_FLAG_DEFAULT, None).ok()?;
+        let result = path.to_string().ok().map(PathBuf::from);
+        CoTaskMemFree(Some(path.0 as *const _));
+        result
+    }
+}
@@ -1,9 +1,23 @@
+pub mod acl;
+pub mod arch;
 pub mod constants;
+pub mod event_log;
 pub mod file_ops;
+pub mod known_folders;
 pub mod filesystem;
 pub mod long_path;
+pub mod mount;
+pub mod network;
+pub mod perf;
+pub mod registry;
+pub mod secrets;
 pub mod service;
 pub mod service_impl;
+pub mod shell;
+pub mod system_state;
+pub mod task;
+pub mod volume;
+pub mod vss;
 
 pub use constants::{is_reserved_name, WINDOWS_RESERVED_NAMES};
 pub use filesystem::WindowsFileSystem;
@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use std::os::windows::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use tracing::{info, warn};
+
+/// Normalize a drive argument ("x", "X:", "x:\") to "X:"
+pub fn parse_drive_letter(drive: &str) -> Result<String> {
+    let trimmed = drive.trim_end_matches(['\\', '/']).trim_end_matches(':');
+
+    match trimmed.chars().collect::<Vec<_>>()[..] {
+        [letter] if letter.is_ascii_alphabetic() => Ok(format!("{}:", letter.to_ascii_uppercase())),
+        _ => bail!("Invalid drive letter: {} (expected e.g. R:)", drive),
+    }
+}
+
+/// Hidden local share a drive letter is mapped through, e.g. `KeepHive-R$`
+fn share_name(drive: &str) -> String {
+    format!("KeepHive-{}$", drive.trim_end_matches(':'))
+}
+
+/// Map a backup directory to a drive letter, read-only.
+///
+/// The backup is shared on this machine with read access for the current user only,
+/// and the drive letter is connected to that share, so Explorer can browse the backup
+/// but nothing can be changed or deleted through the drive. Creating the share needs
+/// administrator rights.
+pub fn mount_backup(drive: &str, backup_path: &Path) -> Result<()> {
+    let drive = parse_drive_letter(drive)?;
+
+    if Path::new(&format!("{}\\", drive)).exists() {
+        bail!("Drive {} is already in use", drive);
+    }
+
+    let backup_path = dunce::canonicalize(backup_path)
+        .with_context(|| format!("Backup not found: {}", backup_path.display()))?;
+    let user = match (std::env::var("USERDOMAIN"), std::env::var("USERNAME")) {
+        (Ok(domain), Ok(name)) => format!("{}\\{}", domain, name),
+        _ => bail!("Cannot tell the current user, which the backup is shared with"),
+    };
+
+    let share = share_name(&drive);
+    net(Command::new("net")
+        .arg("share")
+        .raw_arg(format!("{}=\"{}\"", share, backup_path.display()))
+        .raw_arg(format!("\"/GRANT:{},READ\"", user))
+        .arg("/CACHE:None"))
+        .with_context(|| format!("Failed to share {} read-only (run as administrator)", backup_path.display()))?;
+
+    let remote = format!("\\\\localhost\\{}", share);
+    let connected = net(Command::new("net").args(["use", drive.as_str(), remote.as_str(), "/persistent:no"]));
+    if let Err(e) = connected {
+        remove_share(&share);
+        return Err(e).with_context(|| format!("Failed to map {} to {}", drive, backup_path.display()));
+    }
+
+    info!("Mapped {} to {} (read-only)", drive, backup_path.display());
+    Ok(())
+}
+
+/// Remove a drive mapping created by `mount_backup`, and the share behind it
+pub fn unmount_backup(drive: &str) -> Result<()> {
+    let drive = parse_drive_letter(drive)?;
+
+    net(Command::new("net").args(["use", drive.as_str(), "/delete", "/y"]))
+        .with_context(|| format!("Failed to remove drive mapping {}", drive))?;
+    remove_share(&share_name(&drive));
+
+    info!("Removed drive mapping {}", drive);
+    Ok(())
+}
+
+/// Remove a share of `mount_backup`; one left behind only keeps the backup readable
+fn remove_share(share: &str) {
+    if let Err(e) = net(Command::new("net").args(["share", share, "/delete", "/y"])) {
+        warn!("Failed to remove share {}: {:#}", share, e);
+    }
+}
+
+/// Run a `net` command, failing with its message
+fn net(command: &mut Command) -> Result<()> {
+    let output = command.output().context("Failed to execute net")?;
+
+    if !output.status.success() {
+        let message = if output.stderr.is_empty() { &output.stdout } else { &output.stderr };
+        bail!("net failed: {}", String::from_utf8_lossy(message).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_drive_letter() {
+        assert_eq!(parse_drive_letter("r").unwrap(), "R:");
+        assert_eq!(parse_drive_letter("R:").unwrap(), "R:");
+        assert_eq!(parse_drive_letter("r:\\").unwrap(), "R:");
+        assert!(parse_drive_letter("RR:").is_err());
+        assert!(parse_drive_letter("1:").is_err());
+        assert!(parse_drive_letter("").is_err());
+    }
+
+    #[test]
+    fn test_share_name_is_hidden() {
+        assert_eq!(share_name("R:"), "KeepHive-R$");
+    }
+}
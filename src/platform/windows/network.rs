@@ -0,0 +1,34 @@
+use anyhow::{Context, Result};
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::NetworkManagement::WNet::{WNetAddConnection2W, CONNECT_TEMPORARY, NETRESOURCEW, RESOURCETYPE_DISK};
+
+/// Connect to `share` (`\\server\share`) without a drive letter, so its paths resolve
+/// again after the server came back or the session was dropped. Without an account the
+/// service account's own credentials are used.
+pub fn connect_share(share: &Path, account: Option<(&str, &str)>) -> Result<()> {
+    let mut remote = to_wide(share.as_os_str());
+    let resource = NETRESOURCEW {
+        dwType: RESOURCETYPE_DISK,
+        lpRemoteName: PWSTR(remote.as_mut_ptr()),
+        ..Default::default()
+    };
+
+    let (username, password) = match account {
+        Some((username, password)) => (Some(to_wide(OsStr::new(username))), Some(to_wide(OsStr::new(password)))),
+        None => (None, None),
+    };
+    let as_pcwstr = |value: &Option<Vec<u16>>| value.as_ref().map_or(PCWSTR::null(), |v| PCWSTR(v.as_ptr()));
+
+    unsafe {
+        WNetAddConnection2W(&resource, as_pcwstr(&password), as_pcwstr(&username), CONNECT_TEMPORARY)
+            .ok()
+            .with_context(|| format!("Failed to connect to {}", share.display()))
+    }
+}
+
+fn to_wide(value: &OsStr) -> Vec<u16> {
+    value.encode_wide().chain(std::iter::once(0)).collect()
+}
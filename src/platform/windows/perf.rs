@@ -0,0 +1,290 @@
+//! Windows performance counters (PerfLib V2), so perfmon and monitoring tools can chart
+//! KeepHive without the HTTP API. `--install` registers the counter set from a manifest
+//! with `lodctr`; the running service is the provider and refreshes the values every second.
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use windows::core::{w, GUID};
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::System::Performance::{
+    PerfCreateInstance, PerfProviderHandle, PerfSetCounterSetInfo, PerfSetULongLongCounterValue,
+    PerfStartProvider, PerfStopProvider, PERF_COUNTERSET_INFO, PERF_COUNTERSET_INSTANCE, PERF_COUNTER_INFO,
+};
+
+use crate::observability::{CounterValues, DaemonMetrics};
+use crate::state::StateManager;
+
+/// Provider and counter set IDs, also written to the manifest
+const PROVIDER_GUID: GUID = GUID::from_u128(0x6b1f0c2e_8d4a_4f7e_9a35_2c9e7d41b803);
+const COUNTERSET_GUID: GUID = GUID::from_u128(0x0d7c5a91_3e62_4b0f_8c17_e45a9f26d1c4);
+
+/// Manifest registered with `lodctr`, next to the default config
+const MANIFEST_PATH: &str = r"C:\ProgramData\KeepHive\keephive-counters.man";
+
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Values from winperf.h / perflib.h
+const PERF_COUNTERSET_SINGLE_INSTANCE: u32 = 0;
+const PERF_COUNTER_LARGE_RAWCOUNT: u32 = 0x0001_0100;
+const PERF_COUNTER_BULK_COUNT: u32 = 0x1041_0500;
+const PERF_DETAIL_NOVICE: u32 = 100;
+
+/// A published counter: ID, type, manifest type, name and description
+struct Counter {
+    id: u32,
+    kind: u32,
+    manifest_type: &'static str,
+    name: &'static str,
+    description: &'static str,
+}
+
+const JOBS_RUNNING: u32 = 1;
+const JOBS_QUEUED: u32 = 2;
+const JOBS_FAILED: u32 = 3;
+const BYTES_COPIED: u32 = 4;
+const LAST_SUCCESS_AGE: u32 = 5;
+
+const COUNTERS: [Counter; 5] = [
+    Counter {
+        id: JOBS_RUNNING,
+        kind: PERF_COUNTER_LARGE_RAWCOUNT,
+        manifest_type: "perf_counter_large_rawcount",
+        name: "Jobs Running",
+        description: "Backup jobs running now",
+    },
+    Counter {
+        id: JOBS_QUEUED,
+        kind: PERF_COUNTER_LARGE_RAWCOUNT,
+        manifest_type: "perf_counter_large_rawcount",
+        name: "Jobs Queued",
+        description: "Backup jobs waiting for a free slot",
+    },
+    Counter {
+        id: JOBS_FAILED,
+        kind: PERF_COUNTER_LARGE_RAWCOUNT,
+        manifest_type: "perf_counter_large_rawcount",
+        name: "Jobs Failed",
+        description: "Configured jobs whose last run failed",
+    },
+    Counter {
+        id: BYTES_COPIED,
+        kind: PERF_COUNTER_BULK_COUNT,
+        manifest_type: "perf_counter_bulk_count",
+        name: "Bytes Copied/sec",
+        description: "Rate at which backups write to their targets",
+    },
+    Counter {
+        id: LAST_SUCCESS_AGE,
+        kind: PERF_COUNTER_LARGE_RAWCOUNT,
+        manifest_type: "perf_counter_large_rawcount",
+        name: "Seconds Since Last Success",
+        description: "Age of the oldest last successful backup among configured jobs",
+    },
+];
+
+/// Counter set template passed to `PerfSetCounterSetInfo`: the set, then its counters
+#[repr(C)]
+struct CounterSetTemplate {
+    info: PERF_COUNTERSET_INFO,
+    counters: [PERF_COUNTER_INFO; COUNTERS.len()],
+}
+
+/// The service's registration as counter provider, with its single instance
+pub struct PerfProvider {
+    handle: PerfProviderHandle,
+    instance: *mut PERF_COUNTERSET_INSTANCE,
+}
+
+// SAFETY: the instance block belongs to PerfLib until the provider stops, and the
+// PerfLib calls made through it may come from any thread
+unsafe impl Send for PerfProvider {}
+
+impl PerfProvider {
+    pub fn start() -> Result<Self> {
+        let mut handle = PerfProviderHandle::default();
+
+        // SAFETY: the GUID and the out handle are valid for the call; no callback is used
+        let status = unsafe { PerfStartProvider(&PROVIDER_GUID, None, &mut handle) };
+        if status != 0 {
+            bail!("PerfStartProvider failed: {}", std::io::Error::from_raw_os_error(status as i32));
+        }
+
+        let mut template = CounterSetTemplate {
+            info: PERF_COUNTERSET_INFO {
+                CounterSetGuid: COUNTERSET_GUID,
+                ProviderGuid: PROVIDER_GUID,
+                NumCounters: COUNTERS.len() as u32,
+                InstanceType: PERF_COUNTERSET_SINGLE_INSTANCE,
+            },
+            counters: std::array::from_fn(|i| PERF_COUNTER_INFO {
+                CounterId: COUNTERS[i].id,
+                Type: COUNTERS[i].kind,
+                Attrib: 0,
+                Size: std::mem::size_of::<u64>() as u32,
+                DetailLevel: PERF_DETAIL_NOVICE,
+                Scale: 0,
+                Offset: (i * std::mem::size_of::<u64>()) as u32,
+            }),
+        };
+
+        // SAFETY: the template is a counter set followed by its counters, as PerfLib expects,
+        // and lives for the call; PerfLib copies it
+        let status = unsafe {
+            PerfSetCounterSetInfo(
+                HANDLE(handle.0),
+                &mut template.info,
+                std::mem::size_of::<CounterSetTemplate>() as u32,
+            )
+        };
+        if status != 0 {
+            // SAFETY: the provider was started above
+            unsafe { PerfStopProvider(handle) };
+            bail!("PerfSetCounterSetInfo failed: {}", std::io::Error::from_raw_os_error(status as i32));
+        }
+
+        // SAFETY: the counter set was registered above; the name is a static string
+        let instance = unsafe { PerfCreateInstance(handle, &COUNTERSET_GUID, w!("KeepHive"), 0) };
+        if instance.is_null() {
+            let error = std::io::Error::last_os_error();
+            // SAFETY: the provider was started above
+            unsafe { PerfStopProvider(handle) };
+            bail!("PerfCreateInstance failed: {}", error);
+        }
+
+        Ok(Self { handle, instance })
+    }
+
+    fn set(&self, counter: u32, value: u64) {
+        // SAFETY: the instance stays valid until the provider is stopped in drop
+        unsafe { PerfSetULongLongCounterValue(HANDLE(self.handle.0), self.instance, counter, value) };
+    }
+
+    pub fn publish(&self, values: &CounterValues) {
+        self.set(JOBS_RUNNING, values.jobs_running);
+        self.set(JOBS_QUEUED, values.jobs_queued);
+        self.set(JOBS_FAILED, values.jobs_failed);
+        self.set(BYTES_COPIED, values.bytes_copied);
+        self.set(LAST_SUCCESS_AGE, values.last_success_age_secs);
+    }
+}
+
+impl Drop for PerfProvider {
+    fn drop(&mut self) {
+        // SAFETY: the provider was started in `start`; stopping it frees the instance
+        unsafe { PerfStopProvider(self.handle) };
+    }
+}
+
+/// Refresh the counters every second until cancelled. Counters that were never
+/// registered (service not installed with `--install`) stay invisible, so failing to
+/// start the provider is not an error.
+pub async fn publish_counters(
+    metrics: Arc<DaemonMetrics>,
+    state_manager: Arc<StateManager>,
+    cancellation: CancellationToken,
+) {
+    let provider = match PerfProvider::start() {
+        Ok(provider) => provider,
+        Err(e) => {
+            debug!("Performance counters not published: {:#}", e);
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = cancellation.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        let values = {
+            let state = state_manager.read().await;
+            CounterValues::collect(&metrics.snapshot(), &state, Utc::now())
+        };
+        provider.publish(&values);
+    }
+}
+
+/// Register the counter set for perfmon; `exe_path` is the installed binary
+pub fn register_counters(exe_path: &Path) -> Result<()> {
+    let manifest_path = PathBuf::from(MANIFEST_PATH);
+    if let Some(parent) = manifest_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let exe_name = exe_path.file_name().context("Executable path has no file name")?;
+    std::fs::write(&manifest_path, manifest(&exe_name.to_string_lossy()))
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+
+    let exe_dir = exe_path.parent().context("Executable path has no parent")?;
+    let output = Command::new("lodctr")
+        .arg(format!("/m:{}", manifest_path.display()))
+        .arg(exe_dir)
+        .output()
+        .context("Failed to execute lodctr")?;
+
+    if !output.status.success() {
+        bail!("lodctr failed: {}", String::from_utf8_lossy(&output.stdout).trim());
+    }
+
+    info!("Performance counters registered (KeepHive counter set)");
+    Ok(())
+}
+
+/// Remove the counter set registered by `register_counters`
+pub fn unregister_counters() -> Result<()> {
+    let manifest_path = PathBuf::from(MANIFEST_PATH);
+    if !manifest_path.exists() {
+        return Ok(());
+    }
+
+    let output = Command::new("unlodctr")
+        .arg(format!("/m:{}", manifest_path.display()))
+        .output()
+        .context("Failed to execute unlodctr")?;
+
+    if !output.status.success() {
+        bail!("unlodctr failed: {}", String::from_utf8_lossy(&output.stdout).trim());
+    }
+
+    std::fs::remove_file(&manifest_path).ok();
+    Ok(())
+}
+
+fn guid_string(guid: &GUID) -> String {
+    format!("{{{:?}}}", guid)
+}
+
+/// Instrumentation manifest describing the counter set
+fn manifest(exe_name: &str) -> String {
+    let counters: String = COUNTERS.iter().map(|c| format!(
+        "          <counter id=\"{}\" uri=\"KeepHive.Backup.{}\" name=\"{}\" description=\"{}\" type=\"{}\" detailLevel=\"standard\"/>\n",
+        c.id, c.name.replace([' ', '/'], ""), c.name, c.description, c.manifest_type
+    )).collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<instrumentationManifest xmlns="http://schemas.microsoft.com/win/2004/08/events" xmlns:win="http://manifests.microsoft.com/win/2004/08/windows/events" xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <instrumentation>
+    <counters xmlns="http://schemas.microsoft.com/win/2005/12/counters" schemaVersion="2.0">
+      <provider callback="custom" applicationIdentity="{exe}" providerType="userMode" providerGuid="{provider}">
+        <counterSet guid="{counterset}" uri="KeepHive.Backup" name="KeepHive" description="KeepHive backup service" instances="single">
+{counters}        </counterSet>
+      </provider>
+    </counters>
+  </instrumentation>
+</instrumentationManifest>
+"#,
+        exe = exe_name,
+        provider = guid_string(&PROVIDER_GUID),
+        counterset = guid_string(&COUNTERSET_GUID),
+        counters = counters,
+    )
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use tracing::{debug, info};
+use windows_registry::LOCAL_MACHINE;
+
+use crate::config::policy::Policy;
+
+/// Registry key holding install-time service parameters
+pub const KEEPHIVE_REGISTRY_KEY: &str = r"SOFTWARE\KeepHive";
+
+/// Registry key Group Policy writes KeepHive settings to (see `policy/keephive.admx`)
+pub const POLICY_REGISTRY_KEY: &str = r"SOFTWARE\Policies\KeepHive";
+
+const CONFIG_PATH_VALUE: &str = "ConfigPath";
+const INSTANCE_NAME_VALUE: &str = "InstanceName";
+
+/// Service parameters recorded at install time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceRegistration {
+    /// Absolute path to the configuration file
+    pub config_path: PathBuf,
+    /// Name the service is registered under in the SCM
+    pub instance_name: String,
+}
+
+/// Store service parameters under HKLM\SOFTWARE\KeepHive
+pub fn write_service_registration(registration: &ServiceRegistration) -> Result<()> {
+    let key = LOCAL_MACHINE.create(KEEPHIVE_REGISTRY_KEY)
+        .context("Failed to create KeepHive registry key")?;
+
+    key.set_string(CONFIG_PATH_VALUE, registration.config_path.to_string_lossy())
+        .context("Failed to store config path in registry")?;
+    key.set_string(INSTANCE_NAME_VALUE, &registration.instance_name)
+        .context("Failed to store instance name in registry")?;
+
+    info!("Service parameters stored in HKLM\\{}", KEEPHIVE_REGISTRY_KEY);
+    Ok(())
+}
+
+/// Read service parameters (None if KeepHive was installed without registry support)
+pub fn read_service_registration() -> Result<Option<ServiceRegistration>> {
+    let key = match LOCAL_MACHINE.open(KEEPHIVE_REGISTRY_KEY) {
+        Ok(key) => key,
+        Err(e) => {
+            debug!("KeepHive registry key not available: {}", e);
+            return Ok(None);
+        }
+    };
+
+    let config_path = key.get_string(CONFIG_PATH_VALUE)
+        .context("Registry key is missing ConfigPath")?;
+    let instance_name = key.get_string(INSTANCE_NAME_VALUE)
+        .context("Registry key is missing InstanceName")?;
+
+    Ok(Some(ServiceRegistration {
+        config_path: PathBuf::from(config_path),
+        instance_name,
+    }))
+}
+
+/// Remove stored service parameters (ignores a missing key)
+pub fn remove_service_registration() -> Result<()> {
+    if LOCAL_MACHINE.open(KEEPHIVE_REGISTRY_KEY).is_err() {
+        return Ok(());
+    }
+
+    LOCAL_MACHINE.remove_tree(KEEPHIVE_REGISTRY_KEY)
+        .context("Failed to remove KeepHive registry key")?;
+
+    info!("Removed HKLM\\{}", KEEPHIVE_REGISTRY_KEY);
+    Ok(())
+}
+
+/// Settings under HKLM\SOFTWARE\Policies\KeepHive (empty if no policy is set)
+pub fn read_policy() -> Result<Policy> {
+    let key = match LOCAL_MACHINE.open(POLICY_REGISTRY_KEY) {
+        Ok(key) => key,
+        Err(e) => {
+            debug!("No KeepHive policy: {}", e);
+            return Ok(Policy::default());
+        }
+    };
+
+    // Values left "Not configured" in the policy editor are absent
+    let paths = |name: &str| -> Vec<PathBuf> {
+        key.get_multi_string(name)
+            .map(|values| values.into_iter().filter(|v| !v.is_empty()).map(PathBuf::from).collect())
+            .unwrap_or_default()
+    };
+
+    Ok(Policy {
+        retention_count: key.get_u32("RetentionCount").ok().map(|n| n as usize),
+        allowed_target_roots: paths("AllowedTargetRoots"),
+        replicas: paths("Replicas"),
+        manifest_checksums: key.get_u32("ManifestChecksums").ok().map(|n| n != 0),
+        lock_settings: key.get_u32("LockSettings").is_ok_and(|n| n != 0),
+    })
+}
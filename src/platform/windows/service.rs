@@ -1,8 +1,22 @@
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::Duration;
-use tracing::info;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::file_ops;
+use super::perf;
+use super::registry::{self, ServiceRegistration};
+use super::shell;
+
+/// Name the service is registered under in the SCM
+pub const SERVICE_NAME: &str = "KeepHive";
+
+/// SCM registry key holding the service's ImagePath
+const SERVICE_REGISTRY_KEY: &str = r"SYSTEM\CurrentControlSet\Services\KeepHive";
+
+/// How long to wait for the service to reach a requested state
+const SERVICE_STATE_TIMEOUT: Duration = Duration::from_secs(60);
 
 pub struct WindowsService;
 
@@ -12,7 +26,7 @@ impl WindowsService {
     }
 
     /// Install service in Windows SCM
-    pub fn install(config_path: Option<PathBuf>) -> Result<()> {
+    pub fn install(config_path: Option<PathBuf>, shell_integration: bool) -> Result<()> {
         let exe_path = std::env::current_exe()
             .context("Failed to get executable path")?;
 
@@ -27,8 +41,8 @@ impl WindowsService {
             PathBuf::from(r"C:\ProgramData\KeepHive\keephive_config.json")
         };
 
-        // Pass config path via binPath argument
-        let bin_path = format!("\"{}\" --service \"{}\"", exe_path.display(), config_full_path.display());
+        // Config path is discovered from the registry at service start
+        let bin_path = format!("\"{}\" --service", exe_path.display());
 
         info!("Installing Windows Service: KeepHive");
         info!("Binary path: {}", bin_path);
@@ -53,6 +67,15 @@ impl WindowsService {
             anyhow::bail!("Failed to create service: {}", error);
         }
 
+        // Record install parameters so the service can find its config
+        if let Err(e) = registry::write_service_registration(&ServiceRegistration {
+            config_path: config_full_path.clone(),
+            instance_name: SERVICE_NAME.to_string(),
+        }) {
+            let _ = Command::new("sc").args(&["delete", SERVICE_NAME]).output();
+            return Err(e).context("Failed to store service parameters, installation rolled back");
+        }
+
         // Set description
         let _ = Command::new("sc")
             .args(&[
@@ -62,6 +85,18 @@ impl WindowsService {
             ])
             .output();
 
+        // The service runs without perfmon counters if they cannot be registered
+        if let Err(e) = perf::register_counters(&exe_path) {
+            warn!("Failed to register performance counters: {}", e);
+        }
+
+        // Optional Explorer "Back up this folder now" verb
+        if shell_integration
+            && let Err(e) = shell::register_backup_now_verb(&exe_path)
+        {
+            warn!("Failed to register Explorer integration: {}", e);
+        }
+
         info!("✓ Service installed successfully");
         info!("  Config: {}", config_full_path.display());
         info!("  Start:  sc start KeepHive");
@@ -89,6 +124,18 @@ impl WindowsService {
             anyhow::bail!("Failed to delete service: {}", error);
         }
 
+        if let Err(e) = registry::remove_service_registration() {
+            warn!("Failed to remove service parameters from registry: {}", e);
+        }
+
+        if let Err(e) = shell::unregister_backup_now_verb() {
+            warn!("Failed to remove Explorer integration: {}", e);
+        }
+
+        if let Err(e) = perf::unregister_counters() {
+            warn!("Failed to remove performance counters: {}", e);
+        }
+
         info!("✓ Service uninstalled successfully");
         Ok(())
     }
@@ -126,4 +173,164 @@ impl WindowsService {
         info!("✓ Service stopped");
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Replace the installed service binary with a new one and restart the service
+    ///
+    /// Config, state and registry parameters are left untouched. If the installed
+    /// binary cannot be replaced (file in use), the replacement is scheduled for
+    /// the next reboot instead.
+    pub fn upgrade(new_binary: Option<PathBuf>) -> Result<()> {
+        let new_binary = match new_binary {
+            Some(path) => path,
+            None => std::env::current_exe().context("Failed to get executable path")?,
+        };
+        let new_binary = dunce::canonicalize(&new_binary)
+            .with_context(|| format!("New binary not found: {}", new_binary.display()))?;
+
+        let installed = Self::installed_binary_path()?;
+        let installed = dunce::canonicalize(&installed).unwrap_or(installed);
+
+        if new_binary == installed {
+            anyhow::bail!(
+                "The installed service binary is the one running this command ({}). \
+                 Run --upgrade-service from the new keephive.exe or pass its path.",
+                installed.display()
+            );
+        }
+
+        info!("Upgrading KeepHive service");
+        info!("  Installed: {}", installed.display());
+        info!("  New:       {}", new_binary.display());
+
+        // Stage next to the installed binary so the final rename stays on one volume
+        let staged = installed.with_extension("exe.new");
+        std::fs::copy(&new_binary, &staged)
+            .with_context(|| format!("Failed to stage new binary at {}", staged.display()))?;
+
+        Self::stop_and_wait()?;
+
+        let reboot_required = match Self::swap_binary(&installed, &staged) {
+            Ok(()) => false,
+            Err(e) => {
+                warn!("Could not replace binary now ({}), scheduling replacement on reboot", e);
+                file_ops::move_file_on_reboot(&staged, Some(&installed))?;
+                true
+            }
+        };
+
+        Self::start()?;
+
+        if reboot_required {
+            warn!("Upgrade staged: the new binary will be installed on the next reboot");
+        } else {
+            info!("✓ Service upgraded successfully");
+        }
+        Ok(())
+    }
+
+    /// Read the service binary path from the SCM ImagePath
+    fn installed_binary_path() -> Result<PathBuf> {
+        let key = windows_registry::LOCAL_MACHINE.open(SERVICE_REGISTRY_KEY)
+            .context("KeepHive service is not installed")?;
+        let image_path = key.get_string("ImagePath")
+            .context("Failed to read service ImagePath")?;
+
+        parse_image_path(&image_path)
+            .with_context(|| format!("Cannot parse service ImagePath: {}", image_path))
+    }
+
+    /// Rename the running binary aside and move the staged one into place
+    fn swap_binary(installed: &Path, staged: &Path) -> Result<()> {
+        let previous = installed.with_extension("exe.old");
+        if previous.exists() {
+            std::fs::remove_file(&previous)
+                .with_context(|| format!("Failed to remove {}", previous.display()))?;
+        }
+
+        std::fs::rename(installed, &previous)
+            .context("Failed to move installed binary aside")?;
+
+        if let Err(e) = std::fs::rename(staged, installed) {
+            // Put the old binary back so the service can still start
+            let _ = std::fs::rename(&previous, installed);
+            return Err(e).context("Failed to move new binary into place");
+        }
+
+        if std::fs::remove_file(&previous).is_err() {
+            let _ = file_ops::move_file_on_reboot(&previous, None);
+        }
+
+        Ok(())
+    }
+
+    /// Stop the service (if running) and wait until the SCM reports it stopped
+    fn stop_and_wait() -> Result<()> {
+        if Self::query_state()?.as_deref() != Some("STOPPED") {
+            Self::stop()?;
+        }
+        Self::wait_for_state("STOPPED")
+    }
+
+    fn wait_for_state(expected: &str) -> Result<()> {
+        let start = Instant::now();
+        while start.elapsed() < SERVICE_STATE_TIMEOUT {
+            if Self::query_state()?.as_deref() == Some(expected) {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        anyhow::bail!("Timed out waiting for service to reach state {}", expected)
+    }
+
+    /// Current SCM state name (e.g. RUNNING, STOPPED)
+    fn query_state() -> Result<Option<String>> {
+        let output = Command::new("sc")
+            .args(&["query", SERVICE_NAME])
+            .output()
+            .context("Failed to execute sc query")?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines()
+            .find(|line| line.trim_start().starts_with("STATE"))
+            .and_then(|line| line.split_whitespace().nth(3))
+            .map(|state| state.to_string()))
+    }
+}
+
+/// Extract the executable from an ImagePath such as `"C:\x\keephive.exe" --service`
+fn parse_image_path(image_path: &str) -> Option<PathBuf> {
+    let image_path = image_path.trim();
+
+    if let Some(rest) = image_path.strip_prefix('"') {
+        return rest.split('"').next()
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from);
+    }
+
+    let end = image_path.find(" --").unwrap_or(image_path.len());
+    let path = image_path[..end].trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quoted_image_path() {
+        let path = parse_image_path(r#""C:\Program Files\KeepHive\keephive.exe" --service"#);
+        assert_eq!(path, Some(PathBuf::from(r"C:\Program Files\KeepHive\keephive.exe")));
+    }
+
+    #[test]
+    fn test_parse_unquoted_image_path() {
+        let path = parse_image_path(r"C:\KeepHive\keephive.exe --service C:\config.json");
+        assert_eq!(path, Some(PathBuf::from(r"C:\KeepHive\keephive.exe")));
+    }
+
+    #[test]
+    fn test_parse_empty_image_path() {
+        assert_eq!(parse_image_path("  "), None);
+        assert_eq!(parse_image_path(r#""""#), None);
+    }
+}
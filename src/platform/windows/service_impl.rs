@@ -14,29 +14,48 @@ use windows_service::{
     service_dispatcher,
 };
 
-const SERVICE_NAME: &str = "KeepHive";
+use super::registry;
+use super::service::SERVICE_NAME;
+
+const DEFAULT_CONFIG_PATH: &str = r"C:\ProgramData\KeepHive\keephive_config.json";
 
 define_windows_service!(ffi_service_main, service_entry_point);
 
 /// FFI entry point called by Windows SCM
 fn service_entry_point(arguments: Vec<OsString>) {
-    let config_path = if arguments.len() > 1 {
-        PathBuf::from(&arguments[1])
-    } else {
-        // Fallback
-        eprintln!("WARNING: No config path in service arguments!");
-        eprintln!("Using default: C:\\ProgramData\\KeepHive\\keephive_config.json");
-        PathBuf::from(r"C:\ProgramData\KeepHive\keephive_config.json")
-    };
+    let (config_path, instance_name) = resolve_service_parameters();
 
-    if let Err(e) = run_service(arguments, config_path) {
+    if let Err(e) = run_service(arguments, config_path, &instance_name) {
         error!("Service error: {:?}", e);
     }
 }
 
+/// Find the config path and instance name for this service
+///
+/// Order: registry (written at install time), KEEPHIVE_CONFIG (set from the
+/// binPath argument of older installs), then the default location.
+fn resolve_service_parameters() -> (PathBuf, String) {
+    match registry::read_service_registration() {
+        Ok(Some(registration)) => {
+            return (registration.config_path, registration.instance_name);
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("WARNING: Failed to read service parameters from registry: {}", e),
+    }
+
+    if let Some(path) = std::env::var_os("KEEPHIVE_CONFIG") {
+        return (PathBuf::from(path), SERVICE_NAME.to_string());
+    }
+
+    eprintln!("WARNING: No config path in registry or service arguments!");
+    eprintln!("Using default: {}", DEFAULT_CONFIG_PATH);
+    (PathBuf::from(DEFAULT_CONFIG_PATH), SERVICE_NAME.to_string())
+}
+
 fn run_service(
     _arguments: Vec<OsString>,
     config_path: PathBuf,
+    instance_name: &str,
 ) -> windows_service::Result<()> {
     use std::sync::{Arc, Mutex};
     use tokio_util::sync::CancellationToken;
@@ -62,7 +81,7 @@ fn run_service(
         }
     };
 
-    let status_handle = service_control_handler::register(SERVICE_NAME, event_handler)?;
+    let status_handle = service_control_handler::register(instance_name, event_handler)?;
 
     status_handle.set_service_status(ServiceStatus {
         service_type: ServiceType::OWN_PROCESS,
@@ -152,7 +171,7 @@ async fn load_config(path: &PathBuf) -> Result<crate::config::ServiceConfig> {
     }
 
     let content = tokio::fs::read_to_string(path).await?;
-    let mut config: crate::config::ServiceConfig = serde_json::from_str(&content)
+    let mut config = crate::config::parse_config(path, &content)
         .context("Parse error")?;
 
     // Normalize relative paths to be relative to config file location
@@ -179,7 +198,7 @@ async fn load_config(path: &PathBuf) -> Result<crate::config::ServiceConfig> {
 }
 
 fn init_logging_from_config(config: &crate::config::ServiceConfig) -> Result<()> {
-    use crate::observability::{init_logging, Rotation};
+    use crate::observability::{init_logging, set_path_redaction, Rotation};
 
     let rotation = match config.log_rotation {
         crate::config::LogRotation::Daily => Rotation::Daily,
@@ -187,6 +206,7 @@ fn init_logging_from_config(config: &crate::config::ServiceConfig) -> Result<()>
         crate::config::LogRotation::Never => Rotation::Never,
     };
 
+    set_path_redaction(config.log_path_redaction);
     init_logging(&config.log_level, config.log_directory.as_deref(), rotation)
 }
 
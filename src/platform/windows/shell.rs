@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::info;
+use windows_registry::LOCAL_MACHINE;
+
+/// Explorer context-menu verb shown on folders
+const BACKUP_NOW_VERB_KEY: &str = r"SOFTWARE\Classes\Directory\shell\KeepHiveBackupNow";
+
+const BACKUP_NOW_LABEL: &str = "KeepHive: Back up this folder now";
+
+/// Add "Back up this folder now" to the folder context menu for all users
+pub fn register_backup_now_verb(exe_path: &Path) -> Result<()> {
+    let key = LOCAL_MACHINE.create(BACKUP_NOW_VERB_KEY)
+        .context("Failed to create Explorer verb registry key")?;
+
+    key.set_string("", BACKUP_NOW_LABEL)
+        .context("Failed to set Explorer verb label")?;
+    key.set_string("Icon", exe_path.to_string_lossy())
+        .context("Failed to set Explorer verb icon")?;
+
+    let command = key.create("command")
+        .context("Failed to create Explorer verb command key")?;
+    command.set_string("", format!("\"{}\" --backup-now \"%1\"", exe_path.display()))
+        .context("Failed to set Explorer verb command")?;
+
+    info!("Registered Explorer verb: {}", BACKUP_NOW_LABEL);
+    Ok(())
+}
+
+/// Remove the folder context-menu verb (ignores a missing key)
+pub fn unregister_backup_now_verb() -> Result<()> {
+    if LOCAL_MACHINE.open(BACKUP_NOW_VERB_KEY).is_err() {
+        return Ok(());
+    }
+
+    LOCAL_MACHINE.remove_tree(BACKUP_NOW_VERB_KEY)
+        .context("Failed to remove Explorer verb registry key")?;
+
+    info!("Removed Explorer verb: {}", BACKUP_NOW_LABEL);
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, LUID};
+use windows::Win32::Security::{
+    AdjustTokenPrivileges, LookupPrivilegeValueW, LUID_AND_ATTRIBUTES, SE_PRIVILEGE_ENABLED, TOKEN_ADJUST_PRIVILEGES,
+    TOKEN_PRIVILEGES, TOKEN_QUERY,
+};
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegOpenKeyExW, RegSaveKeyExW, HKEY, HKEY_LOCAL_MACHINE, KEY_READ, REG_LATEST_FORMAT,
+};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+use crate::config::{RegistryHive, SystemStateConfig};
+use crate::core::system_state::{staged_path, FILES_DIR, HIVES_DIR};
+use crate::platform::windows::volume::volume_root;
+use crate::platform::windows::vss::{ComGuard, ShadowCopy};
+
+/// Save the configured hives into `staging\registry` and copy the configured files
+/// from shadow copies into `staging\files`
+pub fn export(config: &SystemStateConfig, staging: &Path) -> Result<()> {
+    // RegSaveKeyEx needs it even for LocalSystem, which holds it disabled
+    enable_privilege(w!("SeBackupPrivilege"))?;
+
+    for hive in &config.hives {
+        save_hive(*hive, &staging.join(HIVES_DIR).join(hive.key_name()))?;
+    }
+
+    if config.files.is_empty() {
+        return Ok(());
+    }
+
+    let _com = ComGuard::initialize()?;
+
+    let mut by_volume: BTreeMap<PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for file in &config.files {
+        match volume_root(file) {
+            Some(root) => by_volume.entry(root).or_default().push(file),
+            None => warn!("Skipping system file without a drive: {}", file.display()),
+        }
+    }
+
+    let snapshot = ShadowCopy::create(by_volume.keys())?;
+
+    for (root, files) in &by_volume {
+        for file in files {
+            let shadow_path = snapshot.path_of(root, file)?;
+            let staged = staged_path(&staging.join(FILES_DIR), file);
+
+            if !shadow_path.exists() {
+                warn!("System file not found, skipping: {}", file.display());
+                continue;
+            }
+
+            copy_tree(&shadow_path, &staged)
+                .with_context(|| format!("Failed to copy {} from shadow copy", file.display()))?;
+        }
+    }
+
+    snapshot.complete();
+    Ok(())
+}
+
+/// Save `HKEY_LOCAL_MACHINE\<hive>` to `file` in the latest hive format
+fn save_hive(hive: RegistryHive, file: &Path) -> Result<()> {
+    let key_name = to_wide(Path::new(hive.key_name()));
+    let file_wide = to_wide(file);
+    let mut key = HKEY::default();
+
+    unsafe {
+        RegOpenKeyExW(HKEY_LOCAL_MACHINE, PCWSTR(key_name.as_ptr()), Some(0), KEY_READ, &mut key)
+            .ok()
+            .with_context(|| format!("Failed to open HKLM\\{}", hive.key_name()))?;
+
+        let saved = RegSaveKeyExW(key, PCWSTR(file_wide.as_ptr()), None, REG_LATEST_FORMAT).ok();
+        let _ = RegCloseKey(key);
+        saved.with_context(|| format!("Failed to save HKLM\\{}", hive.key_name()))?;
+    }
+
+    info!("Saved registry hive HKLM\\{}", hive.key_name());
+    Ok(())
+}
+
+/// Enable a privilege the process token holds
+pub(crate) fn enable_privilege(name: PCWSTR) -> Result<()> {
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_ADJUST_PRIVILEGES | TOKEN_QUERY, &mut token)
+            .context("Failed to open process token")?;
+
+        let mut luid = LUID::default();
+        let result = LookupPrivilegeValueW(PCWSTR::null(), name, &mut luid)
+            .and_then(|()| {
+                let privileges = TOKEN_PRIVILEGES {
+                    PrivilegeCount: 1,
+                    Privileges: [LUID_AND_ATTRIBUTES { Luid: luid, Attributes: SE_PRIVILEGE_ENABLED }],
+                };
+                AdjustTokenPrivileges(token, false, Some(&privileges), 0, None, None)
+            });
+
+        let _ = CloseHandle(token);
+        // AdjustTokenPrivileges succeeds without the privilege; RegSaveKeyEx then reports it
+        result.context("Failed to enable backup privilege (is the service running as LocalSystem?)")
+    }
+}
+
+/// Copy a file or folder tree from the shadow copy; links are not followed
+fn copy_tree(source: &Path, target: &Path) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(source)?;
+
+    if metadata.is_symlink() {
+        return Ok(());
+    }
+
+    if metadata.is_file() {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(source, target)?;
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(target)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        copy_tree(&entry.path(), &target.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
+
+fn to_wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(std::iter::once(0)).collect()
+}
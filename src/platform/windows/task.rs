@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::info;
+
+/// How often the task runs after logon unless `--every` says otherwise
+pub const DEFAULT_TASK_INTERVAL_MINUTES: u32 = 60;
+
+/// Per-user alternative to the service for accounts without admin rights: a
+/// Task Scheduler task running `--run-pending-and-exit` at logon and on a timer
+pub struct ScheduledTask;
+
+impl ScheduledTask {
+    /// Register the task for the current user
+    pub fn install(config_path: Option<PathBuf>, interval_minutes: u32) -> Result<()> {
+        let exe_path = std::env::current_exe()
+            .context("Failed to get executable path")?;
+
+        let config_path = std::path::absolute(config_path.unwrap_or_else(|| PathBuf::from("keephive_config.json")))
+            .context("Failed to resolve config path")?;
+        if !config_path.exists() {
+            anyhow::bail!("Configuration file not found: {}", config_path.display());
+        }
+
+        let user = current_user()?;
+        let name = task_name(&user);
+        let xml = task_definition(&exe_path, &config_path, &user, interval_minutes.max(1));
+
+        // schtasks only reads UTF-16 definitions reliably
+        let definition = std::env::temp_dir().join(format!("keephive_task_{}.xml", std::process::id()));
+        let bytes: Vec<u8> = std::iter::once(0xFEFF)
+            .chain(xml.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        std::fs::write(&definition, bytes)
+            .context("Failed to write task definition")?;
+
+        let output = Command::new("schtasks")
+            .args(["/Create", "/TN", &name, "/XML"])
+            .arg(&definition)
+            .arg("/F")
+            .output();
+        let _ = std::fs::remove_file(&definition);
+        let output = output.context("Failed to execute schtasks /Create")?;
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Failed to create scheduled task: {}", error.trim());
+        }
+
+        info!("✓ Scheduled task installed: {}", name);
+        info!("  Config: {}", config_path.display());
+        info!("  Runs at logon and every {} minutes", interval_minutes.max(1));
+        info!("  Run now: schtasks /Run /TN \"{}\"", name);
+        Ok(())
+    }
+
+    /// Remove the current user's task
+    pub fn uninstall() -> Result<()> {
+        let name = task_name(&current_user()?);
+
+        let output = Command::new("schtasks")
+            .args(["/Delete", "/TN", &name, "/F"])
+            .output()
+            .context("Failed to execute schtasks /Delete")?;
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Failed to delete scheduled task: {}", error.trim());
+        }
+
+        info!("✓ Scheduled task removed: {}", name);
+        Ok(())
+    }
+}
+
+/// `DOMAIN\user` of the account running this command
+fn current_user() -> Result<String> {
+    let user = std::env::var("USERNAME").context("USERNAME is not set")?;
+
+    Ok(match std::env::var("USERDOMAIN") {
+        Ok(domain) => format!("{}\\{}", domain, user),
+        Err(_) => user,
+    })
+}
+
+/// One task per user, so several accounts on a machine can each have one
+fn task_name(user: &str) -> String {
+    format!("KeepHive ({})", user.rsplit('\\').next().unwrap_or(user))
+}
+
+/// Task Scheduler XML: logon and interval triggers, no elevation, one instance at a time
+fn task_definition(exe_path: &Path, config_path: &Path, user: &str, interval_minutes: u32) -> String {
+    let command = escape_xml(&exe_path.display().to_string());
+    let arguments = escape_xml(&format!("--run-pending-and-exit \"{}\"", config_path.display()));
+    let user = escape_xml(user);
+
+    format!(r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Runs due KeepHive backups for {user}</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger>
+      <Enabled>true</Enabled>
+      <UserId>{user}</UserId>
+      <Delay>PT2M</Delay>
+    </LogonTrigger>
+    <TimeTrigger>
+      <Enabled>true</Enabled>
+      <StartBoundary>2000-01-01T00:00:00</StartBoundary>
+      <Repetition>
+        <Interval>PT{interval_minutes}M</Interval>
+      </Repetition>
+    </TimeTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>{user}</UserId>
+      <LogonType>InteractiveToken</LogonType>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{command}</Command>
+      <Arguments>{arguments}</Arguments>
+    </Exec>
+  </Actions>
+</Task>
+"#)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_definition() {
+        let xml = task_definition(
+            Path::new(r"C:\Tools & Apps\keephive.exe"),
+            Path::new(r"C:\Users\me\keephive_config.json"),
+            r"PC\me",
+            30,
+        );
+
+        assert!(xml.contains(r"<Command>C:\Tools &amp; Apps\keephive.exe</Command>"));
+        assert!(xml.contains(r"<Arguments>--run-pending-and-exit &quot;C:\Users\me\keephive_config.json&quot;</Arguments>"));
+        assert!(xml.contains("<Interval>PT30M</Interval>"));
+        assert_eq!(task_name(r"PC\me"), "KeepHive (me)");
+    }
+}
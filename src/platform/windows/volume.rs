@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use windows::core::PCWSTR;
+use windows::Win32::Storage::FileSystem::{GetDriveTypeW, GetLogicalDrives, GetVolumeInformationW};
+
+use crate::core::VolumeId;
+
+/// `GetDriveTypeW` results for removable media and fixed disks
+const DRIVE_REMOVABLE: u32 = 2;
+const DRIVE_FIXED: u32 = 3;
+
+const IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS: u32 = 0x0056_0000;
+const FILE_SHARE_READ_WRITE: u32 = 0x1 | 0x2;
+
+/// `VOLUME_DISK_EXTENTS` with room for the extents of a volume spanning several disks
+#[repr(C)]
+struct VolumeDiskExtents {
+    count: u32,
+    extents: [DiskExtent; 8],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DiskExtent {
+    disk_number: u32,
+    starting_offset: i64,
+    extent_length: i64,
+}
+
+/// Root of the drive holding `path` (`E:\`)
+pub fn volume_root(path: &Path) -> Option<PathBuf> {
+    match path.components().next()? {
+        Component::Prefix(prefix) => Some(PathBuf::from(prefix.as_os_str()).join("\\")),
+        _ => None,
+    }
+}
+
+/// Serial number and label of the volume mounted at `root`
+fn volume_information(root: &Path) -> Result<(u32, String)> {
+    let root_wide: Vec<u16> = root.as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let mut label = [0u16; 261];
+    let mut serial = 0u32;
+
+    unsafe {
+        GetVolumeInformationW(
+            PCWSTR(root_wide.as_ptr()),
+            Some(&mut label),
+            Some(&mut serial as *mut u32),
+            None,
+            None,
+            None,
+        ).with_context(|| format!("Failed to query volume {}", root.display()))?;
+    }
+
+    let len = label.iter().position(|&c| c == 0).unwrap_or(label.len());
+    Ok((serial, String::from_utf16_lossy(&label[..len])))
+}
+
+/// Identity of the volume holding `path`
+pub fn volume_id(path: &Path) -> Result<VolumeId> {
+    let root = volume_root(path)
+        .with_context(|| format!("Path has no drive: {}", path.display()))?;
+    let (serial, label) = volume_information(&root)?;
+
+    Ok(VolumeId { serial, label, root })
+}
+
+/// Serial number of the volume currently mounted at `root`, if any
+pub fn serial_at(root: &Path) -> Option<u32> {
+    volume_information(root).ok().map(|(serial, _)| serial)
+}
+
+/// Root of the drive letter the volume with `serial` is mounted at, if connected
+pub fn find_volume(serial: u32) -> Option<PathBuf> {
+    let drives = unsafe { GetLogicalDrives() };
+
+    (0..26u8)
+        .filter(|bit| drives & (1 << bit) != 0)
+        .map(|bit| PathBuf::from(format!("{}:\\", (b'A' + bit) as char)))
+        .find(|root| serial_at(root) == Some(serial))
+}
+
+/// Physical drives (`PhysicalDrive0`) the volume holding `path` lies on; empty for
+/// network paths and volumes that do not report their extents
+pub fn physical_disks(path: &Path) -> Vec<String> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::IO::DeviceIoControl;
+
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return Vec::new();
+    };
+    let std::path::Prefix::Disk(letter) | std::path::Prefix::VerbatimDisk(letter) = prefix.kind() else {
+        return Vec::new();
+    };
+
+    // Opening the volume without access rights is enough to query it
+    let Ok(volume) = std::fs::OpenOptions::new()
+        .access_mode(0)
+        .share_mode(FILE_SHARE_READ_WRITE)
+        .open(format!("\\\\.\\{}:", letter as char))
+    else {
+        return Vec::new();
+    };
+
+    let mut extents = VolumeDiskExtents {
+        count: 0,
+        extents: [DiskExtent { disk_number: 0, starting_offset: 0, extent_length: 0 }; 8],
+    };
+    let mut returned = 0u32;
+
+    let queried = unsafe {
+        DeviceIoControl(
+            HANDLE(volume.as_raw_handle()),
+            IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS,
+            None,
+            0,
+            Some(&mut extents as *mut VolumeDiskExtents as *mut _),
+            std::mem::size_of::<VolumeDiskExtents>() as u32,
+            Some(&mut returned),
+            None,
+        )
+    };
+    if queried.is_err() {
+        return Vec::new();
+    }
+
+    let mut disks: Vec<String> = extents.extents[..(extents.count as usize).min(extents.extents.len())]
+        .iter()
+        .map(|extent| format!("PhysicalDrive{}", extent.disk_number))
+        .collect();
+    disks.sort();
+    disks.dedup();
+    disks
+}
+
+/// Roots of mounted fixed and removable drives with their label and whether they are removable
+pub fn local_drives() -> Vec<(PathBuf, String, bool)> {
+    let drives = unsafe { GetLogicalDrives() };
+
+    (0..26u8)
+        .filter(|bit| drives & (1 << bit) != 0)
+        .map(|bit| PathBuf::from(format!("{}:\\", (b'A' + bit) as char)))
+        .filter_map(|root| {
+            let root_wide: Vec<u16> = root.as_os_str()
+                .encode_wide()
+                .chain(std::iter::once(0))
+                .collect();
+            let kind = unsafe { GetDriveTypeW(PCWSTR(root_wide.as_ptr())) };
+            if kind != DRIVE_FIXED && kind != DRIVE_REMOVABLE {
+                return None;
+            }
+
+            // Card readers without a card have no volume
+            let (_, label) = volume_information(&root).ok()?;
+            Some((root, label, kind == DRIVE_REMOVABLE))
+        })
+        .collect()
+}
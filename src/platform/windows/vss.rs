@@ -0,0 +1,205 @@
+//! Volume Shadow Copy (VSS) snapshots, so files other programs hold open or locked
+//! (Outlook PST files, SQLite databases, open documents) are read as they were at one
+//! moment instead of being skipped. Used for system-state files and for jobs with
+//! `shadow_copy` set.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use tracing::{info, warn};
+use windows::core::{BSTR, GUID, HRESULT, PWSTR};
+use windows::Win32::Storage::Vss::{
+    CreateVssBackupComponentsInternal, IVssAsync, IVssBackupComponents, VssFreeSnapshotProperties, VSS_BT_COPY,
+    VSS_CTX_BACKUP, VSS_SNAPSHOT_PROP,
+};
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+use crate::platform::arch::{emulated, Architecture};
+
+/// Status of an `IVssAsync` operation that completed successfully
+const VSS_S_ASYNC_FINISHED: HRESULT = HRESULT(0x0004_230A);
+
+/// Shadow copy of one volume for as long as a backup reads from it.
+///
+/// The VSS interfaces stay on the thread that created them, so the snapshot lives on a
+/// thread of its own; dropping this handle lets that thread complete and delete it.
+pub struct VolumeSnapshot {
+    root: PathBuf,
+    device: String,
+    _release: mpsc::Sender<()>,
+}
+
+impl VolumeSnapshot {
+    /// Snapshot the volume at `root` (`C:\`); blocks until the snapshot exists
+    pub fn create(root: &Path) -> Result<Self> {
+        let (created, creation) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        let thread_root = root.to_path_buf();
+
+        std::thread::Builder::new()
+            .name("keephive-vss".to_string())
+            .spawn(move || {
+                let snapshot = ComGuard::initialize()
+                    .and_then(|com| Ok((com, ShadowCopy::create(std::iter::once(&thread_root))?)));
+
+                match snapshot {
+                    Ok((_com, snapshot)) => {
+                        let device = snapshot.devices.get(&thread_root).cloned()
+                            .context("VSS did not report a snapshot device");
+                        let _ = created.send(device);
+
+                        // Returns once the handle is dropped
+                        let _ = released.recv();
+                        snapshot.complete();
+                    }
+                    Err(e) => {
+                        let _ = created.send(Err(e));
+                    }
+                }
+            })
+            .context("Failed to start shadow copy thread")?;
+
+        let device = creation.recv().context("Shadow copy thread stopped")??;
+        Ok(Self { root: root.to_path_buf(), device, _release: release })
+    }
+
+    /// Path of `file` (on the snapshotted volume) inside the shadow copy
+    pub fn path_of(&self, file: &Path) -> Result<PathBuf> {
+        shadow_path(&self.device, &self.root, file)
+    }
+}
+
+/// `file` on the volume at `root`, under the shadow copy device of that volume
+fn shadow_path(device: &str, root: &Path, file: &Path) -> Result<PathBuf> {
+    let relative = file.strip_prefix(root)
+        .with_context(|| format!("{} is not on {}", file.display(), root.display()))?;
+
+    Ok(PathBuf::from(format!("{}\\", device)).join(relative))
+}
+
+/// COM initialized for the current thread until dropped
+pub(crate) struct ComGuard;
+
+impl ComGuard {
+    pub(crate) fn initialize() -> Result<Self> {
+        unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }
+            .ok()
+            .context("Failed to initialize COM")?;
+        Ok(Self)
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        unsafe { CoUninitialize() };
+    }
+}
+
+/// Non-persistent VSS snapshot of one or more volumes, deleted when dropped
+pub(crate) struct ShadowCopy {
+    components: IVssBackupComponents,
+    devices: BTreeMap<PathBuf, String>,
+}
+
+impl ShadowCopy {
+    pub(crate) fn create<'a>(roots: impl Iterator<Item = &'a PathBuf>) -> Result<Self> {
+        // VSS only serves requestors built for the machine's own architecture
+        if let Some(native) = emulated() {
+            bail!(
+                "Shadow copies are not available to the {} build of KeepHive running emulated on {}; install the {} build",
+                Architecture::build(),
+                native,
+                native
+            );
+        }
+
+        let components = unsafe { CreateVssBackupComponentsInternal() }
+            .context("Failed to create VSS backup components (is the service running as administrator?)")?;
+
+        match Self::snapshot(&components, roots) {
+            Ok(devices) => Ok(Self { components, devices }),
+            Err(e) => {
+                let _ = unsafe { components.AbortBackup() };
+                Err(e)
+            }
+        }
+    }
+
+    fn snapshot<'a>(
+        components: &IVssBackupComponents,
+        roots: impl Iterator<Item = &'a PathBuf>,
+    ) -> Result<BTreeMap<PathBuf, String>> {
+        let mut snapshot_ids = Vec::new();
+
+        unsafe {
+            components.InitializeForBackup(&BSTR::new()).context("Failed to initialize VSS backup")?;
+            components.SetContext(VSS_CTX_BACKUP.0).context("Failed to set VSS context")?;
+            components.SetBackupState(false, false, VSS_BT_COPY, false).context("Failed to set VSS backup state")?;
+            wait(components.GatherWriterMetadata(), "gather VSS writer metadata")?;
+
+            components.StartSnapshotSet().context("Failed to start VSS snapshot set")?;
+            for root in roots {
+                let mut volume = to_wide(root);
+                let id = components.AddToSnapshotSet(PWSTR(volume.as_mut_ptr()), GUID::zeroed())
+                    .with_context(|| format!("Failed to add {} to the VSS snapshot set", root.display()))?;
+                snapshot_ids.push((root.clone(), id));
+            }
+
+            wait(components.PrepareForBackup(), "prepare VSS backup")?;
+            wait(components.DoSnapshotSet(), "create VSS snapshot")?;
+
+            let mut devices = BTreeMap::new();
+            for (root, id) in snapshot_ids {
+                let mut properties = VSS_SNAPSHOT_PROP::default();
+                components.GetSnapshotProperties(id, &mut properties)
+                    .context("Failed to read VSS snapshot properties")?;
+
+                let device = properties.m_pwszSnapshotDeviceObject.to_string();
+                VssFreeSnapshotProperties(&mut properties);
+
+                let device = device.context("VSS snapshot device name is not valid UTF-16")?;
+                info!("Created shadow copy of {} at {}", root.display(), device);
+                devices.insert(root, device);
+            }
+
+            Ok(devices)
+        }
+    }
+
+    /// Path of `file` (on the volume at `root`) inside the shadow copy
+    pub(crate) fn path_of(&self, root: &Path, file: &Path) -> Result<PathBuf> {
+        let device = self.devices.get(root)
+            .with_context(|| format!("No shadow copy of {}", root.display()))?;
+        shadow_path(device, root, file)
+    }
+
+    /// Tell the writers the backup finished; the snapshot itself goes away on drop
+    pub(crate) fn complete(self) {
+        if let Err(e) = unsafe { wait(self.components.BackupComplete(), "complete VSS backup") } {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Wait for a VSS operation and fail if it did not finish successfully
+unsafe fn wait(operation: windows::core::Result<IVssAsync>, what: &str) -> Result<()> {
+    let operation = operation.with_context(|| format!("Failed to {}", what))?;
+
+    unsafe {
+        operation.Wait(u32::MAX).with_context(|| format!("Failed to {}", what))?;
+
+        let mut status = HRESULT(0);
+        operation.QueryStatus(&mut status, None).with_context(|| format!("Failed to {}", what))?;
+        if status != VSS_S_ASYNC_FINISHED {
+            bail!("Failed to {}: {}", what, windows::core::Error::from_hresult(status));
+        }
+    }
+
+    Ok(())
+}
+
+fn to_wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(std::iter::once(0)).collect()
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use crate::config::{BackupJob, BackupMode, JobHooks, JobKind, NameConflicts, Schedule, SystemStateConfig, WhenBusy};
+
+/// Prefix of job IDs created for one-off backups outside the configuration
+pub const ADHOC_JOB_PREFIX: &str = "adhoc-";
+
+/// Whether a job ID belongs to an ad-hoc (unconfigured) job
+pub fn is_adhoc_job(job_id: &str) -> bool {
+    job_id.starts_with(ADHOC_JOB_PREFIX)
+}
+
+/// Build a one-off job copying `source` into `target`.
+///
+/// The ID is derived from both paths, so resubmitting the same pair maps to the
+/// same job while it is still queued or running.
+pub fn adhoc_job(
+    source: &Path,
+    target: &Path,
+    retention_count: Option<usize>,
+    description: Option<String>,
+) -> BackupJob {
+    let key = format!("{}-{:08x}", folder_slug(source), path_hash(&[source, target]));
+
+    BackupJob {
+        id: format!("{}{}", ADHOC_JOB_PREFIX, key),
+        source: source.to_path_buf(),
+        target: target.to_path_buf(),
+        schedule: Schedule::Interval { seconds: 0 },
+        description: description.unwrap_or_else(|| format!("Ad-hoc backup of {}", source.display())),
+        retention_count,
+        retention_max_total_gb: None,
+        retention_max_age_days: None,
+        retry: None,
+        max_bytes_per_sec: None,
+        shadow_copy: false,
+        backup_privilege: false,
+        notify: None,
+        dry_run: false,
+        state_path: None,
+        replicas: Vec::new(),
+        exclude_profiles: Vec::new(),
+        exclude: Vec::new(),
+        name_conflicts: NameConflicts::Off,
+        normalize_names: None,
+        max_path_length: None,
+        kind: JobKind::Files,
+        system_state: SystemStateConfig::default(),
+        dump: None,
+        wsl: None,
+        docker: None,
+        app: None,
+        lock_files: Vec::new(),
+        hooks: JobHooks::default(),
+        busy_processes: Vec::new(),
+        when_busy: WhenBusy::Warn,
+        mode: BackupMode::Full,
+        archive: None,
+    }
+}
+
+/// Build the one-off job that backs up `folder` into its own directory under `adhoc_target`.
+///
+/// The ID is derived from the folder path, so repeated requests for the same
+/// folder map to the same job and its backups share one retention set.
+pub fn folder_backup_job(folder: &Path, adhoc_target: &Path, retention_count: usize) -> BackupJob {
+    // Keep same-named folders in different places apart
+    let key = format!("{}-{:08x}", folder_slug(folder), path_hash(&[folder]));
+
+    BackupJob {
+        id: format!("{}{}", ADHOC_JOB_PREFIX, key),
+        source: folder.to_path_buf(),
+        target: adhoc_target.join(key),
+        schedule: Schedule::Interval { seconds: 0 },
+        description: format!("Ad-hoc backup of {}", folder.display()),
+        retention_count: Some(retention_count),
+        retention_max_total_gb: None,
+        retention_max_age_days: None,
+        retry: None,
+        max_bytes_per_sec: None,
+        shadow_copy: false,
+        backup_privilege: false,
+        notify: None,
+        dry_run: false,
+        state_path: None,
+        replicas: Vec::new(),
+        exclude_profiles: Vec::new(),
+        exclude: Vec::new(),
+        name_conflicts: NameConflicts::Off,
+        normalize_names: None,
+        max_path_length: None,
+        kind: JobKind::Files,
+        system_state: SystemStateConfig::default(),
+        dump: None,
+        wsl: None,
+        docker: None,
+        app: None,
+        lock_files: Vec::new(),
+        hooks: JobHooks::default(),
+        busy_processes: Vec::new(),
+        when_busy: WhenBusy::Warn,
+        mode: BackupMode::Full,
+        archive: None,
+    }
+}
+
+/// Folder name reduced to characters safe in job IDs and directory names
+fn folder_slug(folder: &Path) -> String {
+    folder.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "root".to_string())
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .take(64)
+        .collect()
+}
+
+/// FNV-1a over the paths, stable across runs and platforms
+fn path_hash(paths: &[&Path]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for (i, path) in paths.iter().enumerate() {
+        // NUL separator between paths
+        let separator = (i > 0).then_some(0u8);
+        for byte in separator.into_iter().chain(path.to_string_lossy().to_lowercase().bytes()) {
+            hash ^= byte as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_backup_job_is_stable() {
+        let a = folder_backup_job(Path::new("/home/user/My Docs"), Path::new("/backups/adhoc"), 3);
+        let b = folder_backup_job(Path::new("/home/user/My Docs"), Path::new("/backups/adhoc"), 3);
+
+        assert_eq!(a.id, b.id);
+        assert!(a.id.starts_with("adhoc-My_Docs-"));
+        assert!(is_adhoc_job(&a.id));
+        assert_eq!(a.target.parent(), Some(Path::new("/backups/adhoc")));
+        assert_eq!(a.retention_count, Some(3));
+    }
+
+    #[test]
+    fn test_same_name_different_folder() {
+        let a = folder_backup_job(Path::new("/one/docs"), Path::new("/t"), 3);
+        let b = folder_backup_job(Path::new("/two/docs"), Path::new("/t"), 3);
+
+        assert_ne!(a.id, b.id);
+        assert_ne!(a.target, b.target);
+    }
+
+    #[test]
+    fn test_adhoc_job_uses_given_target() {
+        let a = adhoc_job(Path::new("/data/db"), Path::new("/mnt/usb"), None, None);
+        let b = adhoc_job(Path::new("/data/db"), Path::new("/mnt/nas"), Some(2), Some("before upgrade".into()));
+
+        assert_eq!(a.target, Path::new("/mnt/usb"));
+        assert_eq!(a.retention_count, None);
+        assert_ne!(a.id, b.id, "Same source to different targets are separate jobs");
+        assert_eq!(b.description, "before upgrade");
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, Offset, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::state::IntervalAnchor;
+
+/// Difference between wall clock and monotonic time counted as a jump; NTP slews
+/// smaller corrections gradually
+const JUMP_THRESHOLD_SECS: i64 = 120;
+
+/// A change of the system time the schedule has to follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockChange {
+    /// The wall clock moved by this much more than time actually passed (negative: back)
+    Jumped(Duration),
+
+    /// The local UTC offset changed: a time zone change or a daylight saving switch
+    OffsetChanged { from: FixedOffset, to: FixedOffset },
+}
+
+/// Notices jumps of the system clock by comparing it with the monotonic clock between checks
+#[derive(Debug, Clone)]
+pub struct ClockWatch {
+    wall: DateTime<Utc>,
+    monotonic: Instant,
+    offset: FixedOffset,
+}
+
+impl Default for ClockWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockWatch {
+    pub fn new() -> Self {
+        Self {
+            wall: Utc::now(),
+            monotonic: Instant::now(),
+            offset: Local::now().offset().fix(),
+        }
+    }
+
+    /// How the clock changed since the last check, if it did
+    pub fn check(&mut self) -> Option<ClockChange> {
+        self.check_at(Utc::now(), Instant::now(), Local::now().offset().fix())
+    }
+
+    fn check_at(&mut self, wall: DateTime<Utc>, monotonic: Instant, offset: FixedOffset) -> Option<ClockChange> {
+        let passed = Duration::from_std(monotonic.duration_since(self.monotonic)).unwrap_or(Duration::MAX);
+        let drift = (wall - self.wall) - passed;
+        let previous_offset = std::mem::replace(&mut self.offset, offset);
+        self.wall = wall;
+        self.monotonic = monotonic;
+
+        if drift.num_seconds().abs() >= JUMP_THRESHOLD_SECS {
+            Some(ClockChange::Jumped(drift))
+        } else if offset != previous_offset {
+            Some(ClockChange::OffsetChanged { from: previous_offset, to: offset })
+        } else {
+            None
+        }
+    }
+}
+
+/// Where the monotonic clock stood when a run was first measured
+#[derive(Debug, Clone, Copy)]
+struct RunMark {
+    run: DateTime<Utc>,
+    seen: Instant,
+    elapsed: std::time::Duration,
+}
+
+/// Time since the last run of each interval job, measured on the monotonic clock so that
+/// setting the system time neither delays nor hurries interval schedules
+#[derive(Debug, Default)]
+pub struct IntervalClock {
+    runs: Mutex<HashMap<String, RunMark>>,
+}
+
+impl IntervalClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time passed since the run of `job_id` stamped `run`. A run seen for the first time,
+    /// e.g. after a restart, goes on from `anchor` if it belongs to the run, and is measured
+    /// on the wall clock otherwise.
+    pub fn elapsed(&self, job_id: &str, run: DateTime<Utc>, anchor: Option<&IntervalAnchor>) -> std::time::Duration {
+        self.elapsed_at(job_id, run, anchor, Utc::now(), Instant::now())
+    }
+
+    fn elapsed_at(
+        &self,
+        job_id: &str,
+        run: DateTime<Utc>,
+        anchor: Option<&IntervalAnchor>,
+        wall: DateTime<Utc>,
+        monotonic: Instant,
+    ) -> std::time::Duration {
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mark) = runs.get(job_id).filter(|mark| mark.run == run) {
+            return mark.elapsed + monotonic.saturating_duration_since(mark.seen);
+        }
+
+        // A clock set back while the service was stopped adds nothing
+        let since = |stamp: DateTime<Utc>| (wall - stamp).to_std().unwrap_or_default();
+        let elapsed = match anchor.filter(|anchor| anchor.run == run) {
+            Some(anchor) => std::time::Duration::from_secs(anchor.elapsed_seconds) + since(anchor.measured_at),
+            None => since(run),
+        };
+        runs.insert(job_id.to_string(), RunMark { run, seen: monotonic, elapsed });
+        elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jumps_are_told_apart_from_time_passing() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let mut watch = ClockWatch::new();
+        let (wall, monotonic) = (watch.wall, watch.monotonic);
+
+        let later = monotonic + std::time::Duration::from_secs(3600);
+        assert_eq!(watch.check_at(wall + Duration::seconds(3605), later, watch.offset), None);
+
+        let later = later + std::time::Duration::from_secs(5);
+        assert_eq!(
+            watch.check_at(wall - Duration::days(1), later, watch.offset),
+            Some(ClockChange::Jumped(-(Duration::days(1) + Duration::seconds(3610))))
+        );
+
+        let from = watch.offset;
+        let to = FixedOffset::east_opt(from.local_minus_utc() + 3600).unwrap_or(utc);
+        let later = later + std::time::Duration::from_secs(5);
+        assert_eq!(
+            watch.check_at(wall - Duration::days(1) + Duration::seconds(5), later, to),
+            Some(ClockChange::OffsetChanged { from, to })
+        );
+    }
+
+    #[test]
+    fn test_interval_time_follows_the_monotonic_clock() {
+        let clock = IntervalClock::new();
+        let run = Utc::now();
+        let start = Instant::now();
+        let secs = std::time::Duration::from_secs;
+
+        assert_eq!(clock.elapsed_at("docs", run, None, run + Duration::seconds(60), start), secs(60));
+
+        // The wall clock is set back a day and then forward a day; only real time counts
+        let later = start + secs(600);
+        assert_eq!(clock.elapsed_at("docs", run, None, run - Duration::days(1), later), secs(660));
+        assert_eq!(clock.elapsed_at("docs", run, None, run + Duration::days(1), later + secs(60)), secs(720));
+
+        // After a restart the saved measurement goes on, without the jump
+        let anchor = IntervalAnchor { run, elapsed_seconds: 720, measured_at: run - Duration::days(1) };
+        let restarted = IntervalClock::new();
+        assert_eq!(restarted.elapsed_at("docs", run, Some(&anchor), run - Duration::days(1) + Duration::seconds(30), start), secs(750));
+
+        // A newer run than the saved one is measured from its stamp
+        let next = run + Duration::hours(1);
+        assert_eq!(restarted.elapsed_at("docs", next, Some(&anchor), next + Duration::seconds(5), start), secs(5));
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+/// Large runs waiting for an operator to confirm or reject them, by job id
+#[derive(Default)]
+pub struct PendingConfirmations {
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+impl PendingConfirmations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start waiting for an answer for a job, replacing any earlier request
+    pub fn register(&self, job_id: &str) -> oneshot::Receiver<bool> {
+        let (answer, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(job_id.to_string(), answer);
+        receiver
+    }
+
+    /// Deliver an answer; returns false if the job is not waiting for one
+    pub fn resolve(&self, job_id: &str, approve: bool) -> bool {
+        match self.pending.lock().unwrap().remove(job_id) {
+            Some(answer) => answer.send(approve).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Stop waiting for a job (timed out or cancelled)
+    pub fn remove(&self, job_id: &str) {
+        self.pending.lock().unwrap().remove(job_id);
+    }
+
+    /// Jobs currently waiting, sorted by id
+    pub fn waiting_jobs(&self) -> Vec<String> {
+        let mut jobs: Vec<String> = self.pending.lock().unwrap().keys().cloned().collect();
+        jobs.sort();
+        jobs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_delivers_answer() {
+        let pending = PendingConfirmations::new();
+        let answer = pending.register("docs");
+
+        assert_eq!(pending.waiting_jobs(), vec!["docs".to_string()]);
+        assert!(pending.resolve("docs", true));
+        assert!(answer.await.unwrap());
+        assert!(pending.waiting_jobs().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_unknown_job() {
+        let pending = PendingConfirmations::new();
+        let _answer = pending.register("docs");
+        pending.remove("docs");
+
+        assert!(!pending.resolve("docs", true), "Removed requests cannot be answered");
+        assert!(!pending.resolve("media", false));
+    }
+}
@@ -1,19 +1,40 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
+use std::time::Duration;
 use tracing::{debug, info};
 
 pub use super::changes::{ConfigChangeType, ConfigChanges, ModifiedJob};
-use crate::config::BackupJob;
-use crate::state::{JobState, JobStatus, StateManager};
+use super::clock::IntervalClock;
+use crate::config::{BackupJob, Schedule};
+use crate::state::{IntervalAnchor, JobState, JobStatus, StateManager};
 
 pub struct Scheduler {
     state_manager: std::sync::Arc<StateManager>,
+    intervals: IntervalClock,
 }
 
 impl Scheduler {
     pub fn new(state_manager: std::sync::Arc<StateManager>) -> Self {
-        Self { state_manager }
+        Self { state_manager, intervals: IntervalClock::new() }
+    }
+
+    /// A skipped run counts as a run, so the job waits for its next slot
+    fn last_run(js: &JobState) -> Option<DateTime<Utc>> {
+        js.last_run.max(js.last_skipped)
+    }
+
+    /// Time left until an interval job is due, measured on the monotonic clock; None for
+    /// other schedules and for jobs that never ran
+    fn interval_remaining(&self, job: &BackupJob, js: &JobState) -> Option<(Duration, IntervalAnchor)> {
+        let Schedule::Interval { seconds } = job.schedule else {
+            return None;
+        };
+        let run = Self::last_run(js)?;
+
+        let elapsed = self.intervals.elapsed(&job.id, run, js.interval_anchor.as_ref());
+        let anchor = IntervalAnchor { run, elapsed_seconds: elapsed.as_secs(), measured_at: Utc::now() };
+        Some((Duration::from_secs(seconds).saturating_sub(elapsed), anchor))
     }
 
     /// Calculate next run time for all jobs
@@ -21,21 +42,38 @@ impl Scheduler {
         for job in jobs {
             let state = self.state_manager.read().await;
             let job_state = state.get_job(&job.id);
-            let last_run = job_state.and_then(|js| js.last_run);
+            // A run stamped in the future by a clock set back later counts as just now
+            let last_run = job_state
+                .and_then(Self::last_run)
+                .map(|last| last.min(Utc::now()));
+            let interval = job_state.and_then(|js| self.interval_remaining(job, js));
             let current_status = job_state.map(|js| js.status.clone());
+            let deferred = job_state.is_some_and(|js| js.deferred_since.is_some());
+            let retrying = job_state.is_some_and(|js| js.failed_attempts > 0);
             drop(state);
 
+            // A deferred or failed run keeps its retry time
+            if deferred || retrying {
+                debug!("Keeping retry time of job: {}", job.id);
+                continue;
+            }
+
             // Skip calculation for running jobs
             if let Some(JobStatus::Running { .. }) = current_status {
                 debug!("Skipping next_run calculation for running job: {}", job.id);
                 continue;
             }
 
-            let next_duration = job.schedule.next_run_duration(last_run);
+            // Interval jobs count real time passed, whatever the system clock did meanwhile
+            let next_duration = match interval {
+                Some((remaining, _)) => chrono::Duration::from_std(remaining).unwrap_or(chrono::Duration::MAX),
+                None => job.schedule.next_run_duration(last_run),
+            };
             let next_run = Utc::now() + next_duration;
 
             self.state_manager.update_job_state(&job.id, |js| {
                 js.next_run = Some(next_run);
+                js.interval_anchor = interval.map(|(_, anchor)| anchor);
                 debug!("Job {} scheduled for {}", job.id, next_run);
             }).await?;
         }
@@ -43,6 +81,38 @@ impl Scheduler {
         Ok(())
     }
 
+    /// Bring the time measured for interval jobs up to date in memory. Every save of the state
+    /// file carries it, so a restart only goes by the wall clock from the last save on.
+    pub async fn refresh_interval_anchors(&self, jobs: &[BackupJob]) {
+        let mut state = self.state_manager.write().await;
+        for job in jobs {
+            if let Some(js) = state.get_job_mut(&job.id)
+                && let Some((_, anchor)) = self.interval_remaining(job, js)
+            {
+                js.interval_anchor = Some(anchor);
+            }
+        }
+    }
+
+    /// Calculate next runs again after the system clock changed. Jobs that are already due
+    /// stay due, so a run missed during a jump forward still happens, once. Interval jobs
+    /// that ran are always recalculated, since the jump did not bring them closer.
+    pub async fn recalculate_next_runs(&self, jobs: &[BackupJob]) -> Result<()> {
+        let now = Utc::now();
+        let pending: Vec<BackupJob> = {
+            let state = self.state_manager.read().await;
+            jobs.iter()
+                .filter(|job| state.get_job(&job.id).is_some_and(|js| {
+                    js.next_run.is_some_and(|next| next > now)
+                        || (matches!(job.schedule, Schedule::Interval { .. }) && Self::last_run(js).is_some())
+                }))
+                .cloned()
+                .collect()
+        };
+
+        self.calculate_next_runs(&pending).await
+    }
+
     /// Get jobs that are ready to run
     pub async fn get_ready_jobs(&self, jobs: &[BackupJob]) -> Result<Vec<BackupJob>> {
         let mut ready_jobs = Vec::new();
@@ -54,7 +124,12 @@ impl Scheduler {
             if let Some(job_state) = state.get_job(&job.id) {
                 // Only run if idle and next_run has passed
                 if matches!(job_state.status, JobStatus::Idle) {
-                    if let Some(next_run) = job_state.next_run {
+                    let retry_time = job_state.deferred_since.is_some() || job_state.failed_attempts > 0;
+                    if let Some((remaining, _)) = self.interval_remaining(job, job_state).filter(|_| !retry_time) {
+                        if remaining.is_zero() {
+                            ready_jobs.push(job.clone());
+                        }
+                    } else if let Some(next_run) = job_state.next_run {
                         if next_run <= now {
                             ready_jobs.push(job.clone());
                         }
@@ -189,7 +264,7 @@ impl Scheduler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Schedule;
+    use crate::config::{BackupMode, JobHooks, JobKind, NameConflicts, Schedule, SystemStateConfig, WhenBusy};
     use std::path::PathBuf;
     use tempfile::TempDir;
 
@@ -210,9 +285,133 @@ mod tests {
             target: PathBuf::from(format!("C:\\target_{}", id)),
             schedule: Schedule::Interval { seconds: 3600 },
             description: String::new(),
+            retention_count: None,
+            retention_max_total_gb: None,
+            retention_max_age_days: None,
+            retry: None,
+            max_bytes_per_sec: None,
+            shadow_copy: false,
+            backup_privilege: false,
+            notify: None,
+            dry_run: false,
+            state_path: None,
+            replicas: Vec::new(),
+            exclude_profiles: Vec::new(),
+            exclude: Vec::new(),
+            name_conflicts: NameConflicts::Off,
+            normalize_names: None,
+            max_path_length: None,
+            kind: JobKind::Files,
+            system_state: SystemStateConfig::default(),
+            dump: None,
+            wsl: None,
+            docker: None,
+            app: None,
+            lock_files: Vec::new(),
+            hooks: JobHooks::default(),
+            busy_processes: Vec::new(),
+            when_busy: WhenBusy::Warn,
+            mode: BackupMode::Full,
+            archive: None,
         }
     }
 
+    #[tokio::test]
+    async fn test_deferred_job_keeps_retry_time() {
+        let (scheduler, _temp_dir) = create_test_scheduler().await;
+        let jobs = vec![create_test_job("busy")];
+        scheduler.initialize_jobs(&jobs).await.unwrap();
+
+        let retry = Utc::now() + chrono::Duration::minutes(15);
+        scheduler.state_manager.update_job_state("busy", |js| {
+            js.next_run = Some(retry);
+            js.deferred_since = Some(Utc::now());
+        }).await.unwrap();
+
+        scheduler.calculate_next_runs(&jobs).await.unwrap();
+
+        let state = scheduler.state_manager.read().await;
+        assert_eq!(state.get_job("busy").unwrap().next_run, Some(retry));
+    }
+
+    #[tokio::test]
+    async fn test_clock_set_back_does_not_stall_jobs() {
+        let (scheduler, _temp_dir) = create_test_scheduler().await;
+        let jobs = vec![create_test_job("docs"), create_test_job("due")];
+        scheduler.initialize_jobs(&jobs).await.unwrap();
+
+        // Stamped before the clock went back a week
+        let future = Utc::now() + chrono::Duration::days(7);
+        scheduler.state_manager.update_job_state("docs", |js| {
+            js.last_run = Some(future);
+            js.next_run = Some(future + chrono::Duration::hours(1));
+        }).await.unwrap();
+        let due = Utc::now() - chrono::Duration::minutes(1);
+        scheduler.state_manager.update_job_state("due", |js| js.next_run = Some(due)).await.unwrap();
+
+        scheduler.recalculate_next_runs(&jobs).await.unwrap();
+
+        let state = scheduler.state_manager.read().await;
+        let next_run = state.get_job("docs").unwrap().next_run.unwrap();
+        assert!(next_run <= Utc::now() + chrono::Duration::hours(1), "next run {} is still a week away", next_run);
+        assert_eq!(state.get_job("due").unwrap().next_run, Some(due));
+    }
+
+    #[tokio::test]
+    async fn test_interval_counts_time_measured_before_restart() {
+        let (scheduler, _temp_dir) = create_test_scheduler().await;
+        let jobs = vec![create_test_job("docs")];
+        scheduler.initialize_jobs(&jobs).await.unwrap();
+
+        // The wall clock says two hours passed, but only ten minutes were measured before
+        // the clock was set forward and the service restarted
+        let run = Utc::now() - chrono::Duration::hours(2);
+        scheduler.state_manager.update_job_state("docs", |js| {
+            js.last_run = Some(run);
+            js.next_run = Some(run + chrono::Duration::hours(1));
+            js.interval_anchor = Some(IntervalAnchor { run, elapsed_seconds: 600, measured_at: Utc::now() });
+        }).await.unwrap();
+
+        assert!(scheduler.get_ready_jobs(&jobs).await.unwrap().is_empty());
+
+        scheduler.recalculate_next_runs(&jobs).await.unwrap();
+        let state = scheduler.state_manager.read().await;
+        let js = state.get_job("docs").unwrap();
+        let left = js.next_run.unwrap() - Utc::now();
+        assert!(left > chrono::Duration::minutes(49) && left <= chrono::Duration::minutes(50), "next run in {}", left);
+        assert_eq!(js.interval_anchor.unwrap().run, run);
+        let measured_at = js.interval_anchor.unwrap().measured_at;
+        drop(state);
+
+        // Refreshed between saves, without the wall clock's two hours
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        scheduler.refresh_interval_anchors(&jobs).await;
+        let anchor = scheduler.state_manager.read().await.get_job("docs").unwrap().interval_anchor.unwrap();
+        assert!(anchor.measured_at > measured_at);
+        assert_eq!(anchor.elapsed_seconds, 600);
+    }
+
+    #[tokio::test]
+    async fn test_retrying_job_keeps_retry_time() {
+        let (scheduler, _temp_dir) = create_test_scheduler().await;
+        let jobs = vec![create_test_job("flaky")];
+        scheduler.initialize_jobs(&jobs).await.unwrap();
+
+        let retry = Utc::now() + chrono::Duration::minutes(5);
+        scheduler.state_manager.update_job_state("flaky", |js| {
+            js.next_run = Some(retry);
+            js.failed_attempts = 1;
+        }).await.unwrap();
+
+        scheduler.calculate_next_runs(&jobs).await.unwrap();
+        assert_eq!(scheduler.state_manager.read().await.get_job("flaky").unwrap().next_run, Some(retry));
+
+        // Once the retry has run, the schedule takes over again
+        scheduler.state_manager.update_job_state("flaky", |js| js.failed_attempts = 0).await.unwrap();
+        scheduler.calculate_next_runs(&jobs).await.unwrap();
+        assert_ne!(scheduler.state_manager.read().await.get_job("flaky").unwrap().next_run, Some(retry));
+    }
+
     #[tokio::test]
     async fn test_no_duplicate_jobs_succeeds() {
         let (scheduler, _temp_dir) = create_test_scheduler().await;
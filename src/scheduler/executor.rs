@@ -1,26 +1,81 @@
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{bail, Result};
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
-use crate::config::{BackupJob, DEFAULT_RETENTION_COUNT};
-use crate::core::BackupOrchestrator;
-use crate::state::{JobStatus, StateManager};
+use crate::config::{
+    BackupJob, BackupMode, ConfirmationTimeout, DiskFullConfig, Durability, FirstRunConfig, JobKind, LargeRunConfig, NetworkTargetConfig, NotificationConfig, PullConfig, ReplicationConfig,
+    StorageConfig, VerifyConfig, WhenBusy, DEFAULT_RETENTION_COUNT,
+};
+use crate::core::chain::read_token;
+use crate::core::{
+    calculate_dir_size, docker_volume_export, dry_run, dump_dir, export_system_state, held_lock_file, pull_latest, run_hook, run_post_hook, run_result_hook, relocate_target, replica_location, volume_id,
+    pause_volume_users, run_dump, running_processes, snapshot_source, unpause, wsl_export, BackupOrchestrator, BandwidthLimiter, CopyError, CopyFailure, CopyOptions, CopyTransform, PullSource,
+    JobResult, Replicator, RetentionRules, SourceSnapshot, TransformChain, validate_source,
+};
+use crate::observability::{format_bytes, format_duration, raise_alert, send_run_report, should_notify, RUN_SUMMARY_TARGET};
+use crate::platform::{require_full_mode, FaultInjector, PrivateDir};
+use crate::scheduler::{estimate_source, is_adhoc_job, project_run, PendingConfirmations, TargetLocks};
+use crate::storage::{BackendFactory, BackendRegistry, TargetUrl};
+use crate::state::{
+    BackupMetadata, ConfirmationRequest, FirstRunReview, HistoryStore, JobStatus, ReplicaState, ReplicaStatus, RunOutcome, RunRecord, RunResult,
+    StateManager,
+};
+
+/// Latest runs of a job read to project how long its next run takes
+const PROJECTION_HISTORY: usize = 20;
 
 pub struct JobExecutor {
     pub(crate) orchestrator: BackupOrchestrator,
     pub(crate) state_manager: Arc<StateManager>,
     pub(crate) retention_count: usize,
+    pub(crate) durability: Durability,
+    pub(crate) replication: ReplicationConfig,
+    pub(crate) bandwidth: Arc<BandwidthLimiter>,
+    pub(crate) large_run: Option<LargeRunConfig>,
+    pub(crate) first_run: Option<FirstRunConfig>,
+    pub(crate) confirmations: Arc<PendingConfirmations>,
+    pub(crate) disk_full: DiskFullConfig,
+    pub(crate) verify: VerifyConfig,
+    pub(crate) manifest_checksums: bool,
+    pub(crate) network_targets: NetworkTargetConfig,
+    pub(crate) notifications: Option<NotificationConfig>,
+    pub(crate) transforms: TransformChain,
+    pub(crate) backends: Arc<BackendRegistry>,
+    pub(crate) pull: PullConfig,
+    pub(crate) storage: StorageConfig,
+    pub(crate) autotune: bool,
+    pub(crate) faults: Option<Arc<FaultInjector>>,
+    pub(crate) target_locks: Arc<TargetLocks>,
 }
 
 // Make executor cloneable for spawning
 impl Clone for JobExecutor {
     fn clone(&self) -> Self {
         Self {
-            orchestrator: BackupOrchestrator::new(),
+            orchestrator: self.build_orchestrator(),
             state_manager: self.state_manager.clone(),
             retention_count: self.retention_count,
+            durability: self.durability,
+            replication: self.replication,
+            bandwidth: self.bandwidth.clone(),
+            large_run: self.large_run,
+            first_run: self.first_run,
+            confirmations: self.confirmations.clone(),
+            disk_full: self.disk_full,
+            verify: self.verify,
+            manifest_checksums: self.manifest_checksums,
+            network_targets: self.network_targets.clone(),
+            notifications: self.notifications.clone(),
+            transforms: self.transforms.clone(),
+            backends: self.backends.clone(),
+            pull: self.pull.clone(),
+            storage: self.storage.clone(),
+            autotune: self.autotune,
+            faults: self.faults.clone(),
+            target_locks: self.target_locks.clone(),
         }
     }
 }
@@ -31,6 +86,24 @@ impl JobExecutor {
             orchestrator: BackupOrchestrator::new(),
             state_manager,
             retention_count: DEFAULT_RETENTION_COUNT,
+            durability: Durability::default(),
+            replication: ReplicationConfig::default(),
+            bandwidth: Arc::new(BandwidthLimiter::default()),
+            large_run: None,
+            first_run: None,
+            confirmations: Arc::new(PendingConfirmations::new()),
+            disk_full: DiskFullConfig::default(),
+            verify: VerifyConfig::default(),
+            manifest_checksums: true,
+            network_targets: NetworkTargetConfig::default(),
+            notifications: None,
+            transforms: TransformChain::default(),
+            backends: Arc::new(BackendRegistry::new()),
+            pull: PullConfig::default(),
+            storage: StorageConfig::default(),
+            autotune: false,
+            faults: None,
+            target_locks: Arc::new(TargetLocks::new()),
         }
     }
 
@@ -40,6 +113,24 @@ impl JobExecutor {
             orchestrator: BackupOrchestrator::new(),
             state_manager,
             retention_count,
+            durability: Durability::default(),
+            replication: ReplicationConfig::default(),
+            bandwidth: Arc::new(BandwidthLimiter::default()),
+            large_run: None,
+            first_run: None,
+            confirmations: Arc::new(PendingConfirmations::new()),
+            disk_full: DiskFullConfig::default(),
+            verify: VerifyConfig::default(),
+            manifest_checksums: true,
+            network_targets: NetworkTargetConfig::default(),
+            notifications: None,
+            transforms: TransformChain::default(),
+            backends: Arc::new(BackendRegistry::new()),
+            pull: PullConfig::default(),
+            storage: StorageConfig::default(),
+            autotune: false,
+            faults: None,
+            target_locks: Arc::new(TargetLocks::new()),
         }
     }
 
@@ -48,70 +139,1314 @@ impl JobExecutor {
         self.retention_count = retention_count;
     }
 
+    /// Update replica retry policy (called when config changes)
+    pub fn set_replication(&mut self, replication: ReplicationConfig) {
+        self.replication = replication;
+    }
+
+    /// Update durability level (called when config changes)
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    /// Update post-copy verification (called when config changes)
+    pub fn set_verify(&mut self, verify: VerifyConfig) {
+        self.verify = verify;
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    /// Update manifest checksums (called when config changes)
+    pub fn set_manifest_checksums(&mut self, manifest_checksums: bool) {
+        self.manifest_checksums = manifest_checksums;
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    /// Update waiting for offline network share targets (called when config changes)
+    pub fn set_network_targets(&mut self, network_targets: NetworkTargetConfig) {
+        self.network_targets = network_targets;
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    /// Update email notifications (called when config changes)
+    pub fn set_notifications(&mut self, notifications: Option<NotificationConfig>) {
+        self.notifications = notifications;
+    }
+
+    /// Register a transform applied to every file copied by backups
+    pub fn add_copy_transform(&mut self, transform: Arc<dyn CopyTransform>) {
+        self.transforms.push(transform);
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    /// Update settings of the built-in storage backends (called when config changes)
+    pub fn set_storage(&mut self, storage: &StorageConfig) {
+        self.backends.configure(storage, self.state_manager.secrets());
+        self.storage = storage.clone();
+    }
+
+    /// Register a storage backend for `scheme://` replica targets
+    pub fn register_storage_backend(&self, scheme: &str, factory: impl BackendFactory + 'static) {
+        self.backends.register(scheme, factory);
+    }
+
+    /// Update credentials for `keephive://` sources (called when config changes)
+    pub fn set_pull(&mut self, pull: PullConfig) {
+        self.pull = pull;
+    }
+
+    /// Update copy autotuning (called when config changes)
+    pub fn set_autotune(&mut self, autotune: bool) {
+        self.autotune = autotune;
+    }
+
+    /// Fail or slow down the file copies of every backup as `faults` plans
+    pub fn set_faults(&mut self, faults: Arc<FaultInjector>) {
+        self.faults = Some(faults);
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    /// Update the large run check (called when config changes)
+    pub fn set_large_run(&mut self, large_run: Option<LargeRunConfig>) {
+        self.large_run = large_run;
+    }
+
+    /// Update the first run review of new jobs (called when config changes)
+    pub fn set_first_run(&mut self, first_run: Option<FirstRunConfig>) {
+        self.first_run = first_run;
+    }
+
+    /// Update disk-full handling (called when config changes)
+    pub fn set_disk_full(&mut self, disk_full: DiskFullConfig) {
+        self.disk_full = disk_full;
+    }
+
+    /// Answer a pending large run confirmation; returns false if the job is not waiting
+    pub fn resolve_confirmation(&self, job_id: &str, approve: bool) -> bool {
+        self.confirmations.resolve(job_id, approve)
+    }
+
+    /// Answer the first run estimate of a job: confirming lets its next run copy, rejecting
+    /// has the next run estimate again. Returns false if no estimate is waiting.
+    pub async fn answer_first_run(&self, job_id: &str, approve: bool) -> Result<bool> {
+        let waiting = self.state_manager.read().await
+            .get_job(job_id)
+            .and_then(|js| js.first_run_review.as_ref())
+            .is_some_and(|review| !review.confirmed);
+        if !waiting {
+            return Ok(false);
+        }
+
+        self.state_manager.update_job_state(job_id, |js| {
+            if approve {
+                if let Some(review) = &mut js.first_run_review {
+                    review.confirmed = true;
+                }
+            } else {
+                js.first_run_review = None;
+            }
+        }).await?;
+
+        Ok(true)
+    }
+
+    /// Share a bandwidth limiter between this executor's backups and replica copies
+    pub fn set_bandwidth_limiter(&mut self, bandwidth: Arc<BandwidthLimiter>) {
+        self.bandwidth = bandwidth;
+        self.orchestrator = self.build_orchestrator();
+    }
+
+    fn build_orchestrator(&self) -> BackupOrchestrator {
+        BackupOrchestrator::with_bandwidth_limiter(self.durability, self.bandwidth.clone())
+            .with_verification(self.verify)
+            .with_checksums(self.manifest_checksums)
+            .with_transforms(self.transforms.clone())
+            .with_faults(self.faults.clone())
+            .with_network_targets(self.network_targets.clone())
+    }
+
     pub async fn execute_job(
         &self,
         job: &BackupJob,
         cancellation: CancellationToken,
     ) -> Result<()> {
         info!("Executing job: {}", job.id);
+        let started_at = Utc::now();
+        let (deferred_since, failed_attempts) = self.state_manager.read().await
+            .get_job(&job.id)
+            .map_or((None, 0), |js| (js.deferred_since, js.failed_attempts));
 
         // Update state to Running
         self.state_manager.update_job_state(&job.id, |js| {
             js.status = JobStatus::Running {
                 started_at: Utc::now(),
             };
+            js.queued_since = None;
+            js.deferred_since = None;
+            js.failed_attempts = 0;
             js.source = job.source.clone();
             js.target = job.target.clone();
         }).await?;
 
+        // A dry run writes nothing, so it only reports and never reaches the checks below
+        if job.dry_run && job.kind == JobKind::Files && PullSource::parse(&job.source).is_none() {
+            let reason = match dry_run(job, &RetentionRules::for_job(job, self.retention_count)).await {
+                Ok(report) => {
+                    info!("Dry run of job {}:\n  {}", job.id, report.lines().join("\n  "));
+                    format!("dry run: {}", report.summary())
+                }
+                Err(e) => {
+                    warn!("Dry run of job {} failed: {:#}", job.id, e);
+                    format!("dry run failed: {:#}", e)
+                }
+            };
+
+            self.state_manager.update_job_state(&job.id, |js| {
+                js.status = JobStatus::Idle;
+                js.last_skipped = Some(Utc::now());
+            }).await?;
+
+            self.record_run(job, started_at, RunOutcome::Skipped { reason }).await;
+            return Ok(());
+        }
+
+        // Pulled backups were sized on the machine that made them; system state has no source to size
+        if let Some(policy) = self.first_run
+            && job.kind == JobKind::Files
+            && !is_adhoc_job(&job.id)
+            && PullSource::parse(&job.source).is_none()
+            && let Some(reason) = self.review_first_run(job, policy).await?
+        {
+            info!("Job {} skipped: {}", job.id, reason);
+
+            self.state_manager.update_job_state(&job.id, |js| {
+                js.status = JobStatus::Idle;
+                js.last_skipped = Some(Utc::now());
+            }).await?;
+
+            self.record_run(job, started_at, RunOutcome::Skipped { reason }).await;
+            return Ok(());
+        }
+
+        if let Some(policy) = self.large_run
+            && job.kind == JobKind::Files
+            && PullSource::parse(&job.source).is_none()
+            && let Some(reason) = self.check_large_run(job, policy, &cancellation).await?
+        {
+            info!("Job {} skipped: {}", job.id, reason);
+
+            self.state_manager.update_job_state(&job.id, |js| {
+                js.status = JobStatus::Idle;
+                js.last_skipped = Some(Utc::now());
+            }).await?;
+
+            self.record_run(job, started_at, RunOutcome::Skipped { reason }).await;
+            return Ok(());
+        }
+
+        // Copying while the application writes its files would give an inconsistent backup
+        if let Some(lock) = held_lock_file(&job.source, &job.lock_files).await {
+            let reason = format!("Application still running ({} is in use)", lock.display());
+            info!("Job {} skipped: {}", job.id, reason);
+
+            self.state_manager.update_job_state(&job.id, |js| {
+                js.status = JobStatus::Idle;
+                js.last_skipped = Some(Utc::now());
+            }).await?;
+
+            self.record_run(job, started_at, RunOutcome::Skipped { reason }).await;
+            return Ok(());
+        }
+
+        let busy = running_processes(&job.busy_processes).await;
+        if !busy.is_empty() {
+            let busy = busy.join(", ");
+            let deferred_for = deferred_since.map_or(chrono::Duration::zero(), |since| Utc::now() - since);
+
+            match job.when_busy {
+                WhenBusy::Defer if deferred_for < busy_deferral_limit() => {
+                    let retry = Utc::now() + busy_retry_interval();
+                    info!("Job {} deferred until {}: {} running", job.id, retry.with_timezone(&Local).format("%H:%M"), busy);
+
+                    self.state_manager.update_job_state(&job.id, |js| {
+                        js.status = JobStatus::Idle;
+                        js.next_run = Some(retry);
+                        js.deferred_since = Some(deferred_since.unwrap_or(started_at));
+                    }).await?;
+
+                    return Ok(());
+                }
+                WhenBusy::Defer => warn!(
+                    "Job {}: {} still running after deferring for {} hours; files it has open may be inconsistent",
+                    job.id, busy, deferred_for.num_hours()
+                ),
+                WhenBusy::Warn => warn!("Job {}: {} running; files it has open may be inconsistent", job.id, busy),
+            }
+        }
+
+        self.warn_if_overrunning(job, started_at).await;
+
+        // A removable target may be back under another drive letter
+        let relocated = match self.locate_target(job).await {
+            Ok(relocated) => relocated,
+            Err(e) => {
+                error!("Job failed: {}: {}", job.id, e);
+                self.record_run(job, started_at, RunOutcome::Failed { error: e.to_string() }).await;
+                self.report_result(job, started_at, Err(&e)).await;
+                self.record_failure(job, &e, failed_attempts + 1, &cancellation).await?;
+
+                return Err(e);
+            }
+        };
+        let job = relocated.as_ref().unwrap_or(job);
+
+        // Jobs sharing a target write and prune it one at a time
+        let target_lock = match self.target_locks.try_lock(&job.target) {
+            Some(lock) => lock,
+            None => {
+                info!("Job {} waiting for another job writing to {}", job.id, job.target.display());
+                self.target_locks.lock(&job.target).await
+            }
+        };
+
         // Execute backup
-        let result = self.orchestrator.execute_backup(
-            &job.id,
-            &job.source,
-            &job.target,
-            cancellation,
-        ).await;
+        let mut result = self.run_backup(job, cancellation.clone()).await;
+
+        if let Err(e) = &result
+            && CopyError::classify(e) == CopyFailure::DiskFull
+            && !cancellation.is_cancelled()
+            && self.free_target_space(job).await
+        {
+            info!("Retrying job {} after emergency cleanup", job.id);
+
+            result = self.run_backup(job, cancellation.clone()).await;
+        }
+
+        let result = match result {
+            Ok(Some(metadata)) => Ok(metadata),
+            Ok(None) => {
+                let reason = "Already has the newest backup from the replica server".to_string();
+                info!("Job {} skipped: {}", job.id, reason);
+
+                self.state_manager.update_job_state(&job.id, |js| {
+                    js.status = JobStatus::Idle;
+                    js.last_skipped = Some(Utc::now());
+                }).await?;
+
+                self.record_run(job, started_at, RunOutcome::Skipped { reason }).await;
+                return Ok(());
+            }
+            Err(e) => Err(e),
+        };
+
+        let outcome = match &result {
+            Ok(metadata) => RunOutcome::Success {
+                backup_name: metadata.backup_name.clone(),
+                files_copied: metadata.files_copied,
+                bytes_copied: metadata.bytes_copied,
+                files_skipped: metadata.files_skipped,
+            },
+            Err(e) => RunOutcome::Failed { error: e.to_string() },
+        };
+
+        self.record_run(job, started_at, outcome).await;
+        self.report_result(job, started_at, result.as_ref()).await;
 
         match result {
             Ok(metadata) => {
+                // Remember the volume by the configured path, not a temporary drive letter
+                let target_volume = if relocated.is_none() { volume_id(&job.target) } else { None };
+
                 // Update state to Idle with successful backup
                 self.state_manager.update_job_state(&job.id, |js| {
                     js.status = JobStatus::Idle;
                     js.last_run = Some(Utc::now());
                     js.last_backup = Some(metadata.clone());
                     js.active_backup = None;
+                    js.first_run_review = None;
+                    if target_volume.is_some() {
+                        js.target_volume = target_volume;
+                    }
                 }).await?;
 
-                // Cleanup old backups using actual retention count from config
-                info!(
-                    "Cleaning up old backups for job {} (retention: {} backups)",
-                    job.id, self.retention_count
-                );
+                let retention_count = self.job_retention_count(job);
+
+                if let Some(retention_count) = retention_count {
+                    info!(
+                        "Cleaning up old backups for job {} (retention: {} backups)",
+                        job.id, retention_count
+                    );
+
+                    if let Err(e) = BackupOrchestrator::cleanup_old_backups(
+                        &job.target,
+                        &job.source,
+                        retention_count,
+                        Some(&metadata.backup_path),
+                    ).await {
+                        warn!("Failed to cleanup old backups for job {}: {}", job.id, e);
+                    }
+                }
+
+                if let Some(days) = job.retention_max_age_days {
+                    let max_age = std::time::Duration::from_secs(u64::from(days) * 86400);
+                    match BackupOrchestrator::cleanup_older_than(&job.target, &job.source, max_age, Some(&metadata.backup_path)).await {
+                        Ok(0) => {}
+                        Ok(removed) => info!("Removed {} backups of job {} older than {} days", removed, job.id, days),
+                        Err(e) => warn!("Failed to cleanup expired backups for job {}: {}", job.id, e),
+                    }
+                }
 
-                if let Err(e) = BackupOrchestrator::cleanup_old_backups(
-                    &job.target,
-                    self.retention_count,
-                ).await {
-                    warn!("Failed to cleanup old backups for job {}: {}", job.id, e);
+                if let Some(gb) = job.retention_max_total_gb {
+                    match BackupOrchestrator::cleanup_to_size(&job.target, &job.source, gb.saturating_mul(1024 * 1024 * 1024), Some(&metadata.backup_path)).await {
+                        Ok(0) => {}
+                        Ok(removed) => info!("Removed {} backups of job {} to stay within {} GB", removed, job.id, gb),
+                        Err(e) => warn!("Failed to cleanup backups over the size budget for job {}: {}", job.id, e),
+                    }
                 }
 
                 info!("Job completed successfully: {}", job.id);
+                drop(target_lock);
+
+                if !job.replicas.is_empty() {
+                    self.replicate_backup(job, &metadata.backup_path, retention_count, cancellation).await;
+                }
+
                 Ok(())
             }
             Err(e) => {
                 error!("Job failed: {}: {}", job.id, e);
+                self.record_failure(job, &e, failed_attempts + 1, &cancellation).await?;
 
-                // Update state to Failed
-                self.state_manager.update_job_state(&job.id, |js| {
+                Err(e)
+            }
+        }
+    }
+
+    /// Mark a failed run in the state: Failed, or Idle with a retry time while the job's
+    /// retry policy allows another attempt. A cancelled run is not retried.
+    async fn record_failure(
+        &self,
+        job: &BackupJob,
+        error: &anyhow::Error,
+        failed_attempts: u32,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let retry = job.retry
+            .filter(|policy| failed_attempts < policy.max_attempts && !cancellation.is_cancelled())
+            .map(|policy| Utc::now() + chrono::Duration::from_std(policy.retry_delay(failed_attempts)).unwrap_or_default());
+
+        if let (Some(retry), Some(policy)) = (retry, job.retry) {
+            info!(
+                "Retrying job {} in {} at {} (attempt {} of {})",
+                job.id, format_duration(policy.retry_delay(failed_attempts)),
+                retry.with_timezone(&Local).format("%H:%M:%S"), failed_attempts + 1, policy.max_attempts
+            );
+        }
+
+        self.state_manager.update_job_state(&job.id, |js| {
+            match retry {
+                Some(retry) => {
+                    js.status = JobStatus::Idle;
+                    js.next_run = Some(retry);
+                    js.failed_attempts = failed_attempts;
+                }
+                None => {
                     js.status = JobStatus::Failed {
-                        error: e.to_string(),
+                        error: error.to_string(),
                         timestamp: Utc::now(),
                     };
-                    js.active_backup = None;
+                }
+            }
+            js.active_backup = None;
+        }).await
+    }
+
+    /// Run the backup between the job's pre and post hooks
+    async fn run_backup(&self, job: &BackupJob, cancellation: CancellationToken) -> Result<Option<BackupMetadata>> {
+        if let Some(pre) = &job.hooks.pre {
+            run_hook(job, "pre", pre).await?;
+        }
+
+        // The post hook undoes what the pre hook did (e.g. resumes saving), so it runs
+        // regardless, also when the service aborts the run
+        let mut post_hook = job.hooks.post.as_deref().map(|post| PostHook::new(job, post));
+
+        let result = self.back_up_source(job, cancellation.clone()).await;
+
+        if let Some(post_hook) = &mut post_hook {
+            let outcome = match &result {
+                Ok(Some(_)) => "success",
+                Ok(None) => "skipped",
+                Err(_) if cancellation.is_cancelled() => "cancelled",
+                Err(_) => "failed",
+            };
+            if let Some(hook) = post_hook.spawn(outcome) {
+                let _ = hook.await;
+            }
+        }
+
+        result
+    }
+
+    /// Back up the job's source, or pull from a replica server for `keephive://` sources.
+    ///
+    /// None means there was nothing new to pull.
+    async fn back_up_source(&self, job: &BackupJob, cancellation: CancellationToken) -> Result<Option<BackupMetadata>> {
+        match job.kind {
+            JobKind::SystemState => return self.run_system_state_backup(job, cancellation).await.map(Some),
+            JobKind::DatabaseDump | JobKind::WslDistro | JobKind::DockerVolume => {
+                return self.run_dump_backup(job, cancellation).await.map(Some);
+            }
+            JobKind::Files => {}
+        }
+
+        let Some(source) = PullSource::parse(&job.source) else {
+            let mut options = CopyOptions::for_job(job);
+            if self.autotune {
+                options.autotune = true;
+                options.tuning = self.state_manager.read().await.learned_tuning(&job.source, &job.target);
+            }
+
+            // Without a snapshot, locked files are skipped as usual. The snapshot path says
+            // nothing about where the target is, so the job's own source is checked first.
+            let snapshot = if job.shadow_copy {
+                validate_source(&job.source, &job.target).await?;
+                snapshot_source(&job.source).await
+                    .inspect_err(|e| warn!("No shadow copy for job {}, copying the live files: {:#}", job.id, e))
+                    .ok()
+            } else {
+                None
+            };
+            let source = snapshot.as_ref().map_or(job.source.as_path(), SourceSnapshot::path);
+
+            let metadata = self.orchestrator.execute_backup_with(&job.id, source, &job.target, &options, cancellation).await;
+            drop(snapshot);
+            let metadata = metadata?;
+
+            if let Some(tuning) = metadata.tuning {
+                self.state_manager.write().await.learn_tuning(&job.source, &job.target, tuning);
+                if let Err(e) = self.state_manager.save().await {
+                    warn!("Failed to save copy tuning for job {}: {}", job.id, e);
+                }
+            }
+
+            return Ok(Some(metadata));
+        };
+
+        let token = read_token(&self.pull.token_env)?;
+        let pulled = pull_latest(&source, &job.target, &token, &self.bandwidth, self.durability, cancellation).await?;
+
+        if let Some(metadata) = &pulled
+            && let Err(e) = self.state_manager.usage().record(&job.id, "keephive", 0, metadata.bytes_copied).await
+        {
+            warn!("Failed to record transfer usage for job {}: {}", job.id, e);
+        }
+
+        Ok(pulled)
+    }
+
+    /// Export registry hives and system files into a staging folder and back that up
+    async fn run_system_state_backup(&self, job: &BackupJob, cancellation: CancellationToken) -> Result<BackupMetadata> {
+        // Shadow copies are not available in containers
+        require_full_mode("A system-state job")?;
+
+        // Other accounts must not read the hives or plant links where they are written
+        let staging = PrivateDir::create("keephive_system_state")?;
+        let options = CopyOptions::for_job(job);
+
+        let result = match export_system_state(&job.system_state, staging.path()).await {
+            Ok(()) => self.orchestrator.execute_backup_with(&job.id, staging.path(), &job.target, &options, cancellation).await,
+            Err(e) => Err(e),
+        };
+
+        // The hives hold password hashes and must not stay around outside the backup
+        let staged = staging.path().to_path_buf();
+        if let Err(e) = staging.remove().await {
+            warn!("Failed to remove system state staging folder {}: {}", staged.display(), e);
+        }
+
+        result
+    }
+
+    /// Run the job's dump or export command into a staging folder and back up what it wrote
+    async fn run_dump_backup(&self, job: &BackupJob, cancellation: CancellationToken) -> Result<BackupMetadata> {
+        let dump = match (job.kind, &job.dump, &job.wsl, &job.docker) {
+            (JobKind::DatabaseDump, Some(dump), _, _) => dump.clone(),
+            (JobKind::WslDistro, _, Some(wsl), _) => {
+                require_full_mode("A wsl-distro job")?;
+                wsl_export(wsl)?
+            }
+            (JobKind::DockerVolume, _, _, Some(docker)) => docker_volume_export(docker)?,
+            _ => bail!("Job {} has no settings for its kind (dump, wsl or docker)", job.id),
+        };
+        let dir = dump_dir(&dump)?;
+        let options = CopyOptions::for_job(job);
+
+        // Containers writing to the volume are frozen for the export, not stopped
+        let paused = match &job.docker {
+            Some(docker) if job.kind == JobKind::DockerVolume && !docker.keep_running => {
+                pause_volume_users(&docker.volume).await?
+            }
+            _ => Vec::new(),
+        };
+        let dumped = run_dump(&dump, dir.path(), &cancellation).await;
+        unpause(&paused).await;
+
+        let result = match dumped {
+            Ok(outcome) => self.orchestrator.execute_backup_with(&job.id, dir.path(), &job.target, &options, cancellation).await
+                .map(|mut metadata| {
+                    metadata.dump = Some(outcome);
+                    metadata
+                }),
+            Err(e) => Err(e),
+        };
+
+        // Dumps can be large and may hold sensitive data; only the backup keeps them
+        let dumped_to = dir.path().to_path_buf();
+        if let Err(e) = dir.remove().await {
+            warn!("Failed to remove dump folder {}: {}", dumped_to.display(), e);
+        }
+
+        result
+    }
+
+    /// The job with its target moved to wherever its volume is mounted now, if it moved
+    async fn locate_target(&self, job: &BackupJob) -> Result<Option<BackupJob>> {
+        let known = self.state_manager.read().await
+            .get_job(&job.id)
+            .and_then(|js| js.target_volume.clone());
+
+        let Some(known) = known else {
+            return Ok(None);
+        };
+
+        let Some(target) = relocate_target(&job.target, &known)? else {
+            return Ok(None);
+        };
+
+        warn!(
+            "Target volume {} of job {} moved from {}, backing up to {}",
+            known,
+            job.id,
+            known.root.display(),
+            target.display()
+        );
+
+        Ok(Some(BackupJob { target, ..job.clone() }))
+    }
+
+    /// Emergency cleanup after the target filled up; returns true if anything was removed
+    async fn free_target_space(&self, job: &BackupJob) -> bool {
+        let Some(keep) = self.disk_full.emergency_retention else {
+            warn!(
+                "Target of job {} is full (set on_disk_full.emergency_retention to free space automatically)",
+                job.id
+            );
+            return false;
+        };
+
+        let keep = keep.max(1);
+        warn!("Target of job {} is full, pruning to {} backups", job.id, keep);
+
+        let partial = BackupOrchestrator::remove_partial_backups(&job.target).await
+            .unwrap_or_else(|e| {
+                warn!("Failed to remove partial backups for job {}: {}", job.id, e);
+                0
+            });
+
+        let last_backup = self.state_manager.read().await
+            .get_job(&job.id)
+            .and_then(|js| js.last_backup.as_ref())
+            .map(|backup| backup.backup_path.clone());
+
+        let old = BackupOrchestrator::cleanup_old_backups(&job.target, &job.source, keep, last_backup.as_deref()).await
+            .unwrap_or_else(|e| {
+                warn!("Emergency cleanup failed for job {}: {}", job.id, e);
+                0
+            });
+
+        partial + old > 0
+    }
+
+    /// Warn when recent runs suggest this one will still be copying when the next is due,
+    /// so slow targets and growing sources show up before runs start piling up
+    async fn warn_if_overrunning(&self, job: &BackupJob, started_at: chrono::DateTime<Utc>) {
+        if is_adhoc_job(&job.id) {
+            return;
+        }
+
+        let records = match HistoryStore::read_recent(self.state_manager.history_path(), Some(&job.id), PROJECTION_HISTORY).await {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("Could not read run history of job {}: {}", job.id, e);
+                return;
+            }
+        };
+        let Some(projection) = project_run(&records) else {
+            return;
+        };
+
+        let until_next = job.schedule.next_run_duration(Some(started_at)).to_std().unwrap_or_default();
+        if projection.duration <= until_next {
+            return;
+        }
+
+        let hint = match job.mode {
+            BackupMode::Full => "a longer schedule interval, \"mode\": \"incremental\", or narrowing the source",
+            BackupMode::Incremental => "a longer schedule interval or narrowing the source",
+        };
+        warn!(
+            "Job {} may still be running when its next run is due in {}: the last run copied {} and recent runs averaged {}/s, about {}. Consider {}",
+            job.id,
+            format_duration(until_next),
+            format_bytes(projection.bytes),
+            format_bytes(projection.bytes_per_sec as u64),
+            format_duration(projection.duration),
+            hint
+        );
+    }
+
+    /// Record a run whose task ended without reporting its result, such as one that
+    /// panicked. A job left Running is marked Failed with `error`; it is not retried, since
+    /// the next attempt would most likely end the same way.
+    pub async fn record_crash(&self, job: &BackupJob, error: &str) -> Result<()> {
+        let started_at = match self.state_manager.read().await.get_job(&job.id).map(|js| &js.status) {
+            Some(JobStatus::Running { started_at }) => *started_at,
+            _ => return Ok(()),
+        };
+
+        self.record_run(job, started_at, RunOutcome::Failed { error: error.to_string() }).await;
+
+        self.state_manager.update_job_state(&job.id, |js| {
+            js.status = JobStatus::Failed {
+                error: error.to_string(),
+                timestamp: Utc::now(),
+            };
+            js.active_backup = None;
+        }).await
+    }
+
+    async fn record_run(&self, job: &BackupJob, started_at: chrono::DateTime<Utc>, outcome: RunOutcome) {
+        let record = RunRecord {
+            job_id: job.id.clone(),
+            adhoc: is_adhoc_job(&job.id),
+            source: job.source.clone(),
+            target: job.target.clone(),
+            started_at,
+            finished_at: Utc::now(),
+            outcome,
+        };
+
+        log_run_summary(&record);
+        self.notify(job, &record);
+
+        if let Err(e) = self.state_manager.record_run(&record).await {
+            warn!("Failed to record run history for job {}: {}", job.id, e);
+        }
+
+        if !record.adhoc {
+            let result = RunResult::of(&record.outcome);
+            if let Err(e) = self.state_manager.update_job_state(&job.id, |js| js.push_result(result)).await {
+                warn!("Failed to record run result for job {}: {}", job.id, e);
+            }
+        }
+    }
+
+    /// Email the run's result if the job's triggers ask for it; sent in the background so
+    /// a slow mail server never holds up the next run
+    fn notify(&self, job: &BackupJob, record: &RunRecord) {
+        let Some(config) = &self.notifications else {
+            return;
+        };
+        if !should_notify(record, job.notify.unwrap_or(config.triggers)) {
+            return;
+        }
+
+        let config = config.clone();
+        let record = record.clone();
+        tokio::spawn(async move {
+            if let Err(e) = send_run_report(&config, &record).await {
+                warn!("Failed to send notification for job {}: {:#}", record.job_id, e);
+            }
+        });
+    }
+
+    /// Hand how the run ended to the job's `on_success_command` or `on_failure_command`
+    async fn report_result(&self, job: &BackupJob, started_at: chrono::DateTime<Utc>, result: Result<&BackupMetadata, &anyhow::Error>) {
+        let report = JobResult {
+            job_id: &job.id,
+            success: result.is_ok(),
+            started_at,
+            finished_at: Utc::now(),
+            error: result.err().map(|e| e.to_string()),
+            backup: result.ok(),
+        };
+
+        if let Err(e) = run_result_hook(job, &report).await {
+            warn!("Job {}: {:#}", job.id, e);
+        }
+    }
+
+    /// Hold back the copy of a job without backups until its estimate is confirmed or the
+    /// grace period ends. The first run only makes the estimate.
+    ///
+    /// Returns the reason when the run should be skipped.
+    async fn review_first_run(&self, job: &BackupJob, policy: FirstRunConfig) -> Result<Option<String>> {
+        let (has_backup, review) = self.state_manager.read().await
+            .get_job(&job.id)
+            .map_or((false, None), |js| (js.last_backup.is_some(), js.first_run_review.clone()));
+        if has_backup {
+            return Ok(None);
+        }
+
+        if let Some(review) = review {
+            if review.confirmed {
+                return Ok(None);
+            }
+            if let Some(proceed_after) = review.proceed_after
+                && Utc::now() >= proceed_after
+            {
+                info!("First run estimate of job {} was not answered within {}s, copying", job.id, policy.grace_seconds);
+                return Ok(None);
+            }
+
+            return Ok(Some(format!(
+                "first run estimate of {} files, {} waiting for keephive --confirm {}",
+                review.files, format_bytes(review.bytes), job.id
+            )));
+        }
+
+        let estimate = match estimate_source(job).await {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                warn!("Could not estimate first run of job {}: {}", job.id, e);
+                return Ok(None);
+            }
+        };
+
+        let estimated_at = Utc::now();
+        let proceed_after = (policy.grace_seconds > 0)
+            .then(|| estimated_at + chrono::Duration::seconds(policy.grace_seconds as i64));
+        let deadline = match proceed_after {
+            Some(time) => format!("copies from {} unless rejected", time.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+            None => "copies only once confirmed".to_string(),
+        };
+        warn!(
+            "First run of job {} from {} would copy {}. Confirm with keephive --confirm {} or reject with \
+             keephive --reject {}; the job {}",
+            job.id, job.source.display(), estimate.summary(), job.id, job.id, deadline
+        );
+
+        self.state_manager.update_job_state(&job.id, |js| {
+            js.first_run_review = Some(FirstRunReview {
+                files: estimate.files,
+                bytes: estimate.bytes,
+                estimated_at,
+                proceed_after,
+                confirmed: false,
+            });
+        }).await?;
+
+        Ok(Some(format!("first run estimate: {}", estimate.summary())))
+    }
+
+    /// Warn about a run above the size threshold and, if configured, wait for confirmation.
+    ///
+    /// Returns the reason when the run should be skipped.
+    async fn check_large_run(
+        &self,
+        job: &BackupJob,
+        policy: LargeRunConfig,
+        cancellation: &CancellationToken,
+    ) -> Result<Option<String>> {
+        let estimated_bytes = match calculate_dir_size(&job.source).await {
+            Ok(size) => size,
+            Err(e) => {
+                warn!("Could not estimate size of job {}: {}", job.id, e);
+                return Ok(None);
+            }
+        };
+
+        if estimated_bytes <= policy.threshold_bytes {
+            return Ok(None);
+        }
+
+        let estimate = [
+            format!("Job:       {}", job.id),
+            format!("Source:    {}", job.source.display()),
+            format!("Estimate:  {} (threshold {})", format_bytes(estimated_bytes), format_bytes(policy.threshold_bytes)),
+        ].join("\n");
+
+        if !policy.confirm {
+            warn!(
+                "Job {} is about to copy about {} MB (threshold {} MB)",
+                job.id,
+                estimated_bytes / (1024 * 1024),
+                policy.threshold_bytes / (1024 * 1024)
+            );
+            self.alert(format!("KeepHive: large run of {} starting", job.id), estimate);
+            return Ok(None);
+        }
+
+        let timeout = std::time::Duration::from_secs(policy.timeout_seconds);
+        let requested_at = Utc::now();
+        let request = ConfirmationRequest {
+            estimated_bytes,
+            requested_at,
+            expires_at: requested_at + chrono::Duration::from_std(timeout).unwrap_or_default(),
+        };
+
+        let answer = self.confirmations.register(&job.id);
+
+        warn!(
+            "Job {} is about to copy about {} MB (threshold {} MB). Waiting {}s for confirmation: \
+             keephive --confirm {} or keephive --reject {} (on timeout: {:?})",
+            job.id,
+            estimated_bytes / (1024 * 1024),
+            policy.threshold_bytes / (1024 * 1024),
+            policy.timeout_seconds,
+            job.id,
+            job.id,
+            policy.on_timeout
+        );
+
+        self.state_manager.update_job_state(&job.id, |js| {
+            js.awaiting_confirmation = Some(request);
+        }).await?;
+
+        self.alert(
+            format!("KeepHive: large run of {} waits for confirmation", job.id),
+            format!(
+                "{}\n\nRun keephive --confirm {} or keephive --reject {} within {}; without an answer the run is {}.",
+                estimate,
+                job.id,
+                job.id,
+                format_duration(timeout),
+                match policy.on_timeout {
+                    ConfirmationTimeout::Proceed => "copied",
+                    ConfirmationTimeout::Skip => "skipped",
+                }
+            ),
+        );
+
+        let skip_reason = tokio::select! {
+            answer = answer => match answer {
+                Ok(true) => {
+                    info!("Large run of job {} confirmed", job.id);
+                    None
+                }
+                _ => Some("large run rejected".to_string()),
+            },
+            _ = tokio::time::sleep(timeout) => match policy.on_timeout {
+                ConfirmationTimeout::Proceed => {
+                    info!("No confirmation for job {} after {}, proceeding", job.id, format_duration(timeout));
+                    None
+                }
+                ConfirmationTimeout::Skip => Some(format!(
+                    "large run not confirmed within {}s",
+                    policy.timeout_seconds
+                )),
+            },
+            _ = cancellation.cancelled() => {
+                self.confirmations.remove(&job.id);
+                self.state_manager.update_job_state(&job.id, |js| {
+                    js.awaiting_confirmation = None;
                 }).await?;
+                bail!("Backup cancelled while waiting for confirmation");
+            }
+        };
 
-                Err(e)
+        self.confirmations.remove(&job.id);
+        self.state_manager.update_job_state(&job.id, |js| {
+            js.awaiting_confirmation = None;
+        }).await?;
+
+        Ok(skip_reason)
+    }
+
+    /// Raise an alert in the background, so a slow mail server never holds up the job
+    fn alert(&self, subject: String, body: String) {
+        let notifications = self.notifications.clone();
+        tokio::spawn(async move {
+            raise_alert(notifications.as_ref(), &subject, &body).await;
+        });
+    }
+
+    /// Per-job retention wins; ad-hoc jobs only prune when asked to
+    fn job_retention_count(&self, job: &BackupJob) -> Option<usize> {
+        match job.retention_count {
+            Some(count) => Some(count),
+            None if is_adhoc_job(&job.id) => None,
+            None => Some(self.retention_count),
+        }
+    }
+
+    /// Copy a completed backup to every replica target concurrently.
+    ///
+    /// The local backup already counts as successful; each replica is tried once here,
+    /// and a failed copy is left in the state for `retry_replicas`, so one unreachable
+    /// target neither holds back the others nor keeps the job's slot while it waits.
+    async fn replicate_backup(
+        &self,
+        job: &BackupJob,
+        backup_path: &Path,
+        retention_count: Option<usize>,
+        cancellation: CancellationToken,
+    ) {
+        // Forget replicas that were removed from the config
+        if let Err(e) = self.state_manager.update_job_state(&job.id, |js| {
+            js.replicas.retain(|r| job.replicas.contains(&r.target));
+        }).await {
+            warn!("Failed to update replica state for job {}: {}", job.id, e);
+        }
+
+        if backup_path.is_file() {
+            warn!("Replicas of job {} are not updated: archive backups are not replicated", job.id);
+            return;
+        }
+
+        // A newer backup replaces whatever an earlier run left to retry
+        let copies: Vec<(PathBuf, u32)> = job.replicas.iter().map(|replica| (replica.clone(), 1)).collect();
+        for (replica, _) in &copies {
+            update_replica(&self.state_manager, &job.id, replica, |r| {
+                r.pending_backup = Some(backup_path.to_path_buf());
+            }).await;
+        }
+
+        self.replicate_to(job, backup_path, copies, retention_count, cancellation).await;
+    }
+
+    /// Whether a replica of the job has a failed copy due for another attempt
+    pub async fn replica_retry_due(&self, job: &BackupJob) -> bool {
+        let now = Utc::now();
+        self.state_manager.read().await
+            .get_job(&job.id)
+            .is_some_and(|js| js.replicas.iter().any(|r| job.replicas.contains(&r.target) && r.retry_due(now)))
+    }
+
+    /// Attempt each replica copy of the job that is due for a retry once more; the service
+    /// calls this between runs, outside the job's concurrency slot
+    pub async fn retry_replicas(&self, job: &BackupJob, cancellation: CancellationToken) {
+        let now = Utc::now();
+        let due: Vec<(PathBuf, PathBuf, u32)> = self.state_manager.read().await
+            .get_job(&job.id)
+            .map(|js| js.replicas.iter()
+                .filter(|r| job.replicas.contains(&r.target) && r.retry_due(now))
+                .filter_map(|r| Some((r.target.clone(), r.pending_backup.clone()?, r.attempts() + 1)))
+                .collect())
+            .unwrap_or_default();
+
+        let retention_count = self.job_retention_count(job);
+
+        for (replica, backup_path, attempt) in due {
+            if !backup_path.is_dir() {
+                warn!("Backup {} of job {} is gone, not copying it to {}", backup_path.display(), job.id, replica.display());
+                update_replica(&self.state_manager, &job.id, &replica, |r| {
+                    r.pending_backup = None;
+                    r.status = ReplicaStatus::Failed {
+                        error: format!("Backup {} no longer exists", backup_path.display()),
+                        attempts: attempt - 1,
+                        next_retry: None,
+                    };
+                }).await;
+                continue;
+            }
+
+            info!("Retrying replication of job {} to {} (attempt {})", job.id, replica.display(), attempt);
+            self.replicate_to(job, &backup_path, vec![(replica, attempt)], retention_count, cancellation.clone()).await;
+        }
+    }
+
+    /// Copy `backup_path` to each listed replica concurrently, as the given attempt number
+    async fn replicate_to(
+        &self,
+        job: &BackupJob,
+        backup_path: &Path,
+        copies: Vec<(PathBuf, u32)>,
+        retention_count: Option<usize>,
+        cancellation: CancellationToken,
+    ) {
+        let mut tasks = tokio::task::JoinSet::new();
+
+        for (replica, attempt) in copies {
+            if self.transfer_cap_reached(&job.id, &replica).await {
+                continue;
+            }
+
+            tasks.spawn(replicate_once(
+                self.state_manager.clone(),
+                Replicator::new(self.durability, self.bandwidth.clone())
+                    .with_backends(self.backends.clone())
+                    .with_verify_sample(self.replication.verify_sample)
+                    .with_usage(self.state_manager.usage(), &job.id),
+                self.target_locks.clone(),
+                self.replication,
+                job.id.clone(),
+                job.source.clone(),
+                backup_path.to_path_buf(),
+                replica,
+                attempt,
+                retention_count,
+                cancellation.clone(),
+            ));
+        }
+
+        while let Some(result) = tasks.join_next().await {
+            if let Err(e) = result {
+                error!("Replication task for job {} failed: {}", job.id, e);
+            }
+        }
+    }
+
+    /// Whether the replica's backend used up its monthly transfer cap; the replica is then
+    /// marked paused until the next month and skipped, while local backups go on
+    async fn transfer_cap_reached(&self, job_id: &str, replica: &Path) -> bool {
+        let location = replica_location(replica, job_id);
+        if TargetUrl::from_path(&location).is_none() {
+            return false;
+        }
+
+        // Opening fails without credentials, which the replication attempt reports itself
+        let Ok(backend) = self.backends.open(&location) else {
+            return false;
+        };
+        let Some((cap, backends)) = self.storage.monthly_transfer_cap(backend.name()) else {
+            return false;
+        };
+
+        let used = match self.state_manager.usage().month_total(backends).await {
+            Ok(used) => used,
+            Err(e) => {
+                warn!("Failed to read transfer usage: {}", e);
+                return false;
+            }
+        };
+        if used < cap {
+            return false;
+        }
+
+        let already_paused = self.state_manager.read().await
+            .get_job(job_id)
+            .and_then(|js| js.replicas.iter().find(|r| r.target == replica))
+            .is_some_and(|r| matches!(r.status, ReplicaStatus::Paused { .. }));
+
+        let reason = format!("Monthly transfer cap of {} for {} reached", format_bytes(cap), backend.name());
+        if already_paused {
+            info!("{}, not replicating job {} to {}", reason, job_id, location.display());
+        } else {
+            warn!("{}: replication of job {} to {} is paused until next month, local backups continue",
+                reason, job_id, location.display());
+            self.alert(
+                format!("KeepHive: replication of {} paused at the transfer cap", job_id),
+                [
+                    format!("Job:       {}", job_id),
+                    format!("Replica:   {}", location.display()),
+                    format!("Reason:    {}", reason),
+                    String::new(),
+                    "Replication resumes next month; local backups continue.".to_string(),
+                ].join("\n"),
+            );
+        }
+
+        update_replica(&self.state_manager, job_id, replica, |r| {
+            r.status = ReplicaStatus::Paused { reason, until: next_month() };
+        }).await;
+
+        true
+    }
+}
+
+/// A job's post hook still to run. Dropped unrun, as when the service aborts a run, it
+/// runs with the outcome `cancelled` anyway.
+struct PostHook {
+    pending: Option<(BackupJob, String)>,
+}
+
+impl PostHook {
+    fn new(job: &BackupJob, command: &str) -> Self {
+        Self { pending: Some((job.clone(), command.to_string())) }
+    }
+
+    /// Run the hook in a task of its own, so aborting the run cannot cut it short.
+    /// Nothing runs once the runtime is gone, at the very end of a shutdown.
+    fn spawn(&mut self, outcome: &'static str) -> Option<tokio::task::JoinHandle<()>> {
+        let (job, command) = self.pending.take()?;
+        let runtime = tokio::runtime::Handle::try_current().ok()?;
+
+        Some(runtime.spawn(async move {
+            if let Err(e) = run_post_hook(&job, &command, outcome).await {
+                warn!("Job {}: {:#}", job.id, e);
             }
+        }))
+    }
+}
+
+impl Drop for PostHook {
+    fn drop(&mut self) {
+        self.spawn("cancelled");
+    }
+}
+
+/// Wait between attempts of a run deferred by a busy process
+fn busy_retry_interval() -> chrono::Duration {
+    chrono::Duration::minutes(15)
+}
+
+/// Emit the run as a single event with the same fields whatever the outcome, so log
+/// pipelines can alert on it without piecing together several lines
+fn log_run_summary(record: &RunRecord) {
+    let (result, backup_name, files, bytes, skipped, reason) = match &record.outcome {
+        RunOutcome::Success { backup_name, files_copied, bytes_copied, files_skipped } => {
+            ("success", backup_name.as_str(), *files_copied, *bytes_copied, *files_skipped, "")
+        }
+        RunOutcome::Failed { error } => ("failed", "", 0, 0, 0, error.as_str()),
+        RunOutcome::Skipped { reason } => ("skipped", "", 0, 0, 0, reason.as_str()),
+    };
+    let duration = (record.finished_at - record.started_at).to_std().unwrap_or_default();
+
+    info!(
+        target: RUN_SUMMARY_TARGET,
+        job_id = %record.job_id,
+        result,
+        duration_ms = duration.as_millis() as u64,
+        bytes,
+        files,
+        skipped,
+        backup_name,
+        reason,
+        "Job {} finished: {} in {}",
+        record.job_id,
+        result,
+        format_duration(duration)
+    );
+}
+
+/// How long a run is deferred before it goes ahead anyway
+fn busy_deferral_limit() -> chrono::Duration {
+    chrono::Duration::hours(12)
+}
+
+/// Start of the next calendar month in local time
+fn next_month() -> chrono::DateTime<Utc> {
+    let today = Local::now().date_naive();
+    let (year, month) = if today.month() == 12 { (today.year() + 1, 1) } else { (today.year(), today.month() + 1) };
+
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .and_then(|t| t.and_local_timezone(Local).earliest())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now)
+}
+
+/// Make attempt number `attempt` at copying one backup to one target. A failed copy is
+/// given a retry time with backoff until attempts run out; a cancelled one is retried
+/// after the same delay without using up an attempt.
+///
+/// The replica is locked like a job's target while it is written and pruned, since it
+/// may be another job's target or the replica of other jobs too.
+#[allow(clippy::too_many_arguments)]
+async fn replicate_once(
+    state_manager: Arc<StateManager>,
+    replicator: Replicator,
+    target_locks: Arc<TargetLocks>,
+    policy: ReplicationConfig,
+    job_id: String,
+    source: PathBuf,
+    backup_path: PathBuf,
+    replica: PathBuf,
+    attempt: u32,
+    retention_count: Option<usize>,
+    cancellation: CancellationToken,
+) {
+    let backup_name = backup_path.file_name()
+        .map(|n| n.to_string_lossy().into_owned());
+    let max_attempts = policy.max_attempts.max(1);
+    let location = replica_location(&replica, &job_id);
+
+    let _replica_lock = tokio::select! {
+        lock = target_locks.lock(&location) => lock,
+        _ = cancellation.cancelled() => {
+            let retry_at = Utc::now() + chrono::Duration::from_std(policy.retry_delay(attempt)).unwrap_or_default();
+            info!("Replication of job {} to {} was stopped while waiting for the replica, retrying at {}", job_id, location.display(), retry_at);
+
+            update_replica(&state_manager, &job_id, &replica, |r| {
+                r.status = ReplicaStatus::Failed {
+                    error: "Cancelled while another job was writing to the replica".to_string(),
+                    attempts: attempt - 1,
+                    next_retry: Some(retry_at),
+                };
+            }).await;
+            return;
+        }
+    };
+
+    update_replica(&state_manager, &job_id, &replica, |r| {
+        r.status = ReplicaStatus::Replicating {
+            started_at: Utc::now(),
+            attempt,
+        };
+    }).await;
+
+    match replicator.replicate(&backup_path, &location, cancellation.clone()).await {
+        Ok(_) => {
+            update_replica(&state_manager, &job_id, &replica, |r| {
+                r.status = ReplicaStatus::Synced;
+                r.last_synced_backup = backup_name;
+                r.last_synced_at = Some(Utc::now());
+                // A newer run may have queued its own backup meanwhile
+                if r.pending_backup.as_ref() == Some(&backup_path) {
+                    r.pending_backup = None;
+                }
+            }).await;
+
+            if let Some(retention_count) = retention_count
+                && let Err(e) = replicator.cleanup(&location, &source, retention_count).await
+            {
+                warn!("Failed to cleanup old backups on replica {}: {}", location.display(), e);
+            }
+        }
+        Err(e) if cancellation.is_cancelled() => {
+            let retry_at = Utc::now() + chrono::Duration::from_std(policy.retry_delay(attempt)).unwrap_or_default();
+            info!("Replication of job {} to {} was stopped, retrying at {}", job_id, location.display(), retry_at);
+
+            update_replica(&state_manager, &job_id, &replica, |r| {
+                r.status = ReplicaStatus::Failed {
+                    error: e.to_string(),
+                    attempts: attempt - 1,
+                    next_retry: Some(retry_at),
+                };
+            }).await;
+        }
+        Err(e) => {
+            let retry = attempt < max_attempts;
+            let delay = policy.retry_delay(attempt);
+
+            warn!(
+                "Replication of job {} to {} failed (attempt {}/{}): {}",
+                job_id, location.display(), attempt, max_attempts, e
+            );
+
+            update_replica(&state_manager, &job_id, &replica, |r| {
+                r.status = ReplicaStatus::Failed {
+                    error: e.to_string(),
+                    attempts: attempt,
+                    next_retry: retry.then(|| {
+                        Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default()
+                    }),
+                };
+                if !retry && r.pending_backup.as_ref() == Some(&backup_path) {
+                    r.pending_backup = None;
+                }
+            }).await;
         }
     }
-}
\ No newline at end of file
+}
+
+async fn update_replica<F>(state_manager: &StateManager, job_id: &str, replica: &Path, updater: F)
+where
+    F: FnOnce(&mut ReplicaState),
+{
+    if let Err(e) = state_manager.update_job_state(job_id, |js| updater(js.replica_mut(replica))).await {
+        warn!("Failed to update replica state for job {}: {}", job_id, e);
+    }
+}
@@ -0,0 +1,145 @@
+//! Estimate-only first run of new jobs. A job's first run only measures what it would
+//! copy, so a source set to a whole drive by mistake is noticed before terabytes move.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::config::BackupJob;
+use crate::core::exclude::{is_backup_area, Exclusions};
+use crate::observability::format_bytes;
+
+/// Top-level entries listed in the report
+const LARGEST_ENTRIES: usize = 5;
+
+/// What a job's first run would copy
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceEstimate {
+    pub files: u64,
+    pub bytes: u64,
+    /// Largest top-level files and folders of the source with their size, largest first
+    pub largest: Vec<(String, u64)>,
+}
+
+impl SourceEstimate {
+    /// One line for the log and the run history
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} files, {}", self.files, format_bytes(self.bytes));
+        if !self.largest.is_empty() {
+            let largest: Vec<String> = self.largest.iter()
+                .map(|(name, bytes)| format!("{} {}", name, format_bytes(*bytes)))
+                .collect();
+            summary.push_str(&format!("; largest: {}", largest.join(", ")));
+        }
+        summary
+    }
+}
+
+/// Measure the files a job would copy, leaving out its exclusions and backup folders
+pub async fn estimate_source(job: &BackupJob) -> Result<SourceEstimate> {
+    let exclusions = Exclusions::from_profiles(&job.exclude_profiles).with_patterns(&job.exclude);
+    let mut estimate = SourceEstimate::default();
+    let mut top_level: Vec<(String, u64)> = Vec::new();
+
+    let mut entries = tokio::fs::read_dir(&job.source).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        let name = PathBuf::from(entry.file_name());
+        if exclusions.excludes(&name, &metadata) {
+            continue;
+        }
+
+        let (files, bytes) = if metadata.is_dir() {
+            measure_dir(&entry.path(), &name, &exclusions).await
+        } else {
+            (1, metadata.len())
+        };
+        estimate.files += files;
+        estimate.bytes += bytes;
+        top_level.push((name.to_string_lossy().into_owned(), bytes));
+    }
+
+    top_level.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_level.truncate(LARGEST_ENTRIES);
+    estimate.largest = top_level;
+
+    Ok(estimate)
+}
+
+/// Files and bytes below a folder; unreadable entries are left out like the copy skips them
+async fn measure_dir(dir: &Path, relative: &Path, exclusions: &Exclusions) -> (u64, u64) {
+    let (mut files, mut bytes) = (0, 0);
+    let mut stack = vec![(dir.to_path_buf(), relative.to_path_buf())];
+
+    while let Some((current, relative)) = stack.pop() {
+        if is_backup_area(&current).await {
+            continue;
+        }
+        let Ok(mut entries) = tokio::fs::read_dir(&current).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let relative = relative.join(entry.file_name());
+            if exclusions.excludes(&relative, &metadata) {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                stack.push((entry.path(), relative));
+            } else {
+                files += 1;
+                bytes += metadata.len();
+            }
+        }
+    }
+
+    (files, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_estimate_counts_what_would_be_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path();
+        std::fs::create_dir_all(source.join("photos/2025")).unwrap();
+        std::fs::create_dir_all(source.join("cache")).unwrap();
+        std::fs::write(source.join("photos/2025/a.jpg"), vec![0u8; 3000]).unwrap();
+        std::fs::write(source.join("photos/b.jpg"), vec![0u8; 1000]).unwrap();
+        std::fs::write(source.join("notes.txt"), b"hello").unwrap();
+        std::fs::write(source.join("cache/blob"), vec![0u8; 9000]).unwrap();
+
+        let job: BackupJob = serde_json::from_value(serde_json::json!({
+            "id": "docs",
+            "source": source,
+            "target": "/mnt/backups",
+            "exclude": ["cache"],
+            "schedule": { "type": "interval", "seconds": 3600 }
+        })).unwrap();
+
+        let estimate = estimate_source(&job).await.unwrap();
+        assert_eq!(estimate.files, 3);
+        assert_eq!(estimate.bytes, 4005);
+        assert_eq!(estimate.largest, [("photos".to_string(), 4000), ("notes.txt".to_string(), 5)]);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_of_missing_source_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let job: BackupJob = serde_json::from_value(serde_json::json!({
+            "id": "docs",
+            "source": dir.path().join("missing"),
+            "target": "/mnt/backups",
+            "schedule": { "type": "interval", "seconds": 3600 }
+        })).unwrap();
+
+        assert!(estimate_source(&job).await.is_err());
+    }
+}
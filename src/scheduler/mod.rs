@@ -1,7 +1,23 @@
+pub mod adhoc;
 pub mod changes;
+pub mod clock;
+pub mod confirmation;
 pub mod engine;
 pub mod executor;
+pub mod first_run;
+pub mod projection;
+pub mod queue;
+pub mod simulate;
+pub mod target_lock;
 
+pub use adhoc::{adhoc_job, folder_backup_job, is_adhoc_job, ADHOC_JOB_PREFIX};
 pub use changes::{ConfigChangeType, ConfigChanges, ModifiedJob};
+pub use clock::{ClockChange, ClockWatch, IntervalClock};
+pub use confirmation::PendingConfirmations;
 pub use engine::Scheduler;
-pub use executor::JobExecutor;
\ No newline at end of file
+pub use executor::JobExecutor;
+pub use first_run::{estimate_source, SourceEstimate};
+pub use projection::{project_run, RunProjection};
+pub use queue::{JobQueue, QueuedJob};
+pub use simulate::{parse_duration_spec, simulate_runs, MAX_SIMULATED_RUNS};
+pub use target_lock::{TargetGuard, TargetLocks};
\ No newline at end of file
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::state::{RunOutcome, RunRecord};
+
+/// Successful runs the copy rate is averaged over
+const RECENT_RUNS: usize = 5;
+
+/// Runs shorter than this say little about the copy rate
+const MIN_RUN_SECS: f64 = 1.0;
+
+/// Expected length of a job's next run, from the job's recent successful runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunProjection {
+    /// Bytes the latest successful run copied
+    pub bytes: u64,
+    pub bytes_per_sec: f64,
+    pub duration: Duration,
+}
+
+/// Project the next run of a job from its history, most recent runs first. None until
+/// a successful run copied anything.
+pub fn project_run(records: &[RunRecord]) -> Option<RunProjection> {
+    let runs: Vec<(u64, f64)> = records.iter()
+        .filter_map(|record| match record.outcome {
+            RunOutcome::Success { bytes_copied, .. } => {
+                let secs = (record.finished_at - record.started_at).num_milliseconds() as f64 / 1000.0;
+                (bytes_copied > 0 && secs >= MIN_RUN_SECS).then_some((bytes_copied, secs))
+            }
+            _ => None,
+        })
+        .take(RECENT_RUNS)
+        .collect();
+
+    let (bytes, _) = *runs.first()?;
+    let total_bytes: u64 = runs.iter().map(|(bytes, _)| bytes).sum();
+    let total_secs: f64 = runs.iter().map(|(_, secs)| secs).sum();
+    let bytes_per_sec = total_bytes as f64 / total_secs;
+
+    Some(RunProjection {
+        bytes,
+        bytes_per_sec,
+        duration: Duration::from_secs_f64(bytes as f64 / bytes_per_sec),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use std::path::PathBuf;
+
+    fn run(minutes: i64, outcome: RunOutcome) -> RunRecord {
+        let started_at = Utc.with_ymd_and_hms(2026, 3, 1, 2, 0, 0).unwrap();
+        RunRecord {
+            job_id: "docs".to_string(),
+            adhoc: false,
+            source: PathBuf::from("/srv/docs"),
+            target: PathBuf::from("/mnt/backups"),
+            started_at,
+            finished_at: started_at + chrono::Duration::minutes(minutes),
+            outcome,
+        }
+    }
+
+    fn success(bytes_copied: u64) -> RunOutcome {
+        RunOutcome::Success { backup_name: "docs".to_string(), files_copied: 1, bytes_copied, files_skipped: 0 }
+    }
+
+    #[test]
+    fn test_projection_uses_latest_size_and_average_rate() {
+        let gib = 1024 * 1024 * 1024;
+        let records = [
+            run(30, RunOutcome::Failed { error: "disk full".to_string() }),
+            run(60, success(6 * gib)),
+            run(20, success(2 * gib)),
+            run(0, success(gib)),
+        ];
+
+        let projection = project_run(&records).unwrap();
+        assert_eq!(projection.bytes, 6 * gib);
+        assert_eq!(projection.bytes_per_sec, (8 * gib) as f64 / 4800.0);
+        assert_eq!(projection.duration, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_no_projection_without_successful_runs() {
+        assert_eq!(project_run(&[]), None);
+        assert_eq!(project_run(&[run(5, RunOutcome::Skipped { reason: "busy".to_string() }), run(5, success(0))]), None);
+    }
+}
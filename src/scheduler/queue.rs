@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+/// A job waiting for a free concurrency slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedJob {
+    pub job_id: String,
+    pub queued_since: DateTime<Utc>,
+}
+
+/// FIFO of ready jobs waiting for a concurrency slot (each job at most once)
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    entries: VecDeque<QueuedJob>,
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a job; returns false if it is already waiting
+    pub fn push(&mut self, job_id: &str, queued_since: DateTime<Utc>) -> bool {
+        if self.contains(job_id) {
+            return false;
+        }
+
+        self.entries.push_back(QueuedJob {
+            job_id: job_id.to_string(),
+            queued_since,
+        });
+        true
+    }
+
+    pub fn pop_front(&mut self) -> Option<QueuedJob> {
+        self.entries.pop_front()
+    }
+
+    /// Take the longest-waiting job that `can_start` accepts, leaving the others in order
+    pub fn pop_first(&mut self, can_start: impl FnMut(&QueuedJob) -> bool) -> Option<QueuedJob> {
+        let index = self.entries.iter().position(can_start)?;
+        self.entries.remove(index)
+    }
+
+    /// Drop a job from the queue (e.g. removed from config)
+    pub fn remove(&mut self, job_id: &str) -> Option<QueuedJob> {
+        let index = self.entries.iter().position(|q| q.job_id == job_id)?;
+        self.entries.remove(index)
+    }
+
+    pub fn contains(&self, job_id: &str) -> bool {
+        self.entries.iter().any(|q| q.job_id == job_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &QueuedJob> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) -> Vec<QueuedJob> {
+        self.entries.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_is_fifo() {
+        let mut queue = JobQueue::new();
+        let now = Utc::now();
+
+        assert!(queue.push("job1", now));
+        assert!(queue.push("job2", now));
+
+        assert_eq!(queue.pop_front().unwrap().job_id, "job1");
+        assert_eq!(queue.pop_front().unwrap().job_id, "job2");
+        assert!(queue.pop_front().is_none());
+    }
+
+    #[test]
+    fn test_pop_first_skips_jobs_that_cannot_start() {
+        let mut queue = JobQueue::new();
+        let now = Utc::now();
+
+        queue.push("same-disk", now);
+        queue.push("other-disk", now);
+        queue.push("last", now);
+
+        assert_eq!(queue.pop_first(|q| q.job_id != "same-disk").unwrap().job_id, "other-disk");
+        assert!(queue.pop_first(|_| false).is_none());
+        assert_eq!(queue.pop_front().unwrap().job_id, "same-disk");
+        assert_eq!(queue.pop_front().unwrap().job_id, "last");
+    }
+
+    #[test]
+    fn test_queue_rejects_duplicates() {
+        let mut queue = JobQueue::new();
+        let first = Utc::now();
+
+        assert!(queue.push("job1", first));
+        assert!(!queue.push("job1", Utc::now()));
+
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.iter().next().unwrap().queued_since, first);
+    }
+
+    #[test]
+    fn test_queue_remove() {
+        let mut queue = JobQueue::new();
+        let now = Utc::now();
+
+        queue.push("job1", now);
+        queue.push("job2", now);
+        queue.push("job3", now);
+
+        assert_eq!(queue.remove("job2").unwrap().job_id, "job2");
+        assert!(queue.remove("job2").is_none());
+        assert!(!queue.contains("job2"));
+        assert_eq!(queue.len(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local, Utc};
+
+use crate::config::Schedule;
+
+/// Upper bound on simulated runs, so short intervals can't flood the output
+pub const MAX_SIMULATED_RUNS: usize = 1000;
+
+/// List the run times a schedule would produce between `from` and `from + horizon`
+pub fn simulate_runs(
+    schedule: &Schedule,
+    last_run: Option<DateTime<Utc>>,
+    from: DateTime<Local>,
+    horizon: Duration,
+    max_runs: usize,
+) -> Vec<DateTime<Local>> {
+    let until = from + horizon;
+    let mut runs = Vec::new();
+    let mut now = from;
+    let mut last = last_run;
+
+    while runs.len() < max_runs {
+        let next = schedule.next_run_after(now, last);
+        if next > until {
+            break;
+        }
+
+        runs.push(next);
+
+        // Assume each run finishes instantly and becomes the new last run
+        last = Some(next.with_timezone(&Utc));
+        now = next;
+    }
+
+    runs
+}
+
+/// Parse a duration such as "90s", "45m", "12h", "30d" or "2w"
+pub fn parse_duration_spec(spec: &str) -> Result<Duration> {
+    let spec = spec.trim();
+    let split = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+    let (number, unit) = spec.split_at(split);
+
+    let value: i64 = number.parse()
+        .with_context(|| format!("Invalid duration '{}': expected a number followed by s, m, h, d or w", spec))?;
+
+    let duration = match unit {
+        "s" => Duration::seconds(value),
+        "m" => Duration::minutes(value),
+        "h" => Duration::hours(value),
+        "d" => Duration::days(value),
+        "w" => Duration::weeks(value),
+        "" => bail!("Duration '{}' has no unit: add s, m, h, d or w", spec),
+        _ => bail!("Invalid duration unit '{}' in '{}': use s, m, h, d or w", unit, spec),
+    };
+
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Timelike};
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).earliest().unwrap()
+    }
+
+    #[test]
+    fn test_simulate_interval_runs() {
+        let schedule = Schedule::Interval { seconds: 3600 };
+        let from = local(2025, 1, 6, 10, 0);
+
+        let runs = simulate_runs(&schedule, None, from, Duration::hours(3), MAX_SIMULATED_RUNS);
+
+        assert_eq!(runs.len(), 4, "Immediate run plus one per hour: {:?}", runs);
+        assert_eq!(runs[0], from);
+        assert_eq!(runs[3], from + Duration::hours(3));
+    }
+
+    #[test]
+    fn test_simulate_interval_respects_last_run() {
+        let schedule = Schedule::Interval { seconds: 3600 };
+        let from = local(2025, 1, 6, 10, 0);
+        let last_run = (from - Duration::minutes(30)).with_timezone(&Utc);
+
+        let runs = simulate_runs(&schedule, Some(last_run), from, Duration::hours(1), MAX_SIMULATED_RUNS);
+
+        assert_eq!(runs, vec![from + Duration::minutes(30)]);
+    }
+
+    #[test]
+    fn test_simulate_daily_runs() {
+        let schedule = Schedule::Daily { hour: 2, minute: 30 };
+        let from = local(2025, 1, 6, 12, 0);
+
+        let runs = simulate_runs(&schedule, None, from, Duration::days(7), MAX_SIMULATED_RUNS);
+
+        assert_eq!(runs.len(), 7);
+        assert!(runs.iter().all(|r| r.hour() == 2 && r.minute() == 30));
+        assert_eq!(runs[0].day(), 7);
+    }
+
+    #[test]
+    fn test_simulate_weekly_runs() {
+        // 2025-01-06 is a Monday
+        let schedule = Schedule::Weekly { day: 7, hour: 3, minute: 0 };
+        let from = local(2025, 1, 6, 12, 0);
+
+        let runs = simulate_runs(&schedule, None, from, Duration::days(30), MAX_SIMULATED_RUNS);
+
+        assert_eq!(runs.len(), 4);
+        assert!(runs.iter().all(|r| r.weekday() == chrono::Weekday::Sun));
+        assert_eq!(runs[0].day(), 12);
+    }
+
+    #[test]
+    fn test_simulate_cron_runs() {
+        // Weekdays at 02:30, starting on Monday 2025-01-06
+        let schedule: Schedule = serde_json::from_str(r#"{ "type": "cron", "expression": "30 2 * * 1-5" }"#).unwrap();
+        let from = local(2025, 1, 6, 12, 0);
+
+        let runs = simulate_runs(&schedule, None, from, Duration::days(7), MAX_SIMULATED_RUNS);
+
+        assert_eq!(runs.len(), 5);
+        assert!(runs.iter().all(|r| r.hour() == 2 && r.minute() == 30));
+        assert!(runs.iter().all(|r| r.weekday().number_from_monday() <= 5));
+        assert_eq!(runs[0].day(), 7);
+    }
+
+    #[test]
+    fn test_simulate_caps_number_of_runs() {
+        let schedule = Schedule::Interval { seconds: 1 };
+        let from = local(2025, 1, 6, 12, 0);
+
+        let runs = simulate_runs(&schedule, None, from, Duration::days(1), 10);
+
+        assert_eq!(runs.len(), 10);
+    }
+
+    #[test]
+    fn test_parse_duration_spec() {
+        assert_eq!(parse_duration_spec("90s").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_duration_spec("45m").unwrap(), Duration::minutes(45));
+        assert_eq!(parse_duration_spec("12h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_duration_spec("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_duration_spec("2w").unwrap(), Duration::weeks(2));
+    }
+
+    #[test]
+    fn test_parse_duration_spec_rejects_invalid() {
+        assert!(parse_duration_spec("d").is_err());
+        assert!(parse_duration_spec("10y").is_err());
+        assert!(parse_duration_spec("").is_err());
+        assert!(parse_duration_spec("6").is_err(), "A bare number must not pick a unit silently");
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+/// One lock per backup target, so jobs sharing a target take turns writing to it and
+/// pruning it: one job's retention never runs while another writes its new backup, and
+/// emergency cleanup never removes another job's unfinished backup
+#[derive(Default)]
+pub struct TargetLocks {
+    locks: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Held while a job writes to or prunes its target
+pub struct TargetGuard {
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl TargetLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock `target` without waiting, if no other job holds it
+    pub fn try_lock(&self, target: &Path) -> Option<TargetGuard> {
+        self.lock_for(target).try_lock_owned().ok().map(|guard| TargetGuard { _guard: guard })
+    }
+
+    /// Wait until no other job holds `target`, then lock it
+    pub async fn lock(&self, target: &Path) -> TargetGuard {
+        TargetGuard { _guard: self.lock_for(target).lock_owned().await }
+    }
+
+    fn lock_for(&self, target: &Path) -> Arc<tokio::sync::Mutex<()>> {
+        self.locks.lock().unwrap()
+            .entry(target_key(target))
+            .or_default()
+            .clone()
+    }
+}
+
+/// The same folder under every spelling: resolved when it exists, and case-insensitive
+/// on Windows
+fn target_key(target: &Path) -> PathBuf {
+    let resolved = dunce::canonicalize(target).unwrap_or_else(|_| target.to_path_buf());
+
+    #[cfg(windows)]
+    let resolved = PathBuf::from(resolved.to_string_lossy().to_lowercase());
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_take_turns_on_a_shared_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let locks = TargetLocks::new();
+
+        let held = locks.lock(dir.path()).await;
+        assert!(locks.try_lock(&dir.path().join(".")).is_none(), "Another spelling of the same target");
+        assert!(locks.try_lock(other.path()).is_some(), "Other targets are independent");
+
+        drop(held);
+        assert!(locks.try_lock(dir.path()).is_some());
+    }
+}
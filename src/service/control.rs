@@ -0,0 +1,643 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::observability::subscribe_job_log;
+
+/// Largest request line accepted from a client
+const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+
+/// Control endpoint used when the configuration does not set one
+pub fn default_endpoint() -> String {
+    #[cfg(windows)]
+    {
+        r"\\.\pipe\keephive".to_string()
+    }
+
+    // Only root can create files there, so no other user can bind the name first
+    #[cfg(target_os = "linux")]
+    {
+        "/run/keephive.sock".to_string()
+    }
+
+    #[cfg(all(not(windows), not(target_os = "linux")))]
+    {
+        "/var/run/keephive.sock".to_string()
+    }
+}
+
+/// Request sent to a running daemon (one JSON object per line)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Check that the daemon is listening
+    Ping,
+
+    /// Back up a folder once into the configured `adhoc_target`
+    BackupFolder { path: PathBuf },
+
+    /// Run a one-off backup job that is not part of the configuration
+    SubmitJob {
+        source: PathBuf,
+        target: PathBuf,
+        /// Backups to keep in `target` (None = never prune)
+        #[serde(default)]
+        retention_count: Option<usize>,
+        #[serde(default)]
+        description: Option<String>,
+    },
+
+    /// Answer a large run that is waiting for confirmation
+    ConfirmRun { job_id: String, approve: bool },
+
+    /// Run a configured job now, outside its schedule
+    RunJob { job_id: String },
+
+    /// Write pending state updates to disk and report what is running
+    Status,
+
+    /// Cancel a running job, or take a queued one off the queue
+    CancelJob { job_id: String },
+
+    /// Stream the log events of a job over this connection until the client disconnects
+    TailJob { job_id: String },
+
+    /// Store a credential in the daemon's secrets file, encrypted for the service's own account
+    StoreSecret { name: String, value: SecretValue },
+
+    /// Forget the volume recorded for a job's target, so its next run takes on whatever
+    /// volume is mounted there now (a replaced backup drive)
+    AdoptVolume { job_id: String },
+}
+
+/// Credential sent to the daemon, left out of logs
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct SecretValue(pub String);
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+/// Daemon reply to a control request
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ControlResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+impl ControlResponse {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Self { ok: true, message: message.into() }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { ok: false, message: message.into() }
+    }
+}
+
+/// Request handed to the daemon loop, with a channel for its reply
+#[derive(Debug)]
+pub struct ControlCommand {
+    pub request: ControlRequest,
+    pub reply: oneshot::Sender<ControlResponse>,
+}
+
+/// Who is behind a control connection. The daemon usually runs with more rights than its
+/// clients, so requests naming folders are checked against the client's own rights.
+struct Client {
+    /// Pipe the client connected on, for impersonating it
+    #[cfg(windows)]
+    pipe: isize,
+    /// User of the client process and of the daemon
+    #[cfg(unix)]
+    uid: Option<u32>,
+    #[cfg(unix)]
+    daemon_uid: Option<u32>,
+}
+
+impl Client {
+    /// Refuse a request that names a folder the client could not read, or a target it
+    /// could not write, itself
+    fn authorize(&self, request: &ControlRequest) -> Result<()> {
+        let (source, target) = match request {
+            ControlRequest::BackupFolder { path } => (path, None),
+            ControlRequest::SubmitJob { source, target, .. } => (source, Some(target.as_path())),
+            ControlRequest::StoreSecret { .. } => return self.authorize_admin("store credentials for the service"),
+            ControlRequest::AdoptVolume { .. } => return self.authorize_admin("adopt a new target volume"),
+            _ => return Ok(()),
+        };
+
+        #[cfg(windows)]
+        {
+            self.as_client(|| check_client_access(source, target))
+        }
+
+        #[cfg(unix)]
+        {
+            let _ = (source, target);
+            if !names_folders_allowed(self.uid, self.daemon_uid) {
+                bail!("Only the service's own user or root may back up folders over the control channel");
+            }
+            Ok(())
+        }
+    }
+
+    /// Refuse a request that changes how the service works for everyone, such as storing
+    /// credentials it will use, to anyone but administrators; `action` names it in the error
+    fn authorize_admin(&self, action: &str) -> Result<()> {
+        #[cfg(windows)]
+        {
+            self.as_client(|| check_client_is_admin(action))
+        }
+
+        #[cfg(unix)]
+        {
+            if !names_folders_allowed(self.uid, self.daemon_uid) {
+                bail!("Only the service's own user or root may {}", action);
+            }
+            Ok(())
+        }
+    }
+
+    /// Run `check` with the rights of the pipe client instead of the service's
+    #[cfg(windows)]
+    fn as_client<T>(&self, check: impl FnOnce() -> Result<T>) -> Result<T> {
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::Security::RevertToSelf;
+        use windows::Win32::System::Pipes::ImpersonateNamedPipeClient;
+
+        // Impersonation is per thread: nothing in between may await
+        unsafe { ImpersonateNamedPipeClient(HANDLE(self.pipe as *mut std::ffi::c_void)) }
+            .context("Failed to identify the control client")?;
+        let result = check();
+        if let Err(e) = unsafe { RevertToSelf() } {
+            // Going on with the client's rights would be worse than stopping
+            panic!("Failed to stop impersonating the control client: {}", e);
+        }
+        result
+    }
+}
+
+/// Whether a client may name folders: only the daemon's own user and root, since a Unix
+/// daemon cannot take on the rights of another user to check them
+#[cfg(unix)]
+fn names_folders_allowed(uid: Option<u32>, daemon_uid: Option<u32>) -> bool {
+    matches!(uid, Some(uid) if uid == 0 || Some(uid) == daemon_uid)
+}
+
+/// Check, while impersonating the client, that it can list `source` and create files in
+/// `target`; the folders it names are then copied with the service's rights
+#[cfg(windows)]
+fn check_client_access(source: &std::path::Path, target: Option<&std::path::Path>) -> Result<()> {
+    std::fs::read_dir(source)
+        .with_context(|| format!("Access denied: you cannot read {}", source.display()))?;
+
+    if let Some(target) = target {
+        std::fs::create_dir_all(target)
+            .with_context(|| format!("Access denied: you cannot create {}", target.display()))?;
+
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let probe = target.join(format!(".keephive_access_{}_{}", std::process::id(), nanos));
+        std::fs::OpenOptions::new().write(true).create_new(true).open(&probe)
+            .with_context(|| format!("Access denied: you cannot write to {}", target.display()))?;
+        let _ = std::fs::remove_file(&probe);
+    }
+
+    Ok(())
+}
+
+/// Check, while impersonating the client, that it is a member of the Administrators group
+#[cfg(windows)]
+fn check_client_is_admin(action: &str) -> Result<()> {
+    use windows::core::BOOL;
+    use windows::Win32::Security::{CheckTokenMembership, CreateWellKnownSid, WinBuiltinAdministratorsSid, PSID};
+
+    // SECURITY_MAX_SID_SIZE
+    let mut buffer = [0u8; 68];
+    let mut size = buffer.len() as u32;
+    let sid = PSID(buffer.as_mut_ptr() as *mut std::ffi::c_void);
+    let mut member = BOOL::default();
+    unsafe {
+        CreateWellKnownSid(WinBuiltinAdministratorsSid, None, Some(sid), &mut size)
+            .context("Failed to build the Administrators SID")?;
+        CheckTokenMembership(None, sid, &mut member)
+            .context("Failed to check the control client's groups")?;
+    }
+
+    if !member.as_bool() {
+        bail!("Access denied: only administrators may {}", action);
+    }
+    Ok(())
+}
+
+/// Accepts control connections and forwards requests to the daemon loop
+pub struct ControlServer {
+    endpoint: String,
+    commands: mpsc::Sender<ControlCommand>,
+}
+
+impl ControlServer {
+    pub fn new(endpoint: String) -> (Self, mpsc::Receiver<ControlCommand>) {
+        let (commands, rx) = mpsc::channel(16);
+        (Self { endpoint, commands }, rx)
+    }
+
+    /// Sender for other front ends, such as the HTTP API, that forward to the same loop
+    pub fn commands(&self) -> mpsc::Sender<ControlCommand> {
+        self.commands.clone()
+    }
+
+    /// Serve connections until cancelled
+    #[cfg(unix)]
+    pub async fn serve(self, cancellation: CancellationToken) -> Result<()> {
+        use tokio::net::UnixListener;
+
+        // A socket file left behind by a crashed daemon blocks bind()
+        if std::path::Path::new(&self.endpoint).exists() {
+            if tokio::net::UnixStream::connect(&self.endpoint).await.is_ok() {
+                bail!("Control endpoint already in use: {}", self.endpoint);
+            }
+            tokio::fs::remove_file(&self.endpoint).await
+                .context("Failed to remove stale control socket")?;
+        }
+
+        let listener = UnixListener::bind(&self.endpoint)
+            .with_context(|| format!("Failed to bind control socket: {} (set control_endpoint when not running as root)", self.endpoint))?;
+        let daemon_uid = std::fs::metadata(&self.endpoint).ok()
+            .map(|metadata| std::os::unix::fs::MetadataExt::uid(&metadata));
+
+        info!("Control channel listening on {}", self.endpoint);
+
+        loop {
+            tokio::select! {
+                _ = cancellation.cancelled() => break,
+                accepted = listener.accept() => {
+                    match accepted {
+                        Ok((stream, _)) => {
+                            let client = Client {
+                                uid: stream.peer_cred().ok().map(|cred| cred.uid()),
+                                daemon_uid,
+                            };
+                            tokio::spawn(handle_connection(stream, client, self.commands.clone()));
+                        }
+                        Err(e) => warn!("Failed to accept control connection: {}", e),
+                    }
+                }
+            }
+        }
+
+        let _ = tokio::fs::remove_file(&self.endpoint).await;
+        debug!("Control channel stopped");
+        Ok(())
+    }
+
+    /// Serve connections until cancelled
+    #[cfg(windows)]
+    pub async fn serve(self, cancellation: CancellationToken) -> Result<()> {
+        let mut server = create_pipe_instance(&self.endpoint, true)?;
+
+        info!("Control channel listening on {}", self.endpoint);
+
+        loop {
+            tokio::select! {
+                _ = cancellation.cancelled() => break,
+                connected = server.connect() => {
+                    if let Err(e) = connected {
+                        warn!("Failed to accept control connection: {}", e);
+                        continue;
+                    }
+
+                    // Hand the connected instance off and open the next one for new clients
+                    let connected = std::mem::replace(
+                        &mut server,
+                        create_pipe_instance(&self.endpoint, false)?,
+                    );
+                    let client = Client {
+                        pipe: std::os::windows::io::AsRawHandle::as_raw_handle(&connected) as isize,
+                    };
+                    tokio::spawn(handle_connection(connected, client, self.commands.clone()));
+                }
+            }
+        }
+
+        debug!("Control channel stopped");
+        Ok(())
+    }
+}
+
+/// Create a pipe instance that interactive users may write to (the default DACL only allows
+/// reads). What they may ask for is checked per request with their own rights.
+#[cfg(windows)]
+fn create_pipe_instance(
+    endpoint: &str,
+    first: bool,
+) -> Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+    use windows::Win32::Security::SECURITY_ATTRIBUTES;
+
+    // SYSTEM and Administrators: full access; interactive users: read/write
+    let server = crate::platform::windows::acl::with_security("D:P(A;;GA;;;SY)(A;;GA;;;BA)(A;;GRGW;;;IU)", |attributes| unsafe {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .create_with_security_attributes_raw(
+                endpoint,
+                attributes as *mut SECURITY_ATTRIBUTES as *mut std::ffi::c_void,
+            )
+    }).context("Failed to build control pipe security descriptor")?;
+
+    server.with_context(|| format!("Failed to create control pipe: {}", endpoint))
+}
+
+/// Read one request line, forward it to the daemon and write back its reply
+async fn handle_connection<S>(stream: S, client: Client, commands: mpsc::Sender<ControlCommand>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(tokio::io::AsyncReadExt::take(reader, MAX_REQUEST_BYTES));
+
+    let mut line = String::new();
+    let mut tailed_job = None;
+    let response = match reader.read_line(&mut line).await {
+        Ok(_) => match serde_json::from_str::<ControlRequest>(line.trim()) {
+            Ok(request) => {
+                if let ControlRequest::TailJob { job_id } = &request {
+                    tailed_job = Some(job_id.clone());
+                }
+                match client.authorize(&request) {
+                    Ok(()) => dispatch(request, &commands).await,
+                    Err(e) => {
+                        warn!("Refused control request {:?}: {:#}", request, e);
+                        ControlResponse::error(format!("{:#}", e))
+                    }
+                }
+            }
+            Err(e) => ControlResponse::error(format!("Invalid request: {}", e)),
+        },
+        Err(e) => ControlResponse::error(format!("Failed to read request: {}", e)),
+    };
+
+    if let Err(e) = write_response(&mut writer, &response).await {
+        debug!("Failed to write control response: {}", e);
+    } else if let Some(job_id) = tailed_job.filter(|_| response.ok) {
+        stream_job_log(&job_id, &mut reader, &mut writer).await;
+    }
+    let _ = writer.shutdown().await;
+}
+
+async fn write_response<W>(writer: &mut W, response: &ControlResponse) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut json = serde_json::to_string(response)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+    Ok(())
+}
+
+/// Forward the job's log lines to the client until it disconnects
+async fn stream_job_log<R, W>(job_id: &str, reader: &mut R, writer: &mut W)
+where
+    R: AsyncBufReadExt + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut log = subscribe_job_log(job_id);
+    let mut ignored = String::new();
+
+    loop {
+        tokio::select! {
+            line = log.next() => {
+                let Some(line) = line else { break };
+                if write_response(writer, &ControlResponse::ok(line)).await.is_err() {
+                    break;
+                }
+            }
+            // The client sends nothing more; end of input means it went away
+            read = reader.read_line(&mut ignored) => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    break;
+                }
+                ignored.clear();
+            }
+        }
+    }
+
+    debug!("Stopped tailing job {}", job_id);
+}
+
+pub(crate) async fn dispatch(request: ControlRequest, commands: &mpsc::Sender<ControlCommand>) -> ControlResponse {
+    debug!("Control request: {:?}", request);
+
+    let (reply, response) = oneshot::channel();
+    if commands.send(ControlCommand { request, reply }).await.is_err() {
+        return ControlResponse::error("Daemon is shutting down");
+    }
+
+    response.await
+        .unwrap_or_else(|_| ControlResponse::error("Daemon dropped the request"))
+}
+
+/// Connect to the daemon's socket, refusing one served by anyone but root or this user,
+/// who could have bound the name first to receive the commands
+#[cfg(unix)]
+async fn connect_socket(endpoint: &str) -> Result<tokio::net::UnixStream> {
+    let stream = tokio::net::UnixStream::connect(endpoint).await
+        .with_context(|| format!("Cannot reach KeepHive at {} (is the service running?)", endpoint))?;
+
+    let server_uid = stream.peer_cred()
+        .with_context(|| format!("Failed to check who serves {}", endpoint))?
+        .uid();
+    // SAFETY: geteuid has no preconditions and cannot fail
+    let own_uid = unsafe { libc::geteuid() };
+    if !server_trusted(server_uid, own_uid) {
+        bail!("Refusing {}: it is served by user {}, not by root or you", endpoint, server_uid);
+    }
+
+    Ok(stream)
+}
+
+/// Whether a control socket served by `server_uid` may receive requests from `own_uid`
+#[cfg(unix)]
+fn server_trusted(server_uid: u32, own_uid: u32) -> bool {
+    server_uid == 0 || server_uid == own_uid
+}
+
+/// Send a request to a running daemon and wait for its reply
+pub async fn send_request(endpoint: &str, request: &ControlRequest) -> Result<ControlResponse> {
+    #[cfg(unix)]
+    let stream = connect_socket(endpoint).await?;
+
+    #[cfg(windows)]
+    let stream = open_pipe_client(endpoint).await?;
+
+    exchange(stream, request).await
+}
+
+/// Follow the log of a job on a running daemon, calling `on_line` with the daemon's
+/// acknowledgement and then each log line, until the daemon closes the connection
+pub async fn tail_job(endpoint: &str, job_id: &str, mut on_line: impl FnMut(&str)) -> Result<()> {
+    #[cfg(unix)]
+    let stream = connect_socket(endpoint).await?;
+
+    #[cfg(windows)]
+    let stream = open_pipe_client(endpoint).await?;
+
+    let (reader, mut writer) = tokio::io::split(stream);
+
+    let mut json = serde_json::to_string(&ControlRequest::TailJob { job_id: job_id.to_string() })?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await
+        .context("Failed to send control request")?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let Some(first) = lines.next_line().await.context("Failed to read control response")? else {
+        bail!("Daemon closed the connection without replying");
+    };
+
+    let response: ControlResponse = serde_json::from_str(first.trim())
+        .context("Invalid control response")?;
+    if !response.ok {
+        bail!("{}", response.message);
+    }
+    on_line(&response.message);
+
+    while let Some(line) = lines.next_line().await.context("Failed to read job log")? {
+        let response: ControlResponse = serde_json::from_str(line.trim())
+            .context("Invalid control response")?;
+        on_line(&response.message);
+    }
+
+    // Keep the write half open until here so the daemon does not see end of input
+    drop(writer);
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn open_pipe_client(endpoint: &str) -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    // All instances can be briefly busy while the server opens the next one
+    for _ in 0..20 {
+        match ClientOptions::new().open(endpoint) {
+            Ok(client) => return Ok(client),
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Cannot reach KeepHive at {} (is the service running?)", endpoint)
+                });
+            }
+        }
+    }
+
+    bail!("KeepHive control pipe is busy: {}", endpoint)
+}
+
+async fn exchange<S>(stream: S, request: &ControlRequest) -> Result<ControlResponse>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+
+    let mut json = serde_json::to_string(request)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await
+        .context("Failed to send control request")?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await
+        .context("Failed to read control response")?;
+
+    if line.is_empty() {
+        bail!("Daemon closed the connection without replying");
+    }
+
+    serde_json::from_str(line.trim())
+        .context("Invalid control response")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_request_wire_format() {
+        let request = ControlRequest::BackupFolder { path: PathBuf::from("/data/docs") };
+        let json = serde_json::to_string(&request).unwrap();
+
+        assert_eq!(json, r#"{"command":"backup_folder","path":"/data/docs"}"#);
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"run_job","job_id":"docs"}"#).unwrap(),
+            ControlRequest::RunJob { job_id: "docs".to_string() }
+        );
+        assert_eq!(serde_json::from_str::<ControlRequest>(r#"{"command":"ping"}"#).unwrap(), ControlRequest::Ping);
+    }
+
+    #[tokio::test]
+    async fn test_request_roundtrip_over_socket() {
+        let dir = tempdir().unwrap();
+        let endpoint = dir.path().join("control.sock").to_string_lossy().into_owned();
+        let cancellation = CancellationToken::new();
+
+        let (server, mut commands) = ControlServer::new(endpoint.clone());
+        let server_task = tokio::spawn(server.serve(cancellation.clone()));
+
+        // Answer like the daemon loop would
+        tokio::spawn(async move {
+            while let Some(command) = commands.recv().await {
+                let _ = command.reply.send(ControlResponse::ok(format!("{:?}", command.request)));
+            }
+        });
+
+        let mut response = None;
+        for _ in 0..50 {
+            if let Ok(r) = send_request(&endpoint, &ControlRequest::Ping).await {
+                response = Some(r);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(response, Some(ControlResponse::ok("Ping")));
+
+        cancellation.cancel();
+        server_task.await.unwrap().unwrap();
+        assert!(!std::path::Path::new(&endpoint).exists(), "Socket file should be removed on shutdown");
+    }
+
+    #[test]
+    fn test_only_the_daemon_user_and_root_name_folders() {
+        assert!(names_folders_allowed(Some(1000), Some(1000)));
+        assert!(names_folders_allowed(Some(0), Some(1000)));
+        assert!(!names_folders_allowed(Some(1001), Some(0)));
+        assert!(!names_folders_allowed(None, Some(0)));
+
+        let stranger = Client { uid: Some(1001), daemon_uid: Some(0) };
+        assert!(stranger.authorize(&ControlRequest::Status).is_ok());
+        assert!(stranger.authorize(&ControlRequest::BackupFolder { path: PathBuf::from("/root") }).is_err());
+        let secret = ControlRequest::StoreSecret { name: "gdrive".into(), value: SecretValue("token".into()) };
+        assert!(stranger.authorize(&secret).is_err());
+        assert!(!format!("{:?}", secret).contains("token"));
+    }
+
+    #[test]
+    fn test_clients_only_trust_root_or_their_own_daemon() {
+        assert!(server_trusted(0, 1000));
+        assert!(server_trusted(1000, 1000));
+        assert!(!server_trusted(1001, 1000), "Another user's socket must not receive commands");
+    }
+}
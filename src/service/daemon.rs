@@ -1,15 +1,34 @@
 use anyhow::{Context, Result};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Interval, MissedTickBehavior};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, warn};
-
-use crate::config::ServiceConfig;
-use crate::observability::{reload_logging, shutdown_logging, Rotation};
-use crate::scheduler::{JobExecutor, Scheduler};
-use crate::service::{setup_shutdown_handler, RecoveryManager};
+use chrono::{Local, Utc};
+use tracing::{debug, debug_span, error, info, info_span, warn, Instrument};
+
+use crate::config::policy::{apply_machine_policy, machine_policy, LockedSettings};
+use crate::config::{check_overlaps, validate_job_overlaps, BackupJob, JobKind, PathRedaction, ServiceConfig, MAX_POLL_INTERVAL_SECS};
+use crate::core::{active_window, exposed_jobs, BandwidthLimiter, CopyTransform, ExposedJobs, ReplicaServer};
+use crate::observability::{format_duration, load_redaction_key, raise_alert, reload_logging, resident_bytes, set_path_redaction, shutdown_logging, DaemonMetrics, Rotation, JOB_SPAN};
+use crate::platform::{slim_mode, FaultInjector, FaultPlan};
+use crate::scheduler::{
+    adhoc_job, folder_backup_job, is_adhoc_job, ClockChange, ClockWatch, JobExecutor, JobQueue, Scheduler,
+};
+use crate::service::control::default_endpoint;
+use crate::service::http_api::ApiJobs;
+use crate::service::target_probe::probe_targets;
+use crate::service::{
+    setup_shutdown_handler, ControlRequest, ControlResponse, ControlServer, HttpApi, RecoveryManager,
+};
 use crate::state::{ConfigWatcher, StateManager};
+use crate::storage::{validate_replicas, BackendFactory};
+
+/// Main loop passes slower than this are logged as stalls
+const LOOP_STALL_THRESHOLD: Duration = Duration::from_secs(2);
+
+/// Longest wait between checks for due jobs, whatever `poll_interval_seconds` says
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(MAX_POLL_INTERVAL_SECS);
 
 /// Service daemon orchestrating all operations
 pub struct ServiceDaemon {
@@ -19,20 +38,81 @@ pub struct ServiceDaemon {
     executor: JobExecutor,
     recovery: RecoveryManager,
     cancellation: CancellationToken,
+    metrics: Arc<DaemonMetrics>,
+    job_queue: JobQueue,
+    /// One-off jobs submitted over the control channel, removed once they finish
+    adhoc_jobs: std::collections::HashMap<String, BackupJob>,
+    /// Copy rate limiter shared by all jobs, following the throttle calendar
+    bandwidth: Arc<BandwidthLimiter>,
+    /// Jobs the replica server hands out, kept in step with the config
+    replica_jobs: ExposedJobs,
+    /// Jobs the HTTP API may run and cancel, kept in step with the config
+    api_jobs: ApiJobs,
+    /// Physical disks of each job's source and target, for disk-aware scheduling
+    job_disks: std::collections::HashMap<String, Vec<String>>,
+    /// Whether resident memory was above `memory_limit_mb` at the last check
+    memory_over: bool,
+    /// When the targets are probed next, with `target_probe` set
+    next_target_probe: Instant,
+    /// Probe round still checking the targets
+    target_probe: Option<tokio::task::JoinHandle<()>>,
+    /// Retries of failed replica copies, per job, running outside the job slots
+    replica_retries: std::collections::HashMap<String, (tokio::task::JoinHandle<()>, CancellationToken)>,
+    /// Notices jumps of the system clock between loop passes
+    clock: ClockWatch,
+    /// Wakes the main loop when a job task ends, set while the loop runs
+    job_finished: Option<mpsc::UnboundedSender<String>>,
+}
+
+/// Sends the job's ID to the main loop when its task ends, returned or panicked
+struct FinishedSignal {
+    job_id: String,
+    finished: mpsc::UnboundedSender<String>,
+}
+
+impl Drop for FinishedSignal {
+    fn drop(&mut self) {
+        let _ = self.finished.send(std::mem::take(&mut self.job_id));
+    }
 }
 
 impl ServiceDaemon {
-    pub async fn new(config: ServiceConfig) -> Result<Self> {
+    pub async fn new(mut config: ServiceConfig) -> Result<Self> {
+        apply_machine_policy(&mut config)?;
+        validate_job_overlaps(&config.jobs)?;
+        validate_replicas(&config)?;
+
         let state_manager = Arc::new(
             StateManager::new(config.state_path.clone()).await
                 .context("Failed to initialize state manager")?
         );
 
+        state_manager.set_durability(config.durability);
+        state_manager.set_save_mode(config.state_save);
+        state_manager.set_job_files(StateManager::job_files(&config)?).await
+            .context("Failed to set up per-job state files")?;
+        enforce_locked_settings(&state_manager, &config).await?;
+        load_path_redaction_key(&state_manager, config.log_path_redaction).await;
+
         let scheduler = Scheduler::new(state_manager.clone());
-        let executor = JobExecutor::with_retention_count(
+        let mut executor = JobExecutor::with_retention_count(
             state_manager.clone(),
             config.retention_count,
         );
+        executor.set_durability(config.durability);
+        executor.set_replication(config.replication);
+        let bandwidth = Arc::new(BandwidthLimiter::new(config.throttle.clone()));
+        executor.set_bandwidth_limiter(bandwidth.clone());
+        executor.set_large_run(config.large_run);
+        executor.set_first_run(config.first_run);
+        executor.set_disk_full(config.on_disk_full);
+        executor.set_verify(config.verify);
+        executor.set_manifest_checksums(config.manifest_checksums);
+        executor.set_storage(&config.storage);
+        executor.set_pull(config.pull.clone());
+        executor.set_network_targets(config.network_targets.clone());
+        executor.set_notifications(config.notifications.clone());
+        executor.set_autotune(config.autotune);
         let recovery = RecoveryManager::new(state_manager.clone());
         let cancellation = CancellationToken::new();
 
@@ -43,21 +123,59 @@ impl ServiceDaemon {
             executor,
             recovery,
             cancellation,
+            metrics: Arc::new(DaemonMetrics::new()),
+            job_queue: JobQueue::new(),
+            adhoc_jobs: std::collections::HashMap::new(),
+            bandwidth,
+            replica_jobs: ExposedJobs::default(),
+            api_jobs: ApiJobs::default(),
+            job_disks: std::collections::HashMap::new(),
+            memory_over: false,
+            next_target_probe: Instant::now(),
+            target_probe: None,
+            replica_retries: std::collections::HashMap::new(),
+            clock: ClockWatch::new(),
+            job_finished: None,
         })
     }
 
     /// Create daemon with external cancellation token (for service mode)
-    pub async fn new_for_service_impl(config: ServiceConfig, cancellation: CancellationToken) -> Result<Self> {
+    pub async fn new_for_service_impl(mut config: ServiceConfig, cancellation: CancellationToken) -> Result<Self> {
+        apply_machine_policy(&mut config)?;
+        validate_job_overlaps(&config.jobs)?;
+        validate_replicas(&config)?;
+
         let state_manager = Arc::new(
             StateManager::new(config.state_path.clone()).await
                 .context("Failed to initialize state manager")?
         );
 
+        state_manager.set_durability(config.durability);
+        state_manager.set_save_mode(config.state_save);
+        state_manager.set_job_files(StateManager::job_files(&config)?).await
+            .context("Failed to set up per-job state files")?;
+        enforce_locked_settings(&state_manager, &config).await?;
+        load_path_redaction_key(&state_manager, config.log_path_redaction).await;
+
         let scheduler = Scheduler::new(state_manager.clone());
-        let executor = JobExecutor::with_retention_count(
+        let mut executor = JobExecutor::with_retention_count(
             state_manager.clone(),
             config.retention_count,
         );
+        executor.set_durability(config.durability);
+        executor.set_replication(config.replication);
+        let bandwidth = Arc::new(BandwidthLimiter::new(config.throttle.clone()));
+        executor.set_bandwidth_limiter(bandwidth.clone());
+        executor.set_large_run(config.large_run);
+        executor.set_first_run(config.first_run);
+        executor.set_disk_full(config.on_disk_full);
+        executor.set_verify(config.verify);
+        executor.set_manifest_checksums(config.manifest_checksums);
+        executor.set_storage(&config.storage);
+        executor.set_pull(config.pull.clone());
+        executor.set_network_targets(config.network_targets.clone());
+        executor.set_notifications(config.notifications.clone());
+        executor.set_autotune(config.autotune);
         let recovery = RecoveryManager::new(state_manager.clone());
 
         Ok(Self {
@@ -67,9 +185,45 @@ impl ServiceDaemon {
             executor,
             recovery,
             cancellation,
+            metrics: Arc::new(DaemonMetrics::new()),
+            job_queue: JobQueue::new(),
+            adhoc_jobs: std::collections::HashMap::new(),
+            bandwidth,
+            replica_jobs: ExposedJobs::default(),
+            api_jobs: ApiJobs::default(),
+            job_disks: std::collections::HashMap::new(),
+            memory_over: false,
+            next_target_probe: Instant::now(),
+            target_probe: None,
+            replica_retries: std::collections::HashMap::new(),
+            clock: ClockWatch::new(),
+            job_finished: None,
         })
     }
 
+    /// Register a transform applied to every file copied by backups (call before `run`)
+    pub fn add_copy_transform(&mut self, transform: Arc<dyn CopyTransform>) {
+        info!("Registered copy transform: {}", transform.name());
+        self.executor.add_copy_transform(transform);
+    }
+
+    /// Inject copy faults into every backup, for exercising failure handling (call before `run`)
+    pub fn inject_faults(&mut self, plan: FaultPlan) {
+        warn!("Chaos mode: injecting copy faults {:?}", plan);
+        self.executor.set_faults(Arc::new(FaultInjector::new(plan)));
+    }
+
+    /// Register a storage backend for `scheme://` replica targets (call before `run`)
+    pub fn register_storage_backend(&mut self, scheme: &str, factory: impl BackendFactory + 'static) {
+        info!("Registered storage backend: {}://", scheme);
+        self.executor.register_storage_backend(scheme, factory);
+    }
+
+    /// Main loop metrics (shared, updated while the daemon runs)
+    pub fn metrics(&self) -> Arc<DaemonMetrics> {
+        self.metrics.clone()
+    }
+
     /// Run the service daemon
     pub async fn run(mut self, config_path: std::path::PathBuf) -> Result<()> {
         info!("KeepHive service starting...");
@@ -77,17 +231,7 @@ impl ServiceDaemon {
         // Setup shutdown handler
         setup_shutdown_handler(self.cancellation.clone()).await;
 
-        // Initialize job states before recovery
-        self.scheduler.initialize_jobs(&self.config.jobs).await?;
-
-        // Reset failed jobs to Idle on startup
-        self.reset_failed_jobs().await?;
-
-        // Recover from partial backups
-        let target_dirs: Vec<_> = self.config.jobs.iter()
-            .map(|j| j.target.as_path())
-            .collect();
-        self.recovery.recover_partial_backups(target_dirs).await?;
+        self.prepare().await?;
 
         // Calculate initial next runs
         self.scheduler.calculate_next_runs(&self.config.jobs).await?;
@@ -100,12 +244,73 @@ impl ServiceDaemon {
             }
         });
 
+        // Control channel for local clients (CLI, Explorer verb)
+        let endpoint = self.config.control_endpoint.clone().unwrap_or_else(default_endpoint);
+        let (control_server, mut control_rx) = ControlServer::new(endpoint);
+        let control_commands = control_server.commands();
+        let control_cancellation = self.cancellation.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control_server.serve(control_cancellation).await {
+                error!("Control channel error: {}", e);
+            }
+        });
+
+        // Finished jobs wake the loop, so their next run and waiting jobs need no poll tick
+        let (job_finished, mut finished_rx) = mpsc::unbounded_channel();
+        self.job_finished = Some(job_finished);
+
+        // Other instances pull completed backups from here
+        if let Some(server_config) = &self.config.replica_server {
+            *self.replica_jobs.write().unwrap() = exposed_jobs(server_config, &self.config.jobs);
+
+            match ReplicaServer::new(server_config, self.replica_jobs.clone()) {
+                Ok(server) => {
+                    let server_cancellation = self.cancellation.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = server.serve(server_cancellation).await {
+                            error!("Replica server error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => error!("Replica server not started: {}", e),
+            }
+        }
+
+        // Status and control for dashboards, answered by the same loop as the control channel
+        if let Some(api_config) = &self.config.http_api {
+            *self.api_jobs.write().unwrap() = self.config.jobs.iter().map(|job| job.id.clone()).collect();
+
+            match HttpApi::new(api_config, self.state_manager.clone(), self.api_jobs.clone(), control_commands) {
+                Ok(api) => {
+                    let api_cancellation = self.cancellation.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = api.serve(api_cancellation).await {
+                            error!("HTTP API error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => error!("HTTP API not started: {}", e),
+            }
+        }
+
+        // Counters for perfmon, visible once `--install` has registered them
+        #[cfg(windows)]
+        tokio::spawn(crate::platform::windows::perf::publish_counters(
+            self.metrics.clone(),
+            self.state_manager.clone(),
+            self.cancellation.clone(),
+        ));
+
         // Main service loop - track both handles and cancellation tokens
         let mut running_jobs: std::collections::HashMap<
             String,
             (tokio::task::JoinHandle<Result<()>>, CancellationToken)
         > = std::collections::HashMap::new();
 
+        // Kept across passes, so other events cannot put off the periodic check
+        let mut poll_seconds = self.config.poll_interval_seconds;
+        let mut poll = poll_timer(poll_seconds);
+
         loop {
             tokio::select! {
                 // Check for shutdown
@@ -118,12 +323,49 @@ impl ServiceDaemon {
                 // Config changes
                 Some(config_change) = config_rx.recv() => {
                     info!("Configuration changed, processing updates...");
-                    self.handle_config_change(config_change.config, &mut running_jobs).await?;
+                    let started = Instant::now();
+                    self.handle_config_change(config_change.config, &mut running_jobs)
+                        .instrument(debug_span!("config_reload"))
+                        .await?;
+                    self.metrics.record_config_reload();
+                    self.record_loop_iteration("config_reload", started.elapsed());
+
+                    if self.config.poll_interval_seconds != poll_seconds {
+                        poll_seconds = self.config.poll_interval_seconds;
+                        poll = poll_timer(poll_seconds);
+                    }
+                }
+
+                // Requests from local clients
+                Some(command) = control_rx.recv() => {
+                    let started = Instant::now();
+                    let response = self.handle_control_request(command.request, &running_jobs)
+                        .instrument(debug_span!("control_request"))
+                        .await;
+                    let _ = command.reply.send(response);
+                    self.record_loop_iteration("control_request", started.elapsed());
+                }
+
+                // A job task ended
+                Some(job_id) = finished_rx.recv() => {
+                    let started = Instant::now();
+                    let mut finished = vec![job_id];
+                    while let Ok(job_id) = finished_rx.try_recv() {
+                        finished.push(job_id);
+                    }
+                    self.process_jobs(&mut running_jobs, &finished)
+                        .instrument(debug_span!("process_jobs"))
+                        .await?;
+                    self.record_loop_iteration("job_finished", started.elapsed());
                 }
 
                 // Periodic job check
-                _ = sleep(Duration::from_secs(5)) => {
-                    self.process_jobs(&mut running_jobs).await?;
+                _ = poll.tick() => {
+                    let started = Instant::now();
+                    self.process_jobs(&mut running_jobs, &[])
+                        .instrument(debug_span!("process_jobs"))
+                        .await?;
+                    self.record_loop_iteration("process_jobs", started.elapsed());
                 }
             }
         }
@@ -132,6 +374,372 @@ impl ServiceDaemon {
         Ok(())
     }
 
+    /// Run every job that is due once, then return (`--run-pending-and-exit`).
+    ///
+    /// Due means the next run stored by the previous invocation has passed, so runs
+    /// missed while the machine was off are caught up; new jobs run right away.
+    pub async fn run_pending(mut self) -> Result<()> {
+        info!("Running pending jobs...");
+
+        setup_shutdown_handler(self.cancellation.clone()).await;
+        self.prepare().await?;
+
+        let ready = self.scheduler.get_ready_jobs(&self.config.jobs).await?;
+
+        // Failed replica copies get their next attempt; jobs about to run copy a newer backup
+        for job in &self.config.jobs {
+            if !ready.iter().any(|r| r.id == job.id) && self.executor.replica_retry_due(job).await {
+                self.executor.retry_replicas(job, self.cancellation.child_token()).await;
+            }
+        }
+
+        if ready.is_empty() {
+            info!("No jobs are due");
+            return self.state_manager.flush().await;
+        }
+
+        let limit = self.concurrency_limit();
+        let mut tasks = tokio::task::JoinSet::new();
+        let mut failed = Vec::new();
+
+        let disks: std::collections::HashMap<String, Vec<String>> = if self.config.disk_aware_scheduling {
+            ready.iter().map(|job| (job.id.clone(), disks_used_by(job))).collect()
+        } else {
+            std::collections::HashMap::new()
+        };
+        let mut waiting: Vec<&BackupJob> = ready.iter().collect();
+        let mut running: Vec<String> = Vec::new();
+
+        while !waiting.is_empty() {
+            let startable = waiting.iter().position(|job| {
+                tasks.len() < limit && !shares_disk(&disks, &job.id, running.iter())
+            });
+            let Some(index) = startable else {
+                if let Some(finished) = collect_finished(&mut tasks, &mut failed).await {
+                    running.retain(|id| *id != finished);
+                }
+                if tasks.is_empty() {
+                    running.clear();
+                }
+                continue;
+            };
+
+            let job = waiting.remove(index);
+            running.push(job.id.clone());
+
+            info!("Starting job: {}", job.id);
+            let executor = self.executor.clone();
+            let job = job.clone();
+            let job_cancellation = self.cancellation.child_token();
+            tasks.spawn(async move {
+                let result = executor.execute_job(&job, job_cancellation).await;
+                (job.id, result)
+            });
+        }
+
+        while !tasks.is_empty() {
+            collect_finished(&mut tasks, &mut failed).await;
+        }
+
+        self.scheduler.calculate_next_runs(&self.config.jobs).await?;
+        self.state_manager.flush().await?;
+
+        if !failed.is_empty() {
+            anyhow::bail!("{} of {} jobs failed: {}", failed.len(), ready.len(), failed.join(", "));
+        }
+
+        info!("{} pending jobs completed", ready.len());
+        Ok(())
+    }
+
+    /// Bring job state in line with the config and clean up after an unclean stop
+    async fn prepare(&mut self) -> Result<()> {
+        if slim_mode() {
+            warn_slim_mode_limits(&self.config.jobs);
+        }
+
+        if let Some(native) = crate::platform::arch::emulated() {
+            warn!(
+                "Running the {} build emulated on {}; system-state jobs need the {} build",
+                crate::platform::arch::Architecture::build(),
+                native,
+                native
+            );
+        }
+
+        alert_state_recoveries(&self.state_manager, &self.config);
+
+        // Initialize job states before recovery
+        self.scheduler.initialize_jobs(&self.config.jobs).await?;
+
+        // Reset failed jobs to Idle on startup
+        self.reset_failed_jobs().await?;
+
+        // Ad-hoc jobs do not survive a restart
+        self.remove_stale_adhoc_jobs().await?;
+
+        // Backups from before completion markers would otherwise look partial
+        self.recovery.migrate_completion_markers(&self.config.jobs).await?;
+
+        // Recover from partial backups
+        let target_dirs: Vec<_> = self.config.jobs.iter()
+            .map(|j| j.target.as_path())
+            .collect();
+        self.recovery.recover_partial_backups(target_dirs).await
+    }
+
+    /// Record main loop latency and warn when a pass blocked the loop
+    fn record_loop_iteration(&self, event: &str, elapsed: Duration) {
+        self.metrics.record_iteration(elapsed);
+
+        if elapsed >= LOOP_STALL_THRESHOLD {
+            let snapshot = self.metrics.snapshot();
+            warn!(
+                "Main loop stalled for {:?} handling {} (max {}us, avg {}us over {} iterations)",
+                elapsed,
+                event,
+                snapshot.max_iteration_micros,
+                snapshot.avg_iteration_micros,
+                snapshot.loop_iterations
+            );
+        } else {
+            debug!("Main loop handled {} in {:?}", event, elapsed);
+        }
+    }
+
+    /// Answer a request received on the control channel
+    async fn handle_control_request(
+        &mut self,
+        request: ControlRequest,
+        running_jobs: &std::collections::HashMap<String, (tokio::task::JoinHandle<Result<()>>, CancellationToken)>,
+    ) -> ControlResponse {
+        match request {
+            ControlRequest::Ping => {
+                ControlResponse::ok(format!("KeepHive v{} running", env!("CARGO_PKG_VERSION")))
+            }
+            ControlRequest::BackupFolder { path } => {
+                let submitted = match &self.config.adhoc_target {
+                    Some(adhoc_target) => {
+                        let job = folder_backup_job(&path, adhoc_target, self.config.retention_count);
+                        self.submit_adhoc_job(job, running_jobs).await
+                    }
+                    None => Err(anyhow::anyhow!(
+                        "Folder backups are disabled (set adhoc_target in the configuration)"
+                    )),
+                };
+                Self::adhoc_response(submitted)
+            }
+            ControlRequest::SubmitJob { source, target, retention_count, description } => {
+                let job = adhoc_job(&source, &target, retention_count, description);
+                let submitted = self.submit_adhoc_job(job, running_jobs).await;
+                Self::adhoc_response(submitted)
+            }
+            ControlRequest::ConfirmRun { job_id, approve } => {
+                let answer = if approve { "confirmed" } else { "rejected" };
+                if self.executor.resolve_confirmation(&job_id, approve) {
+                    info!("Large run of job {} {} over the control channel", job_id, answer);
+                    return ControlResponse::ok(format!("Run of {} {}", job_id, answer));
+                }
+
+                match self.executor.answer_first_run(&job_id, approve).await {
+                    Ok(true) => {}
+                    Ok(false) => return ControlResponse::error(format!("Job {} is not waiting for confirmation", job_id)),
+                    Err(e) => return ControlResponse::error(format!("Failed to answer first run of {}: {}", job_id, e)),
+                }
+                info!("First run estimate of job {} {} over the control channel", job_id, answer);
+
+                if !approve {
+                    return ControlResponse::ok(format!("First run of {} rejected; its next run estimates again", job_id));
+                }
+
+                // Start the confirmed copy now instead of at the next scheduled time
+                if running_jobs.contains_key(&job_id) || !self.job_queue.push(&job_id, Utc::now()) {
+                    return ControlResponse::ok(format!("First run of {} confirmed", job_id));
+                }
+                self.metrics.record_job_queued();
+                ControlResponse::ok(format!("First run of {} confirmed; job queued", job_id))
+            }
+            ControlRequest::RunJob { job_id } => {
+                if !self.config.jobs.iter().any(|j| j.id == job_id) {
+                    return ControlResponse::error(format!("Unknown job: {}", job_id));
+                }
+
+                if running_jobs.contains_key(&job_id) {
+                    return ControlResponse::error(format!("Job {} is already running", job_id));
+                }
+
+                if !self.job_queue.push(&job_id, Utc::now()) {
+                    return ControlResponse::error(format!("Job {} is already queued", job_id));
+                }
+                self.metrics.record_job_queued();
+
+                info!("Run of job {} requested over the control channel", job_id);
+                ControlResponse::ok(format!("Job {} queued", job_id))
+            }
+            ControlRequest::CancelJob { job_id } => {
+                if let Some((_, token)) = running_jobs.get(&job_id) {
+                    info!("Cancelling job {} on request", job_id);
+                    token.cancel();
+                    return ControlResponse::ok(format!("Cancelling job {}", job_id));
+                }
+
+                if self.job_queue.remove(&job_id).is_some() {
+                    info!("Removed job {} from the queue on request", job_id);
+                    self.state_manager.update_job_state(&job_id, |js| js.queued_since = None).await.ok();
+                    return ControlResponse::ok(format!("Job {} removed from the queue", job_id));
+                }
+
+                ControlResponse::error(format!("Job {} is not running or queued", job_id))
+            }
+            ControlRequest::TailJob { job_id } => {
+                let known = self.config.jobs.iter().any(|j| j.id == job_id)
+                    || self.adhoc_jobs.contains_key(&job_id);
+                if !known {
+                    return ControlResponse::error(format!("Unknown job: {}", job_id));
+                }
+
+                info!("Log of job {} tailed over the control channel", job_id);
+                ControlResponse::ok(format!("Tailing job {}", job_id))
+            }
+            ControlRequest::StoreSecret { name, value } => {
+                match self.state_manager.secrets().set(&name, &value.0).await {
+                    Ok(()) => {
+                        info!("Credential {} stored over the control channel", name);
+                        ControlResponse::ok(format!("Stored credential {}", name))
+                    }
+                    Err(e) => ControlResponse::error(format!("Failed to store credential {}: {:#}", name, e)),
+                }
+            }
+            ControlRequest::AdoptVolume { job_id } => {
+                if !self.config.jobs.iter().any(|j| j.id == job_id) {
+                    return ControlResponse::error(format!("Unknown job: {}", job_id));
+                }
+
+                if running_jobs.contains_key(&job_id) {
+                    return ControlResponse::error(format!("Job {} is running; try again once it finishes", job_id));
+                }
+
+                let mut forgotten = None;
+                let updated = self.state_manager.update_job_state(&job_id, |js| forgotten = js.target_volume.take()).await;
+                match (updated, forgotten) {
+                    (Err(e), _) => ControlResponse::error(format!("Failed to update job {}: {}", job_id, e)),
+                    (Ok(()), None) => ControlResponse::ok(format!("Job {} has no recorded target volume", job_id)),
+                    (Ok(()), Some(volume)) => {
+                        info!("Target volume {} of job {} forgotten over the control channel", volume, job_id);
+                        ControlResponse::ok(format!(
+                            "Job {} no longer expects volume {}; its next run backs up to the volume mounted there now",
+                            job_id, volume
+                        ))
+                    }
+                }
+            }
+            ControlRequest::Status => {
+                // The caller reads the state file, so deferred updates must be on disk
+                if let Err(e) = self.state_manager.flush().await {
+                    return ControlResponse::error(format!("Failed to save state: {}", e));
+                }
+
+                ControlResponse::ok(format!(
+                    "KeepHive v{} running: {} jobs running, {} queued",
+                    env!("CARGO_PKG_VERSION"),
+                    running_jobs.len(),
+                    self.job_queue.len()
+                ))
+            }
+        }
+    }
+
+    fn adhoc_response(submitted: Result<String>) -> ControlResponse {
+        match submitted {
+            Ok(job_id) => ControlResponse::ok(format!("Backup queued: {}", job_id)),
+            Err(e) => {
+                warn!("Rejected ad-hoc backup request: {}", e);
+                ControlResponse::error(e.to_string())
+            }
+        }
+    }
+
+    /// Queue a one-off job that is not part of the configuration
+    async fn submit_adhoc_job(
+        &mut self,
+        job: BackupJob,
+        running_jobs: &std::collections::HashMap<String, (tokio::task::JoinHandle<Result<()>>, CancellationToken)>,
+    ) -> Result<String> {
+        if !job.source.is_absolute() || !job.source.is_dir() {
+            anyhow::bail!("Source is not a folder: {}", job.source.display());
+        }
+
+        if !job.target.is_absolute() {
+            anyhow::bail!("Target must be an absolute path: {}", job.target.display());
+        }
+
+        if !machine_policy()?.allows(&job.target) {
+            anyhow::bail!("Target {} is not below a target root allowed by policy", job.target.display());
+        }
+
+        if self.config.jobs.iter().any(|j| j.id == job.id) {
+            anyhow::bail!("Job ID {} is already used by a configured job", job.id);
+        }
+
+        // Reading past file permissions is only for jobs the administrator configured
+        if job.backup_privilege {
+            anyhow::bail!("Ad-hoc jobs cannot read files with the backup privilege");
+        }
+
+        // A client must not point the service's writes into the folders of configured jobs
+        let mut jobs = self.config.jobs.clone();
+        jobs.push(job.clone());
+        let own = format!("jobs[{}]", job.id);
+        if let Some(overlap) = check_overlaps(&jobs).into_iter()
+            .find(|overlap| overlap.rejected && overlap.location.starts_with(&own))
+        {
+            anyhow::bail!("Target refused: {}", overlap.message);
+        }
+
+        if running_jobs.contains_key(&job.id) || self.job_queue.contains(&job.id) {
+            anyhow::bail!(
+                "A backup of {} to {} is already queued or running",
+                job.source.display(),
+                job.target.display()
+            );
+        }
+
+        tokio::fs::create_dir_all(&job.target).await
+            .with_context(|| format!("Failed to create target: {}", job.target.display()))?;
+
+        self.scheduler.initialize_jobs(std::slice::from_ref(&job)).await?;
+
+        info!("Ad-hoc backup requested: {} ({} -> {})",
+            job.id, job.source.display(), job.target.display());
+
+        let job_id = job.id.clone();
+        self.adhoc_jobs.insert(job_id.clone(), job);
+
+        if self.job_queue.push(&job_id, Utc::now()) {
+            self.metrics.record_job_queued();
+        }
+
+        Ok(job_id)
+    }
+
+    /// Drop state entries of ad-hoc jobs left over from a previous run
+    async fn remove_stale_adhoc_jobs(&self) -> Result<()> {
+        let stale: Vec<String> = {
+            let state = self.state_manager.read().await;
+            state.jobs.iter()
+                .filter(|j| is_adhoc_job(&j.id) && !self.config.jobs.iter().any(|c| c.id == j.id))
+                .map(|j| j.id.clone())
+                .collect()
+        };
+
+        for job_id in stale {
+            info!("Removing ad-hoc job left over from previous run: {}", job_id);
+            self.state_manager.remove_job(&job_id).await?;
+        }
+
+        Ok(())
+    }
+
     /// Reset failed jobs to Idle on startup
     async fn reset_failed_jobs(&self) -> Result<()> {
         let state = self.state_manager.read().await;
@@ -151,23 +759,29 @@ impl ServiceDaemon {
         Ok(())
     }
 
+    /// Collect finished jobs and start the ones that are due; `finished` names jobs whose
+    /// task signalled its end and may not have returned yet
     async fn process_jobs(
         &mut self,
         running_jobs: &mut std::collections::HashMap<String, (tokio::task::JoinHandle<Result<()>>, CancellationToken)>,
+        finished: &[String],
     ) -> Result<()> {
-        // Track which jobs completed
-        let mut completed_jobs = Vec::new();
+        // Persist deferred state updates once the batch interval has elapsed
+        self.scheduler.refresh_interval_anchors(&self.config.jobs).await;
+        self.state_manager.flush_if_due().await?;
+
+        self.follow_clock_change().await?;
 
         // Remove completed jobs
-        running_jobs.retain(|id, (handle, _token)| {
-            if handle.is_finished() {
-                debug!("Job completed: {}", id);
-                completed_jobs.push(id.clone());
-                false
-            } else {
-                true
+        let completed_jobs = self.collect_finished_jobs(running_jobs, finished).await;
+
+        // Ad-hoc jobs run once; forget them when they finish
+        for job_id in &completed_jobs {
+            if self.adhoc_jobs.remove(job_id).is_some() {
+                info!("Ad-hoc job finished: {}", job_id);
+                self.state_manager.remove_job(job_id).await?;
             }
-        });
+        }
 
         // Recalculate next runs only for completed jobs
         if !completed_jobs.is_empty() {
@@ -182,24 +796,273 @@ impl ServiceDaemon {
             }
         }
 
-        // Get ready jobs
+        // Queue ready jobs that are not already running or waiting
         let ready_jobs = self.scheduler.get_ready_jobs(&self.config.jobs).await?;
+        let now = Utc::now();
 
         for job in ready_jobs {
-            if !running_jobs.contains_key(&job.id) {
-                info!("Starting job: {}", job.id);
+            if !running_jobs.contains_key(&job.id) && self.job_queue.push(&job.id, now) {
+                self.metrics.record_job_queued();
+            }
+        }
 
-                let executor = self.executor.clone();
-                let job_clone = job.clone();
-                let job_cancellation = self.cancellation.child_token();
-                let job_cancellation_clone = job_cancellation.clone();
+        // Start queued jobs while concurrency and memory allow
+        let limit = self.concurrency_limit().min(self.memory_slots(running_jobs.len()));
 
-                let handle = tokio::spawn(async move {
-                    executor.execute_job(&job_clone, job_cancellation_clone).await
-                });
+        while running_jobs.len() < limit {
+            let Some(queued) = self.next_queued(running_jobs) else {
+                break;
+            };
 
-                running_jobs.insert(job.id.clone(), (handle, job_cancellation));
+            let configured = self.config.jobs.iter().find(|j| j.id == queued.job_id);
+            let Some(job) = configured.or_else(|| self.adhoc_jobs.get(&queued.job_id)).cloned() else {
+                continue;
+            };
+
+            let waited = (Utc::now() - queued.queued_since).to_std().unwrap_or_default();
+            self.metrics.record_queue_wait(waited);
+
+            info!("Starting job: {} (queued for {})", job.id, format_duration(waited));
+
+            // The run copies its own, newer backup to the replicas
+            if let Some((_, retry_cancellation)) = self.replica_retries.remove(&job.id) {
+                retry_cancellation.cancel();
             }
+
+            let executor = self.executor.clone();
+            let job_cancellation = self.cancellation.child_token();
+            let job_cancellation_clone = job_cancellation.clone();
+            let job_clone = job.clone();
+            let finished_signal = self.job_finished.clone()
+                .map(|finished| FinishedSignal { job_id: job.id.clone(), finished });
+
+            // The span routes the job's log events to `--tail`
+            let span = info_span!(JOB_SPAN, job_id = %job.id);
+            let handle = tokio::spawn(async move {
+                let _finished_signal = finished_signal;
+                executor.execute_job(&job_clone, job_cancellation_clone).await
+            }.instrument(span));
+
+            running_jobs.insert(job.id.clone(), (handle, job_cancellation));
+            self.metrics.record_job_spawned();
+        }
+
+        self.metrics.set_queue_length(self.job_queue.len());
+        self.metrics.set_jobs_running(running_jobs.len());
+
+        self.start_target_probe();
+        self.start_replica_retries(running_jobs).await;
+
+        if !self.job_queue.is_empty() {
+            self.record_queued_jobs(running_jobs.len(), limit).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Take the finished job tasks out of `running_jobs`, along with the `signalled` ones
+    /// about to return, and see how each ended. A task that panicked, was cancelled, or
+    /// returned an error while its job was still Running marks the job Failed instead of
+    /// leaving it Running for good.
+    async fn collect_finished_jobs(
+        &self,
+        running_jobs: &mut std::collections::HashMap<String, (tokio::task::JoinHandle<Result<()>>, CancellationToken)>,
+        signalled: &[String],
+    ) -> Vec<String> {
+        let finished: Vec<String> = running_jobs.iter()
+            .filter(|(id, (handle, _token))| handle.is_finished() || signalled.contains(id))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for job_id in &finished {
+            let Some((handle, _token)) = running_jobs.remove(job_id) else {
+                continue;
+            };
+            debug!("Job completed: {}", job_id);
+
+            let error = match handle.await {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => {
+                    error!("Job {} ended with an error: {:#}", job_id, e);
+                    format!("{:#}", e)
+                }
+                Err(e) if e.is_panic() => {
+                    let message = panic_message(e.into_panic().as_ref());
+                    error!("Job {} panicked: {}", job_id, message);
+                    self.metrics.record_job_panic();
+                    format!("panicked: {}", message)
+                }
+                Err(_) => {
+                    error!("Job {} was stopped before it finished", job_id);
+                    "stopped before it finished".to_string()
+                }
+            };
+
+            let configured = self.config.jobs.iter().find(|j| &j.id == job_id);
+            if let Some(job) = configured.or_else(|| self.adhoc_jobs.get(job_id))
+                && let Err(e) = self.executor.record_crash(job, &error).await
+            {
+                warn!("Failed to record the end of job {}: {:#}", job_id, e);
+            }
+        }
+
+        finished
+    }
+
+    /// Calculate next runs again when the system clock jumped or the time zone changed;
+    /// runs computed from the old time would otherwise all start at once or not for days
+    async fn follow_clock_change(&mut self) -> Result<()> {
+        let Some(change) = self.clock.check() else {
+            return Ok(());
+        };
+
+        match change {
+            ClockChange::Jumped(drift) => warn!(
+                "System clock jumped {} by {} (NTP correction or manual change); recalculating next runs",
+                if drift < chrono::Duration::zero() { "back" } else { "forward" },
+                format_duration(drift.abs().to_std().unwrap_or_default())
+            ),
+            ClockChange::OffsetChanged { from, to } => info!(
+                "UTC offset changed from {} to {} (time zone or daylight saving); recalculating next runs",
+                from, to
+            ),
+        }
+
+        self.scheduler.recalculate_next_runs(&self.config.jobs).await
+    }
+
+    /// Retry the failed replica copies that are due, one task per job, while the job itself
+    /// is not running; they take no job slot, so waiting for a target never holds up backups
+    async fn start_replica_retries<T>(&mut self, running_jobs: &std::collections::HashMap<String, T>) {
+        self.replica_retries.retain(|_, (handle, _)| !handle.is_finished());
+
+        for job in &self.config.jobs {
+            if job.replicas.is_empty()
+                || running_jobs.contains_key(&job.id)
+                || self.replica_retries.contains_key(&job.id)
+                || !self.executor.replica_retry_due(job).await
+            {
+                continue;
+            }
+
+            let executor = self.executor.clone();
+            let job_clone = job.clone();
+            let retry_cancellation = self.cancellation.child_token();
+            let retry_cancellation_clone = retry_cancellation.clone();
+            let span = info_span!(JOB_SPAN, job_id = %job.id);
+            let handle = tokio::spawn(async move {
+                executor.retry_replicas(&job_clone, retry_cancellation_clone).await
+            }.instrument(span));
+
+            self.replica_retries.insert(job.id.clone(), (handle, retry_cancellation));
+        }
+    }
+
+    /// Check the targets in the background once the probe interval has passed; a round
+    /// waiting on an unreachable target never holds up the loop or the next round
+    fn start_target_probe(&mut self) {
+        let Some(config) = self.config.target_probe else {
+            return;
+        };
+        if Instant::now() < self.next_target_probe || self.target_probe.as_ref().is_some_and(|probe| !probe.is_finished()) {
+            return;
+        }
+        self.next_target_probe = Instant::now() + Duration::from_secs(config.interval_seconds.max(60));
+
+        let jobs = self.config.jobs.clone();
+        let state_manager = self.state_manager.clone();
+        let backends = self.executor.backends.clone();
+        let notifications = self.config.notifications.clone();
+        self.target_probe = Some(tokio::spawn(async move {
+            probe_targets(&jobs, &state_manager, &backends, config, notifications.as_ref()).await;
+        }));
+    }
+
+    /// Next queued job to start. With disk-aware scheduling a job that shares a physical
+    /// disk with a running job keeps its place, and jobs on other disks go first.
+    fn next_queued<T>(&mut self, running_jobs: &std::collections::HashMap<String, T>) -> Option<crate::scheduler::QueuedJob> {
+        if !self.config.disk_aware_scheduling {
+            return self.job_queue.pop_front();
+        }
+
+        let job_ids: Vec<String> = running_jobs.keys()
+            .chain(self.job_queue.iter().map(|q| &q.job_id))
+            .filter(|id| !self.job_disks.contains_key(*id))
+            .cloned()
+            .collect();
+        for job_id in job_ids {
+            let configured = self.config.jobs.iter().find(|j| j.id == job_id);
+            let disks = configured.or_else(|| self.adhoc_jobs.get(&job_id)).map(disks_used_by).unwrap_or_default();
+            self.job_disks.insert(job_id, disks);
+        }
+
+        let job_disks = &self.job_disks;
+        self.job_queue.pop_first(|queued| {
+            let free = !shares_disk(job_disks, &queued.job_id, running_jobs.keys());
+            if !free {
+                debug!("Job {} waits for a running job on the same disk", queued.job_id);
+            }
+            free
+        })
+    }
+
+    /// Job slots available now: the active throttle window's limit, else the global one
+    fn concurrency_limit(&self) -> usize {
+        active_window(&self.config.throttle, Local::now().naive_local())
+            .and_then(|w| w.max_concurrent_jobs)
+            .or(self.config.max_concurrent_jobs)
+            .map(|n| n.max(1))
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Job slots allowed by the memory limit. Above it, running jobs finish but no new
+    /// ones start; one job may still start when nothing runs, so the queue moves on.
+    fn memory_slots(&mut self, running: usize) -> usize {
+        let Some(limit_mb) = self.config.memory_limit_mb else {
+            return usize::MAX;
+        };
+        let Some(resident) = resident_bytes() else {
+            return usize::MAX;
+        };
+
+        let over = resident > limit_mb.saturating_mul(1024 * 1024);
+        if over != self.memory_over {
+            if over {
+                warn!(
+                    "Resident memory {} MiB is above the {} MiB limit; holding queued jobs",
+                    resident / (1024 * 1024),
+                    limit_mb
+                );
+            } else {
+                info!("Resident memory back under the {} MiB limit", limit_mb);
+            }
+            self.memory_over = over;
+        }
+
+        if over { running.max(1) } else { usize::MAX }
+    }
+
+    /// Surface jobs still waiting for a slot in state and logs
+    async fn record_queued_jobs(&self, running: usize, limit: usize) -> Result<()> {
+        let unrecorded: Vec<_> = {
+            let state = self.state_manager.read().await;
+            self.job_queue.iter()
+                .filter(|q| state.get_job(&q.job_id).is_some_and(|js| js.queued_since.is_none()))
+                .cloned()
+                .collect()
+        };
+
+        for queued in unrecorded {
+            info!(
+                "Job {} queued: {} running (limit {}), {} waiting",
+                queued.job_id,
+                running,
+                limit,
+                self.job_queue.len()
+            );
+            self.state_manager.update_job_state(&queued.job_id, |js| {
+                js.queued_since = Some(queued.queued_since);
+            }).await?;
         }
 
         Ok(())
@@ -210,6 +1073,24 @@ impl ServiceDaemon {
         new_config: ServiceConfig,
         running_jobs: &mut std::collections::HashMap<String, (tokio::task::JoinHandle<Result<()>>, CancellationToken)>,
     ) -> Result<()> {
+        // Locally edited configs may not change what the administrator locked
+        let violations = machine_policy()?.reload_violations(&self.config, &new_config);
+        if !violations.is_empty() {
+            for violation in &violations {
+                error!("Policy violation: {}", violation);
+            }
+            error!("Configuration change rejected, settings are locked by policy; keeping the running configuration");
+            return Ok(());
+        }
+
+        let job_files = match StateManager::job_files(&new_config) {
+            Ok(files) => files,
+            Err(e) => {
+                error!("Configuration change rejected: {:#}; keeping the running configuration", e);
+                return Ok(());
+            }
+        };
+
         // Detect changes in global configuration parameters
         let retention_changed = self.config.retention_count != new_config.retention_count;
         let log_level_changed = self.config.log_level != new_config.log_level;
@@ -220,7 +1101,25 @@ impl ServiceDaemon {
             (crate::config::LogRotation::Hourly, crate::config::LogRotation::Hourly) |
             (crate::config::LogRotation::Never, crate::config::LogRotation::Never)
         );
+        let log_redaction_changed = self.config.log_path_redaction != new_config.log_path_redaction;
         let state_path_changed = self.config.state_path != new_config.state_path;
+        let durability_changed = self.config.durability != new_config.durability;
+        let state_save_changed = self.config.state_save != new_config.state_save;
+        let poll_interval_changed = self.config.poll_interval_seconds != new_config.poll_interval_seconds;
+        let job_files_changed = StateManager::job_files(&self.config).unwrap_or_default() != job_files;
+        let replication_changed = self.config.replication != new_config.replication;
+        let throttle_changed = self.config.throttle != new_config.throttle;
+        let large_run_changed = self.config.large_run != new_config.large_run;
+        let first_run_changed = self.config.first_run != new_config.first_run;
+        let target_probe_changed = self.config.target_probe != new_config.target_probe;
+        let disk_full_changed = self.config.on_disk_full != new_config.on_disk_full;
+        let verify_changed = self.config.verify != new_config.verify;
+        let checksums_changed = self.config.manifest_checksums != new_config.manifest_checksums;
+        let storage_changed = self.config.storage != new_config.storage;
+        let replica_server_changed = self.config.replica_server != new_config.replica_server;
+        let pull_changed = self.config.pull != new_config.pull;
+        let network_targets_changed = self.config.network_targets != new_config.network_targets;
+        let notifications_changed = self.config.notifications != new_config.notifications;
 
         // Log detected configuration changes
         if retention_changed {
@@ -255,6 +1154,176 @@ impl ServiceDaemon {
             );
         }
 
+        if log_redaction_changed {
+            info!(
+                "Log path redaction changed: {:?} -> {:?}",
+                self.config.log_path_redaction,
+                new_config.log_path_redaction
+            );
+            set_path_redaction(new_config.log_path_redaction);
+            load_path_redaction_key(&self.state_manager, new_config.log_path_redaction).await;
+        }
+
+        if durability_changed {
+            info!(
+                "Durability changed: {:?} -> {:?}",
+                self.config.durability,
+                new_config.durability
+            );
+        }
+
+        if state_save_changed {
+            info!(
+                "State save mode changed: {:?} -> {:?}",
+                self.config.state_save,
+                new_config.state_save
+            );
+            self.state_manager.set_save_mode(new_config.state_save);
+        }
+
+        if poll_interval_changed {
+            info!(
+                "Poll interval changed: {}s -> {}s",
+                self.config.poll_interval_seconds,
+                new_config.poll_interval_seconds
+            );
+        }
+
+        if job_files_changed {
+            info!("Per-job state files changed: {:?}", job_files);
+            self.state_manager.set_job_files(job_files).await?;
+            alert_state_recoveries(&self.state_manager, &new_config);
+        }
+
+        if replication_changed {
+            info!(
+                "Replication policy changed: {:?} -> {:?}",
+                self.config.replication,
+                new_config.replication
+            );
+            self.executor.set_replication(new_config.replication);
+        }
+
+        if throttle_changed {
+            info!(
+                "Throttle calendar changed: {:?} -> {:?}",
+                self.config.throttle,
+                new_config.throttle
+            );
+            self.bandwidth.set_calendar(new_config.throttle.clone());
+        }
+
+        if large_run_changed {
+            info!(
+                "Large run check changed: {:?} -> {:?}",
+                self.config.large_run,
+                new_config.large_run
+            );
+            self.executor.set_large_run(new_config.large_run);
+        }
+
+        if first_run_changed {
+            info!(
+                "First run review changed: {:?} -> {:?}",
+                self.config.first_run,
+                new_config.first_run
+            );
+            self.executor.set_first_run(new_config.first_run);
+        }
+
+        if target_probe_changed {
+            info!(
+                "Target probe changed: {:?} -> {:?}",
+                self.config.target_probe,
+                new_config.target_probe
+            );
+            self.next_target_probe = Instant::now();
+        }
+
+        if disk_full_changed {
+            info!(
+                "Disk-full handling changed: {:?} -> {:?}",
+                self.config.on_disk_full,
+                new_config.on_disk_full
+            );
+            self.executor.set_disk_full(new_config.on_disk_full);
+        }
+
+        if verify_changed {
+            info!(
+                "Verification changed: {:?} -> {:?}",
+                self.config.verify,
+                new_config.verify
+            );
+            self.executor.set_verify(new_config.verify);
+        }
+
+        if checksums_changed {
+            info!(
+                "Manifest checksums changed: {} -> {}",
+                self.config.manifest_checksums,
+                new_config.manifest_checksums
+            );
+            self.executor.set_manifest_checksums(new_config.manifest_checksums);
+        }
+
+        if storage_changed {
+            info!("Storage backend settings changed");
+            self.executor.set_storage(&new_config.storage);
+        }
+
+        if pull_changed {
+            info!(
+                "Pull settings changed: {:?} -> {:?}",
+                self.config.pull,
+                new_config.pull
+            );
+            self.executor.set_pull(new_config.pull.clone());
+        }
+
+        if network_targets_changed {
+            info!(
+                "Network target settings changed: wait {}s, retry every {}s, {} credential(s)",
+                new_config.network_targets.wait_seconds,
+                new_config.network_targets.retry_interval_seconds,
+                new_config.network_targets.credentials.len()
+            );
+            self.executor.set_network_targets(new_config.network_targets.clone());
+        }
+
+        if notifications_changed {
+            match &new_config.notifications {
+                Some(notifications) => info!(
+                    "Email notifications changed: {} recipient(s) through {}",
+                    notifications.to.len(),
+                    notifications.smtp_server
+                ),
+                None => info!("Email notifications disabled"),
+            }
+            self.executor.set_notifications(new_config.notifications.clone());
+        }
+
+        if self.config.autotune != new_config.autotune {
+            info!("Copy autotuning changed: {} -> {}", self.config.autotune, new_config.autotune);
+            self.executor.set_autotune(new_config.autotune);
+        }
+
+        if self.config.http_api != new_config.http_api {
+            warn!(
+                "HTTP API settings changed: {:?} -> {:?}. This requires a service restart to take effect.",
+                self.config.http_api,
+                new_config.http_api
+            );
+        }
+
+        if replica_server_changed {
+            warn!(
+                "Replica server settings changed: {:?} -> {:?}. This requires a service restart to take effect.",
+                self.config.replica_server,
+                new_config.replica_server
+            );
+        }
+
         if state_path_changed {
             warn!(
                 "State path changed: {:?} -> {:?}. This requires a service restart to take effect.",
@@ -286,6 +1355,12 @@ impl ServiceDaemon {
             info!("Retention count updated successfully");
         }
 
+        // Apply durability changes
+        if durability_changed {
+            self.executor.set_durability(new_config.durability);
+            self.state_manager.set_durability(new_config.durability);
+        }
+
         // Detect job configuration changes
         let changes = self.scheduler.detect_config_changes(
             &self.config.jobs,
@@ -294,6 +1369,10 @@ impl ServiceDaemon {
 
         // Handle removed jobs - cancel with token before aborting
         for removed_id in &changes.removed {
+            if self.job_queue.remove(removed_id).is_some() {
+                info!("Job {} removed from config, dropped from queue", removed_id);
+            }
+
             if let Some((handle, token)) = running_jobs.remove(removed_id) {
                 warn!("Job {} removed from config, cancelling running backup", removed_id);
 
@@ -389,6 +1468,14 @@ impl ServiceDaemon {
 
         // Update config
         self.config = new_config;
+        self.job_disks.clear();
+        record_locked_settings(&self.state_manager, &self.config).await?;
+
+        // A running replica server keeps its settings, but follows job changes
+        if let Some(server_config) = &self.config.replica_server {
+            *self.replica_jobs.write().unwrap() = exposed_jobs(server_config, &self.config.jobs);
+        }
+        *self.api_jobs.write().unwrap() = self.config.jobs.iter().map(|job| job.id.clone()).collect();
 
         // Initialize new jobs
         self.scheduler.initialize_jobs(&self.config.jobs).await?;
@@ -408,9 +1495,18 @@ impl ServiceDaemon {
 
     /// Shutdown - wait for running jobs
     async fn shutdown_gracefully(
-        &self,
+        &mut self,
         running_jobs: &mut std::collections::HashMap<String, (tokio::task::JoinHandle<Result<()>>, CancellationToken)>,
     ) -> Result<()> {
+        // Queued jobs never started, so just forget them
+        for queued in self.job_queue.clear() {
+            debug!("Dropping queued job on shutdown: {}", queued.job_id);
+            self.state_manager.update_job_state(&queued.job_id, |js| {
+                js.queued_since = None;
+            }).await?;
+        }
+        self.metrics.set_queue_length(0);
+
         info!("Waiting for {} running jobs to complete...", running_jobs.len());
 
         // Wait for all jobs with timeout
@@ -418,14 +1514,9 @@ impl ServiceDaemon {
         let start = std::time::Instant::now();
 
         while !running_jobs.is_empty() && start.elapsed() < timeout {
-            running_jobs.retain(|id, (handle, _token)| {
-                if handle.is_finished() {
-                    info!("Job finished during shutdown: {}", id);
-                    false
-                } else {
-                    true
-                }
-            });
+            for job_id in self.collect_finished_jobs(running_jobs, &[]).await {
+                info!("Job finished during shutdown: {}", job_id);
+            }
 
             if !running_jobs.is_empty() {
                 sleep(Duration::from_secs(1)).await;
@@ -444,12 +1535,362 @@ impl ServiceDaemon {
         }
 
         // Final state save
+        self.scheduler.refresh_interval_anchors(&self.config.jobs).await;
         self.state_manager.save().await?;
 
+        let snapshot = self.metrics.snapshot();
+        info!(
+            "Main loop metrics: {} iterations (avg {}us, max {}us), {} jobs spawned, {} panicked, {} config reloads",
+            snapshot.loop_iterations,
+            snapshot.avg_iteration_micros,
+            snapshot.max_iteration_micros,
+            snapshot.jobs_spawned,
+            snapshot.job_panics,
+            snapshot.config_reloads
+        );
+
         // Flush logging before shutdown
         info!("Flushing logs before shutdown...");
         shutdown_logging();
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Timer for the periodic check, first ticking one interval from now. A tick missed while
+/// the loop was busy comes late rather than twice in a row.
+fn poll_timer(seconds: u64) -> Interval {
+    let period = Duration::from_secs(seconds).clamp(Duration::from_secs(1), MAX_POLL_INTERVAL);
+    let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+/// Wait for one job started by `run_pending`, noting it when it failed
+async fn collect_finished(tasks: &mut tokio::task::JoinSet<(String, Result<()>)>, failed: &mut Vec<String>) -> Option<String> {
+    match tasks.join_next().await {
+        Some(Ok((job_id, Ok(())))) => Some(job_id),
+        Some(Ok((job_id, Err(e)))) => {
+            error!("Job {} failed: {}", job_id, e);
+            failed.push(job_id.clone());
+            Some(job_id)
+        }
+        Some(Err(e)) => {
+            error!("Job task failed: {}", e);
+            failed.push("(panicked)".to_string());
+            None
+        }
+        None => None,
+    }
+}
+
+/// Text of a panic payload; `panic!` passes a `&str` or a `String`
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+/// Refuse to start when the config changes settings locked by policy since the service
+/// last ran, e.g. edited while it was stopped; otherwise record the settings it runs with
+async fn enforce_locked_settings(state_manager: &StateManager, config: &ServiceConfig) -> Result<()> {
+    let recorded = state_manager.read().await.locked_settings.clone();
+    let violations = machine_policy()?.startup_violations(recorded.as_ref(), config);
+    if !violations.is_empty() {
+        for violation in &violations {
+            error!("Policy violation: {}", violation);
+        }
+        anyhow::bail!("Settings are locked by policy and the configuration changed them since the service last ran; \
+            restore the previous settings to start the service");
+    }
+
+    record_locked_settings(state_manager, config).await
+}
+
+/// Remember the protected settings of the running config, for `enforce_locked_settings`
+async fn record_locked_settings(state_manager: &StateManager, config: &ServiceConfig) -> Result<()> {
+    let settings = LockedSettings::of(config);
+    if state_manager.read().await.locked_settings.as_ref() == Some(&settings) {
+        return Ok(());
+    }
+
+    state_manager.write().await.locked_settings = Some(settings);
+    state_manager.save().await
+}
+
+/// Load the key hashing folders in log output, if `mode` hashes them
+async fn load_path_redaction_key(state_manager: &StateManager, mode: PathRedaction) {
+    if mode == PathRedaction::Hash
+        && let Err(e) = load_redaction_key(&state_manager.secrets()).await
+    {
+        warn!("Failed to load the log redaction key, folders are left out instead of hashed: {:#}", e);
+    }
+}
+
+/// Point out jobs that need features slim mode leaves out
+/// Raise an alert for each corrupt state file replaced while loading, in the background
+fn alert_state_recoveries(state_manager: &StateManager, config: &ServiceConfig) {
+    for recovery in state_manager.take_recoveries() {
+        let notifications = config.notifications.clone();
+        tokio::spawn(async move {
+            raise_alert(notifications.as_ref(), "KeepHive: corrupt state file recovered", &recovery).await;
+        });
+    }
+}
+
+fn warn_slim_mode_limits(jobs: &[BackupJob]) {
+    for job in jobs {
+        match job.kind {
+            JobKind::SystemState => warn!("Job {} will fail in slim mode: system-state jobs need shadow copies", job.id),
+            JobKind::WslDistro => warn!("Job {} will fail in slim mode: wsl-distro jobs need wsl.exe", job.id),
+            _ if !job.busy_processes.is_empty() => {
+                warn!("Job {}: busy_processes are not checked in slim mode", job.id);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Physical disks a job reads from or writes to
+fn disks_used_by(job: &BackupJob) -> Vec<String> {
+    let mut disks = crate::core::physical_disks(&job.source);
+    disks.extend(crate::core::physical_disks(&job.target));
+    disks.sort();
+    disks.dedup();
+    disks
+}
+
+/// Whether `job_id` uses a disk one of the `running` jobs uses; jobs without known disks never do
+fn shares_disk<'a>(
+    disks: &std::collections::HashMap<String, Vec<String>>,
+    job_id: &str,
+    running: impl IntoIterator<Item = &'a String>,
+) -> bool {
+    let Some(own) = disks.get(job_id) else {
+        return false;
+    };
+
+    running.into_iter()
+        .filter_map(|id| disks.get(id))
+        .any(|other| other.iter().any(|disk| own.contains(disk)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_poll_interval_is_kept_within_bounds() {
+        assert_eq!(poll_timer(0).period(), Duration::from_secs(1));
+        assert_eq!(poll_timer(30).period(), Duration::from_secs(30));
+        assert_eq!(poll_timer(86400).period(), MAX_POLL_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn test_poll_timer_ticks_while_other_events_keep_the_loop_busy() {
+        let mut poll = poll_timer(1);
+        let started = Instant::now();
+        let mut ticks = 0;
+
+        // Events every 50ms would put off a sleep started anew on each pass for good
+        while started.elapsed() < Duration::from_millis(2500) {
+            tokio::select! {
+                _ = sleep(Duration::from_millis(50)) => {}
+                _ = poll.tick() => ticks += 1,
+            }
+        }
+
+        assert_eq!(ticks, 2);
+    }
+
+    #[tokio::test]
+    async fn test_crashed_and_cancelled_job_tasks_fail_their_job() {
+        use crate::state::{HistoryStore, JobStatus, RunOutcome};
+
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = ["panics", "aborted"].map(|id| serde_json::json!({
+            "id": id,
+            "source": dir.path().join(id),
+            "target": dir.path().join("backups").join(id),
+            "schedule": { "type": "interval", "seconds": 3600 }
+        }));
+        let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "state_path": dir.path().join("state.json"),
+            "jobs": jobs
+        })).unwrap();
+        let daemon = ServiceDaemon::new(config).await.unwrap();
+        daemon.scheduler.initialize_jobs(&daemon.config.jobs).await.unwrap();
+
+        let mut running_jobs = std::collections::HashMap::new();
+        for job_id in ["panics", "aborted"] {
+            daemon.state_manager.update_job_state(job_id, |js| {
+                js.status = JobStatus::Running { started_at: Utc::now() };
+            }).await.unwrap();
+
+            let handle = tokio::spawn(async move {
+                if job_id == "panics" {
+                    panic!("job crashed");
+                }
+                sleep(Duration::from_secs(3600)).await;
+                Ok(())
+            });
+            running_jobs.insert(job_id.to_string(), (handle, CancellationToken::new()));
+        }
+        running_jobs["aborted"].0.abort();
+
+        let mut finished = daemon.collect_finished_jobs(&mut running_jobs, &["panics".to_string(), "aborted".to_string()]).await;
+        finished.sort();
+        assert_eq!(finished, ["aborted", "panics"]);
+
+        for job_id in ["panics", "aborted"] {
+            let status = daemon.state_manager.read().await.get_job(job_id).unwrap().status.clone();
+            assert!(matches!(status, JobStatus::Failed { .. }), "{}: {:?}", job_id, status);
+
+            let history = HistoryStore::read_recent(daemon.state_manager.history_path(), Some(job_id), 10).await.unwrap();
+            assert_eq!(history.len(), 1, "{} has one run recorded", job_id);
+            assert!(matches!(history[0].outcome, RunOutcome::Failed { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_due_replica_retries_run_outside_the_job_slots() {
+        use crate::core::backup::COMPLETE_MARKER;
+        use crate::state::ReplicaStatus;
+
+        let dir = tempfile::tempdir().unwrap();
+        let backup = dir.path().join("backups").join("docs_2025-01-01_000000_000");
+        tokio::fs::create_dir_all(&backup).await.unwrap();
+        tokio::fs::write(backup.join("a.txt"), b"abc").await.unwrap();
+        tokio::fs::write(backup.join(COMPLETE_MARKER), b"{}").await.unwrap();
+
+        let (due, later) = (dir.path().join("due"), dir.path().join("later"));
+        let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "state_path": dir.path().join("state.json"),
+            "max_concurrent_jobs": 1,
+            "jobs": [{
+                "id": "docs",
+                "source": dir.path().join("docs"),
+                "target": dir.path().join("backups"),
+                "replicas": [due, later],
+                "schedule": { "type": "interval", "seconds": 3600 }
+            }]
+        })).unwrap();
+        let mut daemon = ServiceDaemon::new(config).await.unwrap();
+        daemon.scheduler.initialize_jobs(&daemon.config.jobs).await.unwrap();
+
+        // As left by a failed copy before a restart
+        for (replica, retry_in) in [(&due, -1), (&later, 3600)] {
+            daemon.state_manager.update_job_state("docs", |js| {
+                let r = js.replica_mut(replica);
+                r.pending_backup = Some(backup.clone());
+                r.status = ReplicaStatus::Failed {
+                    error: "unreachable".to_string(),
+                    attempts: 1,
+                    next_retry: Some(Utc::now() + chrono::Duration::seconds(retry_in)),
+                };
+            }).await.unwrap();
+        }
+
+        daemon.start_replica_retries(&std::collections::HashMap::<String, ()>::new()).await;
+        let (handle, _) = daemon.replica_retries.remove("docs").unwrap();
+        handle.await.unwrap();
+
+        let state = daemon.state_manager.read().await;
+        let replicas = &state.get_job("docs").unwrap().replicas;
+        assert_eq!(replicas[0].status, ReplicaStatus::Synced);
+        assert_eq!(replicas[0].pending_backup, None);
+        assert!(due.join("docs_2025-01-01_000000_000").join("a.txt").exists());
+        assert!(matches!(replicas[1].status, ReplicaStatus::Failed { attempts: 1, .. }));
+        assert_eq!(replicas[1].pending_backup.as_ref(), Some(&backup));
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_post_hook_runs_when_the_run_is_aborted() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::create_dir_all(dir.path().join("docs")).await.unwrap();
+        tokio::fs::write(dir.path().join("docs/a.txt"), b"abc").await.unwrap();
+        let outcome = dir.path().join("outcome");
+
+        let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "state_path": dir.path().join("state.json"),
+            "jobs": [{
+                "id": "docs",
+                "source": dir.path().join("docs"),
+                "target": dir.path().join("backups"),
+                "schedule": { "type": "interval", "seconds": 3600 },
+                "hooks": { "post": format!("echo \"$KEEPHIVE_OUTCOME\" > {}", outcome.display()) }
+            }]
+        })).unwrap();
+        let mut daemon = ServiceDaemon::new(config).await.unwrap();
+        daemon.scheduler.initialize_jobs(&daemon.config.jobs).await.unwrap();
+        daemon.inject_faults(FaultPlan { slow: Some(Duration::from_secs(30)), ..FaultPlan::default() });
+
+        // Stuck copying, then removed from the config: cancelled and aborted at once
+        let executor = daemon.executor.clone();
+        let job = daemon.config.jobs[0].clone();
+        let token = CancellationToken::new();
+        let handle = tokio::spawn({
+            let token = token.clone();
+            async move { executor.execute_job(&job, token).await }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        token.cancel();
+        handle.abort();
+
+        for _ in 0..50 {
+            if outcome.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(tokio::fs::read_to_string(&outcome).await.unwrap().trim(), "cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_adopt_volume_forgets_the_recorded_target_volume() {
+        use crate::core::VolumeId;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "state_path": dir.path().join("state.json"),
+            "jobs": [{
+                "id": "docs",
+                "source": dir.path().join("docs"),
+                "target": dir.path().join("backups"),
+                "schedule": { "type": "interval", "seconds": 3600 }
+            }]
+        })).unwrap();
+        let mut daemon = ServiceDaemon::new(config).await.unwrap();
+        daemon.scheduler.initialize_jobs(&daemon.config.jobs).await.unwrap();
+        daemon.state_manager.update_job_state("docs", |js| {
+            js.target_volume = Some(VolumeId { serial: 0x1234_ABCD, label: "BACKUP".to_string(), root: dir.path().to_path_buf() });
+        }).await.unwrap();
+
+        let running_jobs = std::collections::HashMap::new();
+        let unknown = daemon.handle_control_request(ControlRequest::AdoptVolume { job_id: "photos".to_string() }, &running_jobs).await;
+        assert!(!unknown.ok);
+
+        let adopted = daemon.handle_control_request(ControlRequest::AdoptVolume { job_id: "docs".to_string() }, &running_jobs).await;
+        assert!(adopted.ok, "{}", adopted.message);
+        assert!(adopted.message.contains("BACKUP"), "{}", adopted.message);
+        assert_eq!(daemon.state_manager.read().await.get_job("docs").unwrap().target_volume, None);
+    }
+
+    #[tokio::test]
+    async fn test_job_tasks_signal_their_end_even_when_they_panic() {
+        let (finished, mut finished_rx) = mpsc::unbounded_channel();
+
+        for (job_id, panics) in [("docs", false), ("photos", true)] {
+            let signal = FinishedSignal { job_id: job_id.to_string(), finished: finished.clone() };
+            let handle = tokio::spawn(async move {
+                let _signal = signal;
+                if panics {
+                    panic!("job crashed");
+                }
+            });
+            assert_eq!(handle.await.is_err(), panics);
+            assert_eq!(finished_rx.recv().await.as_deref(), Some(job_id));
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! Embedded HTTP/1.1 server for dashboards and remote monitoring. Status is read from
+//! the state manager; runs and cancellations go to the daemon loop like control requests.
+//!
+//! | Method | Path | |
+//! |---|---|---|
+//! | GET | `/status` | Daemon version and job counts |
+//! | GET | `/jobs` | State of every job |
+//! | POST | `/jobs/{id}/run` | Run a job now |
+//! | POST | `/jobs/{id}/cancel` | Cancel a running or queued job |
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::HttpApiConfig;
+use crate::service::control::dispatch;
+use crate::service::{ControlCommand, ControlRequest};
+use crate::state::{JobStatus, StateManager};
+
+/// Largest request head (request line and headers) accepted
+const MAX_HEAD_BYTES: u64 = 16 * 1024;
+
+/// Largest request body read and discarded; the API takes no bodies
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// Clients that do not send a complete request this fast are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shorter tokens are too easy to guess
+const MIN_TOKEN_LEN: usize = 16;
+
+/// IDs of the configured jobs the API may run and cancel, kept in step with the config
+pub type ApiJobs = Arc<RwLock<HashSet<String>>>;
+
+pub struct HttpApi {
+    bind: String,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// Bearer token clients must send; `None` only on loopback addresses, which then
+    /// only answer reads
+    token: Option<String>,
+    /// Whether the API listens on a loopback address, where only loopback host names are accepted
+    loopback: bool,
+    state: Arc<StateManager>,
+    jobs: ApiJobs,
+    commands: mpsc::Sender<ControlCommand>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    host: Option<String>,
+    origin: Option<String>,
+}
+
+impl HttpApi {
+    pub fn new(config: &HttpApiConfig, state: Arc<StateManager>, jobs: ApiJobs, commands: mpsc::Sender<ControlCommand>) -> Result<Self> {
+        let token = match &config.token_env {
+            Some(variable) => {
+                let token = std::env::var(variable)
+                    .with_context(|| format!("HTTP API token variable {} is not set", variable))?;
+                if token.len() < MIN_TOKEN_LEN {
+                    bail!("HTTP API token in {} is too short (at least {} characters)", variable, MIN_TOKEN_LEN);
+                }
+                Some(token)
+            }
+            None => None,
+        };
+
+        let loopback = config.bind.parse::<SocketAddr>().is_ok_and(|address| address.ip().is_loopback());
+        if token.is_none() && !loopback {
+            bail!("HTTP API on {} needs token_env, only loopback addresses may go without a token", config.bind);
+        }
+
+        Ok(Self {
+            bind: config.bind.clone(),
+            shared: Arc::new(Shared { token, loopback, state, jobs, commands }),
+        })
+    }
+
+    /// Serve connections until cancelled
+    pub async fn serve(self, cancellation: CancellationToken) -> Result<()> {
+        let listener = TcpListener::bind(&self.bind).await
+            .with_context(|| format!("Failed to listen on {}", self.bind))?;
+
+        info!("HTTP API listening on {}", self.bind);
+        self.accept(listener, cancellation).await;
+
+        debug!("HTTP API stopped");
+        Ok(())
+    }
+
+    async fn accept(self, listener: TcpListener, cancellation: CancellationToken) {
+        loop {
+            tokio::select! {
+                _ = cancellation.cancelled() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        let shared = self.shared.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, &shared).await {
+                                debug!("HTTP API client {}: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept HTTP API connection: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Answer one request and close the connection
+async fn handle_connection(mut stream: TcpStream, shared: &Shared) -> Result<()> {
+    let (status, body) = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => respond(&request, shared).await,
+        Ok(Err(e)) => (400, json!({ "error": e.to_string() })),
+        Err(_) => (408, json!({ "error": "Request timed out" })),
+    };
+
+    let body = serde_json::to_vec_pretty(&body)?;
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream.take(MAX_HEAD_BYTES));
+
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+        bail!("Malformed request line");
+    };
+    if !version.starts_with("HTTP/1.") {
+        bail!("Unsupported protocol {}", version);
+    }
+
+    let mut request = Request {
+        method: method.to_string(),
+        // The API has no query parameters
+        path: target.split('?').next().unwrap_or_default().to_string(),
+        authorization: None,
+        host: None,
+        origin: None,
+    };
+
+    let mut content_length = 0u64;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("Connection closed inside the request head");
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = header.split_once(':') else {
+            bail!("Malformed header");
+        };
+        if name.eq_ignore_ascii_case("authorization") {
+            request.authorization = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("host") {
+            request.host = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("origin") {
+            request.origin = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.trim().parse().context("Invalid Content-Length")?;
+        }
+    }
+
+    // Bodies are ignored, but read so the client sees the response
+    if content_length > MAX_BODY_BYTES {
+        bail!("Request body too large");
+    }
+    // Part of it may already sit in the reader's buffer
+    let buffered = reader.buffer().len() as u64;
+    reader.get_mut().set_limit(content_length.saturating_sub(buffered));
+    tokio::io::copy(&mut (&mut reader).take(content_length), &mut tokio::io::sink()).await?;
+
+    Ok(request)
+}
+
+async fn respond(request: &Request, shared: &Shared) -> (u16, Value) {
+    if let Some(refusal) = foreign_request(request, shared.loopback) {
+        return (403, json!({ "error": refusal }));
+    }
+
+    if let Some(token) = &shared.token {
+        let expected = format!("Bearer {}", token);
+        if !request.authorization.as_deref().is_some_and(|given| same_token(given, &expected)) {
+            return (401, json!({ "error": "Missing or wrong bearer token" }));
+        }
+    }
+
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["status"]) => (200, status(shared).await),
+        ("GET", ["jobs"]) => {
+            let state = shared.state.read().await;
+            (200, json!(state.jobs))
+        }
+        ("POST", ["jobs", id, action @ ("run" | "cancel")]) => {
+            if shared.token.is_none() {
+                return (403, json!({ "error": "Running and cancelling jobs needs token_env" }));
+            }
+            if !shared.jobs.read().unwrap().contains(*id) {
+                return (404, json!({ "error": format!("Unknown job: {}", id) }));
+            }
+
+            let job_id = id.to_string();
+            let control = if *action == "run" {
+                ControlRequest::RunJob { job_id }
+            } else {
+                ControlRequest::CancelJob { job_id }
+            };
+
+            let response = dispatch(control, &shared.commands).await;
+            let status = if response.ok { 202 } else { 409 };
+            (status, json!({ "ok": response.ok, "message": response.message }))
+        }
+        (_, ["status"] | ["jobs"] | ["jobs", _, "run" | "cancel"]) => {
+            (405, json!({ "error": format!("{} is not allowed on {}", request.method, request.path) }))
+        }
+        _ => (404, json!({ "error": format!("No such endpoint: {}", request.path) })),
+    }
+}
+
+async fn status(shared: &Shared) -> Value {
+    let state = shared.state.read().await;
+    let count = |matches: fn(&JobStatus) -> bool| state.jobs.iter().filter(|js| matches(&js.status)).count();
+
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "jobs": state.jobs.len(),
+        "running": count(|status| matches!(status, JobStatus::Running { .. })),
+        "failed": count(|status| matches!(status, JobStatus::Failed { .. })),
+        "queued": state.jobs.iter().filter(|js| js.queued_since.is_some()).count(),
+        "last_updated": state.last_updated,
+    })
+}
+
+/// Why a request looks like it came from a web page rather than a client of the API: an
+/// `Origin` other than the API itself, or on loopback a `Host` that is not a loopback name,
+/// as sent after DNS rebinding
+fn foreign_request(request: &Request, loopback: bool) -> Option<String> {
+    if let Some(origin) = &request.origin
+        && request.host.as_ref().is_none_or(|host| *origin != format!("http://{}", host))
+    {
+        return Some(format!("Requests from {} are not accepted", origin));
+    }
+
+    if loopback
+        && let Some(host) = &request.host
+        && !is_loopback_host(host)
+    {
+        return Some(format!("Host {} is not accepted on a loopback address", host));
+    }
+
+    None
+}
+
+/// Whether a `Host` header names this machine through loopback: `localhost` or a loopback address
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Compared in constant time, so response timing does not reveal the token
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        _ => "Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::ControlResponse;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    async fn request(address: &str, head: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_status_jobs_and_control_endpoints() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(StateManager::new(dir.path().join("state.json")).await.unwrap());
+        state.write().await.jobs.push(crate::state::JobState::new("docs".into(), PathBuf::from("/src"), PathBuf::from("/dst")));
+
+        // Answer like the daemon loop would
+        let (commands, mut rx) = mpsc::channel::<ControlCommand>(4);
+        tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                let response = match command.request {
+                    ControlRequest::RunJob { job_id } => ControlResponse::ok(format!("Job {} queued", job_id)),
+                    _ => ControlResponse::error("Job docs is not running or queued"),
+                };
+                let _ = command.reply.send(response);
+            }
+        });
+
+        let jobs = ApiJobs::new(RwLock::new(HashSet::from(["docs".to_string()])));
+        let api = HttpApi {
+            bind: String::new(),
+            shared: Arc::new(Shared { token: Some("0123456789abcdef".into()), loopback: false, state, jobs, commands }),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let cancellation = CancellationToken::new();
+        tokio::spawn(api.accept(listener, cancellation.clone()));
+
+        let auth = "Authorization: Bearer 0123456789abcdef\r\n";
+        assert_eq!(request(&address, "GET /status HTTP/1.1\r\n\r\n").await.0, 401);
+
+        let (status, body) = request(&address, &format!("GET /status HTTP/1.1\r\n{}\r\n", auth)).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["jobs"], 1);
+
+        let (status, body) = request(&address, &format!("GET /jobs HTTP/1.1\r\n{}\r\n", auth)).await;
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["id"], "docs");
+
+        let (status, body) = request(&address, &format!("POST /jobs/docs/run HTTP/1.1\r\n{}Content-Length: 2\r\n\r\n{{}}", auth)).await;
+        assert_eq!((status, body["message"].as_str()), (202, Some("Job docs queued")));
+
+        assert_eq!(request(&address, &format!("POST /jobs/docs/cancel HTTP/1.1\r\n{}\r\n", auth)).await.0, 409);
+        assert_eq!(request(&address, &format!("POST /jobs/other/run HTTP/1.1\r\n{}\r\n", auth)).await.0, 404);
+        assert_eq!(request(&address, &format!("GET /jobs/docs/run HTTP/1.1\r\n{}\r\n", auth)).await.0, 405);
+        assert_eq!(request(&address, &format!("POST /jobs/docs/run HTTP/1.1\r\n{}Host: api:7480\r\nOrigin: http://evil.example\r\n\r\n", auth)).await.0, 403);
+
+        cancellation.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_token_required_off_loopback() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(StateManager::new(dir.path().join("state.json")).await.unwrap());
+        let config = |bind: &str| HttpApiConfig { bind: bind.to_string(), token_env: None };
+        let (commands, _rx) = mpsc::channel(1);
+
+        assert!(HttpApi::new(&config("0.0.0.0:7480"), state.clone(), ApiJobs::default(), commands.clone()).is_err());
+        assert!(HttpApi::new(&config("127.0.0.1:7480"), state, ApiJobs::default(), commands).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_loopback_without_token_only_answers_local_reads() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(StateManager::new(dir.path().join("state.json")).await.unwrap());
+        let jobs = ApiJobs::new(RwLock::new(HashSet::from(["docs".to_string()])));
+        let (commands, _rx) = mpsc::channel(1);
+
+        let api = HttpApi {
+            bind: String::new(),
+            shared: Arc::new(Shared { token: None, loopback: true, state, jobs, commands }),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let cancellation = CancellationToken::new();
+        tokio::spawn(api.accept(listener, cancellation.clone()));
+
+        assert_eq!(request(&address, &format!("GET /status HTTP/1.1\r\nHost: {}\r\n\r\n", address)).await.0, 200);
+        assert_eq!(request(&address, "GET /status HTTP/1.1\r\nHost: localhost:7480\r\n\r\n").await.0, 200);
+        assert_eq!(request(&address, "GET /status HTTP/1.1\r\nHost: rebound.example:7480\r\n\r\n").await.0, 403);
+        assert_eq!(request(&address, "POST /jobs/docs/run HTTP/1.1\r\nHost: localhost\r\n\r\n").await.0, 403);
+
+        cancellation.cancel();
+    }
+}
@@ -1,7 +1,12 @@
+pub mod control;
 pub mod daemon;
+pub mod http_api;
 pub mod signals;
 pub mod recovery;
+pub mod target_probe;
 
+pub use control::{ControlCommand, ControlRequest, ControlResponse, ControlServer, SecretValue};
 pub use daemon::ServiceDaemon;
+pub use http_api::HttpApi;
 pub use recovery::RecoveryManager;
 pub use signals::setup_shutdown_handler;
\ No newline at end of file
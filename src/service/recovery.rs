@@ -1,14 +1,46 @@
 use anyhow::Result;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::{info, warn};
 
+use crate::config::BackupJob;
 use crate::core::BackupOrchestrator;
+use crate::state::models::STATE_SCHEMA_VERSION;
+use crate::state::StateManager;
 
-pub struct RecoveryManager;
+pub struct RecoveryManager {
+    state_manager: Arc<StateManager>,
+}
 
 impl RecoveryManager {
-    pub fn new(_state_manager: std::sync::Arc<crate::state::StateManager>) -> Self {
-        Self
+    pub fn new(state_manager: Arc<StateManager>) -> Self {
+        Self { state_manager }
+    }
+
+    /// Mark the backups of a state from before completion markers (schema 1) as complete,
+    /// once, so they are not taken for partial ones. A backup that was being written when
+    /// the old version stopped stays unmarked.
+    pub async fn migrate_completion_markers(&self, jobs: &[BackupJob]) -> Result<()> {
+        let unfinished: Vec<PathBuf> = {
+            let state = self.state_manager.read().await;
+            if state.version >= STATE_SCHEMA_VERSION {
+                return Ok(());
+            }
+            state.jobs.iter()
+                .filter_map(|js| js.active_backup.as_ref().map(|backup| backup.backup_path.clone()))
+                .collect()
+        };
+
+        for job in jobs.iter().filter(|job| job.target.is_dir()) {
+            match BackupOrchestrator::mark_legacy_backups(&job.target, &job.source, &unfinished).await {
+                Ok(0) => {}
+                Ok(marked) => info!("Marked {} earlier backups of job {} as complete", marked, job.id),
+                Err(e) => warn!("Could not mark earlier backups of job {} as complete: {:#}", job.id, e),
+            }
+        }
+
+        self.state_manager.write().await.version = STATE_SCHEMA_VERSION;
+        self.state_manager.save().await
     }
 
     /// Detect and log partial backups on startup
@@ -26,4 +58,4 @@ impl RecoveryManager {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -7,15 +7,42 @@ pub async fn setup_shutdown_handler(cancellation: CancellationToken) {
     tokio::spawn(async move {
         #[cfg(windows)]
         {
-            match signal::ctrl_c().await {
-                Ok(()) => {
-                    info!("Received shutdown signal (Ctrl+C)");
-                    cancellation.cancel();
+            use signal::windows::{ctrl_close, ctrl_shutdown};
+
+            // `docker stop` sends CTRL_SHUTDOWN_EVENT to a container's console process.
+            // Each handler is registered on its own, so Ctrl+C still works if another fails.
+            let mut shutdown = ctrl_shutdown()
+                .inspect_err(|e| eprintln!("Failed to listen for system shutdown: {}", e))
+                .ok();
+            let mut close = ctrl_close()
+                .inspect_err(|e| eprintln!("Failed to listen for console close: {}", e))
+                .ok();
+
+            let shutdown_event = async {
+                match &mut shutdown {
+                    Some(shutdown) => shutdown.recv().await,
+                    None => std::future::pending().await,
                 }
-                Err(e) => {
-                    eprintln!("Failed to listen for shutdown signal: {}", e);
+            };
+            let close_event = async {
+                match &mut close {
+                    Some(close) => close.recv().await,
+                    None => std::future::pending().await,
                 }
+            };
+
+            tokio::select! {
+                result = signal::ctrl_c() => match result {
+                    Ok(()) => info!("Received shutdown signal (Ctrl+C)"),
+                    Err(e) => {
+                        eprintln!("Failed to listen for shutdown signal: {}", e);
+                        return;
+                    }
+                },
+                _ = shutdown_event => info!("Received shutdown signal (system or container shutdown)"),
+                _ = close_event => info!("Received shutdown signal (console closed)"),
             }
+            cancellation.cancel();
         }
 
         #[cfg(unix)]
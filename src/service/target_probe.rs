@@ -0,0 +1,203 @@
+//! Periodic check of backup targets between runs (`target_probe`). A USB drive left
+//! unplugged or a NAS that went offline is reported after a while, instead of only when
+//! the next backup fails.
+
+use chrono::{DateTime, Local, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::{BackupJob, NotificationConfig, TargetProbeConfig};
+use crate::core::probe_target;
+use crate::observability::{format_duration, raise_alert};
+use crate::state::{BackupState, StateManager};
+use crate::storage::{BackendRegistry, TargetUrl};
+
+/// How long one target may take to answer
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Target that has been unreachable for longer than `alert_after_seconds`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetAlert {
+    pub target: PathBuf,
+    pub since: DateTime<Utc>,
+    pub error: String,
+}
+
+/// Targets and replicas that already received a backup; the others may not exist yet
+pub fn probed_targets(jobs: &[BackupJob], state: &BackupState) -> Vec<PathBuf> {
+    let mut targets: Vec<PathBuf> = Vec::new();
+
+    for job in jobs {
+        let Some(js) = state.get_job(&job.id) else {
+            continue;
+        };
+        if js.last_backup.is_some() {
+            targets.push(job.target.clone());
+        }
+        for replica in js.replicas.iter().filter(|r| r.last_synced_at.is_some() && job.replicas.contains(&r.target)) {
+            targets.push(replica.target.clone());
+        }
+    }
+
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// Check every target once, record the results in the state and report targets that
+/// have been unreachable for too long
+pub async fn probe_targets(
+    jobs: &[BackupJob],
+    state_manager: &StateManager,
+    backends: &BackendRegistry,
+    config: TargetProbeConfig,
+    notifications: Option<&NotificationConfig>,
+) {
+    let targets = probed_targets(jobs, &*state_manager.read().await);
+
+    let mut results = Vec::new();
+    for target in &targets {
+        let error = probe(target, backends).await.err().map(|e| format!("{:#}", e));
+        results.push((target, error, Utc::now()));
+    }
+
+    let alerts = {
+        let mut state = state_manager.write().await;
+        state.target_health.retain(|health| targets.contains(&health.target));
+
+        for (target, error, at) in results {
+            if let Some(outage) = state.record_probe(target, error, at) {
+                let lasted = (outage.until - outage.since).to_std().unwrap_or_default();
+                info!("Backup target {} reachable again after {}", target.display(), format_duration(lasted));
+            }
+        }
+        due_alerts(&mut state, Duration::from_secs(config.alert_after_seconds), Utc::now())
+    };
+
+    if let Err(e) = state_manager.save().await {
+        warn!("Failed to save target health: {}", e);
+    }
+
+    for alert in alerts {
+        report(&alert, notifications).await;
+    }
+}
+
+/// Targets unreachable for at least `alert_after` that were not reported yet; they are
+/// marked as reported
+pub fn due_alerts(state: &mut BackupState, alert_after: Duration, now: DateTime<Utc>) -> Vec<TargetAlert> {
+    let alert_after = chrono::Duration::from_std(alert_after).unwrap_or(chrono::Duration::MAX);
+
+    state.target_health.iter_mut()
+        .filter_map(|health| {
+            let since = health.unreachable_since?;
+            if health.alerted || now - since < alert_after {
+                return None;
+            }
+            health.alerted = true;
+            Some(TargetAlert {
+                target: health.target.clone(),
+                since,
+                error: health.error.clone().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Local folders are listed and written to; remote targets are listed
+async fn probe(target: &std::path::Path, backends: &BackendRegistry) -> anyhow::Result<()> {
+    if TargetUrl::from_path(target).is_none() {
+        if !tokio::fs::try_exists(target).await.unwrap_or(false) {
+            anyhow::bail!("{} does not exist", target.display());
+        }
+        return probe_target(target, PROBE_TIMEOUT).await;
+    }
+
+    let backend = backends.open(target)?;
+    match tokio::time::timeout(PROBE_TIMEOUT, backend.list("")).await {
+        Ok(result) => result.map(|_| ()),
+        Err(_) => anyhow::bail!("{} did not answer within {}s", target.display(), PROBE_TIMEOUT.as_secs()),
+    }
+}
+
+/// Log the alert and raise it in the event log and by email
+async fn report(alert: &TargetAlert, notifications: Option<&NotificationConfig>) {
+    let since = alert.since.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    warn!("Backup target {} unreachable since {}: {}", alert.target.display(), since, alert.error);
+
+    let subject = format!("KeepHive: backup target {} unreachable", alert.target.display());
+    let body = [
+        format!("Target:    {}", alert.target.display()),
+        format!("Since:     {}", since),
+        format!("Error:     {}", alert.error),
+        String::new(),
+        "Backups to this target fail until it can be reached again.".to_string(),
+    ].join("\n");
+
+    raise_alert(notifications, &subject, &body).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{BackupMetadata, JobState, TARGET_OUTAGES};
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_outage_is_alerted_once_and_recorded_when_it_ends() {
+        let mut state = BackupState::new();
+        let target = PathBuf::from("/mnt/usb");
+        let alert_after = Duration::from_secs(4 * 3600);
+
+        assert_eq!(state.record_probe(&target, None, at(0)), None);
+        assert_eq!(state.record_probe(&target, Some("gone".to_string()), at(1)), None);
+        assert_eq!(state.record_probe(&target, Some("still gone".to_string()), at(3)), None);
+        assert!(due_alerts(&mut state, alert_after, at(3)).is_empty());
+
+        state.record_probe(&target, Some("still gone".to_string()), at(5));
+        let alerts = due_alerts(&mut state, alert_after, at(5));
+        assert_eq!(alerts, [TargetAlert { target: target.clone(), since: at(1), error: "still gone".to_string() }]);
+        assert!(due_alerts(&mut state, alert_after, at(6)).is_empty(), "An outage is reported once");
+
+        let outage = state.record_probe(&target, None, at(7)).unwrap();
+        assert_eq!((outage.since, outage.until), (at(1), at(7)));
+
+        let health = &state.target_health[0];
+        assert_eq!(health.unreachable_since, None);
+        assert_eq!(health.last_reachable, Some(at(7)));
+        assert!(!health.alerted);
+        assert_eq!(health.outages.len(), 1);
+    }
+
+    #[test]
+    fn test_outage_history_is_bounded() {
+        let mut state = BackupState::new();
+        let target = PathBuf::from("/mnt/usb");
+        for hour in 0..(TARGET_OUTAGES as u32 + 2) {
+            state.record_probe(&target, Some("gone".to_string()), at(hour % 24));
+            state.record_probe(&target, None, at(hour % 24));
+        }
+        assert_eq!(state.target_health[0].outages.len(), TARGET_OUTAGES);
+    }
+
+    #[test]
+    fn test_only_targets_with_backups_are_probed() {
+        let jobs: Vec<BackupJob> = serde_json::from_str(r#"[
+            { "id": "docs", "source": "/srv/docs", "target": "/mnt/usb", "replicas": ["/mnt/nas"], "schedule": { "type": "interval", "seconds": 3600 } },
+            { "id": "new", "source": "/srv/new", "target": "/mnt/other", "schedule": { "type": "interval", "seconds": 3600 } }
+        ]"#).unwrap();
+
+        let mut state = BackupState::new();
+        let mut docs = JobState::new("docs".to_string(), "/srv/docs".into(), "/mnt/usb".into());
+        docs.last_backup = Some(BackupMetadata::new("docs_1".to_string(), "/mnt/usb/docs_1".into()));
+        state.upsert_job(docs);
+        state.upsert_job(JobState::new("new".to_string(), "/srv/new".into(), "/mnt/other".into()));
+
+        assert_eq!(probed_targets(&jobs, &state), [PathBuf::from("/mnt/usb")]);
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Number of run records kept once the history file is compacted
+pub const HISTORY_MAX_RECORDS: usize = 1000;
+
+/// Outcome of a single job run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum RunOutcome {
+    Success {
+        backup_name: String,
+        files_copied: u64,
+        bytes_copied: u64,
+        files_skipped: u64,
+    },
+    Failed {
+        error: String,
+    },
+    /// The run did not start, e.g. a large run that was not confirmed
+    Skipped {
+        reason: String,
+    },
+}
+
+/// One finished run, scheduled or ad-hoc
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RunRecord {
+    pub job_id: String,
+
+    /// Whether the job was submitted at runtime rather than configured
+    #[serde(default)]
+    pub adhoc: bool,
+
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub outcome: RunOutcome,
+}
+
+/// Append-only run history stored as JSON lines next to the state file
+pub struct HistoryStore {
+    path: PathBuf,
+    /// Serializes writers and caches the record count (None until first counted)
+    record_count: Mutex<Option<usize>>,
+}
+
+impl HistoryStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            record_count: Mutex::new(None),
+        }
+    }
+
+    /// History file belonging to a state file (state.json -> state.json.history.jsonl)
+    pub fn path_for_state_file(state_path: &Path) -> PathBuf {
+        let file_name = state_path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "state".to_string());
+        state_path.with_file_name(format!("{}.history.jsonl", file_name))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a run record, compacting the file once it holds twice the record limit
+    pub async fn append(&self, record: &RunRecord) -> Result<()> {
+        let mut record_count = self.record_count.lock().await;
+
+        let mut line = serde_json::to_string(record)
+            .context("Failed to serialize run record")?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open history file: {}", self.path.display()))?;
+
+        file.write_all(line.as_bytes()).await
+            .context("Failed to append run record")?;
+        file.flush().await?;
+        drop(file);
+
+        let count = match *record_count {
+            Some(count) => count + 1,
+            None => count_lines(&self.path).await?,
+        };
+
+        *record_count = Some(if count > HISTORY_MAX_RECORDS * 2 {
+            self.compact().await?
+        } else {
+            count
+        });
+
+        Ok(())
+    }
+
+    /// Keep only the newest records, returning how many remain. The file is copied
+    /// line by line, so compacting a large history does not load it into memory.
+    async fn compact(&self) -> Result<usize> {
+        let total = count_lines(&self.path).await?;
+        let keep = total.min(HISTORY_MAX_RECORDS);
+
+        let temp_path = self.path.with_extension("jsonl.tmp");
+        let mut lines = BufReader::new(tokio::fs::File::open(&self.path).await?).lines();
+        let mut kept = BufWriter::new(tokio::fs::File::create(&temp_path).await
+            .context("Failed to write compacted history")?);
+
+        let mut index = 0;
+        while let Some(line) = lines.next_line().await? {
+            index += 1;
+            if index > total - keep {
+                kept.write_all(line.as_bytes()).await?;
+                kept.write_all(b"\n").await?;
+            }
+        }
+        kept.flush().await
+            .context("Failed to write compacted history")?;
+        drop(kept);
+
+        tokio::fs::rename(&temp_path, &self.path).await
+            .context("Failed to replace history file")?;
+
+        debug!("Compacted run history to {} records", keep);
+        Ok(keep)
+    }
+
+    /// Most recent runs first, optionally for a single job
+    pub async fn read_recent(path: &Path, job_id: Option<&str>, limit: usize) -> Result<Vec<RunRecord>> {
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read history: {}", path.display())),
+        };
+
+        // Only the newest `limit` matches are kept while reading
+        let mut records = VecDeque::new();
+        let mut lines = BufReader::new(file).lines();
+        let mut index = 0;
+
+        while let Some(line) = lines.next_line().await
+            .with_context(|| format!("Failed to read history: {}", path.display()))?
+        {
+            index += 1;
+
+            match serde_json::from_str::<RunRecord>(&line) {
+                Ok(record) if job_id.is_none_or(|id| record.job_id == id) => {
+                    if records.len() == limit {
+                        records.pop_front();
+                    }
+                    if limit > 0 {
+                        records.push_back(record);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Skipping malformed history line {}: {}", index, e),
+            }
+        }
+
+        Ok(records.into_iter().rev().collect())
+    }
+}
+
+/// Number of lines in a file, counted without reading it whole
+async fn count_lines(path: &Path) -> Result<usize> {
+    let mut lines = BufReader::new(tokio::fs::File::open(path).await?).lines();
+    let mut count = 0;
+    while lines.next_line().await?.is_some() {
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record(job_id: &str, ok: bool) -> RunRecord {
+        RunRecord {
+            job_id: job_id.to_string(),
+            adhoc: false,
+            source: PathBuf::from("/src"),
+            target: PathBuf::from("/dst"),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            outcome: if ok {
+                RunOutcome::Success {
+                    backup_name: "b".into(),
+                    files_copied: 1,
+                    bytes_copied: 2,
+                    files_skipped: 0,
+                }
+            } else {
+                RunOutcome::Failed { error: "boom".into() }
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_append_and_read_recent() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("history.jsonl"));
+
+        store.append(&record("a", true)).await.unwrap();
+        store.append(&record("b", false)).await.unwrap();
+        store.append(&record("a", false)).await.unwrap();
+
+        let all = HistoryStore::read_recent(store.path(), None, 10).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].job_id, "a");
+        assert!(matches!(all[0].outcome, RunOutcome::Failed { .. }), "Newest record comes first");
+
+        let only_a = HistoryStore::read_recent(store.path(), Some("a"), 10).await.unwrap();
+        assert_eq!(only_a.len(), 2);
+
+        let limited = HistoryStore::read_recent(store.path(), None, 1).await.unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_history_is_empty() {
+        let dir = tempdir().unwrap();
+        let records = HistoryStore::read_recent(&dir.path().join("none.jsonl"), None, 10).await.unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_history_is_compacted() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("history.jsonl"));
+
+        for _ in 0..=HISTORY_MAX_RECORDS * 2 {
+            store.append(&record("a", true)).await.unwrap();
+        }
+
+        let content = tokio::fs::read_to_string(store.path()).await.unwrap();
+        assert_eq!(content.lines().count(), HISTORY_MAX_RECORDS);
+    }
+}
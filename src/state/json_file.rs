@@ -0,0 +1,75 @@
+//! JSON files written and read as a stream, so large states and manifests never exist
+//! as one JSON string in memory next to the values they hold.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Serialize `value` into `path` (created or truncated) and sync the file to disk
+pub async fn write_json_file<T>(path: &Path, value: T, pretty: bool) -> Result<()>
+where
+    T: Serialize + Send + 'static,
+{
+    let path: PathBuf = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        if pretty {
+            serde_json::to_writer_pretty(&mut writer, &value)
+        } else {
+            serde_json::to_writer(&mut writer, &value)
+        }
+        .with_context(|| format!("Failed to serialize {}", path.display()))?;
+
+        writer.flush()
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        writer.get_ref().sync_all()
+            .with_context(|| format!("Failed to sync {}", path.display()))
+    })
+    .await?
+}
+
+/// Parse the JSON file at `path` while reading it
+pub async fn read_json_file<T>(path: &Path) -> Result<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let path: PathBuf = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("value.json");
+        let value: BTreeMap<String, Vec<u32>> = [("a".to_string(), vec![1, 2]), ("b".to_string(), vec![])].into();
+
+        write_json_file(&path, value.clone(), true).await.unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains('\n'));
+
+        let read: BTreeMap<String, Vec<u32>> = read_json_file(&path).await.unwrap();
+        assert_eq!(read, value);
+
+        std::fs::write(&path, b"{ \"a\": [1,").unwrap();
+        assert!(read_json_file::<BTreeMap<String, Vec<u32>>>(&path).await.is_err());
+    }
+}
@@ -1,48 +1,388 @@
 use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock};
-use tracing::{debug, warn};
+use tracing::{debug, error, info, warn};
 
-use super::models::BackupState;
+use super::json_file::write_json_file;
+use super::history::{HistoryStore, RunRecord};
+use super::secrets::SecretStore;
+use super::usage::UsageStore;
+use super::models::{BackupState, JobState};
+use crate::config::{Durability, ServiceConfig, StateSaveMode};
+use crate::core::names::RESERVED_NAMES;
+use crate::platform::sync_parent_directory;
+
+/// Number of rotated copies of the last known good state file
+const STATE_ROTATION_COUNT: usize = 3;
+
+/// Minimum time between two rotations, so copies are spread out over time
+const STATE_ROTATION_MIN_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct StateManager {
     state: Arc<RwLock<BackupState>>,
     state_path: PathBuf,
     save_mutex: Arc<Mutex<()>>,
+    strict_durability: AtomicBool,
+    /// Flush interval for deferred updates (None = write every update immediately)
+    batch_interval: std::sync::Mutex<Option<Duration>>,
+    /// Set when in-memory state holds updates not yet written to disk
+    dirty: AtomicBool,
+    last_flush: std::sync::Mutex<Instant>,
+    /// Last rotation of each state file
+    last_rotation: std::sync::Mutex<HashMap<PathBuf, Instant>>,
+    /// Files of jobs whose state is kept apart from the shared state file, by job ID
+    job_files: std::sync::Mutex<HashMap<String, PathBuf>>,
+    history: HistoryStore,
+    secrets: Arc<SecretStore>,
+    usage: Arc<UsageStore>,
+    /// Corrupt state files found while loading, described for an alert
+    recoveries: std::sync::Mutex<Vec<String>>,
 }
 
 impl StateManager {
     /// Create new state manager
     pub async fn new(state_path: PathBuf) -> Result<Self> {
+        let mut recoveries = Vec::new();
         let state = if state_path.exists() {
-            Self::load_state(&state_path).await?
+            Self::load_or_recover(&state_path, &mut recoveries).await?.unwrap_or_else(BackupState::new)
         } else {
             debug!("No existing state found, creating new state");
             BackupState::new()
         };
 
+        let history = HistoryStore::new(HistoryStore::path_for_state_file(&state_path));
+        let secrets = Arc::new(SecretStore::new(SecretStore::path_for_state_file(&state_path)));
+        let usage = Arc::new(UsageStore::new(UsageStore::path_for_state_file(&state_path)));
+
         Ok(Self {
             state: Arc::new(RwLock::new(state)),
             state_path,
             save_mutex: Arc::new(Mutex::new(())),
+            strict_durability: AtomicBool::new(false),
+            batch_interval: std::sync::Mutex::new(None),
+            dirty: AtomicBool::new(false),
+            last_flush: std::sync::Mutex::new(Instant::now()),
+            last_rotation: std::sync::Mutex::new(HashMap::new()),
+            job_files: std::sync::Mutex::new(HashMap::new()),
+            history,
+            secrets,
+            usage,
+            recoveries: std::sync::Mutex::new(recoveries),
         })
     }
 
-    /// Load state from disk
-    async fn load_state(path: &Path) -> Result<BackupState> {
+    /// Corrupt state files quarantined since the last call, each with what it was replaced by
+    pub fn take_recoveries(&self) -> Vec<String> {
+        std::mem::take(&mut *self.recoveries.lock().unwrap())
+    }
+
+    /// Set state write strategy
+    pub fn set_save_mode(&self, mode: StateSaveMode) {
+        let interval = match mode {
+            StateSaveMode::Immediate => None,
+            StateSaveMode::Batched { flush_interval_seconds } => {
+                Some(Duration::from_secs(flush_interval_seconds))
+            }
+        };
+        *self.batch_interval.lock().unwrap() = interval;
+    }
+
+    /// Whether there are deferred updates that have not been written yet
+    pub fn has_pending_changes(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+
+    /// Write deferred updates if the flush interval has elapsed
+    pub async fn flush_if_due(&self) -> Result<()> {
+        if self.has_pending_changes() && self.flush_due() {
+            debug!("Flushing deferred state updates");
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    /// Write deferred updates immediately
+    pub async fn flush(&self) -> Result<()> {
+        if self.has_pending_changes() {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    fn flush_due(&self) -> bool {
+        match *self.batch_interval.lock().unwrap() {
+            Some(interval) => self.last_flush.lock().unwrap().elapsed() >= interval,
+            None => true,
+        }
+    }
+
+    /// Set durability level (strict also syncs the state directory after each rename)
+    pub fn set_durability(&self, durability: Durability) {
+        self.strict_durability.store(durability == Durability::Strict, Ordering::Relaxed);
+    }
+
+    /// Load a state file (the shared one or a job's own) from disk
+    async fn load_state<T: DeserializeOwned>(path: &Path) -> Result<T> {
         debug!("Loading state from: {}", path.display());
 
         let content = tokio::fs::read_to_string(path).await
             .context("Failed to read state file")?;
 
-        let state: BackupState = serde_json::from_str(&content)
-            .context("Failed to parse state file")?;
+        serde_json::from_str(&content)
+            .context("Failed to parse state file")
+    }
 
-        debug!("Loaded state with {} jobs", state.jobs.len());
+    /// Read a state file and the files of jobs kept apart from it without taking ownership
+    /// of them (no recovery, no writes); unreadable job files are left out
+    pub async fn load_read_only(path: &Path, job_files: &HashMap<String, PathBuf>) -> Result<BackupState> {
+        let mut state: BackupState = Self::load_state(path).await?;
+
+        for (job_id, file) in job_files {
+            match Self::load_state::<JobState>(file).await {
+                Ok(job) if &job.id == job_id => state.upsert_job(job),
+                Ok(_) => warn!("State file {} belongs to another job", file.display()),
+                Err(e) if file.exists() => warn!("Failed to read state of job {}: {:#}", job_id, e),
+                Err(_) => {}
+            }
+        }
         Ok(state)
     }
 
+    /// Files of the jobs of `config` that keep their state apart from the shared state file:
+    /// a job's own `state_path`, or one per job next to it with `per_job_state`. A job's own
+    /// file must be below the folder of the shared state file, which only the service may
+    /// write, so nobody else can swap it for a link or hand the service made-up state.
+    pub fn job_files(config: &ServiceConfig) -> Result<HashMap<String, PathBuf>> {
+        let state_dir = std::path::absolute(&config.state_path)
+            .context("Failed to resolve state_path")?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let shards = config.state_path.with_file_name(format!(
+            "{}.jobs",
+            config.state_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "state".to_string())
+        ));
+
+        let mut files: HashMap<String, PathBuf> = HashMap::new();
+        for job in &config.jobs {
+            let file = match &job.state_path {
+                Some(path) => {
+                    let inside = !path.components().any(|c| c == std::path::Component::ParentDir)
+                        && std::path::absolute(path).is_ok_and(|p| p.starts_with(&state_dir));
+                    if !inside {
+                        anyhow::bail!(
+                            "Job '{}' has its state_path {} outside {}, the folder of the shared state file",
+                            job.id, path.display(), state_dir.display()
+                        );
+                    }
+                    path.clone()
+                }
+                None if config.per_job_state => shards.join(format!("{}.json", Self::file_name_for(&job.id))),
+                None => continue,
+            };
+
+            if file == config.state_path {
+                anyhow::bail!("Job '{}' has the shared state file as its state_path", job.id);
+            }
+            if let Some((other, _)) = files.iter().find(|(_, path)| **path == file) {
+                anyhow::bail!("Jobs '{}' and '{}' keep their state in the same file {}", other, job.id, file.display());
+            }
+            files.insert(job.id.clone(), file);
+        }
+
+        Ok(files)
+    }
+
+    /// Job ID as a file name, with characters file systems reject replaced and
+    /// Windows device names (`CON`, `NUL`, ...) prefixed
+    fn file_name_for(job_id: &str) -> String {
+        let name: String = job_id.chars()
+            .map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+            .collect();
+
+        let base = name.split('.').next().unwrap_or(&name).to_ascii_lowercase();
+        if RESERVED_NAMES.contains(&base.as_str()) {
+            return format!("_{}", name);
+        }
+        name
+    }
+
+    /// Keep the state of these jobs in files of their own. A job's file replaces its entry
+    /// from the shared state file, and jobs without a file yet are moved into one. Jobs no
+    /// longer listed move back into the shared file. A corrupt job file only loses the
+    /// state of its own job.
+    pub async fn set_job_files(&self, files: HashMap<String, PathBuf>) -> Result<()> {
+        let _save_guard = self.save_mutex.lock().await;
+
+        let previous = std::mem::take(&mut *self.job_files.lock().unwrap());
+        let mut loaded = Vec::new();
+        let mut recoveries = Vec::new();
+        for (job_id, file) in &files {
+            if previous.get(job_id) == Some(file) || !file.exists() {
+                continue;
+            }
+            match Self::load_or_recover::<JobState>(file, &mut recoveries).await {
+                Ok(Some(job)) if &job.id == job_id => loaded.push(job),
+                Ok(Some(job)) => warn!("State file {} belongs to job {}, not {}; replacing it", file.display(), job.id, job_id),
+                Ok(None) => warn!("Job {} keeps its state from the shared state file", job_id),
+                Err(e) => warn!("Failed to load state of job {} from {}: {:#}", job_id, file.display(), e),
+            }
+        }
+
+        let state_snapshot = {
+            let mut state = self.state.write().await;
+            for job in loaded {
+                info!("Loaded state of job {} from {}", job.id, files[&job.id].display());
+                state.upsert_job(job);
+            }
+            state.clone()
+        };
+
+        self.recoveries.lock().unwrap().extend(recoveries);
+        *self.job_files.lock().unwrap() = files.clone();
+        self.save_state_atomic(state_snapshot).await?;
+
+        // Their state is in the shared file now
+        for (job_id, file) in previous {
+            if files.get(&job_id) != Some(&file) {
+                info!("State of job {} moved back from {}", job_id, file.display());
+                if let Err(e) = tokio::fs::remove_file(&file).await {
+                    warn!("Failed to remove {}: {}", file.display(), e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// File the state of `job_id` is kept in, if not the shared one
+    fn job_file(&self, job_id: &str) -> Option<PathBuf> {
+        self.job_files.lock().unwrap().get(job_id).cloned()
+    }
+
+    /// Load a state file, quarantining it if corrupt and falling back to the newest good
+    /// rotation; None when no copy is usable. A quarantined file is described in `recoveries`.
+    async fn load_or_recover<T: DeserializeOwned>(path: &Path, recoveries: &mut Vec<String>) -> Result<Option<T>> {
+        let content = tokio::fs::read_to_string(path).await
+            .context("Failed to read state file")?;
+
+        let parse_error = match serde_json::from_str::<T>(&content) {
+            Ok(state) => {
+                debug!("Loaded state from {}", path.display());
+                return Ok(Some(state));
+            }
+            Err(e) => e,
+        };
+
+        error!("State file is corrupt: {}", path.display());
+        error!("Parse failure at {}", Self::describe_parse_error(&content, &parse_error));
+
+        let quarantine_path = Self::quarantine_path(path);
+        tokio::fs::rename(path, &quarantine_path).await
+            .context("Failed to quarantine corrupt state file")?;
+        error!("Corrupt state file moved to: {}", quarantine_path.display());
+        let quarantined = format!(
+            "State file {} could not be read ({}) and was moved to {}",
+            path.display(),
+            parse_error,
+            quarantine_path.display()
+        );
+
+        for index in 1..=STATE_ROTATION_COUNT {
+            let rotation = Self::rotation_path(path, index);
+            if !rotation.exists() {
+                continue;
+            }
+
+            match Self::load_state(&rotation).await {
+                Ok(state) => {
+                    warn!("Recovered state from rotation: {}", rotation.display());
+                    recoveries.push(format!("{}; the state was recovered from {}", quarantined, rotation.display()));
+                    return Ok(Some(state));
+                }
+                Err(e) => warn!("Rotation {} is not usable: {:#}", rotation.display(), e),
+            }
+        }
+
+        warn!("No usable rotation of {} found, starting with empty state", path.display());
+        recoveries.push(format!("{}; no usable rotated copy was left", quarantined));
+        Ok(None)
+    }
+
+    /// Describe where parsing failed, with the offending line and a caret
+    fn describe_parse_error(content: &str, err: &serde_json::Error) -> String {
+        let line_text = content.lines()
+            .nth(err.line().saturating_sub(1))
+            .unwrap_or("");
+
+        // Keep very long lines (minified JSON) readable
+        let start = err.column().saturating_sub(40);
+        let snippet: String = line_text.chars().skip(start).take(80).collect();
+        let caret_offset = err.column().saturating_sub(start + 1);
+
+        format!(
+            "line {}, column {} ({:?}): {}\n    {}\n    {}^",
+            err.line(),
+            err.column(),
+            err.classify(),
+            err,
+            snippet,
+            " ".repeat(caret_offset)
+        )
+    }
+
+    fn quarantine_path(path: &Path) -> PathBuf {
+        let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+        Self::sibling_path(path, &format!("corrupt-{}", timestamp))
+    }
+
+    fn rotation_path(path: &Path, index: usize) -> PathBuf {
+        Self::sibling_path(path, &index.to_string())
+    }
+
+    /// Append a suffix to the full file name (state.json -> state.json.<suffix>)
+    fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+        let file_name = path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "state".to_string());
+        path.with_file_name(format!("{}.{}", file_name, suffix))
+    }
+
+    /// Keep copies of the current (known good) state file before it is replaced
+    async fn rotate_state_file(&self, path: &Path) -> Result<()> {
+        {
+            let last_rotation = self.last_rotation.lock().unwrap();
+            if last_rotation.get(path).is_some_and(|t| t.elapsed() < STATE_ROTATION_MIN_INTERVAL) {
+                return Ok(());
+            }
+        }
+
+        if !path.exists() {
+            return Ok(());
+        }
+
+        for index in (1..STATE_ROTATION_COUNT).rev() {
+            let from = Self::rotation_path(path, index);
+            if from.exists() {
+                tokio::fs::rename(&from, Self::rotation_path(path, index + 1)).await
+                    .context("Failed to shift state rotation")?;
+            }
+        }
+
+        tokio::fs::copy(path, Self::rotation_path(path, 1)).await
+            .context("Failed to rotate state file")?;
+
+        self.last_rotation.lock().unwrap().insert(path.to_path_buf(), Instant::now());
+        info!("Rotated state file: {}", path.display());
+        Ok(())
+    }
+
     /// Get read-only access to state
     pub async fn read(&self) -> tokio::sync::RwLockReadGuard<'_, BackupState> {
         self.state.read().await
@@ -65,42 +405,125 @@ impl StateManager {
         }; // Read lock released here
 
         // Perform holding the state lock
-        self.save_state_atomic(&state_snapshot).await
+        self.save_state_atomic(state_snapshot).await
     }
 
-    /// Atomic state persistence with fsync
-    async fn save_state_atomic(&self, state: &BackupState) -> Result<()> {
-        let temp_path = self.state_path.with_extension("tmp");
+    /// Atomic state persistence with fsync. Jobs with a file of their own are written to
+    /// it; one that cannot be written stays pending without holding up the others.
+    async fn save_state_atomic(&self, mut state: BackupState) -> Result<()> {
+        let job_files = self.job_files.lock().unwrap().clone();
+        let mut all_written = true;
+
+        if !job_files.is_empty() {
+            let (own, shared): (Vec<JobState>, Vec<JobState>) = std::mem::take(&mut state.jobs)
+                .into_iter()
+                .partition(|job| job_files.contains_key(&job.id));
+            state.jobs = shared;
+
+            for job in own {
+                let job_id = job.id.clone();
+                if let Err(e) = self.write_state_file(&job_files[&job_id], job).await {
+                    error!("Failed to save state of job {}: {:#}", job_id, e);
+                    all_written = false;
+                }
+            }
+        }
 
-        debug!("Saving state atomically to: {}", self.state_path.display());
+        self.write_state_file(&self.state_path, state).await?;
 
-        // 1. Write to temporary file
-        let json = serde_json::to_string_pretty(state)
-            .context("Failed to serialize state")?;
+        if all_written {
+            self.dirty.store(false, Ordering::SeqCst);
+        } else {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        *self.last_flush.lock().unwrap() = Instant::now();
 
-        tokio::fs::write(&temp_path, &json).await
-            .context("Failed to write temporary state file")?;
+        debug!("State saved successfully");
+        Ok(())
+    }
 
-        // 2. fsync temporary file
-        let temp_file = tokio::fs::OpenOptions::new()
-            .write(true)
-            .open(&temp_path)
-            .await?;
+    /// Replace one state file atomically
+    async fn write_state_file<T>(&self, path: &Path, value: T) -> Result<()>
+    where
+        T: Serialize + Send + 'static,
+    {
+        let temp_path = path.with_extension("tmp");
+
+        debug!("Saving state atomically to: {}", path.display());
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty() && !p.exists()) {
+            tokio::fs::create_dir_all(parent).await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
 
-        temp_file.sync_all().await
-            .context("Failed to sync temporary state file")?;
+        // 1-2. Stream into the temporary file and fsync it; large states are never held as text
+        write_json_file(&temp_path, value, true).await
+            .context("Failed to write temporary state file")?;
 
-        drop(temp_file);
+        // Keep the previous good state around in case the new one gets corrupted
+        if let Err(e) = self.rotate_state_file(path).await {
+            warn!("Failed to rotate state file: {:#}", e);
+        }
 
         // 3. Atomic rename
-        tokio::fs::rename(&temp_path, &self.state_path).await
+        tokio::fs::rename(&temp_path, path).await
             .context("Failed to rename temporary state file")?;
 
-        debug!("State saved successfully");
+        // 4. fsync parent directory so the rename itself is durable
+        if self.strict_durability.load(Ordering::Relaxed) {
+            sync_parent_directory(path).await
+                .context("Failed to sync state directory")?;
+        }
+
         Ok(())
     }
 
+    /// Append a finished run to the history file
+    pub async fn record_run(&self, record: &RunRecord) -> Result<()> {
+        self.history.append(record).await
+    }
+
+    /// Path of the run history file
+    pub fn history_path(&self) -> &Path {
+        self.history.path()
+    }
+
+    /// Credentials stored next to the state file
+    pub fn secrets(&self) -> Arc<SecretStore> {
+        self.secrets.clone()
+    }
+
+    /// Network transfer totals stored next to the state file
+    pub fn usage(&self) -> Arc<UsageStore> {
+        self.usage.clone()
+    }
+
+    /// Remove a job's state entry and persist (returns false if it was not present)
+    pub async fn remove_job(&self, job_id: &str) -> Result<bool> {
+        let _save_guard = self.save_mutex.lock().await;
+
+        let state_snapshot = {
+            let mut state = self.state.write().await;
+            let before = state.jobs.len();
+            state.jobs.retain(|j| j.id != job_id);
+
+            if state.jobs.len() == before {
+                return Ok(false);
+            }
+
+            state.last_updated = chrono::Utc::now();
+            state.clone()
+        };
+
+        self.save_state_atomic(state_snapshot).await?;
+        Ok(true)
+    }
+
     /// Update job state and persist
+    ///
+    /// In batched mode only status transitions are written immediately;
+    /// other updates are deferred until the next flush. A job with a state file of its
+    /// own only writes that file.
     pub async fn update_job_state<F>(&self, job_id: &str, updater: F) -> Result<()>
     where
         F: FnOnce(&mut super::models::JobState),
@@ -113,8 +536,28 @@ impl StateManager {
             let mut state = self.state.write().await;
 
             if let Some(job) = state.get_job_mut(job_id) {
+                let previous_status = std::mem::discriminant(&job.status);
                 updater(job);
+                let status_changed = previous_status != std::mem::discriminant(&job.status);
+                let own_file = self.job_file(job_id).map(|file| (file, job.clone()));
                 state.last_updated = chrono::Utc::now();
+
+                if !status_changed && !self.flush_due() {
+                    self.dirty.store(true, Ordering::SeqCst);
+                    debug!("Deferred state update for job: {}", job_id);
+                    return Ok(());
+                }
+
+                if let Some((file, job)) = own_file {
+                    drop(state);
+                    // Like a full save, a job file that cannot be written is tried again at the next flush
+                    if let Err(e) = self.write_state_file(&file, job).await {
+                        error!("Failed to save state of job {}: {:#}", job_id, e);
+                        self.dirty.store(true, Ordering::SeqCst);
+                    }
+                    return Ok(());
+                }
+
                 state.clone()
             } else {
                 drop(state);
@@ -124,7 +567,7 @@ impl StateManager {
         }; // Write lock released here
 
         // Save with snapshot while holding save_mutex
-        self.save_state_atomic(&state_snapshot).await?;
+        self.save_state_atomic(state_snapshot).await?;
 
         Ok(())
     }
@@ -281,6 +724,257 @@ mod tests {
         assert!(matches!(job.status, super::super::models::JobStatus::Running { .. }));
     }
 
+    #[tokio::test]
+    async fn test_save_with_strict_durability() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("test_strict.json");
+
+        let manager = StateManager::new(state_path.clone()).await.unwrap();
+        manager.set_durability(Durability::Strict);
+        manager.save().await.unwrap();
+
+        assert!(state_path.exists());
+        assert!(!state_path.with_extension("tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_batched_mode_defers_minor_updates() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("test_batched.json");
+
+        let manager = StateManager::new(state_path.clone()).await.unwrap();
+        manager.set_save_mode(StateSaveMode::Batched { flush_interval_seconds: 3600 });
+
+        {
+            let mut state = manager.write().await;
+            state.jobs.push(super::super::models::JobState::new(
+                "test_job".to_string(),
+                PathBuf::from("C:\\source"),
+                PathBuf::from("C:\\target"),
+            ));
+        }
+        manager.save().await.unwrap();
+
+        // Minor update stays in memory
+        let next_run = chrono::Utc::now();
+        manager.update_job_state("test_job", |js| {
+            js.next_run = Some(next_run);
+        }).await.unwrap();
+
+        assert!(manager.has_pending_changes());
+        let on_disk = StateManager::new(state_path.clone()).await.unwrap();
+        assert!(on_disk.read().await.get_job("test_job").unwrap().next_run.is_none());
+
+        // Not due yet, so nothing is written
+        manager.flush_if_due().await.unwrap();
+        assert!(manager.has_pending_changes());
+
+        // Explicit flush writes deferred updates
+        manager.flush().await.unwrap();
+        assert!(!manager.has_pending_changes());
+        let on_disk = StateManager::new(state_path).await.unwrap();
+        assert_eq!(on_disk.read().await.get_job("test_job").unwrap().next_run, Some(next_run));
+    }
+
+    #[tokio::test]
+    async fn test_batched_mode_writes_status_transitions_immediately() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("test_batched_status.json");
+
+        let manager = StateManager::new(state_path.clone()).await.unwrap();
+        manager.set_save_mode(StateSaveMode::Batched { flush_interval_seconds: 3600 });
+
+        {
+            let mut state = manager.write().await;
+            state.jobs.push(super::super::models::JobState::new(
+                "test_job".to_string(),
+                PathBuf::from("C:\\source"),
+                PathBuf::from("C:\\target"),
+            ));
+        }
+        manager.save().await.unwrap();
+
+        manager.update_job_state("test_job", |js| {
+            js.next_run = Some(chrono::Utc::now());
+        }).await.unwrap();
+
+        manager.update_job_state("test_job", |js| {
+            js.status = super::super::models::JobStatus::Running {
+                started_at: chrono::Utc::now(),
+            };
+        }).await.unwrap();
+
+        // Status transition flushed together with the earlier deferred update
+        assert!(!manager.has_pending_changes());
+        let on_disk = StateManager::new(state_path).await.unwrap();
+        let state = on_disk.read().await;
+        let job = state.get_job("test_job").unwrap();
+        assert!(matches!(job.status, super::super::models::JobStatus::Running { .. }));
+        assert!(job.next_run.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_state_is_quarantined_and_recovered_from_rotation() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+
+        let manager = StateManager::new(state_path.clone()).await.unwrap();
+        {
+            let mut state = manager.write().await;
+            state.jobs.push(super::super::models::JobState::new(
+                "test_job".to_string(),
+                PathBuf::from("C:\\source"),
+                PathBuf::from("C:\\target"),
+            ));
+        }
+        manager.save().await.unwrap();
+        manager.save().await.unwrap();
+        assert!(dir.path().join("state.json.1").exists(), "Second save should rotate the first");
+
+        tokio::fs::write(&state_path, b"{ \"version\": 1, \"jobs\": [ oops").await.unwrap();
+
+        let recovered = StateManager::new(state_path.clone()).await.unwrap();
+        assert_eq!(recovered.read().await.jobs.len(), 1);
+
+        let mut quarantined = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir.path()).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with("state.json.corrupt-") {
+                quarantined.push(name);
+            }
+        }
+        assert_eq!(quarantined.len(), 1);
+        assert!(!state_path.exists());
+
+        let recoveries = recovered.take_recoveries();
+        assert_eq!(recoveries.len(), 1, "The recovery is reported once for an alert");
+        assert!(recoveries[0].contains(&quarantined[0]) && recoveries[0].contains("state.json.1"), "{}", recoveries[0]);
+        assert!(recovered.take_recoveries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_state_without_rotation_starts_fresh() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        tokio::fs::write(&state_path, b"not json").await.unwrap();
+
+        let manager = StateManager::new(state_path).await.unwrap();
+        assert!(manager.read().await.jobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_job_files_hold_their_jobs_apart() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "state_path": state_path,
+            "per_job_state": true,
+            "jobs": [
+                { "id": "docs", "source": "/srv/docs", "target": "/mnt/docs", "schedule": { "type": "interval", "seconds": 3600 } },
+                { "id": "team/mail", "source": "/srv/mail", "target": "/mnt/mail", "schedule": { "type": "interval", "seconds": 3600 } }
+            ]
+        })).unwrap();
+        let files = StateManager::job_files(&config).unwrap();
+        let docs_file = dir.path().join("state.jobs").join("docs.json");
+        assert_eq!(files["docs"], docs_file);
+        assert_eq!(files["team/mail"], dir.path().join("state.jobs").join("team_mail.json"));
+
+        // Jobs kept in the shared file so far move into their own files
+        let manager = StateManager::new(state_path.clone()).await.unwrap();
+        {
+            let mut state = manager.write().await;
+            for job in &config.jobs {
+                state.upsert_job(JobState::new(job.id.clone(), job.source.clone(), job.target.clone()));
+            }
+        }
+        manager.save().await.unwrap();
+        manager.set_job_files(files.clone()).await.unwrap();
+
+        let shared: BackupState = StateManager::load_state(&state_path).await.unwrap();
+        assert!(shared.jobs.is_empty());
+        let docs: JobState = StateManager::load_state(&docs_file).await.unwrap();
+        assert_eq!(docs.id, "docs");
+
+        // An update writes only the job's own file
+        let before = tokio::fs::read(&state_path).await.unwrap();
+        manager.update_job_state("docs", |js| js.failed_attempts = 2).await.unwrap();
+        assert_eq!(tokio::fs::read(&state_path).await.unwrap(), before);
+        let docs: JobState = StateManager::load_state(&docs_file).await.unwrap();
+        assert_eq!(docs.failed_attempts, 2);
+
+        let state = StateManager::load_read_only(&state_path, &files).await.unwrap();
+        assert_eq!(state.jobs.len(), 2);
+
+        // A corrupt job file is recovered from its own rotation, the other jobs are untouched
+        tokio::fs::write(&docs_file, b"{ oops").await.unwrap();
+        let reopened = StateManager::new(state_path.clone()).await.unwrap();
+        reopened.set_job_files(files).await.unwrap();
+        let state = reopened.read().await;
+        assert_eq!(state.get_job("docs").unwrap().failed_attempts, 0);
+        assert!(state.get_job("team/mail").is_some());
+    }
+
+    #[test]
+    fn test_duplicate_job_files_are_rejected() {
+        let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "state_path": "/var/lib/keephive/state.json",
+            "jobs": [
+                { "id": "a", "source": "/srv/a", "target": "/mnt/a", "state_path": "/var/lib/keephive/team.json", "schedule": { "type": "interval", "seconds": 3600 } },
+                { "id": "b", "source": "/srv/b", "target": "/mnt/b", "state_path": "/var/lib/keephive/team.json", "schedule": { "type": "interval", "seconds": 3600 } }
+            ]
+        })).unwrap();
+        assert!(StateManager::job_files(&config).is_err());
+    }
+
+    #[test]
+    fn test_job_files_stay_in_the_state_folder() {
+        let job_file = |state_path: &str| {
+            let config: ServiceConfig = serde_json::from_value(serde_json::json!({
+                "state_path": "/var/lib/keephive/state.json",
+                "jobs": [
+                    { "id": "a", "source": "/srv/a", "target": "/mnt/a", "state_path": state_path, "schedule": { "type": "interval", "seconds": 3600 } }
+                ]
+            })).unwrap();
+            StateManager::job_files(&config)
+        };
+
+        assert!(job_file("/var/lib/keephive/teams/a.json").is_ok());
+        assert!(job_file("/srv/teams/a.json").is_err());
+        assert!(job_file("/var/lib/keephive/../teams/a.json").is_err());
+    }
+
+    #[test]
+    fn test_job_file_names_avoid_device_names() {
+        assert_eq!(StateManager::file_name_for("con"), "_con");
+        assert_eq!(StateManager::file_name_for("NUL.old"), "_NUL.old");
+        assert_eq!(StateManager::file_name_for("console"), "console");
+    }
+
+    #[test]
+    fn test_describe_parse_error_points_at_failure() {
+        let content = "{\n  \"version\": 1,\n  \"jobs\": [ oops ]\n}";
+        let err = serde_json::from_str::<BackupState>(content).unwrap_err();
+
+        let description = StateManager::describe_parse_error(content, &err);
+
+        assert!(description.starts_with("line 3, column"), "{}", description);
+        assert!(description.contains("\"jobs\": [ oops ]"), "{}", description);
+        assert!(description.ends_with('^'), "{}", description);
+    }
+
     #[tokio::test]
     async fn test_update_nonexistent_job() {
         use tempfile::tempdir;
@@ -1,7 +1,17 @@
+pub mod history;
+pub mod json_file;
 pub mod manager;
 pub mod models;
+pub mod secrets;
+pub mod usage;
 pub mod watcher;
 
+pub use history::{HistoryStore, RunOutcome, RunRecord};
 pub use manager::StateManager;
-pub use models::{BackupMetadata, BackupState, JobState, JobStatus};
+pub use models::{
+    BackupMetadata, BackupState, ConfirmationRequest, FirstRunReview, IntervalAnchor, JobState, JobStatus, LearnedTuning, ReplicaState, ReplicaStatus,
+    RunResult, TargetHealth, TargetOutage, RECENT_RESULTS, TARGET_OUTAGES,
+};
+pub use secrets::SecretStore;
+pub use usage::{UsageBucket, UsageStore};
 pub use watcher::ConfigWatcher;
\ No newline at end of file
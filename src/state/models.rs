@@ -1,9 +1,19 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-/// Current state schema version for migrations
-pub const STATE_SCHEMA_VERSION: u32 = 1;
+use crate::config::policy::LockedSettings;
+use crate::core::{CopyTuning, DumpOutcome, FailureCounts, VerifySummary, VolumeId};
+use crate::state::RunOutcome;
+
+/// Current state schema version for migrations; 2 added completion markers to backups
+pub const STATE_SCHEMA_VERSION: u32 = 2;
+
+/// Outcomes kept per job in `JobState::recent_results`
+pub const RECENT_RESULTS: usize = 10;
+
+/// Ended outages kept per target in `TargetHealth::outages`
+pub const TARGET_OUTAGES: usize = 20;
 
 /// Root state structure persisted to disk
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +24,18 @@ pub struct BackupState {
     /// All job states
     pub jobs: Vec<JobState>,
 
+    /// Copy tuning learned by autotune, per source and target
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub copy_tuning: Vec<LearnedTuning>,
+
+    /// Availability of backup targets found by the target probe
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub target_health: Vec<TargetHealth>,
+
+    /// Settings protected by the `LockSettings` policy the service last ran with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_settings: Option<LockedSettings>,
+
     /// Last time state was updated
     pub last_updated: DateTime<Utc>,
 }
@@ -24,6 +46,9 @@ impl BackupState {
         Self {
             version: STATE_SCHEMA_VERSION,
             jobs: Vec::new(),
+            copy_tuning: Vec::new(),
+            target_health: Vec::new(),
+            locked_settings: None,
             last_updated: Utc::now(),
         }
     }
@@ -47,6 +72,93 @@ impl BackupState {
     pub fn get_job_mut(&mut self, id: &str) -> Option<&mut JobState> {
         self.jobs.iter_mut().find(|j| j.id == id)
     }
+
+    /// Tuning autotune found for copies from `source` to `target`
+    pub fn learned_tuning(&self, source: &Path, target: &Path) -> Option<CopyTuning> {
+        self.copy_tuning.iter()
+            .find(|t| t.source == source && t.target == target)
+            .map(|t| t.tuning)
+    }
+
+    /// Remember the tuning autotune found for copies from `source` to `target`
+    pub fn learn_tuning(&mut self, source: &Path, target: &Path, tuning: CopyTuning) {
+        self.copy_tuning.retain(|t| t.source != source || t.target != target);
+        self.copy_tuning.push(LearnedTuning {
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            tuning,
+            measured_at: Utc::now(),
+        });
+        self.last_updated = Utc::now();
+    }
+
+    /// Record a probe of `target`, with the error if it could not be reached.
+    ///
+    /// Returns the outage this probe ended, if the target was unreachable before.
+    pub fn record_probe(&mut self, target: &Path, error: Option<String>, at: DateTime<Utc>) -> Option<TargetOutage> {
+        let index = match self.target_health.iter().position(|t| t.target == target) {
+            Some(index) => index,
+            None => {
+                self.target_health.push(TargetHealth { target: target.to_path_buf(), ..Default::default() });
+                self.target_health.len() - 1
+            }
+        };
+        let health = &mut self.target_health[index];
+        health.last_checked = Some(at);
+        self.last_updated = Utc::now();
+
+        if let Some(error) = error {
+            health.unreachable_since.get_or_insert(at);
+            health.error = Some(error);
+            return None;
+        }
+
+        health.last_reachable = Some(at);
+        health.error = None;
+        health.alerted = false;
+
+        let outage = TargetOutage { since: health.unreachable_since.take()?, until: at };
+        if health.outages.len() >= TARGET_OUTAGES {
+            health.outages.drain(..=health.outages.len() - TARGET_OUTAGES);
+        }
+        health.outages.push(outage.clone());
+        Some(outage)
+    }
+}
+
+/// Copy tuning autotune picked for a source and target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LearnedTuning {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub tuning: CopyTuning,
+    pub measured_at: DateTime<Utc>,
+}
+
+/// Availability of a backup target between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetHealth {
+    pub target: PathBuf,
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Last probe that could list and write the target
+    pub last_reachable: Option<DateTime<Utc>>,
+    /// First failed probe of the current outage; None while reachable
+    pub unreachable_since: Option<DateTime<Utc>>,
+    /// Why the last probe failed
+    pub error: Option<String>,
+    /// Whether the current outage was already reported
+    #[serde(default)]
+    pub alerted: bool,
+    /// Ended outages, oldest first
+    #[serde(default)]
+    pub outages: Vec<TargetOutage>,
+}
+
+/// Time a target could not be reached
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TargetOutage {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
 }
 
 /// Job execution status
@@ -94,6 +206,47 @@ pub struct JobState {
 
     /// Active backup metadata (if currently running)
     pub active_backup: Option<BackupMetadata>,
+
+    /// When the job became ready but was queued waiting for a concurrency slot
+    #[serde(default)]
+    pub queued_since: Option<DateTime<Utc>>,
+
+    /// Replication status per replica target
+    #[serde(default)]
+    pub replicas: Vec<ReplicaState>,
+
+    /// Set while a large run waits for confirmation
+    #[serde(default)]
+    pub awaiting_confirmation: Option<ConfirmationRequest>,
+
+    /// Last run skipped because it was not confirmed; schedules count it like a run
+    #[serde(default)]
+    pub last_skipped: Option<DateTime<Utc>>,
+
+    /// Volume the target was on at the last successful run, to follow it to a new drive letter
+    #[serde(default)]
+    pub target_volume: Option<VolumeId>,
+
+    /// First time the current run was put off because a busy process was running;
+    /// `next_run` is the retry time while set
+    #[serde(default)]
+    pub deferred_since: Option<DateTime<Utc>>,
+
+    /// Failed runs in a row while retries remain; `next_run` is the retry time while set
+    #[serde(default)]
+    pub failed_attempts: u32,
+
+    /// How the last `RECENT_RESULTS` runs ended, oldest first
+    #[serde(default)]
+    pub recent_results: Vec<RunResult>,
+
+    /// Estimate made by the job's first run, until its first backup
+    #[serde(default)]
+    pub first_run_review: Option<FirstRunReview>,
+
+    /// How long ago the last run of an interval job was, as measured on the monotonic clock
+    #[serde(default)]
+    pub interval_anchor: Option<IntervalAnchor>,
 }
 
 impl JobState {
@@ -107,6 +260,184 @@ impl JobState {
             next_run: None,
             last_backup: None,
             active_backup: None,
+            queued_since: None,
+            replicas: Vec::new(),
+            awaiting_confirmation: None,
+            last_skipped: None,
+            target_volume: None,
+            deferred_since: None,
+            failed_attempts: 0,
+            recent_results: Vec::new(),
+            first_run_review: None,
+            interval_anchor: None,
+        }
+    }
+
+    /// Record how a run ended, dropping the oldest outcome once `RECENT_RESULTS` are kept
+    pub fn push_result(&mut self, result: RunResult) {
+        if self.recent_results.len() >= RECENT_RESULTS {
+            self.recent_results.drain(..=self.recent_results.len() - RECENT_RESULTS);
+        }
+        self.recent_results.push(result);
+    }
+
+    /// Replica entry for a target, created as Pending if missing
+    pub fn replica_mut(&mut self, target: &std::path::Path) -> &mut ReplicaState {
+        let index = match self.replicas.iter().position(|r| r.target == target) {
+            Some(index) => index,
+            None => {
+                self.replicas.push(ReplicaState::new(target.to_path_buf()));
+                self.replicas.len() - 1
+            }
+        };
+
+        &mut self.replicas[index]
+    }
+}
+
+/// How a run ended, in short
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunResult {
+    /// Every file was copied
+    Success,
+    /// Completed, but some files could not be copied
+    Partial,
+    Failed,
+    /// The run did not start
+    Skipped,
+}
+
+impl RunResult {
+    pub fn of(outcome: &RunOutcome) -> Self {
+        match outcome {
+            RunOutcome::Success { files_skipped: 0, .. } => Self::Success,
+            RunOutcome::Success { .. } => Self::Partial,
+            RunOutcome::Failed { .. } => Self::Failed,
+            RunOutcome::Skipped { .. } => Self::Skipped,
+        }
+    }
+
+    /// One-character mark for `--status`
+    pub fn symbol(self) -> char {
+        match self {
+            Self::Success => '✓',
+            Self::Partial => '!',
+            Self::Failed => '✗',
+            Self::Skipped => '-',
+        }
+    }
+}
+
+/// Pending confirmation of a run larger than the configured threshold
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConfirmationRequest {
+    /// Estimated bytes to copy
+    pub estimated_bytes: u64,
+    pub requested_at: DateTime<Utc>,
+    /// When the configured timeout action is taken
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Estimate of a new job's first run, held until it is confirmed or its grace period ends
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FirstRunReview {
+    pub files: u64,
+    pub bytes: u64,
+    pub estimated_at: DateTime<Utc>,
+    /// When the job copies without confirmation (None = only once confirmed)
+    pub proceed_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+/// Time passed since an interval job's last run, kept so a restart goes on counting from
+/// it instead of from a wall-clock stamp the system time may have moved away from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntervalAnchor {
+    /// `last_run` or `last_skipped` stamp of the run
+    pub run: DateTime<Utc>,
+    /// Time passed since the run when `measured_at` was stamped
+    pub elapsed_seconds: u64,
+    pub measured_at: DateTime<Utc>,
+}
+
+/// Replication status of a secondary target
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ReplicaStatus {
+    /// Waiting for the first copy
+    Pending,
+
+    /// Copy in progress
+    Replicating {
+        started_at: DateTime<Utc>,
+        attempt: u32,
+    },
+
+    /// Holds the latest backup
+    Synced,
+
+    /// Last attempt failed (retried by the service at `next_retry`, if any)
+    Failed {
+        error: String,
+        attempts: u32,
+        next_retry: Option<DateTime<Utc>>,
+    },
+
+    /// Not copied to while the backend's monthly transfer cap is used up
+    Paused {
+        reason: String,
+        until: DateTime<Utc>,
+    },
+}
+
+/// State of one replica target of a job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReplicaState {
+    pub target: PathBuf,
+    pub status: ReplicaStatus,
+
+    /// Backup most recently copied to this target
+    pub last_synced_backup: Option<String>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+
+    /// Backup still to be copied here, kept until a copy succeeds so retries survive a restart
+    #[serde(default)]
+    pub pending_backup: Option<PathBuf>,
+}
+
+impl ReplicaState {
+    pub fn new(target: PathBuf) -> Self {
+        Self {
+            target,
+            status: ReplicaStatus::Pending,
+            last_synced_backup: None,
+            last_synced_at: None,
+            pending_backup: None,
+        }
+    }
+
+    /// Whether a copy should be attempted now: a failed copy whose retry time has come, or
+    /// one still marked in progress, which a crash of the service cut off
+    pub fn retry_due(&self, now: DateTime<Utc>) -> bool {
+        if self.pending_backup.is_none() {
+            return false;
+        }
+
+        match &self.status {
+            ReplicaStatus::Failed { next_retry: Some(at), .. } => *at <= now,
+            ReplicaStatus::Replicating { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Attempts already made at copying the pending backup
+    pub fn attempts(&self) -> u32 {
+        match &self.status {
+            ReplicaStatus::Failed { attempts, .. } => *attempts,
+            ReplicaStatus::Replicating { attempt, .. } => *attempt,
+            _ => 0,
         }
     }
 }
@@ -135,6 +466,26 @@ pub struct BackupMetadata {
     /// Total files skipped (e.g., locked files)
     pub files_skipped: u64,
 
+    /// Files of an incremental backup linked from the previous backup instead of copied
+    #[serde(default)]
+    pub files_unchanged: u64,
+
+    /// Skipped files by failure class
+    #[serde(default)]
+    pub skip_reasons: FailureCounts,
+
+    /// Post-copy verification result (None when verification is off)
+    #[serde(default)]
+    pub verification: Option<VerifySummary>,
+
+    /// Dump command result of `database-dump` jobs
+    #[serde(default)]
+    pub dump: Option<DumpOutcome>,
+
+    /// Copy tuning autotune picked during this backup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuning: Option<CopyTuning>,
+
     /// Whether backup completed successfully
     pub is_complete: bool,
 
@@ -153,6 +504,11 @@ impl BackupMetadata {
             bytes_copied: 0,
             files_copied: 0,
             files_skipped: 0,
+            files_unchanged: 0,
+            skip_reasons: FailureCounts::default(),
+            verification: None,
+            dump: None,
+            tuning: None,
             is_complete: false,
             errors: Vec::new(),
         }
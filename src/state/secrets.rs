@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// SYSTEM and administrators only, nothing inherited from the state folder
+#[cfg(windows)]
+const SECRETS_SDDL: &str = "D:P(A;;FA;;;SY)(A;;FA;;;BA)";
+
+/// Credentials such as OAuth refresh tokens, stored next to the state file.
+///
+/// On Windows values are encrypted with DPAPI for the account storing them, which is the
+/// service's own (clients hand secrets to it over the control channel), and the file is
+/// only open to SYSTEM and administrators.
+pub struct SecretStore {
+    path: PathBuf,
+    /// Serializes read-modify-write cycles
+    lock: Mutex<()>,
+}
+
+impl SecretStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Secrets file belonging to a state file (state.json -> state.json.secrets.json)
+    pub fn path_for_state_file(state_path: &Path) -> PathBuf {
+        let file_name = state_path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "state".to_string());
+        state_path.with_file_name(format!("{}.secrets.json", file_name))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn get(&self, name: &str) -> Result<Option<String>> {
+        let _guard = self.lock.lock().await;
+
+        match self.load().await?.get(name) {
+            Some(sealed) => {
+                let bytes = unprotect(&from_hex(sealed)?)
+                    .with_context(|| format!("Failed to decrypt secret '{}'", name))?;
+                Ok(Some(String::from_utf8(bytes).context("Secret is not valid UTF-8")?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn set(&self, name: &str, value: &str) -> Result<()> {
+        let _guard = self.lock.lock().await;
+
+        let mut secrets = self.load().await?;
+        secrets.insert(name.to_string(), to_hex(&protect(value.as_bytes())?));
+        self.save(&secrets).await?;
+
+        debug!("Stored secret '{}' in {}", name, self.path.display());
+        Ok(())
+    }
+
+    /// Values of every stored secret this account can decrypt
+    pub async fn values(&self) -> Result<Vec<String>> {
+        let _guard = self.lock.lock().await;
+
+        let mut values = Vec::new();
+        for (name, sealed) in self.load().await? {
+            match from_hex(&sealed).and_then(|sealed| unprotect(&sealed)) {
+                Ok(bytes) => values.extend(String::from_utf8(bytes).ok()),
+                Err(e) => debug!("Cannot decrypt secret '{}': {:#}", name, e),
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Remove a secret, returning whether it existed
+    pub async fn remove(&self, name: &str) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+
+        let mut secrets = self.load().await?;
+        let existed = secrets.remove(name).is_some();
+        if existed {
+            self.save(&secrets).await?;
+        }
+
+        Ok(existed)
+    }
+
+    async fn load(&self) -> Result<BTreeMap<String, String>> {
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse secrets file: {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read secrets file: {}", self.path.display())),
+        }
+    }
+
+    async fn save(&self, secrets: &BTreeMap<String, String>) -> Result<()> {
+        let content = serde_json::to_string_pretty(secrets)?;
+        let temp_path = self.path.with_extension("json.tmp");
+
+        // The renamed file keeps the descriptor it was created with
+        #[cfg(windows)]
+        {
+            match tokio::fs::remove_file(&temp_path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to remove {}", temp_path.display())),
+            }
+            crate::platform::windows::acl::create_private_file(&temp_path, content.as_bytes(), SECRETS_SDDL)?;
+        }
+
+        #[cfg(unix)]
+        {
+            let mut options = tokio::fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true).mode(0o600);
+
+            let mut file = options.open(&temp_path).await
+                .with_context(|| format!("Failed to write secrets file: {}", temp_path.display()))?;
+            tokio::io::AsyncWriteExt::write_all(&mut file, content.as_bytes()).await?;
+            file.sync_all().await?;
+        }
+
+        tokio::fs::rename(&temp_path, &self.path).await
+            .context("Failed to replace secrets file")
+    }
+}
+
+fn protect(data: &[u8]) -> Result<Vec<u8>> {
+    #[cfg(windows)]
+    {
+        crate::platform::windows::secrets::protect(data)
+    }
+
+    // Elsewhere the file's permissions are the only protection
+    #[cfg(not(windows))]
+    {
+        Ok(data.to_vec())
+    }
+}
+
+fn unprotect(data: &[u8]) -> Result<Vec<u8>> {
+    #[cfg(windows)]
+    {
+        crate::platform::windows::secrets::unprotect(data)
+    }
+
+    #[cfg(not(windows))]
+    {
+        Ok(data.to_vec())
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .context("Secrets file is corrupted")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_set_get_remove() {
+        let dir = tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        let store = SecretStore::new(SecretStore::path_for_state_file(&state_path));
+
+        assert_eq!(store.get("gdrive").await.unwrap(), None);
+
+        store.set("gdrive", "refresh-token").await.unwrap();
+        store.set("other", "x").await.unwrap();
+        assert_eq!(store.get("gdrive").await.unwrap().as_deref(), Some("refresh-token"));
+        assert!(dir.path().join("state.json.secrets.json").exists());
+
+        assert!(store.remove("gdrive").await.unwrap());
+        assert!(!store.remove("gdrive").await.unwrap());
+        assert_eq!(store.get("other").await.unwrap().as_deref(), Some("x"));
+    }
+}
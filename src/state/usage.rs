@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+/// Days of usage kept in the file
+pub const USAGE_RETENTION_DAYS: i64 = 400;
+
+/// Bytes one job moved through one network backend on one (local) day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsageBucket {
+    pub date: NaiveDate,
+    pub job_id: String,
+
+    /// Backend scheme, e.g. `webdav` or `keephive` for pulled backups
+    pub backend: String,
+
+    #[serde(default)]
+    pub bytes_sent: u64,
+
+    #[serde(default)]
+    pub bytes_received: u64,
+}
+
+/// Network transfer totals in daily buckets, stored next to the state file
+pub struct UsageStore {
+    path: PathBuf,
+    /// Serializes read-modify-write cycles
+    lock: Mutex<()>,
+}
+
+impl UsageStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Usage file belonging to a state file (state.json -> state.json.usage.json)
+    pub fn path_for_state_file(state_path: &Path) -> PathBuf {
+        let file_name = state_path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "state".to_string());
+        state_path.with_file_name(format!("{}.usage.json", file_name))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a transfer to today's bucket for the job and backend
+    pub async fn record(&self, job_id: &str, backend: &str, sent: u64, received: u64) -> Result<()> {
+        if sent == 0 && received == 0 {
+            return Ok(());
+        }
+
+        let _guard = self.lock.lock().await;
+        let today = Local::now().date_naive();
+
+        let mut buckets = Self::read(&self.path).await?;
+        buckets.retain(|b| (today - b.date).num_days() < USAGE_RETENTION_DAYS);
+
+        match buckets.iter_mut().find(|b| b.date == today && b.job_id == job_id && b.backend == backend) {
+            Some(bucket) => {
+                bucket.bytes_sent += sent;
+                bucket.bytes_received += received;
+            }
+            None => buckets.push(UsageBucket {
+                date: today,
+                job_id: job_id.to_string(),
+                backend: backend.to_string(),
+                bytes_sent: sent,
+                bytes_received: received,
+            }),
+        }
+
+        self.save(&buckets).await
+    }
+
+    /// Bytes sent and received through any of `backends` in the current (local) month
+    pub async fn month_total(&self, backends: &[&str]) -> Result<u64> {
+        let _guard = self.lock.lock().await;
+        let month = Local::now().date_naive().with_day(1).unwrap_or_default();
+
+        Ok(Self::read(&self.path).await?
+            .iter()
+            .filter(|b| b.date >= month && backends.contains(&b.backend.as_str()))
+            .map(|b| b.bytes_sent + b.bytes_received)
+            .sum())
+    }
+
+    /// All buckets in a usage file, oldest day first
+    pub async fn read(path: &Path) -> Result<Vec<UsageBucket>> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse usage file: {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read usage file: {}", path.display())),
+        }
+    }
+
+    async fn save(&self, buckets: &[UsageBucket]) -> Result<()> {
+        let content = serde_json::to_string_pretty(buckets)?;
+        let temp_path = self.path.with_extension("json.tmp");
+
+        tokio::fs::write(&temp_path, content).await
+            .with_context(|| format!("Failed to write usage file: {}", temp_path.display()))?;
+        tokio::fs::rename(&temp_path, &self.path).await
+            .context("Failed to replace usage file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_record_adds_to_daily_bucket() {
+        let dir = tempdir().unwrap();
+        let store = UsageStore::new(UsageStore::path_for_state_file(&dir.path().join("state.json")));
+
+        store.record("docs", "webdav", 100, 0).await.unwrap();
+        store.record("docs", "webdav", 50, 10).await.unwrap();
+        store.record("docs", "azblob", 7, 0).await.unwrap();
+        store.record("photos", "webdav", 0, 0).await.unwrap();
+
+        let buckets = UsageStore::read(store.path()).await.unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!((buckets[0].backend.as_str(), buckets[0].bytes_sent, buckets[0].bytes_received), ("webdav", 150, 10));
+        assert_eq!(buckets[1].bytes_sent, 7);
+        assert!(dir.path().join("state.json.usage.json").exists());
+
+        assert_eq!(store.month_total(&["webdav"]).await.unwrap(), 160);
+        assert_eq!(store.month_total(&["webdav", "azblob"]).await.unwrap(), 167);
+        assert_eq!(store.month_total(&["gdrive"]).await.unwrap(), 0);
+    }
+}
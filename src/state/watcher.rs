@@ -5,7 +5,9 @@ use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
-use crate::config::ServiceConfig;
+use crate::config::policy::apply_machine_policy;
+use crate::config::{parse_config, validate_job_overlaps, ServiceConfig};
+use crate::storage::validate_replicas;
 
 // Channel capacity constants for bounded channels
 const CONFIG_CHANGE_CHANNEL_CAPACITY: usize = 10;
@@ -121,8 +123,12 @@ impl ConfigWatcher {
         let content = tokio::fs::read_to_string(path).await
             .context("Failed to read config file")?;
 
-        let config: ServiceConfig = serde_json::from_str(&content)
-            .context("Failed to parse config file")?;
+        let mut config = parse_config(path, &content)?;
+
+        // Policy settings win over local edits
+        apply_machine_policy(&mut config)?;
+        validate_job_overlaps(&config.jobs)?;
+        validate_replicas(&config)?;
 
         Ok(config)
     }
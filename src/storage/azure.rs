@@ -0,0 +1,411 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::{AccessTier, AzureConfig};
+use crate::core::md5_file;
+use crate::storage::http::{self, ChunkedFile, HttpRequest};
+use crate::storage::{BackendFuture, Capabilities, StorageBackend, TargetUrl};
+
+/// Storage API version; bearer tokens need 2017-11-09 or later, the cold tier 2021-12-02
+const API_VERSION: &str = "2021-12-02";
+
+/// Larger files are uploaded as blocks
+const SINGLE_PUT_LIMIT: u64 = 256 * 1024 * 1024;
+
+const BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Azure instance metadata endpoint for managed identity tokens
+const IDENTITY_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// Tokens are refreshed this long before they expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+enum Credential {
+    /// SAS token query string, without leading `?`
+    Sas(String),
+    ManagedIdentity {
+        client_id: Option<String>,
+        token: Mutex<Option<(String, Instant)>>,
+    },
+}
+
+/// Backend for Azure Blob Storage: `azblob://account/container/prefix[?tier=cool]`.
+///
+/// Blobs are named `<prefix>/<backup>/<file>`, and backup names start with the
+/// job and end with their creation time, so lifecycle management rules can match
+/// a job by prefix and expire or re-tier its backups by age.
+pub struct AzureBlobBackend {
+    /// `https://<account>.blob.core.windows.net/<container>`
+    container_url: String,
+
+    /// Blob name prefix without trailing slash (may be empty)
+    prefix: String,
+
+    credential: Credential,
+    tier: Option<AccessTier>,
+    lifecycle_retention: bool,
+}
+
+impl AzureBlobBackend {
+    pub fn open(target: &TargetUrl, config: &AzureConfig) -> Result<Self> {
+        let (location, query) = target.location.split_once('?')
+            .unwrap_or((&target.location, ""));
+
+        let mut tier = config.tier;
+        for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match key {
+                "tier" => tier = Some(value.parse().map_err(anyhow::Error::msg)?),
+                _ => bail!("Unknown option '{}' in {}", key, target),
+            }
+        }
+
+        let mut parts = location.trim_matches('/').splitn(3, '/');
+        let account = parts.next().filter(|a| !a.is_empty())
+            .with_context(|| format!("Azure target has no storage account: {}", target))?;
+        let container = parts.next().filter(|c| !c.is_empty())
+            .with_context(|| format!("Azure target has no container: {}", target))?;
+        let prefix = parts.next().unwrap_or("").trim_matches('/').to_string();
+
+        let credential = match &config.sas_token_env {
+            Some(var) => {
+                let token = std::env::var(var)
+                    .with_context(|| format!("SAS token variable {} is not set", var))?;
+                Credential::Sas(token.trim_start_matches('?').to_string())
+            }
+            None => Credential::ManagedIdentity {
+                client_id: config.managed_identity_client_id.clone(),
+                token: Mutex::new(None),
+            },
+        };
+
+        Ok(Self {
+            container_url: format!("https://{}.blob.core.windows.net/{}", account, container),
+            prefix,
+            credential,
+            tier,
+            lifecycle_retention: config.lifecycle_retention,
+        })
+    }
+
+    /// Full blob name of a backend path
+    fn blob_name(&self, remote_path: &str) -> String {
+        let path = remote_path.trim_matches('/');
+        match (self.prefix.is_empty(), path.is_empty()) {
+            (true, _) => path.to_string(),
+            (false, true) => self.prefix.clone(),
+            (false, false) => format!("{}/{}", self.prefix, path),
+        }
+    }
+
+    /// URL of a blob (or of the container when `blob` is empty) with extra query parameters
+    fn url(&self, blob: &str, query: &[(&str, &str)]) -> String {
+        let mut url = if blob.is_empty() {
+            self.container_url.clone()
+        } else {
+            format!("{}/{}", self.container_url, http::encode_path(blob))
+        };
+
+        let mut params: Vec<String> = query.iter()
+            .map(|(key, value)| format!("{}={}", key, http::encode_segment(value)))
+            .collect();
+        if let Credential::Sas(sas) = &self.credential {
+            params.push(sas.clone());
+        }
+
+        if !params.is_empty() {
+            url.push('?');
+            url.push_str(&params.join("&"));
+        }
+
+        url
+    }
+
+    async fn request(&self, method: &str, url: String) -> Result<HttpRequest> {
+        let request = HttpRequest::new(method, url)
+            .header("x-ms-version", API_VERSION)
+            .header("x-ms-date", Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+
+        match &self.credential {
+            Credential::Sas(_) => Ok(request),
+            Credential::ManagedIdentity { client_id, token } => {
+                let token = Self::identity_token(client_id.as_deref(), token).await?;
+                Ok(request.header("Authorization", format!("Bearer {}", token)))
+            }
+        }
+    }
+
+    /// Storage token of the machine's managed identity, cached until shortly before it expires
+    async fn identity_token(client_id: Option<&str>, cache: &Mutex<Option<(String, Instant)>>) -> Result<String> {
+        let mut cache = cache.lock().await;
+
+        if let Some((token, expires)) = cache.as_ref()
+            && Instant::now() + TOKEN_REFRESH_MARGIN < *expires
+        {
+            return Ok(token.clone());
+        }
+
+        let mut url = format!(
+            "{}?api-version=2018-02-01&resource={}",
+            IDENTITY_ENDPOINT,
+            http::encode_segment("https://storage.azure.com/")
+        );
+        if let Some(client_id) = client_id {
+            url.push_str(&format!("&client_id={}", http::encode_segment(client_id)));
+        }
+
+        let response = http::send(&HttpRequest::new("GET", url).header("Metadata", "true")).await
+            .context("Managed identity is not available; set sas_token_env to use a SAS token")?;
+        if !response.is_success() {
+            return Err(response.error("Requesting managed identity token"));
+        }
+
+        let body: serde_json::Value = serde_json::from_slice(&response.body)
+            .context("Invalid managed identity token response")?;
+        let token = body["access_token"].as_str()
+            .context("Managed identity response has no access_token")?
+            .to_string();
+        let expires_in = body["expires_in"].as_str()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(3600);
+
+        *cache = Some((token.clone(), Instant::now() + Duration::from_secs(expires_in)));
+        Ok(token)
+    }
+
+    fn with_tier(&self, request: HttpRequest) -> HttpRequest {
+        match self.tier {
+            Some(tier) => request.header("x-ms-access-tier", tier.header_value()),
+            None => request,
+        }
+    }
+
+    async fn put_single(&self, local: &Path, blob: &str) -> Result<()> {
+        let md5 = md5_file(local).await?;
+        let request = self.request("PUT", self.url(blob, &[])).await?
+            .header("x-ms-blob-type", "BlockBlob")
+            .header("Content-MD5", http::base64(&md5))
+            .file(local);
+
+        let response = http::send(&self.with_tier(request)).await?;
+        if !response.is_success() {
+            return Err(response.error(&format!("Uploading {}", blob)));
+        }
+
+        Ok(())
+    }
+
+    /// Upload as a list of blocks, committed at once so readers never see a partial blob
+    async fn put_blocks(&self, local: &Path, blob: &str) -> Result<()> {
+        let mut chunks = ChunkedFile::open(local, BLOCK_SIZE).await?;
+        let mut block_ids = Vec::new();
+
+        while let Some(chunk) = chunks.next_chunk().await? {
+            // Block IDs must all have the same length
+            let block_id = http::base64(format!("{:08}", block_ids.len()).as_bytes());
+
+            let request = self.request("PUT", self.url(blob, &[("comp", "block"), ("blockid", &block_id)])).await?
+                .header("Content-MD5", http::base64(&chunk.md5))
+                .file(chunk.path);
+
+            let response = http::send(&request).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Uploading block {} of {}", block_ids.len(), blob)));
+            }
+
+            debug!("Uploaded block {} ({} bytes) of {}", block_ids.len(), chunk.len, blob);
+            block_ids.push(block_id);
+        }
+
+        let body: String = block_ids.iter()
+            .map(|id| format!("<Latest>{}</Latest>", id))
+            .collect();
+        // Stored with the blob, so later downloads can be checked too
+        let request = self.request("PUT", self.url(blob, &[("comp", "blocklist")])).await?
+            .header("x-ms-blob-content-md5", http::base64(&chunks.file_md5()))
+            .header("Content-Type", "application/xml")
+            .text(format!(r#"<?xml version="1.0" encoding="utf-8"?><BlockList>{}</BlockList>"#, body));
+
+        let response = http::send(&self.with_tier(request)).await?;
+        if !response.is_success() {
+            return Err(response.error(&format!("Committing {}", blob)));
+        }
+
+        Ok(())
+    }
+
+    /// Every page of a container listing; `delimiter` groups blobs below `/` into prefixes
+    async fn list_blobs(&self, prefix: &str, delimiter: bool) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut marker = String::new();
+
+        loop {
+            let mut query = vec![("restype", "container"), ("comp", "list"), ("prefix", prefix)];
+            if delimiter {
+                query.push(("delimiter", "/"));
+            }
+            if !marker.is_empty() {
+                query.push(("marker", &marker));
+            }
+
+            let response = http::send(&self.request("GET", self.url("", &query)).await?).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Listing {}", prefix)));
+            }
+
+            let xml = response.text();
+            names.extend(http::xml_elements(&xml, "Name"));
+
+            match http::xml_elements(&xml, "NextMarker").into_iter().next() {
+                Some(next) if !next.is_empty() => marker = next,
+                _ => return Ok(names),
+            }
+        }
+    }
+
+    async fn blob_exists(&self, blob: &str) -> Result<bool> {
+        let response = http::send(&self.request("HEAD", self.url(blob, &[])).await?).await?;
+        match response.status {
+            404 => Ok(false),
+            _ if response.is_success() => Ok(true),
+            _ => Err(response.error(&format!("Checking {}", blob))),
+        }
+    }
+
+    async fn delete_blob(&self, blob: &str) -> Result<()> {
+        let response = http::send(&self.request("DELETE", self.url(blob, &[])).await?).await?;
+        if !response.is_success() && response.status != 404 {
+            return Err(response.error(&format!("Deleting {}", blob)));
+        }
+
+        Ok(())
+    }
+}
+
+impl StorageBackend for AzureBlobBackend {
+    fn name(&self) -> &str {
+        "azblob"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            rename: false,
+            hardlink: false,
+            streaming: true,
+            // Uploads carry Content-MD5, which the service checks before storing
+            checksums: true,
+        }
+    }
+
+    fn manages_retention(&self) -> bool {
+        self.lifecycle_retention
+    }
+
+    fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> BackendFuture<'a, u64> {
+        Box::pin(async move {
+            let size = tokio::fs::metadata(local).await
+                .with_context(|| format!("Failed to read {}", local.display()))?
+                .len();
+            let blob = self.blob_name(remote_path);
+
+            if size > SINGLE_PUT_LIMIT {
+                self.put_blocks(local, &blob).await?;
+            } else {
+                self.put_single(local, &blob).await?;
+            }
+
+            Ok(size)
+        })
+    }
+
+    fn get_file<'a>(&'a self, remote_path: &'a str, local: &'a Path) -> Option<BackendFuture<'a, u64>> {
+        Some(Box::pin(async move {
+            let blob = self.blob_name(remote_path);
+            let response = http::send(&self.request("GET", self.url(&blob, &[])).await?.output(local)).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Downloading {}", blob)));
+            }
+            Ok(tokio::fs::metadata(local).await?.len())
+        }))
+    }
+
+    fn list<'a>(&'a self, remote_dir: &'a str) -> BackendFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let dir = self.blob_name(remote_dir);
+            let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+
+            let mut names: Vec<String> = self.list_blobs(&prefix, true).await?
+                .into_iter()
+                .filter_map(|name| {
+                    name.strip_prefix(&prefix).map(|n| n.trim_end_matches('/').to_string())
+                })
+                .filter(|name| !name.is_empty())
+                .collect();
+
+            names.sort();
+            names.dedup();
+            Ok(names)
+        })
+    }
+
+    fn exists<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, bool> {
+        Box::pin(async move {
+            let blob = self.blob_name(remote_path);
+            if self.blob_exists(&blob).await? {
+                return Ok(true);
+            }
+
+            // Directories only exist as a shared name prefix
+            Ok(!self.list_blobs(&format!("{}/", blob), true).await?.is_empty())
+        })
+    }
+
+    fn remove<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let blob = self.blob_name(remote_path);
+
+            for name in self.list_blobs(&format!("{}/", blob), false).await? {
+                self.delete_blob(&name).await?;
+            }
+
+            self.delete_blob(&blob).await
+        })
+    }
+
+    fn rename<'a>(&'a self, _from: &'a str, _to: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async { bail!("Azure Blob Storage cannot rename; uploads are finalized by their completion marker") })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(target: &str, config: &AzureConfig) -> AzureBlobBackend {
+        AzureBlobBackend::open(&TargetUrl::parse(target).unwrap(), config).unwrap()
+    }
+
+    #[test]
+    fn test_open_parses_target() {
+        let backend = open("azblob://acct/backups/pc1?tier=archive", &AzureConfig::default());
+
+        assert_eq!(backend.container_url, "https://acct.blob.core.windows.net/backups");
+        assert_eq!(backend.tier, Some(AccessTier::Archive));
+        assert_eq!(backend.blob_name("docs_1/a b.txt"), "pc1/docs_1/a b.txt");
+        assert_eq!(
+            backend.url(&backend.blob_name("docs_1/a b.txt"), &[("comp", "block")]),
+            "https://acct.blob.core.windows.net/backups/pc1/docs_1/a%20b.txt?comp=block"
+        );
+
+        let config = AzureConfig { tier: Some(AccessTier::Cool), ..Default::default() };
+        let backend = open("azblob://acct/backups", &config);
+        assert_eq!(backend.tier, Some(AccessTier::Cool), "Config tier applies without ?tier=");
+        assert_eq!(backend.blob_name(""), "");
+
+        let target = TargetUrl::parse("azblob://acct").unwrap();
+        assert!(AzureBlobBackend::open(&target, &AzureConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,510 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::GoogleDriveConfig;
+use crate::core::{md5_file, to_hex};
+use crate::observability::format_duration;
+use crate::state::SecretStore;
+use crate::storage::http::{self, HttpRequest, HttpResponse};
+use crate::storage::{BackendFuture, Capabilities, StorageBackend, TargetUrl};
+
+/// Name of the refresh token in the secret store
+pub const REFRESH_TOKEN_SECRET: &str = "gdrive.refresh_token";
+
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+const UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3/files";
+
+/// Access to files created by KeepHive only, not the rest of the drive
+const SCOPE: &str = "https://www.googleapis.com/auth/drive.file";
+
+const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
+
+/// Attempts per request when Drive reports rate limits or server errors
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Tokens are refreshed this long before they expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// Code the user enters at Google to authorize KeepHive
+pub struct DeviceCode {
+    pub user_code: String,
+    pub verification_url: String,
+    pub expires_in: u64,
+}
+
+/// Backend for Google Drive: `gdrive://Folder/Path` below "My Drive".
+///
+/// Drive addresses files by ID, so folder IDs are looked up once and cached.
+pub struct GoogleDriveBackend {
+    /// Folder path below My Drive, without leading or trailing slash
+    root: String,
+
+    client_id: String,
+    client_secret: String,
+    secrets: Arc<SecretStore>,
+    token: Mutex<Option<(String, Instant)>>,
+
+    /// Folder IDs by path below `root` (`""` is `root` itself)
+    folders: Mutex<HashMap<String, String>>,
+}
+
+impl GoogleDriveBackend {
+    pub fn open(target: &TargetUrl, config: &GoogleDriveConfig, secrets: Arc<SecretStore>) -> Result<Self> {
+        let (client_id, client_secret) = client(config)?;
+
+        Ok(Self {
+            root: target.location.trim_matches('/').to_string(),
+            client_id,
+            client_secret,
+            secrets,
+            token: Mutex::new(None),
+            folders: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let mut cache = self.token.lock().await;
+
+        if let Some((token, expires)) = cache.as_ref()
+            && Instant::now() + TOKEN_REFRESH_MARGIN < *expires
+        {
+            return Ok(token.clone());
+        }
+
+        let refresh_token = self.secrets.get(REFRESH_TOKEN_SECRET).await?
+            .context("Google Drive is not authorized; run `keephive --gdrive-login` first")?;
+
+        let response = http::send(&form_request(TOKEN_URL, &[
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+            ("refresh_token", &refresh_token),
+            ("grant_type", "refresh_token"),
+        ])).await?;
+
+        let body = json_body(&response)?;
+        if body["error"] == "invalid_grant" {
+            bail!("Google Drive authorization was revoked or expired; run `keephive --gdrive-login` again");
+        }
+        if !response.is_success() {
+            return Err(response.error("Refreshing Google Drive token"));
+        }
+
+        let token = body["access_token"].as_str()
+            .context("Token response has no access_token")?
+            .to_string();
+        let expires_in = body["expires_in"].as_u64().unwrap_or(3600);
+
+        *cache = Some((token.clone(), Instant::now() + Duration::from_secs(expires_in)));
+        Ok(token)
+    }
+
+    /// Send an authorized request, retrying rate limits and server errors with backoff
+    async fn send(&self, build: impl Fn() -> HttpRequest) -> Result<HttpResponse> {
+        let mut attempt = 1;
+
+        loop {
+            let token = self.access_token().await?;
+            let response = http::send(&build().header("Authorization", format!("Bearer {}", token))).await?;
+            let text = response.text();
+
+            if response.status == 403 && text.contains("storageQuotaExceeded") {
+                bail!("Google Drive storage is full; free up space or upgrade the storage plan");
+            }
+
+            let retry = match response.status {
+                401 => {
+                    // Expired early or revoked; a fresh token settles which
+                    *self.token.lock().await = None;
+                    true
+                }
+                403 => text.contains("rateLimitExceeded") || text.contains("userRateLimitExceeded"),
+                429 | 500..=599 => true,
+                _ => false,
+            };
+
+            if !retry || attempt >= MAX_ATTEMPTS {
+                return Ok(response);
+            }
+
+            let delay = Duration::from_secs(1 << attempt);
+            warn!("Google Drive returned HTTP {}, retrying in {}", response.status, format_duration(delay));
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// ID of the child of `parent` named `name`, with its MIME type
+    async fn find_child(&self, parent: &str, name: &str) -> Result<Option<(String, String)>> {
+        let query = format!(
+            "'{}' in parents and name = '{}' and trashed = false",
+            escape_query(parent),
+            escape_query(name)
+        );
+        let url = format!("{}?{}", FILES_URL, http::form_encode(&[
+            ("q", &query),
+            ("fields", "files(id,mimeType)"),
+            ("pageSize", "1"),
+        ]));
+
+        let response = self.send(|| HttpRequest::new("GET", url.as_str())).await?;
+        if !response.is_success() {
+            return Err(response.error(&format!("Looking up {}", name)));
+        }
+
+        let body = json_body(&response)?;
+        Ok(body["files"].get(0).map(|file| {
+            (
+                file["id"].as_str().unwrap_or_default().to_string(),
+                file["mimeType"].as_str().unwrap_or_default().to_string(),
+            )
+        }))
+    }
+
+    async fn create_folder(&self, parent: &str, name: &str) -> Result<String> {
+        let metadata = json!({ "name": name, "mimeType": FOLDER_MIME_TYPE, "parents": [parent] }).to_string();
+
+        let response = self.send(|| {
+            HttpRequest::new("POST", format!("{}?fields=id", FILES_URL))
+                .header("Content-Type", "application/json; charset=UTF-8")
+                .text(metadata.as_str())
+        }).await?;
+        if !response.is_success() {
+            return Err(response.error(&format!("Creating folder {}", name)));
+        }
+
+        json_body(&response)?["id"].as_str()
+            .map(str::to_string)
+            .context("Folder response has no id")
+    }
+
+    /// Full path below My Drive of a backend path
+    fn drive_path(&self, remote_path: &str) -> Vec<String> {
+        self.root.split('/')
+            .chain(remote_path.split('/'))
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// ID of a file or folder, `None` if it does not exist; with `create`, missing folders are created
+    async fn resolve(&self, remote_path: &str, create: bool) -> Result<Option<String>> {
+        let key = remote_path.trim_matches('/').to_string();
+        if let Some(id) = self.folders.lock().await.get(&key) {
+            return Ok(Some(id.clone()));
+        }
+
+        let mut id = "root".to_string();
+        let mut is_folder = true;
+
+        for name in self.drive_path(remote_path) {
+            if !is_folder {
+                return Ok(None);
+            }
+
+            match self.find_child(&id, &name).await? {
+                Some((child, mime_type)) => {
+                    is_folder = mime_type == FOLDER_MIME_TYPE;
+                    id = child;
+                }
+                None if create => id = self.create_folder(&id, &name).await?,
+                None => return Ok(None),
+            }
+        }
+
+        if is_folder {
+            self.folders.lock().await.insert(key, id.clone());
+        }
+
+        Ok(Some(id))
+    }
+
+    /// Drop cached folder IDs at or below `remote_path`
+    async fn forget(&self, remote_path: &str) {
+        let prefix = remote_path.trim_matches('/');
+        self.folders.lock().await
+            .retain(|path, _| path != prefix && !path.starts_with(&format!("{}/", prefix)));
+    }
+}
+
+impl StorageBackend for GoogleDriveBackend {
+    fn name(&self) -> &str {
+        "gdrive"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            rename: true,
+            hardlink: false,
+            streaming: true,
+            // Drive reports the MD5 of what it stored, which is compared with the local file
+            checksums: true,
+        }
+    }
+
+    fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> BackendFuture<'a, u64> {
+        Box::pin(async move {
+            let (parent, name) = remote_path.rsplit_once('/').unwrap_or(("", remote_path));
+            let parent_id = self.resolve(parent, true).await?
+                .context("Failed to create upload folder")?;
+
+            let size = tokio::fs::metadata(local).await
+                .with_context(|| format!("Failed to read {}", local.display()))?
+                .len();
+
+            // Resumable upload: the session URL accepts the content in one streamed request
+            let metadata = json!({ "name": name, "parents": [parent_id] }).to_string();
+            let response = self.send(|| {
+                HttpRequest::new("POST", format!("{}?uploadType=resumable&fields=id,md5Checksum", UPLOAD_URL))
+                    .header("Content-Type", "application/json; charset=UTF-8")
+                    .header("X-Upload-Content-Length", size.to_string())
+                    .text(metadata.as_str())
+            }).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Starting upload of {}", remote_path)));
+            }
+
+            let session = response.header("Location")
+                .context("Upload response has no session URL")?
+                .to_string();
+
+            let response = self.send(|| HttpRequest::new("PUT", session.as_str()).file(local)).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Uploading {}", remote_path)));
+            }
+
+            let uploaded: Value = serde_json::from_slice(&response.body)
+                .context("Invalid upload response")?;
+            let expected = to_hex(&md5_file(local).await?);
+
+            if uploaded["md5Checksum"].as_str() != Some(expected.as_str()) {
+                // Leave nothing corrupt behind; the caller retries the upload
+                if let Some(id) = uploaded["id"].as_str() {
+                    let url = format!("{}/{}", FILES_URL, id);
+                    let _ = self.send(|| HttpRequest::new("DELETE", url.as_str())).await;
+                }
+                bail!("{} was corrupted in transit (checksum mismatch)", remote_path);
+            }
+
+            Ok(size)
+        })
+    }
+
+    fn get_file<'a>(&'a self, remote_path: &'a str, local: &'a Path) -> Option<BackendFuture<'a, u64>> {
+        Some(Box::pin(async move {
+            let id = self.resolve(remote_path, false).await?
+                .with_context(|| format!("{} does not exist", remote_path))?;
+
+            let url = format!("{}/{}?alt=media", FILES_URL, id);
+            let response = self.send(|| HttpRequest::new("GET", url.as_str()).output(local)).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Downloading {}", remote_path)));
+            }
+            Ok(tokio::fs::metadata(local).await?.len())
+        }))
+    }
+
+    fn list<'a>(&'a self, remote_dir: &'a str) -> BackendFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let Some(folder) = self.resolve(remote_dir, false).await? else {
+                return Ok(Vec::new());
+            };
+
+            let query = format!("'{}' in parents and trashed = false", escape_query(&folder));
+            let mut names = Vec::new();
+            let mut page_token = String::new();
+
+            loop {
+                let mut fields = vec![
+                    ("q", query.as_str()),
+                    ("fields", "nextPageToken,files(name)"),
+                    ("pageSize", "1000"),
+                ];
+                if !page_token.is_empty() {
+                    fields.push(("pageToken", &page_token));
+                }
+                let url = format!("{}?{}", FILES_URL, http::form_encode(&fields));
+
+                let response = self.send(|| HttpRequest::new("GET", url.as_str())).await?;
+                if !response.is_success() {
+                    return Err(response.error(&format!("Listing {}", remote_dir)));
+                }
+
+                let body = json_body(&response)?;
+                names.extend(body["files"].as_array().into_iter().flatten()
+                    .filter_map(|file| file["name"].as_str().map(str::to_string)));
+
+                match body["nextPageToken"].as_str() {
+                    Some(next) => page_token = next.to_string(),
+                    None => break,
+                }
+            }
+
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    fn exists<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, bool> {
+        Box::pin(async move { Ok(self.resolve(remote_path, false).await?.is_some()) })
+    }
+
+    fn remove<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let Some(id) = self.resolve(remote_path, false).await? else {
+                return Ok(());
+            };
+
+            // Deletes permanently (not to the trash), folders with their contents
+            let url = format!("{}/{}", FILES_URL, id);
+            let response = self.send(|| HttpRequest::new("DELETE", url.as_str())).await?;
+            if !response.is_success() && response.status != 404 {
+                return Err(response.error(&format!("Removing {}", remote_path)));
+            }
+
+            self.forget(remote_path).await;
+            Ok(())
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let (from_parent, _) = from.rsplit_once('/').unwrap_or(("", from));
+            let (to_parent, to_name) = to.rsplit_once('/').unwrap_or(("", to));
+            if from_parent != to_parent {
+                bail!("Google Drive backend only renames within a folder: {} -> {}", from, to);
+            }
+
+            let id = self.resolve(from, false).await?
+                .with_context(|| format!("{} does not exist", from))?;
+
+            let metadata = json!({ "name": to_name }).to_string();
+            let url = format!("{}/{}", FILES_URL, id);
+            let response = self.send(|| {
+                HttpRequest::new("PATCH", url.as_str())
+                    .header("Content-Type", "application/json; charset=UTF-8")
+                    .text(metadata.as_str())
+            }).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Renaming {} to {}", from, to)));
+            }
+
+            self.forget(from).await;
+            Ok(())
+        })
+    }
+}
+
+/// Authorize KeepHive with the OAuth device flow, returning the refresh token.
+///
+/// `prompt` is called once with the code the user has to enter at Google.
+pub async fn device_login(config: &GoogleDriveConfig, prompt: impl FnOnce(&DeviceCode)) -> Result<String> {
+    let (client_id, client_secret) = client(config)?;
+
+    let response = http::send(&form_request(DEVICE_CODE_URL, &[
+        ("client_id", &client_id),
+        ("scope", SCOPE),
+    ])).await?;
+    if !response.is_success() {
+        return Err(response.error("Requesting device code"));
+    }
+
+    let body = json_body(&response)?;
+    let device_code = body["device_code"].as_str()
+        .context("Device code response has no device_code")?
+        .to_string();
+    let mut interval = body["interval"].as_u64().unwrap_or(5);
+
+    prompt(&DeviceCode {
+        user_code: body["user_code"].as_str().unwrap_or_default().to_string(),
+        verification_url: body["verification_url"].as_str().unwrap_or_default().to_string(),
+        expires_in: body["expires_in"].as_u64().unwrap_or(1800),
+    });
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        let response = http::send(&form_request(TOKEN_URL, &[
+            ("client_id", &client_id),
+            ("client_secret", &client_secret),
+            ("device_code", &device_code),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ])).await?;
+        let body = json_body(&response)?;
+
+        if response.is_success() {
+            info!("Google Drive authorized");
+            return body["refresh_token"].as_str()
+                .map(str::to_string)
+                .context("Token response has no refresh_token");
+        }
+
+        match body["error"].as_str() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += 5,
+            Some("access_denied") => bail!("Authorization was denied"),
+            Some("expired_token") => bail!("The code expired before it was entered; try again"),
+            _ => return Err(response.error("Waiting for authorization")),
+        }
+    }
+}
+
+fn client(config: &GoogleDriveConfig) -> Result<(String, String)> {
+    match (&config.client_id, &config.client_secret) {
+        (Some(id), Some(secret)) => Ok((id.clone(), secret.clone())),
+        _ => bail!("Google Drive needs storage.gdrive.client_id and client_secret in the config"),
+    }
+}
+
+fn form_request(url: &str, fields: &[(&str, &str)]) -> HttpRequest {
+    HttpRequest::new("POST", url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .text(http::form_encode(fields))
+}
+
+/// JSON body of a response (`Null` when empty)
+fn json_body(response: &HttpResponse) -> Result<Value> {
+    if response.body.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    serde_json::from_slice(&response.body)
+        .with_context(|| format!("Unexpected response from Google (HTTP {})", response.status))
+}
+
+/// Quote a value for a Drive search query
+fn escape_query(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drive_path_and_query_escaping() {
+        let config = GoogleDriveConfig {
+            client_id: Some("id".to_string()),
+            client_secret: Some("secret".to_string()),
+            ..GoogleDriveConfig::default()
+        };
+        let secrets = Arc::new(SecretStore::new("secrets.json".into()));
+        let backend = GoogleDriveBackend::open(&TargetUrl::parse("gdrive://KeepHive/pc1/").unwrap(), &config, secrets)
+            .unwrap();
+
+        assert_eq!(backend.drive_path("docs_1/a.txt"), vec!["KeepHive", "pc1", "docs_1", "a.txt"]);
+        assert_eq!(backend.drive_path(""), vec!["KeepHive", "pc1"]);
+        assert_eq!(escape_query(r"Bob's \files"), r"Bob\'s \\files");
+
+        let missing_client = GoogleDriveConfig::default();
+        let secrets = Arc::new(SecretStore::new("secrets.json".into()));
+        assert!(GoogleDriveBackend::open(&TargetUrl::parse("gdrive://x").unwrap(), &missing_client, secrets).is_err());
+    }
+}
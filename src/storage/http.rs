@@ -0,0 +1,463 @@
+//! Minimal HTTP client for remote backends, built on the `curl` executable
+//! (included with Windows 10 and later), so no TLS stack is linked into the service.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::core::Md5;
+use crate::platform::PrivateDir;
+
+/// Seconds to wait for a connection before giving up
+const CONNECT_TIMEOUT_SECONDS: u64 = 30;
+
+/// A transfer slower than 1 byte/s for this long is aborted
+const STALL_TIMEOUT_SECONDS: u64 = 120;
+
+pub(crate) enum Body {
+    Empty,
+    Text(String),
+    File(PathBuf),
+}
+
+pub(crate) struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Body,
+    output: Option<PathBuf>,
+}
+
+impl HttpRequest {
+    pub(crate) fn new(method: &str, url: impl Into<String>) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.into(),
+            headers: Vec::new(),
+            body: Body::Empty,
+            output: None,
+        }
+    }
+
+    pub(crate) fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub(crate) fn text(mut self, body: impl Into<String>) -> Self {
+        self.body = Body::Text(body.into());
+        self
+    }
+
+    /// Stream a file as the request body
+    pub(crate) fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.body = Body::File(path.into());
+        self
+    }
+
+    /// Write the response body to a file instead of keeping it in memory
+    pub(crate) fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// curl options for this request, in curl's config file syntax.
+    ///
+    /// Options are passed on stdin rather than the command line so credentials
+    /// in headers never show up in process listings.
+    fn curl_config(&self) -> String {
+        let mut config = format!(
+            "silent\nshow-error\ndump-header = \"-\"\nconnect-timeout = {}\nspeed-limit = 1\nspeed-time = {}\n",
+            CONNECT_TIMEOUT_SECONDS, STALL_TIMEOUT_SECONDS
+        );
+
+        // `-X HEAD` would make curl wait for a body that never comes
+        if self.method == "HEAD" {
+            config.push_str("head\n");
+        } else {
+            config.push_str(&format!("request = {}\n", quote(&self.method)));
+        }
+
+        config.push_str(&format!("url = {}\n", quote(&self.url)));
+
+        // No `100 Continue` round trip before each upload
+        config.push_str("header = \"Expect:\"\n");
+        for (name, value) in &self.headers {
+            config.push_str(&format!("header = {}\n", quote(&format!("{}: {}", name, value))));
+        }
+
+        match &self.body {
+            Body::Empty => {}
+            Body::Text(text) => config.push_str(&format!("data-raw = {}\n", quote(text))),
+            Body::File(path) => {
+                config.push_str(&format!("upload-file = {}\n", quote(&path.to_string_lossy())));
+            }
+        }
+
+        if let Some(path) = &self.output {
+            config.push_str(&format!("output = {}\n", quote(&path.to_string_lossy())));
+        }
+
+        config
+    }
+}
+
+pub(crate) struct HttpResponse {
+    pub(crate) status: u16,
+    headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// First header with this name (case-insensitive)
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Error describing an unexpected status, with the start of the body for context
+    pub(crate) fn error(&self, action: &str) -> anyhow::Error {
+        let text = self.text();
+        let detail: String = text.trim().chars().take(200).collect();
+
+        if detail.is_empty() {
+            anyhow::anyhow!("{} failed: HTTP {}", action, self.status)
+        } else {
+            anyhow::anyhow!("{} failed: HTTP {}: {}", action, self.status, detail)
+        }
+    }
+
+    /// Split curl's `--dump-header -` output into the final status, headers and body
+    fn parse(output: &[u8]) -> Result<Self> {
+        let mut rest = output;
+
+        loop {
+            let end = rest.windows(4)
+                .position(|w| w == b"\r\n\r\n")
+                .context("Malformed HTTP response: no end of headers")?;
+
+            let head = String::from_utf8_lossy(&rest[..end]).into_owned();
+            rest = &rest[end + 4..];
+
+            let mut lines = head.lines();
+            let status = lines.next()
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|code| code.parse::<u16>().ok())
+                .with_context(|| format!("Malformed HTTP status line: {}", head.lines().next().unwrap_or("")))?;
+
+            // Informational responses are followed by the real one
+            if (100..200).contains(&status) {
+                continue;
+            }
+
+            let headers = lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect();
+
+            return Ok(Self {
+                status,
+                headers,
+                body: rest.to_vec(),
+            });
+        }
+    }
+}
+
+/// Send a request and wait for the complete response
+pub(crate) async fn send(request: &HttpRequest) -> Result<HttpResponse> {
+    let mut child = tokio::process::Command::new("curl")
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow::anyhow!(
+                "curl was not found; remote targets need curl.exe (included with Windows 10 and later) on the PATH"
+            ),
+            _ => anyhow::Error::new(e).context("Failed to start curl"),
+        })?;
+
+    let mut stdin = child.stdin.take().context("curl stdin unavailable")?;
+    stdin.write_all(request.curl_config().as_bytes()).await
+        .context("Failed to pass request to curl")?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await
+        .context("Failed to wait for curl")?;
+
+    if !output.status.success() {
+        // The query can hold credentials such as SAS tokens
+        bail!(
+            "{} {} failed: {}",
+            request.method,
+            request.url.split('?').next().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    HttpResponse::parse(&output.stdout)
+}
+
+/// A file read in chunks for block uploads.
+///
+/// curl uploads from files, so each chunk is staged in a file in a private
+/// folder, removed again when the reader is dropped.
+pub(crate) struct ChunkedFile {
+    file: tokio::fs::File,
+    chunk_size: u64,
+    chunk: PathBuf,
+
+    /// Private folder `chunk` is staged in, removed with the reader
+    _staging: PrivateDir,
+    buffer: Vec<u8>,
+
+    /// MD5 of everything read so far
+    file_md5: Md5,
+}
+
+/// A staged chunk of a [`ChunkedFile`]
+pub(crate) struct Chunk<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) len: usize,
+
+    /// Computed from the bytes as they were read, for services that check uploads against it
+    pub(crate) md5: [u8; 16],
+}
+
+impl ChunkedFile {
+    pub(crate) async fn open(local: &Path, chunk_size: u64) -> Result<Self> {
+        let file = tokio::fs::File::open(local).await
+            .with_context(|| format!("Failed to open {}", local.display()))?;
+        let staging = PrivateDir::create("keephive_chunk")?;
+        let chunk = staging.path().join("chunk");
+
+        Ok(Self {
+            file,
+            chunk_size,
+            chunk,
+            _staging: staging,
+            buffer: Vec::new(),
+            file_md5: Md5::new(),
+        })
+    }
+
+    /// Stage the next chunk, or `None` at the end
+    pub(crate) async fn next_chunk(&mut self) -> Result<Option<Chunk<'_>>> {
+        self.buffer.clear();
+        (&mut self.file).take(self.chunk_size).read_to_end(&mut self.buffer).await?;
+
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        tokio::fs::write(&self.chunk, &self.buffer).await
+            .context("Failed to stage upload chunk")?;
+
+        let mut md5 = Md5::new();
+        md5.update(&self.buffer);
+        self.file_md5.update(&self.buffer);
+
+        Ok(Some(Chunk {
+            path: &self.chunk,
+            len: self.buffer.len(),
+            md5: md5.finish(),
+        }))
+    }
+
+    /// MD5 of the chunks read so far; the whole file's once `next_chunk` returned `None`
+    pub(crate) fn file_md5(&self) -> [u8; 16] {
+        self.file_md5.clone().finish()
+    }
+}
+
+/// Text of every element with this local name, whatever its namespace prefix
+pub(crate) fn xml_elements(xml: &str, name: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else { break };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        // Closing and empty elements have no text
+        if tag.starts_with('/') || tag.ends_with('/') {
+            continue;
+        }
+
+        let tag_name = tag.split_whitespace().next().unwrap_or("");
+        let local_name = tag_name.rsplit(':').next().unwrap_or(tag_name);
+
+        if local_name.eq_ignore_ascii_case(name)
+            && let Some(close) = rest.find('<')
+        {
+            found.push(unescape_xml(rest[..close].trim()));
+        }
+    }
+
+    found
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Quote a value for a curl config file
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// `Authorization` value for HTTP basic authentication
+pub(crate) fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", base64(format!("{}:{}", username, password).as_bytes()))
+}
+
+/// Standard base64 with padding
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Percent-encode one path segment
+pub(crate) fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+/// `application/x-www-form-urlencoded` body or query string
+pub(crate) fn form_encode(fields: &[(&str, &str)]) -> String {
+    fields.iter()
+        .map(|(key, value)| format!("{}={}", encode_segment(key), encode_segment(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encode every segment of a `/`-separated path, keeping the separators
+pub(crate) fn encode_path(path: &str) -> String {
+    path.split('/').map(encode_segment).collect::<Vec<_>>().join("/")
+}
+
+/// Decode `%XX` escapes; invalid escapes are kept as they are
+pub(crate) fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_skips_informational_responses() {
+        let output = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 201 Created\r\nETag: \"abc\"\r\nContent-Length: 2\r\n\r\nok";
+        let response = HttpResponse::parse(output).unwrap();
+
+        assert_eq!(response.status, 201);
+        assert_eq!(response.header("etag"), Some("\"abc\""));
+        assert_eq!(response.body, b"ok");
+
+        assert_eq!(HttpResponse::parse(b"HTTP/2 404\r\n\r\n").unwrap().status, 404);
+    }
+
+    #[test]
+    fn test_curl_config_quotes_values() {
+        let config = HttpRequest::new("PUT", "https://host/a b")
+            .header("Authorization", "Basic x\"y")
+            .text("line\nnext")
+            .curl_config();
+
+        assert!(config.contains("request = \"PUT\"\n"));
+        assert!(config.contains("header = \"Authorization: Basic x\\\"y\"\n"));
+        assert!(config.contains("data-raw = \"line\\nnext\"\n"));
+        assert!(HttpRequest::new("HEAD", "https://host/").curl_config().contains("\nhead\n"));
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(base64(b"alice:secret"), "YWxpY2U6c2VjcmV0");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+        assert_eq!(encode_path("Backups/docs 2025/ä.txt"), "Backups/docs%202025/%C3%A4.txt");
+        assert_eq!(decode("docs%202025/%C3%A4.txt"), "docs 2025/ä.txt");
+        assert_eq!(decode("100%"), "100%");
+    }
+}
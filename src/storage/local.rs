@@ -0,0 +1,100 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+use crate::storage::{BackendFuture, Capabilities, StorageBackend};
+
+/// Backend for a directory on a local or mounted filesystem (`file://` targets)
+pub struct LocalBackend {
+    root: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, remote_path: &str) -> PathBuf {
+        remote_path.split('/')
+            .filter(|part| !part.is_empty())
+            .fold(self.root.clone(), |path, part| path.join(part))
+    }
+}
+
+impl StorageBackend for LocalBackend {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            rename: true,
+            hardlink: true,
+            streaming: true,
+            checksums: false,
+        }
+    }
+
+    fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> BackendFuture<'a, u64> {
+        Box::pin(async move {
+            let destination = self.path(remote_path);
+            if let Some(parent) = destination.parent() {
+                tokio::fs::create_dir_all(parent).await
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+
+            tokio::fs::copy(local, &destination).await
+                .with_context(|| format!("Failed to copy {} to {}", local.display(), destination.display()))
+        })
+    }
+
+    fn get_file<'a>(&'a self, remote_path: &'a str, local: &'a Path) -> Option<BackendFuture<'a, u64>> {
+        Some(Box::pin(async move {
+            let source = self.path(remote_path);
+            tokio::fs::copy(&source, local).await
+                .with_context(|| format!("Failed to copy {} to {}", source.display(), local.display()))
+        }))
+    }
+
+    fn list<'a>(&'a self, remote_dir: &'a str) -> BackendFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let mut names = Vec::new();
+            let mut entries = match tokio::fs::read_dir(self.path(remote_dir)).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    fn exists<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, bool> {
+        Box::pin(async move { Ok(tokio::fs::try_exists(self.path(remote_path)).await?) })
+    }
+
+    fn remove<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let path = self.path(remote_path);
+            let result = match tokio::fs::symlink_metadata(&path).await {
+                Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(&path).await,
+                Ok(_) => tokio::fs::remove_file(&path).await,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            };
+
+            result.with_context(|| format!("Failed to remove {}", path.display()))
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            tokio::fs::rename(self.path(from), self.path(to)).await
+                .with_context(|| format!("Failed to rename {} to {}", from, to))
+        })
+    }
+}
@@ -0,0 +1,150 @@
+pub mod azure;
+pub mod gdrive;
+pub(crate) mod http;
+pub mod local;
+pub mod registry;
+pub mod rsync;
+pub mod webdav;
+
+pub use azure::AzureBlobBackend;
+pub use gdrive::GoogleDriveBackend;
+pub use local::LocalBackend;
+pub use registry::{check_replicas, validate_replicas, BackendFactory, BackendRegistry, ReplicaIssue};
+pub use rsync::RsyncBackend;
+pub use webdav::WebDavBackend;
+
+use anyhow::Result;
+use std::fmt;
+
+use crate::core::CopyProgress;
+use std::path::Path;
+use std::pin::Pin;
+
+/// Boxed future returned by [`StorageBackend`] methods
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// What a backend can do natively; callers fall back to slower strategies otherwise
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Directories can be renamed in one step (staged uploads are finalized by renaming)
+    pub rename: bool,
+
+    /// Unchanged files can be hard-linked instead of copied again
+    pub hardlink: bool,
+
+    /// Files are uploaded as streams, without staging them in memory or temp files
+    pub streaming: bool,
+
+    /// Every upload is checked against a checksum of the local data, by the service or the
+    /// transfer tool, so corruption in transit fails the upload
+    pub checksums: bool,
+}
+
+/// Options for [`StorageBackend::put_directory`]
+pub struct DirectoryUpload<'a> {
+    /// Top-level file names to leave out; the caller uploads them afterwards
+    pub exclude: &'a [&'a str],
+
+    /// Bytes per second, if transfers are throttled
+    pub rate_limit: Option<u64>,
+
+    /// Called as the transfer progresses
+    pub progress: &'a (dyn Fn(&CopyProgress) + Send + Sync),
+}
+
+/// A place backups can be stored, addressed with `/`-separated paths relative to its root
+pub trait StorageBackend: Send + Sync {
+    /// Short name for logs, usually the URL scheme
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Whether old backups are expired by the storage itself, so retention leaves them alone
+    fn manages_retention(&self) -> bool {
+        false
+    }
+
+    /// Upload a local file to `remote_path`, creating parent directories, returning bytes sent
+    fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> BackendFuture<'a, u64>;
+
+    /// Upload a whole directory in one transfer, for backends that sync trees natively.
+    /// `None` means the backend only uploads file by file.
+    fn put_directory<'a>(
+        &'a self,
+        _local: &'a Path,
+        _remote_dir: &'a str,
+        _options: DirectoryUpload<'a>,
+    ) -> Option<BackendFuture<'a, CopyProgress>> {
+        None
+    }
+
+    /// Download `remote_path` to `local`, returning bytes received; used to spot-check uploads.
+    /// `None` means the backend cannot download.
+    fn get_file<'a>(&'a self, _remote_path: &'a str, _local: &'a Path) -> Option<BackendFuture<'a, u64>> {
+        None
+    }
+
+    /// Names of the entries directly below `remote_dir` (`""` is the root)
+    fn list<'a>(&'a self, remote_dir: &'a str) -> BackendFuture<'a, Vec<String>>;
+
+    fn exists<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, bool>;
+
+    /// Remove a file or a whole directory; missing paths are not an error
+    fn remove<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, ()>;
+
+    /// Rename a file or directory; only called when `capabilities().rename` is set
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BackendFuture<'a, ()>;
+}
+
+/// Target written as `scheme://location`, e.g. `webdav://cloud.example.com/backups`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetUrl {
+    pub scheme: String,
+
+    /// Everything after `://`
+    pub location: String,
+}
+
+impl TargetUrl {
+    /// Parse a target; plain paths (including `C:\...`) are not URLs
+    pub fn parse(target: &str) -> Option<Self> {
+        let (scheme, location) = target.split_once("://")?;
+
+        // Single letters are drive letters, not schemes
+        let valid = scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+
+        valid.then(|| Self {
+            scheme: scheme.to_ascii_lowercase(),
+            location: location.to_string(),
+        })
+    }
+
+    pub fn from_path(target: &Path) -> Option<Self> {
+        Self::parse(target.to_str()?)
+    }
+}
+
+impl fmt::Display for TargetUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target_url() {
+        let url = TargetUrl::parse("WebDAV://cloud.example.com/backups").unwrap();
+        assert_eq!(url.scheme, "webdav");
+        assert_eq!(url.location, "cloud.example.com/backups");
+
+        assert_eq!(TargetUrl::parse(r"D:\Backups"), None);
+        assert_eq!(TargetUrl::parse("C://Backups"), None, "Drive letters are not schemes");
+        assert_eq!(TargetUrl::parse("/mnt/backups"), None);
+        assert_eq!(TargetUrl::parse("1x://host"), None);
+    }
+}
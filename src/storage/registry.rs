@@ -0,0 +1,280 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::config::{ServiceConfig, StorageConfig, WebDavConfig};
+use crate::state::SecretStore;
+use crate::storage::{AzureBlobBackend, GoogleDriveBackend, LocalBackend, RsyncBackend, StorageBackend, TargetUrl, WebDavBackend};
+
+/// Creates a backend for targets with a given URL scheme
+pub trait BackendFactory: Send + Sync {
+    fn open(&self, target: &TargetUrl) -> Result<Arc<dyn StorageBackend>>;
+}
+
+impl<F> BackendFactory for F
+where
+    F: Fn(&TargetUrl) -> Result<Arc<dyn StorageBackend>> + Send + Sync,
+{
+    fn open(&self, target: &TargetUrl) -> Result<Arc<dyn StorageBackend>> {
+        self(target)
+    }
+}
+
+/// Storage backends by URL scheme; plain paths always use the local filesystem
+pub struct BackendRegistry {
+    factories: RwLock<HashMap<String, Arc<dyn BackendFactory>>>,
+}
+
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackendRegistry {
+    /// Registry with the built-in backends
+    pub fn new() -> Self {
+        let registry = Self {
+            factories: RwLock::new(HashMap::new()),
+        };
+
+        registry.register("file", |target: &TargetUrl| {
+            Ok(Arc::new(LocalBackend::new(PathBuf::from(&target.location))) as Arc<dyn StorageBackend>)
+        });
+
+        registry
+    }
+
+    /// (Re-)register the built-in remote backends with their settings
+    pub fn configure(&self, config: &StorageConfig, secrets: Arc<SecretStore>) {
+        for scheme in ["webdav", "webdav+http"] {
+            let webdav = config.webdav.clone();
+            self.register(scheme, move |target: &TargetUrl| {
+                if sends_credentials_in_clear(target, &webdav) {
+                    bail!("{}", PLAIN_HTTP_AUTH);
+                }
+                Ok(Arc::new(WebDavBackend::open(target, &webdav)?) as Arc<dyn StorageBackend>)
+            });
+        }
+
+        let azure = config.azure.clone();
+        self.register("azblob", move |target: &TargetUrl| {
+            Ok(Arc::new(AzureBlobBackend::open(target, &azure)?) as Arc<dyn StorageBackend>)
+        });
+
+        for scheme in ["ssh", "rsync"] {
+            let rsync = config.rsync.clone();
+            self.register(scheme, move |target: &TargetUrl| {
+                Ok(Arc::new(RsyncBackend::open(target, &rsync)?) as Arc<dyn StorageBackend>)
+            });
+        }
+
+        let gdrive = config.gdrive.clone();
+        self.register("gdrive", move |target: &TargetUrl| {
+            Ok(Arc::new(GoogleDriveBackend::open(target, &gdrive, secrets.clone())?) as Arc<dyn StorageBackend>)
+        });
+    }
+
+    /// Add or replace the backend for a scheme (case-insensitive)
+    pub fn register(&self, scheme: &str, factory: impl BackendFactory + 'static) {
+        self.factories.write().unwrap()
+            .insert(scheme.to_ascii_lowercase(), Arc::new(factory));
+    }
+
+    /// Registered schemes, sorted
+    pub fn schemes(&self) -> Vec<String> {
+        let mut schemes: Vec<String> = self.factories.read().unwrap().keys().cloned().collect();
+        schemes.sort();
+        schemes
+    }
+
+    /// Backend for a target path or URL
+    pub fn open(&self, target: &Path) -> Result<Arc<dyn StorageBackend>> {
+        let Some(url) = TargetUrl::from_path(target) else {
+            return Ok(Arc::new(LocalBackend::new(target.to_path_buf())));
+        };
+
+        let factory = self.factories.read().unwrap().get(&url.scheme).cloned();
+        match factory {
+            Some(factory) => factory.open(&url),
+            None => bail!(
+                "No storage backend for '{}://' targets (available: {})",
+                url.scheme,
+                self.schemes().join(", ")
+            ),
+        }
+    }
+}
+
+/// A replica setting the service refuses, whatever the backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaIssue {
+    /// Setting at fault, e.g. `jobs[docs].retention_count`
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for ReplicaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Why credentials are not sent over plain HTTP
+const PLAIN_HTTP_AUTH: &str = "webdav+http:// sends the user name and password unencrypted; \
+    use webdav:// (HTTPS), or set storage.webdav.allow_plain_http_auth on a trusted network";
+
+/// Whether a WebDAV target would send Basic auth credentials over plain HTTP without the
+/// config allowing it
+fn sends_credentials_in_clear(target: &TargetUrl, webdav: &WebDavConfig) -> bool {
+    let authority = target.location.split('/').next().unwrap_or_default();
+    let has_user = authority.contains('@') || webdav.username.is_some();
+
+    target.scheme == "webdav+http" && has_user && !webdav.allow_plain_http_auth
+}
+
+/// Replica settings no backend can be used with
+pub fn check_replicas(config: &ServiceConfig) -> Vec<ReplicaIssue> {
+    let mut issues = Vec::new();
+
+    for job in &config.jobs {
+        for replica in &job.replicas {
+            if let Some(url) = TargetUrl::from_path(replica)
+                && sends_credentials_in_clear(&url, &config.storage.webdav)
+            {
+                issues.push(ReplicaIssue {
+                    location: format!("jobs[{}].replicas", job.id),
+                    message: PLAIN_HTTP_AUTH.to_string(),
+                });
+            }
+        }
+    }
+
+    for job in config.jobs.iter().filter(|job| !job.replicas.is_empty()) {
+        let (location, retention_count) = match job.retention_count {
+            Some(count) => (format!("jobs[{}].retention_count", job.id), count),
+            None => ("retention_count".to_string(), config.retention_count),
+        };
+        if retention_count == 0 {
+            issues.push(ReplicaIssue {
+                location,
+                message: format!("0 would remove every backup on the replicas of job {}; keep at least 1", job.id),
+            });
+        }
+    }
+
+    issues
+}
+
+/// Fail on replica settings no backend can be used with
+pub fn validate_replicas(config: &ServiceConfig) -> Result<()> {
+    let issues: Vec<String> = check_replicas(config).iter()
+        .map(|issue| format!("  - {}", issue))
+        .collect();
+    if !issues.is_empty() {
+        bail!("Invalid replica settings in configuration:\n{}", issues.join("\n"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BackendFuture, Capabilities};
+
+    struct NullBackend;
+
+    impl StorageBackend for NullBackend {
+        fn name(&self) -> &str {
+            "null"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn put_file<'a>(&'a self, _local: &'a Path, _remote_path: &'a str) -> BackendFuture<'a, u64> {
+            Box::pin(async { Ok(0) })
+        }
+
+        fn list<'a>(&'a self, _remote_dir: &'a str) -> BackendFuture<'a, Vec<String>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn exists<'a>(&'a self, _remote_path: &'a str) -> BackendFuture<'a, bool> {
+            Box::pin(async { Ok(false) })
+        }
+
+        fn remove<'a>(&'a self, _remote_path: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn rename<'a>(&'a self, _from: &'a str, _to: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async { bail!("rename not supported") })
+        }
+    }
+
+    #[test]
+    fn test_open_by_scheme() {
+        let registry = BackendRegistry::new();
+        registry.register("NULL", |_: &TargetUrl| Ok(Arc::new(NullBackend) as Arc<dyn StorageBackend>));
+
+        assert_eq!(registry.open(Path::new("null://anything")).unwrap().name(), "null");
+        assert_eq!(registry.open(Path::new("file:///srv/backups")).unwrap().name(), "file");
+        assert_eq!(registry.open(Path::new("/srv/backups")).unwrap().name(), "file");
+
+        let missing = registry.open(Path::new("b2://bucket")).err().unwrap();
+        assert!(missing.to_string().contains("file, null"));
+    }
+
+    #[test]
+    fn test_retention_of_zero_is_refused_for_replicas() {
+        let mut config: ServiceConfig = serde_json::from_str(r#"{
+            "jobs": [
+                { "id": "docs", "source": "/home/me/docs", "target": "/mnt/backup/docs",
+                  "replicas": ["webdav://cloud.example.com/docs"], "schedule": { "type": "daily", "hour": 2, "minute": 0 } },
+                { "id": "music", "source": "/home/me/music", "target": "/mnt/backup/music",
+                  "schedule": { "type": "daily", "hour": 3, "minute": 0 } }
+            ],
+            "retention_count": 0
+        }"#).unwrap();
+
+        assert_eq!(check_replicas(&config), vec![ReplicaIssue {
+            location: "retention_count".to_string(),
+            message: "0 would remove every backup on the replicas of job docs; keep at least 1".to_string(),
+        }]);
+        assert!(validate_replicas(&config).is_err());
+
+        config.jobs[0].retention_count = Some(3);
+        assert!(check_replicas(&config).is_empty(), "Jobs without replicas are not affected");
+
+        config.jobs[0].retention_count = Some(0);
+        assert_eq!(check_replicas(&config)[0].location, "jobs[docs].retention_count");
+    }
+
+    #[test]
+    fn test_credentials_over_plain_http_need_an_opt_in() {
+        let mut config: ServiceConfig = serde_json::from_str(r#"{
+            "jobs": [
+                { "id": "docs", "source": "/home/me/docs", "target": "/mnt/backup/docs",
+                  "replicas": ["webdav+http://alice@nas.local/docs", "webdav://alice@cloud.example.com/docs"],
+                  "schedule": { "type": "daily", "hour": 2, "minute": 0 } }
+            ]
+        }"#).unwrap();
+
+        let issues = check_replicas(&config);
+        assert_eq!(issues.len(), 1, "HTTPS replicas may carry credentials");
+        assert_eq!(issues[0].location, "jobs[docs].replicas");
+
+        let registry = BackendRegistry::new();
+        registry.configure(&config.storage, Arc::new(SecretStore::new(PathBuf::from("secrets.json"))));
+        assert!(registry.open(Path::new("webdav+http://alice@nas.local/docs")).is_err());
+        assert!(registry.open(Path::new("webdav+http://nas.local/docs")).is_ok(), "Anonymous access is fine");
+
+        config.storage.webdav.allow_plain_http_auth = true;
+        assert!(check_replicas(&config).is_empty());
+    }
+}
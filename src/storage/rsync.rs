@@ -0,0 +1,579 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::Mutex;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::config::RsyncConfig;
+use crate::core::replication::backup_timestamp;
+use crate::core::CopyProgress;
+use crate::platform::PrivateDir;
+use crate::storage::http;
+use crate::storage::{BackendFuture, Capabilities, DirectoryUpload, StorageBackend, TargetUrl};
+
+/// rsync exit code for "some source files vanished before they could be transferred"
+const EXIT_VANISHED: i32 = 24;
+
+/// Backend that shells out to rsync for delta transfers to Linux NAS devices.
+///
+/// `ssh://[user@]host[:port]/path` runs rsync over SSH (key authentication only,
+/// since nothing can answer a password prompt), `rsync://[user@]host[:port]/module/path`
+/// talks to an rsync daemon. Whole backups are sent with `--link-dest` against the
+/// previous one, so unchanged files become hard links on the NAS instead of copies.
+pub struct RsyncBackend {
+    scheme: String,
+
+    rsync_command: String,
+
+    /// SSH connection for `ssh://` targets
+    ssh: Option<SshTarget>,
+
+    /// Root of the target as rsync addresses it, without trailing slash
+    base: String,
+
+    /// rsync daemon password for `rsync://` targets
+    password: Option<String>,
+
+    /// Directories known to exist, so each is created once
+    directories: Mutex<HashSet<String>>,
+}
+
+struct SshTarget {
+    command: String,
+
+    /// `[user@]host`
+    destination: String,
+
+    port: u16,
+
+    identity_file: Option<PathBuf>,
+}
+
+impl SshTarget {
+    /// Arguments shared by the `-e` remote shell and direct ssh calls
+    fn options(&self) -> Vec<String> {
+        let mut options = vec![
+            "-p".to_string(),
+            self.port.to_string(),
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+        ];
+        if let Some(identity) = &self.identity_file {
+            options.push("-i".to_string());
+            options.push(identity.to_string_lossy().into_owned());
+        }
+        options
+    }
+
+    /// Value for rsync's `-e`, which splits on whitespace but honors double quotes
+    fn remote_shell(&self) -> String {
+        std::iter::once(self.command.clone())
+            .chain(self.options())
+            .map(|arg| if arg.contains(' ') { format!("\"{}\"", arg) } else { arg })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl RsyncBackend {
+    pub fn open(target: &TargetUrl, config: &RsyncConfig) -> Result<Self> {
+        let (authority, path) = target.location.split_once('/')
+            .unwrap_or((&target.location, ""));
+
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (Some(http::decode(user)), host_port),
+            None => (None, authority),
+        };
+
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse::<u16>()
+                    .with_context(|| format!("Invalid port in {}", target))?;
+                (host, Some(port))
+            }
+            None => (host_port, None),
+        };
+
+        if host.is_empty() {
+            bail!("rsync target has no host: {}", target);
+        }
+
+        let path = http::decode(path.trim_end_matches('/'));
+        let user_prefix = user.map(|user| format!("{}@", user)).unwrap_or_default();
+
+        let (ssh, base, password) = match target.scheme.as_str() {
+            "ssh" => {
+                let ssh = SshTarget {
+                    command: config.ssh_command.clone(),
+                    destination: format!("{}{}", user_prefix, host),
+                    port: port.unwrap_or(22),
+                    identity_file: config.identity_file.clone(),
+                };
+                let base = format!("{}:/{}", ssh.destination, path);
+                (Some(ssh), base, None)
+            }
+            "rsync" => {
+                if path.is_empty() {
+                    bail!("rsync daemon target needs a module: {}", target);
+                }
+                let password = match &config.password_env {
+                    Some(var) => Some(std::env::var(var)
+                        .with_context(|| format!("rsync password variable {} is not set", var))?),
+                    None => None,
+                };
+                let port = port.map(|port| format!(":{}", port)).unwrap_or_default();
+                let base = format!("rsync://{}{}{}/{}", user_prefix, host, port, path);
+                (None, base, password)
+            }
+            other => bail!("Not an rsync target: {}://", other),
+        };
+
+        Ok(Self {
+            scheme: target.scheme.clone(),
+            rsync_command: config.rsync_command.clone(),
+            ssh,
+            base,
+            password,
+            directories: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// rsync address of a path below the root
+    fn remote(&self, path: &str) -> String {
+        if path.is_empty() {
+            format!("{}/", self.base)
+        } else {
+            format!("{}/{}", self.base, path)
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.rsync_command);
+        command.arg("--protect-args").arg("--timeout=600");
+
+        if let Some(ssh) = &self.ssh {
+            command.arg("-e").arg(ssh.remote_shell());
+        }
+        if let Some(password) = &self.password {
+            command.env("RSYNC_PASSWORD", password);
+        }
+
+        command.stdin(Stdio::null()).kill_on_drop(true);
+        command
+    }
+
+    /// Run rsync to completion, returning its output whatever the exit code
+    async fn run(&self, args: &[String]) -> Result<Output> {
+        let mut command = self.command();
+        command.args(args);
+        debug!("rsync {}", args.join(" "));
+
+        command.output().await.map_err(|e| spawn_error(&self.rsync_command, e))
+    }
+
+    /// Create `dir` and its parents below the root; the root's own parent must exist
+    async fn ensure_directory(&self, dir: &str) -> Result<()> {
+        let empty = empty_directory()?;
+
+        let mut prefixes = vec![String::new()];
+        let mut current = String::new();
+        for part in dir.split('/').filter(|part| !part.is_empty()) {
+            if !current.is_empty() {
+                current.push('/');
+            }
+            current.push_str(part);
+            prefixes.push(current.clone());
+        }
+
+        for prefix in prefixes {
+            if self.directories.lock().unwrap().contains(&prefix) {
+                continue;
+            }
+
+            // Sending an empty directory creates the destination without touching its contents
+            let output = self.run(&[
+                "-d".to_string(),
+                format!("{}/", local_path(empty.path())),
+                format!("{}/", self.remote(&prefix).trim_end_matches('/')),
+            ]).await?;
+            check(&output, &format!("create {}", self.remote(&prefix)))?;
+
+            self.directories.lock().unwrap().insert(prefix);
+        }
+        Ok(())
+    }
+
+    /// Most recent other backup next to `remote_dir`, to hard-link unchanged files against
+    async fn previous_backup(&self, remote_dir: &str) -> Option<String> {
+        let (parent, name) = remote_dir.rsplit_once('/').unwrap_or(("", remote_dir));
+        let entries = self.list(parent).await.ok()?;
+
+        entries.into_iter()
+            .filter(|entry| entry != name && entry != name.trim_end_matches("_PARTIAL"))
+            .filter(|entry| !entry.ends_with("_PARTIAL") && !entry.starts_with(".keephive"))
+            .max_by(|a, b| backup_timestamp(a).cmp(backup_timestamp(b)))
+    }
+
+    async fn sync_directory(&self, local: &Path, remote_dir: &str, options: DirectoryUpload<'_>) -> Result<CopyProgress> {
+        if let Some((parent, _)) = remote_dir.rsplit_once('/') {
+            self.ensure_directory(parent).await?;
+        } else {
+            self.ensure_directory("").await?;
+        }
+
+        let mut args = vec![
+            "-a".to_string(),
+            "--no-inc-recursive".to_string(),
+            "--info=progress2".to_string(),
+        ];
+        if let Some(rate) = options.rate_limit {
+            args.push(format!("--bwlimit={}", (rate / 1024).max(1)));
+        }
+        for name in options.exclude {
+            args.push(format!("--exclude=/{}", name));
+        }
+        if let Some(previous) = self.previous_backup(remote_dir).await {
+            // Relative paths are resolved against the destination directory
+            debug!("Hard-linking unchanged files against {}", previous);
+            args.push(format!("--link-dest=../{}", previous));
+        }
+        args.push(format!("{}/", local_path(local)));
+        args.push(format!("{}/", self.remote(remote_dir)));
+
+        let mut command = self.command();
+        command.args(&args).stdout(Stdio::piped()).stderr(Stdio::piped());
+        debug!("rsync {}", args.join(" "));
+
+        let mut child = command.spawn().map_err(|e| spawn_error(&self.rsync_command, e))?;
+        let mut stdout = child.stdout.take().context("rsync stdout unavailable")?;
+        let mut stderr = child.stderr.take().context("rsync stderr unavailable")?;
+
+        let mut progress = CopyProgress::default();
+
+        let read_progress = async {
+            let mut buffer = [0u8; 4096];
+            let mut line = Vec::new();
+            loop {
+                let read = stdout.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                // progress2 rewrites its line with \r
+                for &byte in &buffer[..read] {
+                    if byte != b'\r' && byte != b'\n' {
+                        line.push(byte);
+                        continue;
+                    }
+                    if let Some((bytes, files)) = parse_progress(&String::from_utf8_lossy(&line)) {
+                        progress.bytes_copied = bytes;
+                        progress.files_copied = files.unwrap_or(progress.files_copied);
+                        (options.progress)(&progress);
+                    }
+                    line.clear();
+                }
+            }
+            std::io::Result::Ok(())
+        };
+
+        let mut errors = Vec::new();
+        let (read, _) = tokio::join!(read_progress, stderr.read_to_end(&mut errors));
+        read.context("Failed to read rsync progress")?;
+
+        let status = child.wait().await.context("Failed to wait for rsync")?;
+        let errors = String::from_utf8_lossy(&errors);
+
+        match status.code() {
+            Some(0) => {}
+            Some(EXIT_VANISHED) => warn!("Some files vanished during rsync to {}: {}", self.remote(remote_dir), errors.trim()),
+            _ => bail!("rsync to {} failed ({}): {}", self.remote(remote_dir), status, errors.trim()),
+        }
+
+        Ok(progress)
+    }
+}
+
+impl StorageBackend for RsyncBackend {
+    fn name(&self) -> &str {
+        &self.scheme
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            // The daemon protocol has no rename; SSH can run mv
+            rename: self.ssh.is_some(),
+            hardlink: true,
+            streaming: true,
+            // rsync checks every transferred file against a whole-file checksum
+            checksums: true,
+        }
+    }
+
+    fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> BackendFuture<'a, u64> {
+        Box::pin(async move {
+            let parent = remote_path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+            self.ensure_directory(parent).await?;
+
+            let output = self.run(&[
+                "-t".to_string(),
+                local_path(local),
+                self.remote(remote_path),
+            ]).await?;
+            check(&output, &format!("upload to {}", self.remote(remote_path)))?;
+
+            Ok(tokio::fs::metadata(local).await?.len())
+        })
+    }
+
+    fn put_directory<'a>(
+        &'a self,
+        local: &'a Path,
+        remote_dir: &'a str,
+        options: DirectoryUpload<'a>,
+    ) -> Option<BackendFuture<'a, CopyProgress>> {
+        Some(Box::pin(self.sync_directory(local, remote_dir, options)))
+    }
+
+    fn get_file<'a>(&'a self, remote_path: &'a str, local: &'a Path) -> Option<BackendFuture<'a, u64>> {
+        Some(Box::pin(async move {
+            let output = self.run(&[self.remote(remote_path), local_path(local)]).await?;
+            check(&output, &format!("download {}", self.remote(remote_path)))?;
+            Ok(tokio::fs::metadata(local).await?.len())
+        }))
+    }
+
+    fn list<'a>(&'a self, remote_dir: &'a str) -> BackendFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let target = format!("{}/", self.remote(remote_dir).trim_end_matches('/'));
+            let output = self.run(&["--list-only".to_string(), target.clone()]).await?;
+            if is_missing(&output) {
+                return Ok(Vec::new());
+            }
+            check(&output, &format!("list {}", target))?;
+
+            Ok(String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(parse_list_line)
+                .filter(|name| name != ".")
+                .collect())
+        })
+    }
+
+    fn exists<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, bool> {
+        Box::pin(async move {
+            let output = self.run(&["--list-only".to_string(), self.remote(remote_path)]).await?;
+            if is_missing(&output) {
+                return Ok(false);
+            }
+            check(&output, &format!("check {}", self.remote(remote_path)))?;
+            Ok(true)
+        })
+    }
+
+    fn remove<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            if remote_path.is_empty() {
+                bail!("Refusing to remove the root of {}", self.base);
+            }
+            if !self.exists(remote_path).await? {
+                return Ok(());
+            }
+
+            // rsync has no delete command: sync an empty directory over the parent,
+            // deleting only the one entry that the filter rules include
+            let (parent, name) = remote_path.rsplit_once('/').unwrap_or(("", remote_path));
+            let empty = empty_directory()?;
+            let output = self.run(&[
+                "-r".to_string(),
+                "--delete".to_string(),
+                format!("--include=/{}/***", escape_pattern(name)),
+                "--exclude=*".to_string(),
+                format!("{}/", local_path(empty.path())),
+                format!("{}/", self.remote(parent).trim_end_matches('/')),
+            ]).await?;
+            check(&output, &format!("remove {}", self.remote(remote_path)))?;
+
+            self.directories.lock().unwrap()
+                .retain(|dir| dir != remote_path && !dir.starts_with(&format!("{}/", remote_path)));
+            Ok(())
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let Some(ssh) = &self.ssh else {
+                bail!("rsync daemon targets cannot rename");
+            };
+
+            let root = self.base.split_once(':').map(|(_, path)| path).unwrap_or("/");
+            let script = format!(
+                "mv -- {} {}",
+                shell_quote(&format!("{}/{}", root, from)),
+                shell_quote(&format!("{}/{}", root, to))
+            );
+
+            let output = Command::new(&ssh.command)
+                .args(ssh.options())
+                .arg(&ssh.destination)
+                .arg(script)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output().await
+                .map_err(|e| spawn_error(&ssh.command, e))?;
+
+            if !output.status.success() {
+                bail!("Failed to rename {} to {}: {}", from, to, String::from_utf8_lossy(&output.stderr).trim());
+            }
+            Ok(())
+        })
+    }
+}
+
+fn spawn_error(program: &str, error: std::io::Error) -> anyhow::Error {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => anyhow::anyhow!(
+            "{} was not found; install rsync (e.g. cwRsync on Windows) or set storage.rsync in the config",
+            program
+        ),
+        _ => anyhow::Error::new(error).context(format!("Failed to start {}", program)),
+    }
+}
+
+fn check(output: &Output, action: &str) -> Result<()> {
+    if !output.status.success() {
+        bail!("Failed to {} ({}): {}", action, output.status, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Whether rsync failed only because the remote path does not exist
+fn is_missing(output: &Output) -> bool {
+    !output.status.success()
+        && String::from_utf8_lossy(&output.stderr).contains("No such file or directory")
+}
+
+/// A fresh, randomly named empty directory, used as the source for creating and deleting
+/// remote entries. A predictable name could be created in advance by another user, whose
+/// files would then be sent to the remote, or kept there by `--delete`.
+fn empty_directory() -> Result<PrivateDir> {
+    PrivateDir::create("keephive-rsync")
+}
+
+/// Local path as rsync expects it; Windows builds of rsync (cwRsync) want `/cygdrive/c/...`
+fn local_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        cygdrive_path(&path)
+    } else {
+        path.into_owned()
+    }
+}
+
+fn cygdrive_path(path: &str) -> String {
+    let path = path.strip_prefix(r"\\?\").unwrap_or(path).replace('\\', "/");
+    let bytes = path.as_bytes();
+
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        format!("/cygdrive/{}{}", (bytes[0] as char).to_ascii_lowercase(), &path[2..])
+    } else {
+        path
+    }
+}
+
+/// Bytes and files transferred from an `--info=progress2` line such as
+/// `1,234,567  45%  1.23MB/s  0:00:01 (xfr#5, to-chk=12/40)`
+fn parse_progress(line: &str) -> Option<(u64, Option<u64>)> {
+    let mut fields = line.split_whitespace();
+
+    let bytes = fields.next()?
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.' | '\''))
+        .collect::<String>()
+        .parse()
+        .ok()?;
+
+    if !fields.next()?.ends_with('%') {
+        return None;
+    }
+
+    let files = fields
+        .find_map(|field| field.strip_prefix("(xfr#"))
+        .and_then(|count| count.trim_end_matches([',', ')']).parse().ok());
+
+    Some((bytes, files))
+}
+
+/// Entry name from a `--list-only` line such as `drwxr-xr-x  4,096 2025/01/01 12:00:00 name`
+fn parse_list_line(line: &str) -> Option<String> {
+    let mut rest = line;
+    for _ in 0..4 {
+        rest = rest.trim_start().split_once(' ')?.1;
+    }
+    let name = rest.trim_start();
+
+    let name = match line.starts_with('l') {
+        true => name.split(" -> ").next()?,
+        false => name,
+    };
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Escape wildcard characters so a filter rule matches `name` literally
+fn escape_pattern(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Quote for a POSIX shell on the remote side
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_targets() {
+        let config = RsyncConfig {
+            identity_file: Some(PathBuf::from("/keys/nas key")),
+            ..RsyncConfig::default()
+        };
+
+        let ssh = RsyncBackend::open(&TargetUrl::parse("ssh://backup@nas:2222/volume1/backups/").unwrap(), &config).unwrap();
+        assert_eq!(ssh.base, "backup@nas:/volume1/backups");
+        assert_eq!(ssh.remote("docs_2025-01-01_000000_000"), "backup@nas:/volume1/backups/docs_2025-01-01_000000_000");
+        assert_eq!(ssh.ssh.as_ref().unwrap().remote_shell(), "ssh -p 2222 -o BatchMode=yes -i \"/keys/nas key\"");
+        assert!(ssh.capabilities().rename);
+
+        let daemon = RsyncBackend::open(&TargetUrl::parse("rsync://nas:8730/backups/pc").unwrap(), &config).unwrap();
+        assert_eq!(daemon.base, "rsync://nas:8730/backups/pc");
+        assert!(!daemon.capabilities().rename);
+
+        assert!(RsyncBackend::open(&TargetUrl::parse("rsync://nas").unwrap(), &config).is_err());
+        assert!(RsyncBackend::open(&TargetUrl::parse("ssh://nas:port/x").unwrap(), &config).is_err());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(parse_progress("  1,234,567  45%  1.23MB/s    0:00:01 (xfr#5, to-chk=12/40)"), Some((1234567, Some(5))));
+        assert_eq!(parse_progress("          0   0%    0.00kB/s    0:00:00"), Some((0, None)));
+        assert_eq!(parse_progress("sending incremental file list"), None);
+
+        assert_eq!(parse_list_line("drwxr-xr-x          4,096 2025/01/01 12:00:00 docs_2025-01-01_000000_000").as_deref(), Some("docs_2025-01-01_000000_000"));
+        assert_eq!(parse_list_line("-rw-r--r--             12 2025/01/01 12:00:00 my file.txt").as_deref(), Some("my file.txt"));
+        assert_eq!(parse_list_line("lrwxrwxrwx              6 2025/01/01 12:00:00 latest -> target").as_deref(), Some("latest"));
+
+        assert_eq!(cygdrive_path(r"C:\Backups\docs"), "/cygdrive/c/Backups/docs");
+        assert_eq!(cygdrive_path(r"\\?\D:\x"), "/cygdrive/d/x");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
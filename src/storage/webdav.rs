@@ -0,0 +1,404 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::debug;
+
+use crate::config::WebDavConfig;
+use crate::storage::http::{self, ChunkedFile, HttpRequest, HttpResponse};
+use crate::storage::{BackendFuture, Capabilities, StorageBackend, TargetUrl};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// Backend for WebDAV servers such as Nextcloud and ownCloud.
+///
+/// `webdav://host/path` uses HTTPS, `webdav+http://host/path` plain HTTP.
+/// Uploads carry `If-Match`/`If-None-Match` so a file changed on the server in the
+/// meantime is never silently overwritten. On Nextcloud, large files are uploaded in
+/// chunks that the server assembles, so a dropped connection only costs one chunk.
+pub struct WebDavBackend {
+    /// Collection URL without trailing slash
+    base: String,
+
+    /// `scheme://host[:port]`
+    origin: String,
+
+    authorization: Option<String>,
+
+    /// Nextcloud chunked upload collection for this user, if the server is Nextcloud
+    uploads: Option<String>,
+
+    chunk_size: u64,
+
+    /// Collections known to exist, so each is created once
+    collections: Mutex<HashSet<String>>,
+}
+
+impl WebDavBackend {
+    pub fn open(target: &TargetUrl, config: &WebDavConfig) -> Result<Self> {
+        let protocol = match target.scheme.as_str() {
+            "webdav" => "https",
+            "webdav+http" => "http",
+            other => bail!("Not a WebDAV target: {}://", other),
+        };
+
+        let (authority, path) = target.location.split_once('/')
+            .unwrap_or((&target.location, ""));
+
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(http::decode(user)), host),
+            None => (config.username.clone(), authority),
+        };
+
+        if host.is_empty() {
+            bail!("WebDAV target has no host: {}", target);
+        }
+
+        let authorization = match user {
+            Some(user) => {
+                let password = match &config.password_env {
+                    Some(var) => std::env::var(var)
+                        .with_context(|| format!("WebDAV password variable {} is not set", var))?,
+                    None => String::new(),
+                };
+                Some(http::basic_auth(&user, &password))
+            }
+            None => None,
+        };
+
+        let path = path.trim_matches('/');
+        let origin = format!("{}://{}", protocol, host);
+        let base = if path.is_empty() {
+            origin.clone()
+        } else {
+            format!("{}/{}", origin, http::encode_path(path))
+        };
+
+        Ok(Self {
+            uploads: nextcloud_uploads(&origin, path),
+            base,
+            origin,
+            authorization,
+            chunk_size: config.chunk_size_mb.saturating_mul(1024 * 1024),
+            collections: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn url(&self, remote_path: &str) -> String {
+        let path = remote_path.trim_matches('/');
+        if path.is_empty() {
+            self.base.clone()
+        } else {
+            format!("{}/{}", self.base, http::encode_path(path))
+        }
+    }
+
+    fn request(&self, method: &str, url: impl Into<String>) -> HttpRequest {
+        let request = HttpRequest::new(method, url);
+        match &self.authorization {
+            Some(authorization) => request.header("Authorization", authorization.as_str()),
+            None => request,
+        }
+    }
+
+    async fn propfind(&self, remote_path: &str, depth: u8) -> Result<HttpResponse> {
+        let request = self.request("PROPFIND", self.url(remote_path))
+            .header("Depth", depth.to_string())
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .text(PROPFIND_BODY);
+
+        http::send(&request).await
+    }
+
+    /// Create every collection on the way to `remote_dir`
+    async fn ensure_collections(&self, remote_dir: &str) -> Result<()> {
+        let mut path = String::new();
+
+        for segment in remote_dir.split('/').filter(|s| !s.is_empty()) {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(segment);
+
+            if self.collections.lock().unwrap().contains(&path) {
+                continue;
+            }
+
+            let response = http::send(&self.request("MKCOL", self.url(&path))).await?;
+            // 405: the collection already exists
+            if !response.is_success() && response.status != 405 {
+                return Err(response.error(&format!("Creating collection {}", path)));
+            }
+
+            self.collections.lock().unwrap().insert(path.clone());
+        }
+
+        Ok(())
+    }
+
+    /// ETag of an existing file, `None` if there is no file
+    async fn etag(&self, remote_path: &str) -> Result<Option<String>> {
+        let response = http::send(&self.request("HEAD", self.url(remote_path))).await?;
+
+        match response.status {
+            404 => Ok(None),
+            _ if response.is_success() => Ok(Some(response.header("ETag").unwrap_or("*").to_string())),
+            _ => Err(response.error(&format!("Checking {}", remote_path))),
+        }
+    }
+
+    /// Precondition that the file is still what we saw: unchanged, or still missing
+    fn precondition(request: HttpRequest, etag: &Option<String>) -> HttpRequest {
+        match etag {
+            Some(etag) => request.header("If-Match", etag.as_str()),
+            None => request.header("If-None-Match", "*"),
+        }
+    }
+
+    fn check_upload(response: &HttpResponse, remote_path: &str) -> Result<()> {
+        match response.status {
+            _ if response.is_success() => Ok(()),
+            412 => bail!("{} changed on the server while uploading; not overwriting it", remote_path),
+            _ => Err(response.error(&format!("Uploading {}", remote_path))),
+        }
+    }
+
+    async fn put_whole(&self, local: &Path, remote_path: &str, etag: &Option<String>) -> Result<()> {
+        let request = Self::precondition(self.request("PUT", self.url(remote_path)), etag)
+            .file(local);
+
+        Self::check_upload(&http::send(&request).await?, remote_path)
+    }
+
+    /// Nextcloud chunked upload (v2): chunks go to a temporary upload collection,
+    /// and a final `MOVE` assembles them at the destination.
+    async fn put_chunked(
+        &self,
+        uploads: &str,
+        local: &Path,
+        remote_path: &str,
+        size: u64,
+        etag: &Option<String>,
+    ) -> Result<()> {
+        let transfer = format!(
+            "keephive-{}",
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()
+        );
+        let transfer_url = format!("{}/{}", uploads, transfer);
+        let destination = self.url(remote_path);
+
+        let response = http::send(
+            &self.request("MKCOL", transfer_url.as_str()).header("Destination", destination.as_str())
+        ).await?;
+        if !response.is_success() {
+            return Err(response.error("Starting chunked upload"));
+        }
+
+        let result = async {
+            self.send_chunks(&transfer_url, &destination, local, size).await?;
+
+            let request = self.request("MOVE", format!("{}/.file", transfer_url))
+                .header("Destination", destination.as_str())
+                .header("OC-Total-Length", size.to_string());
+
+            Self::check_upload(&http::send(&Self::precondition(request, etag)).await?, remote_path)
+        }.await;
+
+        if result.is_err() {
+            let _ = http::send(&self.request("DELETE", transfer_url.as_str())).await;
+        }
+
+        result
+    }
+
+    async fn send_chunks(&self, transfer_url: &str, destination: &str, local: &Path, size: u64) -> Result<()> {
+        let mut chunks = ChunkedFile::open(local, self.chunk_size).await?;
+        let mut index = 1u32;
+
+        while let Some(chunk) = chunks.next_chunk().await? {
+            let request = self.request("PUT", format!("{}/{:05}", transfer_url, index))
+                .header("Destination", destination)
+                .header("OC-Total-Length", size.to_string())
+                .file(chunk.path);
+
+            let response = http::send(&request).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Uploading chunk {}", index)));
+            }
+
+            debug!("Uploaded chunk {} ({} bytes) to {}", index, chunk.len, destination);
+            index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl StorageBackend for WebDavBackend {
+    fn name(&self) -> &str {
+        "webdav"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            rename: true,
+            hardlink: false,
+            streaming: true,
+            // Servers store checksums at best; uploads are spot-checked by downloading instead
+            checksums: false,
+        }
+    }
+
+    fn put_file<'a>(&'a self, local: &'a Path, remote_path: &'a str) -> BackendFuture<'a, u64> {
+        Box::pin(async move {
+            if let Some((parent, _)) = remote_path.rsplit_once('/') {
+                self.ensure_collections(parent).await?;
+            }
+
+            let size = tokio::fs::metadata(local).await
+                .with_context(|| format!("Failed to read {}", local.display()))?
+                .len();
+            let etag = self.etag(remote_path).await?;
+
+            match &self.uploads {
+                Some(uploads) if self.chunk_size > 0 && size > self.chunk_size => {
+                    self.put_chunked(uploads, local, remote_path, size, &etag).await?
+                }
+                _ => self.put_whole(local, remote_path, &etag).await?,
+            }
+
+            Ok(size)
+        })
+    }
+
+    fn get_file<'a>(&'a self, remote_path: &'a str, local: &'a Path) -> Option<BackendFuture<'a, u64>> {
+        Some(Box::pin(async move {
+            let response = http::send(&self.request("GET", self.url(remote_path)).output(local)).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Downloading {}", remote_path)));
+            }
+            Ok(tokio::fs::metadata(local).await?.len())
+        }))
+    }
+
+    fn list<'a>(&'a self, remote_dir: &'a str) -> BackendFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let response = self.propfind(remote_dir, 1).await?;
+            if response.status == 404 {
+                return Ok(Vec::new());
+            }
+            if !response.is_success() {
+                return Err(response.error(&format!("Listing {}", remote_dir)));
+            }
+
+            let own_path = http::decode(self.url(remote_dir).trim_start_matches(&self.origin));
+            let own_path = own_path.trim_end_matches('/');
+
+            let mut names: Vec<String> = http::xml_elements(&response.text(), "href")
+                .into_iter()
+                .map(|href| http::decode(href.trim_start_matches(&self.origin)))
+                .filter(|path| path.trim_end_matches('/') != own_path)
+                .filter_map(|path| {
+                    path.trim_end_matches('/').rsplit('/').next().map(str::to_string)
+                })
+                .filter(|name| !name.is_empty())
+                .collect();
+
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    fn exists<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, bool> {
+        Box::pin(async move {
+            let response = self.propfind(remote_path, 0).await?;
+            match response.status {
+                404 => Ok(false),
+                _ if response.is_success() => Ok(true),
+                _ => Err(response.error(&format!("Checking {}", remote_path))),
+            }
+        })
+    }
+
+    fn remove<'a>(&'a self, remote_path: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let response = http::send(&self.request("DELETE", self.url(remote_path))).await?;
+            if !response.is_success() && response.status != 404 {
+                return Err(response.error(&format!("Removing {}", remote_path)));
+            }
+
+            let prefix = remote_path.trim_matches('/');
+            self.collections.lock().unwrap()
+                .retain(|c| c != prefix && !c.starts_with(&format!("{}/", prefix)));
+
+            Ok(())
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let request = self.request("MOVE", self.url(from))
+                .header("Destination", self.url(to))
+                .header("Overwrite", "F");
+
+            let response = http::send(&request).await?;
+            if !response.is_success() {
+                return Err(response.error(&format!("Renaming {} to {}", from, to)));
+            }
+
+            let prefix = from.trim_matches('/');
+            self.collections.lock().unwrap()
+                .retain(|c| c != prefix && !c.starts_with(&format!("{}/", prefix)));
+
+            Ok(())
+        })
+    }
+}
+
+/// Chunked upload collection when `path` is a Nextcloud files path (`remote.php/dav/files/<user>/...`)
+fn nextcloud_uploads(origin: &str, path: &str) -> Option<String> {
+    let (prefix, rest) = path.split_once("remote.php/dav/files/")?;
+    let user = rest.split('/').next().filter(|u| !u.is_empty())?;
+
+    Some(format!("{}/{}remote.php/dav/uploads/{}", origin, http::encode_path(prefix), http::encode_segment(&http::decode(user))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(target: &str) -> WebDavBackend {
+        WebDavBackend::open(&TargetUrl::parse(target).unwrap(), &WebDavConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_open_builds_urls() {
+        let backend = open("webdav://alice@cloud.example.com/remote.php/dav/files/alice/My Backups");
+
+        assert_eq!(backend.url("docs_1/a b.txt"),
+            "https://cloud.example.com/remote.php/dav/files/alice/My%20Backups/docs_1/a%20b.txt");
+        assert_eq!(backend.uploads.as_deref(), Some("https://cloud.example.com/remote.php/dav/uploads/alice"));
+        assert!(backend.authorization.is_some());
+
+        let plain = open("webdav+http://nas.local:8080/backups/");
+        assert_eq!(plain.url(""), "http://nas.local:8080/backups");
+        assert_eq!(plain.uploads, None, "Chunking is Nextcloud only");
+        assert_eq!(plain.authorization, None);
+    }
+
+    #[test]
+    fn test_hrefs_from_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response><d:href>/dav/backups/</d:href></d:response>
+              <d:response><d:href>/dav/backups/docs%202025/</d:href></d:response>
+              <D:response><D:href>https://host/dav/backups/a&amp;b.txt</D:href></D:response>
+            </d:multistatus>"#;
+
+        assert_eq!(http::xml_elements(xml, "href"), vec![
+            "/dav/backups/",
+            "/dav/backups/docs%202025/",
+            "https://host/dav/backups/a&b.txt",
+        ]);
+    }
+}